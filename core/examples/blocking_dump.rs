@@ -0,0 +1,42 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Dumps a partition without any async code.
+//!
+//! Usage: blocking_dump <DA file> <partition> <output file>
+use std::error::Error;
+use std::{env, fs};
+
+use penumbra::DeviceBuilder;
+use penumbra::blocking::BlockingDevice;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    let [_, da_path, partition, output] = args.as_slice() else {
+        return Err("Usage: blocking_dump <DA file> <partition> <output file>".into());
+    };
+
+    let da_data = fs::read(da_path)?;
+    let mut device = BlockingDevice::find(DeviceBuilder::default().with_da_data(da_data))?;
+
+    device.init()?;
+
+    let partitions = device.get_partitions()?;
+    if !partitions.iter().any(|p| &p.name == partition) {
+        let names: Vec<&str> = partitions.iter().map(|p| p.name.as_str()).collect();
+        return Err(format!("No partition '{partition}', available: {}", names.join(", ")).into());
+    }
+
+    device.read_partition_to_path(partition, output, |event| {
+        eprint!(
+            "\r{partition}: {}/{} bytes, {:.2} MiB/s",
+            event.bytes_done,
+            event.bytes_total,
+            event.throughput() / (1024.0 * 1024.0)
+        );
+    })?;
+    eprintln!();
+
+    Ok(())
+}
@@ -0,0 +1,268 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Synchronous wrapper around [`Device`], for small tools that don't want to deal with tokio.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use std::fs;
+//!
+//! use penumbra::DeviceBuilder;
+//! use penumbra::blocking::BlockingDevice;
+//!
+//! let da_data = fs::read("path/to/da/file")?;
+//! let mut device = BlockingDevice::find(DeviceBuilder::default().with_da_data(da_data))?;
+//!
+//! device.init()?;
+//! for part in device.get_partitions()? {
+//!     println!("{}: 0x{:X} bytes", part.name, part.size);
+//! }
+//!
+//! device.read_partition_to_path("boot_a", "boot_a.bin", |event| {
+//!     println!("{}/{} ({:.0} B/s)", event.bytes_done, event.bytes_total, event.throughput());
+//! })?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::fs::File;
+use std::future::{Future, poll_fn};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::pin::{Pin, pin};
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::core::progress::ProgressEvent;
+use crate::core::storage::Partition;
+use crate::da::protocol::BootMode;
+use crate::error::{Error, Result};
+use crate::{Device, DeviceBuilder, find_mtk_port};
+
+/// A [`Device`] with its own runtime, exposing the common operations as blocking calls.
+///
+/// It must not be used from inside a tokio runtime: blocking calls there would stall the
+/// runtime, so they fail with an error instead. Use [`Device`] directly in async code.
+pub struct BlockingDevice {
+    runtime: Runtime,
+    device: Device,
+}
+
+impl BlockingDevice {
+    /// Wraps an already built device.
+    pub fn new(device: Device) -> Result<Self> {
+        Ok(Self { runtime: new_runtime()?, device })
+    }
+
+    /// Looks for a connected MediaTek port, and builds the device on it.
+    pub fn find(builder: DeviceBuilder) -> Result<Self> {
+        let runtime = new_runtime()?;
+        let port =
+            block_on(&runtime, find_mtk_port())?.ok_or_else(|| Error::conn("No MTK port found"))?;
+        let device = builder.with_mtk_port(port).build()?;
+
+        Ok(Self { runtime, device })
+    }
+
+    /// See [`Device::init`].
+    pub fn init(&mut self) -> Result<()> {
+        block_on(&self.runtime, self.device.init())?
+    }
+
+    /// See [`Device::enter_da_mode`].
+    pub fn enter_da_mode(&mut self) -> Result<()> {
+        block_on(&self.runtime, self.device.enter_da_mode())?
+    }
+
+    /// See [`Device::get_partitions`].
+    pub fn get_partitions(&mut self) -> Result<Vec<Partition>> {
+        block_on(&self.runtime, self.device.get_partitions())?
+    }
+
+    /// Reads a partition into `writer`. See [`Device::read_partition`].
+    pub fn read_partition<W, F>(&mut self, name: &str, writer: W, mut progress: F) -> Result<()>
+    where
+        W: Write + Unpin + Send,
+        F: FnMut(ProgressEvent),
+    {
+        let latest = Mutex::new(None);
+        let mut report = |event| record(&latest, event);
+        let mut writer = SyncWriter(writer);
+
+        let op = self.device.read_partition(name, &mut report, &mut writer);
+        block_on(&self.runtime, with_progress(op, &latest, &mut progress))?
+    }
+
+    /// Reads a partition into a new file at `path`, replacing it if it exists.
+    pub fn read_partition_to_path<F>(
+        &mut self,
+        name: &str,
+        path: impl AsRef<Path>,
+        progress: F,
+    ) -> Result<()>
+    where
+        F: FnMut(ProgressEvent),
+    {
+        let file = File::create(path)?;
+        self.read_partition(name, file, progress)
+    }
+
+    /// Writes `reader` to a partition. See [`Device::write_partition`].
+    pub fn write_partition<R, F>(
+        &mut self,
+        name: &str,
+        reader: R,
+        pad_with_zeros: bool,
+        mut progress: F,
+    ) -> Result<()>
+    where
+        R: Read + Unpin + Send,
+        F: FnMut(ProgressEvent),
+    {
+        let latest = Mutex::new(None);
+        let mut report = |event| record(&latest, event);
+        let mut reader = SyncReader(reader);
+
+        let op = self.device.write_partition(name, &mut reader, pad_with_zeros, &mut report);
+        block_on(&self.runtime, with_progress(op, &latest, &mut progress))?
+    }
+
+    /// Writes the file at `path` to a partition, zero-filling the rest of it.
+    pub fn write_partition_from_path<F>(
+        &mut self,
+        name: &str,
+        path: impl AsRef<Path>,
+        progress: F,
+    ) -> Result<()>
+    where
+        F: FnMut(ProgressEvent),
+    {
+        let file = File::open(path)?;
+        self.write_partition(name, file, true, progress)
+    }
+
+    /// See [`Device::reboot`].
+    pub fn reboot(&mut self, bootmode: BootMode) -> Result<()> {
+        block_on(&self.runtime, self.device.reboot(bootmode))?
+    }
+
+    /// See [`Device::set_boot_mode`].
+    pub fn set_boot_mode(&mut self, bootmode: BootMode) -> Result<()> {
+        block_on(&self.runtime, self.device.set_boot_mode(bootmode))?
+    }
+
+    /// See [`Device::shutdown`].
+    pub fn shutdown(&mut self) -> Result<()> {
+        block_on(&self.runtime, self.device.shutdown())?
+    }
+
+    /// The wrapped device, for what isn't covered here.
+    /// Its futures can be driven with [`BlockingDevice::block_on`].
+    pub fn device(&mut self) -> &mut Device {
+        &mut self.device
+    }
+
+    /// Runs a future on the internal runtime, e.g. one from [`BlockingDevice::device`].
+    pub fn block_on<F: Future>(&self, fut: F) -> Result<F::Output> {
+        block_on(&self.runtime, fut)
+    }
+
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+}
+
+fn new_runtime() -> Result<Runtime> {
+    // The libusb backend relies on block_in_place, which needs a multi-thread runtime
+    #[cfg(feature = "libusb")]
+    let mut builder = {
+        let mut builder = Builder::new_multi_thread();
+        builder.worker_threads(1);
+        builder
+    };
+    #[cfg(not(feature = "libusb"))]
+    let mut builder = Builder::new_current_thread();
+
+    builder
+        .enable_all()
+        .build()
+        .map_err(|e| Error::penumbra(format!("Failed to create the runtime: {e}")))
+}
+
+fn block_on<F: Future>(runtime: &Runtime, fut: F) -> Result<F::Output> {
+    if Handle::try_current().is_ok() {
+        return Err(Error::penumbra(
+            "BlockingDevice can't be used from within an async runtime, use Device instead",
+        ));
+    }
+
+    Ok(runtime.block_on(fut))
+}
+
+fn record(latest: &Mutex<Option<ProgressEvent>>, event: ProgressEvent) {
+    if let Ok(mut latest) = latest.lock() {
+        *latest = Some(event);
+    }
+}
+
+/// Drives `op`, calling `progress` on the caller's thread with the latest value recorded
+/// by the device since the previous poll. This way `progress` needs neither `Send` nor
+/// `'static`, at the cost of skipping updates that happen within a single poll.
+async fn with_progress<T>(
+    op: impl Future<Output = Result<T>>,
+    latest: &Mutex<Option<ProgressEvent>>,
+    progress: &mut dyn FnMut(ProgressEvent),
+) -> Result<T> {
+    let mut op = pin!(op);
+
+    poll_fn(|cx| {
+        let poll = op.as_mut().poll(cx);
+        if let Some(event) = latest.lock().ok().and_then(|mut latest| latest.take()) {
+            progress(event);
+        }
+        poll
+    })
+    .await
+}
+
+/// Adapts a [`Write`] to [`AsyncWrite`]. Writing blocks the runtime, which is fine here
+/// since the only task on it is the one waiting for the data.
+struct SyncWriter<W>(W);
+
+impl<W: Write + Unpin> AsyncWrite for SyncWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(self.get_mut().0.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(self.get_mut().0.flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Adapts a [`Read`] to [`AsyncRead`], see [`SyncWriter`].
+struct SyncReader<R>(R);
+
+impl<R: Read + Unpin> AsyncRead for SyncReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let n = match self.get_mut().0.read(buf.initialize_unfilled()) {
+            Ok(n) => n,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,69 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::fmt;
+
+/// Optional capabilities compiled into this build of Penumbra.
+///
+/// Each field maps to the Cargo feature of the same name, and the features don't enable
+/// each other, so frontends can tell which operations are available without guessing
+/// from the build. DA extensions, peek and RPMB are always built, but only work once an
+/// exploit patched the DA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// BROM exploits, like Kamakiri (`exploits-brom`)
+    pub exploits_brom: bool,
+    /// DA patching and DA stage exploits, like Carbonara (`exploits-da-patch`)
+    pub exploits_da_patch: bool,
+    /// Bootloader lock state changes through seccfg (`seccfg`)
+    pub seccfg: bool,
+}
+
+impl Capabilities {
+    /// Returns the capabilities of the current build.
+    pub const fn current() -> Self {
+        Self {
+            exploits_brom: cfg!(feature = "exploits-brom"),
+            exploits_da_patch: cfg!(feature = "exploits-da-patch"),
+            seccfg: cfg!(feature = "seccfg"),
+        }
+    }
+
+    /// Names of the enabled capability features.
+    pub fn features(&self) -> Vec<&'static str> {
+        [
+            (self.exploits_brom, "exploits-brom"),
+            (self.exploits_da_patch, "exploits-da-patch"),
+            (self.seccfg, "seccfg"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, name)| enabled.then_some(name))
+        .collect()
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let features = self.features();
+        if features.is_empty() { write!(f, "none") } else { write!(f, "{}", features.join(", ")) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_are_reported_one_by_one() {
+        let caps = Capabilities::current();
+        assert_eq!(caps.exploits_brom, cfg!(feature = "exploits-brom"));
+        assert_eq!(caps.exploits_da_patch, cfg!(feature = "exploits-da-patch"));
+        assert_eq!(caps.seccfg, cfg!(feature = "seccfg"));
+
+        let none = Capabilities { exploits_brom: false, exploits_da_patch: false, seccfg: false };
+        assert_eq!(none.to_string(), "none");
+        let seccfg_only = Capabilities { seccfg: true, ..none };
+        assert_eq!(seccfg_only.features(), ["seccfg"]);
+    }
+}
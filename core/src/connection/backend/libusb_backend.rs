@@ -3,7 +3,7 @@
     SPDX-FileCopyrightText: 2025 Shomy
 */
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use log::{debug, error, info};
 use rusb::{Context, Device, DeviceHandle, Direction, Recipient, RequestType, UsbContext};
@@ -11,23 +11,33 @@ use tokio::sync::Mutex;
 use tokio::task::spawn_blocking;
 use tokio::time::sleep;
 
-use crate::connection::port::{ConnectionType, KNOWN_PORTS, MTKPort};
+use crate::connection::port::{ConnectionType, KNOWN_PORTS, MTKPort, usb_port_name};
+use crate::connection::retry::{ConnectionStats, Retry, RetryPolicy, TransientError};
 use crate::error::{Error, Result};
+use crate::utilities::patching::write_bytes;
+
+/// Timeout of a single bulk read, before it's retried.
+const BULK_TIMEOUT: Duration = Duration::from_millis(5000);
 
 #[derive(Debug, Clone)]
 pub struct UsbMTKPort {
     handle: Arc<Mutex<DeviceHandle<Context>>>,
+    vid: u16,
+    pid: u16,
     baudrate: u32,
     connection_type: ConnectionType,
     is_open: bool,
     port_name: String,
     in_endpoint: u8,
     out_endpoint: u8,
+    retry_policy: RetryPolicy,
+    stats: ConnectionStats,
 }
 
 impl UsbMTKPort {
     pub fn new(
         handle: DeviceHandle<Context>,
+        (vid, pid): (u16, u16),
         connection_type: ConnectionType,
         port_name: String,
         baudrate: u32,
@@ -36,13 +46,109 @@ impl UsbMTKPort {
     ) -> Self {
         Self {
             handle: Arc::new(Mutex::new(handle)),
+            vid,
+            pid,
             baudrate,
             connection_type,
             is_open: false,
             port_name,
             in_endpoint,
             out_endpoint,
+            retry_policy: RetryPolicy::default(),
+            stats: ConnectionStats::default(),
+        }
+    }
+
+    /// Clears the halt of `endpoint` after a stall, so it takes transfers again.
+    async fn clear_halt(&self, endpoint: u8) -> Result<()> {
+        let handle = self.handle.clone();
+
+        spawn_blocking(move || {
+            let locked = handle.blocking_lock();
+            locked
+                .clear_halt(endpoint)
+                .map_err(|e| Error::io(format!("Failed to clear endpoint halt: {e}")))
+        })
+        .await
+        .map_err(|e| Error::io(format!("USB worker failed: {e}")))?
+    }
+
+    /// Handles a failed bulk transfer on `endpoint`: transient errors are waited out
+    /// (clearing the halt of stalled endpoints) while `retry` allows it, anything else fails.
+    async fn retry_transfer(
+        &mut self,
+        retry: &mut Retry,
+        endpoint: u8,
+        error: rusb::Error,
+    ) -> Result<()> {
+        let kind = match error {
+            rusb::Error::Timeout => TransientError::Timeout,
+            rusb::Error::Pipe => TransientError::Stall,
+            rusb::Error::Overflow => TransientError::Overflow,
+            e => return Err(Error::io(format!("USB bulk transfer failed: {e}"))),
+        };
+
+        if !retry.retry(&mut self.stats, kind).await {
+            return Err(match kind {
+                TransientError::Timeout => Error::io("USB timeout"),
+                _ => Error::io(format!("USB bulk transfer failed: {error}")),
+            });
+        }
+        if kind == TransientError::Stall {
+            self.clear_halt(endpoint).await?;
+        }
+        Ok(())
+    }
+
+    /// Fills `buf`, retrying transient errors. Once `deadline` passed, the read fails
+    /// instead of being retried.
+    async fn read_until(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<usize> {
+        let handle = self.handle.clone();
+        let endpoint = self.in_endpoint;
+        let expected = buf.len();
+
+        let mut retry = Retry::new(self.retry_policy);
+
+        let mut total_read = 0;
+        while total_read < expected {
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(left) if !left.is_zero() => left,
+                    _ => return Err(Error::read_timeout(total_read, expected)),
+                },
+                None => BULK_TIMEOUT,
+            };
+
+            let to_read = expected - total_read;
+            let mut temp_buf = vec![0u8; to_read];
+            let result = spawn_blocking({
+                let handle = handle.clone();
+                move || {
+                    let locked = handle.blocking_lock();
+                    locked.read_bulk(endpoint, &mut temp_buf, timeout).map(|n| (temp_buf, n))
+                }
+            })
+            .await
+            .map_err(|e| Error::io(format!("USB worker failed: {e}")))?;
+
+            let (temp_buf, n) = match result {
+                Ok(read) => read,
+                Err(rusb::Error::Timeout) if deadline.is_some() => continue,
+                Err(e) => {
+                    self.retry_transfer(&mut retry, endpoint, e)
+                        .await
+                        .map_err(|e| Error::read_failed(total_read, expected, e))?;
+                    continue;
+                }
+            };
+            if n == 0 {
+                continue;
+            }
+            write_bytes(buf, total_read, temp_buf.get(..n).unwrap_or(&temp_buf))?;
+            total_read += n;
+            retry.progressed();
         }
+        Ok(total_read)
     }
 
     fn find_bulk_endpoints(device: &Device<Context>) -> Option<(u8, usize, u8, usize)> {
@@ -132,15 +238,16 @@ impl UsbMTKPort {
             ConnectionType::Preloader | ConnectionType::Da => 921_600,
         };
 
-        let port_name = format!("USB:{:04x}:{:04x}", vid, pid);
+        let port_chain = device.port_numbers().unwrap_or_default();
+        let port_name = usb_port_name(device.bus_number(), &port_chain, device.address());
 
         let handle = tokio::task::block_in_place(|| device.open().ok())?;
 
-        let (in_endpoint, _, out_endpoint, _) =
-            Self::find_bulk_endpoints(&device)?;
+        let (in_endpoint, _, out_endpoint, _) = Self::find_bulk_endpoints(&device)?;
 
         Some(Self::new(
             handle,
+            (vid, pid),
             connection_type,
             port_name,
             baudrate,
@@ -236,7 +343,7 @@ impl MTKPort for UsbMTKPort {
             Ok(())
         })
         .await
-        .unwrap()?;
+        .map_err(|e| Error::io(format!("USB worker failed: {e}")))??;
 
         self.is_open = false;
         info!("Closed USB MTK port: {}", port_name);
@@ -245,44 +352,19 @@ impl MTKPort for UsbMTKPort {
     }
 
     async fn read_exact(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let handle = self.handle.clone();
-        let endpoint = self.in_endpoint;
-        let timeout = Duration::from_millis(5000);
-
-        let mut total_read = 0;
-        while total_read < buf.len() {
-            let to_read = buf.len() - total_read;
-            let mut temp_buf = vec![0u8; to_read];
-            let result = spawn_blocking({
-                let handle = handle.clone();
-                move || {
-                    let locked = handle.blocking_lock();
-                    match locked.read_bulk(endpoint, &mut temp_buf, timeout) {
-                        Ok(n) => Ok((temp_buf, n)),
-                        Err(rusb::Error::Timeout) => Err(Error::io("USB timeout")),
-                        Err(e) => Err(Error::io(e.to_string())),
-                    }
-                }
-            })
-            .await
-            .unwrap()?;
+        self.read_until(buf, None).await
+    }
 
-            let (temp_buf, n) = result;
-            if n == 0 {
-                continue;
-            }
-            buf[total_read..total_read + n].copy_from_slice(&temp_buf[..n]);
-            total_read += n;
-        }
-        Ok(total_read)
+    async fn read_exact_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.read_until(buf, Some(Instant::now() + timeout)).await
     }
 
     async fn handshake(&mut self) -> Result<()> {
         let startcmd = [0xA0u8, 0x0A, 0x50, 0x05];
         let mut i = 0;
 
-        while i < startcmd.len() {
-            self.write_all(&[startcmd[i]]).await?;
+        while let Some(&byte) = startcmd.get(i) {
+            self.write_all(&[byte]).await?;
 
             let handle = self.handle.clone();
             let endpoint = self.in_endpoint;
@@ -299,12 +381,10 @@ impl MTKPort for UsbMTKPort {
             .await
             .map_err(|_| Error::io("USB bulk read task failed"))??;
 
-            if n == 0 {
+            let Some(&handshake_byte) = response.get(..n).and_then(<[u8]>::last) else {
                 return Err(Error::io("USB returned 0 bytes"));
-            }
-
-            let expected = !startcmd[i];
-            let handshake_byte = response[n - 1];
+            };
+            let expected = !byte;
 
             if handshake_byte == startcmd[0] {
                 // Already handshaken, return early
@@ -325,15 +405,30 @@ impl MTKPort for UsbMTKPort {
         let handle = self.handle.clone();
         let endpoint = self.out_endpoint;
         let timeout = Duration::from_millis(5000);
-        let data = buf.to_vec();
+        let mut retry = Retry::new(self.retry_policy);
 
-        spawn_blocking(move || {
-            let locked = handle.blocking_lock();
-            let res = locked.write_bulk(endpoint, &data, timeout);
-            res.map_err(|_| Error::io("Bulk write failed"))
-        })
-        .await
-        .unwrap()?;
+        // libusb reports partial writes, so a retry only sends what the device didn't take
+        let mut total_written = 0;
+        while total_written < buf.len() {
+            let data = buf.get(total_written..).unwrap_or_default().to_vec();
+            let result = spawn_blocking({
+                let handle = handle.clone();
+                move || {
+                    let locked = handle.blocking_lock();
+                    locked.write_bulk(endpoint, &data, timeout)
+                }
+            })
+            .await
+            .map_err(|e| Error::io(format!("USB worker failed: {e}")))?;
+
+            match result {
+                Ok(n) => {
+                    total_written += n;
+                    retry.progressed();
+                }
+                Err(e) => self.retry_transfer(&mut retry, endpoint, e).await?,
+            }
+        }
 
         Ok(())
     }
@@ -350,11 +445,28 @@ impl MTKPort for UsbMTKPort {
         self.baudrate
     }
 
+    fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    fn transfer_stats(&self) -> ConnectionStats {
+        self.stats
+    }
+
+    async fn set_baudrate(&mut self, baudrate: u32) -> Result<()> {
+        self.baudrate = baudrate;
+        Ok(())
+    }
+
     fn get_port_name(&self) -> String {
         self.port_name.clone()
     }
 
-    async fn find_device() -> Result<Option<Self>> {
+    fn get_usb_ids(&self) -> (u16, u16) {
+        (self.vid, self.pid)
+    }
+
+    async fn find_devices() -> Result<Vec<Self>> {
         let devices = spawn_blocking(|| -> Result<Vec<Device<Context>>> {
             let context = Context::new()
                 .map_err(|e| Error::io(format!("Failed to create USB context: {:?}", e)))?;
@@ -366,6 +478,7 @@ impl MTKPort for UsbMTKPort {
         .await
         .map_err(|_| Error::io("USB find_device task failed"))??;
 
+        let mut ports = Vec::new();
         for device in devices {
             let descriptor = match device.device_descriptor() {
                 Ok(d) => d,
@@ -376,12 +489,13 @@ impl MTKPort for UsbMTKPort {
             let pid = descriptor.product_id();
 
             if KNOWN_PORTS.iter().any(|(kvid, kpid, _)| *kvid == vid && *kpid == pid)
-                && let Some(port) = UsbMTKPort::from_device(device) {
-                    return Ok(Some(port));
-                }
+                && let Some(port) = UsbMTKPort::from_device(device)
+            {
+                ports.push(port);
+            }
         }
 
-        Ok(None)
+        Ok(ports)
     }
 
     async fn ctrl_out(
@@ -4,23 +4,16 @@
 */
 
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use log::{debug, error, info, warn};
-use rusb::{
-    Context,
-    Device,
-    DeviceHandle,
-    Direction,
-    Recipient,
-    RequestType,
-    UsbContext,
-};
+use rusb::{Context, Device, DeviceHandle, Direction, Recipient, RequestType, UsbContext};
 use tokio::sync::Mutex;
 use tokio::task::spawn_blocking;
 use tokio::time::sleep;
 
-use crate::connection::port::{ConnectionType, KNOWN_PORTS, MTKPort};
+use crate::connection::port::{ConnectionType, KNOWN_PORTS, MTKPort, usb_port_name};
+use crate::connection::retry::{ConnectionStats, Retry, RetryPolicy, TransientError};
 use crate::error::{Error, Result};
 
 /// Default timeout for USB operations
@@ -75,6 +68,8 @@ pub struct UsbMTKPort {
     is_open: bool,
     port_name: String,
     endpoints: BulkEndpoints,
+    retry_policy: RetryPolicy,
+    stats: ConnectionStats,
 }
 
 impl std::fmt::Debug for UsbMTKPort {
@@ -169,7 +164,8 @@ impl UsbMTKPort {
             ConnectionType::Preloader | ConnectionType::Da => 921_600,
         };
 
-        let port_name = format!("USB:{:04X}:{:04X}", vid, pid);
+        let port_chain = device.port_numbers().unwrap_or_default();
+        let port_name = usb_port_name(device.bus_number(), &port_chain, device.address());
 
         Some(Self {
             vid,
@@ -182,6 +178,8 @@ impl UsbMTKPort {
             is_open: false,
             port_name,
             endpoints,
+            retry_policy: RetryPolicy::default(),
+            stats: ConnectionStats::default(),
         })
     }
 
@@ -261,6 +259,19 @@ impl UsbMTKPort {
     }
 
     async fn bulk_read(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.raw_bulk_read(buf, timeout).await?.map_err(|e| bulk_error(e, "read"))
+    }
+
+    async fn bulk_write(&self, buf: &[u8], timeout: Duration) -> Result<usize> {
+        self.raw_bulk_write(buf, timeout).await?.map_err(|e| bulk_error(e, "write"))
+    }
+
+    /// Bulk read keeping the libusb error, for the caller to tell whether it's transient.
+    async fn raw_bulk_read(
+        &self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<rusb::Result<usize>> {
         let handle = self.handle.as_ref().ok_or_else(|| Error::io("Port not open"))?;
         let handle = handle.clone();
         let endpoint = self.endpoints.in_addr;
@@ -270,25 +281,21 @@ impl UsbMTKPort {
             let handle = handle.blocking_lock();
             let mut temp = vec![0u8; len];
 
-            match handle.read_bulk(endpoint, &mut temp, timeout) {
-                Ok(n) => Ok((temp, n)),
-                Err(rusb::Error::Timeout) => Err(Error::io("USB bulk read timeout")),
-                Err(rusb::Error::Pipe) => Err(Error::io("USB endpoint halted")),
-                Err(rusb::Error::NoDevice) => Err(Error::io("USB device disconnected")),
-                Err(e) => Err(Error::io(format!("USB bulk read error: {:?}", e))),
-            }
+            handle.read_bulk(endpoint, &mut temp, timeout).map(|n| (temp, n))
         })
         .await
-        .map_err(|e| Error::io(format!("Bulk read task panicked: {:?}", e)))??;
-
-        let (temp, n) = result;
-        if n > 0 {
-            buf[..n].copy_from_slice(&temp[..n]);
-        }
-        Ok(n)
+        .map_err(|e| Error::io(format!("Bulk read task panicked: {:?}", e)))?;
+
+        Ok(result.map(|(temp, n)| {
+            // Both buffers have the same length
+            let read = temp.get(..n).unwrap_or_default();
+            buf.get_mut(..read.len()).unwrap_or_default().copy_from_slice(read);
+            read.len()
+        }))
     }
 
-    async fn bulk_write(&self, buf: &[u8], timeout: Duration) -> Result<usize> {
+    /// Bulk write keeping the libusb error, for the caller to tell whether it's transient.
+    async fn raw_bulk_write(&self, buf: &[u8], timeout: Duration) -> Result<rusb::Result<usize>> {
         let handle = self.handle.as_ref().ok_or_else(|| Error::io("Port not open"))?;
         let handle = handle.clone();
         let endpoint = self.endpoints.out_addr;
@@ -296,17 +303,87 @@ impl UsbMTKPort {
 
         spawn_blocking(move || {
             let handle = handle.blocking_lock();
-
-            match handle.write_bulk(endpoint, &data, timeout) {
-                Ok(n) => Ok(n),
-                Err(rusb::Error::Timeout) => Err(Error::io("USB bulk write timeout")),
-                Err(rusb::Error::Pipe) => Err(Error::io("USB endpoint halted")),
-                Err(rusb::Error::NoDevice) => Err(Error::io("USB device disconnected")),
-                Err(e) => Err(Error::io(format!("USB bulk write error: {:?}", e))),
-            }
+            handle.write_bulk(endpoint, &data, timeout)
         })
         .await
-        .map_err(|e| Error::io(format!("Bulk write task panicked: {:?}", e)))?
+        .map_err(|e| Error::io(format!("Bulk write task panicked: {:?}", e)))
+    }
+
+    /// Handles a failed bulk transfer on `endpoint`: transient errors are waited out
+    /// (clearing the halt of stalled endpoints) while `retry` allows it, anything else fails.
+    async fn retry_transfer(
+        &mut self,
+        retry: &mut Retry,
+        endpoint: u8,
+        error: rusb::Error,
+        what: &str,
+    ) -> Result<()> {
+        let kind = match error {
+            rusb::Error::Timeout => TransientError::Timeout,
+            rusb::Error::Pipe => TransientError::Stall,
+            rusb::Error::Overflow => TransientError::Overflow,
+            e => return Err(bulk_error(e, what)),
+        };
+
+        if !retry.retry(&mut self.stats, kind).await {
+            return Err(bulk_error(error, what));
+        }
+        if kind == TransientError::Stall {
+            let handle = self.handle.as_ref().ok_or_else(|| Error::io("Port not open"))?;
+            handle
+                .lock()
+                .await
+                .clear_halt(endpoint)
+                .map_err(|e| Error::io(format!("Failed to clear endpoint halt: {:?}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Fills `buf`, retrying transient errors. Once `deadline` passed, the read fails
+    /// instead of being retried. A failure after part of `buf` arrived is an error too,
+    /// those bytes are consumed.
+    async fn read_until(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<usize> {
+        if !self.is_open {
+            return Err(Error::io("Port is not open"));
+        }
+
+        let endpoint = self.endpoints.in_addr;
+        let expected = buf.len();
+        let mut retry = Retry::new(self.retry_policy);
+        let mut total_read = 0;
+
+        while total_read < expected {
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(left) if !left.is_zero() => left,
+                    _ => return Err(Error::read_timeout(total_read, expected)),
+                },
+                None => DEFAULT_TIMEOUT,
+            };
+
+            let error = match self
+                .raw_bulk_read(buf.get_mut(total_read..).unwrap_or_default(), timeout)
+                .await?
+            {
+                Ok(0) => {
+                    sleep(Duration::from_millis(1)).await;
+                    continue;
+                }
+                Ok(n) => {
+                    total_read += n;
+                    retry.progressed();
+                    continue;
+                }
+                Err(rusb::Error::Timeout) if deadline.is_some() => continue,
+                Err(e) => e,
+            };
+
+            if let Err(e) = self.retry_transfer(&mut retry, endpoint, error, "read").await {
+                return Err(Error::read_failed(total_read, expected, e));
+            }
+        }
+
+        Ok(total_read)
     }
 }
 
@@ -391,37 +468,11 @@ impl MTKPort for UsbMTKPort {
     }
 
     async fn read_exact(&mut self, buf: &mut [u8]) -> Result<usize> {
-        if !self.is_open {
-            return Err(Error::io("Port is not open"));
-        }
-
-        let mut total_read = 0;
-
-        while total_read < buf.len() {
-            match self.bulk_read(&mut buf[total_read..], DEFAULT_TIMEOUT).await {
-                Ok(0) => {
-                    sleep(Duration::from_millis(1)).await;
-                    continue;
-                }
-                Ok(n) => {
-                    total_read += n;
-                }
-                Err(e) => {
-                    if total_read > 0 {
-                        warn!(
-                            "Partial read ({}/{} bytes) before error: {:?}",
-                            total_read,
-                            buf.len(),
-                            e
-                        );
-                        return Ok(total_read);
-                    }
-                    return Err(e);
-                }
-            }
-        }
+        self.read_until(buf, None).await
+    }
 
-        Ok(total_read)
+    async fn read_exact_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.read_until(buf, Some(Instant::now() + timeout)).await
     }
 
     async fn write_all(&mut self, buf: &[u8]) -> Result<()> {
@@ -429,20 +480,31 @@ impl MTKPort for UsbMTKPort {
             return Err(Error::io("Port is not open"));
         }
 
+        let endpoint = self.endpoints.out_addr;
+        let mut retry = Retry::new(self.retry_policy);
         let mut total_written = 0;
 
+        // libusb reports partial writes, so a retry only sends what the device didn't take
         while total_written < buf.len() {
-            match self.bulk_write(&buf[total_written..], DEFAULT_TIMEOUT).await {
+            let error = match self
+                .raw_bulk_write(buf.get(total_written..).unwrap_or_default(), DEFAULT_TIMEOUT)
+                .await?
+            {
                 Ok(n) if n > 0 => {
                     total_written += n;
+                    retry.progressed();
+                    continue;
                 }
                 Ok(_) => {
                     sleep(Duration::from_millis(1)).await;
+                    continue;
                 }
-                Err(e) => {
-                    error!("Write failed after {}/{} bytes: {:?}", total_written, buf.len(), e);
-                    return Err(e);
-                }
+                Err(e) => e,
+            };
+
+            if let Err(e) = self.retry_transfer(&mut retry, endpoint, error, "write").await {
+                error!("Write failed after {}/{} bytes: {:?}", total_written, buf.len(), e);
+                return Err(e);
             }
         }
 
@@ -477,8 +539,8 @@ impl MTKPort for UsbMTKPort {
         let mut retry_count = 0;
         const MAX_RETRIES: usize = 100;
 
-        while step < HANDSHAKE_CMD.len() {
-            self.write_all(&[HANDSHAKE_CMD[step]]).await?;
+        while let (Some(&cmd), Some(&rsp)) = (HANDSHAKE_CMD.get(step), HANDSHAKE_RSP.get(step)) {
+            self.write_all(&[cmd]).await?;
 
             let mut response = [0u8; 1];
 
@@ -491,17 +553,17 @@ impl MTKPort for UsbMTKPort {
                         return Ok(());
                     }
 
-                    if byte == HANDSHAKE_RSP[step] {
+                    if byte == rsp {
                         debug!(
                             "Handshake step {}: sent 0x{:02X}, got 0x{:02X} (OK)",
-                            step, HANDSHAKE_CMD[step], byte
+                            step, cmd, byte
                         );
                         step += 1;
                         retry_count = 0;
                     } else {
                         debug!(
                             "Handshake step {}: sent 0x{:02X}, expected 0x{:02X}, got 0x{:02X} (retry)",
-                            step, HANDSHAKE_CMD[step], HANDSHAKE_RSP[step], byte
+                            step, cmd, rsp, byte
                         );
                         step = 0;
                         retry_count += 1;
@@ -540,11 +602,31 @@ impl MTKPort for UsbMTKPort {
         self.baudrate
     }
 
+    fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    fn transfer_stats(&self) -> ConnectionStats {
+        self.stats
+    }
+
+    async fn set_baudrate(&mut self, baudrate: u32) -> Result<()> {
+        if let Some(handle) = &self.handle {
+            Self::setup_cdc(&*handle.lock().await, baudrate)?;
+        }
+        self.baudrate = baudrate;
+        Ok(())
+    }
+
     fn get_port_name(&self) -> String {
         self.port_name.clone()
     }
 
-    async fn find_device() -> Result<Option<Self>> {
+    fn get_usb_ids(&self) -> (u16, u16) {
+        (self.vid, self.pid)
+    }
+
+    async fn find_devices() -> Result<Vec<Self>> {
         let devices = spawn_blocking(|| -> Result<Vec<(Device<Context>, u8, u8)>> {
             let context = Context::new()
                 .map_err(|e| Error::io(format!("Failed to create USB context: {:?}", e)))?;
@@ -565,6 +647,7 @@ impl MTKPort for UsbMTKPort {
         .await
         .map_err(|e| Error::io(format!("USB enumeration task panicked: {:?}", e)))??;
 
+        let mut ports = Vec::new();
         for (device, ..) in devices {
             let descriptor = match device.device_descriptor() {
                 Ok(d) => d,
@@ -580,12 +663,12 @@ impl MTKPort for UsbMTKPort {
                 debug!("Found potential MTK device: {:04X}:{:04X}", vid, pid);
 
                 if let Some(port) = UsbMTKPort::from_device(device) {
-                    return Ok(Some(port));
+                    ports.push(port);
                 }
             }
         }
 
-        Ok(None)
+        Ok(ports)
     }
 
     async fn ctrl_out(
@@ -645,3 +728,13 @@ impl MTKPort for UsbMTKPort {
         .map_err(|e| Error::io(format!("Control IN task panicked: {:?}", e)))?
     }
 }
+
+/// Error of a bulk transfer that failed for good.
+fn bulk_error(error: rusb::Error, what: &str) -> Error {
+    match error {
+        rusb::Error::Timeout => Error::io(format!("USB bulk {} timeout", what)),
+        rusb::Error::Pipe => Error::io("USB endpoint halted"),
+        rusb::Error::NoDevice => Error::io("USB device disconnected"),
+        e => Error::io(format!("USB bulk {} error: {:?}", what, e)),
+    }
+}
@@ -2,11 +2,21 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+//! Serial backend, for USB CDC ports and for boards exposing the BROM over a UART header.
+//!
+//! UART consoles (routers, TV boxes) are opened by path with [`SerialMTKPort::from_path`],
+//! as there is nothing to enumerate. The wiring assumed is a 3.3V TTL adapter with TX and
+//! RX crossed and a common ground, 8N1 without flow control. The BROM only talks at
+//! 115200 baud, so the port is opened at that rate and only switched to the requested one
+//! once DA1 runs. Only the host side is switched: the DA must bring its UART to the same
+//! rate, DAs that don't have to be used at 115200. Start the tool first, then power the
+//! board on: the BROM listens for the handshake only for a short moment after reset.
 // use std::io::{Error, ErrorKind};
 use std::time::Duration;
 
-use log::{error, info};
+use log::{debug, error, info};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{Instant, timeout, timeout_at};
 use tokio_serial::{
     SerialPort,
     SerialPortBuilderExt,
@@ -18,6 +28,13 @@ use tokio_serial::{
 use crate::connection::port::{ConnectionType, KNOWN_PORTS, MTKPort};
 use crate::error::{Error, Result};
 
+/// The only baudrate the BROM UART talks at.
+pub const BROM_UART_BAUDRATE: u32 = 115_200;
+/// How long to wait for an answer to each handshake byte on a UART.
+/// The BROM answers right away, so a short wait keeps the start byte flowing
+/// while the board is still being powered on.
+const UART_HANDSHAKE_INTERVAL: Duration = Duration::from_millis(50);
+
 #[derive(Debug)]
 pub struct SerialMTKPort {
     port: Option<SerialStream>,
@@ -25,11 +42,29 @@ pub struct SerialMTKPort {
     baudrate: u32,
     connection_type: ConnectionType,
     is_open: bool,
+    /// Baudrate to switch to once DA1 runs, for UART consoles
+    da_baudrate: Option<u32>,
 }
 
 impl SerialMTKPort {
     pub fn new(port_info: SerialPortInfo, baudrate: u32, connection_type: ConnectionType) -> Self {
-        Self { port: None, port_info, baudrate, connection_type, is_open: false }
+        Self { port: None, port_info, baudrate, connection_type, is_open: false, da_baudrate: None }
+    }
+
+    /// Creates a port for a BROM UART console at `path` (e.g. `/dev/ttyUSB0`), bypassing
+    /// USB enumeration. The port is opened at [`BROM_UART_BAUDRATE`] and switched to
+    /// `da_baudrate` once DA1 runs.
+    pub fn from_path(path: &str, da_baudrate: u32) -> Self {
+        let port_info =
+            SerialPortInfo { port_name: path.to_string(), port_type: SerialPortType::Unknown };
+
+        let mut port = SerialMTKPort::new(port_info, BROM_UART_BAUDRATE, ConnectionType::Brom);
+        port.da_baudrate = Some(da_baudrate);
+        port
+    }
+
+    fn is_uart(&self) -> bool {
+        self.da_baudrate.is_some()
     }
 
     pub fn from_port_info(port_info: SerialPortInfo) -> Option<Self> {
@@ -58,6 +93,33 @@ impl SerialMTKPort {
 
         Some(SerialMTKPort::new(port_info, baudrate, connection_type))
     }
+
+    /// Fills `buf`, failing once `deadline` passed. Single reads are cancel safe,
+    /// so nothing that arrived is lost when giving up.
+    async fn read_until(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<usize> {
+        let port = self.port.as_mut().ok_or_else(|| Error::io("Port is not open"))?;
+        let expected = buf.len();
+
+        let mut filled = 0;
+        while filled < expected {
+            let read = port.read(&mut buf[filled..]);
+            let result = match deadline {
+                Some(deadline) => match timeout_at(deadline, read).await {
+                    Ok(result) => result,
+                    Err(_) => return Err(Error::read_timeout(filled, expected)),
+                },
+                None => read.await,
+            };
+
+            match result {
+                Ok(0) => return Err(Error::read_failed(filled, expected, "Serial port closed")),
+                Ok(n) => filled += n,
+                Err(e) => return Err(Error::read_failed(filled, expected, e)),
+            }
+        }
+
+        Ok(filled)
+    }
 }
 
 #[async_trait::async_trait]
@@ -88,11 +150,11 @@ impl MTKPort for SerialMTKPort {
     }
 
     async fn read_exact(&mut self, buf: &mut [u8]) -> Result<usize> {
-        if let Some(port) = &mut self.port {
-            port.read_exact(buf).await.map_err(|e| Error::Io(e.to_string()))
-        } else {
-            Err(Error::io("Port is not open"))
-        }
+        self.read_until(buf, None).await
+    }
+
+    async fn read_exact_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.read_until(buf, Some(Instant::now() + timeout)).await
     }
 
     async fn write_all(&mut self, buf: &[u8]) -> Result<()> {
@@ -113,14 +175,27 @@ impl MTKPort for SerialMTKPort {
     }
 
     async fn handshake(&mut self) -> Result<()> {
+        let uart = self.is_uart();
         if let Some(port) = &mut self.port {
             loop {
                 port.write_all(&[0xA0]).await?;
 
                 let mut response = [0u8; 1];
-                match port.read_exact(&mut response).await {
+                let read = if uart {
+                    // Boot logs and line noise come before the BROM listens,
+                    // so keep sending the start byte instead of waiting on it
+                    match timeout(UART_HANDSHAKE_INTERVAL, port.read_exact(&mut response)).await {
+                        Ok(read) => read,
+                        Err(_) => continue,
+                    }
+                } else {
+                    port.read_exact(&mut response).await
+                };
+
+                match read {
                     Ok(_) if response[0] == 0x5F => break,
-                    Ok(_) if response[0] == 0xA0 => {
+                    // On a UART, 0xA0 is just as likely to be part of boot logs
+                    Ok(_) if response[0] == 0xA0 && !uart => {
                         // We already handshaked, just return
                         return Ok(());
                     }
@@ -130,6 +205,12 @@ impl MTKPort for SerialMTKPort {
                 }
             }
 
+            if uart {
+                // Drop whatever the board printed while we were knocking
+                port.clear(tokio_serial::ClearBuffer::Input)
+                    .map_err(|e| Error::io(e.to_string()))?;
+            }
+
             port.write_all(&[0x0A]).await?;
             let mut r1 = [0u8; 1];
             port.read_exact(&mut r1).await?;
@@ -165,11 +246,45 @@ impl MTKPort for SerialMTKPort {
         self.baudrate
     }
 
+    async fn set_baudrate(&mut self, baudrate: u32) -> Result<()> {
+        let reconfigured = match &mut self.port {
+            Some(port) => port.set_baud_rate(baudrate),
+            None => Ok(()),
+        };
+        self.baudrate = baudrate;
+
+        if let Err(e) = reconfigured {
+            // Some drivers refuse to change the rate of an open port, reopen it instead
+            debug!("Could not reconfigure {}: {}, reopening it", self.port_info.port_name, e);
+            self.close().await?;
+            self.open().await?;
+        }
+
+        if let Some(port) = &mut self.port {
+            // Whatever came in during the switch was garbled
+            port.clear(tokio_serial::ClearBuffer::Input).map_err(|e| Error::io(e.to_string()))?;
+        }
+
+        debug!("Switched {} to {} baud", self.port_info.port_name, baudrate);
+        Ok(())
+    }
+
+    fn get_da_baudrate(&self) -> Option<u32> {
+        self.da_baudrate
+    }
+
     fn get_port_name(&self) -> String {
         self.port_info.port_name.clone()
     }
 
-    async fn find_device() -> Result<Option<Self>> {
+    fn get_usb_ids(&self) -> (u16, u16) {
+        match &self.port_info.port_type {
+            SerialPortType::UsbPort(usb_info) => (usb_info.vid, usb_info.pid),
+            _ => (0, 0),
+        }
+    }
+
+    async fn find_devices() -> Result<Vec<Self>> {
         use serialport::{SerialPortType, available_ports};
 
         let serial_ports = match available_ports() {
@@ -188,13 +303,7 @@ impl MTKPort for SerialMTKPort {
             }
         };
 
-        for port_info in serial_ports {
-            if let Some(port) = SerialMTKPort::from_port_info(port_info) {
-                return Ok(Some(port));
-            }
-        }
-
-        Ok(None)
+        Ok(serial_ports.into_iter().filter_map(SerialMTKPort::from_port_info).collect())
     }
 
     async fn ctrl_out(
@@ -3,20 +3,33 @@
     SPDX-FileCopyrightText: 2025 Shomy
 */
 
-use std::fmt;
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use std::{fmt, io};
 
 use async_trait::async_trait;
 use log::debug;
 use nusb::descriptors::TransferType;
-use nusb::io::{EndpointRead, EndpointWrite};
-use nusb::transfer::{Bulk, ControlIn, ControlOut, ControlType, Direction, In, Out, Recipient};
-use nusb::{DeviceInfo, Interface};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use nusb::io::EndpointRead;
+use nusb::transfer::{
+    Bulk,
+    ControlIn,
+    ControlOut,
+    ControlType,
+    Direction,
+    In,
+    Out,
+    Recipient,
+    TransferError,
+};
+use nusb::{DeviceInfo, Endpoint, Interface};
+use tokio::io::AsyncReadExt;
+use tokio::time::timeout;
 
 use crate::MTKPort;
 use crate::connection::ConnectionType;
-use crate::connection::port::KNOWN_PORTS;
+use crate::connection::port::{KNOWN_PORTS, usb_port_name};
+use crate::connection::retry::{ConnectionStats, Retry, RetryPolicy, TransientError};
 use crate::error::{Error, Result};
 
 const MAX_TIMEOUT: Duration = Duration::from_secs(2);
@@ -28,13 +41,18 @@ pub struct UsbMTKPort {
     interface: Option<Interface>,
     ctrl_interface: Option<Interface>,
     reader: Option<EndpointRead<Bulk>>,
-    writer: Option<EndpointWrite<Bulk>>,
+    /// Written to directly rather than through `EndpointWrite`, so that a failed transfer
+    /// tells how much of it reached the device, and the rest can be sent again.
+    writer: Option<Endpoint<Bulk, Out>>,
+    num_transfers: usize,
     ep_out: u8,
     ep_in: u8,
     in_max_packet_size: usize,
     out_max_packet_size: usize,
     connection_type: ConnectionType,
     is_open: bool,
+    retry_policy: RetryPolicy,
+    stats: ConnectionStats,
 }
 
 impl fmt::Debug for UsbMTKPort {
@@ -55,12 +73,15 @@ impl UsbMTKPort {
             ctrl_interface: None,
             writer: None,
             reader: None,
+            num_transfers: 0,
             ep_out: 0,
             ep_in: 0,
             in_max_packet_size: 0,
             out_max_packet_size: 0,
             connection_type,
             is_open: false,
+            retry_policy: RetryPolicy::default(),
+            stats: ConnectionStats::default(),
         }
     }
 
@@ -138,6 +159,84 @@ impl UsbMTKPort {
         debug!("CDC Setup complete");
         Ok(())
     }
+
+    /// Fills `buf`, retrying transient errors. Once `deadline` passed, the read fails
+    /// instead, leaving the transfers still queued to the next read.
+    async fn read_until(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<usize> {
+        let mut retry = Retry::new(self.retry_policy);
+
+        let mut filled = 0;
+        let result = loop {
+            if filled == buf.len() {
+                break Ok(filled);
+            }
+            let reader = self.reader.as_mut().ok_or_else(|| Error::io("USB port is not open"))?;
+            if let Some(deadline) = deadline {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    break Err(Error::read_timeout(filled, buf.len()));
+                }
+                reader.set_read_timeout(left);
+            }
+
+            match reader.read(buf.get_mut(filled..).unwrap_or_default()).await {
+                Ok(0) => {
+                    break Err(Error::read_failed(
+                        filled,
+                        buf.len(),
+                        "USB endpoint returned no data",
+                    ));
+                }
+                Ok(n) => {
+                    filled += n;
+                    retry.progressed();
+                }
+                Err(e) => {
+                    let kind = transient_error(&e);
+                    if deadline.is_some() && kind == Some(TransientError::Timeout) {
+                        continue;
+                    }
+                    let Some(kind) = kind else {
+                        break Err(Error::read_failed(filled, buf.len(), e));
+                    };
+                    if !retry.retry(&mut self.stats, kind).await {
+                        break Err(Error::read_failed(filled, buf.len(), e));
+                    }
+                    if kind == TransientError::Stall
+                        && let Err(e) = self.clear_read_halt().await
+                    {
+                        break Err(Error::read_failed(filled, buf.len(), e));
+                    }
+                }
+            }
+        };
+
+        if deadline.is_some()
+            && let Some(reader) = self.reader.as_mut()
+        {
+            reader.set_read_timeout(MAX_TIMEOUT);
+        }
+        result
+    }
+
+    /// Clears a stalled IN endpoint. The reader has to give the endpoint back for that,
+    /// so it's rebuilt afterwards, once the cancelled transfers came back.
+    async fn clear_read_halt(&mut self) -> Result<()> {
+        let reader = self.reader.take().ok_or_else(|| Error::io("USB port is not open"))?;
+        let mut ep = reader.into_inner();
+        ep.cancel_all();
+        while ep.pending() > 0 {
+            ep.next_complete().await;
+        }
+        let result = ep.clear_halt().await;
+
+        self.reader = Some(
+            ep.reader(BULK_IN_SZ)
+                .with_num_transfers(self.num_transfers)
+                .with_read_timeout(MAX_TIMEOUT),
+        );
+        Ok(result?)
+    }
 }
 
 #[async_trait]
@@ -163,10 +262,10 @@ impl MTKPort for UsbMTKPort {
         let ep_in = iface.endpoint::<Bulk, In>(self.ep_in)?;
         let rdr = ep_in.reader(BULK_IN_SZ).with_num_transfers(tr).with_read_timeout(MAX_TIMEOUT);
         let ep_out = iface.endpoint::<Bulk, Out>(self.ep_out)?;
-        let wr = ep_out.writer(BULK_OUT_SZ).with_num_transfers(tr).with_write_timeout(MAX_TIMEOUT);
 
         self.reader = Some(rdr);
-        self.writer = Some(wr);
+        self.writer = Some(ep_out);
+        self.num_transfers = tr;
         self.interface = Some(iface);
         self.ctrl_interface = Some(ctrl_iface);
 
@@ -197,17 +296,71 @@ impl MTKPort for UsbMTKPort {
     }
 
     async fn read_exact(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let reader = self.reader.as_mut().ok_or_else(|| Error::io("USB port is not open"))?;
+        self.read_until(buf, None).await
+    }
 
-        reader.read_exact(buf).await?;
-        Ok(buf.len())
+    async fn read_exact_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.read_until(buf, Some(Instant::now() + timeout)).await
     }
 
     async fn write_all(&mut self, buf: &[u8]) -> Result<()> {
-        let writer = self.writer.as_mut().ok_or_else(|| Error::io("USB port is not open"))?;
+        let ep = self.writer.as_mut().ok_or_else(|| Error::io("USB port is not open"))?;
+        let mut retry = Retry::new(self.retry_policy);
+
+        // Bytes the device took, and lengths of the transfers in flight after them
+        let mut written = 0;
+        let mut in_flight = VecDeque::new();
+
+        while written < buf.len() {
+            let mut queued = written + in_flight.iter().sum::<usize>();
+            while queued < buf.len() && in_flight.len() < self.num_transfers {
+                let len = (buf.len() - queued).min(BULK_OUT_SZ);
+                let mut transfer = ep.allocate(len);
+                transfer.extend_from_slice(buf.get(queued..queued + len).unwrap_or_default());
+                ep.submit(transfer);
+                in_flight.push_back(len);
+                queued += len;
+            }
+
+            let error = match timeout(MAX_TIMEOUT, ep.next_complete()).await {
+                Ok(completion) => {
+                    written += completion.actual_len;
+                    in_flight.pop_front();
+                    match completion.status {
+                        Ok(()) => {
+                            retry.progressed();
+                            continue;
+                        }
+                        Err(TransferError::Stall) => TransientError::Stall,
+                        Err(e) => return Err(io::Error::from(e).into()),
+                    }
+                }
+                Err(_) => TransientError::Timeout,
+            };
+
+            // Take back everything in flight, keeping what made it before the failure
+            ep.cancel_all();
+            let mut failed = error == TransientError::Stall;
+            while !in_flight.is_empty() {
+                let completion = ep.next_complete().await;
+                let len = in_flight.pop_front().unwrap_or_default();
+                if !failed {
+                    written += completion.actual_len;
+                    failed = completion.actual_len < len;
+                }
+            }
+
+            if !retry.retry(&mut self.stats, error).await {
+                return Err(match error {
+                    TransientError::Timeout => Error::io("USB bulk write timed out"),
+                    _ => Error::io("USB endpoint halted"),
+                });
+            }
+            if error == TransientError::Stall {
+                ep.clear_halt().await?;
+            }
+        }
 
-        writer.write_all(buf).await?;
-        writer.flush().await?;
         Ok(())
     }
 
@@ -261,23 +414,33 @@ impl MTKPort for UsbMTKPort {
         0
     }
 
+    fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    fn transfer_stats(&self) -> ConnectionStats {
+        self.stats
+    }
+
     fn get_port_name(&self) -> String {
-        format!("USB {:04X}:{:04X}", self.info.vendor_id(), self.info.product_id())
+        usb_port_name(self.info.bus_id(), self.info.port_chain(), self.info.device_address())
     }
 
-    async fn find_device() -> Result<Option<Self>> {
-        let devices = nusb::list_devices().await?;
+    fn get_usb_ids(&self) -> (u16, u16) {
+        (self.info.vendor_id(), self.info.product_id())
+    }
 
-        for device in devices {
-            if let Some((_, _, conn_type)) = KNOWN_PORTS
-                .iter()
-                .find(|(vid, pid, _)| device.vendor_id() == *vid && device.product_id() == *pid)
-            {
-                return Ok(Some(UsbMTKPort::new(device, *conn_type)));
-            }
-        }
+    async fn find_devices() -> Result<Vec<Self>> {
+        let devices = nusb::list_devices().await?;
 
-        Ok(None)
+        Ok(devices
+            .filter_map(|device| {
+                KNOWN_PORTS
+                    .iter()
+                    .find(|(vid, pid, _)| device.vendor_id() == *vid && device.product_id() == *pid)
+                    .map(|(_, _, conn_type)| UsbMTKPort::new(device, *conn_type))
+            })
+            .collect())
     }
 
     async fn ctrl_out(
@@ -352,3 +515,16 @@ impl MTKPort for UsbMTKPort {
         Ok(buf)
     }
 }
+
+/// Tells apart the errors worth retrying. NUSB doesn't report overflows on their own,
+/// they surface as faults and aren't retried.
+fn transient_error(e: &io::Error) -> Option<TransientError> {
+    if e.kind() == io::ErrorKind::TimedOut {
+        return Some(TransientError::Timeout);
+    }
+
+    match e.get_ref().and_then(|e| e.downcast_ref::<TransferError>()) {
+        Some(TransferError::Stall) => Some(TransientError::Stall),
+        _ => None,
+    }
+}
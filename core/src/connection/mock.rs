@@ -0,0 +1,510 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Scripted port, to exercise the protocols without a device.
+//!
+//! A [`Script`] lists what the host is expected to write and what the device answers,
+//! in order. [`MockMTKPort`] plays it back: a write that doesn't match the script panics
+//! with a diff of the bytes, and a read answers with the next canned response. Writes and
+//! reads don't need to be split like the steps, so a 12-byte header and its payload can be
+//! expected as a single step, and the other way around.
+//!
+//! ```no_run
+//! # async fn example() -> penumbra::Result<()> {
+//! use penumbra::connection::Connection;
+//! use penumbra::connection::mock::{MockMTKPort, Script};
+//!
+//! let script = Script::new().echo([0xFD]).respond([0x07, 0x66]).respond([0x00, 0x00]);
+//! let port = MockMTKPort::new(script);
+//! let handle = port.handle();
+//!
+//! let mut conn = Connection::new(Box::new(port));
+//! assert_eq!(conn.get_hw_code().await?, 0x0766);
+//! handle.assert_done();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only available with the `mock` feature.
+// Failing the test on a script mismatch is the whole point. The port is the device here,
+// so its offsets into the script are not device input.
+#![allow(clippy::panic, clippy::indexing_slicing)]
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::future::pending;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use tokio::time::sleep;
+
+use crate::connection::port::{ConnectionType, MTKPort};
+use crate::error::{Error, Result};
+
+/// Bytes shown around the first difference of a mismatched write.
+const DIFF_CONTEXT: usize = 16;
+
+#[derive(Debug, Clone)]
+enum Step {
+    /// The host writes exactly these bytes
+    Expect(Vec<u8>),
+    /// The host writes this many bytes, whatever they are
+    ExpectAny(usize),
+    /// The device answers with these bytes
+    Respond(Vec<u8>),
+    /// The device answers with these bytes, whatever the host writes meanwhile
+    Noise(Vec<u8>),
+    /// The next read or write fails with this message
+    Fail(String),
+    /// The next read or write never completes, like a device that stopped answering
+    Hang,
+}
+
+/// Exchange played back by a [`MockMTKPort`], built step by step.
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    steps: Vec<Step>,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expects the host to write `data`.
+    pub fn expect(mut self, data: impl AsRef<[u8]>) -> Self {
+        if !data.as_ref().is_empty() {
+            self.steps.push(Step::Expect(data.as_ref().to_vec()));
+        }
+        self
+    }
+
+    /// Expects the host to write `len` bytes, without checking them.
+    /// Meant for data the test can't predict, like random values or large payloads.
+    pub fn expect_any(mut self, len: usize) -> Self {
+        if len != 0 {
+            self.steps.push(Step::ExpectAny(len));
+        }
+        self
+    }
+
+    /// Makes the device answer with `data`.
+    pub fn respond(mut self, data: impl AsRef<[u8]>) -> Self {
+        if !data.as_ref().is_empty() {
+            self.steps.push(Step::Respond(data.as_ref().to_vec()));
+        }
+        self
+    }
+
+    /// Makes the device answer with `data`, taking any write until all of it was read.
+    /// Meant for random answers, where the host may write anything in between.
+    pub fn noise(mut self, data: impl AsRef<[u8]>) -> Self {
+        if !data.as_ref().is_empty() {
+            self.steps.push(Step::Noise(data.as_ref().to_vec()));
+        }
+        self
+    }
+
+    /// Expects the host to write `data`, and echoes it back like the BROM does.
+    pub fn echo(self, data: impl AsRef<[u8]>) -> Self {
+        let data = data.as_ref();
+        self.expect(data).respond(data)
+    }
+
+    /// Makes the next read or write fail with an I/O error.
+    pub fn fail(mut self, msg: impl Into<String>) -> Self {
+        self.steps.push(Step::Fail(msg.into()));
+        self
+    }
+
+    /// Makes the next read or write hang, like a device that stopped answering.
+    /// The code under test is expected to give up on a timeout. A read with a timeout
+    /// fails once it elapsed, and the script goes on with the next step.
+    pub fn hang(mut self) -> Self {
+        self.steps.push(Step::Hang);
+        self
+    }
+
+    /// Appends the steps of `other`, to build scripts out of reusable sequences.
+    pub fn then(mut self, other: Script) -> Self {
+        self.steps.extend(other.steps);
+        self
+    }
+}
+
+/// XFlash and XML DA packet: magic, data type (protocol flow) and length, then the data.
+pub fn packet(data: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0xEF, 0xEE, 0xEE, 0xFE, 0x01, 0x00, 0x00, 0x00];
+    packet.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// XFlash status packet, 0 being success.
+pub fn status(code: u32) -> Vec<u8> {
+    packet(&code.to_le_bytes())
+}
+
+/// What interrupts a read or write, instead of moving data.
+enum Interrupt {
+    Fail(String),
+    Hang,
+}
+
+#[derive(Debug)]
+struct State {
+    steps: VecDeque<Step>,
+    /// Position in the script of the step at the front
+    index: usize,
+    /// Bytes of the step at the front already written or read
+    offset: usize,
+    /// Everything the host wrote so far
+    written: Vec<u8>,
+}
+
+impl State {
+    fn advance(&mut self, n: usize, step_len: usize) {
+        self.offset += n;
+        if self.offset == step_len {
+            self.steps.pop_front();
+            self.index += 1;
+            self.offset = 0;
+        }
+    }
+
+    fn interrupt(&mut self) -> Interrupt {
+        self.index += 1;
+        self.offset = 0;
+        match self.steps.pop_front() {
+            Some(Step::Fail(msg)) => Interrupt::Fail(msg),
+            _ => Interrupt::Hang,
+        }
+    }
+
+    /// Matches the start of `data` against the script, returning how much of it was taken.
+    fn write(&mut self, data: &[u8]) -> std::result::Result<usize, Interrupt> {
+        let n = match self.steps.front() {
+            Some(Step::Expect(expected)) => {
+                let expected = &expected[self.offset..];
+                let n = expected.len().min(data.len());
+                if expected[..n] != data[..n] {
+                    panic!("{}", self.mismatch(expected, data));
+                }
+                n
+            }
+            Some(Step::ExpectAny(len)) => (len - self.offset).min(data.len()),
+            Some(Step::Noise(_)) => {
+                self.written.extend_from_slice(data);
+                return Ok(data.len());
+            }
+            Some(Step::Respond(response)) => panic!(
+                "Mock port: host wrote {} bytes ({}) at step {}, where the device answers \
+                 with {} bytes ({})",
+                data.len(),
+                hex_window(data, 0),
+                self.index,
+                response.len() - self.offset,
+                hex_window(&response[self.offset..], 0)
+            ),
+            Some(Step::Fail(_) | Step::Hang) => return Err(self.interrupt()),
+            None => panic!(
+                "Mock port: host wrote {} bytes ({}) past the end of the script",
+                data.len(),
+                hex_window(data, 0)
+            ),
+        };
+
+        let step_len = self.front_len();
+        self.written.extend_from_slice(&data[..n]);
+        self.advance(n, step_len);
+        Ok(n)
+    }
+
+    /// Fills the start of `buf` from the script, returning how much of it was filled.
+    fn read(&mut self, buf: &mut [u8]) -> std::result::Result<usize, Interrupt> {
+        let n = match self.steps.front() {
+            Some(Step::Respond(response) | Step::Noise(response)) => {
+                let response = &response[self.offset..];
+                let n = response.len().min(buf.len());
+                buf[..n].copy_from_slice(&response[..n]);
+                n
+            }
+            Some(Step::Expect(_) | Step::ExpectAny(_)) => panic!(
+                "Mock port: host read {} bytes at step {}, where it should write {} more bytes{}",
+                buf.len(),
+                self.index,
+                self.front_len() - self.offset,
+                match self.steps.front() {
+                    Some(Step::Expect(expected)) => {
+                        format!(" ({})", hex_window(&expected[self.offset..], 0))
+                    }
+                    _ => String::new(),
+                }
+            ),
+            Some(Step::Fail(_) | Step::Hang) => return Err(self.interrupt()),
+            None => panic!("Mock port: host read {} bytes past the end of the script", buf.len()),
+        };
+
+        let step_len = self.front_len();
+        self.advance(n, step_len);
+        Ok(n)
+    }
+
+    fn front_len(&self) -> usize {
+        match self.steps.front() {
+            Some(Step::Expect(data) | Step::Respond(data) | Step::Noise(data)) => data.len(),
+            Some(Step::ExpectAny(len)) => *len,
+            _ => 0,
+        }
+    }
+
+    fn mismatch(&self, expected: &[u8], data: &[u8]) -> String {
+        let at = expected.iter().zip(data).position(|(a, b)| a != b).unwrap_or(0);
+        let step_offset = self.offset + at;
+
+        let mut msg = format!(
+            "Mock port: write mismatch at step {}, byte 0x{:X} of the step \
+             (byte 0x{:X} of everything written)\n",
+            self.index,
+            step_offset,
+            self.written.len() + at
+        );
+        writeln!(msg, "  expected: {}", hex_window(expected, at)).ok();
+        writeln!(msg, "  written:  {}", hex_window(data, at)).ok();
+        // Point at the first differing byte, each one being 3 columns wide
+        let caret = at.min(DIFF_CONTEXT) * 3 + if at > DIFF_CONTEXT { 3 } else { 0 };
+        write!(msg, "            {}^^", " ".repeat(caret)).ok();
+        msg
+    }
+}
+
+/// Hex dump of the bytes around `at`.
+fn hex_window(data: &[u8], at: usize) -> String {
+    let start = at.saturating_sub(DIFF_CONTEXT);
+    let end = data.len().min(at + DIFF_CONTEXT);
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push_str(".. ");
+    }
+    for byte in &data[start..end] {
+        write!(out, "{:02X} ", byte).ok();
+    }
+    if end < data.len() {
+        write!(out, ".. ({} bytes)", data.len()).ok();
+    }
+    out.trim_end().to_string()
+}
+
+/// Checks a [`MockMTKPort`] once it has been handed over to a connection.
+#[derive(Debug, Clone)]
+pub struct MockHandle {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockHandle {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Panics if part of the script wasn't played.
+    pub fn assert_done(&self) {
+        let state = self.state();
+        if let Some(step) = state.steps.front() {
+            let remaining = match step {
+                Step::Expect(data) => {
+                    format!("a write of {}", hex_window(&data[state.offset..], 0))
+                }
+                Step::ExpectAny(len) => format!("a write of {} bytes", len - state.offset),
+                Step::Respond(data) | Step::Noise(data) => {
+                    format!("a read of {}", hex_window(&data[state.offset..], 0))
+                }
+                Step::Fail(msg) => format!("a failure ({})", msg),
+                Step::Hang => "a hang".to_string(),
+            };
+            panic!(
+                "Mock port: script stopped at step {} of {}, expecting {}",
+                state.index,
+                state.index + state.steps.len(),
+                remaining
+            );
+        }
+    }
+
+    /// Returns everything the host wrote so far.
+    pub fn written(&self) -> Vec<u8> {
+        self.state().written.clone()
+    }
+}
+
+/// Port playing back a [`Script`] instead of talking to a device.
+#[derive(Debug)]
+pub struct MockMTKPort {
+    state: Arc<Mutex<State>>,
+    connection_type: ConnectionType,
+    is_open: bool,
+}
+
+impl MockMTKPort {
+    /// Creates a port in BROM mode, playing back `script`.
+    pub fn new(script: Script) -> Self {
+        let state = State { steps: script.steps.into(), index: 0, offset: 0, written: Vec::new() };
+
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            connection_type: ConnectionType::Brom,
+            is_open: true,
+        }
+    }
+
+    /// Makes the port look like a preloader or DA port.
+    pub fn with_connection_type(mut self, connection_type: ConnectionType) -> Self {
+        self.connection_type = connection_type;
+        self
+    }
+
+    /// Returns a handle to check the script once the port is owned by a connection.
+    pub fn handle(&self) -> MockHandle {
+        MockHandle { state: self.state.clone() }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn read_until(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize> {
+        if !self.is_open {
+            return Err(Error::io("Mock port is not open"));
+        }
+
+        let mut pos = 0;
+        while pos < buf.len() {
+            let read = self.state().read(&mut buf[pos..]);
+            match read {
+                Ok(n) => pos += n,
+                Err(Interrupt::Fail(msg)) => return Err(Error::read_failed(pos, buf.len(), msg)),
+                Err(Interrupt::Hang) => match timeout {
+                    Some(timeout) => {
+                        sleep(timeout).await;
+                        return Err(Error::read_timeout(pos, buf.len()));
+                    }
+                    None => pending::<()>().await,
+                },
+            }
+        }
+
+        Ok(buf.len())
+    }
+}
+
+#[async_trait::async_trait]
+impl MTKPort for MockMTKPort {
+    async fn open(&mut self) -> Result<()> {
+        self.is_open = true;
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        self.is_open = false;
+        Ok(())
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.read_until(buf, None).await
+    }
+
+    /// A [`Script::hang`] step makes the read time out once `timeout` elapsed.
+    async fn read_exact_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.read_until(buf, Some(timeout)).await
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        if !self.is_open {
+            return Err(Error::io("Mock port is not open"));
+        }
+
+        let mut pos = 0;
+        while pos < buf.len() {
+            let written = self.state().write(&buf[pos..]);
+            match written {
+                Ok(n) => pos += n,
+                Err(Interrupt::Fail(msg)) => return Err(Error::io(msg)),
+                Err(Interrupt::Hang) => pending::<()>().await,
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Same exchange as the USB backends, so it has to be part of the script.
+    async fn handshake(&mut self) -> Result<()> {
+        let mut resp = [0u8; 1];
+
+        for byte in [0xA0, 0x0A, 0x50, 0x05] {
+            self.write_all(&[byte]).await?;
+            self.read_exact(&mut resp).await?;
+
+            if resp[0] != (byte ^ 0xFF) {
+                return Err(Error::conn(format!(
+                    "Handshake failed: sent 0x{:02X}, expected 0x{:02X}, got 0x{:02X}",
+                    byte,
+                    byte ^ 0xFF,
+                    resp[0]
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_connection_type(&self) -> ConnectionType {
+        self.connection_type
+    }
+
+    fn get_baudrate(&self) -> u32 {
+        115_200
+    }
+
+    fn get_port_name(&self) -> String {
+        "mock".to_string()
+    }
+
+    fn get_usb_ids(&self) -> (u16, u16) {
+        match self.connection_type {
+            ConnectionType::Brom => (0x0E8D, 0x0003),
+            ConnectionType::Preloader => (0x0E8D, 0x2000),
+            ConnectionType::Da => (0x0E8D, 0x2001),
+        }
+    }
+
+    /// Mock ports are never enumerated, they're created with [`MockMTKPort::new`].
+    async fn find_devices() -> Result<Vec<Self>> {
+        Ok(Vec::new())
+    }
+
+    async fn ctrl_out(
+        &mut self,
+        _request_type: u8,
+        _request: u8,
+        _value: u16,
+        _index: u16,
+        _data: &[u8],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn ctrl_in(
+        &mut self,
+        _request_type: u8,
+        _request: u8,
+        _value: u16,
+        _index: u16,
+        len: usize,
+    ) -> Result<Vec<u8>> {
+        Ok(vec![0u8; len])
+    }
+}
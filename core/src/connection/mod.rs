@@ -4,45 +4,181 @@
 */
 mod backend;
 mod command;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod port;
-use std::time::Duration;
+pub mod probe;
+pub mod retry;
+pub mod suspend;
+use std::time::{Duration, Instant};
 
-use log::{debug, error, info};
-use tokio::time::timeout;
+use log::{debug, error, info, warn};
+use tokio::time::sleep;
 
 use crate::connection::command::Command;
-use crate::connection::port::{ConnectionType, MTKPort};
+use crate::connection::port::{ConnectionType, MTKPort, find_mtk_ports};
+use crate::connection::retry::{ConnectionStats, RetryPolicy};
+use crate::connection::suspend::SuspendDetector;
+use crate::core::auth::AuthFile;
 use crate::error::{Error, Result};
 
+/// Largest MEID/SoC ID a device is expected to announce, anything bigger is garbage
+const MAX_ID_LENGTH: usize = 64;
+/// How long to wait for the MEID/SoC ID length, devices without support never answer
+const ID_RESPONSE_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long to keep listening for a late MEID/SoC ID answer after giving up on it
+const ID_DRAIN_TIMEOUT: Duration = Duration::from_millis(300);
+/// Bytes moved by each Read32/Write32 command in `read_mem` and `write_mem`
+const MEM_CHUNK_SIZE: usize = 0x1000;
+/// How long a re-enumerating device is given to drop off the bus before looking for it again
+const REENUMERATION_DELAY: Duration = Duration::from_millis(500);
+/// Interval between two lookups of a re-enumerating device
+const RECONNECT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a device dropping off the bus after jumping to the DA has to come back.
+const DA_REENUMERATION_WAIT: Duration = Duration::from_secs(5);
+/// How long the device gets to switch its UART before the new rate is checked.
+const BAUDRATE_SETTLE_DELAY: Duration = Duration::from_millis(100);
+/// How long each step of a baudrate negotiation may take, as a device that doesn't
+/// know the command may not answer at all.
+const BAUDRATE_STEP_TIMEOUT: Duration = Duration::from_millis(500);
+
 #[derive(Debug)]
 pub struct Connection {
     pub port: Box<dyn MTKPort>,
     pub connection_type: ConnectionType,
     pub baudrate: u32,
+    /// Last time data was exchanged with the device, used for inactivity tracking.
+    last_activity: Instant,
+    /// Tells whether the host slept since data was last exchanged.
+    suspend: SuspendDetector,
+    /// Auth file sent before the DA, for devices with SLA or DAA enabled.
+    auth: Option<AuthFile>,
+    /// Whether the device may re-enumerate as a DA port before the next successful read.
+    reenumeration_expected: bool,
+    /// How transient USB errors are retried, kept across reconnections.
+    retry_policy: RetryPolicy,
+    /// Transient errors seen on ports replaced by a reconnection.
+    past_stats: ConnectionStats,
 }
 
 impl Connection {
-    pub fn new(port: Box<dyn MTKPort>) -> Self {
+    pub fn new(mut port: Box<dyn MTKPort>) -> Self {
         let connection_type = port.get_connection_type();
         let baudrate = port.get_baudrate();
+        let retry_policy = RetryPolicy::default();
+        port.set_retry_policy(retry_policy);
+
+        Connection {
+            port,
+            connection_type,
+            baudrate,
+            last_activity: Instant::now(),
+            suspend: SuspendDetector::new(),
+            auth: None,
+            reenumeration_expected: false,
+            retry_policy,
+            past_stats: ConnectionStats::default(),
+        }
+    }
+
+    /// Sets how transient USB errors (timeouts, stalls, overflows) are retried.
+    /// Retries happen at the USB transfer level, resuming where the failed transfer stopped.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+        self.port.set_retry_policy(policy);
+    }
 
-        Connection { port, connection_type, baudrate }
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// Returns the transient errors seen since the connection was made, across reconnections.
+    pub fn stats(&self) -> ConnectionStats {
+        let mut stats = self.past_stats;
+        stats.merge(self.port.transfer_stats());
+        stats
+    }
+
+    // Returns how long the connection has been idle, i.e. no data was sent or received
+    pub fn idle_time(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
+
+    /// Fails with [`Error::HostSuspended`] if the host slept since data was last exchanged.
+    /// Meant to be called between chunks of long transfers, so that they fail right away
+    /// instead of after the next timeout. Always passes where suspends can't be detected,
+    /// see [`SuspendDetector`].
+    pub fn check_host_awake(&self) -> Result<()> {
+        match self.suspend.slept() {
+            Some(slept) => Err(Error::HostSuspended(slept)),
+            None => Ok(()),
+        }
     }
 
     // Writes the provided data to the device
     pub async fn write(&mut self, data: &[u8]) -> Result<()> {
-        self.port.write_all(data).await
+        self.last_activity = Instant::now();
+        let mut result = self.port.write_all(data).await;
+        if self.reenumeration_expected
+            && let Err(e) = result
+        {
+            result = match self.follow_reenumeration(e).await {
+                Ok(()) => self.port.write_all(data).await,
+                Err(e) => Err(e),
+            };
+        }
+        self.track_io(result)
     }
 
     // Reads the exact number of bytes required to fill the provided buffer
     pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        self.port.read_exact(buf).await
+        let mut read = self.port.read_exact(buf).await;
+        if self.reenumeration_expected {
+            read = match read {
+                Ok(n) => {
+                    self.reenumeration_expected = false;
+                    Ok(n)
+                }
+                Err(e) => match self.follow_reenumeration(e).await {
+                    Ok(()) => self.port.read_exact(buf).await,
+                    Err(e) => Err(e),
+                },
+            };
+        }
+        self.last_activity = Instant::now();
+        self.track_io(read)
+    }
+
+    /// Like [`Connection::read`], but fails with a timed out [`Error::ReadFailed`] once
+    /// `timeout` elapsed. Unlike cancelling a read, this leaves the port at a known point
+    /// of the stream: only a timeout before any byte arrived leaves it in sync.
+    pub async fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        let read = self.port.read_exact_timeout(buf, timeout).await;
+        if read.is_ok() {
+            self.reenumeration_expected = false;
+        }
+        self.last_activity = Instant::now();
+        self.track_io(read)
+    }
+
+    /// Marks successful exchanges, and blames failures on a host suspend when there was one.
+    fn track_io<T>(&mut self, result: Result<T>) -> Result<T> {
+        match result {
+            Ok(value) => {
+                self.suspend.mark();
+                Ok(value)
+            }
+            Err(e) => {
+                self.check_host_awake()?;
+                Err(e)
+            }
+        }
     }
 
     // Reads the specified number of bytes
     pub async fn read_bytes(&mut self, size: usize) -> Result<Vec<u8>> {
         let mut buf = vec![0u8; size];
-        self.port.read_exact(&mut buf).await?;
+        self.read(&mut buf).await?;
         Ok(buf)
     }
 
@@ -80,6 +216,14 @@ impl Connection {
         self.check(&buf, data)
     }
 
+    /// Like [`Connection::echo`], but gives up once `timeout` elapsed without the echo.
+    async fn echo_timeout(&mut self, data: &[u8], timeout: Duration) -> Result<()> {
+        self.write(data).await?;
+        let mut buf = vec![0u8; data.len()];
+        self.read_timeout(&mut buf, timeout).await?;
+        self.check(&buf, data)
+    }
+
     /* BROM / Preloader download handlers below :D */
 
     pub async fn handshake(&mut self) -> Result<()> {
@@ -89,6 +233,201 @@ impl Connection {
         Ok(())
     }
 
+    /// Reopens the port after the device re-enumerated, e.g. after switching USB speed.
+    ///
+    /// The device is looked up again by port name, which doesn't change as long as it
+    /// stays plugged in the same physical port. If the name changed anyway, the only port
+    /// with the same USB IDs is taken. Fails if the device isn't back within `wait`.
+    pub async fn reconnect(&mut self, wait: Duration) -> Result<()> {
+        let name = self.port.get_port_name();
+        let usb_ids = self.port.get_usb_ids();
+
+        self.port.close().await.ok();
+        sleep(REENUMERATION_DELAY).await;
+
+        self.reopen(&name, wait, |p| p.get_usb_ids() == usb_ids).await
+    }
+
+    /// Marks that the device may drop off the bus and come back as a DA port, with
+    /// another PID, until the next successful read. Meant to be called right after
+    /// jumping to the DA, as many devices re-enumerate there.
+    ///
+    /// When a transfer then fails because the device is gone, it's followed to its
+    /// new port and the transfer is retried there. Failures with the device still
+    /// on the bus are returned as they are.
+    pub fn expect_reenumeration(&mut self) {
+        self.reenumeration_expected = true;
+    }
+
+    /// Follows the device to its DA port after `error`, see [`Connection::expect_reenumeration`].
+    async fn follow_reenumeration(&mut self, error: Error) -> Result<()> {
+        self.reenumeration_expected = false;
+
+        // UART consoles don't enumerate, there's nothing to follow
+        if self.port.get_da_baudrate().is_some() {
+            return Err(error);
+        }
+
+        // Give the OS the time to notice the device is gone
+        sleep(REENUMERATION_DELAY).await;
+
+        let name = self.port.get_port_name();
+        let usb_ids = self.port.get_usb_ids();
+        let still_there = find_mtk_ports()
+            .await
+            .iter()
+            .any(|p| p.get_port_name() == name && p.get_usb_ids() == usb_ids);
+        if still_there {
+            return Err(error);
+        }
+
+        info!("Device dropped off the bus ({}), waiting for it to come back as a DA...", error);
+        self.port.close().await.ok();
+        self.reopen(&name, DA_REENUMERATION_WAIT, |p| p.get_connection_type() == ConnectionType::Da)
+            .await
+    }
+
+    /// Waits for the device to show up again, and swaps its port in.
+    ///
+    /// The port named `name` is taken if it `matches`, otherwise the only port that
+    /// `matches`. Fails if none shows up within `wait`.
+    async fn reopen(
+        &mut self,
+        name: &str,
+        wait: Duration,
+        matches: impl Fn(&dyn MTKPort) -> bool,
+    ) -> Result<()> {
+        let deadline = Instant::now() + wait;
+        loop {
+            let mut ports = find_mtk_ports().await;
+            let index = ports
+                .iter()
+                .position(|p| p.get_port_name() == name && matches(p.as_ref()))
+                .or_else(|| {
+                    let mut matching =
+                        ports.iter().enumerate().filter(|(_, p)| matches(p.as_ref()));
+                    match (matching.next(), matching.next()) {
+                        (Some((i, _)), None) => Some(i),
+                        _ => None,
+                    }
+                });
+
+            if let Some(index) = index {
+                let mut port = ports.swap_remove(index);
+                match port.open().await {
+                    Ok(()) => {
+                        info!("Reconnected to {}", port.get_port_name());
+                        port.set_retry_policy(self.retry_policy);
+                        self.past_stats.merge(self.port.transfer_stats());
+                        self.port = port;
+                        self.last_activity = Instant::now();
+                        self.suspend.mark();
+                        return Ok(());
+                    }
+                    Err(e) => debug!("Could not reopen {} yet: {}", name, e),
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::conn(format!(
+                    "Device on {} did not come back within {}s",
+                    name,
+                    wait.as_secs()
+                )));
+            }
+            sleep(RECONNECT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Switches a UART port to the baudrate requested for the DA, once DA1 runs.
+    /// Does nothing on USB ports, or when the rate is already the BROM one.
+    pub async fn switch_to_da_baudrate(&mut self) -> Result<()> {
+        let Some(baudrate) = self.port.get_da_baudrate() else {
+            return Ok(());
+        };
+        if baudrate == self.baudrate {
+            return Ok(());
+        }
+
+        info!("Switching UART from {} to {} baud", self.baudrate, baudrate);
+        self.port.set_baudrate(baudrate).await?;
+        self.baudrate = baudrate;
+        Ok(())
+    }
+
+    /// Negotiates a higher baudrate with the BROM or preloader over a UART, after handshake.
+    ///
+    /// The device is asked to switch with `Uart1SetBaudrate`, then the host follows and
+    /// checks the link with `GetHwCode`. If any step fails, the host drops back to the
+    /// original rate and checks the link again, so the session goes on at the old rate:
+    /// only a link lost at both rates is an error. Returns whether the new rate is in use.
+    /// Does nothing on USB ports.
+    pub async fn negotiate_baudrate(&mut self, baudrate: u32) -> Result<bool> {
+        if self.port.get_da_baudrate().is_none() || baudrate == self.baudrate {
+            return Ok(false);
+        }
+
+        let original = self.baudrate;
+        info!("Negotiating {} baud with the device", baudrate);
+
+        match self.request_baudrate(baudrate).await {
+            Ok(()) => {
+                info!("Switched UART from {} to {} baud", original, baudrate);
+                return Ok(true);
+            }
+            Err(e) => {
+                warn!("Could not switch to {} baud ({}), staying at {}", baudrate, e, original)
+            }
+        }
+
+        self.port.set_baudrate(original).await?;
+        self.baudrate = original;
+        sleep(BAUDRATE_SETTLE_DELAY).await;
+
+        self.probe_link().await.map_err(|e| {
+            Error::conn(format!("Device lost after a failed switch to {} baud: {}", baudrate, e))
+        })?;
+        Ok(false)
+    }
+
+    async fn request_baudrate(&mut self, baudrate: u32) -> Result<()> {
+        self.echo_timeout(&[Command::Uart1SetBaudrate as u8], BAUDRATE_STEP_TIMEOUT)
+            .await
+            .map_err(|e| Error::conn(format!("No answer to Uart1SetBaudrate: {}", e)))?;
+        self.echo_timeout(&baudrate.to_be_bytes(), BAUDRATE_STEP_TIMEOUT).await?;
+
+        let mut status = [0u8; 2];
+        self.read_timeout(&mut status, BAUDRATE_STEP_TIMEOUT).await?;
+        let status = u16::from_le_bytes(status);
+        if status != 0 {
+            error!("Uart1SetBaudrate failed with status: 0x{:04X}", status);
+            return Err(Error::conn("Uart1SetBaudrate failed"));
+        }
+
+        self.port.set_baudrate(baudrate).await?;
+        self.baudrate = baudrate;
+        sleep(BAUDRATE_SETTLE_DELAY).await;
+
+        self.probe_link().await
+    }
+
+    /// Checks that the device still answers, with a harmless command.
+    async fn probe_link(&mut self) -> Result<()> {
+        self.echo_timeout(&[Command::GetHwCode as u8], BAUDRATE_STEP_TIMEOUT)
+            .await
+            .map_err(|e| Error::conn(format!("No answer to GetHwCode: {}", e)))?;
+
+        // Hardware code, then status
+        let mut answer = [0u8; 4];
+        self.read_timeout(&mut answer, BAUDRATE_STEP_TIMEOUT).await?;
+        let status = u16::from_le_bytes([answer[2], answer[3]]);
+        if status != 0 {
+            error!("GetHwCode failed with status: {:04X}", status);
+            return Err(Error::conn("GetHwCode failed"));
+        }
+        Ok(())
+    }
+
     pub async fn jump_da(&mut self, address: u32) -> Result<()> {
         debug!("Jump to DA at 0x{:08X}", address);
 
@@ -104,6 +443,61 @@ impl Connection {
         Ok(())
     }
 
+    /// Sets the auth file sent by [`Connection::authenticate`].
+    pub fn set_auth(&mut self, auth: AuthFile) {
+        self.auth = Some(auth);
+    }
+
+    /// Sends the auth file before the DA when SLA or DAA are enabled, as the BROM
+    /// refuses the DA otherwise. Does nothing when both are disabled, e.g. after Kamakiri.
+    ///
+    /// Fails with [`Error::AuthRequired`] if they're enabled and no auth file was set.
+    pub async fn authenticate(&mut self, target_config: u32) -> Result<()> {
+        let required = match (target_config & 0x2 != 0, target_config & 0x4 != 0) {
+            (false, false) => return Ok(()),
+            (true, false) => "SLA",
+            (false, true) => "DAA",
+            (true, true) => "SLA/DAA",
+        };
+
+        let Some(auth) = self.auth.take() else {
+            return Err(Error::AuthRequired(required.to_string()));
+        };
+
+        info!("{} is enabled, sending the auth file...", required);
+        let result = self.send_auth(&auth).await;
+        self.auth = Some(auth);
+        result
+    }
+
+    pub async fn send_auth(&mut self, auth: &AuthFile) -> Result<()> {
+        let data = auth.data();
+        debug!("Sending auth file, size: {}", data.len());
+        self.echo(&[Command::SendAuth as u8], 1).await?;
+        self.echo(&(data.len() as u32).to_be_bytes(), 4).await?;
+
+        // Anything up to 0xFF is only a warning
+        let status = self.read_u16_be().await?;
+        if status > 0xFF {
+            error!("SendAuth command failed with status: {:04X}", status);
+            return Err(Error::conn("SendAuth command failed"));
+        }
+
+        self.port.write_all(data).await?;
+
+        let checksum = self.read_u16_be().await?;
+        debug!("Received checksum: 0x{:04X}", checksum);
+
+        let status = self.read_u16_be().await?;
+        if status != 0 {
+            error!("SendAuth data transfer failed with status: {:04X}", status);
+            return Err(Error::conn("Auth file rejected, it's not the one of this device"));
+        }
+
+        info!("Auth file accepted");
+        Ok(())
+    }
+
     pub async fn send_da(
         &mut self,
         da_data: &[u8],
@@ -172,36 +566,14 @@ impl Connection {
         Ok((hw_sub_code, hw_ver, sw_ver))
     }
 
-    pub async fn get_soc_id(&mut self) -> Result<Vec<u8>> {
+    /// Returns the SoC ID, or `None` if the device doesn't support the command.
+    pub async fn get_soc_id(&mut self) -> Result<Option<Vec<u8>>> {
         self.echo(&[Command::GetSocId as u8], 1).await?;
-
-        let mut length_bytes = [0u8; 4];
-
-        let read_result =
-            timeout(Duration::from_millis(500), self.port.read_exact(&mut length_bytes)).await;
-
-        let length_bytes = match read_result {
-            Ok(Ok(_)) => length_bytes,
-            Ok(Err(e)) => return Err(e), // I/O error
-            Err(_) => return Ok(vec![]), // Timeout -> no SocId available
-        };
-
-        let length = u32::from_be_bytes(length_bytes) as usize;
-
-        let mut soc_id = vec![0u8; length];
-        self.port.read_exact(&mut soc_id).await?;
-
-        let status = self.read_u16_le().await?;
-
-        if status != 0 {
-            error!("GetSocId failed with status: 0x{:04X}", status);
-            return Err(Error::conn("GetSocId failed"));
-        }
-
-        Ok(soc_id)
+        self.read_id_response("GetSocId").await
     }
 
-    pub async fn get_meid(&mut self) -> Result<Vec<u8>> {
+    /// Returns the MEID, or `None` if the device doesn't support the command.
+    pub async fn get_meid(&mut self) -> Result<Option<Vec<u8>>> {
         self.port.write_all(&[Command::GetMeId as u8]).await?;
 
         let mut echo = [0u8; 1];
@@ -222,30 +594,77 @@ impl Connection {
             return Err(Error::conn("Data mismatch"));
         }
 
-        let mut length_bytes = [0u8; 4];
+        self.read_id_response("GetMeid").await
+    }
 
-        let read_result =
-            timeout(Duration::from_millis(500), self.port.read_exact(&mut length_bytes)).await;
+    /// Reads the length-prefixed response shared by GetMeid and GetSocId.
+    ///
+    /// Devices without support for the command never answer, which is reported as `None`.
+    /// Some preloaders are slow right after the banner though, so the wait is retried once
+    /// before giving up, and an answer arriving even later is drained so the next command
+    /// doesn't read it. Once the device answers, any failure is an error.
+    async fn read_id_response(&mut self, name: &str) -> Result<Option<Vec<u8>>> {
+        let mut length_bytes = [0u8; 4];
+        let mut answered = false;
+
+        for attempt in 1..=2 {
+            match self.port.read_exact_timeout(&mut length_bytes, ID_RESPONSE_TIMEOUT).await {
+                Ok(_) => {
+                    answered = true;
+                    break;
+                }
+                Err(e) if e.is_timeout() && e.bytes_read() == Some(0) => {
+                    debug!("{} did not answer (attempt {})", name, attempt)
+                }
+                // A partial length leaves the stream out of sync, don't pretend it's fine
+                Err(e) => return Err(e),
+            }
+        }
 
-        let length_bytes = match read_result {
-            Ok(Ok(_)) => length_bytes,
-            Ok(Err(e)) => return Err(e), // I/O error
-            Err(_) => return Ok(vec![]), // Device did not reply -> no MEID support
-        };
+        if !answered {
+            self.drain_late_id_response(name).await?;
+            return Ok(None);
+        }
 
         let length = u32::from_be_bytes(length_bytes) as usize;
+        if length > MAX_ID_LENGTH {
+            return Err(Error::conn(format!(
+                "{} announced an invalid length of {} bytes",
+                name, length
+            )));
+        }
 
-        let mut meid = vec![0u8; length];
-        self.port.read_exact(&mut meid).await?;
+        let mut id = vec![0u8; length];
+        self.port.read_exact(&mut id).await?;
 
         let status = self.read_u16_le().await?;
 
         if status != 0 {
-            error!("GetMeid failed with status: 0x{:04X}", status);
-            return Err(Error::conn("GetMeid failed"));
+            error!("{} failed with status: 0x{:04X}", name, status);
+            return Err(Error::conn(format!("{} failed", name)));
         }
 
-        Ok(meid)
+        Ok(Some(id))
+    }
+
+    /// Discards an ID response arriving after [`Connection::read_id_response`] gave up.
+    ///
+    /// Left in the stream, it would be read as the echo and answer of the next command.
+    async fn drain_late_id_response(&mut self, name: &str) -> Result<()> {
+        // Length, the longest ID and status: anything filling it isn't a late answer
+        let mut late = [0u8; 4 + MAX_ID_LENGTH + 2];
+
+        match self.port.read_exact_timeout(&mut late, ID_DRAIN_TIMEOUT).await {
+            Err(e) if e.is_timeout() => {
+                let dropped = e.bytes_read().unwrap_or(0);
+                if dropped > 0 {
+                    debug!("Dropped {} bytes of a late {} answer", dropped, name);
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+            Ok(_) => Err(Error::conn(format!("Device kept sending data after {} timed out", name))),
+        }
     }
 
     /// Returns the target configuration of the device.
@@ -303,4 +722,63 @@ impl Connection {
         data.truncate(size);
         Ok(data)
     }
+
+    /// Writes memory to the device, split into 4-byte chunks, each of them echoed back.
+    /// `data` is sent as is, so it must be laid out like what `read32` returns,
+    /// and its length must be a multiple of 4.
+    pub async fn write32(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        if !data.len().is_multiple_of(4) {
+            return Err(Error::penumbra(format!(
+                "Write32 needs a multiple of 4 bytes, got {}",
+                data.len()
+            )));
+        }
+
+        self.echo(&[Command::Write32 as u8], 1).await?;
+        self.echo(&address.to_be_bytes(), 4).await?;
+        self.echo(&((data.len() / 4) as u32).to_be_bytes(), 4).await?;
+
+        let status = self.read_u16_be().await?;
+        if status != 0 {
+            return Err(Error::conn(format!("Write32 failed with status: 0x{:04X}", status)));
+        }
+
+        for chunk in data.chunks(4) {
+            self.echo(chunk, 4).await?;
+        }
+
+        let status = self.read_u16_be().await?;
+        if status != 0 {
+            return Err(Error::conn(format!("Write32 failed with status: 0x{:04X}", status)));
+        }
+
+        Ok(())
+    }
+
+    /// Reads `size` bytes of memory, with one `read32` per `MEM_CHUNK_SIZE` bytes
+    /// so a large range doesn't stall on a single command.
+    pub async fn read_mem(&mut self, address: u32, size: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(size);
+        while data.len() < size {
+            let chunk = MEM_CHUNK_SIZE.min(size - data.len());
+            let offset = u32::try_from(data.len())
+                .ok()
+                .and_then(|offset| address.checked_add(offset))
+                .ok_or_else(|| Error::penumbra("Memory range overflows the address space"))?;
+            data.extend(self.read32(offset, chunk).await?);
+        }
+        Ok(data)
+    }
+
+    /// Writes `data` to memory, with one `write32` per `MEM_CHUNK_SIZE` bytes.
+    pub async fn write_mem(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        for (i, chunk) in data.chunks(MEM_CHUNK_SIZE).enumerate() {
+            let offset = u32::try_from(i * MEM_CHUNK_SIZE)
+                .ok()
+                .and_then(|offset| address.checked_add(offset))
+                .ok_or_else(|| Error::penumbra("Memory range overflows the address space"))?;
+            self.write32(offset, chunk).await?;
+        }
+        Ok(())
+    }
 }
@@ -3,9 +3,11 @@
     SPDX-FileCopyrightText: 2025 Shomy
 */
 
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
+use std::time::Duration;
 
 use crate::connection::backend::*;
+use crate::connection::retry::{ConnectionStats, RetryPolicy};
 use crate::error::Result;
 
 /// List of all ports available for connecting and what mode they refer to.
@@ -34,23 +36,67 @@ pub enum ConnectionType {
     Da,
 }
 
+impl fmt::Display for ConnectionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionType::Brom => write!(f, "brom"),
+            ConnectionType::Preloader => write!(f, "preloader"),
+            ConnectionType::Da => write!(f, "da"),
+        }
+    }
+}
+
 #[async_trait::async_trait]
 pub trait MTKPort: Send + Debug {
     async fn open(&mut self) -> Result<()>;
     async fn close(&mut self) -> Result<()>;
     async fn read_exact(&mut self, buf: &mut [u8]) -> Result<usize>;
+    /// Like `read_exact`, but gives up once `timeout` elapsed, with a timed out
+    /// [`Error::ReadFailed`](crate::error::Error::ReadFailed) telling how much arrived.
+    /// Unlike cancelling `read_exact`, the port is left at a known point of the stream.
+    /// Ports that can't bound their reads just read.
+    async fn read_exact_timeout(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize> {
+        self.read_exact(buf).await
+    }
     async fn write_all(&mut self, buf: &[u8]) -> Result<()>;
     async fn flush(&mut self) -> Result<()>;
 
     async fn handshake(&mut self) -> Result<()>;
     fn get_connection_type(&self) -> ConnectionType;
     fn get_baudrate(&self) -> u32;
+    /// Changes the baudrate of an open port. Only UART ports have one to change,
+    /// USB ports ignore it.
+    async fn set_baudrate(&mut self, _baudrate: u32) -> Result<()> {
+        Ok(())
+    }
+    /// Baudrate to switch to once DA1 runs, for UART ports whose BROM only
+    /// talks at 115200. `None` for USB ports.
+    fn get_da_baudrate(&self) -> Option<u32> {
+        None
+    }
+    /// Sets how transient USB errors are retried by `read_exact` and `write_all`.
+    /// Ports without such errors, like UART ones, ignore it.
+    fn set_retry_policy(&mut self, _policy: RetryPolicy) {}
+    /// Returns the transient errors seen by the port, and how they were retried.
+    fn transfer_stats(&self) -> ConnectionStats {
+        ConnectionStats::default()
+    }
     fn get_port_name(&self) -> String;
+    /// Returns the USB vendor and product ID of the port.
+    fn get_usb_ids(&self) -> (u16, u16);
 
-    async fn find_device() -> Result<Option<Self>>
+    /// Returns all known MTK ports of this backend, without opening them.
+    async fn find_devices() -> Result<Vec<Self>>
     where
         Self: Sized;
 
+    async fn find_device() -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        Ok(Self::find_devices().await?.into_iter().next())
+    }
+
     // Only for USB ports
     async fn ctrl_out(
         &mut self,
@@ -70,28 +116,72 @@ pub trait MTKPort: Send + Debug {
     ) -> Result<Vec<u8>>;
 }
 
-pub async fn find_mtk_port() -> Option<Box<dyn MTKPort>> {
-    // Default NUSB backend
+/// Opens the BROM UART console at `path` (e.g. `/dev/ttyUSB0`), bypassing USB enumeration.
+/// The BROM is talked to at 115200 baud, and `baudrate` is used from DA1 on.
+///
+/// Only available with the `serial` feature.
+#[cfg(feature = "serial")]
+pub async fn open_serial_port(path: &str, baudrate: u32) -> Result<Box<dyn MTKPort>> {
+    let mut port = SerialMTKPort::from_path(path, baudrate);
+    port.open().await?;
+    Ok(Box::new(port))
+}
+
+/// Returns every known MTK port exposed by the enabled backends, without opening them.
+///
+/// Ports are sorted by name (see [`MTKPort::get_port_name`]), so the same set of
+/// connected devices always comes in the same order. USB port names are based on the
+/// physical port the device is plugged in, and don't change when it re-enumerates.
+pub async fn find_mtk_ports() -> Vec<Box<dyn MTKPort>> {
+    let mut ports: Vec<Box<dyn MTKPort>> = Vec::new();
+
     #[cfg(not(any(feature = "libusb", feature = "serial")))]
-    let port = UsbMTKPort::find_device().await;
+    if let Ok(found) = UsbMTKPort::find_devices().await {
+        ports.extend(found.into_iter().map(|p| Box::new(p) as Box<dyn MTKPort>));
+    }
 
-    // LibUSB backend
     #[cfg(feature = "libusb")]
-    let port = UsbMTKPort::find_device().await;
+    if let Ok(found) = UsbMTKPort::find_devices().await {
+        ports.extend(found.into_iter().map(|p| Box::new(p) as Box<dyn MTKPort>));
+    }
 
-    // Serial backend, not ideal since some features (i.e. linecoding) aren't available.
     #[cfg(feature = "serial")]
-    let port = SerialMTKPort::find_device().await;
-
-    match port {
-        Ok(Some(mut port)) => {
-            if port.open().await.is_ok() {
-                Some(Box::new(port))
-            } else {
-                None
-            }
-        }
-        Ok(None) => None,
-        Err(_) => None,
+    if let Ok(found) = SerialMTKPort::find_devices().await {
+        ports.extend(found.into_iter().map(|p| Box::new(p) as Box<dyn MTKPort>));
+    }
+
+    ports.sort_by_key(|port| port.get_port_name());
+    ports
+}
+
+/// Opens the first port returned by [`find_mtk_ports`].
+pub async fn find_mtk_port() -> Option<Box<dyn MTKPort>> {
+    let port = find_mtk_ports().await.into_iter().next()?;
+    open_port(port).await
+}
+
+/// Opens the port named `name`, as returned by [`MTKPort::get_port_name`].
+/// Used to pick one device out of several connected at the same time.
+pub async fn find_mtk_port_by_name(name: &str) -> Option<Box<dyn MTKPort>> {
+    let port = find_mtk_ports().await.into_iter().find(|port| port.get_port_name() == name)?;
+    open_port(port).await
+}
+
+async fn open_port(mut port: Box<dyn MTKPort>) -> Option<Box<dyn MTKPort>> {
+    port.open().await.ok()?;
+    Some(port)
+}
+
+/// Name of a USB port, from its bus and the chain of hub ports leading to it
+/// (e.g. `usb:1-2.3`, like Linux sysfs does). Unlike the device address, this
+/// stays the same when the device re-enumerates from BROM to preloader or DA.
+/// Falls back to the address when the OS doesn't report the port chain.
+#[cfg_attr(all(feature = "serial", not(feature = "libusb")), allow(dead_code))]
+pub(crate) fn usb_port_name(bus: impl fmt::Display, port_chain: &[u8], address: u8) -> String {
+    if port_chain.is_empty() {
+        return format!("usb:{}@{}", bus, address);
     }
+
+    let chain = port_chain.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(".");
+    format!("usb:{}-{}", bus, chain)
 }
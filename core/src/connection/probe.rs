@@ -0,0 +1,79 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::time::Duration;
+
+use log::debug;
+use tokio::time::timeout;
+
+use crate::connection::Connection;
+use crate::connection::port::{ConnectionType, MTKPort};
+use crate::error::{Error, Result};
+
+/// Upper bound for a whole probe. A device stuck mid-boot shouldn't hang the caller.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A MTK port found during enumeration, described without opening it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortInfo {
+    pub vid: u16,
+    pub pid: u16,
+    pub connection_type: ConnectionType,
+    pub port_name: String,
+}
+
+impl PortInfo {
+    pub fn from_port(port: &dyn MTKPort) -> Self {
+        let (vid, pid) = port.get_usb_ids();
+        PortInfo {
+            vid,
+            pid,
+            connection_type: port.get_connection_type(),
+            port_name: port.get_port_name(),
+        }
+    }
+}
+
+/// Outcome of a non-destructive probe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeResult {
+    pub port: PortInfo,
+    /// HW code reported by BROM or preloader.
+    /// Always `None` in DA mode, since no DA command is safe to send without a session.
+    pub hw_code: Option<u16>,
+}
+
+/// Confirms that the device behind `port` is alive, with the least possible interaction.
+///
+/// For BROM and preloader this is a handshake followed by `GetHwCode`, both of which
+/// are read-only and are sent anyway at the start of every session.
+/// DA mode ports are only opened, as the DA protocol in use is unknown at this point.
+///
+/// The port is closed afterwards, so it can be handed to a `Device` later on.
+pub async fn probe_port(port: Box<dyn MTKPort>) -> Result<ProbeResult> {
+    let info = PortInfo::from_port(port.as_ref());
+    debug!("Probing {} ({:?})", info.port_name, info.connection_type);
+
+    let mut conn = Connection::new(port);
+
+    let probe = async {
+        conn.port.open().await?;
+
+        if conn.connection_type == ConnectionType::Da {
+            return Ok(None);
+        }
+
+        conn.handshake().await?;
+        conn.get_hw_code().await.map(Some)
+    };
+
+    let hw_code = match timeout(PROBE_TIMEOUT, probe).await {
+        Ok(result) => result,
+        Err(_) => Err(Error::conn("Probe timed out")),
+    };
+
+    conn.port.close().await.ok();
+
+    Ok(ProbeResult { port: info, hw_code: hw_code? })
+}
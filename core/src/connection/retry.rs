@@ -0,0 +1,145 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Retries of transient USB errors, at the transfer level.
+//!
+//! A timed out, stalled or overflowed bulk transfer is retried by the backend that issued
+//! it, for the bytes it didn't move yet. Nothing the protocols framed is sent twice or
+//! skipped: a retried transfer picks up exactly where the failed one stopped.
+// Serial ports don't retry, only USB backends do
+#![cfg_attr(all(feature = "serial", not(feature = "libusb")), allow(dead_code))]
+use std::fmt;
+use std::time::Duration;
+
+use log::warn;
+use tokio::time::sleep;
+
+/// Longest wait between two attempts, however large the backoff grows.
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// How many times, and how patiently, transient USB errors are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries of a single transfer before its error is returned, 0 disables retrying.
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled on each following one.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Returns transient errors right away, like before retries existed.
+    pub const fn none() -> Self {
+        Self { max_retries: 0, backoff: Duration::ZERO }
+    }
+
+    /// Wait before retry number `attempt`, starting at 1.
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(16);
+        self.backoff.saturating_mul(factor).min(MAX_BACKOFF)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_retries: 3, backoff: Duration::from_millis(50) }
+    }
+}
+
+/// Transient errors seen on a connection, to tell how flaky the cable is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// Transfers that timed out.
+    pub timeouts: u64,
+    /// Transfers that stalled, with the endpoint halted.
+    pub stalls: u64,
+    /// Transfers where the device sent more than asked for.
+    pub overflows: u64,
+    /// Transfers retried after one of the above.
+    pub retries: u64,
+    /// Transfers given up on after running out of retries.
+    pub failures: u64,
+}
+
+impl ConnectionStats {
+    /// Transient errors of any kind.
+    pub fn errors(&self) -> u64 {
+        self.timeouts + self.stalls + self.overflows
+    }
+
+    pub(crate) fn merge(&mut self, other: ConnectionStats) {
+        self.timeouts += other.timeouts;
+        self.stalls += other.stalls;
+        self.overflows += other.overflows;
+        self.retries += other.retries;
+        self.failures += other.failures;
+    }
+}
+
+impl fmt::Display for ConnectionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} timeouts, {} stalls, {} overflows, {} retries, {} failed transfers",
+            self.timeouts, self.stalls, self.overflows, self.retries, self.failures
+        )
+    }
+}
+
+/// USB errors worth retrying, as told apart by the backends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TransientError {
+    Timeout,
+    /// The endpoint halted, it needs a clear-halt before the next transfer.
+    Stall,
+    /// Only told apart by libusb
+    #[cfg_attr(not(feature = "libusb"), allow(dead_code))]
+    Overflow,
+}
+
+/// Retry state of a single `read_exact` or `write_all`.
+pub(crate) struct Retry {
+    policy: RetryPolicy,
+    attempt: u32,
+}
+
+impl Retry {
+    pub(crate) fn new(policy: RetryPolicy) -> Self {
+        Self { policy, attempt: 0 }
+    }
+
+    /// Resets the attempts once the transfer moved data again.
+    pub(crate) fn progressed(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Counts `error` in `stats`, then waits before the next attempt.
+    /// Returns false when the transfer is out of retries, and its error should be returned.
+    pub(crate) async fn retry(
+        &mut self,
+        stats: &mut ConnectionStats,
+        error: TransientError,
+    ) -> bool {
+        match error {
+            TransientError::Timeout => stats.timeouts += 1,
+            TransientError::Stall => stats.stalls += 1,
+            TransientError::Overflow => stats.overflows += 1,
+        }
+
+        if self.attempt >= self.policy.max_retries {
+            if self.policy.max_retries > 0 {
+                stats.failures += 1;
+            }
+            return false;
+        }
+
+        self.attempt += 1;
+        stats.retries += 1;
+        warn!(
+            "USB transfer failed ({:?}), retrying ({}/{})",
+            error, self.attempt, self.policy.max_retries
+        );
+        sleep(self.policy.delay(self.attempt)).await;
+        true
+    }
+}
@@ -0,0 +1,109 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::time::Duration;
+
+/// How long the host has to have been suspended since the last exchange for it to be
+/// reported. Shorter naps don't give the device time to drop off the bus.
+pub const SUSPEND_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// Detects the host sleeping since the last exchange with the device.
+///
+/// On Linux and Android, `CLOCK_BOOTTIME` keeps counting while the host is suspended and
+/// `CLOCK_MONOTONIC` doesn't, so their difference grows by exactly the time spent asleep.
+/// Neither follows the wall clock, so NTP steps and manual adjustments aren't mistaken
+/// for a suspend.
+///
+/// Other platforms have no such pair of clocks (on Windows the monotonic clock keeps
+/// counting across a suspend, and the wall clock can be stepped at any time), so the
+/// detector is inactive there and [`slept`](Self::slept) always returns `None`.
+#[derive(Debug, Clone, Copy)]
+pub struct SuspendDetector {
+    /// Time spent suspended since boot, at the last mark
+    asleep: Option<Duration>,
+}
+
+impl Default for SuspendDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SuspendDetector {
+    pub fn new() -> Self {
+        Self { asleep: suspended_since_boot() }
+    }
+
+    /// Records that data was exchanged with the device.
+    pub fn mark(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Returns how long the host slept since the last mark, if it did.
+    pub fn slept(&self) -> Option<Duration> {
+        self.slept_until(suspended_since_boot()?)
+    }
+
+    fn slept_until(&self, asleep: Duration) -> Option<Duration> {
+        let gap = asleep.checked_sub(self.asleep?)?;
+        (gap > SUSPEND_THRESHOLD).then_some(gap)
+    }
+}
+
+/// Total time the host spent suspended since it booted.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn suspended_since_boot() -> Option<Duration> {
+    use rustix::time::{ClockId, Timespec, clock_gettime};
+
+    let duration =
+        |ts: Timespec| Some(Duration::new(ts.tv_sec.try_into().ok()?, ts.tv_nsec.try_into().ok()?));
+    let mono = duration(clock_gettime(ClockId::Monotonic))?;
+    let boot = duration(clock_gettime(ClockId::Boottime))?;
+    boot.checked_sub(mono)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn suspended_since_boot() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn reports_the_time_spent_asleep() {
+        let detector = SuspendDetector { asleep: Some(MINUTE) };
+        assert_eq!(detector.slept_until(MINUTE * 3), Some(MINUTE * 2));
+    }
+
+    #[test]
+    fn short_naps_are_ignored() {
+        let detector = SuspendDetector { asleep: Some(MINUTE) };
+        assert_eq!(detector.slept_until(MINUTE), None);
+        assert_eq!(detector.slept_until(MINUTE + SUSPEND_THRESHOLD), None);
+    }
+
+    #[test]
+    fn nothing_reported_without_a_baseline() {
+        let detector = SuspendDetector { asleep: None };
+        assert_eq!(detector.slept_until(MINUTE), None);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn boot_clock_is_read() {
+        assert!(suspended_since_boot().is_some());
+        assert_eq!(SuspendDetector::new().slept(), None);
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn inactive_without_a_boot_clock() {
+        let detector = SuspendDetector { asleep: Some(Duration::ZERO) };
+        assert_eq!(detector.slept(), None);
+    }
+}
@@ -0,0 +1,64 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Auth files, for devices with Serial Link Authentication (SLA) or DA Authentication (DAA).
+//!
+//! Vendors ship them along with their firmware (e.g. `auth_sv5.auth`). They are signed for
+//! the device and opaque to the host, which sends them as they are with SendAuth before the DA.
+use crate::core::inspect::{ContentKind, detect_content};
+use crate::core::preloader::is_boot_image;
+use crate::error::{Error, Result};
+
+/// Smallest auth file the BROM accepts, the size of its signature alone.
+const AUTH_MIN_SIZE: usize = 0x100;
+/// Largest auth file the BROM accepts.
+const AUTH_MAX_SIZE: usize = 0x10000;
+/// Magic found in the header of DA files, the file most often passed by mistake.
+const DA_MAGIC: &[u8] = b"MTK_DOWNLOAD_AGENT";
+
+/// A vendor auth file, checked to be plausible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthFile {
+    data: Vec<u8>,
+}
+
+impl AuthFile {
+    /// Checks that `data` can be an auth file.
+    ///
+    /// The content is signed and can't be checked on the host, so only the size is,
+    /// along with the headers of the other files of a firmware package (DA, preloader,
+    /// images) that are easily picked instead.
+    pub fn parse(data: Vec<u8>) -> Result<AuthFile> {
+        if !(AUTH_MIN_SIZE..=AUTH_MAX_SIZE).contains(&data.len()) {
+            return Err(Error::penumbra(format!(
+                "Not an auth file: {} bytes, auth files are 0x{:X} to 0x{:X} bytes",
+                data.len(),
+                AUTH_MIN_SIZE,
+                AUTH_MAX_SIZE
+            )));
+        }
+
+        let header = data.get(..0x6C).unwrap_or_default();
+        if header.windows(DA_MAGIC.len()).any(|w| w == DA_MAGIC) {
+            return Err(Error::penumbra("Not an auth file: this is a DA file"));
+        }
+        if is_boot_image(&data).is_ok() {
+            return Err(Error::penumbra("Not an auth file: this is a preloader"));
+        }
+        match detect_content(&data) {
+            ContentKind::Unknown => {}
+            ContentKind::Empty => {
+                return Err(Error::penumbra("Not an auth file: it's only zeros or 0xFF"));
+            }
+            kind => return Err(Error::penumbra(format!("Not an auth file: found {:?}", kind))),
+        }
+
+        Ok(AuthFile { data })
+    }
+
+    /// The raw auth file, as sent to the device.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
@@ -41,6 +41,7 @@ impl Signer for LocalKeyring {
 }
 
 impl LocalKeyring {
+    #[allow(clippy::expect_used)]
     pub fn new() -> Self {
         let keys = SLA_KEYS
             .iter()
@@ -2,10 +2,12 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+mod authfile;
 #[cfg(not(feature = "no_localslakeyring"))]
 mod keys;
 #[cfg(not(feature = "no_localslakeyring"))]
 pub mod local_keyring;
 mod sla;
 
+pub use authfile::AuthFile;
 pub use sla::{AuthManager, SignData, SignPurpose, SignRequest, Signer};
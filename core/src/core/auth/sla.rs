@@ -23,6 +23,24 @@ pub struct SignData {
     pub raw: Vec<u8>,
 }
 
+impl SignData {
+    /// Picks the SoC ID to sign with. Some DAs report a zeroed one in their firmware info,
+    /// then the one the BROM answered GetSocId with is used instead. `None` there means the
+    /// BROM doesn't support the command, which leaves nothing to sign with.
+    pub fn pick_soc_id(reported: &[u8], brom: Option<Vec<u8>>) -> Result<Vec<u8>> {
+        if reported.iter().any(|&b| b != 0) {
+            return Ok(reported.to_vec());
+        }
+
+        match brom {
+            Some(soc_id) if !soc_id.is_empty() => Ok(soc_id),
+            _ => Err(Error::penumbra(
+                "SLA needs the SoC ID, but neither the DA nor the BROM reported one",
+            )),
+        }
+    }
+}
+
 pub struct SignRequest {
     pub data: SignData,
     pub purpose: SignPurpose,
@@ -104,3 +122,26 @@ impl AuthManager {
         Err(Error::penumbra("Could not find any signer"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reported_soc_id_is_preferred() {
+        let soc_id = SignData::pick_soc_id(&[0xAB; 32], Some(vec![0xCD; 32])).unwrap();
+        assert_eq!(soc_id, vec![0xAB; 32]);
+    }
+
+    #[test]
+    fn zeroed_soc_id_falls_back_to_the_brom() {
+        let soc_id = SignData::pick_soc_id(&[0; 32], Some(vec![0xCD; 32])).unwrap();
+        assert_eq!(soc_id, vec![0xCD; 32]);
+    }
+
+    #[test]
+    fn unsupported_brom_soc_id_is_an_error() {
+        assert!(SignData::pick_soc_id(&[0; 32], None).is_err());
+        assert!(SignData::pick_soc_id(&[], Some(Vec::new())).is_err());
+    }
+}
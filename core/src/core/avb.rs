@@ -0,0 +1,151 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use crate::error::{Error, Result};
+
+/// Magic at the start of a vbmeta image.
+pub const VBMETA_MAGIC: &[u8; 4] = b"AVB0";
+/// Size of the fixed vbmeta header, followed by the authentication and auxiliary blocks.
+pub const VBMETA_HEADER_SIZE: usize = 256;
+
+/// vbmeta flag disabling dm-verity for hashtree descriptors.
+pub const FLAG_HASHTREE_DISABLED: u32 = 1 << 0;
+/// vbmeta flag disabling verification of all descriptors.
+pub const FLAG_VERIFICATION_DISABLED: u32 = 1 << 1;
+/// Offset of the flags field in the vbmeta header.
+pub const FLAGS_OFFSET: usize = 120;
+
+const TAG_HASHTREE: u64 = 1;
+const TAG_HASH: u64 = 2;
+const TAG_CHAIN_PARTITION: u64 = 4;
+
+// Offsets of the partition name length and the partition name itself,
+// from the start of each descriptor (tag and size included)
+const HASHTREE_NAME_LEN_OFFSET: usize = 104;
+const HASHTREE_NAME_OFFSET: usize = 180;
+const HASH_NAME_LEN_OFFSET: usize = 56;
+const HASH_NAME_OFFSET: usize = 132;
+const CHAIN_NAME_LEN_OFFSET: usize = 20;
+const CHAIN_NAME_OFFSET: usize = 92;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorKind {
+    /// The partition is verified at runtime through dm-verity.
+    Hashtree,
+    /// The partition is hashed as a whole by the bootloader.
+    Hash,
+    /// The partition carries its own vbmeta, signed with the given key.
+    ChainPartition,
+}
+
+/// A descriptor of a vbmeta image that refers to a partition.
+#[derive(Debug, Clone)]
+pub struct PartitionDescriptor {
+    pub kind: DescriptorKind,
+    /// Partition name, without slot suffix.
+    pub partition_name: String,
+}
+
+/// The parts of a vbmeta image needed to tell which partitions it covers.
+#[derive(Debug, Clone)]
+pub struct VbMeta {
+    pub flags: u32,
+    pub descriptors: Vec<PartitionDescriptor>,
+}
+
+impl VbMeta {
+    /// Parses a vbmeta image, as stored in the `vbmeta` partitions.
+    /// Descriptors that don't refer to a partition (properties, kernel cmdline) are skipped.
+    pub fn parse(data: &[u8]) -> Result<VbMeta> {
+        let Some(header) = data.get(..VBMETA_HEADER_SIZE).filter(|h| h.starts_with(VBMETA_MAGIC))
+        else {
+            return Err(Error::penumbra("Invalid vbmeta magic"));
+        };
+
+        let auth_size = be_u64(data, 12)?;
+        let descriptors_offset = be_u64(data, 96)?;
+        let descriptors_size = be_u64(data, 104)?;
+        let flags = be_u32(data, FLAGS_OFFSET)?;
+
+        // Descriptors live in the auxiliary block, right after the authentication one
+        let start = (VBMETA_HEADER_SIZE as u64)
+            .checked_add(auth_size)
+            .and_then(|s| s.checked_add(descriptors_offset))
+            .ok_or_else(|| Error::malformed("vbmeta descriptors offset overflows", header))?;
+        let mut area = start
+            .checked_add(descriptors_size)
+            .and_then(|end| data.get(usize::try_from(start).ok()?..usize::try_from(end).ok()?))
+            .ok_or_else(|| Error::malformed("vbmeta descriptors out of bounds", header))?;
+
+        let mut descriptors = Vec::new();
+        while area.len() >= 16 {
+            let tag = be_u64(area, 0)?;
+            let following = be_u64(area, 8)?;
+            let len = following
+                .checked_add(16)
+                .filter(|&len| len <= area.len() as u64)
+                .ok_or_else(|| Error::malformed("vbmeta descriptor out of bounds", area))?
+                as usize;
+            let (desc, rest) = area.split_at(len);
+
+            let name_fields = match tag {
+                TAG_HASHTREE => {
+                    Some((DescriptorKind::Hashtree, HASHTREE_NAME_LEN_OFFSET, HASHTREE_NAME_OFFSET))
+                }
+                TAG_HASH => Some((DescriptorKind::Hash, HASH_NAME_LEN_OFFSET, HASH_NAME_OFFSET)),
+                TAG_CHAIN_PARTITION => {
+                    Some((DescriptorKind::ChainPartition, CHAIN_NAME_LEN_OFFSET, CHAIN_NAME_OFFSET))
+                }
+                _ => None,
+            };
+
+            if let Some((kind, len_offset, name_offset)) = name_fields {
+                let name_len = be_u32(desc, len_offset)? as usize;
+                let name = desc
+                    .get(name_offset..name_offset + name_len)
+                    .ok_or_else(|| Error::malformed("vbmeta partition name out of bounds", desc))?;
+                descriptors.push(PartitionDescriptor {
+                    kind,
+                    partition_name: String::from_utf8_lossy(name).into_owned(),
+                });
+            }
+
+            area = rest;
+        }
+
+        Ok(VbMeta { flags, descriptors })
+    }
+
+    /// Whether the bootloader checks the descriptors at all.
+    /// When verification is disabled, the descriptors are still present but ignored.
+    pub fn verification_enabled(&self) -> bool {
+        self.flags & FLAG_VERIFICATION_DISABLED == 0
+    }
+
+    /// Whether `partition` is covered by this vbmeta, either directly or through a chain.
+    /// Slot suffixes (`_a`, `_b`) are ignored, as descriptors never carry them.
+    pub fn covers(&self, partition: &str) -> bool {
+        let name = strip_slot_suffix(partition);
+        self.descriptors.iter().any(|d| d.partition_name == name)
+    }
+}
+
+/// Returns the partition name without its A/B slot suffix, if any.
+pub fn strip_slot_suffix(partition: &str) -> &str {
+    partition.strip_suffix("_a").or_else(|| partition.strip_suffix("_b")).unwrap_or(partition)
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_be_bytes)
+        .ok_or_else(|| Error::malformed(format!("Expected a u32 at offset {}", offset), data))
+}
+
+fn be_u64(data: &[u8], offset: usize) -> Result<u64> {
+    data.get(offset..offset + 8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_be_bytes)
+        .ok_or_else(|| Error::malformed(format!("Expected a u64 at offset {}", offset), data))
+}
@@ -0,0 +1,310 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Backup manifests (`backup.json`), describing a full dump so it can be restored later.
+//!
+//! The manifest records the device the dump comes from, its storage and partition table,
+//! and the file and SHA-256 of every dumped partition. It's written next to the dumps,
+//! which are raw partition images unless the frontend encodes them.
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs::{File, read, rename, try_exists, write};
+use tokio::io::AsyncReadExt;
+
+use crate::core::storage::{Partition, StorageInfo, StorageType, is_pl_part};
+use crate::error::{Error, Result};
+
+/// Version of the manifest format, bumped on incompatible changes.
+pub const BACKUP_VERSION: u32 = 1;
+/// File name of the manifest, within the backup directory.
+pub const BACKUP_MANIFEST_NAME: &str = "backup.json";
+/// Partitions left alone by a restore unless asked for: user data, and the factory reset
+/// protection, which would tie the device to the account of the backup.
+pub const DEFAULT_RESTORE_SKIP: &[&str] = &["userdata", "frp"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupStorage {
+    /// `emmc`, `ufs`, `nand` or `unknown`
+    pub kind: String,
+    pub block_size: u32,
+    pub total_size: u64,
+    pub user_size: u64,
+}
+
+impl From<&StorageInfo> for BackupStorage {
+    fn from(info: &StorageInfo) -> Self {
+        let kind = match info.kind {
+            StorageType::Emmc => "emmc",
+            StorageType::Ufs => "ufs",
+            StorageType::Nand => "nand",
+            StorageType::Unknown => "unknown",
+        };
+
+        Self {
+            kind: kind.to_string(),
+            block_size: info.block_size,
+            total_size: info.total_size,
+            user_size: info.user_size,
+        }
+    }
+}
+
+/// A partition of the table, with its dump if it was backed up.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupPartition {
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+    /// Region holding the partition (e.g. `EMMC_USER`, `UFS_LU2`)
+    pub region: String,
+    /// Dump file, relative to the manifest. `None` when the partition wasn't backed up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// SHA-256 of the raw partition data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl BackupPartition {
+    /// Compares `actual` with the recorded hash, failing with [`Error::HashMismatch`].
+    pub fn check_sha256(&self, actual: &str) -> Result<()> {
+        let expected = self.sha256.as_deref().unwrap_or_default();
+        if !expected.eq_ignore_ascii_case(actual) {
+            return Err(Error::HashMismatch {
+                file: self.file.clone().unwrap_or_else(|| self.name.clone()),
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Hashes the dump of the partition in `dir`, as a raw image, and checks it.
+    pub async fn verify(&self, dir: &Path) -> Result<()> {
+        let (Some(file), Some(_)) = (&self.file, &self.sha256) else {
+            return Err(Error::penumbra(format!("Partition '{}' wasn't backed up", self.name)));
+        };
+
+        let path = dir.join(file);
+        if !try_exists(&path).await? {
+            return Err(Error::penumbra(format!("Backup file '{}' is missing", path.display())));
+        }
+
+        self.check_sha256(&file_sha256(&path).await?)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub hw_code: u16,
+    /// Hex encoded, when the device reports one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soc_id: Option<String>,
+    pub storage: BackupStorage,
+    /// The whole partition table, in its order, backed up or not
+    pub partitions: Vec<BackupPartition>,
+}
+
+impl BackupManifest {
+    /// A manifest for a device with the given partition table, with nothing backed up yet.
+    pub fn new(
+        hw_code: u16,
+        soc_id: Option<&[u8]>,
+        storage: &StorageInfo,
+        partitions: &[Partition],
+    ) -> Self {
+        let partitions = partitions
+            .iter()
+            .map(|p| BackupPartition {
+                name: p.name.clone(),
+                address: p.address,
+                size: p.size as u64,
+                region: p.kind.as_str().to_string(),
+                file: None,
+                sha256: None,
+            })
+            .collect();
+
+        Self {
+            version: BACKUP_VERSION,
+            hw_code,
+            soc_id: soc_id.map(hex::encode),
+            storage: storage.into(),
+            partitions,
+        }
+    }
+
+    pub fn partition(&self, name: &str) -> Option<&BackupPartition> {
+        self.partitions.iter().find(|p| p.name == name)
+    }
+
+    /// Records the dump of partition `name`. Returns false if it isn't in the table.
+    pub fn record(&mut self, name: &str, file: &str, sha256: &str) -> bool {
+        let Some(part) = self.partitions.iter_mut().find(|p| p.name == name) else {
+            return false;
+        };
+        part.file = Some(file.to_string());
+        part.sha256 = Some(sha256.to_string());
+        true
+    }
+
+    /// Fails unless the backup comes from a device with the same hardware code.
+    pub fn check_device(&self, hw_code: u16) -> Result<()> {
+        if self.hw_code != hw_code {
+            return Err(Error::penumbra(format!(
+                "Backup is from a 0x{:04X} device, the connected one is 0x{:04X}",
+                self.hw_code, hw_code
+            )));
+        }
+        Ok(())
+    }
+
+    /// The backed up partitions to restore, leaving out `skip`, in table order with the
+    /// preloader last: a restore interrupted before it still leaves a bootable preloader.
+    pub fn restore_plan(&self, skip: &[&str]) -> Vec<&BackupPartition> {
+        let mut plan: Vec<_> = self
+            .partitions
+            .iter()
+            .filter(|p| p.file.is_some() && p.sha256.is_some())
+            .filter(|p| !skip.iter().any(|s| s.eq_ignore_ascii_case(&p.name)))
+            .collect();
+
+        // Stable, the rest keeps the table order
+        plan.sort_by_key(|p| is_pl_part(&p.name));
+        plan
+    }
+
+    /// Loads a manifest, usually [`BACKUP_MANIFEST_NAME`] in the backup directory.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let data = read(path).await?;
+        let manifest: Self = serde_json::from_slice(&data)
+            .map_err(|e| Error::malformed(format!("Invalid backup manifest: {}", e), &data))?;
+
+        if manifest.version > BACKUP_VERSION {
+            return Err(Error::unsupported(format!(
+                "Backup manifest version {} is newer than this version of penumbra",
+                manifest.version
+            )));
+        }
+
+        Ok(manifest)
+    }
+
+    /// Writes the manifest, replacing the file atomically.
+    /// Dump files are looked up relative to it.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|e| Error::penumbra(format!("Failed to encode backup manifest: {}", e)))?;
+
+        let tmp = path.with_extension("json.tmp");
+        write(&tmp, data).await?;
+        rename(&tmp, path).await?;
+        Ok(())
+    }
+}
+
+async fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(buf.get(..n).unwrap_or_default());
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// An empty directory under the system temp one, unique to the test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("penumbra-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn part(name: &str, file: Option<&str>, data: &[u8]) -> BackupPartition {
+        BackupPartition {
+            name: name.to_string(),
+            address: 0,
+            size: data.len() as u64,
+            region: "EMMC_USER".to_string(),
+            file: file.map(str::to_string),
+            sha256: file.map(|_| hex::encode(Sha256::digest(data))),
+        }
+    }
+
+    fn manifest(partitions: Vec<BackupPartition>) -> BackupManifest {
+        BackupManifest {
+            version: BACKUP_VERSION,
+            hw_code: 0x0766,
+            soc_id: None,
+            storage: BackupStorage {
+                kind: "emmc".to_string(),
+                block_size: 512,
+                total_size: 0x1000,
+                user_size: 0x1000,
+            },
+            partitions,
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_corrupted_hash() {
+        let dir = temp_dir("backup-corrupted");
+        let mut boot = part("boot", Some("boot.bin"), b"boot image");
+        boot.sha256 = Some(hex::encode(Sha256::digest(b"another image")));
+        let path = dir.join(BACKUP_MANIFEST_NAME);
+        manifest(vec![boot]).save(&path).await.unwrap();
+        std::fs::write(dir.join("boot.bin"), b"boot image").unwrap();
+
+        let loaded = BackupManifest::load(&path).await.unwrap();
+        let err = loaded.partitions[0].verify(&dir).await.unwrap_err();
+
+        assert!(matches!(err, Error::HashMismatch { ref file, .. } if file == "boot.bin"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn verify_missing_file() {
+        let dir = temp_dir("backup-missing");
+        let backup = manifest(vec![part("boot", Some("boot.bin"), b"boot image")]);
+
+        let err = backup.partitions[0].verify(&dir).await.unwrap_err();
+
+        assert!(matches!(err, Error::Penumbra(ref msg) if msg.contains("missing")));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn restore_plan_skips_and_orders() {
+        let backup = manifest(vec![
+            part("preloader", Some("preloader.bin"), b"pl"),
+            part("frp", Some("frp.bin"), b"frp"),
+            part("boot", Some("boot.bin"), b"boot"),
+            part("cache", None, b""),
+            part("userdata", Some("userdata.bin"), b"data"),
+        ]);
+
+        let plan: Vec<_> =
+            backup.restore_plan(DEFAULT_RESTORE_SKIP).iter().map(|p| p.name.as_str()).collect();
+
+        assert_eq!(plan, ["boot", "preloader"]);
+        assert!(backup.check_device(0x0766).is_ok());
+        assert!(backup.check_device(0x0813).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+
+/// Name of the partition holding the bootloader control block.
+pub const MISC_PARTITION: &str = "misc";
+/// Size of AOSP's `struct bootloader_message`, at the start of misc.
+pub const BCB_SIZE: usize = 2048;
+
+const COMMAND_OFFSET: usize = 0;
+const RECOVERY_OFFSET: usize = 64;
+
+/// Builds a bootloader message asking LK/the bootloader to boot into recovery.
+/// The bootloader clears it once recovery is done, so it only affects the next boot.
+pub fn recovery_message() -> [u8; BCB_SIZE] {
+    let mut bcb = [0u8; BCB_SIZE];
+
+    let command = b"boot-recovery";
+    let recovery = b"recovery\n";
+    for (offset, field) in [(COMMAND_OFFSET, &command[..]), (RECOVERY_OFFSET, &recovery[..])] {
+        if let Some(dest) = bcb.get_mut(offset..offset + field.len()) {
+            dest.copy_from_slice(field);
+        }
+    }
+    bcb
+}
@@ -0,0 +1,155 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::fmt;
+
+use log::debug;
+
+/// A window of the device address space that code can be loaded into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWindow {
+    pub name: &'static str,
+    pub start: u64,
+    /// First address past the window
+    pub end: u64,
+}
+
+impl MemoryWindow {
+    /// Whether `length` bytes loaded at `addr` fit entirely in the window.
+    pub fn contains(&self, addr: u32, length: u32) -> bool {
+        let start = addr as u64;
+        start >= self.start && start + length as u64 <= self.end
+    }
+}
+
+impl fmt::Display for MemoryWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} 0x{:08X}-0x{:08X}", self.name, self.start, self.end)
+    }
+}
+
+/// Known properties of a chipset, keyed by the hw_code reported by BROM.
+#[derive(Debug, Clone, Copy)]
+pub struct Chipset {
+    pub hw_code: u16,
+    /// Code of the chipset entry in V5 DA files, which use the commercial name
+    pub da_code: u16,
+    /// Where DA regions can be loaded
+    pub memory: &'static [MemoryWindow],
+}
+
+impl Chipset {
+    /// Commercial name of the chipset, e.g. `MT6768`.
+    pub fn name(&self) -> String {
+        format!("MT{:04X}", self.da_code)
+    }
+}
+
+/// BROM SRAM followed by the L2 cache used as SRAM, where DA1 runs.
+const SRAM: MemoryWindow = MemoryWindow { name: "SRAM", start: 0x0010_0000, end: 0x0030_0000 };
+/// DRAM, mapped from 0x40000000 up to the end of the 32 bit address space.
+const DRAM: MemoryWindow = MemoryWindow { name: "DRAM", start: 0x4000_0000, end: 0x1_0000_0000 };
+
+const MEMORY_MAP: &[MemoryWindow] = &[SRAM, DRAM];
+
+/// IO base of the SEJ block on most chipsets.
+pub const DEFAULT_SEJ_BASE: u32 = 0x1000_A000;
+
+/// `hw_code => da_code`
+macro_rules! chipsets {
+    ( $( $hw_code:literal => $da_code:literal ),+ $(,)? ) => {
+        &[ $( Chipset { hw_code: $hw_code, da_code: $da_code, memory: MEMORY_MAP } ),+ ]
+    };
+}
+
+const CHIPSETS: &[Chipset] = chipsets! {
+    0x279 => 0x6797,
+    0x321 => 0x6735,
+    0x326 => 0x6755,
+    0x335 => 0x6735,
+    0x337 => 0x6735,
+    0x507 => 0x6758,
+    0x551 => 0x6757,
+    0x562 => 0x6799,
+    0x601 => 0x6755,
+    0x633 => 0x6570,
+    0x688 => 0x6758,
+    0x690 => 0x6763,
+    0x699 => 0x6739,
+    0x707 => 0x6768,
+    0x717 => 0x6761,
+    0x725 => 0x6779,
+    0x766 => 0x6765,
+    0x788 => 0x6771,
+    0x813 => 0x6785,
+    0x816 => 0x6885,
+    0x886 => 0x6873,
+    0x908 => 0x8696,
+    0x930 => 0x8195,
+    0x950 => 0x6893,
+    0x959 => 0x6877,
+    0x989 => 0x6833,
+    0x996 => 0x6853,
+    0x1066 => 0x6781,
+    0x6583 => 0x6589,
+    0x8172 => 0x8173,
+    0x8176 => 0x8173,
+};
+
+/// Returns what is known about the chipset with the given hw_code, if anything.
+pub fn chipset(hw_code: u16) -> Option<&'static Chipset> {
+    CHIPSETS.iter().find(|c| c.hw_code == hw_code)
+}
+
+/// Returns the chipsets using the DA entry with the given code.
+pub fn chipsets_for_da_code(da_code: u16) -> Vec<&'static Chipset> {
+    CHIPSETS.iter().filter(|c| c.da_code == da_code).collect()
+}
+
+/// Returns the SEJ base to use on `hw_code`: the `requested` one, then the one `detected`
+/// in the DA, and [`DEFAULT_SEJ_BASE`] last.
+///
+/// The chipset table has no SEJ bases yet: none of the chipsets said to have their SEJ
+/// block elsewhere (MT6893, MT6877, MT8195) has a verified base. On XFlash, where nothing
+/// is detected in the DA, those need `--sej-base` until they're known.
+pub fn sej_base(hw_code: u16, requested: Option<u32>, detected: Option<u32>) -> u32 {
+    let (base, source) = match (requested, detected) {
+        (Some(base), _) => (base, "requested"),
+        (None, Some(base)) => (base, "detected in the DA"),
+        (None, None) => (DEFAULT_SEJ_BASE, "default"),
+    };
+
+    debug!("SEJ base of 0x{:04X}: 0x{:08X} ({})", hw_code, base, source);
+    base
+}
+
+/// Returns the code of the DA entry for `hw_code`.
+/// Chipsets missing from the table are assumed to use their hw_code, as XML DAs do.
+pub fn da_code(hw_code: u16) -> u16 {
+    chipset(hw_code).map_or(hw_code, |c| c.da_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MT6893: u16 = 0x950;
+
+    #[test]
+    fn requested_sej_base_wins() {
+        assert_eq!(sej_base(MT6893, Some(0x1000_B000), Some(0x1000_C000)), 0x1000_B000);
+        assert_eq!(sej_base(MT6893, Some(0x1000_B000), None), 0x1000_B000);
+    }
+
+    #[test]
+    fn detected_sej_base_is_used_next() {
+        assert_eq!(sej_base(MT6893, None, Some(0x1000_C000)), 0x1000_C000);
+    }
+
+    #[test]
+    fn default_sej_base_is_the_last_resort() {
+        assert_eq!(sej_base(MT6893, None, None), DEFAULT_SEJ_BASE);
+        assert_eq!(sej_base(0xFFFF, None, None), DEFAULT_SEJ_BASE);
+    }
+}
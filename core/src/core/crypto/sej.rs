@@ -19,6 +19,7 @@ use cipher::block_padding::Pkcs7;
 use cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 
 use crate::core::crypto::config::CryptoConfig;
+use crate::error::{Error, Result};
 
 #[repr(u32)]
 #[derive(Copy, Clone, Debug)]
@@ -83,6 +84,12 @@ pub const SEJ_AES_START: u32 = 0x00000001;
 pub const SEJ_AES_CLR: u32 = 0x00000002;
 pub const SEJ_AES_RDY: u32 = 0x00008000;
 
+/// Whether ACON, read back after a hardware SEJ operation, holds the CBC mode the operation
+/// left there. A base without a SEJ block behind it reads back as all ones, or as zero.
+pub fn acon_is_valid(acon: u32) -> bool {
+    acon != 0xFFFFFFFF && acon & SEJ_AES_MODE_MASK == SEJ_AES_MODE_CBC
+}
+
 pub const SEJ_AES_BK2C: u32 = 0x00000010;
 pub const SEJ_AES_R2K: u32 = 0x00000100;
 
@@ -133,28 +140,33 @@ impl<'a> SEJCrypto<'a> {
 
     // Note: This modifies the data directly, it does not return a new Vec
     fn xor(&self, data: &mut [u8]) {
-        for (i, &pad) in HACC_CFG_1.iter().enumerate().take(4) {
-            let offset = i * 4;
-            let orig = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
-            data[offset..offset + 4].copy_from_slice(&(orig ^ pad).to_le_bytes());
+        for (word, &pad) in data.chunks_exact_mut(4).zip(HACC_CFG_1.iter()).take(4) {
+            if let Ok(bytes) = <[u8; 4]>::try_from(&*word) {
+                word.copy_from_slice(&(u32::from_le_bytes(bytes) ^ pad).to_le_bytes());
+            }
         }
     }
 
     // Software based AES128 CBC.
-    pub fn sej_seccfg_sw(&mut self, data: &[u8], encrypt: bool) -> Vec<u8> {
+    pub fn sej_seccfg_sw(&mut self, data: &[u8], encrypt: bool) -> Result<Vec<u8>> {
         let mut buf = data.to_vec();
         let buf_len = buf.len();
         if encrypt {
             let cipher = Encryptor::<Aes128>::new_from_slices(DEFAULT_KEY, DEFAULT_IV)
-                .expect("Invalid key/IV");
-            cipher.encrypt_padded_mut::<Pkcs7>(&mut buf, buf_len).expect("Encrypt failed").to_vec()
+                .map_err(|_| Error::penumbra("Invalid SEJ key/IV"))?;
+            // Room for the padding block, PKCS7 always adds at least one byte
+            buf.resize(buf_len + 16, 0);
+            let encrypted = cipher
+                .encrypt_padded_mut::<Pkcs7>(&mut buf, buf_len)
+                .map_err(|_| Error::penumbra("SEJ software encryption failed"))?;
+            Ok(encrypted.to_vec())
         } else {
             let cipher = Decryptor::<Aes128>::new_from_slices(DEFAULT_KEY, DEFAULT_IV)
-                .expect("Invalid key/IV");
-            match cipher.decrypt_padded_mut::<Pkcs7>(&mut buf) {
+                .map_err(|_| Error::penumbra("Invalid SEJ key/IV"))?;
+            Ok(match cipher.decrypt_padded_mut::<Pkcs7>(&mut buf) {
                 Ok(decrypted) => decrypted.to_vec(),
                 Err(_) => buf,
-            }
+            })
         }
     }
 
@@ -191,13 +203,12 @@ impl<'a> SEJCrypto<'a> {
     }
 
     async fn sej_run(&mut self, data: &[u8]) -> Vec<u8> {
-        let num_blocks = data.len() / 16; // I'm using u8, mtkclient uses u32
+        // I'm using u8, mtkclient uses u32. Trailing bytes that don't fill a block are dropped
         let mut output = Vec::with_capacity(data.len());
 
-        for block in 0..num_blocks {
-            for word in 0..4 {
-                let offset = block * 16 + word * 4;
-                let val = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        for block in data.chunks_exact(16) {
+            for (word, bytes) in block.chunks_exact(4).enumerate() {
+                let val = bytes.try_into().map(u32::from_le_bytes).unwrap_or_default();
                 self.wreg(
                     match word {
                         0 => SejReg::ASRC0,
@@ -291,12 +302,11 @@ impl<'a> SEJCrypto<'a> {
         } else {
             self.wreg(SejReg::UNK, 1).await;
 
-            for i in 0..3 {
-                let pos = i * 4;
-                self.wreg(SejReg::ASRC0, G_CFG_RANDOM_PATTERN[pos]).await;
-                self.wreg(SejReg::ASRC1, G_CFG_RANDOM_PATTERN[pos + 1]).await;
-                self.wreg(SejReg::ASRC2, G_CFG_RANDOM_PATTERN[pos + 2]).await;
-                self.wreg(SejReg::ASRC3, G_CFG_RANDOM_PATTERN[pos + 3]).await;
+            for pattern in G_CFG_RANDOM_PATTERN.chunks_exact(4) {
+                let regs = [SejReg::ASRC0, SejReg::ASRC1, SejReg::ASRC2, SejReg::ASRC3];
+                for (reg, &val) in regs.into_iter().zip(pattern) {
+                    self.wreg(reg, val).await;
+                }
                 self.wreg(SejReg::ACON2, SEJ_AES_START).await;
                 for _ in 0..20 {
                     if self.rreg(SejReg::ACON2).await & SEJ_AES_RDY != 0 {
@@ -307,10 +317,10 @@ impl<'a> SEJCrypto<'a> {
 
             self.wreg(SejReg::ACON2, SEJ_AES_CLR).await;
 
-            self.wreg(SejReg::ACFG0, iv[0]).await;
-            self.wreg(SejReg::ACFG1, iv[1]).await;
-            self.wreg(SejReg::ACFG2, iv[2]).await;
-            self.wreg(SejReg::ACFG3, iv[3]).await;
+            let regs = [SejReg::ACFG0, SejReg::ACFG1, SejReg::ACFG2, SejReg::ACFG3];
+            for (reg, &val) in regs.into_iter().zip(iv) {
+                self.wreg(reg, val).await;
+            }
 
             self.wreg(SejReg::ACON, acon_settings).await;
             self.wreg(SejReg::ACONK, 0).await;
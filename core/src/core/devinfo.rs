@@ -19,12 +19,20 @@ pub struct DeviceInfo {
 #[derive(Clone, Default)]
 pub struct DevInfoData {
     pub chipset: String,
-    pub soc_id: Vec<u8>,
-    pub meid: Vec<u8>,
+    /// SoC ID, `None` if the device doesn't support querying it
+    pub soc_id: Option<Vec<u8>>,
+    /// MEID, `None` if the device doesn't support querying it
+    pub meid: Option<Vec<u8>>,
     pub hw_code: u16,
     pub partitions: Vec<Partition>,
     pub storage: Option<Arc<dyn Storage + Send + Sync>>,
     pub target_config: u32,
+    /// Raw primary GPT, as read from the device
+    pub raw_pgpt: Option<Vec<u8>>,
+    /// Raw backup GPT, only available if it was read
+    pub raw_sgpt: Option<Vec<u8>>,
+    /// Per-boot random ID of the DA session, if the DA supports it
+    pub random_id: Option<Vec<u8>>,
 }
 
 impl DeviceInfo {
@@ -49,11 +57,11 @@ impl DeviceInfo {
         self.inner().read().await.chipset.clone()
     }
 
-    pub async fn soc_id(&self) -> Vec<u8> {
+    pub async fn soc_id(&self) -> Option<Vec<u8>> {
         self.inner().read().await.soc_id.clone()
     }
 
-    pub async fn meid(&self) -> Vec<u8> {
+    pub async fn meid(&self) -> Option<Vec<u8>> {
         self.inner().read().await.meid.clone()
     }
 
@@ -84,6 +92,33 @@ impl DeviceInfo {
         write_guard.partitions = partitions;
     }
 
+    pub async fn raw_pgpt(&self) -> Option<Vec<u8>> {
+        self.inner().read().await.raw_pgpt.clone()
+    }
+
+    pub async fn set_raw_pgpt(&self, data: Vec<u8>) {
+        let mut write_guard = self.inner().write().await;
+        write_guard.raw_pgpt = Some(data);
+    }
+
+    pub async fn raw_sgpt(&self) -> Option<Vec<u8>> {
+        self.inner().read().await.raw_sgpt.clone()
+    }
+
+    pub async fn set_raw_sgpt(&self, data: Vec<u8>) {
+        let mut write_guard = self.inner().write().await;
+        write_guard.raw_sgpt = Some(data);
+    }
+
+    pub async fn random_id(&self) -> Option<Vec<u8>> {
+        self.inner().read().await.random_id.clone()
+    }
+
+    pub async fn set_random_id(&self, random_id: Vec<u8>) {
+        let mut write_guard = self.inner().write().await;
+        write_guard.random_id = Some(random_id);
+    }
+
     pub async fn target_config(&self) -> u32 {
         self.inner().read().await.target_config
     }
@@ -0,0 +1,71 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+
+/// Number of 32-bit eFuse words read from the DA.
+pub const EFUSE_WORDS: usize = 0x100;
+
+/// Word holding the security control bits, on most chipsets.
+const SEC_CTRL_INDEX: usize = 0x18;
+/// Secure boot (SBC) enable bit in the security control word.
+const SBC_EN: u32 = 1 << 1;
+/// First word of the SHA-256 of the SBC public key, on most chipsets.
+const SBC_PUBK_HASH_INDEX: usize = 0x24;
+const SBC_PUBK_HASH_WORDS: usize = 8;
+
+/// eFuse words as read from the DA, keyed by their index.
+///
+/// The layout of the eFuses depends on the chipset: the helpers only cover the
+/// fuses sitting at the same place on most of them, and should be double-checked
+/// against the chipset documentation before relying on them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EfuseMap {
+    words: Vec<u32>,
+}
+
+impl EfuseMap {
+    /// Parses the little-endian eFuse words returned by the DA. Trailing bytes not
+    /// making a whole word are ignored.
+    pub fn parse(data: &[u8]) -> Self {
+        let (words, _) = data.as_chunks::<4>();
+        let words = words.iter().map(|&w| u32::from_le_bytes(w)).collect();
+        EfuseMap { words }
+    }
+
+    /// The eFuse word at `index`, if it was read.
+    pub fn get(&self, index: usize) -> Option<u32> {
+        self.words.get(index).copied()
+    }
+
+    /// All eFuse words, in index order.
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    /// The eFuses as raw little-endian bytes, like the DA returned them.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    /// Whether secure boot (SBC) is enabled.
+    pub fn secure_boot_enabled(&self) -> Option<bool> {
+        self.get(SEC_CTRL_INDEX).map(|ctrl| ctrl & SBC_EN != 0)
+    }
+
+    /// The SHA-256 of the public key BROM checks the preloader against, or `None`
+    /// when it wasn't read or was never fused.
+    pub fn sbc_pubk_hash(&self) -> Option<[u8; 32]> {
+        let words =
+            self.words.get(SBC_PUBK_HASH_INDEX..SBC_PUBK_HASH_INDEX + SBC_PUBK_HASH_WORDS)?;
+        if words.iter().all(|&w| w == 0) {
+            return None;
+        }
+
+        let mut hash = [0u8; 32];
+        for (dst, word) in hash.chunks_exact_mut(4).zip(words) {
+            dst.copy_from_slice(&word.to_le_bytes());
+        }
+        Some(hash)
+    }
+}
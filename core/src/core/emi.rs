@@ -2,31 +2,104 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
-use log::error;
-
+use crate::error::{Error, Result};
+use crate::le_u32;
 use crate::utilities::patching::{HEX_NOT_FOUND, find_pattern};
 
 const FILE_INFO_EMI: &str = "4D4D4D0138000000";
+/// Start of the EMI settings block, followed by the version of its layout.
+const BLOADER_INFO_MAGIC: &[u8] = b"MTK_BLOADER_INFO_v";
 
-pub fn extract_emi_settings(preloader: &[u8]) -> Option<Vec<u8>> {
+/// Extracts the EMI settings (the `MTK_BLOADER_INFO` block) from a preloader image.
+///
+/// When booted from BROM, DRAM isn't initialized yet: DA1 needs these settings,
+/// normally applied by the preloader, before it can use it.
+pub fn parse_emi(preloader: &[u8]) -> Result<Vec<u8>> {
     let header_off = find_pattern(preloader, FILE_INFO_EMI, 0);
     if header_off == HEX_NOT_FOUND {
-        error!("Failed to extract EMI: EMI header not found.");
-        return None;
+        return Err(Error::penumbra(
+            "No EMI settings in the preloader: file info header not found",
+        ));
     }
 
-    let mut data = &preloader[header_off..];
+    let mut data = preloader.get(header_off..).unwrap_or_default();
 
-    let mlen = u32::from_le_bytes(data[0x20..0x24].try_into().ok()?) as usize;
-    let siglen = u32::from_le_bytes(data[0x2C..0x30].try_into().ok()?) as usize;
-    data = &data[..mlen - siglen];
+    let mlen = le_u32!(data, 0x20)? as usize;
+    let siglen = le_u32!(data, 0x2C)? as usize;
+    data = mlen
+        .checked_sub(siglen)
+        .and_then(|len| data.get(..len))
+        .ok_or_else(|| Error::malformed("Preloader file info lengths", data))?;
 
-    let mut dramsize = u32::from_le_bytes(data[data.len() - 4..].try_into().ok()?) as usize;
-    if dramsize == 0 && data.len() >= 0x804 {
-        data = &data[..data.len() - 0x800];
-        dramsize = u32::from_le_bytes(data[data.len() - 4..].try_into().ok()?) as usize;
+    let mut dramsize = le_u32!(data, data.len().saturating_sub(4))? as usize;
+    if dramsize == 0
+        && let Some((head, _)) = data.split_at_checked(data.len().saturating_sub(0x800))
+        && head.len() >= 4
+    {
+        data = head;
+        dramsize = le_u32!(data, data.len() - 4)? as usize;
     }
-    data = &data[data.len() - dramsize - 4..data.len() - 4];
+    let end = data.len() - 4;
+    data = end.checked_sub(dramsize).and_then(|start| data.get(start..end)).ok_or_else(|| {
+        Error::penumbra(format!(
+            "EMI settings of 0x{:X} bytes don't fit in the preloader",
+            dramsize
+        ))
+    })?;
+
+    let Some(start) = data.windows(BLOADER_INFO_MAGIC.len()).position(|w| w == BLOADER_INFO_MAGIC)
+    else {
+        return Err(Error::penumbra("No MTK_BLOADER_INFO block in the preloader EMI settings"));
+    };
+
+    Ok(data.get(start..).unwrap_or_default().to_vec())
+}
+
+/// Layout version of EMI settings extracted by [`parse_emi`],
+/// e.g. 16 for a `MTK_BLOADER_INFO_v16` block.
+pub fn emi_version(emi: &[u8]) -> Option<u32> {
+    let digits = emi.strip_prefix(BLOADER_INFO_MAGIC)?;
+    let digits = digits.split(|b| !b.is_ascii_digit()).next()?;
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+#[deprecated(note = "use `parse_emi`, which says why the preloader can't be used")]
+pub fn extract_emi_settings(preloader: &[u8]) -> Option<Vec<u8>> {
+    parse_emi(preloader).ok()
+}
 
-    Some(data[..].to_vec())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A preloader holding `emi` as its EMI settings, after some code.
+    fn preloader(emi: &[u8]) -> Vec<u8> {
+        let mut body = vec![0u8; 0x30];
+        body[..8].copy_from_slice(&[0x4D, 0x4D, 0x4D, 0x01, 0x38, 0x00, 0x00, 0x00]);
+        body.extend_from_slice(&[0xAA; 0x40]);
+        body.extend_from_slice(emi);
+        body.extend_from_slice(&(emi.len() as u32).to_le_bytes());
+        let mlen = body.len() as u32;
+        body[0x20..0x24].copy_from_slice(&mlen.to_le_bytes());
+
+        [vec![0x55; 0x100], body].concat()
+    }
+
+    #[test]
+    fn emi_block_found() {
+        let emi = [BLOADER_INFO_MAGIC, b"16\0\0", &[0x11; 0x20]].concat();
+
+        assert_eq!(parse_emi(&preloader(&emi)).unwrap(), emi);
+        assert_eq!(emi_version(&emi), Some(16));
+    }
+
+    #[test]
+    fn emi_errors_instead_of_panicking() {
+        assert!(parse_emi(&[0x55; 0x100]).is_err());
+        assert!(parse_emi(&preloader(&[0x11; 0x20])).is_err());
+
+        let mut truncated = preloader(&[BLOADER_INFO_MAGIC, &[0x11; 0x20]].concat());
+        truncated.truncate(0x120);
+        assert!(parse_emi(&truncated).is_err());
+    }
 }
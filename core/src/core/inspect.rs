@@ -0,0 +1,152 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::fmt;
+
+use crate::core::avb::VBMETA_MAGIC;
+
+/// Bytes needed from the start of a partition to classify its content.
+/// Filesystem superblocks all live within the first 4 KB.
+pub const PROBE_SIZE: usize = 4096;
+
+/// Offset of the ext4, f2fs and erofs superblocks.
+const SUPERBLOCK_OFFSET: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    Ext4,
+    F2fs,
+    Erofs,
+    Squashfs,
+    /// Android boot image (`boot`, `recovery`, `init_boot`)
+    BootImage,
+    /// Android vendor boot image
+    VendorBootImage,
+    /// AVB vbmeta image
+    Vbmeta,
+    /// Only zeros or only 0xFF (erased flash)
+    Empty,
+    Unknown,
+}
+
+/// Magics checked in order: the offset within the probe and the bytes expected there.
+const MAGICS: &[(usize, &[u8], ContentKind)] = &[
+    (0, b"ANDROID!", ContentKind::BootImage),
+    (0, b"VNDRBOOT", ContentKind::VendorBootImage),
+    (0, VBMETA_MAGIC, ContentKind::Vbmeta),
+    (0, b"hsqs", ContentKind::Squashfs),
+    (SUPERBLOCK_OFFSET, &0xF2F5_2010u32.to_le_bytes(), ContentKind::F2fs),
+    (SUPERBLOCK_OFFSET, &0xE0F5_E1E2u32.to_le_bytes(), ContentKind::Erofs),
+    (SUPERBLOCK_OFFSET + 0x38, &0xEF53u16.to_le_bytes(), ContentKind::Ext4),
+];
+
+/// Classifies the content of a partition from its first bytes, ideally [`PROBE_SIZE`] of them.
+/// Shorter data only detects the formats whose magic fits in it.
+pub fn detect_content(data: &[u8]) -> ContentKind {
+    if data.is_empty() {
+        return ContentKind::Unknown;
+    }
+
+    for &(offset, magic, kind) in MAGICS {
+        if data.get(offset..offset + magic.len()) == Some(magic) {
+            return kind;
+        }
+    }
+
+    if data.iter().all(|&b| b == 0x00) || data.iter().all(|&b| b == 0xFF) {
+        return ContentKind::Empty;
+    }
+
+    ContentKind::Unknown
+}
+
+impl ContentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentKind::Ext4 => "ext4",
+            ContentKind::F2fs => "f2fs",
+            ContentKind::Erofs => "erofs",
+            ContentKind::Squashfs => "squashfs",
+            ContentKind::BootImage => "boot image",
+            ContentKind::VendorBootImage => "vendor boot image",
+            ContentKind::Vbmeta => "vbmeta",
+            ContentKind::Empty => "empty",
+            ContentKind::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A zeroed probe with `magic` at `offset`.
+    fn probe(offset: usize, magic: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; PROBE_SIZE];
+        data[offset..offset + magic.len()].copy_from_slice(magic);
+        data
+    }
+
+    #[test]
+    fn formats_are_detected() {
+        let cases: &[(&str, Vec<u8>, ContentKind)] = &[
+            ("boot", probe(0, b"ANDROID!"), ContentKind::BootImage),
+            ("vendor_boot", probe(0, b"VNDRBOOT"), ContentKind::VendorBootImage),
+            ("vbmeta", probe(0, b"AVB0"), ContentKind::Vbmeta),
+            ("squashfs", probe(0, b"hsqs"), ContentKind::Squashfs),
+            ("f2fs", probe(0x400, &[0x10, 0x20, 0xF5, 0xF2]), ContentKind::F2fs),
+            ("erofs", probe(0x400, &[0xE2, 0xE1, 0xF5, 0xE0]), ContentKind::Erofs),
+            ("ext4", probe(0x438, &[0x53, 0xEF]), ContentKind::Ext4),
+            ("zeros", vec![0x00; PROBE_SIZE], ContentKind::Empty),
+            ("erased", vec![0xFF; PROBE_SIZE], ContentKind::Empty),
+            ("preloader", probe(0, b"MMM\x01\x38"), ContentKind::Unknown),
+            ("nothing", Vec::new(), ContentKind::Unknown),
+        ];
+
+        for (name, data, kind) in cases {
+            assert_eq!(detect_content(data), *kind, "{}", name);
+        }
+    }
+
+    #[test]
+    fn short_probes_only_see_leading_magics() {
+        let cases: &[(&str, Vec<u8>, ContentKind)] = &[
+            ("boot", probe(0, b"ANDROID!")[..8].to_vec(), ContentKind::BootImage),
+            ("ext4 cut before", probe(0x438, &[0x53, 0xEF])[..0x438].to_vec(), ContentKind::Empty),
+            (
+                "ext4 cut within",
+                probe(0x438, &[0x53, 0xEF])[..0x439].to_vec(),
+                ContentKind::Unknown,
+            ),
+            ("ext4 just fits", probe(0x438, &[0x53, 0xEF])[..0x43A].to_vec(), ContentKind::Ext4),
+            (
+                "f2fs cut",
+                probe(0x400, &[0x10, 0x20, 0xF5, 0xF2])[..0x402].to_vec(),
+                ContentKind::Unknown,
+            ),
+        ];
+
+        for (name, data, kind) in cases {
+            assert_eq!(detect_content(data), *kind, "{}", name);
+        }
+    }
+
+    #[test]
+    fn leading_magics_win() {
+        // A boot image whose payload happens to hold a superblock magic
+        let mut data = probe(0x438, &[0x53, 0xEF]);
+        data[..8].copy_from_slice(b"ANDROID!");
+        assert_eq!(detect_content(&data), ContentKind::BootImage);
+
+        // Magics elsewhere than at their offset don't count
+        assert_eq!(detect_content(&probe(0x200, b"ANDROID!")), ContentKind::Unknown);
+        assert_eq!(detect_content(&probe(0, &[0x53, 0xEF])), ContentKind::Unknown);
+    }
+}
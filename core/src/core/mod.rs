@@ -3,8 +3,18 @@
     SPDX-FileCopyrightText: 2025 Shomy
 */
 pub mod auth;
+pub mod avb;
+pub mod backup;
+pub mod bcb;
+pub mod chipset;
 pub mod crypto;
 pub mod devinfo;
+pub mod efuse;
 pub mod emi;
+pub mod inspect;
+pub mod preloader;
+pub mod progress;
+pub mod scatter;
 pub mod seccfg;
 pub mod storage;
+pub mod summary;
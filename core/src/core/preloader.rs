@@ -0,0 +1,137 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use crate::core::storage::{Partition, Storage};
+use crate::error::{Error, Result};
+use crate::le_u32;
+
+/// Headers of full boot partition images, which are written as they are.
+const BOOT_IMAGE_MAGICS: [&[u8]; 2] = [b"EMMC_BOOT", b"UFS_BOOT"];
+/// Start of the FILE_INFO header of a bare preloader, as found in firmware packages:
+/// `MMM`, version 1, header size 0x38 and type 0 (FILE_INFO).
+const FILE_INFO_HEADER: &[u8] = b"MMM\x01\x38\x00\x00\x00";
+/// Offset of the preloader length in its FILE_INFO header.
+const FILE_INFO_LENGTH: usize = 0x20;
+
+/// Checks that `data` can be written to the boot partitions, returning whether it's
+/// a full boot image (with an `EMMC_BOOT`/`UFS_BOOT` header) rather than a bare preloader.
+/// Anything else is refused, as writing garbage to the boot partitions leaves the device
+/// unable to boot.
+pub fn is_boot_image(data: &[u8]) -> Result<bool> {
+    if BOOT_IMAGE_MAGICS.iter().any(|magic| data.starts_with(magic)) {
+        // A boot header alone, with no preloader after it, is as good as garbage
+        extract_preloader(data)?;
+        Ok(true)
+    } else if data.starts_with(FILE_INFO_HEADER) {
+        Ok(false)
+    } else {
+        Err(Error::penumbra("Not a preloader image: no boot header or preloader magic found"))
+    }
+}
+
+/// Returns the offset within the boot partition at which `data` must be written,
+/// `current` being the image on the device.
+///
+/// Full boot images carry their own header and start at 0, while a bare preloader
+/// replaces the one on the device, wherever it is: after the boot header on eMMC,
+/// or at 0 on UFS boot LUs without one.
+pub fn preloader_offset(data: &[u8], current: &[u8]) -> Result<u64> {
+    if is_boot_image(data)? {
+        return Ok(0);
+    }
+
+    find_file_info(current).map(|start| start as u64).ok_or_else(|| {
+        Error::penumbra(
+            "No preloader on the device to take the place of, write a full boot image instead",
+        )
+    })
+}
+
+/// Position of the FILE_INFO header starting the preloader.
+fn find_file_info(data: &[u8]) -> Option<usize> {
+    data.windows(FILE_INFO_HEADER.len()).position(|window| window == FILE_INFO_HEADER)
+}
+
+/// Returns the bare preloader within a boot partition image, without the boot header.
+///
+/// The preloader is found by its FILE_INFO header, so images without a boot header
+/// (e.g. on some UFS boot LUs) work as well. Its length comes from the same header.
+pub fn extract_preloader(data: &[u8]) -> Result<&[u8]> {
+    let start = find_file_info(data)
+        .ok_or_else(|| Error::penumbra("No preloader found: missing FILE_INFO header"))?;
+
+    let length = le_u32!(data, start + FILE_INFO_LENGTH)? as usize;
+    data.get(start..start + length).filter(|_| length >= FILE_INFO_HEADER.len()).ok_or_else(|| {
+        Error::penumbra(format!(
+            "Invalid preloader length 0x{:X} at 0x{:X} (image is 0x{:X} bytes)",
+            length,
+            start,
+            data.len()
+        ))
+    })
+}
+
+/// Returns the `preloader` and `preloader_backup` pseudo-partitions of `storage`,
+/// which live in the boot partitions and are not part of the GPT.
+pub fn preloader_partitions(storage: &dyn Storage) -> [Partition; 2] {
+    [
+        Partition::new("preloader", storage.get_pl1_size() as usize, 0, storage.get_pl_part1()),
+        Partition::new(
+            "preloader_backup",
+            storage.get_pl2_size() as usize,
+            0,
+            storage.get_pl_part2(),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bare preloader of `len` bytes, as found in firmware packages.
+    fn bare(len: usize) -> Vec<u8> {
+        let mut data = vec![0xAA; len];
+        data[..FILE_INFO_HEADER.len()].copy_from_slice(FILE_INFO_HEADER);
+        data[FILE_INFO_LENGTH..FILE_INFO_LENGTH + 4].copy_from_slice(&(len as u32).to_le_bytes());
+        data
+    }
+
+    /// A boot partition holding `preloader` at `offset`, behind an eMMC boot header if any.
+    fn boot_partition(preloader: &[u8], offset: usize) -> Vec<u8> {
+        let mut image = vec![0u8; offset];
+        if offset > 0 {
+            image[..9].copy_from_slice(b"EMMC_BOOT");
+        }
+        image.extend_from_slice(preloader);
+        image.resize(0x4000, 0);
+        image
+    }
+
+    #[test]
+    fn bare_preloader_replaces_the_one_on_the_device() {
+        let new = bare(0x200);
+
+        assert_eq!(preloader_offset(&new, &boot_partition(&bare(0x400), 0x800)).unwrap(), 0x800);
+        // UFS boot LUs without a boot header
+        assert_eq!(preloader_offset(&new, &boot_partition(&bare(0x400), 0)).unwrap(), 0);
+        assert!(preloader_offset(&new, &[0u8; 0x4000]).is_err());
+    }
+
+    #[test]
+    fn boot_image_written_from_the_start() {
+        let image = boot_partition(&bare(0x200), 0x1000);
+
+        assert!(is_boot_image(&image).unwrap());
+        assert_eq!(preloader_offset(&image, &[0u8; 0x4000]).unwrap(), 0);
+        assert_eq!(extract_preloader(&image).unwrap(), bare(0x200));
+    }
+
+    #[test]
+    fn garbage_refused() {
+        assert!(is_boot_image(&[0x55; 0x200]).is_err());
+        // A boot header with no preloader after it
+        assert!(is_boot_image(&boot_partition(&[], 0x1000)).is_err());
+    }
+}
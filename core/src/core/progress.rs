@@ -0,0 +1,80 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::time::{Duration, Instant};
+
+use crate::core::summary::OperationKind;
+
+/// A progress update of a flash or memory operation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressEvent {
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// Time since the operation started
+    pub elapsed: Duration,
+    /// What the operation is doing, `Verify` while reading back written data
+    pub phase: OperationKind,
+}
+
+impl ProgressEvent {
+    /// Average throughput so far, in bytes per second.
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { self.bytes_done as f64 / secs } else { 0.0 }
+    }
+
+    /// Estimated time left at the average throughput, `None` until something was transferred.
+    pub fn eta(&self) -> Option<Duration> {
+        let throughput = self.throughput();
+        if throughput <= 0.0 {
+            return None;
+        }
+
+        let left = self.bytes_total.saturating_sub(self.bytes_done) as f64;
+        Some(Duration::from_secs_f64(left / throughput))
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.bytes_done >= self.bytes_total
+    }
+}
+
+/// Adapts a `(done, total)` closure to the [`ProgressEvent`] callbacks.
+///
+/// ```no_run
+/// # use penumbra::{Device, progress_fn};
+/// # async fn example(device: &mut Device) -> penumbra::Result<()> {
+/// let mut progress = progress_fn(|erased, total| println!("{erased}/{total}"));
+/// device.erase_partition("userdata", &mut progress).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn progress_fn<F>(mut f: F) -> impl FnMut(ProgressEvent)
+where
+    F: FnMut(usize, usize),
+{
+    move |event| f(event.bytes_done as usize, event.bytes_total as usize)
+}
+
+/// Turns the byte counts of a transfer loop into events, timed from its creation.
+pub(crate) struct ProgressReporter<'a> {
+    phase: OperationKind,
+    start: Instant,
+    callback: &'a mut (dyn FnMut(ProgressEvent) + Send),
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub fn new(phase: OperationKind, callback: &'a mut (dyn FnMut(ProgressEvent) + Send)) -> Self {
+        ProgressReporter { phase, start: Instant::now(), callback }
+    }
+
+    pub fn report(&mut self, done: usize, total: usize) {
+        (self.callback)(ProgressEvent {
+            bytes_done: done as u64,
+            bytes_total: total as u64,
+            elapsed: self.start.elapsed(),
+            phase: self.phase,
+        });
+    }
+}
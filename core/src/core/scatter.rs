@@ -0,0 +1,164 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Scatter files (`MT*_Android_scatter.txt`), the layout shipped with SP Flash Tool firmware.
+//!
+//! Two formats are around:
+//! * The old line format of the first chipsets, a partition name and its start address per line,
+//!   each followed by an empty `{ }` block. It lists no image files.
+//! * The YAML-like format (V1.1.x and V2), a `- key: value` block per partition, with its image
+//!   file, region and whether SP Flash Tool downloads it. V2 adds a `general` block and a few keys,
+//!   which are ignored.
+use crate::error::{Error, Result};
+
+/// Value scatter files use for a missing file or region.
+const NONE_VALUE: &str = "NONE";
+/// Prefix the old format puts in front of partitions that aren't downloaded.
+const NODL_PREFIX: &str = "__NODL_";
+
+/// A partition of a scatter file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScatterEntry {
+    pub partition_name: String,
+    /// The image to flash, relative to the scatter file. `None` for partitions without one.
+    pub file_name: Option<String>,
+    pub physical_start_addr: u64,
+    /// Size of the partition, when the scatter file gives it.
+    pub partition_size: Option<u64>,
+    /// Region of the storage holding the partition (e.g. `EMMC_USER`, `EMMC_BOOT1`, `UFS_LU2`),
+    /// `None` when not given.
+    pub region: Option<String>,
+    /// Whether SP Flash Tool downloads the partition.
+    pub is_download: bool,
+}
+
+impl ScatterEntry {
+    /// Whether the partition is meant to be flashed: it is downloaded and has an image.
+    pub fn is_flashable(&self) -> bool {
+        self.is_download && self.file_name.is_some()
+    }
+}
+
+/// Parses a scatter file, in either format.
+pub fn parse_scatter(text: &str) -> Result<Vec<ScatterEntry>> {
+    let has_blocks = text.lines().any(|l| strip_comment(l).contains("partition_name:"));
+    let entries = if has_blocks { parse_blocks(text)? } else { parse_lines(text)? };
+
+    if entries.is_empty() {
+        return Err(Error::penumbra("Invalid scatter file: no partitions found"));
+    }
+
+    Ok(entries)
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split_once('#').map_or(line, |(content, _)| content)
+}
+
+fn parse_number(value: &str, line: usize) -> Result<u64> {
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    parsed.map_err(|_| {
+        Error::penumbra(format!("Invalid scatter file: line {}: bad number '{}'", line, value))
+    })
+}
+
+fn optional(value: &str) -> Option<String> {
+    (!value.is_empty() && !value.eq_ignore_ascii_case(NONE_VALUE)).then(|| value.to_string())
+}
+
+/// Parses the YAML-like format: each `- ` item opens a block, partitions being the blocks
+/// with a `partition_name`.
+fn parse_blocks(text: &str) -> Result<Vec<ScatterEntry>> {
+    let mut entries = Vec::new();
+    let mut current: Option<ScatterEntry> = None;
+
+    for (i, raw) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let line = match line.strip_prefix('-') {
+            Some(rest) => {
+                entries.extend(current.take().filter(|e| !e.partition_name.is_empty()));
+                rest.trim()
+            }
+            None => line,
+        };
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+
+        if key == "partition_name" {
+            current = Some(ScatterEntry {
+                partition_name: value.to_string(),
+                file_name: None,
+                physical_start_addr: 0,
+                partition_size: None,
+                region: None,
+                // SP Flash Tool downloads partitions with an image unless told otherwise
+                is_download: true,
+            });
+            continue;
+        }
+
+        let Some(entry) = current.as_mut() else {
+            continue;
+        };
+        match key {
+            "file_name" => entry.file_name = optional(value),
+            "physical_start_addr" => entry.physical_start_addr = parse_number(value, line_no)?,
+            "partition_size" => entry.partition_size = Some(parse_number(value, line_no)?),
+            "region" => entry.region = optional(value),
+            "is_download" => entry.is_download = value.eq_ignore_ascii_case("true"),
+            _ => {}
+        }
+    }
+
+    entries.extend(current.filter(|e| !e.partition_name.is_empty()));
+    Ok(entries)
+}
+
+/// Parses the old line format: `NAME 0xADDRESS`, followed by a `{ }` block.
+fn parse_lines(text: &str) -> Result<Vec<ScatterEntry>> {
+    let mut entries = Vec::new();
+
+    for (i, raw) in text.lines().enumerate() {
+        let line = strip_comment(raw).trim();
+        if line.is_empty() || line.starts_with('{') || line.starts_with('}') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(address)) = (fields.next(), fields.next()) else {
+            return Err(Error::penumbra(format!(
+                "Invalid scatter file: line {}: expected a partition name and address",
+                i + 1
+            )));
+        };
+
+        let (name, is_download) = match name.strip_prefix(NODL_PREFIX) {
+            Some(name) => (name, false),
+            None => (name, true),
+        };
+
+        entries.push(ScatterEntry {
+            partition_name: name.to_string(),
+            file_name: None,
+            physical_start_addr: parse_number(address, i + 1)?,
+            partition_size: None,
+            region: None,
+            is_download,
+        });
+    }
+
+    Ok(entries)
+}
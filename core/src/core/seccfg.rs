@@ -15,23 +15,62 @@
 use sha2::{Digest, Sha256};
 
 use crate::error::{Error, Result};
+use crate::le_u32;
+use crate::utilities::patching::write_bytes;
 
 const V4_MAGIC_BEGIN: u32 = 0x4D4D4D4D;
 const V4_MAGIC_END: u32 = 0x45454545;
 
+/// V3 is prefixed by this identifier, followed by the same begin magic as V4.
+const V3_INFO_HEADER: &[u8; 12] = b"AND_SECCFG_v";
+/// Identifier, magic, version, size, encrypted region offset and length, lock try/done,
+/// page size and page count.
+const V3_HEADER_SIZE: usize = 0x2C;
+/// Within the encrypted region: 20 image infos of 0x68 bytes, then the SIU status.
+const V3_STATUS_OFFSET: usize = 20 * 0x68 + 4;
+const V3_ATTR_OFFSET: usize = V3_STATUS_OFFSET + 4;
+const V3_STATUS_COMPLETE: u32 = 0x43434343;
+const V3_STATUS_INCOMPLETE: u32 = 0x49494949;
+/// Image verification enabled, the default (locked) state.
+const V3_ATTR_DEFAULT: u32 = 0x33333333;
+/// Image verification disabled, what unlocking writes.
+const V3_ATTR_DISABLE_IMG_CHECK: u32 = 0x44444444;
+/// Enough to hold either version, V3 being the largest.
+pub const SECCFG_MAX_SIZE: usize = 0x2000;
+
 pub enum LockFlag {
     Lock,
     Unlock,
 }
 
-#[derive(Clone)]
+/// How the protected part of seccfg is encrypted, found by trying each one.
+/// Used by both V3 and V4.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SecCfgV4Algo {
+    /// Not encrypted: the plain SHA-256 (V4) or data (V3) is stored as is
+    Plain,
     SW,
     HW,
     HWv3,
     HWv4,
 }
 
+impl SecCfgV4Algo {
+    /// Every algorithm, in the order they're tried. `Plain` comes first, as it needs no SEJ.
+    pub const ALL: [SecCfgV4Algo; 5] = [
+        SecCfgV4Algo::Plain,
+        SecCfgV4Algo::SW,
+        SecCfgV4Algo::HW,
+        SecCfgV4Algo::HWv3,
+        SecCfgV4Algo::HWv4,
+    ];
+
+    /// Whether the algorithm runs on the SEJ block, with its hardware key.
+    pub fn is_hardware(&self) -> bool {
+        matches!(self, SecCfgV4Algo::HW | SecCfgV4Algo::HWv3 | SecCfgV4Algo::HWv4)
+    }
+}
+
 #[derive(Default)]
 pub struct SecCfgV4 {
     pub seccfg_ver: u32,
@@ -57,18 +96,20 @@ impl SecCfgV4 {
     }
 
     pub fn parse_header(data: &[u8]) -> Result<SecCfgV4> {
-        if data.len() < 0x20 {
+        // Header up to the end flag, followed by the 32 bytes hash
+        if data.len() < 0x3C {
             return Err(Error::penumbra("SecCfg v4 data too short"));
         }
 
-        let magic = u32::from_le_bytes(data[0..4].try_into().unwrap());
-        let seccfg_ver = u32::from_le_bytes(data[4..8].try_into().unwrap());
-        let seccfg_size = u32::from_le_bytes(data[8..12].try_into().unwrap());
-        let lock_state = u32::from_le_bytes(data[12..16].try_into().unwrap());
-        let critical_lock_state = u32::from_le_bytes(data[16..20].try_into().unwrap());
-        let sboot_runtime = u32::from_le_bytes(data[20..24].try_into().unwrap());
-        let endflag = u32::from_le_bytes(data[24..28].try_into().unwrap());
-        let enc_hash = data[28..60].to_vec();
+        let magic = le_u32!(data, 0)?;
+        let seccfg_ver = le_u32!(data, 4)?;
+        let seccfg_size = le_u32!(data, 8)?;
+        let lock_state = le_u32!(data, 12)?;
+        let critical_lock_state = le_u32!(data, 16)?;
+        let sboot_runtime = le_u32!(data, 20)?;
+        let endflag = le_u32!(data, 24)?;
+        let enc_hash =
+            data.get(28..60).ok_or_else(|| Error::penumbra("SecCfg v4 data too short"))?.to_vec();
 
         if magic != V4_MAGIC_BEGIN || endflag != V4_MAGIC_END {
             return Err(Error::penumbra("Invalid SecCfg v4 magic values"));
@@ -117,6 +158,12 @@ impl SecCfgV4 {
         self.enc_hash.clone().unwrap_or_default()
     }
 
+    /// Whether the bootloader is unlocked. Any state other than the one written
+    /// by `set_lock_state(LockFlag::Unlock)` is treated as locked, including the factory default.
+    pub fn is_unlocked(&self) -> bool {
+        self.lock_state == 3
+    }
+
     pub fn set_lock_state(&mut self, lock_flag: LockFlag) {
         match lock_flag {
             LockFlag::Lock => {
@@ -154,3 +201,404 @@ impl SecCfgV4 {
         seccfg_data
     }
 }
+
+/// Seccfg V3, used by older devices. The lock state lives in a region encrypted as a whole,
+/// instead of being protected by an encrypted hash like V4.
+#[derive(Default)]
+pub struct SecCfgV3 {
+    /// The whole partition data, up to the end flag
+    raw: Vec<u8>,
+    enc_offset: usize,
+    enc_len: usize,
+    algo: Option<SecCfgV4Algo>,
+    /// The encrypted region once decrypted, `None` until the algorithm is known
+    decrypted: Option<Vec<u8>>,
+}
+
+impl SecCfgV3 {
+    pub fn parse(data: &[u8]) -> Result<SecCfgV3> {
+        if data.len() < V3_HEADER_SIZE || !data.starts_with(V3_INFO_HEADER) {
+            return Err(Error::penumbra("Not a SecCfg v3"));
+        }
+
+        let magic = le_u32!(data, 0x10)?;
+        let seccfg_ver = le_u32!(data, 0x14)?;
+        let seccfg_size = le_u32!(data, 0x18)? as usize;
+        let enc_offset = V3_HEADER_SIZE + le_u32!(data, 0x1C)? as usize;
+        let enc_len = le_u32!(data, 0x20)? as usize;
+
+        if magic != V4_MAGIC_BEGIN || seccfg_ver != 3 {
+            return Err(Error::penumbra("Invalid SecCfg v3 magic values"));
+        }
+        if seccfg_size < V3_HEADER_SIZE + 4
+            || seccfg_size > data.len()
+            || enc_offset + enc_len > seccfg_size - 4
+        {
+            return Err(Error::penumbra("SecCfg v3 data too short"));
+        }
+        if le_u32!(data, seccfg_size - 4)? != V4_MAGIC_END {
+            return Err(Error::penumbra("Invalid SecCfg v3 end flag"));
+        }
+
+        let mut seccfg = SecCfgV3 {
+            raw: data.get(..seccfg_size).unwrap_or_default().to_vec(),
+            enc_offset,
+            enc_len,
+            algo: None,
+            decrypted: None,
+        };
+
+        // Some devices don't encrypt it at all
+        let region = seccfg.get_encrypted_region();
+        seccfg.accept_decrypted(SecCfgV4Algo::Plain, &region);
+
+        Ok(seccfg)
+    }
+
+    pub fn get_encrypted_region(&self) -> Vec<u8> {
+        self.raw.get(self.enc_offset..self.enc_offset + self.enc_len).unwrap_or_default().to_vec()
+    }
+
+    fn status_valid(region: &[u8]) -> bool {
+        let status = le_u32!(region, V3_STATUS_OFFSET).ok();
+        let attr = le_u32!(region, V3_ATTR_OFFSET).ok();
+
+        matches!(status, Some(V3_STATUS_COMPLETE | V3_STATUS_INCOMPLETE))
+            && matches!(attr, Some(V3_ATTR_DEFAULT | V3_ATTR_DISABLE_IMG_CHECK))
+    }
+
+    /// Keeps `decrypted` as the encrypted region decrypted with `algo`, if it makes sense.
+    /// Returns whether it did.
+    pub fn accept_decrypted(&mut self, algo: SecCfgV4Algo, decrypted: &[u8]) -> bool {
+        let Some(region) = decrypted.get(..self.enc_len) else {
+            return false;
+        };
+        if !Self::status_valid(region) {
+            return false;
+        }
+
+        self.decrypted = Some(region.to_vec());
+        self.algo = Some(algo);
+        true
+    }
+
+    pub fn get_algo(&self) -> Option<SecCfgV4Algo> {
+        self.algo.clone()
+    }
+
+    /// The encrypted region in the clear, `None` until it was decrypted.
+    pub fn get_decrypted_region(&self) -> Option<Vec<u8>> {
+        self.decrypted.clone()
+    }
+
+    /// Whether the bootloader is unlocked, `None` until the region was decrypted.
+    pub fn is_unlocked(&self) -> Option<bool> {
+        let decrypted = self.decrypted.as_ref()?;
+        Some(le_u32!(decrypted, V3_ATTR_OFFSET).ok()? == V3_ATTR_DISABLE_IMG_CHECK)
+    }
+
+    /// Sets the lock state in the decrypted region. Does nothing until it was decrypted.
+    pub fn set_lock_state(&mut self, lock_flag: LockFlag) {
+        let Some(decrypted) = self.decrypted.as_mut() else {
+            return;
+        };
+
+        let attr = match lock_flag {
+            LockFlag::Lock => V3_ATTR_DEFAULT,
+            LockFlag::Unlock => V3_ATTR_DISABLE_IMG_CHECK,
+        };
+        // Both fields were checked to be there when the region was accepted
+        if let Some(status) = decrypted.get_mut(V3_STATUS_OFFSET..V3_STATUS_OFFSET + 4) {
+            status.copy_from_slice(&V3_STATUS_COMPLETE.to_le_bytes());
+        }
+        if let Some(field) = decrypted.get_mut(V3_ATTR_OFFSET..V3_ATTR_OFFSET + 4) {
+            field.copy_from_slice(&attr.to_le_bytes());
+        }
+    }
+
+    /// Puts the region encrypted back in place.
+    /// Extra bytes (e.g. padding) are dropped, as the region has a fixed size.
+    pub fn set_encrypted_region(&mut self, encrypted: &[u8]) -> Result<()> {
+        let Some(encrypted) = encrypted.get(..self.enc_len) else {
+            return Err(Error::penumbra("Encrypted SecCfg v3 region too short"));
+        };
+
+        write_bytes(&mut self.raw, self.enc_offset, encrypted)
+    }
+
+    pub fn create(&self) -> Vec<u8> {
+        let mut seccfg_data = self.raw.clone();
+        while !seccfg_data.len().is_multiple_of(0x200) {
+            seccfg_data.push(0);
+        }
+        seccfg_data
+    }
+}
+
+/// Seccfg of either version, detected from its header.
+pub enum SecCfg {
+    V3(SecCfgV3),
+    V4(SecCfgV4),
+}
+
+impl SecCfg {
+    pub fn parse(data: &[u8]) -> Result<SecCfg> {
+        if data.starts_with(V3_INFO_HEADER) {
+            return SecCfgV3::parse(data).map(SecCfg::V3);
+        }
+
+        match le_u32!(data, 0) {
+            Ok(V4_MAGIC_BEGIN) => SecCfgV4::parse_header(data).map(SecCfg::V4),
+            Ok(_) => Err(Error::penumbra("Unknown SecCfg format")),
+            Err(_) => Err(Error::penumbra("SecCfg data too short")),
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            SecCfg::V3(_) => 3,
+            SecCfg::V4(_) => 4,
+        }
+    }
+
+    /// The data to decrypt to find the algorithm: the hash for V4, the region for V3.
+    pub fn get_encrypted(&self) -> Vec<u8> {
+        match self {
+            SecCfg::V3(seccfg) => seccfg.get_encrypted_region(),
+            SecCfg::V4(seccfg) => seccfg.get_encrypted_hash(),
+        }
+    }
+
+    /// Checks `decrypted`, the encrypted data decrypted with `algo`.
+    /// If it is right, `algo` is kept for writing back and `true` is returned.
+    pub fn accept_decrypted(&mut self, algo: SecCfgV4Algo, decrypted: &[u8]) -> bool {
+        match self {
+            SecCfg::V3(seccfg) => seccfg.accept_decrypted(algo, decrypted),
+            SecCfg::V4(seccfg) => {
+                if decrypted != seccfg.get_hash() {
+                    return false;
+                }
+                seccfg.set_algo(algo);
+                true
+            }
+        }
+    }
+
+    pub fn get_algo(&self) -> Option<SecCfgV4Algo> {
+        match self {
+            SecCfg::V3(seccfg) => seccfg.get_algo(),
+            SecCfg::V4(seccfg) => seccfg.get_algo(),
+        }
+    }
+
+    /// The data to encrypt with the algorithm before writing: the hash for V4, the region
+    /// for V3. `None` if V3 wasn't decrypted.
+    pub fn get_plain(&self) -> Option<Vec<u8>> {
+        match self {
+            SecCfg::V3(seccfg) => seccfg.get_decrypted_region(),
+            SecCfg::V4(seccfg) => Some(seccfg.get_hash()),
+        }
+    }
+
+    /// Puts back the data returned by `get_plain` once encrypted.
+    pub fn set_encrypted(&mut self, encrypted: Vec<u8>) -> Result<()> {
+        match self {
+            SecCfg::V3(seccfg) => seccfg.set_encrypted_region(&encrypted),
+            SecCfg::V4(seccfg) => {
+                seccfg.set_encrypted_hash(encrypted);
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the bootloader is unlocked, `None` if V3 wasn't decrypted.
+    pub fn is_unlocked(&self) -> Option<bool> {
+        match self {
+            SecCfg::V3(seccfg) => seccfg.is_unlocked(),
+            SecCfg::V4(seccfg) => Some(seccfg.is_unlocked()),
+        }
+    }
+
+    pub fn set_lock_state(&mut self, lock_flag: LockFlag) {
+        match self {
+            SecCfg::V3(seccfg) => seccfg.set_lock_state(lock_flag),
+            SecCfg::V4(seccfg) => seccfg.set_lock_state(lock_flag),
+        }
+    }
+
+    pub fn create(&mut self) -> Vec<u8> {
+        match self {
+            SecCfg::V3(seccfg) => seccfg.create(),
+            SecCfg::V4(seccfg) => seccfg.create(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for SEJ: each algorithm XORs with its own key, so only the right one
+    /// decrypts what it encrypted.
+    fn crypt(algo: &SecCfgV4Algo, data: &[u8]) -> Vec<u8> {
+        let key = match algo {
+            SecCfgV4Algo::Plain => 0x00,
+            SecCfgV4Algo::SW => 0x5A,
+            SecCfgV4Algo::HW => 0xA5,
+            SecCfgV4Algo::HWv3 => 0x3C,
+            SecCfgV4Algo::HWv4 => 0xC3,
+        };
+        data.iter().map(|b| b ^ key).collect()
+    }
+
+    /// Tries each algorithm in order, like the DA protocols do.
+    fn trial_match(seccfg: &mut SecCfg) -> Option<SecCfgV4Algo> {
+        let encrypted = seccfg.get_encrypted();
+        SecCfgV4Algo::ALL
+            .into_iter()
+            .find(|algo| seccfg.accept_decrypted(algo.clone(), &crypt(algo, &encrypted)))
+    }
+
+    /// Encrypts the lock state with the algorithm found, then reads the result back.
+    fn write_back(seccfg: &mut SecCfg) -> Vec<u8> {
+        let algo = seccfg.get_algo().unwrap();
+        seccfg.set_encrypted(crypt(&algo, &seccfg.get_plain().unwrap())).unwrap();
+        seccfg.create()
+    }
+
+    /// The seccfg partition as read from flash, padded with zeros.
+    fn partition(mut data: Vec<u8>) -> Vec<u8> {
+        data.resize(SECCFG_MAX_SIZE, 0);
+        data
+    }
+
+    fn v4(lock_flag: LockFlag, algo: &SecCfgV4Algo) -> Vec<u8> {
+        let mut seccfg = SecCfgV4::new();
+        seccfg.set_lock_state(lock_flag);
+        seccfg.set_encrypted_hash(crypt(algo, &seccfg.get_hash()));
+        seccfg.create()
+    }
+
+    const V3_ENC_LEN: usize = 0x830;
+    const V3_SIZE: usize = V3_HEADER_SIZE + V3_ENC_LEN + 4;
+
+    fn v3(attr: u32, algo: &SecCfgV4Algo) -> Vec<u8> {
+        let mut region = vec![0u8; V3_ENC_LEN];
+        region[V3_STATUS_OFFSET..V3_STATUS_OFFSET + 4]
+            .copy_from_slice(&V3_STATUS_COMPLETE.to_le_bytes());
+        region[V3_ATTR_OFFSET..V3_ATTR_OFFSET + 4].copy_from_slice(&attr.to_le_bytes());
+
+        let mut data = V3_INFO_HEADER.to_vec();
+        data.resize(0x10, 0);
+        // Magic, version, size, region offset and length, lock try and done
+        for field in [V4_MAGIC_BEGIN, 3, V3_SIZE as u32, 0, V3_ENC_LEN as u32, 0, 0] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend(crypt(algo, &region));
+        data.extend_from_slice(&V4_MAGIC_END.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn v4_round_trips_with_a_sw_hash() {
+        let data = v4(LockFlag::Lock, &SecCfgV4Algo::SW);
+        let mut seccfg = SecCfg::parse(&partition(data.clone())).unwrap();
+
+        assert_eq!(seccfg.version(), 4);
+        assert_eq!(trial_match(&mut seccfg), Some(SecCfgV4Algo::SW));
+        assert_eq!(seccfg.is_unlocked(), Some(false));
+        assert_eq!(seccfg.create(), data);
+
+        seccfg.set_lock_state(LockFlag::Unlock);
+        let unlocked = write_back(&mut seccfg);
+        assert_eq!(unlocked, v4(LockFlag::Unlock, &SecCfgV4Algo::SW));
+
+        let mut seccfg = SecCfg::parse(&partition(unlocked)).unwrap();
+        assert_eq!(trial_match(&mut seccfg), Some(SecCfgV4Algo::SW));
+        let SecCfg::V4(v4) = &seccfg else { panic!("not a V4") };
+        assert_eq!((v4.seccfg_ver, v4.seccfg_size), (4, 20));
+        assert_eq!((v4.lock_state, v4.critical_lock_state), (3, 0));
+        assert!(v4.is_unlocked());
+    }
+
+    #[test]
+    fn v3_round_trips() {
+        let data = v3(V3_ATTR_DEFAULT, &SecCfgV4Algo::SW);
+        let mut seccfg = SecCfg::parse(&partition(data.clone())).unwrap();
+
+        assert_eq!(seccfg.version(), 3);
+        // An encrypted region doesn't pass for a plain one
+        assert_eq!(seccfg.get_algo(), None);
+        assert_eq!(seccfg.is_unlocked(), None);
+        assert_eq!(trial_match(&mut seccfg), Some(SecCfgV4Algo::SW));
+        assert_eq!(seccfg.is_unlocked(), Some(false));
+
+        seccfg.set_lock_state(LockFlag::Unlock);
+        let unlocked = write_back(&mut seccfg);
+        assert_eq!(unlocked.len(), V3_SIZE.next_multiple_of(0x200));
+        assert_eq!(unlocked[..V3_SIZE], v3(V3_ATTR_DISABLE_IMG_CHECK, &SecCfgV4Algo::SW));
+        assert!(unlocked[V3_SIZE..].iter().all(|&b| b == 0));
+
+        let mut seccfg = SecCfg::parse(&unlocked).unwrap();
+        assert_eq!(trial_match(&mut seccfg), Some(SecCfgV4Algo::SW));
+        assert_eq!(seccfg.is_unlocked(), Some(true));
+    }
+
+    #[test]
+    fn plain_v3_is_accepted_right_away() {
+        let seccfg = SecCfg::parse(&v3(V3_ATTR_DISABLE_IMG_CHECK, &SecCfgV4Algo::Plain)).unwrap();
+        assert_eq!(seccfg.get_algo(), Some(SecCfgV4Algo::Plain));
+        assert_eq!(seccfg.is_unlocked(), Some(true));
+    }
+
+    #[test]
+    fn trial_match_picks_the_algorithm_used() {
+        for algo in [SecCfgV4Algo::SW, SecCfgV4Algo::HW, SecCfgV4Algo::HWv4] {
+            let mut seccfg = SecCfg::parse(&v4(LockFlag::Lock, &algo)).unwrap();
+            assert_eq!(trial_match(&mut seccfg).as_ref(), Some(&algo));
+
+            let mut seccfg = SecCfg::parse(&v3(V3_ATTR_DEFAULT, &algo)).unwrap();
+            assert_eq!(trial_match(&mut seccfg).as_ref(), Some(&algo));
+        }
+    }
+
+    #[test]
+    fn unknown_encryption_is_not_matched() {
+        let mut data = v4(LockFlag::Lock, &SecCfgV4Algo::SW);
+        data[28] ^= 0xFF;
+        let mut seccfg = SecCfg::parse(&data).unwrap();
+
+        assert_eq!(trial_match(&mut seccfg), None);
+        assert_eq!(seccfg.get_algo(), None);
+    }
+
+    fn parse_error(data: &[u8]) -> String {
+        SecCfg::parse(data).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn bad_end_magics_are_refused() {
+        let mut data = v4(LockFlag::Lock, &SecCfgV4Algo::SW);
+        data[24] ^= 0xFF;
+        assert!(parse_error(&data).contains("Invalid SecCfg v4 magic values"));
+
+        let mut data = v3(V3_ATTR_DEFAULT, &SecCfgV4Algo::SW);
+        data[V3_SIZE - 1] ^= 0xFF;
+        assert!(parse_error(&data).contains("Invalid SecCfg v3 end flag"));
+    }
+
+    #[test]
+    fn truncated_seccfg_is_refused() {
+        let data = v4(LockFlag::Lock, &SecCfgV4Algo::SW);
+        assert!(parse_error(&data[..0x30]).contains("too short"));
+
+        // Announcing more than was read
+        let data = v3(V3_ATTR_DEFAULT, &SecCfgV4Algo::SW);
+        assert!(parse_error(&data[..V3_SIZE - 0x10]).contains("too short"));
+
+        // Announcing less than the encrypted region and the end flag need
+        let mut data = partition(v3(V3_ATTR_DEFAULT, &SecCfgV4Algo::SW));
+        data[0x18..0x1C].copy_from_slice(&((V3_SIZE - 0x10) as u32).to_le_bytes());
+        assert!(parse_error(&data).contains("too short"));
+    }
+}
@@ -4,9 +4,10 @@
 */
 use async_trait::async_trait;
 
-use crate::core::storage::{PartitionKind, Storage, StorageType};
+use crate::core::storage::{PartitionKind, Storage, StorageInfo, StorageType, ascii_field};
 use crate::error::{Error, Result};
 use crate::utilities::xml::{get_tag, get_tag_usize};
+use crate::{le_u32, le_u64};
 
 /// Represents eMMC storage information.
 #[derive(Debug)]
@@ -129,6 +130,50 @@ impl Storage for EmmcStorage {
     fn get_user_size(&self) -> u64 {
         self.info.user_size
     }
+
+    fn info(&self) -> StorageInfo {
+        StorageInfo {
+            kind: StorageType::Emmc,
+            block_size: self.info.block_size,
+            total_size: self.total_size(),
+            user_size: self.info.user_size,
+            boot1_size: self.info.boot1_size,
+            boot2_size: self.info.boot2_size,
+            rpmb_size: self.info.rpmb_size,
+            cid: self.info.cid.clone(),
+            model: self.info.product_name(),
+            fw_version: (self.info.fwver != 0).then(|| format!("0x{:X}", self.info.fwver)),
+            serial: self.info.serial_number().map(|psn| format!("0x{:08X}", psn)),
+        }
+    }
+}
+
+impl EmmcInfo {
+    /// CID register in its JEDEC byte order. Some DAs send it as little endian words,
+    /// those are recognized by their product name not being text.
+    fn jedec_cid(&self) -> Option<[u8; 16]> {
+        let cid: [u8; 16] = self.cid.get(..16)?.try_into().ok()?;
+        if ascii_field(&cid[3..9]).is_some() {
+            return Some(cid);
+        }
+
+        let mut swapped = cid;
+        for word in swapped.chunks_exact_mut(4) {
+            word.reverse();
+        }
+        ascii_field(&swapped[3..9]).map(|_| swapped)
+    }
+
+    /// Product name (PNM) from the CID.
+    pub fn product_name(&self) -> Option<String> {
+        ascii_field(&self.jedec_cid()?[3..9])
+    }
+
+    /// Product serial number (PSN) from the CID.
+    pub fn serial_number(&self) -> Option<u32> {
+        let cid = self.jedec_cid()?;
+        Some(u32::from_be_bytes([cid[10], cid[11], cid[12], cid[13]]))
+    }
 }
 
 impl EmmcStorage {
@@ -138,25 +183,28 @@ impl EmmcStorage {
         }
 
         let mut pos = 0;
-        let kind = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
-        let block_size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap());
+        let kind = le_u32!(data, pos)?;
+        let block_size = le_u32!(data, pos + 4)?;
 
         pos += 8;
 
-        let boot1_size = u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap());
-        let boot2_size = u64::from_le_bytes(data[pos + 8..pos + 16].try_into().unwrap());
-        let rpmb_size = u64::from_le_bytes(data[pos + 16..pos + 24].try_into().unwrap());
-        let gp1_size = u64::from_le_bytes(data[pos + 24..pos + 32].try_into().unwrap());
-        let gp2_size = u64::from_le_bytes(data[pos + 32..pos + 40].try_into().unwrap());
-        let gp3_size = u64::from_le_bytes(data[pos + 40..pos + 48].try_into().unwrap());
-        let gp4_size = u64::from_le_bytes(data[pos + 48..pos + 56].try_into().unwrap());
-        let user_size = u64::from_le_bytes(data[pos + 56..pos + 64].try_into().unwrap());
+        let boot1_size = le_u64!(data, pos)?;
+        let boot2_size = le_u64!(data, pos + 8)?;
+        let rpmb_size = le_u64!(data, pos + 16)?;
+        let gp1_size = le_u64!(data, pos + 24)?;
+        let gp2_size = le_u64!(data, pos + 32)?;
+        let gp3_size = le_u64!(data, pos + 40)?;
+        let gp4_size = le_u64!(data, pos + 48)?;
+        let user_size = le_u64!(data, pos + 56)?;
 
         pos += 64;
-        let cid = data[pos..pos + 16].to_vec();
+        let cid = data
+            .get(pos..pos + 16)
+            .ok_or_else(|| Error::malformed("eMMC CID out of bounds", data))?
+            .to_vec();
 
         pos += 16;
-        let fwver = u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap());
+        let fwver = le_u64!(data, pos)?;
 
         Ok(EmmcStorage {
             info: EmmcInfo {
@@ -176,6 +224,40 @@ impl EmmcStorage {
         })
     }
 
+    /// Parses the eMMC info sent by legacy DAs once DA2 started. Its fields are big endian,
+    /// and there's no block size: legacy DAs always address eMMC in 512 bytes blocks.
+    pub fn from_legacy_response(data: &[u8]) -> Result<Self> {
+        let data: &[u8; 92] =
+            data.first_chunk().ok_or_else(|| Error::penumbra("Legacy eMMC info too short"))?;
+
+        let be_u64 = |pos: usize| {
+            let word = data.get(pos..pos + 8).and_then(|word| word.try_into().ok());
+            word.map_or(0, u64::from_be_bytes)
+        };
+
+        let status = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        if status != 0 {
+            return Err(Error::penumbra(format!("eMMC init failed with status 0x{:X}", status)));
+        }
+
+        Ok(EmmcStorage {
+            info: EmmcInfo {
+                kind: 0x1,
+                block_size: 512,
+                boot1_size: be_u64(4),
+                boot2_size: be_u64(12),
+                rpmb_size: be_u64(20),
+                gp1_size: be_u64(28),
+                gp2_size: be_u64(36),
+                gp3_size: be_u64(44),
+                gp4_size: be_u64(52),
+                user_size: be_u64(60),
+                cid: data[68..84].to_vec(),
+                fwver: be_u64(84),
+            },
+        })
+    }
+
     pub fn from_xml_response(xml: &str) -> Result<Self> {
         let block_size = get_tag_usize(xml, "emmc/block_size")? as u32;
 
@@ -2,15 +2,26 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+use std::fmt;
+
 use crc32fast::hash as crc32;
 
-use crate::core::storage::{EmmcPartition, Partition, PartitionKind, StorageType, UfsPartition};
+use crate::core::storage::{
+    EmmcPartition,
+    NandPartition,
+    Partition,
+    PartitionKind,
+    StorageType,
+    UfsPartition,
+};
 use crate::error::{Error, Result};
+use crate::{le_u32, le_u64};
 
 const EFI_PART_SIGNATURE: &[u8; 8] = b"EFI PART";
 
+/// Which copy of the GPT a blob holds: primary (start of the disk) or backup (end of the disk).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum GptType {
+pub enum GptType {
     Pgpt,
     Sgpt,
 }
@@ -43,64 +54,100 @@ impl Gpt {
         let part_kind = match storage_type {
             StorageType::Emmc => PartitionKind::Emmc(EmmcPartition::User),
             StorageType::Ufs => PartitionKind::Ufs(UfsPartition::Lu2),
+            StorageType::Nand => PartitionKind::Nand(NandPartition::Whole),
             _ => PartitionKind::Unknown,
         };
 
         let (gpt_type, header_offset) =
             Self::detect_type(data).ok_or_else(|| Error::penumbra("No valid GPT header found"))?;
 
-        let header = Self::parse_header(data, header_offset)?;
+        // The primary header is at LBA 1, the backup one is the last sector of the buffer
+        let sector_size = match gpt_type {
+            GptType::Pgpt => header_offset,
+            GptType::Sgpt => data.len() - header_offset,
+        };
+        let header = Self::parse_header(data, header_offset, sector_size)?;
+        let sector_size = sector_size as u64;
+        let len = header.num_entries as usize * header.entry_size as usize;
 
-        let entries_data = match gpt_type {
+        let start = match gpt_type {
             GptType::Pgpt => {
-                let start = header.part_entry_lba as usize * header_offset;
-                let len = header.num_entries as usize * header.entry_size as usize;
-                if data.len() < start + len {
-                    return Err(Error::io("Partition array out of bounds"));
-                }
-                &data[start..start + len]
-            }
-            GptType::Sgpt => {
-                let len = header.num_entries as usize * header.entry_size as usize;
-                if data.len() < header_offset || header_offset < len {
-                    return Err(Error::io("SGPT buffer too small for entries"));
+                if header.current_lba != 1 {
+                    return Err(Error::penumbra(format!(
+                        "Primary GPT header claims to be at LBA {}, not 1",
+                        header.current_lba
+                    )));
                 }
-                &data[0..len]
+                header.part_entry_lba.checked_mul(sector_size)
             }
+            // The buffer ends with the header, the entries are located relative to it
+            GptType::Sgpt => header
+                .current_lba
+                .checked_sub(header.part_entry_lba)
+                .and_then(|lbas| lbas.checked_mul(sector_size))
+                .and_then(|distance| (header_offset as u64).checked_sub(distance)),
         };
 
+        let entries_data = start
+            .and_then(|start| data.get(start as usize..(start as usize).checked_add(len)?))
+            .ok_or_else(|| {
+                Error::io(format!(
+                    "Partition array at LBA {} is out of bounds",
+                    header.part_entry_lba
+                ))
+            })?;
+
         let partitions = Self::parse_partition_entries(entries_data, &header, part_kind)?;
 
         Ok(Self { header, partitions })
     }
 
+    /// Like [`Gpt::parse`], but fails unless `data` holds the `expected` copy of the GPT,
+    /// so a stray header at the wrong place isn't trusted.
+    pub fn parse_as(data: &[u8], expected: GptType, storage_type: StorageType) -> Result<Self> {
+        match Self::detect_type(data) {
+            Some((found, _)) if found == expected => Self::parse(data, storage_type),
+            Some((found, _)) => Err(Error::penumbra(format!(
+                "Expected a {:?} header, found a {:?}",
+                expected, found
+            ))),
+            None if data.is_empty() => Err(Error::io("Could not read the GPT")),
+            None => Err(Error::penumbra("No GPT signature found")),
+        }
+    }
+
     pub fn partitions(&self) -> Vec<Partition> {
         self.partitions.clone()
     }
 
-    fn parse_header(data: &[u8], offset: usize) -> Result<GptHeader> {
-        if offset + 92 > data.len() {
-            return Err(Error::io("GPT header out of bounds"));
-        }
+    /// Size of the disk in sectors, from the LBA of the header at its end.
+    pub fn total_sectors(&self) -> u64 {
+        self.header.current_lba.max(self.header.backup_lba) + 1
+    }
 
-        let hdr = &data[offset..offset + 92];
+    fn parse_header(data: &[u8], offset: usize, sector_size: usize) -> Result<GptHeader> {
+        let Some(hdr) = data.get(offset..).and_then(|data| data.first_chunk::<92>()) else {
+            return Err(Error::io("GPT header out of bounds"));
+        };
 
         if &hdr[0..8] != EFI_PART_SIGNATURE {
             return Err(Error::penumbra("Invalid GPT signature"));
         }
 
-        let header_size = u32::from_le_bytes(hdr[12..16].try_into().unwrap()) as usize;
-        let stored_crc = u32::from_le_bytes(hdr[16..20].try_into().unwrap());
+        let header_size = le_u32!(hdr, 12)? as usize;
+        let stored_crc = le_u32!(hdr, 16)?;
 
         if !(92..=512).contains(&header_size) {
             return Err(Error::penumbra("Invalid GPT header size"));
         }
-        if offset + header_size > data.len() {
-            return Err(Error::io("GPT header out of bounds"));
+        let mut crc_buf = data
+            .get(offset..offset + header_size)
+            .ok_or_else(|| Error::io("GPT header out of bounds"))?
+            .to_vec();
+        // The CRC is computed with its own field zeroed
+        if let Some(crc) = crc_buf.get_mut(16..20) {
+            crc.fill(0);
         }
-
-        let mut crc_buf = data[offset..offset + header_size].to_vec();
-        crc_buf[16..20].fill(0);
         let computed_crc = crc32(&crc_buf);
 
         if computed_crc != stored_crc {
@@ -110,25 +157,25 @@ impl Gpt {
         Ok(GptHeader {
             header_size: header_size as u32,
             header_crc32: stored_crc,
-            current_lba: u64::from_le_bytes(hdr[24..32].try_into().unwrap()),
-            backup_lba: u64::from_le_bytes(hdr[32..40].try_into().unwrap()),
-            first_usable_lba: u64::from_le_bytes(hdr[40..48].try_into().unwrap()),
-            last_usable_lba: u64::from_le_bytes(hdr[48..56].try_into().unwrap()),
-            part_entry_lba: u64::from_le_bytes(hdr[72..80].try_into().unwrap()),
-            num_entries: u32::from_le_bytes(hdr[80..84].try_into().unwrap()),
-            entry_size: u32::from_le_bytes(hdr[84..88].try_into().unwrap()),
-            part_array_crc32: u32::from_le_bytes(hdr[88..92].try_into().unwrap()),
-            sector_size: offset,
+            current_lba: le_u64!(hdr, 24)?,
+            backup_lba: le_u64!(hdr, 32)?,
+            first_usable_lba: le_u64!(hdr, 40)?,
+            last_usable_lba: le_u64!(hdr, 48)?,
+            part_entry_lba: le_u64!(hdr, 72)?,
+            num_entries: le_u32!(hdr, 80)?,
+            entry_size: le_u32!(hdr, 84)?,
+            part_array_crc32: le_u32!(hdr, 88)?,
+            sector_size,
         })
     }
 
     fn validate_parts_crc(entries: &[u8], header: &GptHeader) -> Result<()> {
         let array_len = header.num_entries as usize * header.entry_size as usize;
-        if entries.len() < array_len {
+        let Some(array) = entries.get(..array_len) else {
             return Err(Error::io("Partition array out of bounds"));
-        }
+        };
 
-        let computed = crc32(&entries[..array_len]);
+        let computed = crc32(array);
         if computed != header.part_array_crc32 {
             return Err(Error::penumbra("Partition array CRC mismatch"));
         }
@@ -151,17 +198,17 @@ impl Gpt {
 
         for i in 0..header.num_entries {
             let off = i as usize * header.entry_size as usize;
-            if off + header.entry_size as usize > entries_data.len() {
+            // Entries are 128 bytes, as checked above
+            let Some(entry) = entries_data.get(off..).and_then(|data| data.first_chunk::<128>())
+            else {
                 return Err(Error::io("Partition entry out of bounds"));
-            }
-
-            let entry = &entries_data[off..off + header.entry_size as usize];
+            };
             if entry[0..16].iter().all(|&b| b == 0) {
                 continue;
             }
 
-            let first_lba = u64::from_le_bytes(entry[32..40].try_into().unwrap());
-            let last_lba = u64::from_le_bytes(entry[40..48].try_into().unwrap());
+            let first_lba = le_u64!(entry, 32)?;
+            let last_lba = le_u64!(entry, 40)?;
             if last_lba < first_lba {
                 return Err(Error::io("Partition last_lba < first_lba"));
             }
@@ -169,7 +216,8 @@ impl Gpt {
             let name = String::from_utf16_lossy(
                 &entry[56..128]
                     .chunks_exact(2)
-                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .filter_map(|c| c.try_into().ok())
+                    .map(u16::from_le_bytes)
                     .take_while(|&c| c != 0)
                     .collect::<Vec<_>>(),
             );
@@ -193,13 +241,15 @@ impl Gpt {
         let sector_sizes = [512, 1024, 2048, 4096, 8192];
 
         for &sector_size in &sector_sizes {
-            if end >= sector_size + 8 && &data[end - sector_size..end - sector_size + 8] == EFI_PART_SIGNATURE {
+            if end >= sector_size + 8
+                && data.get(end - sector_size..).is_some_and(|d| d.starts_with(EFI_PART_SIGNATURE))
+            {
                 return Some((GptType::Sgpt, end - sector_size));
             }
         }
 
         for &sector_size in &sector_sizes {
-            if data.len() >= sector_size + 8 && &data[sector_size..sector_size + 8] == EFI_PART_SIGNATURE {
+            if data.get(sector_size..).is_some_and(|d| d.starts_with(EFI_PART_SIGNATURE)) {
                 return Some((GptType::Pgpt, sector_size));
             }
         }
@@ -208,8 +258,168 @@ impl Gpt {
     }
 }
 
+/// Why a partition can't be read as reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionIssue {
+    /// The partition is empty. Some DAs hang on zero-length reads.
+    ZeroSize,
+    /// The partition ends at `end`, past the end of its region at `limit`.
+    /// Usually a leftover from OEM resize scripts.
+    OutOfBounds { end: u64, limit: u64 },
+}
+
+impl fmt::Display for PartitionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionIssue::ZeroSize => write!(f, "zero size"),
+            PartitionIssue::OutOfBounds { end, limit } => {
+                write!(f, "ends at 0x{:X}, past the end of the storage (0x{:X})", end, limit)
+            }
+        }
+    }
+}
+
+/// Checks the geometry of a partition living in a region of `region_size` bytes.
+/// A `region_size` of 0 means it is unknown, and only the size is checked.
+pub fn check_geometry(part: &Partition, region_size: u64) -> Option<PartitionIssue> {
+    if part.size == 0 {
+        return Some(PartitionIssue::ZeroSize);
+    }
+
+    let end = part.address.saturating_add(part.size as u64);
+    if region_size != 0 && end > region_size {
+        return Some(PartitionIssue::OutOfBounds { end, limit: region_size });
+    }
+
+    None
+}
+
 impl From<Gpt> for Vec<Partition> {
     fn from(gpt: Gpt) -> Self {
         gpt.partitions
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECTOR: usize = 512;
+    /// Size of the user region the crafted tables describe.
+    const DISK_SIZE: u64 = 0x10_0000;
+
+    /// A primary GPT with an entry per `(name, first_lba, last_lba)`, as read from the
+    /// device: 32 KiB from the start of the disk.
+    fn pgpt(entries: &[(&str, u64, u64)]) -> Vec<u8> {
+        let mut data = vec![0u8; 0x8000];
+
+        let mut array = vec![0u8; SECTOR];
+        for (i, (name, first_lba, last_lba)) in entries.iter().enumerate() {
+            let entry = &mut array[i * 128..(i + 1) * 128];
+            entry[0..16].fill(0xAF);
+            entry[32..40].copy_from_slice(&first_lba.to_le_bytes());
+            entry[40..48].copy_from_slice(&last_lba.to_le_bytes());
+            for (j, c) in name.encode_utf16().enumerate() {
+                entry[56 + j * 2..58 + j * 2].copy_from_slice(&c.to_le_bytes());
+            }
+        }
+
+        let last_lba = DISK_SIZE / SECTOR as u64 - 1;
+        let hdr = &mut data[SECTOR..SECTOR + 92];
+        hdr[0..8].copy_from_slice(EFI_PART_SIGNATURE);
+        hdr[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        hdr[12..16].copy_from_slice(&92u32.to_le_bytes());
+        hdr[24..32].copy_from_slice(&1u64.to_le_bytes());
+        hdr[32..40].copy_from_slice(&last_lba.to_le_bytes());
+        hdr[40..48].copy_from_slice(&34u64.to_le_bytes());
+        hdr[48..56].copy_from_slice(&(last_lba - 33).to_le_bytes());
+        hdr[72..80].copy_from_slice(&2u64.to_le_bytes());
+        hdr[80..84].copy_from_slice(&4u32.to_le_bytes());
+        hdr[84..88].copy_from_slice(&128u32.to_le_bytes());
+        hdr[88..92].copy_from_slice(&crc32(&array).to_le_bytes());
+        let crc = crc32(hdr);
+        hdr[16..20].copy_from_slice(&crc.to_le_bytes());
+
+        data[SECTOR * 2..SECTOR * 3].copy_from_slice(&array);
+        data
+    }
+
+    /// Issues of each entry of the crafted table, by name.
+    fn issues(
+        entries: &[(&str, u64, u64)],
+        region_size: u64,
+    ) -> Vec<(String, Option<PartitionIssue>)> {
+        let gpt = Gpt::parse_as(&pgpt(entries), GptType::Pgpt, StorageType::Emmc).unwrap();
+        gpt.partitions().iter().map(|p| (p.name.clone(), check_geometry(p, region_size))).collect()
+    }
+
+    #[test]
+    fn crafted_table_parses() {
+        let gpt = Gpt::parse_as(&pgpt(&[("boot", 0x40, 0x7F)]), GptType::Pgpt, StorageType::Emmc)
+            .unwrap();
+        let parts = gpt.partitions();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(
+            (parts[0].name.as_str(), parts[0].address, parts[0].size),
+            ("boot", 0x8000, 0x8000)
+        );
+        assert_eq!(gpt.total_sectors(), DISK_SIZE / SECTOR as u64);
+    }
+
+    #[test]
+    fn out_of_bounds_entries_are_flagged() {
+        let last = DISK_SIZE / SECTOR as u64 - 1;
+        let found = issues(
+            &[
+                ("boot", 0x40, 0x7F),
+                ("userdata", 0x80, last),
+                // Leftovers of a resize script, past the end of the disk
+                ("oem_old", last + 0x100, last + 0x1FF),
+                ("tail", last - 0x0F, last + 0x10),
+            ],
+            DISK_SIZE,
+        );
+
+        assert_eq!(found[0], ("boot".into(), None));
+        assert_eq!(found[1], ("userdata".into(), None));
+        assert_eq!(
+            found[2],
+            (
+                "oem_old".into(),
+                Some(PartitionIssue::OutOfBounds {
+                    end: (last + 0x200) * SECTOR as u64,
+                    limit: DISK_SIZE
+                })
+            )
+        );
+        assert_eq!(
+            found[3],
+            (
+                "tail".into(),
+                Some(PartitionIssue::OutOfBounds {
+                    end: (last + 0x11) * SECTOR as u64,
+                    limit: DISK_SIZE
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_region_size_only_checks_the_size() {
+        let last = DISK_SIZE / SECTOR as u64 - 1;
+        let found = issues(&[("oem_old", last + 0x100, last + 0x1FF)], 0);
+        assert_eq!(found, vec![("oem_old".into(), None)]);
+    }
+
+    #[test]
+    fn zero_size_partitions_are_flagged() {
+        // The DA lists boot regions the storage doesn't have with a size of 0
+        let empty =
+            Partition::new("preloader_backup", 0, 0, PartitionKind::Nand(NandPartition::Whole));
+        assert_eq!(check_geometry(&empty, DISK_SIZE), Some(PartitionIssue::ZeroSize));
+        assert_eq!(check_geometry(&empty, 0), Some(PartitionIssue::ZeroSize));
+
+        let end = Partition::new("last", 0x1000, DISK_SIZE - 0x1000, PartitionKind::Unknown);
+        assert_eq!(check_geometry(&end, DISK_SIZE), None);
+    }
+}
@@ -0,0 +1,617 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::{le_u16, le_u32, le_u64};
+
+/// Partition holding the dynamic partitions.
+pub const SUPER_PARTITION: &str = "super";
+
+/// Bytes reserved at the start of super, before the geometry.
+pub const LP_PARTITION_RESERVED_BYTES: u64 = 4096;
+/// Size of each geometry copy. The backup copy follows the primary one.
+pub const LP_METADATA_GEOMETRY_SIZE: usize = 4096;
+/// Extents are expressed in 512 bytes sectors, whatever the storage block size.
+pub const LP_SECTOR_SIZE: u64 = 512;
+
+const LP_METADATA_GEOMETRY_MAGIC: u32 = 0x616C_4467;
+const LP_METADATA_HEADER_MAGIC: u32 = 0x414C_5030;
+const LP_METADATA_MAJOR_VERSION: u16 = 10;
+
+/// Fields of the geometry up to the logical block size, checksum included.
+const GEOMETRY_STRUCT_SIZE: usize = 52;
+/// Size of the v1.0 header, later versions only append fields.
+const HEADER_MIN_SIZE: usize = 128;
+
+const PARTITION_ENTRY_SIZE: usize = 52;
+const EXTENT_ENTRY_SIZE: usize = 24;
+const GROUP_ENTRY_SIZE: usize = 48;
+const BLOCK_DEVICE_ENTRY_SIZE: usize = 64;
+const NAME_SIZE: usize = 36;
+
+const TARGET_TYPE_LINEAR: u32 = 0;
+const TARGET_TYPE_ZERO: u32 = 1;
+
+/// The partition is mounted read-only.
+pub const LP_PARTITION_ATTR_READONLY: u32 = 1 << 0;
+/// The partition name gets the slot suffix appended when flashing.
+pub const LP_PARTITION_ATTR_SLOT_SUFFIXED: u32 = 1 << 1;
+/// The partition was created or resized by a virtual A/B update.
+pub const LP_PARTITION_ATTR_UPDATED: u32 = 1 << 2;
+/// The partition exists in the metadata but isn't mapped.
+pub const LP_PARTITION_ATTR_DISABLED: u32 = 1 << 3;
+
+/// Location and size of the metadata copies, stored right after the reserved bytes of super.
+#[derive(Debug, Clone, Copy)]
+pub struct LpGeometry {
+    /// Space reserved for each metadata copy
+    pub metadata_max_size: u32,
+    /// Number of metadata copies, one per slot
+    pub metadata_slot_count: u32,
+    pub logical_block_size: u32,
+}
+
+impl LpGeometry {
+    /// Offset of the geometry within super.
+    pub const OFFSET: u64 = LP_PARTITION_RESERVED_BYTES;
+
+    /// Parses the primary geometry, [`LP_METADATA_GEOMETRY_SIZE`] bytes read at [`Self::OFFSET`].
+    pub fn parse(data: &[u8]) -> Result<LpGeometry> {
+        if le_u32!(data, 0)? != LP_METADATA_GEOMETRY_MAGIC {
+            return Err(Error::penumbra("Invalid LP geometry magic, super has no LP metadata"));
+        }
+
+        let struct_size = le_u32!(data, 4)? as usize;
+        let geometry = data
+            .get(..struct_size)
+            .filter(|_| struct_size >= GEOMETRY_STRUCT_SIZE)
+            .ok_or_else(|| Error::malformed("LP geometry size out of bounds", data))?;
+        verify_checksum(geometry, 8, "LP geometry")?;
+
+        let metadata_max_size = le_u32!(geometry, 40)?;
+        let metadata_slot_count = le_u32!(geometry, 44)?;
+        if metadata_max_size as usize <= HEADER_MIN_SIZE || metadata_slot_count == 0 {
+            return Err(Error::malformed("LP geometry has no room for metadata", geometry));
+        }
+
+        Ok(LpGeometry {
+            metadata_max_size,
+            metadata_slot_count,
+            logical_block_size: le_u32!(geometry, 48)?,
+        })
+    }
+
+    /// Offset within super of the primary metadata of `slot` (0 for `_a`, 1 for `_b`).
+    pub fn metadata_offset(&self, slot: u32) -> Result<u64> {
+        if slot >= self.metadata_slot_count {
+            return Err(Error::penumbra(format!(
+                "No LP metadata for slot {}, super only has {}",
+                slot, self.metadata_slot_count
+            )));
+        }
+
+        let primary = LpGeometry::OFFSET + 2 * LP_METADATA_GEOMETRY_SIZE as u64;
+        Ok(primary + slot as u64 * self.metadata_max_size as u64)
+    }
+}
+
+/// Where the data of an extent lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LpExtentTarget {
+    /// Mapped to `physical_sector` of the given block device (0 being super itself).
+    Linear { block_device: u32, physical_sector: u64 },
+    /// Reads as zeros, nothing is stored.
+    Zero,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LpExtent {
+    pub num_sectors: u64,
+    pub target: LpExtentTarget,
+}
+
+impl LpExtent {
+    pub fn size(&self) -> u64 {
+        self.num_sectors * LP_SECTOR_SIZE
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LpGroup {
+    pub name: String,
+    /// Maximum size of all the partitions of the group together, 0 if unlimited
+    pub maximum_size: u64,
+}
+
+/// A partition living inside super.
+#[derive(Debug, Clone)]
+pub struct DynamicPartition {
+    pub name: String,
+    /// `LP_PARTITION_ATTR_*` flags
+    pub attributes: u32,
+    pub group: String,
+    /// Extents in logical order, concatenated they make the partition
+    pub extents: Vec<LpExtent>,
+}
+
+impl DynamicPartition {
+    pub fn size(&self) -> u64 {
+        self.extents.iter().map(LpExtent::size).sum()
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.attributes & LP_PARTITION_ATTR_READONLY != 0
+    }
+}
+
+/// The LP metadata of one slot, listing the dynamic partitions of super.
+#[derive(Debug, Clone)]
+pub struct LpMetadata {
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub partitions: Vec<DynamicPartition>,
+    pub groups: Vec<LpGroup>,
+    /// Partition names of the block devices holding the extents, super first
+    pub block_devices: Vec<String>,
+}
+
+/// A table of the metadata: where it starts within the tables, and how many entries it has.
+struct TableDescriptor {
+    offset: usize,
+    num_entries: usize,
+    entry_size: usize,
+}
+
+impl TableDescriptor {
+    fn parse(header: &[u8], offset: usize) -> Result<TableDescriptor> {
+        Ok(TableDescriptor {
+            offset: le_u32!(header, offset)? as usize,
+            num_entries: le_u32!(header, offset + 4)? as usize,
+            entry_size: le_u32!(header, offset + 8)? as usize,
+        })
+    }
+
+    /// Returns each entry of the table, checking they fit in `tables`.
+    fn entries<'a>(&self, tables: &'a [u8], min_size: usize, what: &str) -> Result<Vec<&'a [u8]>> {
+        if self.entry_size < min_size {
+            return Err(Error::penumbra(format!(
+                "LP {} entries are 0x{:X} bytes, expected at least 0x{:X}",
+                what, self.entry_size, min_size
+            )));
+        }
+
+        let len = self.num_entries.checked_mul(self.entry_size);
+        let table = len
+            .and_then(|len| tables.get(self.offset..self.offset.checked_add(len)?))
+            .ok_or_else(|| Error::penumbra(format!("LP {} table out of bounds", what)))?;
+
+        Ok(table.chunks_exact(self.entry_size).collect())
+    }
+}
+
+impl LpMetadata {
+    /// Parses the metadata of a slot, read at [`LpGeometry::metadata_offset`].
+    /// `data` may be longer than the metadata, up to `metadata_max_size`.
+    pub fn parse(data: &[u8]) -> Result<LpMetadata> {
+        if le_u32!(data, 0)? != LP_METADATA_HEADER_MAGIC {
+            return Err(Error::penumbra("Invalid LP metadata header magic"));
+        }
+
+        let major_version = le_u16!(data, 4)?;
+        let minor_version = le_u16!(data, 6)?;
+        if major_version != LP_METADATA_MAJOR_VERSION {
+            return Err(Error::penumbra(format!(
+                "Unsupported LP metadata version {}.{}",
+                major_version, minor_version
+            )));
+        }
+
+        let header_size = le_u32!(data, 8)? as usize;
+        let header = data
+            .get(..header_size)
+            .filter(|_| header_size >= HEADER_MIN_SIZE)
+            .ok_or_else(|| Error::malformed("LP metadata header size out of bounds", data))?;
+        verify_checksum(header, 12, "LP metadata header")?;
+
+        let tables_size = le_u32!(header, 44)? as usize;
+        let tables = data
+            .get(header_size..header_size + tables_size)
+            .ok_or_else(|| Error::penumbra("LP metadata tables out of bounds"))?;
+        if header.get(48..80) != Some(Sha256::digest(tables).as_slice()) {
+            return Err(Error::penumbra("LP metadata tables checksum mismatch"));
+        }
+
+        let partitions = TableDescriptor::parse(header, 80)?;
+        let extents = TableDescriptor::parse(header, 92)?;
+        let groups = TableDescriptor::parse(header, 104)?;
+        let block_devices = TableDescriptor::parse(header, 116)?;
+
+        let extents = extents
+            .entries(tables, EXTENT_ENTRY_SIZE, "extent")?
+            .into_iter()
+            .map(parse_extent)
+            .collect::<Result<Vec<_>>>()?;
+
+        let groups = groups
+            .entries(tables, GROUP_ENTRY_SIZE, "group")?
+            .into_iter()
+            .map(|entry| Ok(LpGroup { name: parse_name(entry), maximum_size: le_u64!(entry, 40)? }))
+            .collect::<Result<Vec<_>>>()?;
+
+        let partitions = partitions
+            .entries(tables, PARTITION_ENTRY_SIZE, "partition")?
+            .into_iter()
+            .map(|entry| parse_partition(entry, &extents, &groups))
+            .collect::<Result<Vec<_>>>()?;
+
+        let block_devices = block_devices
+            .entries(tables, BLOCK_DEVICE_ENTRY_SIZE, "block device")?
+            .into_iter()
+            .map(|entry| parse_name(entry.get(24..).unwrap_or_default()))
+            .collect();
+
+        Ok(LpMetadata { major_version, minor_version, partitions, groups, block_devices })
+    }
+
+    pub fn get_partition(&self, name: &str) -> Option<&DynamicPartition> {
+        self.partitions.iter().find(|p| p.name == name)
+    }
+}
+
+/// Returns the metadata slot for a slot suffix, the one of `_a` when there is none.
+pub fn metadata_slot(suffix: &str) -> u32 {
+    match suffix {
+        "_b" => 1,
+        _ => 0,
+    }
+}
+
+fn parse_extent(entry: &[u8]) -> Result<LpExtent> {
+    let num_sectors = le_u64!(entry, 0)?;
+    let target = match le_u32!(entry, 8)? {
+        TARGET_TYPE_LINEAR => LpExtentTarget::Linear {
+            physical_sector: le_u64!(entry, 12)?,
+            block_device: le_u32!(entry, 20)?,
+        },
+        TARGET_TYPE_ZERO => LpExtentTarget::Zero,
+        other => {
+            return Err(Error::penumbra(format!("Unknown LP extent target type {}", other)));
+        }
+    };
+
+    Ok(LpExtent { num_sectors, target })
+}
+
+fn parse_partition(
+    entry: &[u8],
+    extents: &[LpExtent],
+    groups: &[LpGroup],
+) -> Result<DynamicPartition> {
+    let name = parse_name(entry);
+    let first_extent = le_u32!(entry, 40)? as usize;
+    let num_extents = le_u32!(entry, 44)? as usize;
+    let group_index = le_u32!(entry, 48)? as usize;
+
+    let extents = first_extent
+        .checked_add(num_extents)
+        .and_then(|end| extents.get(first_extent..end))
+        .ok_or_else(|| Error::penumbra(format!("Extents of '{}' out of bounds", name)))?
+        .to_vec();
+    let group = groups
+        .get(group_index)
+        .ok_or_else(|| Error::penumbra(format!("Group of '{}' out of bounds", name)))?
+        .name
+        .clone();
+
+    Ok(DynamicPartition { name, attributes: le_u32!(entry, 36)?, group, extents })
+}
+
+/// Reads a NUL padded name, as stored at the start of partitions, groups and block devices.
+fn parse_name(entry: &[u8]) -> String {
+    let name = entry.get(..NAME_SIZE).unwrap_or(entry);
+    let name = name.split(|&b| b == 0).next().unwrap_or_default();
+    String::from_utf8_lossy(name).into_owned()
+}
+
+/// Checks the SHA-256 stored at `offset` in `data`, computed with the checksum zeroed.
+fn verify_checksum(data: &[u8], offset: usize, what: &str) -> Result<()> {
+    let stored = data
+        .get(offset..offset + 32)
+        .ok_or_else(|| Error::malformed(format!("{} checksum out of bounds", what), data))?;
+
+    let mut zeroed = data.to_vec();
+    zeroed.get_mut(offset..offset + 32).unwrap_or_default().fill(0);
+
+    if Sha256::digest(&zeroed).as_slice() != stored {
+        return Err(Error::penumbra(format!("{} checksum mismatch", what)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn name(name: &str, size: usize) -> Vec<u8> {
+        let mut entry = vec![0u8; size];
+        entry[..name.len()].copy_from_slice(name.as_bytes());
+        entry
+    }
+
+    fn seal(data: &mut [u8], offset: usize) {
+        let digest = Sha256::digest(&*data);
+        data[offset..offset + 32].copy_from_slice(&digest);
+    }
+
+    /// Geometry as lpmake writes it: 64 KiB per metadata copy, 4 KiB blocks.
+    fn geometry(slots: u32) -> Vec<u8> {
+        let mut data = vec![0u8; LP_METADATA_GEOMETRY_SIZE];
+        data[0..4].copy_from_slice(&LP_METADATA_GEOMETRY_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&(GEOMETRY_STRUCT_SIZE as u32).to_le_bytes());
+        data[40..44].copy_from_slice(&0x1_0000u32.to_le_bytes());
+        data[44..48].copy_from_slice(&slots.to_le_bytes());
+        data[48..52].copy_from_slice(&4096u32.to_le_bytes());
+        seal(&mut data[..GEOMETRY_STRUCT_SIZE], 8);
+        data
+    }
+
+    fn partition(part: &str, attributes: u32, extents: (u32, u32), group: u32) -> Vec<u8> {
+        let mut entry = name(part, PARTITION_ENTRY_SIZE);
+        entry[36..40].copy_from_slice(&attributes.to_le_bytes());
+        entry[40..44].copy_from_slice(&extents.0.to_le_bytes());
+        entry[44..48].copy_from_slice(&extents.1.to_le_bytes());
+        entry[48..52].copy_from_slice(&group.to_le_bytes());
+        entry
+    }
+
+    fn linear(num_sectors: u64, physical_sector: u64) -> Vec<u8> {
+        let mut entry = vec![0u8; EXTENT_ENTRY_SIZE];
+        entry[0..8].copy_from_slice(&num_sectors.to_le_bytes());
+        entry[8..12].copy_from_slice(&TARGET_TYPE_LINEAR.to_le_bytes());
+        entry[12..20].copy_from_slice(&physical_sector.to_le_bytes());
+        entry
+    }
+
+    fn zero(num_sectors: u64) -> Vec<u8> {
+        let mut entry = vec![0u8; EXTENT_ENTRY_SIZE];
+        entry[0..8].copy_from_slice(&num_sectors.to_le_bytes());
+        entry[8..12].copy_from_slice(&TARGET_TYPE_ZERO.to_le_bytes());
+        entry
+    }
+
+    fn group(group: &str, maximum_size: u64) -> Vec<u8> {
+        let mut entry = name(group, GROUP_ENTRY_SIZE);
+        entry[40..48].copy_from_slice(&maximum_size.to_le_bytes());
+        entry
+    }
+
+    fn block_device(partition: &str, size: u64) -> Vec<u8> {
+        let mut entry = vec![0u8; BLOCK_DEVICE_ENTRY_SIZE];
+        // First logical sector, right after both copies of the metadata of 2 slots
+        entry[0..8].copy_from_slice(&2048u64.to_le_bytes());
+        entry[12..16].copy_from_slice(&(1024 * 1024u32).to_le_bytes());
+        entry[16..24].copy_from_slice(&size.to_le_bytes());
+        entry[24..24 + partition.len()].copy_from_slice(partition.as_bytes());
+        entry
+    }
+
+    /// Metadata with the tables in the order lpmake writes them, padded to its maximum size.
+    /// A 10.2 header is 256 bytes, adding flags to the 128 bytes of 10.0.
+    fn metadata(minor_version: u16, tables: [Vec<Vec<u8>>; 4]) -> Vec<u8> {
+        let header_size = if minor_version >= 2 { 256 } else { HEADER_MIN_SIZE };
+        let mut header = vec![0u8; header_size];
+        header[0..4].copy_from_slice(&LP_METADATA_HEADER_MAGIC.to_le_bytes());
+        header[4..6].copy_from_slice(&LP_METADATA_MAJOR_VERSION.to_le_bytes());
+        header[6..8].copy_from_slice(&minor_version.to_le_bytes());
+        header[8..12].copy_from_slice(&(header_size as u32).to_le_bytes());
+
+        let mut data = Vec::new();
+        for (i, table) in tables.iter().enumerate() {
+            let sizes = [
+                PARTITION_ENTRY_SIZE,
+                EXTENT_ENTRY_SIZE,
+                GROUP_ENTRY_SIZE,
+                BLOCK_DEVICE_ENTRY_SIZE,
+            ];
+            let entry_size = table.first().map_or(sizes[i], Vec::len);
+            let descriptor = 80 + i * 12;
+            header[descriptor..descriptor + 4].copy_from_slice(&(data.len() as u32).to_le_bytes());
+            header[descriptor + 4..descriptor + 8]
+                .copy_from_slice(&(table.len() as u32).to_le_bytes());
+            header[descriptor + 8..descriptor + 12]
+                .copy_from_slice(&(entry_size as u32).to_le_bytes());
+            data.extend(table.concat());
+        }
+
+        header[44..48].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        header[48..80].copy_from_slice(&Sha256::digest(&data));
+        seal(&mut header, 12);
+
+        let mut metadata = [header, data].concat();
+        metadata.resize(0x1_0000, 0);
+        metadata
+    }
+
+    /// Slot `_a` of a virtual A/B device right after flashing: the `_b` partitions exist,
+    /// but have no extents yet.
+    fn ab_metadata(minor_version: u16) -> Vec<u8> {
+        metadata(minor_version, [
+            vec![
+                partition("system_a", LP_PARTITION_ATTR_READONLY, (0, 2), 1),
+                partition("system_b", LP_PARTITION_ATTR_READONLY, (2, 0), 2),
+                partition("vendor_a", LP_PARTITION_ATTR_READONLY, (2, 1), 1),
+                partition("vendor_b", LP_PARTITION_ATTR_READONLY, (3, 0), 2),
+                partition("product_a", LP_PARTITION_ATTR_UPDATED, (3, 2), 1),
+            ],
+            vec![
+                linear(0x20_0000, 0x800),
+                linear(0x8_0000, 0x40_0800),
+                linear(0x10_0000, 0x20_0800),
+                linear(0x4_0000, 0x30_0800),
+                zero(0x800),
+            ],
+            vec![group("default", 0), group("main_a", 4 * GIB), group("main_b", 4 * GIB)],
+            vec![block_device("super", 9 * GIB)],
+        ])
+    }
+
+    #[test]
+    fn geometry_parses() {
+        let geometry = LpGeometry::parse(&geometry(2)).unwrap();
+        assert_eq!(geometry.metadata_max_size, 0x1_0000);
+        assert_eq!(geometry.metadata_slot_count, 2);
+        assert_eq!(geometry.logical_block_size, 4096);
+
+        // Reserved bytes, then both geometry copies
+        assert_eq!(geometry.metadata_offset(0).unwrap(), 0x3000);
+        assert_eq!(geometry.metadata_offset(1).unwrap(), 0x1_3000);
+        assert!(geometry.metadata_offset(2).is_err());
+    }
+
+    #[test]
+    fn broken_geometries_are_refused() {
+        let mut bad_magic = geometry(2);
+        bad_magic[0] ^= 0xFF;
+        let mut bad_checksum = geometry(2);
+        bad_checksum[48] = 0x02;
+        let no_slots = geometry(0);
+        let mut bad_size = geometry(2);
+        bad_size[4..8].copy_from_slice(&0x2000u32.to_le_bytes());
+
+        for (name, data, error) in [
+            ("magic", bad_magic, "magic"),
+            ("checksum", bad_checksum, "checksum mismatch"),
+            ("slots", no_slots, "no room for metadata"),
+            ("size", bad_size, "size out of bounds"),
+        ] {
+            let err = LpGeometry::parse(&data).unwrap_err().to_string();
+            assert!(err.contains(error), "{}: {}", name, err);
+        }
+    }
+
+    #[test]
+    fn metadata_parses() {
+        for minor_version in [0, 2] {
+            let metadata = LpMetadata::parse(&ab_metadata(minor_version)).unwrap();
+            assert_eq!((metadata.major_version, metadata.minor_version), (10, minor_version));
+            assert_eq!(metadata.block_devices, ["super"]);
+
+            let groups: Vec<_> =
+                metadata.groups.iter().map(|g| (&*g.name, g.maximum_size)).collect();
+            assert_eq!(groups, [("default", 0), ("main_a", 4 * GIB), ("main_b", 4 * GIB)]);
+
+            let partitions: Vec<_> =
+                metadata.partitions.iter().map(|p| (&*p.name, &*p.group, p.size())).collect();
+            assert_eq!(partitions, [
+                ("system_a", "main_a", 0x28_0000 * LP_SECTOR_SIZE),
+                ("system_b", "main_b", 0),
+                ("vendor_a", "main_a", 0x10_0000 * LP_SECTOR_SIZE),
+                ("vendor_b", "main_b", 0),
+                ("product_a", "main_a", 0x4_0800 * LP_SECTOR_SIZE),
+            ]);
+
+            let system = metadata.get_partition("system_a").unwrap();
+            assert!(system.is_readonly());
+            assert_eq!(system.extents[1].target, LpExtentTarget::Linear {
+                block_device: 0,
+                physical_sector: 0x40_0800
+            });
+
+            let product = metadata.get_partition("product_a").unwrap();
+            assert!(!product.is_readonly());
+            assert_eq!(product.attributes, LP_PARTITION_ATTR_UPDATED);
+            assert_eq!(product.extents[1].target, LpExtentTarget::Zero);
+            assert!(metadata.get_partition("product_b").is_none());
+        }
+    }
+
+    #[test]
+    fn corrupted_metadata_is_refused() {
+        let mut bad_magic = ab_metadata(2);
+        bad_magic[3] ^= 0xFF;
+        let mut bad_version = ab_metadata(2);
+        bad_version[4] = 11;
+        let mut bad_header = ab_metadata(2);
+        bad_header[130] ^= 0x01;
+        let mut bad_tables = ab_metadata(2);
+        bad_tables[256] ^= 0x01;
+        let mut truncated = ab_metadata(0);
+        truncated.truncate(HEADER_MIN_SIZE + 100);
+
+        for (name, data, error) in [
+            ("magic", bad_magic, "header magic"),
+            ("version", bad_version, "version 11.2"),
+            ("header", bad_header, "header checksum mismatch"),
+            ("tables", bad_tables, "tables checksum mismatch"),
+            ("truncated", truncated, "tables out of bounds"),
+        ] {
+            let err = LpMetadata::parse(&data).unwrap_err().to_string();
+            assert!(err.contains(error), "{}: {}", name, err);
+        }
+    }
+
+    #[test]
+    fn inconsistent_tables_are_refused() {
+        let groups = || vec![group("default", 0)];
+        let super_ = || vec![block_device("super", 9 * GIB)];
+        let cases = [
+            (
+                "extents past the table",
+                [
+                    vec![partition("system", 0, (0, 2), 0)],
+                    vec![linear(8, 0x800)],
+                    groups(),
+                    super_(),
+                ],
+                "Extents of 'system' out of bounds",
+            ),
+            (
+                "missing group",
+                [
+                    vec![partition("system", 0, (0, 1), 1)],
+                    vec![linear(8, 0x800)],
+                    groups(),
+                    super_(),
+                ],
+                "Group of 'system' out of bounds",
+            ),
+            (
+                "unknown target",
+                [
+                    vec![],
+                    vec![[&linear(8, 0)[..8], &[2, 0, 0, 0], &[0; 12]].concat()],
+                    groups(),
+                    super_(),
+                ],
+                "Unknown LP extent target type 2",
+            ),
+            (
+                "short entries",
+                [
+                    vec![partition("system", 0, (0, 0), 0)[..40].to_vec()],
+                    vec![],
+                    groups(),
+                    super_(),
+                ],
+                "LP partition entries are 0x28 bytes, expected at least 0x34",
+            ),
+        ];
+
+        for (name, tables, error) in cases {
+            let err = LpMetadata::parse(&metadata(0, tables)).unwrap_err().to_string();
+            assert!(err.contains(error), "{}: {}", name, err);
+        }
+    }
+
+    #[test]
+    fn slots_follow_the_suffix() {
+        assert_eq!(metadata_slot("_a"), 0);
+        assert_eq!(metadata_slot("_b"), 1);
+        assert_eq!(metadata_slot(""), 0);
+    }
+}
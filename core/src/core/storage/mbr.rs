@@ -0,0 +1,213 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::fmt;
+
+/// Size of the MBR, regardless of the sector size of the storage.
+pub const MBR_SIZE: usize = 512;
+
+/// Bootstrap code and disk signature, kept as they are when regenerating the MBR.
+const PRESERVED_SIZE: usize = 444;
+const ENTRIES_OFFSET: usize = 446;
+const ENTRY_SIZE: usize = 16;
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+/// Partition type of the entry protecting a GPT disk
+const PROTECTIVE_TYPE: u8 = 0xEE;
+
+/// State of the protective MBR found in LBA0, in front of the primary GPT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MbrStatus {
+    /// A single 0xEE entry covering the disk, as the UEFI spec mandates
+    Protective,
+    /// The 0xEE entry along with legacy partitions. Valid, but some bootloaders refuse it.
+    Hybrid,
+    /// Not a usable protective MBR, with the reason
+    Broken(String),
+}
+
+impl MbrStatus {
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, MbrStatus::Protective)
+    }
+}
+
+impl fmt::Display for MbrStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MbrStatus::Protective => write!(f, "protective MBR OK"),
+            MbrStatus::Hybrid => write!(f, "hybrid MBR (protective entry plus legacy partitions)"),
+            MbrStatus::Broken(reason) => write!(f, "broken protective MBR: {}", reason),
+        }
+    }
+}
+
+struct MbrEntry {
+    kind: u8,
+    first_lba: u32,
+    sectors: u32,
+}
+
+fn parse_entry(raw: &[u8; ENTRY_SIZE]) -> MbrEntry {
+    MbrEntry {
+        kind: raw[4],
+        first_lba: u32::from_le_bytes([raw[8], raw[9], raw[10], raw[11]]),
+        sectors: u32::from_le_bytes([raw[12], raw[13], raw[14], raw[15]]),
+    }
+}
+
+/// Sectors the protective entry must cover: the whole disk after the MBR,
+/// capped to what fits in the entry.
+fn protective_sectors(total_sectors: u64) -> u32 {
+    total_sectors.saturating_sub(1).min(u32::MAX as u64) as u32
+}
+
+/// Checks the first sector of a GPT disk of `total_sectors` sectors.
+pub fn check_protective_mbr(sector: &[u8], total_sectors: u64) -> MbrStatus {
+    let Some(sector) = sector.first_chunk::<MBR_SIZE>() else {
+        return MbrStatus::Broken("first sector is truncated".into());
+    };
+
+    if sector.iter().all(|&b| b == 0) {
+        return MbrStatus::Broken("first sector is empty".into());
+    }
+
+    if sector[510..512] != BOOT_SIGNATURE {
+        return MbrStatus::Broken("missing 0x55AA boot signature".into());
+    }
+
+    let entries: Vec<MbrEntry> = sector[ENTRIES_OFFSET..ENTRIES_OFFSET + 4 * ENTRY_SIZE]
+        .chunks_exact(ENTRY_SIZE)
+        .filter_map(|raw| raw.try_into().ok())
+        .map(parse_entry)
+        .filter(|e| e.kind != 0)
+        .collect();
+
+    let Some(protective) = entries.iter().find(|e| e.kind == PROTECTIVE_TYPE) else {
+        return MbrStatus::Broken("no protective (0xEE) entry".into());
+    };
+
+    if protective.first_lba != 1 {
+        return MbrStatus::Broken(format!(
+            "protective entry starts at LBA {} instead of 1",
+            protective.first_lba
+        ));
+    }
+
+    // Some tools always write the maximum, which is tolerated by everything we know of
+    let expected = protective_sectors(total_sectors);
+    if protective.sectors != expected && protective.sectors != u32::MAX {
+        return MbrStatus::Broken(format!(
+            "protective entry covers {} sectors instead of {}",
+            protective.sectors, expected
+        ));
+    }
+
+    if entries.len() > 1 { MbrStatus::Hybrid } else { MbrStatus::Protective }
+}
+
+/// Builds a protective MBR for a disk of `total_sectors` sectors.
+/// Bootstrap code and disk signature are taken from `existing` (the current first sector),
+/// everything else is regenerated.
+pub fn protective_mbr(existing: &[u8], total_sectors: u64) -> [u8; MBR_SIZE] {
+    let mut mbr = [0u8; MBR_SIZE];
+
+    if let Some(preserved) = existing.first_chunk::<PRESERVED_SIZE>() {
+        mbr[..PRESERVED_SIZE].copy_from_slice(preserved);
+    }
+
+    let mut entry = [0u8; ENTRY_SIZE];
+    // Not bootable, CHS start 0/0/2 and end at the maximum, as the spec mandates
+    entry[1..4].copy_from_slice(&[0x00, 0x02, 0x00]);
+    entry[4] = PROTECTIVE_TYPE;
+    entry[5..8].copy_from_slice(&[0xFF, 0xFF, 0xFF]);
+    entry[8..12].copy_from_slice(&1u32.to_le_bytes());
+    entry[12..16].copy_from_slice(&protective_sectors(total_sectors).to_le_bytes());
+    mbr[ENTRIES_OFFSET..ENTRIES_OFFSET + ENTRY_SIZE].copy_from_slice(&entry);
+
+    mbr[510..512].copy_from_slice(&BOOT_SIGNATURE);
+    mbr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECTORS: u64 = 0x0100_0000;
+
+    /// Adds an entry of `kind` to the partition table of `mbr`.
+    fn with_entry(
+        mut mbr: [u8; MBR_SIZE],
+        slot: usize,
+        kind: u8,
+        first_lba: u32,
+        sectors: u32,
+    ) -> [u8; MBR_SIZE] {
+        let entry = ENTRIES_OFFSET + slot * ENTRY_SIZE;
+        mbr[entry + 4] = kind;
+        mbr[entry + 8..entry + 12].copy_from_slice(&first_lba.to_le_bytes());
+        mbr[entry + 12..entry + 16].copy_from_slice(&sectors.to_le_bytes());
+        mbr
+    }
+
+    #[test]
+    fn generated_mbr_is_protective() {
+        let mbr = protective_mbr(&[], SECTORS);
+        assert_eq!(check_protective_mbr(&mbr, SECTORS), MbrStatus::Protective);
+        assert!(check_protective_mbr(&mbr, SECTORS).is_healthy());
+    }
+
+    #[test]
+    fn bootstrap_code_is_preserved() {
+        let mut existing = [0xAB; MBR_SIZE];
+        existing[ENTRIES_OFFSET..].fill(0);
+
+        let mbr = protective_mbr(&existing, SECTORS);
+        assert_eq!(mbr[..PRESERVED_SIZE], existing[..PRESERVED_SIZE]);
+        assert_eq!(check_protective_mbr(&mbr, SECTORS), MbrStatus::Protective);
+    }
+
+    #[test]
+    fn huge_disks_are_capped() {
+        let sectors = u32::MAX as u64 * 4;
+        let mbr = protective_mbr(&[], sectors);
+        assert_eq!(check_protective_mbr(&mbr, sectors), MbrStatus::Protective);
+
+        // Tools writing the maximum on smaller disks are tolerated
+        let mbr = with_entry(mbr, 0, PROTECTIVE_TYPE, 1, u32::MAX);
+        assert_eq!(check_protective_mbr(&mbr, SECTORS), MbrStatus::Protective);
+    }
+
+    #[test]
+    fn legacy_partitions_make_it_hybrid() {
+        let mbr = with_entry(protective_mbr(&[], SECTORS), 1, 0x0C, 0x800, 0x1000);
+        let status = check_protective_mbr(&mbr, SECTORS);
+        assert_eq!(status, MbrStatus::Hybrid);
+        assert!(!status.is_healthy());
+    }
+
+    #[test]
+    fn broken_mbrs_are_reported() {
+        let healthy = protective_mbr(&[], SECTORS);
+        let broken = |sector: &[u8]| match check_protective_mbr(sector, SECTORS) {
+            MbrStatus::Broken(reason) => reason,
+            status => panic!("{:?} isn't broken", status),
+        };
+
+        assert!(broken(&healthy[..MBR_SIZE - 1]).contains("truncated"));
+        assert!(broken(&[0u8; MBR_SIZE]).contains("empty"));
+
+        let mut unsigned = healthy;
+        unsigned[511] = 0;
+        assert!(broken(&unsigned).contains("signature"));
+
+        let unprotected = with_entry(healthy, 0, 0x83, 1, 0x1000);
+        assert!(broken(&unprotected).contains("no protective"));
+
+        let misplaced = with_entry(healthy, 0, PROTECTIVE_TYPE, 2, (SECTORS - 1) as u32);
+        assert!(broken(&misplaced).contains("LBA 2"));
+
+        let short = with_entry(healthy, 0, PROTECTIVE_TYPE, 1, 0x1000);
+        assert!(broken(&short).contains("covers 4096 sectors"));
+    }
+}
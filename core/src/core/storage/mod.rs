@@ -1,23 +1,49 @@
 pub mod emmc;
 pub mod gpt;
+pub mod lp;
+pub mod mbr;
+pub mod nand;
 pub mod ufs;
 
+use std::fmt;
+
 pub use emmc::EmmcPartition;
-pub use gpt::Gpt;
+pub use gpt::{Gpt, GptType, PartitionIssue};
+pub use lp::{DynamicPartition, LpMetadata};
+pub use mbr::MbrStatus;
+pub use nand::{NandExtension, NandPartition};
 pub use ufs::UfsPartition;
 
+use crate::error::{Error, Result};
+
+/// Size of the GPT partition entries array, 128 entries of 128 bytes
+const GPT_ENTRIES_SIZE: u64 = 0x4000;
+
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StorageType {
     Unknown = 0,
     Emmc = 0x1,
+    Nand = 0x10,
     Ufs = 0x30,
 }
 
+impl fmt::Display for StorageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StorageType::Emmc => "eMMC",
+            StorageType::Ufs => "UFS",
+            StorageType::Nand => "NAND",
+            StorageType::Unknown => "unknown",
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum PartitionKind {
     Emmc(EmmcPartition),
     Ufs(UfsPartition),
+    Nand(NandPartition),
     Unknown,
 }
 
@@ -27,11 +53,13 @@ pub struct Partition {
     pub size: usize,
     pub address: u64,
     pub kind: PartitionKind,
+    /// Set when the geometry reported for the partition can't be read safely
+    pub issue: Option<PartitionIssue>,
 }
 
 impl Partition {
     pub fn new(name: &str, size: usize, address: u64, kind: PartitionKind) -> Self {
-        Self { name: name.to_string(), size, address, kind }
+        Self { name: name.to_string(), size, address, kind, issue: None }
     }
 }
 
@@ -40,6 +68,7 @@ impl PartitionKind {
         match self {
             PartitionKind::Emmc(part) => *part as u32,
             PartitionKind::Ufs(part) => *part as u32,
+            PartitionKind::Nand(part) => *part as u32,
             PartitionKind::Unknown => 0,
         }
     }
@@ -48,9 +77,144 @@ impl PartitionKind {
         match self {
             PartitionKind::Emmc(part) => part.as_str(),
             PartitionKind::Ufs(part) => part.as_str(),
+            PartitionKind::Nand(part) => part.as_str(),
             PartitionKind::Unknown => "Unknown",
         }
     }
+
+    /// Sections of `storage` that can be addressed directly, by name.
+    pub fn sections_for(storage: StorageType) -> &'static [(&'static str, PartitionKind)] {
+        match storage {
+            StorageType::Emmc => &[
+                ("user", PartitionKind::Emmc(EmmcPartition::User)),
+                ("boot1", PartitionKind::Emmc(EmmcPartition::Boot1)),
+                ("boot2", PartitionKind::Emmc(EmmcPartition::Boot2)),
+                ("rpmb", PartitionKind::Emmc(EmmcPartition::Rpmb)),
+            ],
+            StorageType::Ufs => &[
+                ("user", PartitionKind::Ufs(UfsPartition::Lu2)),
+                ("lu0", PartitionKind::Ufs(UfsPartition::Lu0)),
+                ("lu1", PartitionKind::Ufs(UfsPartition::Lu1)),
+                ("lu2", PartitionKind::Ufs(UfsPartition::Lu2)),
+                ("rpmb", PartitionKind::Ufs(UfsPartition::Lu3)),
+            ],
+            StorageType::Nand => &[("user", PartitionKind::Nand(NandPartition::Whole))],
+            StorageType::Unknown => &[],
+        }
+    }
+
+    /// Parses a section name (`user`, `boot1`, `lu0`...) for the given storage type.
+    /// Sections the storage doesn't have, like `boot1` on UFS, are refused with the list
+    /// of the ones it has.
+    pub fn from_str_for(name: &str, storage: StorageType) -> Result<PartitionKind> {
+        let sections = Self::sections_for(storage);
+        if let Some((_, kind)) = sections.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            return Ok(*kind);
+        }
+
+        if sections.is_empty() {
+            return Err(Error::unsupported(format!(
+                "No '{}' section: the storage type of the device is unknown",
+                name
+            )));
+        }
+        let valid: Vec<&str> = sections.iter().map(|(n, _)| *n).collect();
+        Err(Error::unsupported(format!(
+            "No '{}' section on {} storage, valid sections are: {}",
+            name,
+            storage,
+            valid.join(", ")
+        )))
+    }
+}
+
+/// Geometry and identification of the storage, as reported by the DA.
+///
+/// Neither DA protocol reports the eMMC/UFS lifetime estimates along with it,
+/// so they're not part of it.
+#[derive(Debug, Clone)]
+pub struct StorageInfo {
+    pub kind: StorageType,
+    pub block_size: u32,
+    /// Size of all the regions together
+    pub total_size: u64,
+    /// Size of the user region (eMMC USER, UFS LU2)
+    pub user_size: u64,
+    /// Size of the first boot region (eMMC BOOT1, UFS LU0)
+    pub boot1_size: u64,
+    /// Size of the second boot region (eMMC BOOT2, UFS LU1)
+    pub boot2_size: u64,
+    /// Size of the RPMB region, 0 if not reported
+    pub rpmb_size: u64,
+    /// Raw CID register (eMMC), product identification (UFS) or ID (NAND)
+    pub cid: Vec<u8>,
+    /// Product name, if the device reports a readable one
+    pub model: Option<String>,
+    pub fw_version: Option<String>,
+    pub serial: Option<String>,
+}
+
+impl StorageInfo {
+    /// Size of a section of the storage, `None` when it isn't one of its sections
+    /// or its size wasn't reported.
+    pub fn section_size(&self, kind: PartitionKind) -> Option<u64> {
+        let size = match kind {
+            PartitionKind::Emmc(part) => match part {
+                EmmcPartition::User => self.user_size,
+                EmmcPartition::Boot1 => self.boot1_size,
+                EmmcPartition::Boot2 => self.boot2_size,
+                EmmcPartition::Rpmb => self.rpmb_size,
+                _ => return None,
+            },
+            PartitionKind::Ufs(part) => match part {
+                UfsPartition::Lu2 => self.user_size,
+                UfsPartition::Lu0 => self.boot1_size,
+                UfsPartition::Lu1 => self.boot2_size,
+                UfsPartition::Lu3 => self.rpmb_size,
+                _ => return None,
+            },
+            PartitionKind::Nand(NandPartition::Whole) => self.user_size,
+            _ => return None,
+        };
+        (size > 0).then_some(size)
+    }
+
+    /// Checks that `size` bytes at `address` fit in `section`, so that a wrong unit or
+    /// offset is refused instead of wrapping around or hitting the next section.
+    /// Sections whose size isn't known aren't checked.
+    pub fn check_range(&self, address: u64, size: u64, section: PartitionKind) -> Result<()> {
+        let Some(capacity) = self.section_size(section) else {
+            return Ok(());
+        };
+
+        // Wide enough to never overflow, so a wrapping range reports its real end
+        let end = address as u128 + size as u128;
+        if end > capacity as u128 {
+            return Err(Error::penumbra(format!(
+                "Range ends at 0x{:X}, past the end of {} (0x{:X} bytes)",
+                end,
+                section.as_str(),
+                capacity
+            )));
+        }
+        Ok(())
+    }
+
+    /// Bytes at the start of the user area holding the protective MBR and the primary GPT:
+    /// LBA 0 and 1, then 16 KiB of partition entries (LBA 0 to 33 with 512 bytes blocks).
+    pub fn gpt_size(&self) -> u64 {
+        2 * self.block_size as u64 + GPT_ENTRIES_SIZE
+    }
+
+    /// Whether `size` bytes at `address` overwrite part of the protective MBR or primary GPT.
+    /// NAND has no GPT, so nothing overlaps it there.
+    pub fn overlaps_gpt(&self, address: u64, size: u64, section: PartitionKind) -> bool {
+        let user = matches!(
+            section,
+            PartitionKind::Emmc(EmmcPartition::User) | PartitionKind::Ufs(UfsPartition::Lu2)
+        );
+        user && size > 0 && address < self.gpt_size()
+    }
 }
 
 #[async_trait::async_trait]
@@ -66,8 +230,165 @@ pub trait Storage: Send + Sync {
     fn get_pl1_size(&self) -> u64;
     fn get_pl2_size(&self) -> u64;
     fn get_user_size(&self) -> u64;
+
+    fn info(&self) -> StorageInfo;
+
+    /// NAND parameters to send along with flash operations, `None` for other storages.
+    fn nand_extension(&self) -> Option<NandExtension> {
+        None
+    }
+}
+
+/// Decodes a fixed-size text field, padded with NULs or spaces.
+/// Empty or non-printable fields, which some devices report, give `None`.
+pub(crate) fn ascii_field(bytes: &[u8]) -> Option<String> {
+    let text = bytes.iter().map(|&b| b as char).collect::<String>();
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_ascii_whitespace());
+
+    if text.is_empty() || !text.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+        return None;
+    }
+    Some(text.to_string())
 }
 
 pub fn is_pl_part(name: &str) -> bool {
     matches!(name, "preloader" | "preloader_backup")
 }
+
+/// What a partition is for, whatever the name the device gives it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionRole {
+    /// Factory reset protection, tying the device to its Google account
+    Frp,
+    /// Calibration data and IMEI, as written at the factory
+    Nvram,
+    /// Runtime copy of the calibration data
+    Nvdata,
+    /// Lock state of the bootloader
+    Seccfg,
+    /// Bootloader control block, see [`crate::core::bcb`]
+    Misc,
+}
+
+impl PartitionRole {
+    /// Names the role goes by across devices, in the order they're looked up.
+    pub fn candidates(&self) -> &'static [&'static str] {
+        match self {
+            PartitionRole::Frp => &["frp", "config", "persistent"],
+            PartitionRole::Nvram => &["nvram"],
+            PartitionRole::Nvdata => &["nvdata"],
+            PartitionRole::Seccfg => &["seccfg"],
+            PartitionRole::Misc => &["misc", "para"],
+        }
+    }
+
+    /// Returns the partition holding the role, trying each candidate name in turn.
+    pub fn find<'a>(&self, partitions: &'a [Partition]) -> Option<&'a Partition> {
+        self.candidates()
+            .iter()
+            .find_map(|name| partitions.iter().find(|p| p.name.eq_ignore_ascii_case(name)))
+    }
+}
+
+impl fmt::Display for PartitionRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PartitionRole::Frp => "FRP",
+            PartitionRole::Nvram => "NVRAM",
+            PartitionRole::Nvdata => "NVDATA",
+            PartitionRole::Seccfg => "seccfg",
+            PartitionRole::Misc => "misc",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(names: &[&str]) -> Vec<Partition> {
+        names.iter().map(|name| Partition::new(name, 0x1000, 0, PartitionKind::Unknown)).collect()
+    }
+
+    #[test]
+    fn role_found_under_any_alias() {
+        for alias in PartitionRole::Frp.candidates() {
+            let partitions = table(&["boot", &alias.to_uppercase(), "userdata"]);
+            let found = PartitionRole::Frp.find(&partitions).map(|p| p.name.to_lowercase());
+            assert_eq!(found.as_deref(), Some(*alias));
+        }
+
+        assert!(PartitionRole::Frp.find(&table(&["boot", "userdata"])).is_none());
+    }
+
+    #[test]
+    fn section_names_per_storage() {
+        let boot2 = PartitionKind::from_str_for("boot2", StorageType::Emmc).unwrap();
+        assert!(matches!(boot2, PartitionKind::Emmc(EmmcPartition::Boot2)));
+        let lu1 = PartitionKind::from_str_for("LU1", StorageType::Ufs).unwrap();
+        assert!(matches!(lu1, PartitionKind::Ufs(UfsPartition::Lu1)));
+        let user = PartitionKind::from_str_for("user", StorageType::Ufs).unwrap();
+        assert!(matches!(user, PartitionKind::Ufs(UfsPartition::Lu2)));
+
+        let err = PartitionKind::from_str_for("boot1", StorageType::Ufs).unwrap_err();
+        assert!(err.to_string().contains("lu0, lu1, lu2"));
+        assert!(PartitionKind::from_str_for("lu1", StorageType::Emmc).is_err());
+        assert!(PartitionKind::from_str_for("user", StorageType::Unknown).is_err());
+    }
+
+    fn emmc(block_size: u32) -> StorageInfo {
+        StorageInfo {
+            kind: StorageType::Emmc,
+            block_size,
+            total_size: 0x1_0040_0000,
+            user_size: 0x1_0000_0000,
+            boot1_size: 0x40_0000,
+            boot2_size: 0x40_0000,
+            rpmb_size: 0,
+            cid: Vec::new(),
+            model: None,
+            fw_version: None,
+            serial: None,
+        }
+    }
+
+    #[test]
+    fn range_up_to_the_section_end() {
+        let info = emmc(512);
+        let boot1 = PartitionKind::Emmc(EmmcPartition::Boot1);
+
+        assert!(info.check_range(0, 0x40_0000, boot1).is_ok());
+        assert!(info.check_range(0x3F_F000, 0x1000, boot1).is_ok());
+
+        let err = info.check_range(0x3F_F000, 0x1001, boot1).unwrap_err();
+        assert!(err.to_string().contains("0x400001"));
+        assert!(err.to_string().contains("0x400000 bytes"));
+
+        assert!(info.check_range(u64::MAX, 2, boot1).is_err());
+        // No size reported for RPMB, nothing to check against
+        assert!(info.check_range(0, u64::MAX, PartitionKind::Emmc(EmmcPartition::Rpmb)).is_ok());
+    }
+
+    #[test]
+    fn gpt_overlap_in_user_area() {
+        let info = emmc(512);
+        let user = PartitionKind::Emmc(EmmcPartition::User);
+
+        // LBA 0 to 33
+        assert_eq!(info.gpt_size(), 34 * 512);
+        assert!(info.overlaps_gpt(0, 1, user));
+        assert!(info.overlaps_gpt(33 * 512, 512, user));
+        assert!(!info.overlaps_gpt(34 * 512, 512, user));
+        assert!(!info.overlaps_gpt(0, 0, user));
+        assert!(!info.overlaps_gpt(0, 0x1000, PartitionKind::Emmc(EmmcPartition::Boot1)));
+
+        // 4 KiB blocks fit the entries in LBA 2 to 5
+        assert_eq!(emmc(4096).gpt_size(), 6 * 4096);
+    }
+
+    #[test]
+    fn role_prefers_earlier_candidates() {
+        let partitions = table(&["para", "misc"]);
+        assert_eq!(PartitionRole::Misc.find(&partitions).unwrap().name, "misc");
+    }
+}
@@ -0,0 +1,238 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use async_trait::async_trait;
+
+use crate::core::storage::{PartitionKind, Storage, StorageInfo, StorageType, ascii_field};
+use crate::error::{Error, Result};
+use crate::utilities::xml::{get_tag, get_tag_usize};
+use crate::{le_u32, le_u64};
+
+/// NAND kinds, as reported by the DA.
+const NAND_SLC: u32 = 0x11;
+const NAND_MLC: u32 = 0x12;
+const NAND_TLC: u32 = 0x13;
+const NAND_AMLC: u32 = 0x14;
+
+/// Represents NAND storage information.
+#[derive(Debug)]
+pub struct NandInfo {
+    /// NAND kind (generic, SLC, MLC, TLC, AMLC or SPI NAND)
+    pub kind: u32,
+    /// Page size in bytes, the unit of reads and writes.
+    pub page_size: u32,
+    /// Erase block size in bytes.
+    pub block_size: u32,
+    /// Spare (OOB) bytes of each page.
+    pub spare_size: u32,
+    /// Size of the whole NAND in bytes.
+    pub total_size: u64,
+    /// Size left once the blocks reserved for bad block management are taken out.
+    pub available_size: u64,
+    /// Whether the DA manages bad blocks with a BMT.
+    pub bmt_exist: bool,
+    /// NAND ID, as read from the chip.
+    pub id: Vec<u8>,
+}
+
+/// Represents NAND partitions types.
+/// Unlike eMMC and UFS, NAND has a single region holding everything, preloader included.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NandPartition {
+    /// Fallback case, should not be used
+    Unknown = 0,
+    /// The whole NAND
+    Whole = 1,
+}
+
+impl NandPartition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NandPartition::Whole => "NAND-WHOLE",
+            NandPartition::Unknown => "NAND-UNKNOWN", // Assumed to be unreachable
+        }
+    }
+}
+
+/// NAND parameters of the XFlash read, write and erase commands.
+/// Other storages send zeros in their place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NandExtension {
+    /// How cells are used: 0 for SLC, 1 for MLC, 2 for TLC, 3 for AMLC
+    pub cell_usage: u32,
+    /// 0 for logical addresses, going through bad block management, 1 for physical ones
+    pub addr_type: u32,
+    pub bin_type: u32,
+    /// 0 for page data only, 1 for page data along with the spare bytes
+    pub operation_type: u32,
+    pub sys_slc_percent: u32,
+    pub usr_slc_percent: u32,
+    pub phy_max_size: u32,
+}
+
+impl NandExtension {
+    /// The parameter block, as sent after the address and size.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let fields = [
+            self.cell_usage,
+            self.addr_type,
+            self.bin_type,
+            self.operation_type,
+            self.sys_slc_percent,
+            self.usr_slc_percent,
+            self.phy_max_size,
+            0, // reserved
+        ];
+
+        let mut bytes = [0u8; 32];
+        for (dst, field) in bytes.chunks_exact_mut(4).zip(fields) {
+            dst.copy_from_slice(&field.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// Represents NAND storage device.
+pub struct NandStorage {
+    /// NAND storage information.
+    pub info: NandInfo,
+}
+
+#[async_trait]
+impl Storage for NandStorage {
+    fn kind(&self) -> StorageType {
+        StorageType::Nand
+    }
+
+    fn block_size(&self) -> u32 {
+        self.info.block_size
+    }
+
+    fn total_size(&self) -> u64 {
+        self.info.total_size
+    }
+
+    fn get_user_part(&self) -> PartitionKind {
+        PartitionKind::Nand(NandPartition::Whole)
+    }
+
+    fn get_pl_part1(&self) -> PartitionKind {
+        PartitionKind::Nand(NandPartition::Whole)
+    }
+
+    fn get_pl_part2(&self) -> PartitionKind {
+        PartitionKind::Nand(NandPartition::Whole)
+    }
+
+    // The preloader sits in the first blocks of the NAND, and is listed by the
+    // partition table like any other partition. There are no boot regions.
+    fn get_pl1_size(&self) -> u64 {
+        0
+    }
+
+    fn get_pl2_size(&self) -> u64 {
+        0
+    }
+
+    fn get_user_size(&self) -> u64 {
+        self.info.available_size
+    }
+
+    fn info(&self) -> StorageInfo {
+        StorageInfo {
+            kind: StorageType::Nand,
+            block_size: self.info.block_size,
+            total_size: self.info.total_size,
+            user_size: self.info.available_size,
+            boot1_size: 0,
+            boot2_size: 0,
+            rpmb_size: 0,
+            cid: self.info.id.clone(),
+            model: ascii_field(&self.info.id),
+            fw_version: None,
+            serial: None,
+        }
+    }
+
+    fn nand_extension(&self) -> Option<NandExtension> {
+        let cell_usage = match self.info.kind {
+            NAND_MLC => 1,
+            NAND_TLC => 2,
+            NAND_AMLC => 3,
+            // SLC, SPI NAND and generic NAND
+            _ => 0,
+        };
+
+        // Logical addresses and page data only, like the partition table describes them
+        Some(NandExtension { cell_usage, ..Default::default() })
+    }
+}
+
+impl NandStorage {
+    /// Parses the response to XFlash's GetNandInfo.
+    pub fn from_response(data: &[u8]) -> Result<Self> {
+        if data.len() < 0x2D {
+            return Err(Error::io("NAND response data too short"));
+        }
+
+        // Format:
+        // Kind u32, page size u32, block size u32, spare size u32
+        // Total size u64, available size u64
+        // BMT exists u8, NAND ID 12 bytes
+        let kind = le_u32!(data, 0)?;
+        let page_size = le_u32!(data, 4)?;
+        let block_size = le_u32!(data, 8)?;
+        let spare_size = le_u32!(data, 12)?;
+        let total_size = le_u64!(data, 16)?;
+        let available_size = le_u64!(data, 24)?;
+        let bmt_exist = data.get(32).is_some_and(|&b| b != 0);
+        let id = data
+            .get(33..45)
+            .ok_or_else(|| Error::malformed("NAND ID out of bounds", data))?
+            .to_vec();
+
+        Ok(NandStorage {
+            info: NandInfo {
+                kind,
+                page_size,
+                block_size,
+                spare_size,
+                total_size,
+                available_size,
+                bmt_exist,
+                id,
+            },
+        })
+    }
+
+    pub fn from_xml_response(xml: &str) -> Result<Self> {
+        let page_size = get_tag_usize(xml, "nand/page_size")? as u32;
+        let block_size = get_tag_usize(xml, "nand/block_size")? as u32;
+        let spare_size = get_tag_usize(xml, "nand/spare_size")? as u32;
+        let total_size = get_tag_usize(xml, "nand/total_size")? as u64;
+        // Older DAs don't tell the size left by bad block management
+        let available_size =
+            get_tag_usize(xml, "nand/available_size").map_or(total_size, |s| s as u64);
+
+        let id = match get_tag::<String>(xml, "nand/id") {
+            Ok(id) => hex::decode(id.trim_start_matches("0x"))
+                .map_err(|_| Error::io("Failed to parse NAND ID from XML"))?,
+            Err(_) => Vec::new(),
+        };
+
+        Ok(NandStorage {
+            info: NandInfo {
+                kind: NAND_SLC,
+                page_size,
+                block_size,
+                spare_size,
+                total_size,
+                available_size,
+                bmt_exist: true,
+                id,
+            },
+        })
+    }
+}
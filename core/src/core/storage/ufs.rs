@@ -4,9 +4,10 @@
 */
 use async_trait::async_trait;
 
-use crate::core::storage::{PartitionKind, Storage, StorageType};
+use crate::core::storage::{PartitionKind, Storage, StorageInfo, StorageType, ascii_field};
 use crate::error::{Error, Result};
 use crate::utilities::xml::{get_tag, get_tag_usize};
+use crate::{le_u32, le_u64};
 
 #[derive(Debug)]
 pub struct UfsInfo {
@@ -99,6 +100,22 @@ impl Storage for UfsStorage {
     fn get_user_size(&self) -> u64 {
         self.info.lu2_size
     }
+
+    fn info(&self) -> StorageInfo {
+        StorageInfo {
+            kind: StorageType::Ufs,
+            block_size: self.info.block_size,
+            total_size: self.info.lu0_size + self.info.lu1_size + self.info.lu2_size,
+            user_size: self.info.lu2_size,
+            boot1_size: self.info.lu0_size,
+            boot2_size: self.info.lu1_size,
+            rpmb_size: 0,
+            cid: self.info.cid.clone(),
+            model: ascii_field(&self.info.cid),
+            fw_version: ascii_field(&self.info.fwver),
+            serial: ascii_field(&self.info.serial),
+        }
+    }
 }
 
 impl UfsStorage {
@@ -110,22 +127,27 @@ impl UfsStorage {
         let mut pos = 0;
 
         // 0x30 == UFS
-        let kind = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
-        let block_size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap());
+        let kind = le_u32!(data, pos)?;
+        let block_size = le_u32!(data, pos + 4)?;
         pos += 8;
 
-        let lu0_size = u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap());
+        let lu0_size = le_u64!(data, pos)?;
         pos += 8;
-        let lu1_size = u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap());
+        let lu1_size = le_u64!(data, pos)?;
         pos += 8;
-        let lu2_size = u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap());
+        let lu2_size = le_u64!(data, pos)?;
         pos += 8;
 
-        let cid = data[pos..pos + 16].to_vec();
+        let field = |range: std::ops::Range<usize>| {
+            data.get(range)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| Error::malformed("UFS identification out of bounds", data))
+        };
+        let cid = field(pos..pos + 16)?;
         pos += 16;
 
-        let fwver = data[pos + 0x16..pos + 0x1A].to_vec();
-        let serial = data[pos + 0x1E..pos + 0x2A].to_vec();
+        let fwver = field(pos + 0x16..pos + 0x1A)?;
+        let serial = field(pos + 0x1E..pos + 0x2A)?;
 
         Ok(UfsStorage {
             info: UfsInfo { kind, block_size, lu0_size, lu1_size, lu2_size, cid, fwver, serial },
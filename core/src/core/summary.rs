@@ -0,0 +1,279 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::core::progress::ProgressEvent;
+
+/// Minimum time between two throughput samples, shorter windows are too noisy
+const SAMPLE_WINDOW: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    Read,
+    Write,
+    Erase,
+    /// Reading back written data to compare it, see [`crate::Device::write_partition_verified`]
+    Verify,
+}
+
+impl fmt::Display for OperationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationKind::Read => write!(f, "Read"),
+            OperationKind::Write => write!(f, "Wrote"),
+            OperationKind::Erase => write!(f, "Erased"),
+            OperationKind::Verify => write!(f, "Verified"),
+        }
+    }
+}
+
+/// A device range that kept failing and was skipped, see [`crate::Device::set_skip_bad_blocks`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BadRange {
+    /// Absolute offset on the device, within the operation's section
+    pub offset: u64,
+    pub length: u64,
+    /// Last error seen for the range
+    pub error: String,
+}
+
+/// Statistics of the last flash operation performed through `Device`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationSummary {
+    pub kind: OperationKind,
+    /// Bytes transferred, as reported by the protocol progress
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// Highest throughput seen over a sample window, in bytes per second
+    pub peak_throughput: f64,
+    /// Retries performed while transferring: transfers retried by the connection, and chunks
+    /// retried when skipping bad blocks
+    pub retries: u32,
+    /// SHA-256 of the data read from the device, only set for successful reads
+    pub sha256: Option<String>,
+    pub success: bool,
+    /// Ranges skipped because they kept failing. Skipped reads are zero-filled.
+    pub bad_ranges: Vec<BadRange>,
+}
+
+impl OperationSummary {
+    /// Average throughput in bytes per second.
+    pub fn average_throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { self.bytes as f64 / secs } else { 0.0 }
+    }
+}
+
+impl fmt::Display for OperationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:.2} MiB in {:.2}s (avg {:.2} MiB/s, peak {:.2} MiB/s), {} retries",
+            self.kind,
+            mib(self.bytes as f64),
+            self.elapsed.as_secs_f64(),
+            mib(self.average_throughput()),
+            mib(self.peak_throughput),
+            self.retries,
+        )?;
+
+        if let Some(hash) = &self.sha256 {
+            write!(f, ", sha256 {}", hash)?;
+        }
+
+        if !self.bad_ranges.is_empty() {
+            write!(f, ", {} bad ranges skipped", self.bad_ranges.len())?;
+        }
+
+        if !self.success {
+            write!(f, ", FAILED")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn mib(bytes: f64) -> f64 {
+    bytes / (1024.0 * 1024.0)
+}
+
+/// Collects the statistics of a single operation from its progress updates.
+pub(crate) struct SummaryTracker {
+    kind: OperationKind,
+    start: Instant,
+    bytes: u64,
+    sample_time: Instant,
+    sample_bytes: u64,
+    peak_throughput: f64,
+    /// Connection retries when the operation started, from [`crate::ConnectionStats`]
+    retries: u64,
+}
+
+impl SummaryTracker {
+    /// Starts tracking an operation, `retries` being the connection retries so far.
+    pub fn new(kind: OperationKind, retries: u64) -> Self {
+        let now = Instant::now();
+        SummaryTracker {
+            kind,
+            start: now,
+            bytes: 0,
+            sample_time: now,
+            sample_bytes: 0,
+            peak_throughput: 0.0,
+            retries,
+        }
+    }
+
+    pub fn update(&mut self, done: u64) {
+        self.bytes = done;
+
+        let window = self.sample_time.elapsed();
+        if window >= SAMPLE_WINDOW {
+            let rate = self.bytes.saturating_sub(self.sample_bytes) as f64 / window.as_secs_f64();
+            self.peak_throughput = self.peak_throughput.max(rate);
+            self.sample_time = Instant::now();
+            self.sample_bytes = self.bytes;
+        }
+    }
+
+    /// Wraps a progress callback, so that every update is also tracked.
+    pub fn wrap<'a>(
+        &'a mut self,
+        progress: &'a mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> impl FnMut(ProgressEvent) + Send + 'a {
+        move |event| {
+            self.update(event.bytes_done);
+            progress(event);
+        }
+    }
+
+    /// Ends the operation, `retries` being the connection retries so far.
+    pub fn finish(self, success: bool, sha256: Option<String>, retries: u64) -> OperationSummary {
+        let elapsed = self.start.elapsed();
+
+        // Operations shorter than a sample window never get a peak sample
+        let average = if elapsed.as_secs_f64() > 0.0 {
+            self.bytes as f64 / elapsed.as_secs_f64()
+        } else {
+            0.0
+        };
+
+        OperationSummary {
+            kind: self.kind,
+            bytes: self.bytes,
+            elapsed,
+            peak_throughput: self.peak_throughput.max(average),
+            retries: u32::try_from(retries.saturating_sub(self.retries)).unwrap_or(u32::MAX),
+            sha256: if success { sha256 } else { None },
+            success,
+            bad_ranges: Vec::new(),
+        }
+    }
+}
+
+/// Writer that hashes everything passing through it with SHA-256.
+/// Wrapping [`tokio::io::sink`] hashes data without keeping it, like a read back
+/// checked against what was written.
+pub struct HashingWriter<'a> {
+    inner: &'a mut (dyn AsyncWrite + Unpin + Send),
+    hasher: Sha256,
+}
+
+impl<'a> HashingWriter<'a> {
+    pub fn new(inner: &'a mut (dyn AsyncWrite + Unpin + Send)) -> Self {
+        HashingWriter { inner, hasher: Sha256::new() }
+    }
+
+    /// Returns the hex encoded hash of the data written.
+    pub fn finalize(self) -> String {
+        hex::encode(self.hasher.finalize())
+    }
+}
+
+impl AsyncWrite for HashingWriter<'_> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut *self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.hasher.update(buf.get(..n).unwrap_or_default());
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Reader that hashes and counts everything read through it.
+pub(crate) struct HashingReader<'a> {
+    inner: &'a mut (dyn AsyncRead + Unpin + Send),
+    hasher: Sha256,
+    len: u64,
+}
+
+impl<'a> HashingReader<'a> {
+    pub fn new(inner: &'a mut (dyn AsyncRead + Unpin + Send)) -> Self {
+        HashingReader { inner, hasher: Sha256::new(), len: 0 }
+    }
+
+    /// Returns the hash of the data read, along with its length.
+    pub fn finalize(self) -> (String, u64) {
+        (hex::encode(self.hasher.finalize()), self.len)
+    }
+}
+
+impl AsyncRead for HashingReader<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut *self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            let read = buf.filled().get(before..).unwrap_or_default();
+            self.hasher.update(read);
+            self.len += read.len() as u64;
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_are_counted_over_the_operation() {
+        let mut tracker = SummaryTracker::new(OperationKind::Read, 5);
+        tracker.update(0x1000);
+
+        let summary = tracker.finish(true, Some("hash".to_string()), 8);
+        assert_eq!(summary.retries, 3);
+        assert_eq!(summary.bytes, 0x1000);
+        assert_eq!(summary.sha256.as_deref(), Some("hash"));
+    }
+
+    #[test]
+    fn no_retries_without_a_connection() {
+        let summary = SummaryTracker::new(OperationKind::Erase, 0).finish(false, None, 0);
+        assert_eq!(summary.retries, 0);
+        assert!(!summary.success);
+    }
+}
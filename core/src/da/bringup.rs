@@ -0,0 +1,77 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+/// A completed DA bring-up step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BringupStep {
+    pub name: &'static str,
+    pub duration: Duration,
+}
+
+/// Timeline of the last `Device::enter_da_mode` call.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BringupReport {
+    /// Steps that completed, in order
+    pub steps: Vec<BringupStep>,
+    /// Step that was running when bring-up failed, and how long it ran for
+    pub failed_at: Option<BringupStep>,
+    pub total: Duration,
+}
+
+impl fmt::Display for BringupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(step) = &self.failed_at {
+            write!(f, "failed at {} after {:.1} s", step.name, step.duration.as_secs_f64())?;
+        } else {
+            write!(f, "completed in {:.1} s", self.total.as_secs_f64())?;
+        }
+
+        for (i, step) in self.steps.iter().enumerate() {
+            let sep = if i == 0 { "; " } else { ", " };
+            write!(f, "{}{} {:.1} s", sep, step.name, step.duration.as_secs_f64())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Records named, timed steps while the DA is being brought up.
+///
+/// Starting a step ends the previous one, so protocols only need to mark where each
+/// phase begins. A step that never ends is the one that failed or stalled.
+#[derive(Debug, Default)]
+pub struct StepRecorder {
+    steps: Vec<BringupStep>,
+    current: Option<(&'static str, Instant)>,
+}
+
+impl StepRecorder {
+    /// Starts a new step, ending the running one if any.
+    pub fn begin(&mut self, name: &'static str) {
+        self.end();
+        debug!("[Bring-up] {}", name);
+        self.current = Some((name, Instant::now()));
+    }
+
+    /// Ends the running step.
+    pub fn end(&mut self) {
+        if let Some((name, start)) = self.current.take() {
+            self.steps.push(BringupStep { name, duration: start.elapsed() });
+        }
+    }
+
+    /// Returns the recorded steps and the one still running, resetting the recorder.
+    pub fn take(&mut self) -> (Vec<BringupStep>, Option<BringupStep>) {
+        let running = self
+            .current
+            .take()
+            .map(|(name, start)| BringupStep { name, duration: start.elapsed() });
+        (std::mem::take(&mut self.steps), running)
+    }
+}
@@ -2,16 +2,24 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+use std::fmt;
+
 use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
+use crate::core::chipset::{MemoryWindow, chipset, chipsets_for_da_code, da_code};
 use crate::error::{Error, Result};
 use crate::{le_u16, le_u32};
 
+/// Size of a DA entry in the file header, V5 and V6 DAs
+const DA_ENTRY_SIZE: usize = 0xDC;
+
 /// Protocol used by the DA
 /// - Legacy: Old DA, used in old devices
 /// - V5 (XFlash): Used mainly in early Dimensity devices and most Helio devices
 /// - V6 (XML): Newest protocol, used in most recent Dimensity and Helio devices
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DAType {
     Legacy,
     V5,
@@ -39,6 +47,55 @@ pub struct DAEntryRegion {
     pub sig_len: u32,
 }
 
+/// An inconsistency found while parsing a DA entry.
+/// These usually mean the DA file was badly repacked, and will likely fail on the device.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DAIssue {
+    /// The entry doesn't have the File Info, DA1 and DA2 regions
+    MissingRegions { count: usize },
+    /// The signature is longer than the region itself
+    SigLenExceedsLength { region: usize, length: u32, sig_len: u32 },
+    /// The region length in the header doesn't match `length - sig_len`
+    RegionLengthMismatch { region: usize, expected: u32, found: u32 },
+    /// The DA2 hash slot couldn't be located in DA1
+    HashSlotNotFound,
+    /// The DA2 hash stored in DA1 doesn't match the actual DA2
+    Da2HashMismatch { stored: String, actual: String },
+    /// The region would be loaded outside of the memory of the chipset
+    LoadAddressOutOfRange { region: usize, addr: u32, length: u32 },
+}
+
+impl fmt::Display for DAIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DAIssue::MissingRegions { count } => {
+                write!(f, "expected at least 3 regions, found {}", count)
+            }
+            DAIssue::SigLenExceedsLength { region, length, sig_len } => write!(
+                f,
+                "region {} signature length 0x{:X} exceeds its length 0x{:X}",
+                region, sig_len, length
+            ),
+            DAIssue::RegionLengthMismatch { region, expected, found } => write!(
+                f,
+                "region {} length is 0x{:X} in the header, expected 0x{:X}",
+                region, found, expected
+            ),
+            DAIssue::HashSlotNotFound => write!(f, "DA2 hash slot not found in DA1"),
+            DAIssue::Da2HashMismatch { stored, actual } => {
+                write!(f, "DA1 expects DA2 hash {}, but DA2 hashes to {}", stored, actual)
+            }
+            DAIssue::LoadAddressOutOfRange { region, addr, length } => write!(
+                f,
+                "region {} loads at 0x{:08X}-0x{:08X}, outside of the chipset memory",
+                region,
+                addr,
+                *addr as u64 + *length as u64
+            ),
+        }
+    }
+}
+
 /// Represents a Download Agent (DA) entry for a specific SoC
 #[derive(Clone, Debug)]
 pub struct DA {
@@ -54,6 +111,8 @@ pub struct DA {
     pub hw_code: u16,
     /// Always seems to be 0xCA00
     pub hw_sub_code: u16,
+    /// Inconsistencies found while parsing, empty for a sane DA
+    pub issues: Vec<DAIssue>,
 }
 
 /// Represents a Download Agent (DA) file containing multiple DA entries
@@ -61,22 +120,23 @@ pub struct DAFile {
     /// Raw data of the entire DA file
     pub da_raw_data: Vec<u8>,
     pub da_type: DAType,
+    /// Identifier of the DA build, e.g. `MTK_AllInOne_DA_v3.3001.2020/11/19.13:59_532089`
+    pub da_id: String,
+    /// Version of the DA file format
+    pub version: u32,
     /// List of DA entries for different SoCs
     pub das: Vec<DA>,
 }
 
 impl DAFile {
     pub fn parse_da(raw_data: &[u8]) -> Result<DAFile> {
-        if raw_data.len() < 0x6C + 0xDC {
+        let Some(hdr) = raw_data.first_chunk::<0x6C>().filter(|_| raw_data.len() >= 0x6C + 0xDC)
+        else {
             return Err(Error::penumbra("Invalid DA file, too small"));
-        }
-
-        let hdr = &raw_data[..0x6C];
+        };
 
         let legacy_test_pos = 0x6C + 0xD8;
-        let da_type = if raw_data.len() >= legacy_test_pos + 2
-            && &raw_data[legacy_test_pos..legacy_test_pos + 2] == b"\xDA\xDA"
-        {
+        let da_type = if raw_data.get(legacy_test_pos..legacy_test_pos + 2) == Some(b"\xDA\xDA") {
             DAType::Legacy
         } else if hdr.windows(9).any(|w| w == b"MTK_DA_v6") {
             DAType::V6
@@ -88,14 +148,14 @@ impl DAFile {
             return Err(Error::penumbra("Invalid DA file: Missing MTK_DOWNLOAD_AGENT signature"));
         }
 
-        let _da_id = String::from_utf8_lossy(&hdr[0x20..0x60]).trim_end_matches('\0').to_string();
-        let _version = le_u32!(hdr, 0x60);
-        let num_socs = le_u32!(hdr, 0x68);
+        let da_id = String::from_utf8_lossy(&hdr[0x20..0x60]).trim_end_matches('\0').to_string();
+        let version = le_u32!(hdr, 0x60)?;
+        let num_socs = le_u32!(hdr, 0x68)?;
         let _magic_number = &hdr[0x64..0x68];
 
         let da_entry_size = match da_type {
             DAType::Legacy => 0xD8,
-            _ => 0xDC,
+            _ => DA_ENTRY_SIZE,
         };
 
         let mut das = Vec::new();
@@ -103,16 +163,19 @@ impl DAFile {
             // Each one of this is a DA entry in the header
             let start = 0x6C + (i as usize * da_entry_size);
             let end = start + da_entry_size;
-            let da_entry = &raw_data[start..end];
+            let da_entry = raw_data
+                .get(start..end)
+                .ok_or_else(|| Error::penumbra("Invalid DA file: truncated DA entry table"))?;
             let mut inner_da_type = da_type.clone();
 
             // For each DA, we parse its header entry
-            let magic = le_u16!(da_entry, 0x00);
-            let hw_code = le_u16!(da_entry, 0x02);
-            let hw_sub_code = le_u16!(da_entry, 0x04);
-            let _hw_version = le_u16!(da_entry, 0x06);
+            let magic = le_u16!(da_entry, 0x00)?;
+            let hw_code = le_u16!(da_entry, 0x02)?;
+            let hw_sub_code = le_u16!(da_entry, 0x04)?;
+            let _hw_version = le_u16!(da_entry, 0x06)?;
             let mut regions: Vec<DAEntryRegion> = Vec::new();
-            let region_count = le_u16!(da_entry, 0x12);
+            let mut issues = Vec::new();
+            let region_count = le_u16!(da_entry, 0x12)?;
             // Structure of the DA header entry
             // 0x00	magic	u16
             // 0x02	hw_code	u16
@@ -133,14 +196,23 @@ impl DAFile {
                 // 0x08	addr (m_addr)	u32
                 // 0x0C	m_region_offset (m_len - m_sig_len)	u32
                 // 0x10	sig_len (m_sig_len)	u32
-                let region_header_data =
-                    &da_entry[current_region_offset..current_region_offset + 20];
-                let offset = le_u32!(region_header_data, 0x00);
-                let length = le_u32!(region_header_data, 0x04);
-                let addr = le_u32!(region_header_data, 0x08);
-                let sig_len = le_u32!(region_header_data, 0x10);
-                let region_data: Vec<u8> =
-                    raw_data[offset as usize..(offset + length) as usize].to_vec();
+                let region_header_data = da_entry
+                    .get(current_region_offset..current_region_offset + 20)
+                    .ok_or_else(|| Error::penumbra("Invalid DA file: too many regions"))?;
+                let offset = le_u32!(region_header_data, 0x00)?;
+                let length = le_u32!(region_header_data, 0x04)?;
+                let addr = le_u32!(region_header_data, 0x08)?;
+                let header_region_length = le_u32!(region_header_data, 0x0C)?;
+                let sig_len = le_u32!(region_header_data, 0x10)?;
+                let region_data: Vec<u8> = raw_data
+                    .get(offset as usize..offset as usize + length as usize)
+                    .ok_or_else(|| {
+                        Error::penumbra(format!(
+                            "Invalid DA file: region at 0x{:X} (0x{:X} bytes) is out of bounds",
+                            offset, length
+                        ))
+                    })?
+                    .to_vec();
                 debug!(
                     "Region: offset={:08X}, length={:08X}, addr={:08X}, sig_len={:08X}",
                     offset, length, addr, sig_len
@@ -152,76 +224,219 @@ impl DAFile {
                     inner_da_type = DAType::Legacy;
                 }
 
+                let index = regions.len();
+                if sig_len > length {
+                    issues.push(DAIssue::SigLenExceedsLength { region: index, length, sig_len });
+                }
+
+                let region_length = length.saturating_sub(sig_len);
+                // Legacy DAs don't fill this field
+                if inner_da_type != DAType::Legacy && header_region_length != region_length {
+                    issues.push(DAIssue::RegionLengthMismatch {
+                        region: index,
+                        expected: region_length,
+                        found: header_region_length,
+                    });
+                }
+
                 regions.push(DAEntryRegion {
                     data: region_data,
                     offset,
                     length,
                     addr,
-                    region_length: length - sig_len,
+                    region_length,
                     sig_len,
                 });
                 current_region_offset += 20; // Move to the next region header
             }
 
-            das.push(DA { da_type: inner_da_type, regions, magic, hw_code, hw_sub_code });
+            let mut da =
+                DA { da_type: inner_da_type, regions, magic, hw_code, hw_sub_code, issues };
+            da.check_da2_hash();
+            for issue in &da.issues {
+                debug!("DA entry {:04X}: {}", hw_code, issue);
+            }
+
+            das.push(da);
             debug!(
                 "Parsed DA entry: hw_code={:04X}, hw_sub_code={:04X}, regions={}",
                 hw_code, hw_sub_code, region_count
             );
         }
 
-        Ok(DAFile { da_raw_data: raw_data.to_vec(), da_type, das })
+        Ok(DAFile { da_raw_data: raw_data.to_vec(), da_type, da_id, version, das })
+    }
+
+    /// Serializes `das` into a DA file, the reverse of [`DAFile::parse_da`].
+    ///
+    /// The file header and the entry fields that aren't kept in [`DA`] (versions, page size)
+    /// are taken from this file, matching entries by hw_code. The region data is laid out
+    /// after the entry table, in order, so patched regions may change size.
+    /// Legacy DA files can't be written, their layout is detected differently.
+    pub fn export(&self, das: &[DA]) -> Result<Vec<u8>> {
+        if self.da_type == DAType::Legacy {
+            return Err(Error::penumbra("Legacy DA files can't be exported"));
+        }
+
+        let mut hdr = *self
+            .da_raw_data
+            .first_chunk::<0x6C>()
+            .ok_or_else(|| Error::penumbra("Invalid DA file, too small"))?;
+        let num_socs = le_u32!(hdr, 0x68)? as usize;
+        let max_regions = (DA_ENTRY_SIZE - 0x14) / 20;
+
+        hdr[0x68..0x6C].copy_from_slice(&(das.len() as u32).to_le_bytes());
+        let mut out = hdr.to_vec();
+
+        let mut data_offset = 0x6C + das.len() * DA_ENTRY_SIZE;
+        let mut region_data = Vec::new();
+
+        for da in das {
+            if da.regions.len() > max_regions {
+                return Err(Error::penumbra(format!(
+                    "DA entry {:04X} has {} regions, at most {} fit in an entry",
+                    da.hw_code,
+                    da.regions.len(),
+                    max_regions
+                )));
+            }
+
+            let template = (0..num_socs)
+                .filter_map(|i| {
+                    let start = 0x6C + i * DA_ENTRY_SIZE;
+                    self.da_raw_data.get(start..)?.first_chunk::<0x14>()
+                })
+                .find(|entry| le_u16!(entry, 0x02).ok() == Some(da.hw_code));
+
+            let mut head = template.copied().unwrap_or([0u8; 0x14]);
+            head[0x00..0x02].copy_from_slice(&da.magic.to_le_bytes());
+            head[0x02..0x04].copy_from_slice(&da.hw_code.to_le_bytes());
+            head[0x04..0x06].copy_from_slice(&da.hw_sub_code.to_le_bytes());
+            head[0x12..0x14].copy_from_slice(&(da.regions.len() as u16).to_le_bytes());
+
+            let mut entry = Vec::with_capacity(DA_ENTRY_SIZE);
+            entry.extend_from_slice(&head);
+            for region in &da.regions {
+                let length = region.data.len() as u32;
+                let fields = [
+                    data_offset as u32,
+                    length,
+                    region.addr,
+                    length.saturating_sub(region.sig_len),
+                    region.sig_len,
+                ];
+
+                for field in fields {
+                    entry.extend_from_slice(&field.to_le_bytes());
+                }
+
+                region_data.extend_from_slice(&region.data);
+                data_offset += region.data.len();
+            }
+
+            entry.resize(DA_ENTRY_SIZE, 0);
+            out.extend_from_slice(&entry);
+        }
+
+        out.extend_from_slice(&region_data);
+        Ok(out)
+    }
+
+    /// Returns the inconsistencies of every DA entry, along with the entry hw_code.
+    pub fn issues(&self) -> impl Iterator<Item = (u16, &DAIssue)> {
+        self.das.iter().flat_map(|da| da.issues.iter().map(move |issue| (da.hw_code, issue)))
+    }
+
+    /// Returns the hw_codes of the chipsets this file has a DA entry for, sorted, along
+    /// with their name.
+    ///
+    /// Entries are matched through the chipset table the same way as in
+    /// [`DAFile::get_da_from_hw_code`]. Entries of chipsets missing from the table are
+    /// listed under their own code, with an unknown name.
+    pub fn supported_hw_codes(&self) -> Vec<(u16, String)> {
+        let mut supported: Vec<(u16, String)> = Vec::new();
+
+        for da in &self.das {
+            let known = chipsets_for_da_code(da.hw_code);
+            if known.is_empty() && chipset(da.hw_code).is_none() {
+                supported.push((da.hw_code, "Unknown".to_string()));
+            }
+            supported.extend(known.iter().map(|c| (c.hw_code, c.name())));
+        }
+
+        supported.sort_by_key(|(hw_code, _)| *hw_code);
+        supported.dedup_by_key(|(hw_code, _)| *hw_code);
+        supported
     }
 
-    // TODO: Make an Hashmap, possibly also including other info about a chip
     pub fn get_da_from_hw_code(&self, hw_code: u16) -> Option<DA> {
-        let da_code = match hw_code {
-            0x279 => 0x6797,
-            0x321 => 0x6735,
-            0x326 => 0x6755,
-            0x335 => 0x6735,
-            0x337 => 0x6735,
-            0x507 => 0x6758,
-            0x551 => 0x6757,
-            0x562 => 0x6799,
-            0x601 => 0x6755,
-            0x633 => 0x6570,
-            0x688 => 0x6758,
-            0x690 => 0x6763,
-            0x699 => 0x6739,
-            0x707 => 0x6768,
-            0x717 => 0x6761,
-            0x725 => 0x6779,
-            0x766 => 0x6765,
-            0x788 => 0x6771,
-            0x813 => 0x6785,
-            0x816 => 0x6885,
-            0x886 => 0x6873,
-            0x908 => 0x8696,
-            0x930 => 0x8195,
-            0x950 => 0x6893,
-            0x959 => 0x6877,
-            0x989 => 0x6833,
-            0x996 => 0x6853,
-            0x1066 => 0x6781,
-            0x6583 => 0x6589,
-            0x8172 => 0x8173,
-            0x8176 => 0x8173,
-            _ => hw_code,
-        };
+        let da_code = da_code(hw_code);
 
         // I did the clone, I'm sorry!
         self.das.iter().find(|da| da.hw_code == da_code).cloned()
     }
 }
 
+impl DAEntryRegion {
+    /// SHA-256 of the region, without the signature.
+    /// This is what the previous stage checks before jumping to the region.
+    pub fn sha256(&self) -> [u8; 32] {
+        let end = (self.region_length as usize).min(self.data.len());
+        Sha256::digest(self.data.split_at(end).0).into()
+    }
+
+    /// The signature at the end of the region, empty if it isn't signed.
+    pub fn signature(&self) -> &[u8] {
+        self.data.split_at(self.data.len().saturating_sub(self.sig_len as usize)).1
+    }
+}
+
 impl DA {
     pub fn get_da1(&self) -> Option<&DAEntryRegion> {
-        if self.regions.len() >= 3 { Some(&self.regions[1]) } else { None }
+        self.regions.get(1).filter(|_| self.regions.len() >= 3)
     }
 
     pub fn get_da2(&self) -> Option<&DAEntryRegion> {
-        if self.regions.len() >= 3 { Some(&self.regions[2]) } else { None }
+        self.regions.get(2)
+    }
+
+    /// Describes the regions of the entry, one per line, with where they load
+    /// and how much of them is signature.
+    pub fn entry_summary(&self) -> String {
+        self.regions
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let name = match i {
+                    0 => "File Info",
+                    1 => "DA1",
+                    2 => "DA2",
+                    _ => "Extra",
+                };
+                format!(
+                    "Region {} ({}): addr 0x{:08X}, length 0x{:X}, sig_len 0x{:X}",
+                    i, name, r.addr, r.length, r.sig_len
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Checks that DA1 and DA2 are loaded entirely within one of the given memory windows.
+    /// A region loaded elsewhere makes the device hard-fault when jumping to it.
+    pub fn check_load_addresses(&self, memory: &[MemoryWindow]) -> Vec<DAIssue> {
+        let loaded = [(1, self.get_da1()), (2, self.get_da2())];
+
+        loaded
+            .into_iter()
+            .filter_map(|(index, region)| region.map(|r| (index, r)))
+            .filter(|(_, r)| !memory.iter().any(|w| w.contains(r.addr, r.length)))
+            .map(|(region, r)| DAIssue::LoadAddressOutOfRange {
+                region,
+                addr: r.addr,
+                length: r.length,
+            })
+            .collect()
     }
 
     pub fn find_da_hash_offset(&self) -> Option<usize> {
@@ -254,11 +469,10 @@ impl DA {
                     // TODO: Consider being a decent human being and actually make sig_len a usize
                     let search_end = da1.data.len().checked_sub(da1.sig_len as usize)?;
                     let search_start = search_end.checked_sub(0x30)?;
-                    if search_end <= da1.data.len() {
-                        let hash_candidate = &da1.data[search_start..search_end];
-                        if hash_candidate.ends_with(&[0, 0, 0, 0]) {
-                            return Some(search_start);
-                        }
+                    if let Some(hash_candidate) = da1.data.get(search_start..search_end)
+                        && hash_candidate.ends_with(&[0, 0, 0, 0])
+                    {
+                        return Some(search_start);
                     }
                 }
                 None
@@ -267,11 +481,227 @@ impl DA {
         }
     }
 
+    /// Compares the DA2 hash stored in DA1 against the actual DA2.
+    /// A mismatch means DA1 will refuse to boot DA2, which is typical of bad repacks.
+    fn check_da2_hash(&mut self) {
+        if self.da_type == DAType::Legacy {
+            return;
+        }
+
+        let (Some(da1), Some(da2)) = (self.get_da1(), self.get_da2()) else {
+            self.issues.push(DAIssue::MissingRegions { count: self.regions.len() });
+            return;
+        };
+
+        let Some(slot) = self.find_da_hash_offset().and_then(|pos| da1.data.get(pos..pos + 32))
+        else {
+            self.issues.push(DAIssue::HashSlotNotFound);
+            return;
+        };
+
+        let actual = da2.sha256();
+        if slot != actual {
+            let issue =
+                DAIssue::Da2HashMismatch { stored: hex::encode(slot), actual: hex::encode(actual) };
+            self.issues.push(issue);
+        }
+    }
+
+    /// Whether `other` is this DA with only its code changed, like a patched copy of it.
+    /// Entries, load addresses and signatures must all match, patches never touch those.
+    pub fn same_layout(&self, other: &DA) -> bool {
+        self.da_type == other.da_type
+            && self.hw_code == other.hw_code
+            && self.hw_sub_code == other.hw_sub_code
+            && self.regions.len() == other.regions.len()
+            && self.regions.iter().zip(&other.regions).all(|(a, b)| {
+                a.addr == b.addr && a.sig_len == b.sig_len && a.signature() == b.signature()
+            })
+    }
+
     pub fn is_arm64(&self) -> bool {
         if let Some(da2) = self.get_da2() {
-            return da2.data.len() > 4 && da2.data[0..4] == [0xC6, 0x01, 0x00, 0x58];
+            return da2.data.len() > 4 && da2.data.starts_with(&[0xC6, 0x01, 0x00, 0x58]);
         }
 
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Regions of an entry: File Info, DA1 and a signed DA2.
+    fn regions(fill: u8) -> [(Vec<u8>, u32, u32); 3] {
+        [
+            (vec![fill; 0x40], 0x0000_0000, 0),
+            (vec![fill + 1; 0x200], 0x0020_0000, 0x100),
+            (vec![fill + 2; 0x300], 0x4000_0000, 0x100),
+        ]
+    }
+
+    /// A V5 DA file with an entry per hw_code, regions laid out after the entry table in
+    /// order, as `export` writes them.
+    fn da_file(hw_codes: &[u16]) -> Vec<u8> {
+        let mut data = vec![0u8; 0x6C + hw_codes.len() * DA_ENTRY_SIZE];
+        data[..0x12].copy_from_slice(b"MTK_DOWNLOAD_AGENT");
+        data[0x20..0x32].copy_from_slice(b"MTK_AllInOne_DA_v5");
+        data[0x60..0x64].copy_from_slice(&4u32.to_le_bytes());
+        data[0x64..0x68].copy_from_slice(&0x9999_0000u32.to_le_bytes());
+        data[0x68..0x6C].copy_from_slice(&(hw_codes.len() as u32).to_le_bytes());
+
+        for (i, hw_code) in hw_codes.iter().enumerate() {
+            let entry = 0x6C + i * DA_ENTRY_SIZE;
+            data[entry..entry + 2].copy_from_slice(&0xDADAu16.to_le_bytes());
+            data[entry + 2..entry + 4].copy_from_slice(&hw_code.to_le_bytes());
+            data[entry + 4..entry + 6].copy_from_slice(&0xCA00u16.to_le_bytes());
+            // Fields `DA` doesn't keep: hw_version and page size
+            data[entry + 6..entry + 8].copy_from_slice(&0x8A00u16.to_le_bytes());
+            data[entry + 0x0C..entry + 0x0E].copy_from_slice(&0x0800u16.to_le_bytes());
+            data[entry + 0x12..entry + 0x14].copy_from_slice(&3u16.to_le_bytes());
+
+            for (j, (region, addr, sig_len)) in regions(i as u8 * 0x10).into_iter().enumerate() {
+                let fields = [
+                    data.len() as u32,
+                    region.len() as u32,
+                    addr,
+                    region.len() as u32 - sig_len,
+                    sig_len,
+                ];
+                let table = entry + 0x14 + j * 20;
+                for (k, field) in fields.iter().enumerate() {
+                    data[table + k * 4..table + k * 4 + 4].copy_from_slice(&field.to_le_bytes());
+                }
+                data.extend_from_slice(&region);
+            }
+        }
+        data
+    }
+
+    fn assert_same_entries(a: &[DA], b: &[DA]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert_eq!(a.da_type, b.da_type);
+            assert_eq!((a.magic, a.hw_code, a.hw_sub_code), (b.magic, b.hw_code, b.hw_sub_code));
+            assert_eq!(a.issues, b.issues);
+            assert_eq!(a.regions.len(), b.regions.len());
+            for (a, b) in a.regions.iter().zip(&b.regions) {
+                assert_eq!(a.data, b.data);
+                assert_eq!((a.length, a.addr, a.sig_len), (b.length, b.addr, b.sig_len));
+                assert_eq!(a.region_length, b.region_length);
+            }
+        }
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let raw = da_file(&[0x1234, 0x4321]);
+        let file = DAFile::parse_da(&raw).unwrap();
+
+        let exported = file.export(&file.das).unwrap();
+        assert_eq!(exported, raw);
+
+        let reparsed = DAFile::parse_da(&exported).unwrap();
+        assert_eq!(reparsed.da_type, file.da_type);
+        assert_eq!(reparsed.da_id, file.da_id);
+        assert_eq!(reparsed.version, file.version);
+        assert_same_entries(&reparsed.das, &file.das);
+    }
+
+    #[test]
+    fn export_lays_out_grown_regions() {
+        let raw = da_file(&[0x1234, 0x4321]);
+        let file = DAFile::parse_da(&raw).unwrap();
+
+        // Only the second entry, with a DA2 grown by its patches
+        let mut patched = file.das[1].clone();
+        patched.regions[2].data.extend_from_slice(&[0xC3; 0x80]);
+        patched.regions[2].length += 0x80;
+        patched.regions[2].region_length += 0x80;
+
+        let exported = file.export(std::slice::from_ref(&patched)).unwrap();
+        let reparsed = DAFile::parse_da(&exported).unwrap();
+        assert_same_entries(&reparsed.das, std::slice::from_ref(&patched));
+
+        // Fields not kept in `DA` come from the matching entry of the original file
+        let entry = &exported[0x6C..0x6C + DA_ENTRY_SIZE];
+        assert_eq!(entry[0x06..0x08], 0x8A00u16.to_le_bytes());
+        assert_eq!(entry[0x0C..0x0E], 0x0800u16.to_le_bytes());
+    }
+
+    #[test]
+    fn legacy_files_are_not_exported() {
+        let mut file = DAFile::parse_da(&da_file(&[0x1234])).unwrap();
+        file.da_type = DAType::Legacy;
+        assert!(file.export(&file.das).is_err());
+    }
+
+    /// The first entry of the test file, with DA1 and DA2 moved to the given addresses.
+    fn loaded_at(da1: (u32, u32), da2: (u32, u32)) -> DA {
+        let mut da = DAFile::parse_da(&da_file(&[0x6765])).unwrap().das.remove(0);
+        (da.regions[1].addr, da.regions[1].length) = da1;
+        (da.regions[2].addr, da.regions[2].length) = da2;
+        da
+    }
+
+    fn out_of_range(region: usize, (addr, length): (u32, u32)) -> DAIssue {
+        DAIssue::LoadAddressOutOfRange { region, addr, length }
+    }
+
+    #[test]
+    fn load_addresses_in_memory_pass() {
+        let memory = chipset(0x766).unwrap().memory;
+        let cases = [
+            // As found in stock DAs
+            ((0x0020_0000, 0x4_0000), (0x4000_0000, 0x8_0000)),
+            // Both windows filled exactly
+            ((0x0010_0000, 0x20_0000), (0x4000_0000, 0xC000_0000)),
+            // Right before the end of the address space
+            ((0x002F_FF00, 0x100), (0xFFFF_FF00, 0x100)),
+        ];
+
+        for (da1, da2) in cases {
+            assert_eq!(loaded_at(da1, da2).check_load_addresses(memory), [], "{:X?}", (da1, da2));
+        }
+    }
+
+    #[test]
+    fn load_addresses_out_of_memory_are_flagged() {
+        let memory = chipset(0x766).unwrap().memory;
+        let sram = (0x0020_0000, 0x4_0000);
+        let dram = (0x4000_0000, 0x8_0000);
+        let cases = [
+            // Below SRAM, in the BROM
+            ((0x0000_0000, 0x100), dram, vec![1]),
+            // Across the end of SRAM
+            ((0x002F_FF00, 0x200), dram, vec![1]),
+            // Between SRAM and DRAM, in the peripheral registers
+            (sram, (0x1000_0000, 0x100), vec![2]),
+            // Past the end of the 32 bit address space
+            (sram, (0xFFFF_FF00, 0x200), vec![2]),
+            ((0x1020_0000, 0x100), (0x0030_0000, 0x100), vec![1, 2]),
+        ];
+
+        for (da1, da2, regions) in cases {
+            let expected: Vec<DAIssue> = regions
+                .iter()
+                .map(|&region| out_of_range(region, if region == 1 { da1 } else { da2 }))
+                .collect();
+            assert_eq!(
+                loaded_at(da1, da2).check_load_addresses(memory),
+                expected,
+                "{:X?}",
+                (da1, da2)
+            );
+        }
+    }
+
+    #[test]
+    fn incomplete_entries_are_not_load_checked() {
+        // Without all three regions, there's no telling which one is DA1
+        let mut da = loaded_at((0, 0x100), (0, 0x100));
+        da.regions.truncate(2);
+        assert_eq!(da.check_load_addresses(chipset(0x766).unwrap().memory), []);
+    }
+}
@@ -0,0 +1,64 @@
+/*
+    SPDX-License-Identifier: GPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+
+    Derived from:
+    https://github.com/bkerler/mtkclient/blob/main/mtkclient/Library/DA/legacy/dalegacy_param.py
+    Original SPDX-License-Identifier: GPL-3.0-or-later
+    Original SPDX-FileCopyrightText: 2018–2024 bkerler
+
+    This file remains under the GPL-3.0-or-later license.
+    However, as part of a larger project licensed under the AGPL-3.0-or-later,
+    the combined work is subject to the networking terms of the AGPL-3.0-or-later,
+    as for term 13 of the GPL-3.0-or-later license.
+*/
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Cmd {
+    UsbCheckStatus = 0x72,
+    ReadReg32 = 0x7A,
+    WriteReg32 = 0x7B,
+    Read = 0xD6,
+    Finish = 0xD9,
+    EnableWatchdog = 0xDB,
+}
+
+/// Single byte answers of the legacy DA.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Rsp {
+    Sync = 0xC0,
+    SocOk = 0xC1,
+    SocFail = 0xCF,
+    Ack = 0x5A,
+    Nack = 0xA5,
+    UnknownCmd = 0xBB,
+}
+
+/// Host type sent with the read command, Linux.
+pub const HOST_LINUX: u8 = 0x0C;
+/// Storage type sent with the read command for eMMC.
+pub const STORAGE_EMMC: u8 = 0x02;
+
+/// Status of DA1 after the stage 2 config, when DRAM needs the EMI settings.
+pub const DRAM_NEEDS_EMI: u32 = 0xBC3;
+/// Status of DA1 after the stage 2 config, when DRAM doesn't need setting up.
+pub const DRAM_READY: u32 = 0xBC4;
+/// Asks DA1 to bring up DRAM and report it.
+pub const DRAM_INIT: u32 = 0x8000_0001;
+
+/// DA2 is sent in packets of this size, each acknowledged.
+pub const DA2_PACKET_SIZE: usize = 0x1000;
+/// Packet size requested when reading flash, each packet is followed by its checksum.
+pub const READ_PACKET_SIZE: usize = 0x10_0000;
+
+// Sizes of the flash info DA2 reports once started
+pub const NOR_INFO_SIZE: usize = 0x1C;
+pub const NAND_INFO_SIZE: usize = 0x11;
+pub const NAND_INFO2_SIZE: usize = 0x09;
+pub const EMMC_INFO_SIZE: usize = 0x5C;
+pub const SDC_INFO_SIZE: usize = 0x1C;
+pub const CONFIG_INFO_SIZE: usize = 0x26;
+pub const PASS_INFO_SIZE: usize = 0x0A;
@@ -0,0 +1,342 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::sync::Arc;
+
+#[cfg(feature = "seccfg")]
+use log::warn;
+use log::{debug, info};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::connection::Connection;
+use crate::connection::port::ConnectionType;
+use crate::core::devinfo::DeviceInfo;
+use crate::core::preloader::preloader_partitions;
+use crate::core::progress::ProgressEvent;
+#[cfg(feature = "seccfg")]
+use crate::core::seccfg::LockFlag;
+use crate::core::storage::{Gpt, GptType, Partition, PartitionKind, Storage, StorageType};
+use crate::da::legacy::Legacy;
+use crate::da::legacy::cmds::*;
+use crate::da::protocol::BootMode;
+use crate::da::{DA, DAEntryRegion, DAProtocol, StepRecorder};
+use crate::error::{Error, Result};
+
+/// Size of the primary GPT read back from the user area.
+const GPT_SIZE: usize = 32 * 1024;
+
+#[async_trait::async_trait]
+impl DAProtocol for Legacy {
+    async fn upload_da(&mut self) -> Result<bool> {
+        self.bringup.begin("DA1");
+        let target_config = self.dev_info.target_config().await;
+        self.conn.authenticate(target_config).await?;
+
+        let da1 =
+            self.da.get_da1().cloned().ok_or_else(|| Error::penumbra("DA1 region not found"))?;
+        self.upload_stage1(&da1).await?;
+
+        self.bringup.begin("DRAM");
+        self.setup_stage2().await?;
+
+        self.bringup.begin("DA2");
+        let da2 =
+            self.da.get_da2().cloned().ok_or_else(|| Error::penumbra("DA2 region not found"))?;
+        self.upload_stage2(&da2).await?;
+
+        self.bringup.begin("flash-info");
+        self.read_flash_info().await?;
+
+        self.bringup.end();
+        Ok(true)
+    }
+
+    async fn boot_to(&mut self, _addr: u32, _data: &[u8]) -> Result<bool> {
+        Err(Error::unsupported("Legacy DAs can't boot code once DA2 is running"))
+    }
+
+    async fn send(&mut self, data: &[u8]) -> Result<bool> {
+        self.conn.write(data).await?;
+        Ok(true)
+    }
+
+    async fn send_data(&mut self, data: &[&[u8]]) -> Result<bool> {
+        for param in data {
+            self.conn.write(param).await?;
+        }
+        Ok(true)
+    }
+
+    async fn get_status(&mut self) -> Result<u32> {
+        match self.read_u8().await? {
+            ack if ack == Rsp::Ack as u8 => Ok(0),
+            status => Err(Error::proto(format!("DA answered 0x{:02X}", status))),
+        }
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        info!("Shutting down device...");
+        self.conn.write(&[Cmd::Finish as u8]).await?;
+        self.conn.write(&0u32.to_be_bytes()).await?;
+        self.expect_ack("Shutdown").await?;
+
+        self.conn.port.close().await.ok();
+        Ok(())
+    }
+
+    async fn reboot(&mut self, bootmode: BootMode) -> Result<()> {
+        let dlbit = match bootmode {
+            BootMode::Normal => 0,
+            BootMode::Brom => 1,
+            _ => {
+                return Err(Error::unsupported(format!(
+                    "Legacy DAs can't reboot into {:?} mode",
+                    bootmode
+                )));
+            }
+        };
+
+        let mut param = Vec::with_capacity(9);
+        param.push(Cmd::EnableWatchdog as u8);
+        param.extend_from_slice(&1000u32.to_be_bytes()); // timeout_ms
+        param.push(0); // async
+        param.push(1); // reboot
+        param.push(dlbit); // dlbit
+        param.push(0); // bNotResetRTCTime
+
+        info!("Rebooting device into {:?} mode...", bootmode);
+
+        self.conn.write(&param).await?;
+        self.expect_ack("Reboot").await?;
+
+        self.conn.port.close().await.ok();
+        Ok(())
+    }
+
+    async fn keepalive(&mut self) -> Result<()> {
+        debug!("Sending keepalive to DA");
+        self.get_usb_speed().await.map(|_| ())
+    }
+
+    async fn read_flash(
+        &mut self,
+        addr: u64,
+        size: usize,
+        section: PartitionKind,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<()> {
+        let PartitionKind::Emmc(part) = section else {
+            return Err(Error::unsupported("Legacy DAs can only read eMMC"));
+        };
+
+        info!("Reading flash at address {:#X} with size {:#X}", addr, size);
+
+        let mut param = vec![Cmd::Read as u8, HOST_LINUX, STORAGE_EMMC, part as u8];
+        param.extend_from_slice(&addr.to_be_bytes());
+        param.extend_from_slice(&(size as u64).to_be_bytes());
+        param.extend_from_slice(&(READ_PACKET_SIZE as u32).to_be_bytes());
+        self.conn.write(&param).await?;
+
+        let ack = self.read_u8().await?;
+        if ack != Rsp::Ack as u8 {
+            self.conn.write(&[Rsp::Nack as u8]).await?;
+            let status = self.read_u32().await?;
+            return Err(Error::proto(format!(
+                "Read of {} at 0x{:X} refused (0x{:02X}), status 0x{:08X}",
+                section.as_str(),
+                addr,
+                ack,
+                status
+            )));
+        }
+
+        self.upload_data(size, writer, progress).await?;
+
+        info!("Flash read completed, 0x{:X} bytes read.", size);
+        Ok(())
+    }
+
+    async fn write_flash(
+        &mut self,
+        _addr: u64,
+        _size: usize,
+        _reader: &mut (dyn AsyncRead + Unpin + Send),
+        _section: PartitionKind,
+        _pad_with_zeros: bool,
+        _progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        Err(Error::unsupported("Writing flash is not supported with legacy DAs yet"))
+    }
+
+    async fn erase_flash(
+        &mut self,
+        _addr: u64,
+        _size: usize,
+        _section: PartitionKind,
+        _progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        Err(Error::unsupported("Erasing flash is not supported with legacy DAs yet"))
+    }
+
+    async fn download(
+        &mut self,
+        _part_name: String,
+        _size: usize,
+        _reader: &mut (dyn AsyncRead + Unpin + Send),
+        _progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        Err(Error::unsupported("Writing partitions is not supported with legacy DAs yet"))
+    }
+
+    // Legacy DAs don't read partitions by name, they are looked up in the partition table
+    async fn upload(
+        &mut self,
+        part_name: String,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        let part = self
+            .dev_info
+            .get_partition(&part_name)
+            .await
+            .ok_or_else(|| Error::penumbra(format!("Partition '{}' not found", part_name)))?;
+
+        self.read_flash(part.address, part.size, part.kind, progress, writer).await
+    }
+
+    async fn format(
+        &mut self,
+        _part_name: String,
+        _progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        Err(Error::unsupported("Formatting is not supported with legacy DAs yet"))
+    }
+
+    async fn read32(&mut self, addr: u32) -> Result<u32> {
+        debug!("Reading 32-bit register at address 0x{:08X}", addr);
+        self.conn.write(&[Cmd::ReadReg32 as u8]).await?;
+        self.conn.write(&addr.to_be_bytes()).await?;
+        let value = self.read_u32().await?;
+        self.expect_ack("Register read").await?;
+        Ok(value)
+    }
+
+    async fn write32(&mut self, addr: u32, value: u32) -> Result<()> {
+        debug!("[TX] Writing 32-bit value 0x{:08X} to address 0x{:08X}", value, addr);
+        self.conn.write(&[Cmd::WriteReg32 as u8]).await?;
+        self.conn.write(&addr.to_be_bytes()).await?;
+        self.conn.write(&value.to_be_bytes()).await?;
+        self.expect_ack("Register write").await
+    }
+
+    async fn get_usb_speed(&mut self) -> Result<u32> {
+        self.conn.write(&[Cmd::UsbCheckStatus as u8]).await?;
+        self.expect_ack("USB status").await?;
+        Ok(self.read_u8().await? as u32)
+    }
+
+    async fn get_random_id(&mut self) -> Result<Vec<u8>> {
+        Err(Error::unsupported("Legacy DAs have no random ID"))
+    }
+
+    async fn get_hw_code(&mut self) -> Result<u16> {
+        Err(Error::unsupported("Legacy DAs can't report the hardware code"))
+    }
+
+    fn get_connection(&mut self) -> &mut Connection {
+        &mut self.conn
+    }
+
+    fn into_connection(self: Box<Self>) -> Connection {
+        self.conn
+    }
+
+    fn bringup_steps(&mut self) -> &mut StepRecorder {
+        &mut self.bringup
+    }
+
+    fn set_connection_type(&mut self, conn_type: ConnectionType) -> Result<()> {
+        self.conn.connection_type = conn_type;
+        Ok(())
+    }
+
+    async fn get_storage(&mut self) -> Option<Arc<dyn Storage>> {
+        self.dev_info.storage().await.map(|s| s as Arc<dyn Storage>)
+    }
+
+    async fn get_storage_type(&mut self) -> StorageType {
+        self.get_storage().await.map_or(StorageType::Unknown, |s| s.kind())
+    }
+
+    async fn get_partitions(&mut self) -> Result<Vec<Partition>> {
+        let storage = self.get_storage().await.ok_or_else(|| {
+            Error::penumbra("Storage not detected, can't read the partition table")
+        })?;
+
+        let storage_type = storage.kind();
+        let user_part = storage.get_user_part();
+        let user_size = storage.get_user_size();
+
+        let mut partitions: Vec<_> =
+            preloader_partitions(storage.as_ref()).into_iter().filter(|p| p.size != 0).collect();
+        partitions.push(Partition::new("PGPT", GPT_SIZE, 0, user_part));
+
+        let mut pgpt_data = Vec::new();
+        self.read_flash(0, GPT_SIZE, user_part, &mut |_| {}, &mut pgpt_data).await?;
+        self.dev_info.set_raw_pgpt(pgpt_data.clone()).await;
+
+        // Many MT6572 and MT6580 devices use MediaTek's own partition table (PMT) instead,
+        // which isn't parsed: only GPT devices are supported with legacy DAs
+        let gpt = Gpt::parse_as(&pgpt_data, GptType::Pgpt, storage_type).map_err(|e| {
+            Error::unsupported(format!(
+                "No GPT found ({}). The device probably uses a MediaTek PMT, which isn't \
+                 supported with legacy DAs",
+                e
+            ))
+        })?;
+        partitions.append(&mut gpt.partitions());
+
+        let sgpt_address = user_size.saturating_sub(GPT_SIZE as u64);
+        partitions.push(Partition::new("SGPT", GPT_SIZE, sgpt_address, user_part));
+
+        Ok(partitions)
+    }
+
+    #[cfg(feature = "seccfg")]
+    async fn set_seccfg_lock_state(&mut self, _locked: LockFlag) -> Option<Vec<u8>> {
+        warn!("Changing the lock state is not supported with legacy DAs");
+        None
+    }
+
+    async fn peek(
+        &mut self,
+        _addr: u32,
+        _length: usize,
+        _writer: &mut (dyn AsyncWrite + Unpin + Send),
+        _progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        Err(Error::unsupported("Reading memory requires the DA extensions"))
+    }
+
+    fn patch_da(&mut self) -> Option<DA> {
+        None
+    }
+
+    fn patch_da1(&mut self) -> Option<DAEntryRegion> {
+        None
+    }
+
+    fn patch_da2(&mut self) -> Option<DAEntryRegion> {
+        None
+    }
+
+    fn get_devinfo(&self) -> &DeviceInfo {
+        &self.dev_info
+    }
+
+    fn get_da(&self) -> &DA {
+        &self.da
+    }
+}
@@ -0,0 +1,303 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::sync::Arc;
+
+use log::{debug, info, warn};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::connection::Connection;
+use crate::core::devinfo::DeviceInfo;
+use crate::core::emi::{emi_version, parse_emi};
+use crate::core::progress::{ProgressEvent, ProgressReporter};
+use crate::core::storage::emmc::EmmcStorage;
+use crate::core::summary::OperationKind;
+use crate::da::legacy::cmds::*;
+use crate::da::protocol::packet_checksum;
+use crate::da::{DA, DAEntryRegion, StepRecorder};
+use crate::error::{Error, Result};
+
+/// Handler for the DAs that predate XFlash, found on MT65xx and early MT67xx devices.
+///
+/// Only reading is implemented: writes, erases and formats fail with
+/// [`Error::Unsupported`]. Storage is limited to eMMC partitioned with a GPT: the partitions
+/// of devices using MediaTek's PMT, common on MT6572 and MT6580, can't be listed, so only
+/// raw offsets can be read on them.
+pub struct Legacy {
+    pub conn: Connection,
+    pub da: DA,
+    pub pl: Option<Vec<u8>>,
+    pub dev_info: DeviceInfo,
+    pub(super) bringup: StepRecorder,
+}
+
+impl Legacy {
+    pub fn new(conn: Connection, da: DA, dev_info: DeviceInfo, pl: Option<Vec<u8>>) -> Self {
+        Legacy { conn, da, pl, dev_info, bringup: StepRecorder::default() }
+    }
+
+    pub(super) async fn read_u8(&mut self) -> Result<u8> {
+        let mut buf = [0u8; 1];
+        self.conn.read(&mut buf).await?;
+        Ok(buf[0])
+    }
+
+    pub(super) async fn read_u16(&mut self) -> Result<u16> {
+        let mut buf = [0u8; 2];
+        self.conn.read(&mut buf).await?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    pub(super) async fn read_u32(&mut self) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        self.conn.read(&mut buf).await?;
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    pub(super) async fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        self.conn.read(&mut buf).await?;
+        Ok(buf)
+    }
+
+    /// Reads a fixed size answer, so its fields can be picked without bound checks.
+    pub(super) async fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.conn.read(&mut buf).await?;
+        Ok(buf)
+    }
+
+    /// Reads the one byte answer of a command, failing unless it's an ACK.
+    pub(super) async fn expect_ack(&mut self, what: &str) -> Result<()> {
+        match self.read_u8().await? {
+            ack if ack == Rsp::Ack as u8 => Ok(()),
+            other => Err(Error::proto(format!("{} not acknowledged (0x{:02X})", what, other))),
+        }
+    }
+
+    /// Sends DA1 through BROM and jumps to it, then reads what it reports of the flash.
+    pub(super) async fn upload_stage1(&mut self, da1: &DAEntryRegion) -> Result<()> {
+        info!(
+            "[Penumbra] Uploading DA1 region to address 0x{:08X} with length 0x{:X}",
+            da1.addr, da1.length
+        );
+
+        self.conn.send_da(&da1.data, da1.length, da1.addr, da1.sig_len).await?;
+        info!("[Penumbra] Sent DA1, jumping to address 0x{:08X}...", da1.addr);
+        self.conn.jump_da(da1.addr).await?;
+        self.conn.expect_reenumeration();
+
+        if self.read_u8().await? != Rsp::Sync as u8 {
+            return Err(Error::proto("Incorrect sync byte received"));
+        }
+        info!("[Penumbra] Received sync byte");
+
+        let nand_info = self.read_u32().await?;
+        let nand_id_count = self.read_u16().await?;
+        let nand_ids = self.read_bytes(nand_id_count as usize * 2).await?;
+        debug!("NAND info: 0x{:08X}, IDs: {:02X?}", nand_info, nand_ids);
+
+        let emmc_info = self.read_u32().await?;
+        let emmc_ids = self.read_bytes(16).await?;
+        debug!("eMMC info: 0x{:08X}, IDs: {:02X?}", emmc_info, emmc_ids);
+
+        self.conn.write(&[Rsp::Ack as u8]).await?;
+        let version = self.read_array::<3>().await?;
+        info!("[Penumbra] DA1 v{}.{}, baseband chip 0x{:02X}", version[0], version[1], version[2]);
+
+        Ok(())
+    }
+
+    /// Sends the settings DA1 needs before DA2, then brings up DRAM, with the EMI settings
+    /// of the preloader when DA1 asks for them.
+    pub(super) async fn setup_stage2(&mut self) -> Result<()> {
+        let hw_code = self.da.hw_code;
+
+        let mut config = Vec::new();
+        config.extend_from_slice(&0x0008u16.to_be_bytes()); // NOR chip: CS_WITH_DECODER
+        config.push(0x00); // NOR chip select: CS_0
+        config.extend_from_slice(&0x7007_FFFFu32.to_be_bytes()); // NAND ACCCON
+        config.push(0x00); // BMT flag
+        config.extend_from_slice(&0u32.to_be_bytes()); // BMT partition size
+        config.push(0x02); // Force charge: auto
+        config.push(if hw_code == 0x6583 { 0x00 } else { 0x01 }); // Reset keys
+        config.push(0x02); // External clock: 26 MHz
+        config.push(0x00); // MSDC boot channel
+        config.extend_from_slice(&stage2_quirks(hw_code));
+        self.conn.write(&config).await?;
+
+        match self.read_u32().await? {
+            DRAM_NEEDS_EMI => self.send_emi().await?,
+            DRAM_READY => debug!("DRAM needs no EMI settings"),
+            status => {
+                return Err(Error::proto(format!("Unexpected DRAM status 0x{:X}", status)));
+            }
+        }
+
+        self.conn.write(&DRAM_INIT.to_be_bytes()).await?;
+        let status = self.read_u32().await?;
+        let ram = self.read_array::<10>().await?;
+        if status != 0 {
+            return Err(Error::proto(format!("DRAM init failed with status 0x{:X}", status)));
+        }
+
+        let mut size = [0u8; 8];
+        size.copy_from_slice(&ram[2..10]);
+        info!("[Penumbra] DRAM ready, 0x{:X} bytes", u64::from_be_bytes(size));
+
+        Ok(())
+    }
+
+    async fn send_emi(&mut self) -> Result<()> {
+        let dram_info = self.read_bytes(20).await?;
+        debug!("DRAM info: {:02X?}", dram_info);
+
+        let pl = self.pl.as_ref().ok_or_else(|| {
+            Error::penumbra("The DA needs the EMI settings of the preloader, but none was provided")
+        })?;
+        let emi = parse_emi(pl)?;
+
+        info!("[Penumbra] Uploading EMI settings to device...");
+        match emi_version(&emi) {
+            Some(0x0F | 0x10 | 0x11 | 0x14 | 0x15) => {
+                let expected = self.read_u32().await?;
+                debug!("DA1 expects 0x{:X} bytes of EMI settings", expected);
+                self.conn.write(&[Rsp::Ack as u8]).await?;
+                self.conn.write(&(emi.len() as u32).to_be_bytes()).await?;
+            }
+            Some(0x0B) => {
+                let info = self.read_bytes(16).await?;
+                let expected = self.read_u32().await?;
+                debug!("EMI info {:02X?}, 0x{:X} bytes expected", info, expected);
+                self.conn.write(&[Rsp::Ack as u8]).await?;
+            }
+            version => {
+                return Err(Error::unsupported(format!(
+                    "EMI settings version {:?} isn't supported by the legacy DA",
+                    version
+                )));
+            }
+        }
+
+        self.conn.write(&emi).await?;
+        let checksum = self.read_u16().await?;
+        debug!("EMI checksum: 0x{:04X}", checksum);
+        self.conn.write(&[Rsp::Ack as u8]).await?;
+
+        info!("[Penumbra] EMI settings uploaded successfully.");
+        Ok(())
+    }
+
+    /// Sends DA2 to DA1 in acknowledged packets. DA1 starts it once complete.
+    pub(super) async fn upload_stage2(&mut self, da2: &DAEntryRegion) -> Result<()> {
+        info!(
+            "[Penumbra] Uploading DA2 to address 0x{:08X} with size 0x{:X} bytes",
+            da2.addr,
+            da2.data.len()
+        );
+
+        let mut param = [0u8; 12];
+        param[0..4].copy_from_slice(&da2.addr.to_be_bytes());
+        param[4..8].copy_from_slice(&(da2.data.len() as u32).to_be_bytes());
+        param[8..12].copy_from_slice(&(DA2_PACKET_SIZE as u32).to_be_bytes());
+        self.conn.write(&param).await?;
+        self.expect_ack("DA2 upload").await?;
+
+        for (i, chunk) in da2.data.chunks(DA2_PACKET_SIZE).enumerate() {
+            self.conn.write(chunk).await?;
+            self.expect_ack(&format!("DA2 packet {}", i)).await?;
+        }
+
+        self.conn.write(&[Rsp::Ack as u8]).await?;
+        self.expect_ack("DA2 start").await?;
+
+        info!("[Penumbra] Successfully uploaded and executed DA2");
+        Ok(())
+    }
+
+    /// Reads the flash info DA2 sends once started, keeping the eMMC one as storage.
+    pub(super) async fn read_flash_info(&mut self) -> Result<()> {
+        let nor = self.read_bytes(NOR_INFO_SIZE).await?;
+        debug!("NOR info: {:02X?}", nor);
+
+        let nand = self.read_array::<NAND_INFO_SIZE>().await?;
+        let nand_id_count = u16::from_be_bytes([nand[15], nand[16]]) as usize;
+        let nand_ids = self.read_bytes(nand_id_count * 2).await?;
+        let nand2 = self.read_bytes(NAND_INFO2_SIZE).await?;
+        debug!("NAND info: {:02X?}, IDs: {:02X?}, {:02X?}", nand, nand_ids, nand2);
+
+        let emmc = self.read_bytes(EMMC_INFO_SIZE).await?;
+        let sdc = self.read_bytes(SDC_INFO_SIZE).await?;
+        let config = self.read_bytes(CONFIG_INFO_SIZE).await?;
+        debug!("SDC info: {:02X?}, config: {:02X?}", sdc, config);
+
+        let pass = self.read_array::<PASS_INFO_SIZE>().await?;
+        if pass[0] != Rsp::Ack as u8 {
+            let status = u32::from_be_bytes([pass[1], pass[2], pass[3], pass[4]]);
+            return Err(Error::proto(format!("DA2 failed to start, status 0x{:08X}", status)));
+        }
+
+        match EmmcStorage::from_legacy_response(&emmc) {
+            Ok(storage) if storage.info.user_size != 0 => {
+                debug!("eMMC storage detected.");
+                self.dev_info.set_storage(Arc::new(storage)).await;
+            }
+            Ok(_) => warn!("No eMMC found, only eMMC is supported with legacy DAs"),
+            Err(e) => warn!("No usable eMMC: {}", e),
+        }
+
+        Ok(())
+    }
+
+    /// Receives `size` bytes of flash, sent by the DA in checksummed packets.
+    pub(super) async fn upload_data(
+        &mut self,
+        size: usize,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        let mut progress = ProgressReporter::new(OperationKind::Read, progress);
+        let mut bytes_read = 0;
+        progress.report(0, size);
+
+        while bytes_read < size {
+            self.conn.check_host_awake()?;
+            let chunk = self.read_bytes(READ_PACKET_SIZE.min(size - bytes_read)).await?;
+            let checksum = self.read_u16().await?;
+            if checksum as u32 != packet_checksum(&chunk) {
+                return Err(Error::ChecksumMismatch { offset: bytes_read as u64 });
+            }
+            self.conn.write(&[Rsp::Ack as u8]).await?;
+
+            writer.write_all(&chunk).await?;
+            bytes_read += chunk.len();
+            progress.report(bytes_read, size);
+        }
+
+        Ok(())
+    }
+}
+
+/// Extra stage 2 settings some chipsets expect after the common ones.
+fn stage2_quirks(hw_code: u16) -> Vec<u8> {
+    match hw_code {
+        // GPT solution flag
+        0x6592 => 0u32.to_be_bytes().to_vec(),
+        // SLC percentage, then a fixed pattern
+        0x6580 | 0x8163 => {
+            let mut quirk = 1u32.to_be_bytes().to_vec();
+            quirk.extend_from_slice(&[0x46; 17]);
+            quirk.extend_from_slice(&[0x00; 15]);
+            quirk
+        }
+        // Force DRAM
+        0x6583 => 0u32.to_be_bytes().to_vec(),
+        0x6589 => 1u32.to_be_bytes().to_vec(),
+        // Skip download
+        0x8127 => 0u32.to_be_bytes().to_vec(),
+        // New combo
+        0x6582 => vec![0x00],
+        _ => Vec::new(),
+    }
+}
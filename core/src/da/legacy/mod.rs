@@ -0,0 +1,10 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+mod cmds;
+mod da_protocol;
+mod legacy_lib;
+#[cfg(test)]
+mod tests;
+pub use legacy_lib::Legacy;
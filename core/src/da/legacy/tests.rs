@@ -0,0 +1,208 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Legacy DA sequences against scripted exchanges, played back by the mock port.
+//! The scripts follow the documented legacy protocol, they aren't captures from a device.
+use std::sync::Arc;
+
+use crate::connection::Connection;
+use crate::connection::mock::{MockHandle, MockMTKPort, Script};
+use crate::core::devinfo::DeviceInfo;
+use crate::core::storage::emmc::{EmmcPartition, EmmcStorage};
+use crate::core::storage::{PartitionKind, StorageType};
+use crate::da::legacy::Legacy;
+use crate::da::protocol::packet_checksum;
+use crate::da::{DA, DAEntryRegion, DAProtocol, DAType};
+use crate::error::Error;
+
+const DA1_ADDR: u32 = 0x0020_0000;
+const DA2_ADDR: u32 = 0x8000_0000;
+const USER_SIZE: u64 = 0x1_D1C0_0000;
+
+fn region(addr: u32, data: Vec<u8>) -> DAEntryRegion {
+    let length = data.len() as u32;
+    DAEntryRegion { data, offset: 0, length, addr, region_length: length, sig_len: 0 }
+}
+
+fn legacy(script: Script) -> (Legacy, MockHandle) {
+    let port = MockMTKPort::new(script);
+    let handle = port.handle();
+
+    let da = DA {
+        da_type: DAType::Legacy,
+        regions: vec![
+            region(0, Vec::new()),
+            region(DA1_ADDR, vec![0x11; 0x40]),
+            region(DA2_ADDR, vec![0x22; 0x1800]),
+        ],
+        magic: 0xDADA,
+        hw_code: 0x0321,
+        hw_sub_code: 0x8A00,
+        issues: Vec::new(),
+    };
+
+    (Legacy::new(Connection::new(Box::new(port)), da, DeviceInfo::new(), None), handle)
+}
+
+/// eMMC info as DA2 reports it: status, partition sizes, CID and firmware version.
+fn emmc_info() -> Vec<u8> {
+    let mut info = 0u32.to_be_bytes().to_vec();
+    info.extend_from_slice(&0x40_0000u64.to_be_bytes()); // Boot1
+    info.extend_from_slice(&0x40_0000u64.to_be_bytes()); // Boot2
+    info.extend_from_slice(&0x40_0000u64.to_be_bytes()); // RPMB
+    info.extend_from_slice(&[0x00; 32]); // GP1-4
+    info.extend_from_slice(&USER_SIZE.to_be_bytes());
+    info.extend_from_slice(&[0x15, 0x01, 0x00, b'Q', b'E', b'1', b'3', b'M', b'B', 0x02]);
+    info.extend_from_slice(&[0x12, 0x34, 0x56, 0x78, 0x51, 0x00]);
+    info.extend_from_slice(&0x0300_0000_0000_0000u64.to_be_bytes());
+    info
+}
+
+/// Legacy DA boot from BROM, up to the flash info of DA2, on a device with eMMC
+/// and DRAM already set up.
+fn boot_script() -> Script {
+    Script::new()
+        // BROM side: send and jump to DA1
+        .echo([0xD7])
+        .echo(DA1_ADDR.to_be_bytes())
+        .echo(0x40u32.to_be_bytes())
+        .echo(0u32.to_be_bytes())
+        .respond([0x00, 0x00])
+        .expect([0x11; 0x40])
+        .respond([0x04, 0x40])
+        .respond([0x00, 0x00])
+        .echo([0xD5])
+        .echo(DA1_ADDR.to_be_bytes())
+        .respond([0x00, 0x00])
+        // DA1: sync, NAND and eMMC IDs, then its version
+        .respond([0xC0])
+        .respond([0x00; 4])
+        .respond([0x00, 0x01, 0x00, 0x00])
+        .respond([0x00; 4])
+        .respond([0x15, 0x01, 0x00, 0x51].repeat(4))
+        .expect([0x5A])
+        .respond([0x04, 0x00, 0x81])
+        // Stage 2 config
+        .expect([
+            0x00, 0x08, // NOR chip
+            0x00, // NOR chip select
+            0x70, 0x07, 0xFF, 0xFF, // NAND ACCCON
+            0x00, 0x00, 0x00, 0x00, 0x00, // BMT
+            0x02, 0x01, 0x02, 0x00, // Force charge, reset keys, clock, MSDC channel
+        ])
+        .respond(0xBC4u32.to_be_bytes())
+        .expect(0x8000_0001u32.to_be_bytes())
+        .respond([0x00; 4])
+        .respond([0x02, 0x00])
+        .respond(0x4000_0000u64.to_be_bytes())
+        // DA2, in two acknowledged packets
+        .expect(DA2_ADDR.to_be_bytes())
+        .expect(0x1800u32.to_be_bytes())
+        .expect(0x1000u32.to_be_bytes())
+        .respond([0x5A])
+        .expect([0x22; 0x1000])
+        .respond([0x5A])
+        .expect([0x22; 0x800])
+        .respond([0x5A])
+        .expect([0x5A])
+        .respond([0x5A])
+        // DA2 flash info: NOR, NAND (no IDs), eMMC, SDC, config, then the pass info
+        .respond([0x00; 0x1C])
+        .respond([0x00; 0x11])
+        .respond([0x00; 0x09])
+        .respond(emmc_info())
+        .respond([0x00; 0x1C])
+        .respond([0x00; 0x26])
+        .respond([0x5A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01])
+}
+
+#[tokio::test]
+async fn boot_detects_emmc() {
+    let (mut legacy, handle) = legacy(boot_script());
+
+    assert!(legacy.upload_da().await.unwrap());
+    handle.assert_done();
+
+    let storage = legacy.get_storage().await.expect("eMMC not detected");
+    assert_eq!(storage.kind(), StorageType::Emmc);
+    assert_eq!(storage.get_user_size(), USER_SIZE);
+    assert_eq!(storage.info().model.as_deref(), Some("QE13MB"));
+}
+
+#[test]
+fn legacy_emmc_info_rejects_failed_init() {
+    let mut info = emmc_info();
+    info[3] = 0x01;
+
+    assert!(EmmcStorage::from_legacy_response(&info).is_err());
+    assert!(EmmcStorage::from_legacy_response(&info[..0x40]).is_err());
+}
+
+/// Read command for `size` bytes at `addr` of the eMMC user area.
+fn read_cmd(addr: u64, size: u64) -> Vec<u8> {
+    let mut cmd = vec![0xD6, 0x0C, 0x02, 0x08];
+    cmd.extend_from_slice(&addr.to_be_bytes());
+    cmd.extend_from_slice(&size.to_be_bytes());
+    cmd.extend_from_slice(&0x10_0000u32.to_be_bytes());
+    cmd
+}
+
+#[tokio::test]
+async fn read_flash_checks_packets() {
+    let data: Vec<u8> = (0..0x200).map(|i| i as u8).collect();
+    let checksum = packet_checksum(&data) as u16;
+
+    let script = Script::new()
+        .expect(read_cmd(0x1000, 0x200))
+        .respond([0x5A])
+        .respond(&data)
+        .respond(checksum.to_be_bytes())
+        .expect([0x5A])
+        .expect(read_cmd(0x1000, 0x200))
+        .respond([0x5A])
+        .respond(&data)
+        .respond((checksum ^ 0x1).to_be_bytes());
+    let (mut legacy, handle) = legacy(script);
+
+    let user = PartitionKind::Emmc(EmmcPartition::User);
+    let mut out = Vec::new();
+    legacy.read_flash(0x1000, 0x200, user, &mut |_| {}, &mut out).await.unwrap();
+    assert_eq!(out, data);
+
+    let mut out = Vec::new();
+    let err = legacy.read_flash(0x1000, 0x200, user, &mut |_| {}, &mut out).await.unwrap_err();
+    assert!(matches!(err, Error::ChecksumMismatch { offset: 0 }));
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn writes_are_unsupported() {
+    let (mut legacy, handle) = legacy(Script::new());
+
+    let user = PartitionKind::Emmc(EmmcPartition::User);
+    let mut data: &[u8] = &[0x00; 0x200];
+    let err = legacy.write_flash(0, 0x200, &mut data, user, false, &mut |_| {}).await.unwrap_err();
+    assert!(matches!(err, Error::Unsupported(_)));
+    assert!(legacy.erase_flash(0, 0x200, user, &mut |_| {}).await.is_err());
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn pmt_devices_are_unsupported() {
+    // A PMT device has no GPT header at LBA1
+    let pgpt = [0x00; 0x8000];
+    let script = Script::new()
+        .expect(read_cmd(0, 0x8000))
+        .respond([0x5A])
+        .respond(pgpt)
+        .respond((packet_checksum(&pgpt) as u16).to_be_bytes())
+        .expect([0x5A]);
+    let (mut legacy, handle) = legacy(script);
+    let emmc = EmmcStorage::from_legacy_response(&emmc_info()).unwrap();
+    legacy.dev_info.set_storage(Arc::new(emmc)).await;
+
+    let err = legacy.get_partitions().await.unwrap_err();
+    assert!(matches!(&err, Error::Unsupported(msg) if msg.contains("PMT")), "{}", err);
+    handle.assert_done();
+}
@@ -2,11 +2,17 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+pub mod bringup;
 pub mod dafile;
+pub mod legacy;
 pub mod protocol;
+pub mod repository;
 pub mod xflash;
 pub mod xml;
-pub use dafile::{DA, DAEntryRegion, DAFile, DAType};
+pub use bringup::{BringupReport, BringupStep, StepRecorder};
+pub use dafile::{DA, DAEntryRegion, DAFile, DAIssue, DAType};
+pub use legacy::Legacy;
 pub use protocol::DAProtocol;
+pub use repository::DaRepository;
 pub use xflash::XFlash;
 pub use xml::Xml;
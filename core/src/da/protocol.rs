@@ -5,15 +5,19 @@
 use std::sync::Arc;
 
 use downcast_rs::{DowncastSend, impl_downcast};
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
 
 use crate::connection::Connection;
 use crate::connection::port::ConnectionType;
 use crate::core::devinfo::DeviceInfo;
+use crate::core::progress::ProgressEvent;
+#[cfg(feature = "seccfg")]
 use crate::core::seccfg::LockFlag;
 use crate::core::storage::{Partition, PartitionKind, Storage, StorageType};
-use crate::da::{DA, DAEntryRegion};
-use crate::error::Result;
+use crate::da::xml::RawXmlCommand;
+use crate::da::{DA, DAEntryRegion, StepRecorder};
+use crate::error::{Error, Result};
+use crate::exploit::ExploitReport;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BootMode {
@@ -22,19 +26,80 @@ pub enum BootMode {
     Fastboot,
     Test,
     Meta,
+    /// META mode with ADB enabled alongside
+    AdbMeta,
+    /// Recovery, through the bootloader message in `misc`
+    Recovery,
+    /// Back to the BootROM/preloader download mode
+    Brom,
 }
 
 impl BootMode {
     pub fn to_text(&self) -> Option<&'static str> {
         match self {
             BootMode::Fastboot => Some("FASTBOOT"),
-            BootMode::Meta => Some("META"),
+            BootMode::Meta | BootMode::AdbMeta => Some("META"),
             BootMode::Test => Some("ANDROID-TEST-MODE"),
-            BootMode::Normal | BootMode::HomeScreen => None,
+            BootMode::Normal | BootMode::HomeScreen | BootMode::Recovery | BootMode::Brom => None,
         }
     }
 }
 
+/// How much the DA checks transferred data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumLevel {
+    /// No checks, the fastest
+    #[default]
+    None,
+    /// Each packet is checksummed over USB
+    Usb,
+    /// Data is also checked after being written to storage
+    Storage,
+}
+
+impl ChecksumLevel {
+    pub fn to_text(&self) -> &'static str {
+        match self {
+            ChecksumLevel::None => "NONE",
+            ChecksumLevel::Usb => "USB",
+            ChecksumLevel::Storage => "STORAGE",
+        }
+    }
+}
+
+/// Size of the data carried by one RPMB frame, the unit of RPMB reads and writes.
+pub const RPMB_FRAME_SIZE: usize = 256;
+/// Size of the RPMB authentication key.
+pub const RPMB_KEY_SIZE: usize = 32;
+
+/// Largest packet a DA is expected to announce in a header. Anything above comes from a
+/// corrupted header, and would only make the host allocate the announced length.
+pub(crate) const MAX_PACKET_LENGTH: u32 = 0x400_0000;
+
+/// The 16-bit additive checksum DAs use for data packets: all bytes summed, ANDed with 0xFFFF.
+pub fn packet_checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |total, &byte| total.wrapping_add(byte as u32)) & 0xFFFF
+}
+
+/// Fills `buf` from `reader`, reading on after short reads, which don't mean EOF.
+/// `done` is how much of the `expected` input was read before, for the error
+/// when the reader ends first.
+pub async fn fill_chunk<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    buf: &mut [u8],
+    done: usize,
+    expected: usize,
+) -> Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(buf.get_mut(filled..).unwrap_or_default()).await? {
+            0 => return Err(Error::InputTooShort { expected, got: done + filled }),
+            n => filled += n,
+        }
+    }
+    Ok(())
+}
+
 #[async_trait::async_trait]
 pub trait DAProtocol: DowncastSend {
     // Main helpers
@@ -45,6 +110,8 @@ pub trait DAProtocol: DowncastSend {
     async fn get_status(&mut self) -> Result<u32>;
     async fn shutdown(&mut self) -> Result<()>;
     async fn reboot(&mut self, bootmode: BootMode) -> Result<()>;
+    // Sends a lightweight no-op command, to keep the DA watchdog from firing while idle
+    async fn keepalive(&mut self) -> Result<()>;
     // FLASH operations
     // fn read_partition(&mut self, name: &str) -> Result<Vec<u8>, Error>;
     async fn read_flash(
@@ -52,17 +119,19 @@ pub trait DAProtocol: DowncastSend {
         addr: u64,
         size: usize,
         section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
         writer: &mut (dyn AsyncWrite + Unpin + Send),
     ) -> Result<()>;
 
+    // Fails with InputTooShort if `reader` ends before `size`, unless `pad_with_zeros`
     async fn write_flash(
         &mut self,
         addr: u64,
         size: usize,
         reader: &mut (dyn AsyncRead + Unpin + Send),
         section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        pad_with_zeros: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()>;
 
     async fn erase_flash(
@@ -70,7 +139,7 @@ pub trait DAProtocol: DowncastSend {
         addr: u64,
         size: usize,
         section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()>;
 
     async fn download(
@@ -78,20 +147,20 @@ pub trait DAProtocol: DowncastSend {
         part_name: String,
         size: usize,
         reader: &mut (dyn AsyncRead + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()>;
 
     async fn upload(
         &mut self,
         part_name: String,
         reader: &mut (dyn AsyncWrite + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()>;
 
     async fn format(
         &mut self,
         part_name: String,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()>;
 
     // Memory
@@ -99,45 +168,129 @@ pub trait DAProtocol: DowncastSend {
     async fn write32(&mut self, addr: u32, value: u32) -> Result<()>;
 
     async fn get_usb_speed(&mut self) -> Result<u32>;
+    // Per-boot random value generated by the DA, used to identify a session
+    async fn get_random_id(&mut self) -> Result<Vec<u8>>;
+    // Hardware code as reported by the DA, for sessions that didn't go through BROM
+    async fn get_hw_code(&mut self) -> Result<u16>;
+    /// Reads the eFuse words, as raw little-endian bytes.
+    async fn read_efuse(&mut self) -> Result<Vec<u8>> {
+        Err(Error::unsupported("Reading eFuses is not supported by this DA protocol"))
+    }
+    /// Sends an XML command built at runtime, returning the DA answer as text.
+    async fn send_raw_xml(&mut self, _cmd: &RawXmlCommand) -> Result<String> {
+        Err(Error::unsupported("Raw XML commands need an XML DA"))
+    }
     // fn set_usb_speed(&mut self, speed: u32) -> Result<(), Error>;
 
     // Connection
     fn get_connection(&mut self) -> &mut Connection;
+    /// Gives the connection back, to rebuild the protocol around another DA.
+    fn into_connection(self: Box<Self>) -> Connection;
+    // Step timeline of the DA bring-up, filled by `upload_da`
+    fn bringup_steps(&mut self) -> &mut StepRecorder;
     fn set_connection_type(&mut self, conn_type: ConnectionType) -> Result<()>;
 
     async fn get_storage(&mut self) -> Option<Arc<dyn Storage>>;
     async fn get_storage_type(&mut self) -> StorageType;
-    async fn get_partitions(&mut self) -> Vec<Partition>;
+    /// Reads the partition table, falling back to the backup GPT if the primary one is invalid.
+    async fn get_partitions(&mut self) -> Result<Vec<Partition>>;
 
     // DevInfo helpers
     fn get_devinfo(&self) -> &DeviceInfo;
     fn get_da(&self) -> &DA;
 
     /* EXTENSIONS / EXPLOITS
-     * These functions won't be included if the "no_exploits" feature is enabled
+     * These functions are only included with the matching capability features enabled
      */
 
     // Sec
-    #[cfg(not(feature = "no_exploits"))]
+    #[cfg(feature = "seccfg")]
     async fn set_seccfg_lock_state(&mut self, locked: LockFlag) -> Option<Vec<u8>>;
 
-    #[cfg(not(feature = "no_exploits"))]
+    /// Returns the exploits considered by the last `upload_da`, resetting them.
+    fn take_exploit_report(&mut self) -> ExploitReport {
+        ExploitReport::default()
+    }
+    /// Whether the DA extensions are running.
+    fn exts_active(&self) -> bool {
+        false
+    }
+    /// Marks the DA extensions as running or not, for sessions resumed from a previous
+    /// run where they were already booted. Nothing is sent to the device.
+    fn set_exts_state(&mut self, _active: bool) {}
+    /// Checks that the DA extensions answer, without booting them.
+    async fn probe_extensions(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Sets the RPMB key, if given, and prepares the extensions for RPMB access.
+    async fn init_rpmb(&mut self, _key: Option<&[u8; RPMB_KEY_SIZE]>) -> Result<()> {
+        Err(Error::unsupported("RPMB access is not supported by this DA protocol"))
+    }
+    /// Reads `blocks` RPMB frames, starting at frame `address`.
+    async fn read_rpmb(
+        &mut self,
+        _address: u16,
+        _blocks: u16,
+        _writer: &mut (dyn AsyncWrite + Unpin + Send),
+        _progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        Err(Error::unsupported("RPMB access is not supported by this DA protocol"))
+    }
+    /// Writes `blocks` RPMB frames, starting at frame `address`.
+    async fn write_rpmb(
+        &mut self,
+        _address: u16,
+        _blocks: u16,
+        _reader: &mut (dyn AsyncRead + Unpin + Send),
+        _progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        Err(Error::unsupported("RPMB access is not supported by this DA protocol"))
+    }
+
     async fn peek(
         &mut self,
         addr: u32,
         length: usize,
         writer: &mut (dyn AsyncWrite + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()>;
 
     // DA Patching utils. These *must* be protocol specific, as different protocols
     // have different DA implementations
-    #[cfg(not(feature = "no_exploits"))]
     fn patch_da(&mut self) -> Option<DA>;
-    #[cfg(not(feature = "no_exploits"))]
     fn patch_da1(&mut self) -> Option<DAEntryRegion>;
-    #[cfg(not(feature = "no_exploits"))]
     fn patch_da2(&mut self) -> Option<DAEntryRegion>;
 }
 
 impl_downcast!(DAProtocol);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_checksum_sums_the_bytes() {
+        assert_eq!(packet_checksum(&[]), 0);
+        assert_eq!(packet_checksum(&[0x01, 0x02, 0x03]), 0x06);
+        assert_eq!(packet_checksum(&[0xA5; 0x10]), 0xA50);
+    }
+
+    #[test]
+    fn packet_checksum_keeps_the_low_16_bits() {
+        // 0x101 * 0xFF fills exactly 16 bits, one more byte carries out of them
+        assert_eq!(packet_checksum(&[0xFF; 0x101]), 0xFFFF);
+        assert_eq!(packet_checksum(&[0xFF; 0x102]), 0x00FE);
+        // A 1 MiB packet, whose sum wraps many times
+        assert_eq!(packet_checksum(&vec![0x5A; 0x10_0000]), (0x5A * 0x10_0000) & 0xFFFF);
+    }
+
+    #[test]
+    fn checksum_levels() {
+        assert_eq!(ChecksumLevel::default(), ChecksumLevel::None);
+
+        let levels = [ChecksumLevel::None, ChecksumLevel::Usb, ChecksumLevel::Storage];
+        let texts: Vec<_> = levels.iter().map(ChecksumLevel::to_text).collect();
+        assert_eq!(texts, ["NONE", "USB", "STORAGE"]);
+    }
+}
@@ -0,0 +1,385 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Directory of DA files, indexed by the chipsets they support.
+//!
+//! Each file is parsed once, the hw_codes it supports being kept in an index next to
+//! the files, keyed by the SHA-256 of the file. Files are only read again once their
+//! size or modification time change, so opening a repository stays fast.
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs::{copy, create_dir_all, metadata, read, read_dir, rename, try_exists, write};
+
+use crate::da::{DAFile, DAType};
+use crate::error::{Error, Result};
+
+/// File name of the index, within the repository directory.
+pub const DA_INDEX_NAME: &str = "index.json";
+
+/// What the index knows of a DA file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DaIndexEntry {
+    /// File name, relative to the repository directory
+    pub file: String,
+    pub da_type: DAType,
+    /// Identifier of the DA build
+    pub da_id: String,
+    /// Chipsets with an entry in the file, sorted
+    pub hw_codes: Vec<u16>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct DaIndex {
+    /// Indexed files, by SHA-256
+    #[serde(default)]
+    files: BTreeMap<String, DaIndexEntry>,
+    /// File picked for a chipset when several support it
+    #[serde(default)]
+    preferred: BTreeMap<u16, String>,
+    /// What each file of the directory looked like when it was last read, by name
+    #[serde(default)]
+    stamps: BTreeMap<String, FileStamp>,
+}
+
+/// Size and modification time of a file when it was read, to tell whether it changed since.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct FileStamp {
+    size: u64,
+    modified: Option<SystemTime>,
+    /// SHA-256 of the file, `None` if it couldn't be parsed as a DA
+    hash: Option<String>,
+}
+
+impl FileStamp {
+    fn new(meta: &Metadata, hash: Option<String>) -> Self {
+        FileStamp { size: meta.len(), modified: meta.modified().ok(), hash }
+    }
+
+    /// Whether the file is unchanged since `self` was taken. Without a modification
+    /// time, files are always read again.
+    fn matches(&self, meta: &Metadata) -> bool {
+        self.modified.is_some() && self.size == meta.len() && self.modified == meta.modified().ok()
+    }
+}
+
+/// DA files of a directory, looked up by the chipset they support.
+pub struct DaRepository {
+    dir: PathBuf,
+    index: DaIndex,
+}
+
+impl DaRepository {
+    /// Opens the repository at `dir`, creating the directory if needed, and indexes
+    /// the files added or changed since it was last opened.
+    pub async fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        create_dir_all(&dir).await?;
+
+        let index_path = dir.join(DA_INDEX_NAME);
+        let index = match read(&index_path).await {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                warn!("DA index is corrupted ({}), rebuilding it", e);
+                DaIndex::default()
+            }),
+            Err(_) => DaIndex::default(),
+        };
+
+        let mut repo = DaRepository { dir, index };
+        if repo.refresh().await? {
+            repo.save().await?;
+        }
+        Ok(repo)
+    }
+
+    /// Syncs the index with the files of the directory, returning whether it changed.
+    async fn refresh(&mut self) -> Result<bool> {
+        let mut files = BTreeMap::new();
+        let mut stamps = BTreeMap::new();
+        let mut entries = read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let meta = entry.metadata().await?;
+            if name == DA_INDEX_NAME || name.ends_with(".tmp") || !meta.is_file() {
+                continue;
+            }
+
+            // Unchanged files are taken from the index, unparseable ones included
+            if let Some(stamp) = self.index.stamps.get(&name).filter(|s| s.matches(&meta)) {
+                let known = stamp.hash.as_ref().map(|hash| (hash, self.index.files.get(hash)));
+                match known {
+                    Some((hash, Some(known))) => {
+                        files.insert(hash.clone(), DaIndexEntry {
+                            file: name.clone(),
+                            ..known.clone()
+                        });
+                        stamps.insert(name, stamp.clone());
+                        continue;
+                    }
+                    None => {
+                        stamps.insert(name, stamp.clone());
+                        continue;
+                    }
+                    Some((_, None)) => {}
+                }
+            }
+
+            let data = read(entry.path()).await?;
+            let hash = hex::encode(Sha256::digest(&data));
+            if let Some(known) = self.index.files.get(&hash) {
+                files.insert(hash.clone(), DaIndexEntry { file: name.clone(), ..known.clone() });
+                stamps.insert(name, FileStamp::new(&meta, Some(hash)));
+                continue;
+            }
+
+            match index_entry(&name, &data) {
+                Ok(indexed) => {
+                    debug!("Indexed DA '{}' ({} chipsets)", name, indexed.hw_codes.len());
+                    files.insert(hash.clone(), indexed);
+                    stamps.insert(name, FileStamp::new(&meta, Some(hash)));
+                }
+                Err(e) => {
+                    warn!("Skipping '{}' in the DA repository: {}", name, e);
+                    stamps.insert(name, FileStamp::new(&meta, None));
+                }
+            }
+        }
+
+        let changed = stamps != self.index.stamps
+            || files.len() != self.index.files.len()
+            || files.iter().any(|(hash, e)| {
+                self.index.files.get(hash).is_none_or(|known| known.file != e.file)
+            });
+        self.index.files = files;
+        self.index.stamps = stamps;
+
+        let before = self.index.preferred.len();
+        let names: Vec<String> = self.index.files.values().map(|e| e.file.clone()).collect();
+        self.index.preferred.retain(|_, file| names.contains(file));
+
+        Ok(changed || before != self.index.preferred.len())
+    }
+
+    async fn save(&self) -> Result<()> {
+        let data = serde_json::to_vec_pretty(&self.index)
+            .map_err(|e| Error::penumbra(format!("Failed to encode the DA index: {}", e)))?;
+
+        let path = self.dir.join(DA_INDEX_NAME);
+        let tmp = path.with_extension("json.tmp");
+        write(&tmp, data).await?;
+        rename(&tmp, path).await?;
+        Ok(())
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Indexed files, in no particular order.
+    pub fn entries(&self) -> impl Iterator<Item = &DaIndexEntry> {
+        self.index.files.values()
+    }
+
+    /// Files supporting `hw_code`, best first: the preferred one, then V6, V5 and Legacy DAs.
+    pub fn candidates(&self, hw_code: u16) -> Vec<&DaIndexEntry> {
+        let preferred = self.index.preferred.get(&hw_code);
+        let mut candidates: Vec<_> =
+            self.entries().filter(|e| e.hw_codes.binary_search(&hw_code).is_ok()).collect();
+
+        candidates.sort_by_key(|e| {
+            let rank = match e.da_type {
+                DAType::V6 => 1,
+                DAType::V5 => 2,
+                DAType::Legacy => 3,
+            };
+            (if Some(&e.file) == preferred { 0 } else { rank }, e.file.clone())
+        });
+        candidates
+    }
+
+    /// Path of the best DA for `hw_code`, see [`DaRepository::candidates`].
+    pub fn find_for_hw_code(&self, hw_code: u16) -> Option<PathBuf> {
+        self.candidates(hw_code).first().map(|e| self.dir.join(&e.file))
+    }
+
+    /// Makes `file` the DA picked for `hw_code`, over any other supporting it.
+    pub async fn set_preferred(&mut self, hw_code: u16, file: &str) -> Result<()> {
+        if !self.entries().any(|e| e.file == file && e.hw_codes.contains(&hw_code)) {
+            return Err(Error::penumbra(format!(
+                "'{}' isn't a DA of the repository supporting 0x{:04X}",
+                file, hw_code
+            )));
+        }
+
+        self.index.preferred.insert(hw_code, file.to_string());
+        self.save().await
+    }
+
+    /// Makes `file` the DA picked for every chipset it supports, saving the index once.
+    pub async fn set_preferred_for_all(&mut self, file: &str) -> Result<()> {
+        let hw_codes =
+            self.entries().find(|e| e.file == file).map(|e| e.hw_codes.clone()).ok_or_else(
+                || Error::penumbra(format!("'{}' isn't a DA of the repository", file)),
+            )?;
+
+        for hw_code in hw_codes {
+            self.index.preferred.insert(hw_code, file.to_string());
+        }
+        self.save().await
+    }
+
+    /// Copies the DA at `path` into the repository and indexes it.
+    /// Fails if it can't be parsed, or if another file already has its name.
+    pub async fn import(&mut self, path: &Path) -> Result<DaIndexEntry> {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| Error::penumbra(format!("'{}' isn't a file", path.display())))?;
+
+        let data = read(path).await?;
+        let hash = hex::encode(Sha256::digest(&data));
+        if let Some(known) = self.index.files.get(&hash) {
+            return Ok(known.clone());
+        }
+
+        let entry = index_entry(&name, &data)?;
+        let dest = self.dir.join(&name);
+        if try_exists(&dest).await? {
+            return Err(Error::penumbra(format!(
+                "Another '{}' is already in the DA repository",
+                name
+            )));
+        }
+
+        copy(path, &dest).await?;
+        let stamp = FileStamp::new(&metadata(&dest).await?, Some(hash.clone()));
+        self.index.stamps.insert(name, stamp);
+        self.index.files.insert(hash, entry.clone());
+        self.save().await?;
+        Ok(entry)
+    }
+}
+
+fn index_entry(name: &str, data: &[u8]) -> Result<DaIndexEntry> {
+    let da_file = DAFile::parse_da(data)?;
+    Ok(DaIndexEntry {
+        file: name.to_string(),
+        da_type: da_file.da_type.clone(),
+        da_id: da_file.da_id.clone(),
+        hw_codes: da_file.supported_hw_codes().into_iter().map(|(hw_code, _)| hw_code).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory under the system temp one, unique to the test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("penumbra-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A DA file with a single entry, without regions, for `hw_code`.
+    fn da_file(id: &str, hw_code: u16) -> Vec<u8> {
+        let mut data = vec![0u8; 0x6C + 0xDC];
+        data[..0x12].copy_from_slice(b"MTK_DOWNLOAD_AGENT");
+        data[0x20..0x20 + id.len()].copy_from_slice(id.as_bytes());
+        data[0x68] = 1;
+        data[0x6C..0x6E].copy_from_slice(&0xDADAu16.to_le_bytes());
+        data[0x6E..0x70].copy_from_slice(&hw_code.to_le_bytes());
+        data
+    }
+
+    #[tokio::test]
+    async fn picks_v6_unless_overridden() {
+        let dir = temp_dir("da-repository");
+        std::fs::write(dir.join("v5.bin"), da_file("MTK_AllInOne_DA_v5", 0x1234)).unwrap();
+        std::fs::write(dir.join("v6.bin"), da_file("MTK_DA_v6", 0x1234)).unwrap();
+        std::fs::write(dir.join("junk.bin"), [0x55; 0x10]).unwrap();
+
+        let mut repo = DaRepository::open(&dir).await.unwrap();
+        assert_eq!(repo.entries().count(), 2);
+        assert_eq!(repo.find_for_hw_code(0x1234), Some(dir.join("v6.bin")));
+        assert_eq!(repo.find_for_hw_code(0x4321), None);
+
+        repo.set_preferred(0x1234, "v5.bin").await.unwrap();
+        assert!(repo.set_preferred(0x4321, "v5.bin").await.is_err());
+
+        // The choice is kept in the index
+        let repo = DaRepository::open(&dir).await.unwrap();
+        assert_eq!(repo.find_for_hw_code(0x1234), Some(dir.join("v5.bin")));
+    }
+
+    #[tokio::test]
+    async fn import_copies_and_indexes() {
+        let dir = temp_dir("da-repository-import");
+        let src = temp_dir("da-repository-src").join("DA.bin");
+        std::fs::write(&src, da_file("MTK_DA_v6", 0x1234)).unwrap();
+
+        let mut repo = DaRepository::open(&dir).await.unwrap();
+        let entry = repo.import(&src).await.unwrap();
+        assert_eq!(entry.da_type, DAType::V6);
+        assert_eq!(repo.find_for_hw_code(0x1234), Some(dir.join("DA.bin")));
+
+        // Importing it again is a no-op, another file with the same name is refused
+        repo.import(&src).await.unwrap();
+        std::fs::write(&src, da_file("MTK_AllInOne_DA_v5", 0x1234)).unwrap();
+        assert!(repo.import(&src).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unchanged_files_are_not_read_again() {
+        let dir = temp_dir("da-repository-stamps");
+        let path = dir.join("DA.bin");
+        std::fs::write(&path, da_file("MTK_DA_v6", 0x1234)).unwrap();
+        DaRepository::open(&dir).await.unwrap();
+
+        // Same size and time, so the index is trusted over the contents
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        let junk = vec![0x55; std::fs::metadata(&path).unwrap().len() as usize];
+        std::fs::write(&path, &junk).unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        let repo = DaRepository::open(&dir).await.unwrap();
+        assert_eq!(repo.find_for_hw_code(0x1234), Some(path.clone()));
+
+        // Once the time changes, the file is read again
+        let later = modified + std::time::Duration::from_secs(1);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        let repo = DaRepository::open(&dir).await.unwrap();
+        assert_eq!(repo.find_for_hw_code(0x1234), None);
+    }
+
+    #[tokio::test]
+    async fn unparseable_files_are_remembered() {
+        let dir = temp_dir("da-repository-junk");
+        std::fs::write(dir.join("junk.bin"), [0x55; 0x10]).unwrap();
+
+        let mut repo = DaRepository::open(&dir).await.unwrap();
+        assert_eq!(repo.entries().count(), 0);
+        assert_eq!(repo.index.stamps.get("junk.bin").map(|s| s.hash.clone()), Some(None));
+        assert!(!repo.refresh().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn prefers_a_file_for_all_its_chipsets() {
+        let dir = temp_dir("da-repository-prefer");
+        std::fs::write(dir.join("v5.bin"), da_file("MTK_AllInOne_DA_v5", 0x1234)).unwrap();
+        std::fs::write(dir.join("v6.bin"), da_file("MTK_DA_v6", 0x1234)).unwrap();
+
+        let mut repo = DaRepository::open(&dir).await.unwrap();
+        repo.set_preferred_for_all("v5.bin").await.unwrap();
+        assert!(repo.set_preferred_for_all("missing.bin").await.is_err());
+
+        let repo = DaRepository::open(&dir).await.unwrap();
+        assert_eq!(repo.find_for_hw_code(0x1234), Some(dir.join("v5.bin")));
+    }
+}
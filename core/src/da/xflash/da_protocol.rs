@@ -2,50 +2,79 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
-use std::io::Cursor;
 use std::sync::Arc;
 
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::time::{Duration, timeout};
+use tokio::time::Duration;
 
 use crate::connection::Connection;
 use crate::connection::port::ConnectionType;
+#[cfg(feature = "seccfg")]
+use crate::core::chipset::DEFAULT_SEJ_BASE;
 use crate::core::devinfo::DeviceInfo;
+use crate::core::preloader::preloader_partitions;
+use crate::core::progress::ProgressEvent;
+#[cfg(feature = "seccfg")]
 use crate::core::seccfg::LockFlag;
-use crate::core::storage::{Gpt, Partition, PartitionKind, Storage, StorageType};
-use crate::da::protocol::BootMode;
+use crate::core::storage::{Gpt, GptType, Partition, PartitionKind, Storage, StorageType};
+use crate::da::protocol::{BootMode, RPMB_KEY_SIZE};
 use crate::da::xflash::cmds::*;
-#[cfg(not(feature = "no_exploits"))]
-use crate::da::xflash::exts::{read32_ext, write32_ext};
-use crate::da::xflash::flash;
-#[cfg(not(feature = "no_exploits"))]
+use crate::da::xflash::exts::{self, ext_ack, read32_ext, write32_ext};
+#[cfg(feature = "exploits-da-patch")]
 use crate::da::xflash::patch;
-#[cfg(not(feature = "no_exploits"))]
+#[cfg(feature = "seccfg")]
 use crate::da::xflash::sec::{parse_seccfg, write_seccfg};
-use crate::da::{DA, DAEntryRegion, DAProtocol, XFlash};
+use crate::da::xflash::{efuse, flash, rpmb};
+use crate::da::{DA, DAEntryRegion, DAProtocol, StepRecorder, XFlash};
 use crate::error::{Error, Result, XFlashError};
-#[cfg(not(feature = "no_exploits"))]
-use crate::exploit::{Carbonara, Exploit, Kamakiri};
-use crate::{exploit, le_u16, le_u32};
+#[cfg(any(feature = "exploits-brom", feature = "exploits-da-patch"))]
+use crate::exploit;
+#[cfg(feature = "exploits-da-patch")]
+use crate::exploit::Carbonara;
+use crate::exploit::ExploitReport;
+#[cfg(feature = "exploits-brom")]
+use crate::exploit::Kamakiri;
+use crate::{le_u16, le_u32};
+
+/// How long the DA gets to send a status header.
+const STATUS_TIMEOUT: Duration = Duration::from_millis(3000);
 
 #[async_trait::async_trait]
 impl DAProtocol for XFlash {
     async fn upload_da(&mut self) -> Result<bool> {
-        exploit!(Kamakiri, self);
+        self.exploits = ExploitReport::default();
+
+        #[cfg(feature = "exploits-brom")]
+        {
+            self.bringup.begin("kamakiri");
+            exploit!(Kamakiri, self);
+        }
+
+        self.bringup.begin("DA1");
+        let target_config = self.dev_info.target_config().await;
+        self.conn.authenticate(target_config).await?;
 
         let da1 = self.da.get_da1().ok_or_else(|| Error::penumbra("DA1 region not found"))?;
         self.upload_stage1(da1.addr, da1.length, da1.data.clone(), da1.sig_len)
             .await
-            .map_err(|e| Error::proto(format!("Failed to upload DA1: {}", e)))?;
+            .inspect_err(|e| error!("[Penumbra] Failed to upload DA1: {}", e))?;
+        self.conn.switch_to_da_baudrate().await?;
 
+        self.bringup.begin("packet-length");
         flash::get_packet_length(self).await?;
 
-        exploit!(Carbonara, self);
+        #[cfg(feature = "exploits-da-patch")]
+        {
+            self.bringup.begin("carbonara");
+            exploit!(Carbonara, self);
+        }
 
         let da2 = self.da.get_da2().ok_or_else(|| Error::penumbra("DA2 region not found"))?;
-        let sig_len = da2.sig_len as usize;
-        let da2data = da2.data[..da2.data.len().saturating_sub(sig_len)].to_vec();
+        // Without patching, the DA2 is the genuine one and needs its signature
+        let sig_len = if self.da_patching { da2.sig_len as usize } else { 0 };
+        let mut da2data = da2.data.clone();
+        da2data.truncate(da2.data.len().saturating_sub(sig_len));
 
         info!(
             "[Penumbra] Uploading DA2 to address 0x{:08X} with size 0x{:X} bytes",
@@ -53,21 +82,28 @@ impl DAProtocol for XFlash {
             da2data.len()
         );
 
+        self.bringup.begin("DA2");
         match self.boot_to(da2.addr, &da2data).await {
             Ok(true) => {
                 info!("[Penumbra] Successfully uploaded and executed DA2");
+                self.bringup.begin("SLA");
                 self.handle_sla().await?;
+                self.bringup.begin("packet-length");
                 flash::get_packet_length(self).await?; // Re-query packet length for DA loop, for faster speeds :)
 
-                #[cfg(not(feature = "no_exploits"))]
-                self.boot_extensions().await?;
+                if self.da_patching {
+                    self.bringup.begin("extensions");
+                    self.boot_extensions().await?;
+                }
 
+                self.bringup.end();
                 Ok(true)
             }
             Ok(false) => Err(Error::proto("Failed to execute DA2")),
             Err(e) => {
+                error!("[Penumbra] Error uploading DA2: {}", e);
                 self.reboot(BootMode::Normal).await.ok();
-                Err(Error::proto(format!("Error uploading DA2: {}", e)))
+                Err(e)
             }
         }
     }
@@ -96,15 +132,11 @@ impl DAProtocol for XFlash {
 
             self.conn.write(&hdr).await?;
 
-            let mut pos = 0;
-            let max_chunk_size = self.write_packet_length.unwrap_or(0x8000);
+            let max_chunk_size = self.write_packet_length.unwrap_or(0x8000).max(1);
 
-            while pos < param.len() {
-                let end = param.len().min(pos + max_chunk_size);
-                let chunk = &param[pos..end];
+            for chunk in param.chunks(max_chunk_size) {
                 debug!("[TX] Sending chunk (0x{:X} bytes)", chunk.len());
                 self.conn.write(chunk).await?;
-                pos = end;
             }
 
             debug!("[TX] Completed sending 0x{:X} bytes", param.len());
@@ -117,13 +149,12 @@ impl DAProtocol for XFlash {
 
     async fn get_status(&mut self) -> Result<u32> {
         let mut hdr = [0u8; 12];
-        match timeout(Duration::from_millis(3000), self.conn.read(&mut hdr)).await {
-            Ok(result) => result?,
-            Err(_) => {
+        if let Err(e) = self.conn.read_timeout(&mut hdr, STATUS_TIMEOUT).await {
+            if e.is_timeout() {
                 debug!("Status timeout");
-                return Err(Error::io("Status read timed out"));
             }
-        };
+            return Err(e);
+        }
 
         debug!("[RX] Status Header: {:02X?}", hdr);
         let len = self.parse_header(&hdr)?;
@@ -131,14 +162,13 @@ impl DAProtocol for XFlash {
         let mut data = vec![0u8; len as usize];
         self.conn.read(&mut data).await?;
         let status = match len {
-            2 => le_u16!(data, 0) as u32,
+            2 => le_u16!(data, 0)? as u32,
             4 => {
-                let val = le_u32!(data, 0);
+                let val = le_u32!(data, 0)?;
                 if val == Cmd::Magic as u32 { 0 } else { val }
             }
-            _ if data.len() >= 4 => le_u32!(data, 0),
-            _ if !data.is_empty() => data[0] as u32,
-            _ => 0xFFFFFFFF,
+            _ if data.len() >= 4 => le_u32!(data, 0)?,
+            _ => data.first().map_or(0xFFFFFFFF, |&b| b as u32),
         };
 
         debug!("[RX] Status: 0x{:08X}", status);
@@ -166,10 +196,7 @@ impl DAProtocol for XFlash {
             0, // bNotDisconnectUSB (0 = disconnect USB)
         ];
 
-        let mut buf = [0u8; 28];
-        for (i, v) in params.iter().enumerate() {
-            buf[i * 4..(i + 1) * 4].copy_from_slice(&v.to_le_bytes());
-        }
+        let buf: Vec<u8> = params.iter().flat_map(|v| v.to_le_bytes()).collect();
 
         info!("Shutting down device...");
 
@@ -180,6 +207,22 @@ impl DAProtocol for XFlash {
     }
 
     async fn reboot(&mut self, bootmode: BootMode) -> Result<()> {
+        if matches!(bootmode, BootMode::Meta | BootMode::AdbMeta) {
+            let adb = (bootmode == BootMode::AdbMeta) as u32;
+            let params: [u32; 4] = [
+                1,   // boot_mode (1 = META)
+                1,   // com_type (1 = USB, 2 = UART)
+                1,   // mobile_log
+                adb, // adb
+            ];
+
+            let buf: Vec<u8> = params.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+            self.devctrl(Cmd::SetMetaBootMode, Some(&[buf.as_slice()])).await.map_err(|e| {
+                Error::unsupported(format!("The DA rejected {:?} boot mode: {e}", bootmode))
+            })?;
+        }
+
         self.send_cmd(Cmd::Shutdown).await?;
 
         let bootup = match bootmode {
@@ -188,21 +231,19 @@ impl DAProtocol for XFlash {
             BootMode::Fastboot => 2,
             _ => 0,
         };
+        let dlbit = if bootmode == BootMode::Brom { 1 } else { 0 };
 
         let params: [u32; 7] = [
             1,      // is_dev_reboot
             0,      // timeout_ms (0 = default, WDT decides)
             0,      // async
             bootup, // bootup
-            0,      // dlbit
+            dlbit,  // dlbit
             0,      // bNotResetRTCTime
             0,      // bNotDisconnectUSB
         ];
 
-        let mut buf = [0u8; 28];
-        for (i, v) in params.iter().enumerate() {
-            buf[i * 4..(i + 1) * 4].copy_from_slice(&v.to_le_bytes());
-        }
+        let buf: Vec<u8> = params.iter().flat_map(|v| v.to_le_bytes()).collect();
 
         info!("Rebooting device into {:?} mode...", bootmode);
 
@@ -212,12 +253,17 @@ impl DAProtocol for XFlash {
         Ok(())
     }
 
+    async fn keepalive(&mut self) -> Result<()> {
+        debug!("Sending keepalive to DA");
+        flash::get_packet_length(self).await.map(|_| ())
+    }
+
     async fn read_flash(
         &mut self,
         addr: u64,
         size: usize,
         section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
         writer: &mut (dyn AsyncWrite + Unpin + Send),
     ) -> Result<()> {
         flash::read_flash(self, addr, size, section, progress, writer).await
@@ -229,9 +275,10 @@ impl DAProtocol for XFlash {
         size: usize,
         reader: &mut (dyn AsyncRead + Unpin + Send),
         section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        pad_with_zeros: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
-        flash::write_flash(self, addr, size, reader, section, progress).await
+        flash::write_flash(self, addr, size, reader, section, pad_with_zeros, progress).await
     }
 
     async fn erase_flash(
@@ -239,7 +286,7 @@ impl DAProtocol for XFlash {
         addr: u64,
         size: usize,
         section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         flash::erase_flash(self, addr, size, section, progress).await
     }
@@ -249,7 +296,7 @@ impl DAProtocol for XFlash {
         part_name: String,
         size: usize,
         reader: &mut (dyn AsyncRead + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         flash::download(self, part_name, size, reader, progress).await
     }
@@ -258,7 +305,7 @@ impl DAProtocol for XFlash {
         &mut self,
         part_name: String,
         writer: &mut (dyn AsyncWrite + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         flash::upload(self, part_name, writer, progress).await
     }
@@ -266,7 +313,7 @@ impl DAProtocol for XFlash {
     async fn format(
         &mut self,
         part_name: String,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         flash::format(self, part_name, progress).await
     }
@@ -274,20 +321,46 @@ impl DAProtocol for XFlash {
     async fn get_usb_speed(&mut self) -> Result<u32> {
         let usb_speed = self.devctrl(Cmd::GetUsbSpeed, None).await?;
         debug!("USB Speed Data: {:?}", usb_speed);
-        Ok(le_u32!(usb_speed, 0))
+        Ok(le_u32!(usb_speed, 0)?)
+    }
+
+    async fn get_random_id(&mut self) -> Result<Vec<u8>> {
+        let random_id = self.devctrl(Cmd::GetRandomId, None).await?;
+        debug!("Random ID: {}", hex::encode(&random_id));
+        Ok(random_id)
+    }
+
+    async fn get_hw_code(&mut self) -> Result<u16> {
+        let chip_id = self.devctrl(Cmd::GetChipId, None).await?;
+        debug!("Chip ID Data: {:?}", chip_id);
+        if chip_id.len() < 2 {
+            return Err(Error::proto("Chip ID response is too short"));
+        }
+        Ok(le_u16!(chip_id, 0)?)
+    }
+
+    async fn read_efuse(&mut self) -> Result<Vec<u8>> {
+        efuse::read_efuse(self).await
     }
 
     fn get_connection(&mut self) -> &mut Connection {
         &mut self.conn
     }
 
+    fn into_connection(self: Box<Self>) -> Connection {
+        self.conn
+    }
+
+    fn bringup_steps(&mut self) -> &mut StepRecorder {
+        &mut self.bringup
+    }
+
     fn set_connection_type(&mut self, conn_type: ConnectionType) -> Result<()> {
         self.conn.connection_type = conn_type;
         Ok(())
     }
 
     async fn read32(&mut self, addr: u32) -> Result<u32> {
-        #[cfg(not(feature = "no_exploits"))]
         if self.using_exts {
             return read32_ext(self, addr).await;
         }
@@ -299,11 +372,10 @@ impl DAProtocol for XFlash {
             debug!("Short read: expected 4 bytes, got {}", resp.len());
             return Err(Error::io("Short register read"));
         }
-        Ok(le_u32!(resp, 0))
+        Ok(le_u32!(resp, 0)?)
     }
 
     async fn write32(&mut self, addr: u32, value: u32) -> Result<()> {
-        #[cfg(not(feature = "no_exploits"))]
         if self.using_exts {
             return write32_ext(self, addr, value).await;
         }
@@ -323,95 +395,161 @@ impl DAProtocol for XFlash {
         self.get_or_detect_storage().await
     }
 
-    async fn get_partitions(&mut self) -> Vec<Partition> {
-        let storage = match self.get_storage().await {
-            Some(s) => s,
-            None => {
-                error!("[Penumbra] Failed to get storage for partition parsing");
-                return Vec::new();
-            }
-        };
+    async fn get_partitions(&mut self) -> Result<Vec<Partition>> {
+        let storage = self.get_storage().await.ok_or_else(|| {
+            Error::penumbra("Storage not detected, can't read the partition table")
+        })?;
 
         let storage_type = storage.kind();
-        let pl_part1 = storage.get_pl_part1();
-        let pl_part2 = storage.get_pl_part2();
         let user_part = storage.get_user_part();
-        let pl1_size = storage.get_pl1_size() as usize;
-        let pl2_size = storage.get_pl2_size() as usize;
         let user_size = storage.get_user_size() as usize;
         let gpt_size = 32 * 1024; // TODO: Change this when adding NAND support and PMT
 
-        let mut partitions = vec![
-            Partition::new("preloader", pl1_size, 0, pl_part1),
-            Partition::new("preloader_backup", pl2_size, 0, pl_part2),
-            Partition::new("PGPT", gpt_size, 0, user_part),
-        ];
+        // NAND has no boot regions, its preloader is listed by the partition table
+        let mut partitions: Vec<_> =
+            preloader_partitions(storage.as_ref()).into_iter().filter(|p| p.size != 0).collect();
+        partitions.push(Partition::new("PGPT", gpt_size, 0, user_part));
 
         let sgpt = Partition::new("SGPT", gpt_size, user_size as u64 - gpt_size as u64, user_part);
 
-        let mut progress = |_, _| {};
-
-        let mut pgpt_data = Vec::new();
-        let mut pgpt_cursor = Cursor::new(&mut pgpt_data);
-        self.upload("PGPT".into(), &mut pgpt_cursor, &mut progress).await.ok();
-        self.send(&[0u8; 4]).await.ok();
-        let parsed_gpt_parts =
-            Gpt::parse(&pgpt_data, storage_type).map(|g| g.partitions()).unwrap_or_default();
-
-        let mut gpt_parts = if !parsed_gpt_parts.is_empty() {
-            parsed_gpt_parts
-        } else {
-            let mut sgpt_data = Vec::new();
-            let mut sgpt_cursor = Cursor::new(&mut sgpt_data);
-            self.upload("SGPT".into(), &mut sgpt_cursor, &mut progress).await.ok();
-            self.send(&[0u8; 4]).await.ok();
-            Gpt::parse(&sgpt_data, storage_type).map(|g| g.partitions()).unwrap_or_default()
+        let pgpt_data = flash::read_gpt(self, &["PGPT", "pgpt"], 0, gpt_size, user_part).await;
+        if !pgpt_data.is_empty() {
+            self.dev_info.set_raw_pgpt(pgpt_data.clone()).await;
+        }
+
+        let mut gpt_parts = match Gpt::parse_as(&pgpt_data, GptType::Pgpt, storage_type) {
+            Ok(gpt) => gpt.partitions(),
+            Err(pgpt_err) => {
+                warn!("Primary GPT is invalid ({}), trying the backup GPT...", pgpt_err);
+
+                let sgpt_data =
+                    flash::read_gpt(self, &["SGPT", "sgpt"], sgpt.address, gpt_size, user_part)
+                        .await;
+                if !sgpt_data.is_empty() {
+                    self.dev_info.set_raw_sgpt(sgpt_data.clone()).await;
+                }
+
+                let gpt =
+                    Gpt::parse_as(&sgpt_data, GptType::Sgpt, storage_type).map_err(|sgpt_err| {
+                        Error::penumbra(format!(
+                            "No valid GPT found. Primary: {}. Backup: {}",
+                            pgpt_err, sgpt_err
+                        ))
+                    })?;
+                warn!("Using the backup GPT, the primary one should be restored.");
+                gpt.partitions()
+            }
         };
 
         partitions.append(&mut gpt_parts);
         partitions.push(sgpt);
 
-        partitions
+        Ok(partitions)
     }
 
-    #[cfg(not(feature = "no_exploits"))]
+    #[cfg(feature = "seccfg")]
     async fn set_seccfg_lock_state(&mut self, locked: LockFlag) -> Option<Vec<u8>> {
-        let seccfg = parse_seccfg(self).await;
-        if seccfg.is_none() {
+        info!("Using SEJ base 0x{:08X}", self.sej_base.unwrap_or(DEFAULT_SEJ_BASE));
+
+        let Some(mut seccfg) = parse_seccfg(self).await else {
             error!("[Penumbra] Failed to parse seccfg, cannot set lock state");
             return None;
-        }
+        };
 
-        let mut seccfg = seccfg.unwrap();
         seccfg.set_lock_state(locked);
         write_seccfg(self, &mut seccfg).await
     }
 
-    #[cfg(not(feature = "no_exploits"))]
+    fn take_exploit_report(&mut self) -> ExploitReport {
+        std::mem::take(&mut self.exploits)
+    }
+
+    fn exts_active(&self) -> bool {
+        self.using_exts
+    }
+
+    fn set_exts_state(&mut self, active: bool) {
+        self.using_exts = active;
+    }
+
+    async fn probe_extensions(&mut self) -> Result<bool> {
+        ext_ack(self).await
+    }
+
+    async fn init_rpmb(&mut self, key: Option<&[u8; RPMB_KEY_SIZE]>) -> Result<()> {
+        if !self.using_exts {
+            return Err(Error::unsupported("RPMB access requires the DA extensions"));
+        }
+        rpmb::init_rpmb(self, key).await
+    }
+
+    async fn read_rpmb(
+        &mut self,
+        address: u16,
+        blocks: u16,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        if !self.using_exts {
+            return Err(Error::unsupported("RPMB access requires the DA extensions"));
+        }
+        rpmb::read_rpmb(self, address, blocks, writer, progress).await
+    }
+
+    async fn write_rpmb(
+        &mut self,
+        address: u16,
+        blocks: u16,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        if !self.using_exts {
+            return Err(Error::unsupported("RPMB access requires the DA extensions"));
+        }
+        rpmb::write_rpmb(self, address, blocks, reader, progress).await
+    }
+
     async fn peek(
         &mut self,
-        _addr: u32,
-        _length: usize,
-        _writer: &mut (dyn AsyncWrite + Unpin + Send),
-        _progress: &mut (dyn FnMut(usize, usize) + Send),
+        addr: u32,
+        length: usize,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
-        // TODO: Rewrite V5 extensions, this is currently broken with current extensions
-        todo!()
+        if !self.using_exts {
+            return Err(Error::unsupported("Reading memory requires the DA extensions"));
+        }
+        exts::peek(self, addr, length, writer, progress).await
     }
 
-    #[cfg(not(feature = "no_exploits"))]
     fn patch_da(&mut self) -> Option<DA> {
-        patch::patch_da(self).ok()
+        if let Some(da) = &self.prepatched {
+            return Some(da.clone());
+        }
+        #[cfg(feature = "exploits-da-patch")]
+        return patch::patch_da(self).ok();
+        #[cfg(not(feature = "exploits-da-patch"))]
+        None
     }
 
-    #[cfg(not(feature = "no_exploits"))]
     fn patch_da1(&mut self) -> Option<DAEntryRegion> {
-        patch::patch_da1(self).ok()
+        if let Some(da) = &self.prepatched {
+            return da.get_da1().cloned();
+        }
+        #[cfg(feature = "exploits-da-patch")]
+        return patch::patch_da1(self).ok();
+        #[cfg(not(feature = "exploits-da-patch"))]
+        None
     }
 
-    #[cfg(not(feature = "no_exploits"))]
     fn patch_da2(&mut self) -> Option<DAEntryRegion> {
-        patch::patch_da2(self).ok()
+        if let Some(da) = &self.prepatched {
+            return da.get_da2().cloned();
+        }
+        #[cfg(feature = "exploits-da-patch")]
+        return patch::patch_da2(self).ok();
+        #[cfg(not(feature = "exploits-da-patch"))]
+        None
     }
 
     fn get_devinfo(&self) -> &DeviceInfo {
@@ -0,0 +1,69 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use log::debug;
+#[cfg(feature = "dangerous")]
+use log::info;
+
+use crate::core::efuse::EFUSE_WORDS;
+use crate::da::DAProtocol;
+use crate::da::xflash::{Cmd, XFlash};
+use crate::error::{Error, Result, XFlashErrorKind};
+
+/// Sends an eFuse command, turning a DA that doesn't know it into an unsupported error.
+/// The DA answers unknown commands with a status, so nothing is left to read afterwards.
+async fn send_efuse_cmd(xflash: &mut XFlash, cmd: Cmd) -> Result<()> {
+    match xflash.send_cmd(cmd).await {
+        Ok(_) => Ok(()),
+        Err(Error::XFlash(e))
+            if matches!(
+                e.kind,
+                XFlashErrorKind::UnsupportedCommand | XFlashErrorKind::UnsupportedCtrlCode
+            ) =>
+        {
+            Err(Error::unsupported("This DA does not support eFuse commands"))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads the eFuse words, as raw little-endian bytes.
+pub async fn read_efuse(xflash: &mut XFlash) -> Result<Vec<u8>> {
+    // Format:
+    // First word index u32
+    // Word count u32
+    let mut param = [0u8; 8];
+    param[4..8].copy_from_slice(&(EFUSE_WORDS as u32).to_le_bytes());
+
+    send_efuse_cmd(xflash, Cmd::ReadEfuse).await?;
+    xflash.send(&param).await?;
+
+    let data = xflash.read_data().await?;
+    status_ok!(xflash);
+
+    debug!("Read {} bytes of eFuses", data.len());
+    Ok(data)
+}
+
+/// Blows the eFuse word at `index` with `value`.
+///
+/// This can't be undone and can brick the device for good,
+/// which is why it's only built with the `dangerous` feature.
+#[cfg(feature = "dangerous")]
+pub async fn write_efuse(xflash: &mut XFlash, index: u32, value: u32) -> Result<()> {
+    // Format:
+    // Word index u32
+    // Value u32
+    let mut param = [0u8; 8];
+    param[0..4].copy_from_slice(&index.to_le_bytes());
+    param[4..8].copy_from_slice(&value.to_le_bytes());
+
+    info!("Blowing eFuse word 0x{:X} with 0x{:08X}", index, value);
+
+    send_efuse_cmd(xflash, Cmd::WriteEfuse).await?;
+    xflash.send(&param).await?;
+    status_ok!(xflash);
+
+    Ok(())
+}
@@ -13,11 +13,17 @@
     as for term 13 of the GPL-3.0-or-later license.
 */
 use log::{debug, info};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
+use crate::core::chipset::{DEFAULT_SEJ_BASE, sej_base};
+#[cfg(feature = "seccfg")]
+use crate::core::crypto::sej::{SejReg, acon_is_valid};
+use crate::core::progress::{ProgressEvent, ProgressReporter};
+use crate::core::summary::OperationKind;
 use crate::da::DAProtocol;
 use crate::da::xflash::{Cmd, XFlash};
 use crate::error::{Error, Result};
-use crate::utilities::patching::{HEX_NOT_FOUND, find_pattern, patch_ptr};
+use crate::utilities::patching::{HEX_NOT_FOUND, find_pattern, patch_pattern, patch_ptr};
 use crate::{extract_ptr, le_u32};
 
 const DA_EXT: &[u8] = include_bytes!("../../../payloads/da_x.bin");
@@ -25,6 +31,9 @@ const DA_EXT: &[u8] = include_bytes!("../../../payloads/da_x.bin");
 pub async fn boot_extensions(xflash: &mut XFlash) -> Result<bool> {
     debug!("Trying booting XFlash extensions...");
 
+    let hw_code = xflash.dev_info.hw_code().await;
+    xflash.sej_base = Some(sej_base(hw_code, xflash.sej_base, None));
+
     let ext_data = match prepare_extensions(xflash) {
         Some(data) => data,
         None => {
@@ -47,15 +56,23 @@ pub async fn boot_extensions(xflash: &mut XFlash) -> Result<bool> {
     }
     info!("DA extensions uploaded");
 
+    if !ext_ack(xflash).await? {
+        return Err(Error::proto("DA extensions failed to start (invalid ACK)"));
+    }
+
+    Ok(true)
+}
+
+/// Asks the extensions for their ack, returning whether they answered.
+pub async fn ext_ack(xflash: &mut XFlash) -> Result<bool> {
     let ack = xflash.devctrl(Cmd::ExtAck, None).await?;
 
     // Ack must be 0xA1A2A3A4
-    if ack.len() < 4 || ack[0..4] != [0xA4, 0xA3, 0xA2, 0xA1] {
-        return Err(Error::proto("DA extensions failed to start (invalid ACK)"));
-    } else {
-        info!("Received ack: {:02X?}", &ack[0..4]);
-    }
+    let Some(ack @ [0xA4, 0xA3, 0xA2, 0xA1]) = ack.first_chunk::<4>() else {
+        return Ok(false);
+    };
 
+    info!("Received ack: {:02X?}", ack);
     Ok(true)
 }
 
@@ -94,7 +111,7 @@ fn prepare_extensions(xflash: &XFlash) -> Option<Vec<u8>> {
             break;
         }
 
-        if pos + 22 <= da2.len() && da2[pos + 20] == 0xB3 && da2[pos + 21] == 0x21 {
+        if da2.get(pos + 20..pos + 22) == Some(&[0xB3, 0x21]) {
             mmc_set_part_config = pos;
             break;
         }
@@ -152,10 +169,15 @@ fn prepare_extensions(xflash: &XFlash) -> Option<Vec<u8>> {
 
     for (offset, value) in patches {
         if offset != HEX_NOT_FOUND && value != HEX_NOT_FOUND {
-            patch_ptr(&mut da_ext_data, offset, value as u32, da2address, true);
+            patch_ptr(&mut da_ext_data, offset, value as u32, da2address, true).ok()?;
         }
     }
 
+    // The extensions have no command to set the SEJ base, it's a literal of the payload
+    if let Some(base) = xflash.sej_base.filter(|&base| base != DEFAULT_SEJ_BASE) {
+        patch_pattern(&mut da_ext_data, "00A00010", base)?;
+    }
+
     Some(da_ext_data)
 }
 
@@ -165,7 +187,67 @@ pub async fn read32_ext(xflash: &mut XFlash, addr: u32) -> Result<u32> {
     let payload = xflash.read_data().await?;
     status_ok!(xflash);
 
-    Ok(le_u32!(payload, 0))
+    Ok(le_u32!(payload, 0)?)
+}
+
+/// Reads `length` bytes of memory from `addr` with ExtReadMem, a packet at a time.
+///
+/// The extensions only read whole words, so an unaligned range is widened to words
+/// and trimmed back before being written out.
+pub async fn peek(
+    xflash: &mut XFlash,
+    addr: u32,
+    length: usize,
+    writer: &mut (dyn AsyncWrite + Unpin + Send),
+    progress: &mut (dyn FnMut(ProgressEvent) + Send),
+) -> Result<()> {
+    if addr as u64 + length as u64 > 1 << 32 {
+        return Err(Error::penumbra(format!("Range 0x{:08X}+0x{:X} is past 4 GB", addr, length)));
+    }
+
+    let chunk_size = xflash.read_packet_length.unwrap_or(0x8000) & !3;
+    let mut progress = ProgressReporter::new(OperationKind::Read, progress);
+    let mut bytes_read = 0;
+    progress.report(0, length);
+
+    while bytes_read < length {
+        let start = addr as u64 + bytes_read as u64;
+        let aligned = start & !3;
+        let skip = (start - aligned) as usize;
+        let to_read = (length - bytes_read).min(chunk_size - skip);
+        let request = (skip + to_read).next_multiple_of(4);
+
+        // Format:
+        // Address u32
+        // Length u32
+        xflash.conn.check_host_awake()?;
+        xflash
+            .devctrl(
+                Cmd::ExtReadMem,
+                Some(&[&(aligned as u32).to_le_bytes(), &(request as u32).to_le_bytes()]),
+            )
+            .await?;
+
+        let data = xflash.read_data().await?;
+        status_ok!(xflash);
+
+        let Some(chunk) = data.get(skip..skip + to_read) else {
+            return Err(Error::proto(format!(
+                "Short memory read at 0x{:08X}: got 0x{:X} bytes, expected 0x{:X}",
+                aligned,
+                data.len(),
+                request
+            )));
+        };
+
+        writer.write_all(chunk).await?;
+        bytes_read += to_read;
+        progress.report(bytes_read, length);
+        debug!("Peeked {:X}/{:X} bytes...", bytes_read, length);
+    }
+
+    writer.flush().await?;
+    Ok(())
 }
 
 pub async fn write32_ext(xflash: &mut XFlash, addr: u32, value: u32) -> Result<()> {
@@ -177,6 +259,23 @@ pub async fn write32_ext(xflash: &mut XFlash, addr: u32, value: u32) -> Result<(
     Ok(())
 }
 
+/// Checks that a SEJ block answers at the base the extensions use, right after a hardware
+/// operation. A wrong base would silently give garbage instead.
+#[cfg(feature = "seccfg")]
+pub async fn check_sej(xflash: &mut XFlash) -> Result<()> {
+    let base = xflash.sej_base.unwrap_or(DEFAULT_SEJ_BASE);
+    let acon = read32_ext(xflash, base + SejReg::ACON.offset()).await?;
+
+    if !acon_is_valid(acon) {
+        return Err(Error::penumbra(format!(
+            "No SEJ block answers at 0x{:08X} (ACON reads 0x{:08X})",
+            base, acon
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "seccfg")]
 pub async fn sej(
     xflash: &mut XFlash,
     data: &[u8],
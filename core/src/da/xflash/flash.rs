@@ -2,29 +2,49 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
-use log::{debug, info};
+use log::{debug, info, warn};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
 
-use crate::core::storage::PartitionKind;
+use crate::core::progress::{ProgressEvent, ProgressReporter};
+use crate::core::storage::{PartitionKind, StorageType};
+use crate::core::summary::OperationKind;
 use crate::da::DAProtocol;
 use crate::da::xflash::XFlash;
 use crate::da::xflash::cmds::*;
 use crate::error::{Error, Result};
 use crate::{le_u32, le_u64};
 
+/// Builds the parameters of the read, write and erase commands.
+/// The NAND specific area is left to zeros for other storages.
+async fn flash_param(
+    xflash: &mut XFlash,
+    addr: u64,
+    size: usize,
+    section: PartitionKind,
+) -> [u8; 56] {
+    let storage = xflash.get_storage().await;
+    let storage_type = storage.as_ref().map_or(StorageType::Unknown, |s| s.kind()) as u32;
+    let nand_ext = storage.and_then(|s| s.nand_extension()).unwrap_or_default();
+
+    let mut param = [0u8; 56];
+    param[0..4].copy_from_slice(&storage_type.to_le_bytes());
+    param[4..8].copy_from_slice(&section.as_u32().to_le_bytes());
+    param[8..16].copy_from_slice(&addr.to_le_bytes());
+    param[16..24].copy_from_slice(&(size as u64).to_le_bytes());
+    param[24..56].copy_from_slice(&nand_ext.to_bytes());
+    param
+}
+
 pub async fn read_flash(
     xflash: &mut XFlash,
     addr: u64,
     size: usize,
     section: PartitionKind,
-    progress: &mut (dyn FnMut(usize, usize) + Send),
+    progress: &mut (dyn FnMut(ProgressEvent) + Send),
     writer: &mut (dyn AsyncWrite + Unpin + Send),
 ) -> Result<()> {
     info!("Reading flash at address {:#X} with size {:#X}", addr, size);
 
-    let storage_type = xflash.get_storage_type().await as u32;
-    let partition_type = section.as_u32();
-
     // Format:
     // Storage Type (EMMC, UFS, NAND) u32
     // PartType u32 (BOOT or USER for EMMC)
@@ -38,11 +58,7 @@ pub async fn read_flash(
     // 4400000000000000 u64
     // 0000000000000000000000000000000000000000000000000000000000000000 8u32
     // The payload above is sent when reading PGPT (addr: 0x0, size: 0x44)
-    let mut param = [0u8; 56];
-    param[0..4].copy_from_slice(&storage_type.to_le_bytes());
-    param[4..8].copy_from_slice(&partition_type.to_le_bytes());
-    param[8..16].copy_from_slice(&addr.to_le_bytes());
-    param[16..24].copy_from_slice(&(size as u64).to_le_bytes());
+    let param = flash_param(xflash, addr, size, section).await;
 
     xflash.send_cmd(Cmd::ReadData).await?;
     xflash.send(&param).await?;
@@ -61,23 +77,17 @@ pub async fn write_flash(
     size: usize,
     reader: &mut (dyn AsyncRead + Unpin + Send),
     section: PartitionKind,
-    progress: &mut (dyn FnMut(usize, usize) + Send),
+    pad_with_zeros: bool,
+    progress: &mut (dyn FnMut(ProgressEvent) + Send),
 ) -> Result<()> {
     info!("Writing flash at address {:#X} with size {:#X}", addr, size);
 
-    let storage_type = xflash.get_storage_type().await as u32;
-    let partition_type = section.as_u32();
-
-    let mut param = [0u8; 56];
-    param[0..4].copy_from_slice(&storage_type.to_le_bytes());
-    param[4..8].copy_from_slice(&partition_type.to_le_bytes());
-    param[8..16].copy_from_slice(&addr.to_le_bytes());
-    param[16..24].copy_from_slice(&(size as u64).to_le_bytes());
+    let param = flash_param(xflash, addr, size, section).await;
 
     xflash.send_cmd(Cmd::WriteData).await?;
     xflash.send(&param).await?;
 
-    xflash.download_data(size, reader, progress).await?;
+    xflash.download_data(size, reader, pad_with_zeros, progress).await?;
 
     info!("Flash write completed, 0x{:X} bytes written.", size);
 
@@ -89,18 +99,11 @@ pub async fn erase_flash(
     addr: u64,
     size: usize,
     section: PartitionKind,
-    progress: &mut (dyn FnMut(usize, usize) + Send),
+    progress: &mut (dyn FnMut(ProgressEvent) + Send),
 ) -> Result<()> {
     info!("Erasing flash at address {:#X} with size {:#X}", addr, size);
 
-    let storage_type = xflash.get_storage_type().await as u32;
-    let partition_type = section.as_u32();
-
-    let mut param = [0u8; 56];
-    param[0..4].copy_from_slice(&storage_type.to_le_bytes());
-    param[4..8].copy_from_slice(&partition_type.to_le_bytes());
-    param[8..16].copy_from_slice(&addr.to_le_bytes());
-    param[16..24].copy_from_slice(&(size as u64).to_le_bytes());
+    let param = flash_param(xflash, addr, size, section).await;
 
     xflash.send_cmd(Cmd::Format).await?;
     xflash.send(&param).await?;
@@ -116,7 +119,7 @@ pub async fn download(
     part_name: String,
     size: usize,
     reader: &mut (dyn AsyncRead + Unpin + Send),
-    progress: &mut (dyn FnMut(usize, usize) + Send),
+    progress: &mut (dyn FnMut(ProgressEvent) + Send),
 ) -> Result<()> {
     // Works like write_flash, but instead of address and size, it takes a partition name
     // and writes the whole data to it.
@@ -134,7 +137,7 @@ pub async fn download(
 
     info!("Starting download to partition '{}' with size 0x{:X}", part_name, size);
 
-    xflash.download_data(size, reader, progress).await?;
+    xflash.download_data(size, reader, false, progress).await?;
 
     xflash.send_cmd(Cmd::DeviceCtrl).await?;
     xflash.send_cmd(Cmd::EndDlInfo).await?;
@@ -149,7 +152,7 @@ pub async fn upload(
     xflash: &mut XFlash,
     part_name: String,
     writer: &mut (dyn AsyncWrite + Unpin + Send),
-    progress: &mut (dyn FnMut(usize, usize) + Send),
+    progress: &mut (dyn FnMut(ProgressEvent) + Send),
 ) -> Result<()> {
     xflash.send_cmd(Cmd::Upload).await?;
     xflash.send(part_name.as_bytes()).await?;
@@ -160,7 +163,7 @@ pub async fn upload(
         if size_data.len() < 8 {
             return Err(Error::proto("Received upload size is too short"));
         }
-        le_u64!(size_data, 0) as usize
+        le_u64!(size_data, 0)? as usize
     };
 
     info!("Starting readback of partition '{}' with size 0x{:X}", part_name, size);
@@ -172,10 +175,45 @@ pub async fn upload(
     Ok(())
 }
 
+/// Reads a partition table, asking the DA for it by each of `names` in turn, then reading
+/// it raw at `addr` when the DA knows none of them. DAs disagree on the case of the GPT
+/// names, and some don't accept them at all. Returns an empty buffer if nothing worked.
+pub async fn read_gpt(
+    xflash: &mut XFlash,
+    names: &[&str],
+    addr: u64,
+    size: usize,
+    section: PartitionKind,
+) -> Vec<u8> {
+    let mut progress = |_| {};
+
+    for name in names {
+        let mut data = Vec::new();
+        match upload(xflash, name.to_string(), &mut data, &mut progress).await {
+            Ok(()) if !data.is_empty() => {
+                // Only a streamed upload waits for a last ack. Sending it after a refused
+                // one would be taken as the status of the next command.
+                xflash.send(&[0u8; 4]).await.ok();
+                return data;
+            }
+            Ok(()) => debug!("The DA sent an empty '{}'", name),
+            Err(e) => debug!("The DA can't upload '{}': {}", name, e),
+        }
+    }
+
+    debug!("Reading the partition table raw at 0x{:X}", addr);
+    let mut data = Vec::new();
+    if let Err(e) = read_flash(xflash, addr, size, section, &mut progress, &mut data).await {
+        warn!("Could not read the partition table at 0x{:X}: {}", addr, e);
+        data.clear();
+    }
+    data
+}
+
 pub async fn format(
     xflash: &mut XFlash,
     part_name: String,
-    progress: &mut (dyn FnMut(usize, usize) + Send),
+    progress: &mut (dyn FnMut(ProgressEvent) + Send),
 ) -> Result<()> {
     let part = match xflash.dev_info.get_partition(&part_name).await {
         Some(p) => p,
@@ -212,8 +250,10 @@ pub async fn set_rsc_info<F, R>(
 ) -> Result<()>
 where
     R: AsyncRead + Unpin,
-    F: FnMut(usize, usize),
+    F: FnMut(ProgressEvent) + Send,
 {
+    let mut progress = ProgressReporter::new(OperationKind::Write, &mut progress);
+
     // Split in chunks of 256 bytes
     // The payload structure is like this:
     // u64 offset LE (each iteration, it increases by 1)
@@ -231,10 +271,9 @@ where
         let offset_bytes = offset.to_le_bytes();
         payload.extend_from_slice(&offset_bytes[..7]);
 
-        let mut part_name_bytes = vec![0u8; 64];
         let name_bytes = part_name.as_bytes();
-        let name_len = name_bytes.len().min(63);
-        part_name_bytes[..name_len].copy_from_slice(&name_bytes[..name_len]);
+        let mut part_name_bytes = name_bytes.get(..63).unwrap_or(name_bytes).to_vec();
+        part_name_bytes.resize(64, 0);
         payload.extend_from_slice(&part_name_bytes);
 
         let bytes_read = reader.read(&mut buffer).await?;
@@ -242,20 +281,15 @@ where
             break;
         }
 
-        let chunk = if bytes_read < 256 {
-            let mut chunk_buf = vec![0u8; 256];
-            chunk_buf[..bytes_read].copy_from_slice(&buffer[..bytes_read]);
-            chunk_buf
-        } else {
-            buffer[..].to_vec()
-        };
+        let mut chunk = buffer.get(..bytes_read).unwrap_or_default().to_vec();
+        chunk.resize(256, 0);
 
         payload.extend_from_slice(&chunk);
         assert_eq!(payload.len(), 328);
 
         xflash.devctrl(Cmd::SetRscInfo, Some(&[&payload])).await?;
 
-        progress(offset as usize * 256 + bytes_read, size);
+        progress.report(offset as usize * 256 + bytes_read, size);
         offset += 1;
     }
 
@@ -269,8 +303,8 @@ pub async fn get_packet_length(xflash: &mut XFlash) -> Result<(usize, usize)> {
         return Err(Error::proto("Received packet length is too short"));
     }
 
-    let write_len = le_u32!(packet_length, 0) as usize;
-    let read_len = le_u32!(packet_length, 4) as usize;
+    let write_len = le_u32!(packet_length, 0)? as usize;
+    let read_len = le_u32!(packet_length, 4)? as usize;
 
     xflash.write_packet_length = Some(write_len);
     xflash.read_packet_length = Some(read_len);
@@ -8,7 +8,7 @@ macro_rules! status {
         let status = $self.get_status().await?;
         if status != $expected {
             let xflash_err = crate::error::XFlashError::from_code(status);
-            log::error!("{}: 0x{:08X} ({})", $msg, status, xflash_err);
+            log::error!("{}: {}", $msg, xflash_err);
             return Err(Error::XFlash(xflash_err));
         }
     }};
@@ -17,7 +17,7 @@ macro_rules! status {
         let status = $self.get_status().await?;
         if status != $expected {
             let xflash_err = crate::error::XFlashError::from_code(status);
-            log::error!("Status is not expected: 0x{:08X} ({})", status, xflash_err);
+            log::error!("Status is not expected: {}", xflash_err);
             return Err(Error::XFlash(xflash_err));
         }
     }};
@@ -37,7 +37,7 @@ macro_rules! status_any {
         let status = $self.get_status().await?;
         if ![$($valid),+].contains(&status) {
             let xflash_err = XFlashError::from_code(status);
-            error!("Status is not expected: 0x{:08X} ({})", status, xflash_err);
+            error!("Status is not expected: {}", xflash_err);
             return Err(Error::XFlash(xflash_err));
         }
     }};
@@ -6,14 +6,20 @@
 mod macros;
 mod cmds;
 mod da_protocol;
-#[cfg(not(feature = "no_exploits"))]
+mod efuse;
 mod exts;
-pub mod flash;
-#[cfg(not(feature = "no_exploits"))]
+pub(crate) mod flash;
+#[cfg(feature = "exploits-da-patch")]
 mod patch;
-#[cfg(not(feature = "no_exploits"))]
+mod rpmb;
+#[cfg(feature = "seccfg")]
 mod sec;
 mod storage;
+#[cfg(test)]
+mod tests;
 mod xflash_lib;
 pub use cmds::*;
+#[cfg(feature = "dangerous")]
+pub use efuse::write_efuse;
+pub use flash::set_rsc_info;
 pub use xflash_lib::*;
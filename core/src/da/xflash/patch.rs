@@ -10,7 +10,7 @@ use sha2::{Digest, Sha256};
 
 use crate::da::xflash::XFlash;
 use crate::da::{DA, DAEntryRegion};
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::utilities::arm::*;
 use crate::utilities::patching::*;
 
@@ -23,17 +23,23 @@ pub fn patch_da(xflash: &mut XFlash) -> Result<DA> {
     match hash_pos {
         Some(pos) => {
             let mut hasher = Sha256::new();
-            hasher.update(&da2.data[..da2.data.len().saturating_sub(da2.sig_len as usize)]);
+            let signed_len = da2.data.len().saturating_sub(da2.sig_len as usize);
+            hasher.update(da2.data.get(..signed_len).unwrap_or_default());
             let hash_result = hasher.finalize();
             patch(&mut da1.data, pos, &bytes_to_hex(&hash_result))?;
 
             let original_da = &xflash.da;
+            let header = original_da
+                .regions
+                .first()
+                .ok_or_else(|| Error::penumbra("DA has no regions to patch"))?;
             let da = DA {
                 da_type: xflash.da.da_type.clone(),
-                regions: vec![original_da.regions[0].clone(), da1.clone(), da2.clone()],
+                regions: vec![header.clone(), da1.clone(), da2.clone()],
                 magic: original_da.magic,
                 hw_code: original_da.hw_code,
                 hw_sub_code: original_da.hw_sub_code,
+                issues: Vec::new(),
             };
             Ok(da)
         }
@@ -46,13 +52,15 @@ pub fn patch_da(xflash: &mut XFlash) -> Result<DA> {
 
 /// Patches only DA1, specific for V5 DA
 pub fn patch_da1(xflash: &mut XFlash) -> Result<DAEntryRegion> {
-    let da1 = xflash.da.get_da1().cloned().unwrap();
+    let da1 =
+        xflash.da.get_da1().cloned().ok_or_else(|| Error::penumbra("DA1 region not found"))?;
     Ok(da1)
 }
 
 /// Patches only DA2, specific for V5 DA
 pub fn patch_da2(xflash: &mut XFlash) -> Result<DAEntryRegion> {
-    let mut da2 = xflash.da.get_da2().cloned().unwrap();
+    let mut da2 =
+        xflash.da.get_da2().cloned().ok_or_else(|| Error::penumbra("DA2 region not found"))?;
 
     patch_boot_to(&mut da2)?;
 
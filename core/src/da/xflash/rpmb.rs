@@ -0,0 +1,129 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! RPMB access through the DA extensions.
+//!
+//! The extensions build and authenticate the RPMB frames themselves: the host only deals
+//! with the 256 bytes of data of each frame, addressed by frame index. Authenticated writes
+//! need the RPMB key of the device, set with [`init_rpmb`] before the first write.
+use log::{debug, info};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::core::progress::{ProgressEvent, ProgressReporter};
+use crate::core::summary::OperationKind;
+use crate::da::DAProtocol;
+use crate::da::protocol::{RPMB_FRAME_SIZE, RPMB_KEY_SIZE};
+use crate::da::xflash::{Cmd, XFlash};
+use crate::error::{Error, Result};
+use crate::{le_u16, le_u32};
+
+/// Turns the result code of an RPMB operation into an error, as defined by JEDEC.
+fn check_result(op: &str, code: u32) -> Result<()> {
+    // The top bit only flags an expired write counter
+    let reason = match code & 0x7F {
+        0 => return Ok(()),
+        1 => "general failure",
+        2 => "authentication failure, the key is wrong",
+        3 => "write counter failure",
+        4 => "address failure",
+        5 => "write failure",
+        6 => "read failure",
+        7 => "no authentication key programmed",
+        _ => "unknown error",
+    };
+
+    Err(Error::proto(format!("RPMB {} failed: {} (0x{:04X})", op, reason, code)))
+}
+
+/// Sets the RPMB key, if given, and initializes RPMB access in the extensions.
+/// Without a key, the one the extensions already hold is used.
+pub async fn init_rpmb(xflash: &mut XFlash, key: Option<&[u8; RPMB_KEY_SIZE]>) -> Result<()> {
+    if let Some(key) = key {
+        xflash.devctrl(Cmd::ExtSetRpmbKey, Some(&[&key[..]])).await?;
+
+        // The extensions echo the key they stored
+        let echoed = xflash.read_data().await?;
+        status_ok!(xflash);
+
+        if echoed != key[..] {
+            return Err(Error::proto("RPMB key was not set, the extensions echoed another one"));
+        }
+        debug!("RPMB key set");
+    }
+
+    let resp = xflash.devctrl(Cmd::ExtInitRpmb, None).await?;
+    check_result("init", le_u32!(resp, 0)?)?;
+
+    info!("RPMB initialized");
+    Ok(())
+}
+
+/// Reads a single frame of data at `address`.
+async fn read_frame(xflash: &mut XFlash, address: u16) -> Result<Vec<u8>> {
+    xflash.devctrl(Cmd::ExtReadRpmb, Some(&[&(address as u32).to_le_bytes()])).await?;
+
+    let resp = xflash.read_data().await?;
+    check_result("read", le_u16!(resp, 0)? as u32)?;
+
+    let frame = xflash.read_data().await?;
+    status_ok!(xflash);
+
+    if frame.len() != RPMB_FRAME_SIZE {
+        return Err(Error::malformed("RPMB frame", &frame));
+    }
+    Ok(frame)
+}
+
+/// Writes a single frame of data at `address`.
+async fn write_frame(xflash: &mut XFlash, address: u16, frame: &[u8]) -> Result<()> {
+    xflash.devctrl(Cmd::ExtWriteRpmb, Some(&[&(address as u32).to_le_bytes(), frame])).await?;
+
+    let resp = xflash.read_data().await?;
+    check_result("write", le_u16!(resp, 0)? as u32)?;
+    status_ok!(xflash);
+
+    Ok(())
+}
+
+/// Reads `blocks` frames starting at frame `address` into `writer`.
+pub async fn read_rpmb(
+    xflash: &mut XFlash,
+    address: u16,
+    blocks: u16,
+    writer: &mut (dyn AsyncWrite + Unpin + Send),
+    progress: &mut (dyn FnMut(ProgressEvent) + Send),
+) -> Result<()> {
+    let total = blocks as usize * RPMB_FRAME_SIZE;
+    let mut progress = ProgressReporter::new(OperationKind::Read, progress);
+
+    for i in 0..blocks {
+        let frame = read_frame(xflash, address + i).await?;
+        writer.write_all(&frame).await?;
+        progress.report((i as usize + 1) * RPMB_FRAME_SIZE, total);
+    }
+
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Writes `blocks` frames from `reader`, starting at frame `address`.
+pub async fn write_rpmb(
+    xflash: &mut XFlash,
+    address: u16,
+    blocks: u16,
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+    progress: &mut (dyn FnMut(ProgressEvent) + Send),
+) -> Result<()> {
+    let total = blocks as usize * RPMB_FRAME_SIZE;
+    let mut progress = ProgressReporter::new(OperationKind::Write, progress);
+    let mut frame = [0u8; RPMB_FRAME_SIZE];
+
+    for i in 0..blocks {
+        reader.read_exact(&mut frame).await?;
+        write_frame(xflash, address + i, &frame).await?;
+        progress.report((i as usize + 1) * RPMB_FRAME_SIZE, total);
+    }
+
+    Ok(())
+}
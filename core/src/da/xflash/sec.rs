@@ -4,33 +4,59 @@
 */
 use std::io::Cursor;
 
-use crate::core::seccfg::{SecCfgV4, SecCfgV4Algo};
-use crate::da::xflash::exts::sej;
+use log::error;
+
+use crate::core::seccfg::{SECCFG_MAX_SIZE, SecCfg, SecCfgV4Algo};
+use crate::da::xflash::exts::{check_sej, sej};
 use crate::da::{DAProtocol, XFlash};
 
-pub async fn parse_seccfg(xflash: &mut XFlash) -> Option<SecCfgV4> {
+/// Runs `data` through SEJ the way `algo` needs. `Plain` leaves it as is.
+async fn crypt(
+    xflash: &mut XFlash,
+    data: &[u8],
+    encrypt: bool,
+    algo: &SecCfgV4Algo,
+) -> Option<Vec<u8>> {
+    let result = match algo {
+        SecCfgV4Algo::Plain => return Some(data.to_vec()),
+        SecCfgV4Algo::SW => sej(xflash, data, encrypt, false, false, false).await,
+        SecCfgV4Algo::HW => sej(xflash, data, encrypt, false, true, true).await,
+        SecCfgV4Algo::HWv3 => sej(xflash, data, encrypt, true, true, false).await,
+        SecCfgV4Algo::HWv4 => sej(xflash, data, encrypt, false, true, false).await,
+    };
+    let result = result.ok()?;
+
+    if algo.is_hardware()
+        && let Err(e) = check_sej(xflash).await
+    {
+        error!("{}, not trusting its output. The chipset may need another SEJ base.", e);
+        return None;
+    }
+    Some(result)
+}
+
+pub async fn parse_seccfg(xflash: &mut XFlash) -> Option<SecCfg> {
     let seccfg = xflash.dev_info.get_partition("seccfg").await?;
     let section = xflash.get_storage().await?.get_user_part();
 
-    let mut progress = |_, _| {};
-
-    // We only need the header and padding, which is 200 bytes
-    let mut seccfg_header = Vec::with_capacity(200);
-    let mut cursor = Cursor::new(&mut seccfg_header);
-
-    xflash.read_flash(seccfg.address, 200, section, &mut progress, &mut cursor).await.ok()?;
-
-    let mut parsed_seccfg = SecCfgV4::parse_header(&seccfg_header).ok()?;
-    let hash = parsed_seccfg.get_encrypted_hash();
-    for algo in [SecCfgV4Algo::SW, SecCfgV4Algo::HW, SecCfgV4Algo::HWv3, SecCfgV4Algo::HWv4] {
-        let dec_hash = match algo {
-            SecCfgV4Algo::SW => sej(xflash, &hash, false, false, false, false).await.ok()?,
-            SecCfgV4Algo::HW => sej(xflash, &hash, false, false, true, true).await.ok()?,
-            SecCfgV4Algo::HWv3 => sej(xflash, &hash, false, true, true, false).await.ok()?,
-            SecCfgV4Algo::HWv4 => sej(xflash, &hash, false, false, true, false).await.ok()?,
-        };
-        if dec_hash == parsed_seccfg.get_hash() {
-            parsed_seccfg.set_algo(algo);
+    let mut progress = |_| {};
+
+    // V4 only needs its header, but V3 keeps the lock state further in
+    let size = SECCFG_MAX_SIZE.min(seccfg.size);
+    let mut seccfg_data = Vec::with_capacity(size);
+    let mut cursor = Cursor::new(&mut seccfg_data);
+
+    xflash.read_flash(seccfg.address, size, section, &mut progress, &mut cursor).await.ok()?;
+
+    let mut parsed_seccfg = SecCfg::parse(&seccfg_data).ok()?;
+    if parsed_seccfg.get_algo().is_some() {
+        return Some(parsed_seccfg);
+    }
+
+    let encrypted = parsed_seccfg.get_encrypted();
+    for algo in SecCfgV4Algo::ALL {
+        let decrypted = crypt(xflash, &encrypted, false, &algo).await?;
+        if parsed_seccfg.accept_decrypted(algo, &decrypted) {
             return Some(parsed_seccfg);
         }
     }
@@ -38,34 +64,29 @@ pub async fn parse_seccfg(xflash: &mut XFlash) -> Option<SecCfgV4> {
     None
 }
 
-pub async fn write_seccfg(xflash: &mut XFlash, seccfg: &mut SecCfgV4) -> Option<Vec<u8>> {
+pub async fn write_seccfg(xflash: &mut XFlash, seccfg: &mut SecCfg) -> Option<Vec<u8>> {
     let seccfg_part = xflash.dev_info.get_partition("seccfg").await?;
     let section = xflash.get_storage().await?.get_user_part();
 
-    let enc_hash = match seccfg.get_algo() {
-        Some(SecCfgV4Algo::SW) => {
-            sej(xflash, &seccfg.get_hash(), true, false, false, false).await.ok()?
-        }
-        Some(SecCfgV4Algo::HW) => {
-            sej(xflash, &seccfg.get_hash(), true, false, true, true).await.ok()?
-        }
-        Some(SecCfgV4Algo::HWv3) => {
-            sej(xflash, &seccfg.get_hash(), true, true, true, false).await.ok()?
-        }
-        Some(SecCfgV4Algo::HWv4) => {
-            sej(xflash, &seccfg.get_hash(), true, false, true, false).await.ok()?
-        }
-        _ => return None,
-    };
+    let algo = seccfg.get_algo()?;
+    let plain = seccfg.get_plain()?;
+    let encrypted = crypt(xflash, &plain, true, &algo).await?;
 
-    seccfg.set_encrypted_hash(enc_hash);
+    seccfg.set_encrypted(encrypted).ok()?;
     let seccfg_data = seccfg.create();
 
-    let mut progress = |_, _| {};
+    let mut progress = |_| {};
     let mut cursor = Cursor::new(&seccfg_data);
 
     xflash
-        .write_flash(seccfg_part.address, seccfg_data.len(), &mut cursor, section, &mut progress)
+        .write_flash(
+            seccfg_part.address,
+            seccfg_data.len(),
+            &mut cursor,
+            section,
+            false,
+            &mut progress,
+        )
         .await
         .ok()?;
 
@@ -8,6 +8,7 @@ use log::debug;
 
 use crate::core::storage::Storage;
 use crate::core::storage::emmc::EmmcStorage;
+use crate::core::storage::nand::NandStorage;
 use crate::core::storage::ufs::UfsStorage;
 use crate::da::xflash::{Cmd, XFlash};
 
@@ -15,9 +16,11 @@ use crate::da::xflash::{Cmd, XFlash};
 pub async fn detect_storage(xflash: &mut XFlash) -> Option<Arc<dyn Storage>> {
     let emmc_response = xflash.devctrl(Cmd::GetEmmcInfo, None).await;
     let ufs_response = xflash.devctrl(Cmd::GetUfsInfo, None).await;
+    let nand_response = xflash.devctrl(Cmd::GetNandInfo, None).await;
 
     debug!("EMMC response: {:?}", emmc_response);
     debug!("UFS response: {:?}", ufs_response);
+    debug!("NAND response: {:?}", nand_response);
     if let Ok(resp) = emmc_response
         && !resp.iter().all(|&b| b == 0)
     {
@@ -36,5 +39,14 @@ pub async fn detect_storage(xflash: &mut XFlash) -> Option<Arc<dyn Storage>> {
         }
     }
 
+    if let Ok(resp) = nand_response
+        && !resp.iter().all(|&b| b == 0)
+    {
+        debug!("NAND storage detected.");
+        if let Ok(storage) = NandStorage::from_response(&resp) {
+            return Some(Arc::new(storage));
+        }
+    }
+
     None
 }
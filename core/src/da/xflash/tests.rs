@@ -0,0 +1,357 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! XFlash sequences against scripted exchanges, played back by the mock port.
+//! Packets are built from the XFlash command flow, no device traffic was recorded.
+use std::sync::Arc;
+
+use crate::connection::Connection;
+use crate::connection::mock::{MockHandle, MockMTKPort, Script, packet, status};
+use crate::core::devinfo::DeviceInfo;
+use crate::core::storage::nand::{NandInfo, NandStorage};
+use crate::core::storage::{NandPartition, PartitionKind};
+use crate::da::protocol::{ChecksumLevel, packet_checksum};
+use crate::da::xflash::flash::{read_flash, read_gpt, write_flash};
+use crate::da::xflash::{Cmd, XFlash};
+use crate::da::{DA, DAType};
+use crate::error::Error;
+
+const DA1_ADDR: u32 = 0x0020_0000;
+
+trait XFlashScript {
+    /// The host sends a packet, which the DA acknowledges with a status.
+    fn send(self, data: &[u8]) -> Self;
+    fn cmd(self, cmd: Cmd) -> Self;
+}
+
+impl XFlashScript for Script {
+    fn send(self, data: &[u8]) -> Self {
+        self.expect(packet(data)).respond(status(0))
+    }
+
+    fn cmd(self, cmd: Cmd) -> Self {
+        self.send(&(cmd as u32).to_le_bytes())
+    }
+}
+
+fn xflash(script: Script) -> (XFlash, MockHandle) {
+    let port = MockMTKPort::new(script);
+    let handle = port.handle();
+
+    let da = DA {
+        da_type: DAType::V5,
+        regions: Vec::new(),
+        magic: 0xDADA,
+        hw_code: 0x6768,
+        hw_sub_code: 0xCA00,
+        issues: Vec::new(),
+    };
+
+    (XFlash::new(Connection::new(Box::new(port)), da, DeviceInfo::new(), None, false), handle)
+}
+
+#[tokio::test]
+async fn upload_stage1() {
+    let da1 = vec![0x5A; 0x40];
+    let sync = (Cmd::SyncSignal as u32).to_le_bytes();
+
+    let script = Script::new()
+        // BROM side: send and jump to DA1
+        .echo([0xD7])
+        .echo(DA1_ADDR.to_be_bytes())
+        .echo((da1.len() as u32).to_be_bytes())
+        .echo(0u32.to_be_bytes())
+        .respond([0x00, 0x00])
+        .expect(&da1)
+        .respond([0x0B, 0x40])
+        .respond([0x00, 0x00])
+        .echo([0xD5])
+        .echo(DA1_ADDR.to_be_bytes())
+        .respond([0x00, 0x00])
+        // DA1 side
+        .respond([0xC0])
+        .expect(packet(&sync))
+        .expect(packet(&(Cmd::SetupEnvironment as u32).to_le_bytes()))
+        .expect(packet(&[
+            0x02, 0x00, 0x00, 0x00, // INFO log level
+            0x01, 0x00, 0x00, 0x00, // UART log channel
+            0x01, 0x00, 0x00, 0x00, // Linux host
+            0x00, 0x00, 0x00, 0x00, // No UFS provisioning
+            0x00, 0x00, 0x00, 0x00,
+        ]))
+        .respond(status(0))
+        .expect(packet(&(Cmd::SetupHwInitParams as u32).to_le_bytes()))
+        .expect(packet(&[0x00; 4]))
+        .respond(status(0))
+        .respond(status(Cmd::SyncSignal as u32))
+        // Started from the preloader, no EMI settings to send
+        .cmd(Cmd::DeviceCtrl)
+        .cmd(Cmd::GetConnectionAgent)
+        .respond(packet(b"preloader"))
+        .respond(status(0))
+        .cmd(Cmd::DeviceCtrl)
+        .cmd(Cmd::SetChecksumLevel)
+        .send(&[0x00; 4]);
+
+    let (mut xflash, handle) = xflash(script);
+
+    assert!(xflash.upload_stage1(DA1_ADDR, da1.len() as u32, da1.clone(), 0).await.unwrap());
+    handle.assert_done();
+}
+
+fn nand() -> Arc<NandStorage> {
+    Arc::new(NandStorage {
+        info: NandInfo {
+            kind: 0x12, // MLC
+            page_size: 0x1000,
+            block_size: 0x40000,
+            spare_size: 0x100,
+            total_size: 0x2000_0000,
+            available_size: 0x1F00_0000,
+            bmt_exist: true,
+            id: Vec::new(),
+        },
+    })
+}
+
+/// ReadData (or WriteData) parameters for 0x20 bytes at 0x40000 of an MLC NAND.
+#[rustfmt::skip]
+const NAND_READ_PARAM: [u8; 56] = [
+    0x10, 0x00, 0x00, 0x00, // NAND
+    0x01, 0x00, 0x00, 0x00, // NAND-WHOLE
+    0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, // Address
+    0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Size
+    0x01, 0x00, 0x00, 0x00, // MLC cells
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00,
+];
+
+#[tokio::test]
+async fn read_flash_loop() {
+    let script = Script::new()
+        .cmd(Cmd::ReadData)
+        .send(&NAND_READ_PARAM)
+        .respond(status(0))
+        .respond(packet(&[0xA5; 0x10]))
+        .send(&[0x00; 4])
+        .respond(packet(&[0x5A; 0x10]))
+        .send(&[0x00; 4]);
+
+    let (mut xflash, handle) = xflash(script);
+    xflash.dev_info.set_storage(nand()).await;
+
+    let mut data = Vec::new();
+    let mut events = 0;
+    read_flash(
+        &mut xflash,
+        0x40000,
+        0x20,
+        PartitionKind::Nand(NandPartition::Whole),
+        &mut |_| events += 1,
+        &mut data,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(data, [[0xA5; 0x10], [0x5A; 0x10]].concat());
+    assert!(events > 0);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn read_flash_checksum_match() {
+    let chunk = [0xA5; 0x20];
+
+    let script = Script::new()
+        .cmd(Cmd::ReadData)
+        .send(&NAND_READ_PARAM)
+        .respond(status(0))
+        .respond(packet(&packet_checksum(&chunk).to_le_bytes()))
+        .respond(packet(&chunk))
+        .send(&[0x00; 4]);
+
+    let (mut xflash, handle) = xflash(script);
+    xflash.dev_info.set_storage(nand()).await;
+    xflash.set_checksum_level(ChecksumLevel::Usb);
+
+    let mut data = Vec::new();
+    read_flash(
+        &mut xflash,
+        0x40000,
+        0x20,
+        PartitionKind::Nand(NandPartition::Whole),
+        &mut |_| {},
+        &mut data,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(data, chunk);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn read_flash_checksum_mismatch() {
+    let chunk = [0xA5; 0x10];
+    let bad_checksum = packet_checksum(&chunk) ^ 0xFFFF;
+
+    let script = Script::new()
+        .cmd(Cmd::ReadData)
+        .send(&NAND_READ_PARAM)
+        .respond(status(0))
+        .respond(packet(&bad_checksum.to_le_bytes()))
+        .respond(packet(&chunk));
+
+    let (mut xflash, handle) = xflash(script);
+    xflash.dev_info.set_storage(nand()).await;
+    xflash.set_checksum_level(ChecksumLevel::Usb);
+
+    let mut data = Vec::new();
+    let err = read_flash(
+        &mut xflash,
+        0x40000,
+        0x20,
+        PartitionKind::Nand(NandPartition::Whole),
+        &mut |_| {},
+        &mut data,
+    )
+    .await
+    .unwrap_err();
+
+    assert!(matches!(err, Error::ChecksumMismatch { offset: 0 }));
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn write_flash_pads_with_zeros() {
+    let padded = [[0xA5; 0x10], [0x00; 0x10]].concat();
+
+    let script = Script::new()
+        .cmd(Cmd::WriteData)
+        .send(&NAND_READ_PARAM)
+        // The checksum covers the zero-filled tail too
+        .expect(packet(&0u32.to_le_bytes()))
+        .expect(packet(&packet_checksum(&padded).to_le_bytes()))
+        .send(&padded)
+        .respond(status(0));
+
+    let (mut xflash, handle) = xflash(script);
+    xflash.dev_info.set_storage(nand()).await;
+
+    let mut reader = &[0xA5; 0x10][..];
+    write_flash(
+        &mut xflash,
+        0x40000,
+        0x20,
+        &mut reader,
+        PartitionKind::Nand(NandPartition::Whole),
+        true,
+        &mut |_| {},
+    )
+    .await
+    .unwrap();
+
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn write_flash_input_too_short() {
+    // Nothing is sent once the input runs out
+    let script = Script::new().cmd(Cmd::WriteData).send(&NAND_READ_PARAM);
+
+    let (mut xflash, handle) = xflash(script);
+    xflash.dev_info.set_storage(nand()).await;
+
+    let mut reader = &[0xA5; 0x10][..];
+    let err = write_flash(
+        &mut xflash,
+        0x40000,
+        0x20,
+        &mut reader,
+        PartitionKind::Nand(NandPartition::Whole),
+        false,
+        &mut |_| {},
+    )
+    .await
+    .unwrap_err();
+
+    assert!(matches!(err, Error::InputTooShort { expected: 0x20, got: 0x10 }));
+    handle.assert_done();
+}
+
+/// Status of a DA refusing an unknown partition name.
+const NOT_FOUND: u32 = 0xC002_0000;
+
+#[tokio::test]
+async fn read_gpt_refused_upload_keeps_sync() {
+    let usb_speed = 0x1234u32.to_le_bytes();
+
+    let script = Script::new()
+        // Both names are refused, nothing is streamed and no ack is owed
+        .cmd(Cmd::Upload)
+        .expect(packet(b"PGPT"))
+        .respond(status(NOT_FOUND))
+        .cmd(Cmd::Upload)
+        .expect(packet(b"pgpt"))
+        .respond(status(NOT_FOUND))
+        // Raw read fallback
+        .cmd(Cmd::ReadData)
+        .send(&NAND_READ_PARAM)
+        .respond(status(0))
+        .respond(packet(&[0xA5; 0x20]))
+        .send(&[0x00; 4])
+        // The next command still gets its own status
+        .cmd(Cmd::DeviceCtrl)
+        .cmd(Cmd::GetUsbSpeed)
+        .respond(packet(&usb_speed))
+        .respond(status(0));
+
+    let (mut xflash, handle) = xflash(script);
+    xflash.dev_info.set_storage(nand()).await;
+
+    let data = read_gpt(
+        &mut xflash,
+        &["PGPT", "pgpt"],
+        0x40000,
+        0x20,
+        PartitionKind::Nand(NandPartition::Whole),
+    )
+    .await;
+
+    assert_eq!(data, [0xA5; 0x20]);
+    assert_eq!(xflash.devctrl(Cmd::GetUsbSpeed, None).await.unwrap(), usb_speed);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn read_gpt_acks_streamed_upload() {
+    let script = Script::new()
+        .cmd(Cmd::Upload)
+        .expect(packet(b"PGPT"))
+        .respond(status(NOT_FOUND))
+        .cmd(Cmd::Upload)
+        .send(b"pgpt")
+        .respond(packet(&0x10u64.to_le_bytes()))
+        .respond(status(0))
+        .respond(packet(&[0x5A; 0x10]))
+        .send(&[0x00; 4])
+        // Final ack, only owed once the upload streamed
+        .send(&[0x00; 4]);
+
+    let (mut xflash, handle) = xflash(script);
+
+    let data = read_gpt(
+        &mut xflash,
+        &["PGPT", "pgpt"],
+        0,
+        0x8000,
+        PartitionKind::Nand(NandPartition::Whole),
+    )
+    .await;
+
+    assert_eq!(data, [0x5A; 0x10]);
+    handle.assert_done();
+}
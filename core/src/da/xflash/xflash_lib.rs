@@ -5,19 +5,22 @@
 use std::sync::Arc;
 
 use log::{debug, error, info, warn};
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, repeat};
 
 use crate::connection::Connection;
 use crate::core::auth::{AuthManager, SignData, SignPurpose, SignRequest};
 use crate::core::devinfo::DeviceInfo;
-use crate::core::emi::extract_emi_settings;
+use crate::core::emi::parse_emi;
+use crate::core::progress::{ProgressEvent, ProgressReporter};
 use crate::core::storage::Storage;
+use crate::core::summary::OperationKind;
+use crate::da::protocol::{ChecksumLevel, MAX_PACKET_LENGTH, fill_chunk, packet_checksum};
 use crate::da::xflash::cmds::*;
-#[cfg(not(feature = "no_exploits"))]
 use crate::da::xflash::exts::boot_extensions;
 use crate::da::xflash::storage::detect_storage;
-use crate::da::{DA, DAProtocol};
+use crate::da::{DA, DAProtocol, StepRecorder};
 use crate::error::{Error, Result, XFlashError};
+use crate::exploit::ExploitReport;
 use crate::le_u32;
 
 pub struct XFlash {
@@ -29,7 +32,18 @@ pub struct XFlash {
     pub(super) read_packet_length: Option<usize>,
     pub(super) write_packet_length: Option<usize>,
     pub(super) patch: bool,
+    /// Whether exploits may patch the DA at all. When disabled, the signed DA is booted as is
+    pub(super) da_patching: bool,
+    /// Patched copy of `da` from an earlier session, used instead of patching again
+    pub(super) prepatched: Option<DA>,
     pub(super) verbose: bool,
+    /// Checksum level set with SetChecksumLevel at bring-up.
+    pub(super) checksum_level: ChecksumLevel,
+    /// SEJ base given by the user, or the one resolved when booting the extensions
+    pub(super) sej_base: Option<u32>,
+    pub(super) bringup: StepRecorder,
+    /// Exploits considered by the last `upload_da`
+    pub(super) exploits: ExploitReport,
 }
 
 impl XFlash {
@@ -55,10 +69,41 @@ impl XFlash {
             read_packet_length: None,
             write_packet_length: None,
             patch: true,
+            da_patching: true,
+            prepatched: None,
             verbose,
+            checksum_level: ChecksumLevel::None,
+            sej_base: None,
+            bringup: StepRecorder::default(),
+            exploits: ExploitReport::default(),
         }
     }
 
+    /// Provides the result of an earlier patching of the DA, so exploits skip the pattern
+    /// searches and reuse it. It must come from the same DA, see [`DA::same_layout`].
+    pub fn set_prepatched(&mut self, da: DA) {
+        self.prepatched = Some(da);
+    }
+
+    /// Sets the checksum level requested at bring-up. Must be called before uploading the DA.
+    pub fn set_checksum_level(&mut self, level: ChecksumLevel) {
+        self.checksum_level = level;
+    }
+
+    /// Overrides the IO base of the SEJ block the extensions use for seccfg,
+    /// for chipsets where it can't be told from the DA.
+    pub fn set_sej_base(&mut self, base: u32) {
+        self.sej_base = Some(base);
+    }
+
+    /// Enables or disables exploits and DA patching, enabled by default.
+    /// When disabled, the original DA2 is booted along with its signature, for devices
+    /// that only accept a genuine DA.
+    pub fn set_da_patching(&mut self, enabled: bool) {
+        self.patch = enabled;
+        self.da_patching = enabled;
+    }
+
     // Note: When called with multiple params, this function sends data only and does not read any
     // response. For that, call read_data separately and check status manually.
     // This is to accomodate the protocol, while also not breaking read_data for other operations.
@@ -108,6 +153,7 @@ impl XFlash {
         self.conn.send_da(&data, length, addr, sig_len).await?;
         info!("[Penumbra] Sent DA1, jumping to address 0x{:08X}...", addr);
         self.conn.jump_da(addr).await?;
+        self.conn.expect_reenumeration();
 
         let sync_byte = {
             let mut sync_buf = [0u8; 1];
@@ -146,10 +192,7 @@ impl XFlash {
             0,            // ufs_provision
             0,            // reserved
         ];
-        let mut env_buf = [0u8; 20];
-        for (i, v) in env_params.iter().enumerate() {
-            env_buf[i * 4..(i + 1) * 4].copy_from_slice(&v.to_le_bytes());
-        }
+        let env_buf: Vec<u8> = env_params.iter().flat_map(|v| v.to_le_bytes()).collect();
 
         self.send_data(&[&(Cmd::SetupEnvironment as u32).to_le_bytes(), &env_buf]).await?;
 
@@ -160,12 +203,16 @@ impl XFlash {
         info!("[Penumbra] Received DA1 sync signal.");
 
         self.handle_emi().await?;
-        self.devctrl(Cmd::SetChecksumLevel, Some(&[&0u32.to_le_bytes()])).await?;
+        let level: u32 = match self.checksum_level {
+            ChecksumLevel::None => 0,
+            ChecksumLevel::Usb => 1,
+            ChecksumLevel::Storage => 2,
+        };
+        self.devctrl(Cmd::SetChecksumLevel, Some(&[&level.to_le_bytes()])).await?;
 
         Ok(true)
     }
 
-    #[cfg(not(feature = "no_exploits"))]
     pub(super) async fn boot_extensions(&mut self) -> Result<bool> {
         if self.using_exts {
             warn!("DA extensions already in use, skipping re-upload");
@@ -196,23 +243,35 @@ impl XFlash {
         &mut self,
         size: usize,
         writer: &mut (dyn AsyncWrite + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
+        let mut progress = ProgressReporter::new(OperationKind::Read, progress);
         let mut bytes_read = 0;
-        progress(0, size);
+        progress.report(0, size);
         loop {
+            self.conn.check_host_awake()?;
+            // With checksums enabled, the DA sends the packet checksum ahead of the data
+            let expected = match self.checksum_level {
+                ChecksumLevel::None => None,
+                _ => Some(le_u32!(self.read_data().await?, 0)?),
+            };
             let chunk = self.read_data().await?;
             if chunk.is_empty() {
                 debug!("No data received, breaking.");
                 break;
             }
+            if let Some(expected) = expected
+                && expected != packet_checksum(&chunk)
+            {
+                return Err(Error::ChecksumMismatch { offset: bytes_read as u64 });
+            }
 
             writer.write_all(&chunk).await?;
             bytes_read += chunk.len();
 
             self.send(&[0u8; 4]).await?;
 
-            progress(bytes_read, size);
+            progress.report(bytes_read, size);
 
             if bytes_read >= size {
                 debug!("Requested size read. Breaking.");
@@ -228,19 +287,24 @@ impl XFlash {
     /// Sends data to the device from the provided reader.
     /// Common loop for `write_flash` and `download`.
     ///
-    /// If we receive less data than requested from the reader,
-    /// we pad the remaining bytes with 0s and send it anyway.
+    /// If the reader ends before `size`, the rest is zero-filled when `pad_with_zeros`,
+    /// otherwise this fails with [`Error::InputTooShort`].
     pub async fn download_data(
         &mut self,
         size: usize,
         reader: &mut (dyn AsyncRead + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        pad_with_zeros: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         let chunk_size = self.write_packet_length.unwrap_or(0x8000);
         let mut buffer = vec![0u8; chunk_size];
         let mut bytes_written = 0;
 
-        progress(0, size);
+        let fill = if pad_with_zeros { size as u64 } else { 0 };
+        let mut reader = reader.chain(repeat(0).take(fill));
+
+        let mut progress = ProgressReporter::new(OperationKind::Write, progress);
+        progress.report(0, size);
         loop {
             if bytes_written >= size {
                 break;
@@ -255,25 +319,21 @@ impl XFlash {
             let remaining = size - bytes_written;
             let to_read = remaining.min(chunk_size);
 
-            let bytes_read = reader.read(&mut buffer[..to_read]).await?;
-            let chunk = if bytes_read == 0 {
-                &buffer[..to_read]
-            } else if bytes_read < to_read {
-                buffer[bytes_read..to_read].fill(0);
-                &buffer[..to_read]
-            } else {
-                &buffer[..to_read]
-            };
+            // Padding comes from the reader itself, so the checksum covers it too
+            buffer.truncate(to_read);
+            fill_chunk(&mut reader, &mut buffer, bytes_written, size).await?;
+            let chunk = buffer.as_slice();
 
             // DA expects a checksum of the data chunk before the actual data
             // The actual checksum is a additive 16-bit checksum (Good job MTK!!)
             // For whoever is reading this code and has no clue what this is doing:
             // Just sum all bytes then AND with 0xFFFF :D!!!
-            let checksum = chunk.iter().fold(0u32, |total, &byte| total + byte as u32) & 0xFFFF;
+            let checksum = packet_checksum(chunk);
+            self.conn.check_host_awake()?;
             self.send_data(&[&0u32.to_le_bytes(), &checksum.to_le_bytes(), chunk]).await?;
 
             bytes_written += chunk.len();
-            progress(bytes_written, size);
+            progress.report(bytes_written, size);
             debug!("Written {}/{} bytes...", bytes_written, size);
         }
 
@@ -285,18 +345,19 @@ impl XFlash {
     pub async fn progress_report(
         &mut self,
         size: usize,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
-        progress(0, size);
+        let mut progress = ProgressReporter::new(OperationKind::Erase, progress);
+        progress.report(0, size);
         loop {
             let status = self.read_data().await?;
-            if le_u32!(status, 0) == 0x40040005 {
-                progress(size, size);
+            if le_u32!(status, 0)? == 0x40040005 {
+                progress.report(size, size);
                 break;
             }
 
             let status = self.read_data().await?;
-            let progress_percent = le_u32!(status, 0);
+            let progress_percent = le_u32!(status, 0)?;
 
             // The device doesn't send statuses during erase/format, so we have to send
             // an acknowledgment manually through the port and not through send()
@@ -306,7 +367,7 @@ impl XFlash {
             self.conn.write(&ack).await?;
 
             let progress_bytes = (progress_percent as usize * size) / 100;
-            progress(progress_bytes, size);
+            progress.report(progress_bytes, size);
         }
 
         Ok(())
@@ -326,12 +387,15 @@ impl XFlash {
     }
 
     pub(super) fn parse_header(&self, hdr: &[u8; 12]) -> Result<u32> {
-        let magic = le_u32!(hdr, 0);
-        let len = le_u32!(hdr, 8);
+        let magic = le_u32!(hdr, 0)?;
+        let len = le_u32!(hdr, 8)?;
 
         if magic != Cmd::Magic as u32 {
             return Err(Error::io("Invalid magic"));
         }
+        if len > MAX_PACKET_LENGTH {
+            return Err(Error::malformed("DA packet length out of bounds", hdr));
+        }
 
         debug!("[RX] Data Length from Header: 0x{:X}", len);
 
@@ -351,8 +415,7 @@ impl XFlash {
             .as_ref()
             .ok_or_else(|| Error::penumbra("Device is in BROM but no preloader was provided!"))?;
 
-        let emi = extract_emi_settings(pl)
-            .ok_or_else(|| Error::penumbra("Failed to extract EMI settings from preloader!"))?;
+        let emi = parse_emi(pl)?;
 
         info!("[Penumbra] Uploading EMI settings to device...");
         self.send_cmd(Cmd::InitExtRam).await?;
@@ -371,7 +434,7 @@ impl XFlash {
             }
         };
 
-        let sla_enabled = le_u32!(resp, 0) != 0;
+        let sla_enabled = le_u32!(resp, 0)? != 0;
 
         if !sla_enabled {
             return Ok(true);
@@ -386,7 +449,7 @@ impl XFlash {
 
         let auth = AuthManager::get();
         if !auth.can_sign(&da2_data) {
-            #[cfg(not(feature = "no_exploits"))]
+            #[cfg(feature = "exploits-da-patch")]
             {
                 info!("No available signers for DA SLA, trying dummy signature...");
                 let dummy_sig = vec![0u8; 256];
@@ -404,14 +467,20 @@ impl XFlash {
 
         let firmware_info = self.devctrl(Cmd::GetDevFwInfo, None).await?;
         debug!("Firmware Info: {:02X?}", firmware_info);
-        let rnd = &firmware_info[4..4 + 0x10];
-        let hrid = &firmware_info[4 + 0x10..4 + 0x10 + 16];
-        let soc_id = &firmware_info[4 + 0x10 + 16..4 + 0x10 + 16 + 32];
+        // Header, random challenge, HRID and SoC ID
+        let (Some(rnd), Some(hrid), Some(soc_id)) = (
+            firmware_info.get(4..4 + 0x10),
+            firmware_info.get(4 + 0x10..4 + 0x10 + 16),
+            firmware_info.get(4 + 0x10 + 16..4 + 0x10 + 16 + 32),
+        ) else {
+            return Err(Error::malformed("Firmware info is too short", &firmware_info));
+        };
 
+        let soc_id = SignData::pick_soc_id(soc_id, self.dev_info.soc_id().await)?;
         let sign_data = SignData {
             rnd: rnd.to_vec(),
             hrid: hrid.to_vec(),
-            soc_id: soc_id.to_vec(),
+            soc_id,
             raw: firmware_info.to_vec(),
         };
         let sign_req =
@@ -49,9 +49,53 @@ pub enum XmlCmdLifetime {
 /// Each XML command should implement this trait, by
 /// using the `XmlCommand` derive macro.
 pub trait XmlCommand {
-    fn cmd_name(&self) -> &'static str;
-    fn args(&self) -> Vec<(Option<&'static str>, &'static str, String)>;
-    fn version(&self) -> &'static str;
+    fn cmd_name(&self) -> &str;
+    fn args(&self) -> Vec<(Option<&str>, &str, String)>;
+    fn version(&self) -> &str;
+}
+
+/// An XML command built at runtime, for commands Penumbra doesn't wrap.
+///
+/// The command is sent as is: nothing checks that the DA expects these arguments,
+/// or that running it is harmless. See [`crate::Device::send_raw_xml`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawXmlCommand {
+    /// Name of the command, with or without its `CMD:` prefix
+    pub name: String,
+    /// Version of the command layout, `1.0` for most commands
+    pub version: String,
+    /// `(section, tag, value)` of each argument, the section defaulting to `arg`.
+    /// Tags may be paths like `source_file/path`.
+    pub args: Vec<(Option<String>, String, String)>,
+}
+
+impl RawXmlCommand {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), version: "1.0".to_string(), args: Vec::new() }
+    }
+
+    /// Adds an argument to the `arg` section.
+    pub fn arg(mut self, tag: impl Into<String>, value: impl Into<String>) -> Self {
+        self.args.push((None, tag.into(), value.into()));
+        self
+    }
+}
+
+impl XmlCommand for RawXmlCommand {
+    fn cmd_name(&self) -> &str {
+        self.name.strip_prefix("CMD:").unwrap_or(&self.name)
+    }
+
+    fn args(&self) -> Vec<(Option<&str>, &str, String)> {
+        self.args
+            .iter()
+            .map(|(section, tag, value)| (section.as_deref(), tag.as_str(), value.clone()))
+            .collect()
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
 }
 
 #[derive(XmlCommand)]
@@ -106,6 +150,16 @@ pub struct GetSysProperty {
     target_file: String,
 }
 
+#[derive(XmlCommand)]
+pub struct CanHigherUsbSpeed {
+    #[allow(dead_code)]
+    #[xml(tag = "target_file", fmt = "MEM://0x0:0x200000")]
+    target_file: String,
+}
+
+#[derive(XmlCommand)]
+pub struct SwitchHigherUsbSpeed;
+
 #[derive(XmlCommand)]
 pub struct SecurityGetDevFwInfo {
     #[allow(dead_code)]
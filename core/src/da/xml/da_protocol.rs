@@ -6,76 +6,120 @@ use std::io::Cursor;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use tokio::io::{AsyncRead, AsyncWrite, BufReader};
 
 use crate::connection::Connection;
 use crate::connection::port::ConnectionType;
+#[cfg(feature = "seccfg")]
+use crate::core::chipset::DEFAULT_SEJ_BASE;
 use crate::core::devinfo::DeviceInfo;
+use crate::core::preloader::preloader_partitions;
+use crate::core::progress::ProgressEvent;
+#[cfg(feature = "seccfg")]
 use crate::core::seccfg::LockFlag;
-use crate::core::storage::{Gpt, Partition, PartitionKind, Storage, StorageType};
+use crate::core::storage::{Gpt, GptType, Partition, PartitionKind, Storage, StorageType};
 use crate::da::protocol::{BootMode, DAProtocol};
 use crate::da::xml::cmds::{
     BootTo,
+    GetSysProperty,
     HOST_CMDS,
     HostSupportedCommands,
     NotifyInitHw,
+    RawXmlCommand,
     Reboot,
     SetBootMode,
     XmlCmdLifetime,
 };
-use crate::da::xml::flash;
-#[cfg(not(feature = "no_exploits"))]
+#[cfg(feature = "exploits-da-patch")]
+use crate::da::xml::patch;
+#[cfg(feature = "seccfg")]
 use crate::da::xml::sec::{parse_seccfg, write_seccfg};
-#[cfg(not(feature = "no_exploits"))]
-use crate::da::xml::{exts, patch};
-use crate::da::{DA, DAEntryRegion, Xml};
+use crate::da::xml::xml_lib::DEFAULT_PACKET_LENGTH;
+use crate::da::xml::{USB_FULL_SPEED, USB_HIGH_SPEED, USB_SUPER_SPEED, exts, flash};
+use crate::da::{DA, DAEntryRegion, StepRecorder, Xml};
 use crate::error::{Error, Result};
+#[cfg(feature = "exploits-da-patch")]
 use crate::exploit;
-#[cfg(not(feature = "no_exploits"))]
-use crate::exploit::{Carbonara, Exploit, HeapBait};
+use crate::exploit::ExploitReport;
+#[cfg(feature = "exploits-da-patch")]
+use crate::exploit::{Carbonara, HeapBait};
 
 #[async_trait]
 impl DAProtocol for Xml {
     async fn upload_da(&mut self) -> Result<bool> {
+        self.exploits = ExploitReport::default();
+
+        self.bringup.begin("DA1");
+        let target_config = self.dev_info.target_config().await;
+        self.conn.authenticate(target_config).await?;
+
         let da1 = self.da.get_da1().ok_or_else(|| Error::penumbra("DA1 region not found"))?;
 
         self.upload_stage1(da1.addr, da1.length, da1.data.clone(), da1.sig_len)
             .await
-            .map_err(|e| Error::proto(format!("Failed to upload XML DA1: {e}")))?;
+            .inspect_err(|e| error!("Failed to upload XML DA1: {e}"))?;
+        self.conn.switch_to_da_baudrate().await?;
 
-        exploit!(Carbonara, self);
+        #[cfg(feature = "exploits-da-patch")]
+        {
+            self.bringup.begin("carbonara");
+            exploit!(Carbonara, self);
+        }
 
         let (da2_addr, da2_data) = {
             let da2 = self.da.get_da2().ok_or_else(|| Error::penumbra("DA2 region not found"))?;
-            let sig_len = da2.sig_len as usize;
-            let data = da2.data[..da2.data.len().saturating_sub(sig_len)].to_vec();
+            // Without patching, the DA2 is the genuine one and needs its signature
+            let sig_len = if self.da_patching { da2.sig_len as usize } else { 0 };
+            let mut data = da2.data.clone();
+            data.truncate(da2.data.len().saturating_sub(sig_len));
             (da2.addr, data)
         };
 
+        self.bringup.begin("DA2");
         info!("Uploading and booting to XML DA2...");
         if let Err(e) = self.boot_to(da2_addr, &da2_data).await {
+            error!("Failed to upload XML DA2: {e}");
             self.reboot(BootMode::Normal).await.ok();
-            return Err(Error::proto(format!("Failed to upload XML DA2: {e}")));
+            return Err(e);
         }
 
         info!("Successfully uploaded and booted to XML DA2");
 
-        exploit!(HeapBait, self);
+        #[cfg(feature = "exploits-da-patch")]
+        {
+            self.bringup.begin("heapbait");
+            exploit!(HeapBait, self);
+        }
 
+        self.bringup.begin("host-info");
         // These may fail on some devices — safe to ignore
         xmlcmd_e!(self, HostSupportedCommands, HOST_CMDS).ok();
+        self.send_host_info().await?;
 
+        self.bringup.begin("usb-speed");
+        self.switch_usb_speed().await?;
+
+        self.bringup.begin("init-hw");
         xmlcmd!(self, NotifyInitHw)?;
-        let mut mock_progress = |_, _| {};
+        let mut mock_progress = |_| {};
         self.progress_report(&mut mock_progress).await?;
         self.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
 
+        self.bringup.begin("packet-length");
+        if let Err(e) = self.set_runtime_packet_length().await {
+            warn!("Failed to negotiate the DA packet length: {e}");
+        }
+
+        self.bringup.begin("SLA");
         self.handle_sla().await?;
 
-        #[cfg(not(feature = "no_exploits"))]
-        self.boot_extensions().await?;
+        if self.da_patching {
+            self.bringup.begin("extensions");
+            self.boot_extensions().await?;
+        }
 
+        self.bringup.end();
         Ok(true)
     }
 
@@ -83,7 +127,7 @@ impl DAProtocol for Xml {
         xmlcmd!(self, BootTo, addr, addr, 0x0u64, data.len() as u64)?;
 
         let reader = BufReader::new(Cursor::new(data));
-        let mut progress = |_, _| {};
+        let mut progress = |_| {};
         self.download_file(data.len(), reader, &mut progress).await?;
 
         self.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
@@ -95,19 +139,15 @@ impl DAProtocol for Xml {
     }
 
     async fn send_data(&mut self, data: &[&[u8]]) -> Result<bool> {
-        let max_chunk_size = self.write_packet_length.unwrap_or(0x8000);
+        let max_chunk_size = self.write_packet_length.unwrap_or(DEFAULT_PACKET_LENGTH).max(1);
 
         for param in data {
             let hdr = self.generate_header(param);
             self.conn.write(&hdr).await?;
 
-            let mut pos = 0;
-            while pos < param.len() {
-                let end = (pos + max_chunk_size).min(param.len());
-                let chunk = &param[pos..end];
+            for chunk in param.chunks(max_chunk_size) {
                 debug!("[TX] Sending chunk (0x{:X} bytes)", chunk.len());
                 self.conn.write(chunk).await?;
-                pos = end;
             }
 
             debug!("[TX] Completed sending 0x{:X} bytes", param.len());
@@ -126,19 +166,35 @@ impl DAProtocol for Xml {
 
         xmlcmd_e!(self, Reboot, "IMMEDIATE".to_string())
             .map(|_| ())
-            .map_err(|e| Error::proto(format!("Failed to shutdown device: {e}")))
+            .inspect_err(|e| error!("Failed to shutdown device: {e}"))
     }
 
     async fn reboot(&mut self, bootmode: BootMode) -> Result<()> {
         info!("Rebooting device into {:?} mode...", bootmode);
-        match bootmode {
-            BootMode::Normal | BootMode::HomeScreen => self.shutdown().await?,
-            mode => {
-                let xml_mode = mode.to_text().unwrap();
-                xmlcmd_e!(self, SetBootMode, xml_mode.to_string(), "USB", "ON", "ON")?;
+        let xml_mode = match bootmode {
+            BootMode::Brom => {
+                return Err(Error::penumbra("XML DAs can't reboot into download mode"));
             }
+            mode => mode.to_text(),
+        };
+
+        // The boot mode only applies to the next boot, the reboot itself is always needed
+        if let Some(xml_mode) = xml_mode {
+            let adb = if bootmode == BootMode::AdbMeta { "ON" } else { "OFF" };
+            xmlcmd_e!(self, SetBootMode, xml_mode.to_string(), "USB", "ON", adb).map_err(|e| {
+                Error::unsupported(format!("The DA rejected {:?} boot mode: {e}", bootmode))
+            })?;
         }
 
+        self.shutdown().await
+    }
+
+    async fn keepalive(&mut self) -> Result<()> {
+        // DA.SLA is harmless to query and is supported by every XML DA
+        debug!("Sending keepalive to DA");
+        xmlcmd!(self, GetSysProperty, "DA.SLA", "0")?;
+        self.get_upload_file_resp().await?;
+        self.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
         Ok(())
     }
 
@@ -147,7 +203,7 @@ impl DAProtocol for Xml {
         addr: u64,
         size: usize,
         section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
         writer: &mut (dyn AsyncWrite + Unpin + Send),
     ) -> Result<()> {
         flash::read_flash(self, addr, size, section, writer, progress).await
@@ -159,9 +215,10 @@ impl DAProtocol for Xml {
         size: usize,
         reader: &mut (dyn AsyncRead + Unpin + Send),
         section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        pad_with_zeros: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
-        flash::write_flash(self, addr, size, section, reader, progress).await
+        flash::write_flash(self, addr, size, section, pad_with_zeros, reader, progress).await
     }
 
     async fn erase_flash(
@@ -169,7 +226,7 @@ impl DAProtocol for Xml {
         addr: u64,
         size: usize,
         section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         flash::erase_flash(self, addr, size, section, progress).await
     }
@@ -179,7 +236,7 @@ impl DAProtocol for Xml {
         part_name: String,
         size: usize,
         reader: &mut (dyn AsyncRead + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         flash::download(self, part_name, size, reader, progress).await
     }
@@ -188,7 +245,7 @@ impl DAProtocol for Xml {
         &mut self,
         part_name: String,
         reader: &mut (dyn AsyncWrite + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         flash::upload(self, part_name, reader, progress).await
     }
@@ -196,27 +253,93 @@ impl DAProtocol for Xml {
     async fn format(
         &mut self,
         part_name: String,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         flash::format(self, part_name, progress).await
     }
 
-    async fn read32(&mut self, _addr: u32) -> Result<u32> {
-        todo!()
+    async fn read32(&mut self, addr: u32) -> Result<u32> {
+        if self.using_exts {
+            return exts::read32_ext(self, addr).await;
+        }
+        // Stock XML DAs have no command to access registers
+        Err(Error::unsupported(format!(
+            "Reading registers (0x{:08X}) on XML DAs needs DA extensions",
+            addr
+        )))
     }
 
-    async fn write32(&mut self, _addr: u32, _value: u32) -> Result<()> {
-        todo!()
+    async fn write32(&mut self, addr: u32, value: u32) -> Result<()> {
+        if self.using_exts {
+            return exts::write32_ext(self, addr, value).await;
+        }
+        Err(Error::unsupported(format!(
+            "Writing registers (0x{:08X} to 0x{:08X}) on XML DAs needs DA extensions",
+            value, addr
+        )))
     }
 
     async fn get_usb_speed(&mut self) -> Result<u32> {
-        todo!()
+        if !xmlcmd!(self, GetSysProperty, "DA.USB_SPEED", "0")? {
+            return Err(Error::unsupported("DA does not support querying the USB speed"));
+        }
+
+        let resp = self.get_upload_file_resp().await?;
+        self.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
+
+        let speed = resp.trim_end_matches('\0').trim();
+        debug!("USB speed: {}", speed);
+        match speed.to_ascii_uppercase().as_str() {
+            "FULL-SPEED" => Ok(USB_FULL_SPEED),
+            "HIGH-SPEED" => Ok(USB_HIGH_SPEED),
+            "SUPER-SPEED" | "SUPER-SPEED-PLUS" => Ok(USB_SUPER_SPEED),
+            _ => Err(Error::malformed("USB speed", resp.as_bytes())),
+        }
+    }
+
+    async fn get_random_id(&mut self) -> Result<Vec<u8>> {
+        if !xmlcmd!(self, GetSysProperty, "DA.RANDOM_ID", "0")? {
+            return Err(Error::proto("DA does not support querying the random ID"));
+        }
+
+        let resp = self.get_upload_file_resp().await?;
+        self.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
+
+        let random_id = resp.trim_end_matches('\0').trim();
+        debug!("Random ID: {}", random_id);
+        hex::decode(random_id).map_err(|_| Error::proto("Invalid random ID response"))
+    }
+
+    async fn get_hw_code(&mut self) -> Result<u16> {
+        if !xmlcmd!(self, GetSysProperty, "DA.HW_CODE", "0")? {
+            return Err(Error::proto("DA does not support querying the hardware code"));
+        }
+
+        let resp = self.get_upload_file_resp().await?;
+        self.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
+
+        let hw_code = resp.trim_end_matches('\0').trim();
+        debug!("HW Code: {}", hw_code);
+        u16::from_str_radix(hw_code.trim_start_matches("0x"), 16)
+            .map_err(|_| Error::proto("Invalid hardware code response"))
+    }
+
+    async fn send_raw_xml(&mut self, cmd: &RawXmlCommand) -> Result<String> {
+        self.send_raw_cmd(cmd).await
     }
 
     fn get_connection(&mut self) -> &mut Connection {
         &mut self.conn
     }
 
+    fn into_connection(self: Box<Self>) -> Connection {
+        self.conn
+    }
+
+    fn bringup_steps(&mut self) -> &mut StepRecorder {
+        &mut self.bringup
+    }
+
     fn set_connection_type(&mut self, conn_type: ConnectionType) -> Result<()> {
         self.conn.connection_type = conn_type;
         Ok(())
@@ -230,57 +353,66 @@ impl DAProtocol for Xml {
         self.get_or_detect_storage().await.map_or(StorageType::Unknown, |s| s.kind())
     }
 
-    async fn get_partitions(&mut self) -> Vec<Partition> {
-        let storage = match self.get_storage().await {
-            Some(s) => s,
-            None => {
-                error!("[Penumbra] Failed to get storage for partition parsing");
-                return Vec::new();
-            }
-        };
+    async fn get_partitions(&mut self) -> Result<Vec<Partition>> {
+        let storage = self.get_storage().await.ok_or_else(|| {
+            Error::penumbra("Storage not detected, can't read the partition table")
+        })?;
 
         let storage_type = storage.kind();
-        let pl_part1 = storage.get_pl_part1();
-        let pl_part2 = storage.get_pl_part2();
         let user_part = storage.get_user_part();
-        let pl1_size = storage.get_pl1_size() as usize;
-        let pl2_size = storage.get_pl2_size() as usize;
         let user_size = storage.get_user_size() as usize;
         let gpt_size = 32 * 1024; // TODO: Change this when adding NAND support and PMT
 
-        let mut partitions = vec![
-            Partition::new("preloader", pl1_size, 0, pl_part1),
-            Partition::new("preloader_backup", pl2_size, 0, pl_part2),
-            Partition::new("PGPT", gpt_size, 0, user_part),
-        ];
+        // NAND has no boot regions, its preloader is listed by the partition table
+        let mut partitions: Vec<_> =
+            preloader_partitions(storage.as_ref()).into_iter().filter(|p| p.size != 0).collect();
+        partitions.push(Partition::new("PGPT", gpt_size, 0, user_part));
 
         let sgpt = Partition::new("SGPT", gpt_size, user_size as u64 - gpt_size as u64, user_part);
 
-        let mut progress = |_, _| {};
+        let mut progress = |_| {};
 
         let mut pgpt_data = Vec::new();
         let mut pgpt_cursor = Cursor::new(&mut pgpt_data);
         self.upload("PGPT".into(), &mut pgpt_cursor, &mut progress).await.ok();
-        let parsed_gpt_parts =
-            Gpt::parse(&pgpt_data, storage_type).map(|g| g.partitions()).unwrap_or_default();
-
-        let mut gpt_parts = if !parsed_gpt_parts.is_empty() {
-            parsed_gpt_parts
-        } else {
-            let mut sgpt_data = Vec::new();
-            let mut sgpt_cursor = Cursor::new(&mut sgpt_data);
-            self.upload("SGPT".into(), &mut sgpt_cursor, &mut progress).await.ok();
-            Gpt::parse(&sgpt_data, storage_type).map(|g| g.partitions()).unwrap_or_default()
+        if !pgpt_data.is_empty() {
+            self.dev_info.set_raw_pgpt(pgpt_data.clone()).await;
+        }
+
+        let mut gpt_parts = match Gpt::parse_as(&pgpt_data, GptType::Pgpt, storage_type) {
+            Ok(gpt) => gpt.partitions(),
+            Err(pgpt_err) => {
+                warn!("Primary GPT is invalid ({}), trying the backup GPT...", pgpt_err);
+
+                let mut sgpt_data = Vec::new();
+                let mut sgpt_cursor = Cursor::new(&mut sgpt_data);
+                self.upload("SGPT".into(), &mut sgpt_cursor, &mut progress).await.ok();
+                if !sgpt_data.is_empty() {
+                    self.dev_info.set_raw_sgpt(sgpt_data.clone()).await;
+                }
+
+                let gpt =
+                    Gpt::parse_as(&sgpt_data, GptType::Sgpt, storage_type).map_err(|sgpt_err| {
+                        Error::penumbra(format!(
+                            "No valid GPT found. Primary: {}. Backup: {}",
+                            pgpt_err, sgpt_err
+                        ))
+                    })?;
+                warn!("Using the backup GPT, the primary one should be restored.");
+                gpt.partitions()
+            }
         };
 
         partitions.append(&mut gpt_parts);
         partitions.push(sgpt);
 
-        partitions
+        Ok(partitions)
     }
 
-    #[cfg(not(feature = "no_exploits"))]
+    #[cfg(feature = "seccfg")]
     async fn set_seccfg_lock_state(&mut self, locked: LockFlag) -> Option<Vec<u8>> {
+        info!("Using SEJ base 0x{:08X}", self.sej_base.unwrap_or(DEFAULT_SEJ_BASE));
+
         let mut seccfg = match parse_seccfg(self).await {
             Some(s) => s,
             None => {
@@ -293,30 +425,60 @@ impl DAProtocol for Xml {
         write_seccfg(self, &mut seccfg).await
     }
 
-    #[cfg(not(feature = "no_exploits"))]
+    fn take_exploit_report(&mut self) -> ExploitReport {
+        std::mem::take(&mut self.exploits)
+    }
+
+    fn exts_active(&self) -> bool {
+        self.using_exts
+    }
+
+    fn set_exts_state(&mut self, active: bool) {
+        self.using_exts = active;
+    }
+
+    async fn probe_extensions(&mut self) -> Result<bool> {
+        exts::ext_ack(self).await
+    }
+
     async fn peek(
         &mut self,
         addr: u32,
         length: usize,
         writer: &mut (dyn AsyncWrite + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         exts::peek(self, addr, length, writer, progress).await
     }
 
-    #[cfg(not(feature = "no_exploits"))]
     fn patch_da(&mut self) -> Option<DA> {
-        patch::patch_da(self).ok()
+        if let Some(da) = &self.prepatched {
+            return Some(da.clone());
+        }
+        #[cfg(feature = "exploits-da-patch")]
+        return patch::patch_da(self).ok();
+        #[cfg(not(feature = "exploits-da-patch"))]
+        None
     }
 
-    #[cfg(not(feature = "no_exploits"))]
     fn patch_da1(&mut self) -> Option<DAEntryRegion> {
-        patch::patch_da1(self).ok()
+        if let Some(da) = &self.prepatched {
+            return da.get_da1().cloned();
+        }
+        #[cfg(feature = "exploits-da-patch")]
+        return patch::patch_da1(self).ok();
+        #[cfg(not(feature = "exploits-da-patch"))]
+        None
     }
 
-    #[cfg(not(feature = "no_exploits"))]
     fn patch_da2(&mut self) -> Option<DAEntryRegion> {
-        patch::patch_da2(self).ok()
+        if let Some(da) = &self.prepatched {
+            return da.get_da2().cloned();
+        }
+        #[cfg(feature = "exploits-da-patch")]
+        return patch::patch_da2(self).ok();
+        #[cfg(not(feature = "exploits-da-patch"))]
+        None
     }
 
     fn get_devinfo(&self) -> &DeviceInfo {
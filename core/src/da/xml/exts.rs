@@ -2,23 +2,76 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+#[cfg(feature = "seccfg")]
 use std::io::Cursor;
 
-use log::{debug, info};
+use log::{debug, info, warn};
 use tokio::io::AsyncWrite;
 use xmlcmd_derive::XmlCommand;
 
+use crate::core::chipset;
+#[cfg(feature = "seccfg")]
+use crate::core::chipset::DEFAULT_SEJ_BASE;
+#[cfg(feature = "seccfg")]
+use crate::core::crypto::sej::{SejReg, acon_is_valid};
+use crate::core::progress::ProgressEvent;
 use crate::da::DAProtocol;
 use crate::da::xml::Xml;
 use crate::da::xml::cmds::{XmlCmdLifetime, XmlCommand};
-use crate::da::xml::patch::{detect_arch, find_sej_base, to_arch};
+#[cfg(feature = "seccfg")]
+use crate::error::Error;
 use crate::error::Result;
 use crate::exploit::get_v6_payload;
-use crate::utilities::analysis::create_analyzer;
-use crate::utilities::patching::{bytes_to_hex, patch_pattern_str};
+use crate::le_u32;
+use crate::utilities::analysis::{Arch, create_analyzer};
+use crate::utilities::patching::{HEX_NOT_FOUND, bytes_to_hex, find_pattern, patch_pattern_str};
 use crate::utilities::xml::get_tag;
 
 const DA_EXT: &[u8] = include_bytes!("../../../payloads/da_xml.bin");
+const SEJ_BASE_PATTERN_ARM64: &str = "0801XX52XX00805208XXXX72";
+const SEJ_BASE_PATTERN_ARM64_ALT: &str = "0901XX52XX031faa09XXXX72";
+const SEJ_BASE_PATTERN_ARM: &str = "0800XXE30210A0E3XXXX41E3";
+
+pub fn detect_arch(data: &[u8]) -> bool {
+    data.len() > 4 && data.starts_with(&[0xC6, 0x01, 0x00, 0x58])
+}
+
+pub fn to_arch(is_arm64: bool) -> Arch {
+    if is_arm64 { Arch::Aarch64 } else { Arch::Arm }
+}
+
+/// Finds the SEJ base the DA2 loads into a register, `None` if the pattern isn't found.
+pub fn find_sej_base(data: &[u8]) -> Option<u32> {
+    let is_arm64 = detect_arch(data);
+    let offset = if is_arm64 {
+        let off = find_pattern(data, SEJ_BASE_PATTERN_ARM64, 0);
+        if off == HEX_NOT_FOUND { find_pattern(data, SEJ_BASE_PATTERN_ARM64_ALT, 0) } else { off }
+    } else {
+        find_pattern(data, SEJ_BASE_PATTERN_ARM, 0)
+    };
+
+    if offset == HEX_NOT_FOUND {
+        debug!("Could not find the SEJ base in DA2");
+        return None;
+    }
+
+    // MOV/MOVK on arm64, MOVW/MOVT on arm
+    let (Ok(lo_insn), Ok(hi_insn)) = (le_u32!(data, offset), le_u32!(data, offset + 8)) else {
+        warn!("SEJ base pattern is truncated!");
+        return None;
+    };
+
+    let base = if is_arm64 {
+        let low = (lo_insn >> 5) & 0xFFFF;
+        let high = (hi_insn >> 5) & 0xFFFF;
+        ((high << 16) | low) & 0xFFFFF000
+    } else {
+        let low = (((lo_insn >> 16) & 0xF) << 12) | (lo_insn & 0xFFF);
+        let high = (((hi_insn >> 16) & 0xF) << 12) | (hi_insn & 0xFFF);
+        ((high << 16) | low) & 0xFFFFF000
+    };
+    Some(base)
+}
 
 #[derive(XmlCommand)]
 pub struct ExtAck;
@@ -37,7 +90,6 @@ pub struct ExtReadMem {
     length: usize,
 }
 
-/*
 #[derive(XmlCommand)]
 pub struct ExtWriteMem {
     #[xml(tag = "address", fmt = "0x{address:X}")]
@@ -45,8 +97,8 @@ pub struct ExtWriteMem {
     #[xml(tag = "length", fmt = "0x{length:X}")]
     length: u32,
 }
-*/
 
+#[cfg(feature = "seccfg")]
 #[derive(XmlCommand)]
 pub struct ExtSej {
     #[xml(tag = "encrypt")]
@@ -81,19 +133,34 @@ pub async fn boot_extensions(xml: &mut Xml) -> Result<bool> {
         return Ok(false);
     }
 
-    match xmlcmd!(xml, ExtAck) {
-        Ok(_) => {}
-        Err(_) => {
-            info!("Extensions did not reply, continuing without extensions");
-            return Ok(false);
-        }
+    if !ext_ack(xml).await? {
+        info!("Extensions did not ack, continuing without extensions");
+        return Ok(false);
+    }
+
+    // Some V6 devices have a different SEJ base, we need to set it here so that SEJ commands work
+    let detected = find_sej_base(xml.da.get_da2().map_or(&[][..], |da| &da.data[..]));
+    let sej_base = chipset::sej_base(xml.dev_info.hw_code().await, xml.sej_base, detected);
+    xmlcmd_e!(xml, ExtSetSejBase, sej_base)?;
+    xml.sej_base = Some(sej_base);
+
+    info!("Successfully booted XML extensions");
+
+    Ok(true)
+}
+
+/// Asks the extensions for their ack, returning whether they answered.
+pub async fn ext_ack(xml: &mut Xml) -> Result<bool> {
+    if xmlcmd!(xml, ExtAck).is_err() {
+        debug!("Extensions did not reply to the ack");
+        return Ok(false);
     }
 
     let response = match xml.get_upload_file_resp().await {
         Ok(resp) => resp,
         Err(_) => {
             xml.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
-            info!("Failed to get extension ack response, continuing without extensions");
+            debug!("Failed to get extension ack response");
             return Ok(false);
         }
     };
@@ -102,16 +169,10 @@ pub async fn boot_extensions(xml: &mut Xml) -> Result<bool> {
 
     let ack: String = get_tag(&response, "status")?;
     if ack != "OK" {
-        info!("DA extensions failed to start: {}", ack);
+        debug!("Extensions answered the ack with {}", ack);
         return Ok(false);
     }
 
-    // Some V6 devices have a different SEJ base, we need to set it here so that SEJ commands work
-    let sej_base = find_sej_base(xml.da.get_da2().map_or(&[][..], |da| &da.data[..]));
-    xmlcmd_e!(xml, ExtSetSejBase, sej_base)?;
-
-    info!("Successfully booted XML extensions");
-
     Ok(true)
 }
 
@@ -120,7 +181,7 @@ fn prepare_extensions(xml: &Xml) -> Option<Vec<u8>> {
     let da2data = &xml.da.get_da2()?.data;
 
     let is_arm64 = detect_arch(da2data);
-    let mut da_ext_data = get_v6_payload(DA_EXT, is_arm64).to_vec();
+    let mut da_ext_data = get_v6_payload(DA_EXT, is_arm64).ok()?.to_vec();
 
     patch_pattern_str(&mut da_ext_data, "11111111", &bytes_to_hex(&da2address.to_le_bytes()))?;
 
@@ -174,6 +235,7 @@ fn prepare_extensions(xml: &Xml) -> Option<Vec<u8>> {
     Some(da_ext_data)
 }
 
+#[cfg(feature = "seccfg")]
 pub async fn sej(
     xml: &mut Xml,
     data: &[u8],
@@ -192,7 +254,7 @@ pub async fn sej(
 
     let mut buf = data.to_vec();
     let mut cursor = Cursor::new(&mut buf);
-    let mut progress = |_: usize, _: usize| {};
+    let mut progress = |_: ProgressEvent| {};
 
     xml.download_file(length as usize, &mut cursor, &mut progress).await?;
     cursor.set_position(0);
@@ -203,6 +265,22 @@ pub async fn sej(
     Ok(buf)
 }
 
+/// Checks that a SEJ block answers at the base the extensions use, right after a hardware
+/// operation. A wrong base would silently give garbage instead.
+#[cfg(feature = "seccfg")]
+pub async fn check_sej(xml: &mut Xml) -> Result<()> {
+    let base = xml.sej_base.unwrap_or(DEFAULT_SEJ_BASE);
+    let acon = read32_ext(xml, base + SejReg::ACON.offset()).await?;
+
+    if !acon_is_valid(acon) {
+        return Err(Error::penumbra(format!(
+            "No SEJ block answers at 0x{:08X} (ACON reads 0x{:08X})",
+            base, acon
+        )));
+    }
+    Ok(())
+}
+
 pub async fn peek<F>(
     xml: &mut Xml,
     addr: u32,
@@ -211,7 +289,7 @@ pub async fn peek<F>(
     mut progress: F,
 ) -> Result<()>
 where
-    F: FnMut(usize, usize) + Send,
+    F: FnMut(ProgressEvent) + Send,
 {
     xmlcmd!(xml, ExtReadMem, addr, length)?;
 
@@ -221,3 +299,21 @@ where
 
     Ok(())
 }
+
+pub async fn read32_ext(xml: &mut Xml, addr: u32) -> Result<u32> {
+    let mut buf = Vec::with_capacity(4);
+    peek(xml, addr, 4, &mut buf, |_| {}).await?;
+
+    Ok(le_u32!(buf, 0)?)
+}
+
+pub async fn write32_ext(xml: &mut Xml, addr: u32, value: u32) -> Result<()> {
+    let data = value.to_le_bytes();
+    let mut progress = |_: ProgressEvent| {};
+
+    xmlcmd!(xml, ExtWriteMem, addr, data.len() as u32)?;
+    xml.download_file(data.len(), &data[..], &mut progress).await?;
+    xml.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
+
+    Ok(())
+}
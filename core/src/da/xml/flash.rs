@@ -2,9 +2,12 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
-use tokio::io::{AsyncRead, AsyncWrite};
+use log::warn;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, repeat};
 
-use crate::core::storage::{PartitionKind, is_pl_part};
+use crate::core::devinfo::DeviceInfo;
+use crate::core::progress::ProgressEvent;
+use crate::core::storage::{Partition, PartitionKind, is_pl_part};
 use crate::da::Xml;
 use crate::da::xml::cmds::{
     ErasePartition,
@@ -14,7 +17,7 @@ use crate::da::xml::cmds::{
     XmlCmdLifetime,
 };
 use crate::da::xml::{EraseFlash, ReadFlash, WriteFlash};
-use crate::error::Result;
+use crate::error::{Error, Result};
 
 pub async fn upload<F, W>(
     xml: &mut Xml,
@@ -24,7 +27,7 @@ pub async fn upload<F, W>(
 ) -> Result<()>
 where
     W: AsyncWrite + Unpin,
-    F: FnMut(usize, usize) + Send,
+    F: FnMut(ProgressEvent) + Send,
 {
     xmlcmd!(xml, ReadPartition, &part_name, &part_name)?;
 
@@ -44,9 +47,13 @@ pub async fn read_flash<F, W>(
 ) -> Result<()>
 where
     W: AsyncWrite + Unpin,
-    F: FnMut(usize, usize) + Send,
+    F: FnMut(ProgressEvent) + Send,
 {
-    xmlcmd!(xml, ReadFlash, section.as_str(), section.as_str(), size, addr)?;
+    if !xmlcmd!(xml, ReadFlash, section.as_str(), section.as_str(), size, addr)? {
+        let part = raw_fallback(&xml.dev_info, "read", addr, size, section).await?;
+        return upload(xml, part.name, writer, progress).await;
+    }
+
     xml.upload_file(&mut writer, &mut progress).await?;
     xml.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
 
@@ -62,13 +69,13 @@ pub async fn download<F, R>(
 ) -> Result<()>
 where
     R: AsyncRead + Unpin,
-    F: FnMut(usize, usize) + Send,
+    F: FnMut(ProgressEvent) + Send,
 {
     xmlcmd!(xml, WritePartition, &part_name, &part_name)?;
     // Progress report is not needed for PL partitions,
     // because the DA skips the erase process for them.
     if !is_pl_part(&part_name) {
-        let mut mock_progress = |_: usize, _: usize| {};
+        let mut mock_progress = |_: ProgressEvent| {};
         xml.progress_report(&mut mock_progress).await?;
     }
 
@@ -86,17 +93,25 @@ pub async fn write_flash<F, R>(
     addr: u64,
     size: usize,
     section: PartitionKind,
-    mut reader: R,
+    pad_with_zeros: bool,
+    reader: R,
     mut progress: F,
 ) -> Result<()>
 where
     R: AsyncRead + Unpin,
-    F: FnMut(usize, usize) + Send,
+    F: FnMut(ProgressEvent) + Send,
 {
-    xmlcmd!(xml, WriteFlash, section.as_str(), size, addr)?;
+    // Only what's past the end of the input is zero-filled, the transfer stops at `size`
+    let fill = if pad_with_zeros { size as u64 } else { 0 };
+    let mut reader = reader.chain(repeat(0).take(fill));
+
+    if !xmlcmd!(xml, WriteFlash, section.as_str(), size, addr)? {
+        let part = raw_fallback(&xml.dev_info, "write", addr, size, section).await?;
+        return download(xml, part.name, size, reader, progress).await;
+    }
 
     xml.file_system_op(FileSystemOp::FileSize(size)).await?;
-    xml.progress_report(&mut |_, _| {}).await?; // Pre-erase
+    xml.progress_report(&mut |_| {}).await?; // Pre-erase
     xml.download_file(size, &mut reader, &mut progress).await?;
     xml.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
 
@@ -105,7 +120,7 @@ where
 
 pub async fn format<F>(xml: &mut Xml, part_name: String, mut progress: F) -> Result<()>
 where
-    F: FnMut(usize, usize) + Send,
+    F: FnMut(ProgressEvent) + Send,
 {
     xmlcmd!(xml, ErasePartition, &part_name)?;
     xml.progress_report(&mut progress).await?;
@@ -123,11 +138,48 @@ pub async fn erase_flash<F>(
     mut progress: F,
 ) -> Result<()>
 where
-    F: FnMut(usize, usize) + Send,
+    F: FnMut(ProgressEvent) + Send,
 {
-    xmlcmd!(xml, EraseFlash, section.as_str(), size, addr)?;
+    if !xmlcmd!(xml, EraseFlash, section.as_str(), size, addr)? {
+        let part = raw_fallback(&xml.dev_info, "erase", addr, size, section).await?;
+        return format(xml, part.name, progress).await;
+    }
+
     xml.progress_report(&mut progress).await?;
     xml.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
 
     Ok(())
 }
+
+/// Some DAs answer ERR!UNSUPPORTED to raw offset access (READ-FLASH, WRITE-FLASH, ...).
+/// When the range is exactly a known partition, the partition commands can be used instead.
+async fn raw_fallback(
+    dev_info: &DeviceInfo,
+    op: &str,
+    addr: u64,
+    size: usize,
+    section: PartitionKind,
+) -> Result<Partition> {
+    let part = dev_info
+        .partitions()
+        .await
+        .into_iter()
+        .find(|p| p.address == addr && p.size == size && p.kind.as_str() == section.as_str());
+
+    match part {
+        Some(part) => {
+            warn!(
+                "DA does not support raw {} access, falling back to partition '{}'",
+                op, part.name
+            );
+            Ok(part)
+        }
+        None => Err(Error::proto(format!(
+            "DA does not support raw {} access, and 0x{:X}+0x{:X} in {} is not a whole partition",
+            op,
+            addr,
+            size,
+            section.as_str()
+        ))),
+    }
+}
@@ -6,14 +6,15 @@
 mod macros;
 mod cmds;
 mod da_protocol;
-#[cfg(not(feature = "no_exploits"))]
 mod exts;
 mod flash;
-#[cfg(not(feature = "no_exploits"))]
+#[cfg(feature = "exploits-da-patch")]
 mod patch;
-#[cfg(not(feature = "no_exploits"))]
+#[cfg(feature = "seccfg")]
 mod sec;
 mod storage;
+#[cfg(test)]
+mod tests;
 mod xml_lib;
 pub use cmds::*;
-pub use xml_lib::Xml;
+pub use xml_lib::{USB_FULL_SPEED, USB_HIGH_SPEED, USB_SUPER_SPEED, Xml};
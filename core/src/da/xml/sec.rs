@@ -4,33 +4,53 @@
 */
 use std::io::Cursor;
 
-use crate::core::seccfg::{SecCfgV4, SecCfgV4Algo};
-use crate::da::xml::exts::sej;
+use log::error;
+
+use crate::core::seccfg::{SECCFG_MAX_SIZE, SecCfg, SecCfgV4Algo};
+use crate::da::xml::exts::{check_sej, sej};
 use crate::da::{DAProtocol, Xml};
 
-pub async fn parse_seccfg(xml: &mut Xml) -> Option<SecCfgV4> {
+/// Runs `data` through SEJ the way `algo` needs. `Plain` leaves it as is.
+async fn crypt(xml: &mut Xml, data: &[u8], encrypt: bool, algo: &SecCfgV4Algo) -> Option<Vec<u8>> {
+    let result = match algo {
+        SecCfgV4Algo::Plain => return Some(data.to_vec()),
+        SecCfgV4Algo::SW => sej(xml, data, encrypt, false, false, false).await,
+        SecCfgV4Algo::HW => sej(xml, data, encrypt, false, true, true).await,
+        SecCfgV4Algo::HWv3 => sej(xml, data, encrypt, true, true, false).await,
+        SecCfgV4Algo::HWv4 => sej(xml, data, encrypt, false, true, false).await,
+    };
+    let result = result.ok()?;
+
+    if algo.is_hardware()
+        && let Err(e) = check_sej(xml).await
+    {
+        error!("{}, not trusting its output. The chipset may need another SEJ base.", e);
+        return None;
+    }
+    Some(result)
+}
+
+pub async fn parse_seccfg(xml: &mut Xml) -> Option<SecCfg> {
     let seccfg = xml.dev_info.get_partition("seccfg").await?;
-    let mut progress = |_, _| {};
+    let mut progress = |_| {};
 
-    let mut seccfg_header = Vec::with_capacity(seccfg.size);
-    let mut cursor = Cursor::new(&mut seccfg_header);
+    let mut seccfg_data = Vec::with_capacity(seccfg.size);
+    let mut cursor = Cursor::new(&mut seccfg_data);
 
     xml.upload("seccfg".to_string(), &mut cursor, &mut progress).await.ok()?;
 
-    // Cut to 200 bytes
-    seccfg_header.truncate(200);
-
-    let mut parsed_seccfg = SecCfgV4::parse_header(&seccfg_header).ok()?;
-    let hash = parsed_seccfg.get_encrypted_hash();
-    for algo in [SecCfgV4Algo::SW, SecCfgV4Algo::HW, SecCfgV4Algo::HWv3, SecCfgV4Algo::HWv4] {
-        let dec_hash = match algo {
-            SecCfgV4Algo::SW => sej(xml, &hash, false, false, false, false).await.ok()?,
-            SecCfgV4Algo::HW => sej(xml, &hash, false, false, true, true).await.ok()?,
-            SecCfgV4Algo::HWv3 => sej(xml, &hash, false, true, true, false).await.ok()?,
-            SecCfgV4Algo::HWv4 => sej(xml, &hash, false, false, true, false).await.ok()?,
-        };
-        if dec_hash == parsed_seccfg.get_hash() {
-            parsed_seccfg.set_algo(algo);
+    // V4 only needs its header, but V3 keeps the lock state further in
+    seccfg_data.truncate(SECCFG_MAX_SIZE);
+
+    let mut parsed_seccfg = SecCfg::parse(&seccfg_data).ok()?;
+    if parsed_seccfg.get_algo().is_some() {
+        return Some(parsed_seccfg);
+    }
+
+    let encrypted = parsed_seccfg.get_encrypted();
+    for algo in SecCfgV4Algo::ALL {
+        let decrypted = crypt(xml, &encrypted, false, &algo).await?;
+        if parsed_seccfg.accept_decrypted(algo, &decrypted) {
             return Some(parsed_seccfg);
         }
     }
@@ -38,30 +58,18 @@ pub async fn parse_seccfg(xml: &mut Xml) -> Option<SecCfgV4> {
     None
 }
 
-pub async fn write_seccfg(xml: &mut Xml, seccfg: &mut SecCfgV4) -> Option<Vec<u8>> {
-    let enc_hash = match seccfg.get_algo() {
-        Some(SecCfgV4Algo::SW) => {
-            sej(xml, &seccfg.get_hash(), true, false, false, false).await.ok()?
-        }
-        Some(SecCfgV4Algo::HW) => {
-            sej(xml, &seccfg.get_hash(), true, false, true, true).await.ok()?
-        }
-        Some(SecCfgV4Algo::HWv3) => {
-            sej(xml, &seccfg.get_hash(), true, true, true, false).await.ok()?
-        }
-        Some(SecCfgV4Algo::HWv4) => {
-            sej(xml, &seccfg.get_hash(), true, false, true, false).await.ok()?
-        }
-        _ => return None,
-    };
+pub async fn write_seccfg(xml: &mut Xml, seccfg: &mut SecCfg) -> Option<Vec<u8>> {
+    let algo = seccfg.get_algo()?;
+    let plain = seccfg.get_plain()?;
+    let encrypted = crypt(xml, &plain, true, &algo).await?;
 
-    seccfg.set_encrypted_hash(enc_hash);
+    seccfg.set_encrypted(encrypted).ok()?;
     let seccfg_data = seccfg.create();
 
-    let mut progress = |_, _| {};
+    let mut progress = |_| {};
     let mut cursor = Cursor::new(&seccfg_data);
 
-    xml.download("seccfg".to_string(), 200, &mut cursor, &mut progress).await.ok()?;
+    xml.download("seccfg".to_string(), seccfg_data.len(), &mut cursor, &mut progress).await.ok()?;
 
     Some(seccfg_data)
 }
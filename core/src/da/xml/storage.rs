@@ -8,6 +8,7 @@ use log::debug;
 
 use crate::core::storage::Storage;
 use crate::core::storage::emmc::EmmcStorage;
+use crate::core::storage::nand::NandStorage;
 use crate::core::storage::ufs::UfsStorage;
 use crate::da::xml::Xml;
 use crate::da::xml::cmds::{GetHwInfo, XmlCmdLifetime};
@@ -34,7 +35,15 @@ pub async fn detect_storage(xml: &mut Xml) -> Option<Arc<dyn Storage>> {
                 return Some(Arc::new(storage));
             }
         }
-        _ => {}
+        // Feature phones with raw NAND, either parallel or SPI
+        "NAND" | "SNAND" | "SPI-NAND" => {
+            debug!("NAND storage detected.");
+            match NandStorage::from_xml_response(&reponse) {
+                Ok(storage) => return Some(Arc::new(storage)),
+                Err(e) => debug!("Failed to parse NAND info: {}", e),
+            }
+        }
+        _ => debug!("Unsupported storage: {}", storage_str),
     }
 
     None
@@ -0,0 +1,321 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! XML DA sequences against scripted exchanges, played back by the mock port.
+//! The scripts are written from the protocol as implemented by the DA, not captured.
+use crate::connection::Connection;
+use crate::connection::mock::{MockHandle, MockMTKPort, Script, packet};
+use crate::core::devinfo::DeviceInfo;
+use crate::da::xml::xml_lib::default_host_info;
+use crate::da::xml::{RawXmlCommand, Xml, create_cmd};
+use crate::da::{DA, DAProtocol, DAType};
+use crate::error::Error;
+
+const UPLOAD_FILE: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?><da><version>1.0</version>\
+    <command>CMD:UPLOAD-FILE</command><arg><checksum>CHK_NO</checksum><info>preloader</info>\
+    <source_file>preloader.bin</source_file><packet_length>0x1000</packet_length></arg></da>";
+
+const DOWNLOAD_FILE: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?><da><version>1.0</version>\
+    <command>CMD:DOWNLOAD-FILE</command><arg><checksum>CHK_NO</checksum><info>da2</info>\
+    <source_file>MEM://0x0:0x900</source_file><packet_length>0x400</packet_length></arg></da>";
+
+const CMD_START: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?><da><version>1.0</version>\
+    <command>CMD:START</command></da>";
+
+trait XmlScript {
+    /// The host acknowledges with "OK".
+    fn ack(self) -> Self;
+    /// The device sends a packet, acknowledged by the host.
+    fn send_acked(self, data: &[u8]) -> Self;
+}
+
+impl XmlScript for Script {
+    fn ack(self) -> Self {
+        self.expect(packet(b"OK\0"))
+    }
+
+    fn send_acked(self, data: &[u8]) -> Self {
+        self.respond(packet(data)).ack()
+    }
+}
+
+fn xml(script: Script) -> (Xml, MockHandle) {
+    let port = MockMTKPort::new(script);
+    let handle = port.handle();
+
+    let da = DA {
+        da_type: DAType::V6,
+        regions: Vec::new(),
+        magic: 0xDADA,
+        hw_code: 0x0766,
+        hw_sub_code: 0xCA00,
+        issues: Vec::new(),
+    };
+
+    (Xml::new(Connection::new(Box::new(port)), da, DeviceInfo::new(), false), handle)
+}
+
+/// Start of CMD:UPLOAD-FILE for a file of `size` bytes.
+fn upload_header(size: usize) -> Script {
+    Script::new()
+        .send_acked(UPLOAD_FILE.as_bytes())
+        .send_acked(format!("OK@0x{:X}\0", size).as_bytes())
+}
+
+#[tokio::test]
+async fn upload_file_short_packets() {
+    let file: Vec<u8> = (0..0x1000).map(|i| i as u8).collect();
+
+    // The DA announces 0x1000 bytes packets, but sends 0x600 bytes ones
+    let mut script = upload_header(file.len());
+    for chunk in file.chunks(0x600) {
+        script = script.send_acked(b"OK\0").send_acked(chunk);
+    }
+
+    let (mut xml, handle) = xml(script);
+
+    let mut data = Vec::new();
+    xml.upload_file(&mut data, &mut |_| {}).await.unwrap();
+
+    assert_eq!(data, file);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn upload_file_overshoot_is_truncated() {
+    let script = upload_header(0x10).send_acked(b"OK\0").send_acked(&[0xA5; 0x20]);
+
+    let (mut xml, handle) = xml(script);
+
+    let mut data = Vec::new();
+    xml.upload_file(&mut data, &mut |_| {}).await.unwrap();
+
+    assert_eq!(data, [0xA5; 0x10]);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn upload_file_stopped_early() {
+    let script = upload_header(0x1000)
+        .send_acked(b"OK\0")
+        .send_acked(&[0xA5; 0x600])
+        .send_acked(b"OK\0")
+        .respond(packet(&[]));
+
+    let (mut xml, handle) = xml(script);
+
+    let mut data = Vec::new();
+    let err = xml.upload_file(&mut data, &mut |_| {}).await.unwrap_err();
+
+    assert!(matches!(err, Error::Protocol(_)));
+    assert_eq!(data.len(), 0x600);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn download_file_honors_packet_length() {
+    let file: Vec<u8> = (0..0x900).map(|i| i as u8).collect();
+
+    let mut script = Script::new()
+        .send_acked(DOWNLOAD_FILE.as_bytes())
+        .expect(packet(b"OK@0x900\0"))
+        .respond(packet(b"OK\0"));
+    for chunk in file.chunks(0x400) {
+        script = script
+            .expect(packet(b"OK@0x0\0"))
+            .respond(packet(b"OK\0"))
+            .expect(packet(chunk))
+            .respond(packet(b"OK\0"));
+    }
+
+    let (mut xml, handle) = xml(script);
+
+    xml.download_file(file.len(), &file[..], &mut |_| {}).await.unwrap();
+
+    assert_eq!(xml.write_packet_length, Some(0x400));
+    handle.assert_done();
+}
+
+/// Start of an XML command: CMD:START, the command itself and its acknowledgment.
+fn cmd_start(cmd: &RawXmlCommand) -> Script {
+    Script::new()
+        .send_acked(CMD_START.as_bytes())
+        .expect(packet(create_cmd(cmd).as_bytes()))
+        .respond(packet(b"OK\0"))
+}
+
+fn cmd_end(result: &str) -> Vec<u8> {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?><da><version>1.0</version>\
+         <command>CMD:END</command><result>{}</result><message>done</message></da>",
+        result
+    )
+    .into_bytes()
+}
+
+#[tokio::test]
+async fn raw_cmd_captures_upload() {
+    let cmd = RawXmlCommand::new("CMD:GET-SYS-PROPERTY").arg("key", "DA.VERSION");
+    let script = cmd_start(&cmd)
+        .then(upload_header(4))
+        .send_acked(b"OK\0")
+        .send_acked(b"2.10")
+        .send_acked(&cmd_end("OK"));
+
+    let (mut xml, handle) = xml(script);
+
+    assert_eq!(xml.send_raw_cmd(&cmd).await.unwrap(), "2.10");
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn raw_cmd_reports_failure() {
+    let cmd = RawXmlCommand::new("SECURITY-GET-CERT");
+    let script = cmd_start(&cmd).send_acked(&cmd_end("ERR"));
+
+    let (mut xml, handle) = xml(script);
+
+    let err = xml.send_raw_cmd(&cmd).await.unwrap_err();
+    assert!(err.to_string().contains("CMD:SECURITY-GET-CERT failed: done"));
+    handle.assert_done();
+}
+
+fn set_host_info(info: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?><da><version>1.0</version>\
+         <command>CMD:SET-HOST-INFO</command><arg><info>{}</info></arg></da>\0",
+        info
+    )
+}
+
+#[tokio::test]
+async fn host_info_is_sent() {
+    let script = Script::new()
+        .send_acked(CMD_START.as_bytes())
+        .expect(packet(set_host_info(&default_host_info()).as_bytes()))
+        .respond(packet(b"OK\0"))
+        .send_acked(&cmd_end("OK"));
+
+    let (mut xml, handle) = xml(script);
+
+    xml.send_host_info().await.unwrap();
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn host_info_can_be_overridden() {
+    let script = Script::new()
+        .send_acked(CMD_START.as_bytes())
+        .expect(packet(set_host_info("Flash Tool v1.0").as_bytes()))
+        .respond(packet(b"OK\0"))
+        .send_acked(&cmd_end("OK"));
+
+    let (mut xml, handle) = xml(script);
+
+    xml.set_host_info("Flash Tool v1.0".to_string());
+    xml.send_host_info().await.unwrap();
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn host_info_unsupported_is_skipped() {
+    // Older DAs refuse the command, then end it as usual
+    let script = Script::new()
+        .send_acked(CMD_START.as_bytes())
+        .expect(packet(set_host_info(&default_host_info()).as_bytes()))
+        .respond(packet(b"ERR!UNSUPPORTED\0"))
+        .send_acked(&cmd_end("OK"));
+
+    let (mut xml, handle) = xml(script);
+
+    xml.send_host_info().await.unwrap();
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn host_info_failure_is_reported() {
+    let script = Script::new()
+        .send_acked(CMD_START.as_bytes())
+        .expect(packet(set_host_info(&default_host_info()).as_bytes()))
+        .respond(packet(b"ERR!0xC0010001\0"));
+
+    let (mut xml, handle) = xml(script);
+
+    assert!(xml.send_host_info().await.is_err());
+    handle.assert_done();
+}
+
+/// Watchdog mode register, the kind of register read32 and write32 are used on.
+const WDT_MODE: u32 = 0x1000_7000;
+
+#[cfg(feature = "exploits-da-patch")]
+fn ext_cmd(name: &str, addr: u32, len: usize) -> Vec<u8> {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?><da><version>1.0</version>\
+         <command>CMD:{}</command><arg><address>0x{:X}</address><length>0x{:X}</length></arg></da>\0",
+        name, addr, len
+    )
+    .into_bytes()
+}
+
+/// Start of an extension command, the command being checked byte for byte.
+#[cfg(feature = "exploits-da-patch")]
+fn ext_start(name: &str, addr: u32, len: usize) -> Script {
+    Script::new()
+        .send_acked(CMD_START.as_bytes())
+        .expect(packet(&ext_cmd(name, addr, len)))
+        .respond(packet(b"OK\0"))
+}
+
+#[cfg(feature = "exploits-da-patch")]
+#[tokio::test]
+async fn read32_through_extensions() {
+    let script = ext_start("EXT-READ-MEM", WDT_MODE, 4)
+        .then(upload_header(4))
+        .send_acked(b"OK\0")
+        .send_acked(&0x2200_0064u32.to_le_bytes())
+        .send_acked(&cmd_end("OK"));
+
+    let (mut xml, handle) = xml(script);
+    xml.using_exts = true;
+
+    assert_eq!(xml.read32(WDT_MODE).await.unwrap(), 0x2200_0064);
+    handle.assert_done();
+}
+
+#[cfg(feature = "exploits-da-patch")]
+#[tokio::test]
+async fn write32_through_extensions() {
+    let download = "<?xml version=\"1.0\" encoding=\"utf-8\"?><da><version>1.0</version>\
+        <command>CMD:DOWNLOAD-FILE</command><arg><checksum>CHK_NO</checksum><info>mem</info>\
+        <source_file>MEM://0x10007000:0x4</source_file><packet_length>0x1000</packet_length>\
+        </arg></da>";
+    let script = ext_start("EXT-WRITE-MEM", WDT_MODE, 4)
+        .send_acked(download.as_bytes())
+        .expect(packet(b"OK@0x4\0"))
+        .respond(packet(b"OK\0"))
+        .expect(packet(b"OK@0x0\0"))
+        .respond(packet(b"OK\0"))
+        .expect(packet(&0x2200_0000u32.to_le_bytes()))
+        .respond(packet(b"OK\0"))
+        .send_acked(&cmd_end("OK"));
+
+    let (mut xml, handle) = xml(script);
+    xml.using_exts = true;
+
+    xml.write32(WDT_MODE, 0x2200_0000).await.unwrap();
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn register_access_needs_extensions() {
+    // Nothing is sent to a DA that can't do it
+    let (mut xml, handle) = xml(Script::new());
+
+    let err = xml.read32(WDT_MODE).await.unwrap_err();
+    assert!(matches!(err, Error::Unsupported(_)), "{err}");
+    let err = xml.write32(WDT_MODE, 0x2200_0000).await.unwrap_err();
+    assert!(matches!(err, Error::Unsupported(_)), "{err}");
+    handle.assert_done();
+}
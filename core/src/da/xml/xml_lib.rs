@@ -5,17 +5,20 @@
 use std::sync::Arc;
 
 use log::{debug, error, info, warn};
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter};
-use tokio::time::{Duration, timeout};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufWriter};
+use tokio::time::Duration;
 
-use crate::VERSION;
 use crate::connection::Connection;
 use crate::core::auth::{AuthManager, SignData, SignPurpose, SignRequest};
 use crate::core::devinfo::DeviceInfo;
+use crate::core::progress::{ProgressEvent, ProgressReporter};
 use crate::core::storage::Storage;
+use crate::core::summary::OperationKind;
+use crate::da::protocol::{ChecksumLevel, MAX_PACKET_LENGTH, fill_chunk, packet_checksum};
 use crate::da::xml::cmds::{
     CMD_END,
     CMD_START,
+    CanHigherUsbSpeed,
     DT_PROTOCOL_FLOW,
     FileSystemOp,
     GetSysProperty,
@@ -23,20 +26,34 @@ use crate::da::xml::cmds::{
     HostSupportedCommands,
     MAGIC,
     NotifyInitHw,
+    RawXmlCommand,
     SecurityGetDevFwInfo,
     SecuritySetFlashPolicy,
     SetHostInfo,
     SetRuntimeParameter,
+    SwitchHigherUsbSpeed,
     XmlCmdLifetime,
     XmlCommand,
     create_cmd,
 };
-#[cfg(not(feature = "no_exploits"))]
 use crate::da::xml::exts::boot_extensions;
 use crate::da::xml::storage::detect_storage;
-use crate::da::{DA, DAProtocol};
+use crate::da::{DA, DAProtocol, StepRecorder};
 use crate::error::{Error, Result, XmlError, XmlErrorKind};
+use crate::exploit::ExploitReport;
 use crate::utilities::xml::{get_tag, get_tag_usize};
+use crate::{VERSION, le_u32};
+
+/// USB full-speed (12 Mbps), as returned by `get_usb_speed`.
+pub const USB_FULL_SPEED: u32 = 0;
+/// USB high-speed (480 Mbps), as returned by `get_usb_speed`.
+pub const USB_HIGH_SPEED: u32 = 1;
+/// USB super-speed (5 Gbps and up), as returned by `get_usb_speed`.
+pub const USB_SUPER_SPEED: u32 = 2;
+/// How long the device gets to come back after switching USB speed.
+const USB_RECONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the device gets to acknowledge the start or end of a command.
+const LIFETIME_TIMEOUT: Duration = Duration::from_millis(700);
 
 pub struct Xml {
     pub conn: Connection,
@@ -44,11 +61,32 @@ pub struct Xml {
     pub dev_info: DeviceInfo,
     #[allow(dead_code)]
     pub(super) using_exts: bool,
-    #[allow(dead_code)]
+    /// Packet lengths advertised by the DA, for uploads and downloads.
     pub(super) read_packet_length: Option<usize>,
     pub(super) write_packet_length: Option<usize>,
     pub(super) patch: bool,
+    /// Whether exploits may patch the DA at all. When disabled, the signed DA is booted as is
+    pub(super) da_patching: bool,
+    /// Patched copy of `da` from an earlier session, used instead of patching again
+    pub(super) prepatched: Option<DA>,
     pub(super) verbose: bool,
+    /// Tool identification sent with CMD:SET-HOST-INFO.
+    pub(super) host_info: String,
+    /// Checksum level negotiated with CMD:SET-RUNTIME-PARAMETER.
+    pub(super) checksum_level: ChecksumLevel,
+    /// SEJ base given by the user, or the one resolved when booting the extensions
+    pub(super) sej_base: Option<u32>,
+    pub(super) bringup: StepRecorder,
+    /// Exploits considered by the last `upload_da`
+    pub(super) exploits: ExploitReport,
+}
+
+/// Chunk size of raw transfers, until the DA advertises its packet length.
+pub(super) const DEFAULT_PACKET_LENGTH: usize = 0x8000;
+
+/// Default identification sent to the DA, which honestly names this library.
+pub fn default_host_info() -> String {
+    format!("Penumbra v{}", VERSION)
 }
 
 impl Xml {
@@ -61,10 +99,47 @@ impl Xml {
             read_packet_length: None,
             write_packet_length: None,
             patch: true,
+            da_patching: true,
+            prepatched: None,
             verbose,
+            host_info: default_host_info(),
+            checksum_level: ChecksumLevel::None,
+            sej_base: None,
+            bringup: StepRecorder::default(),
+            exploits: ExploitReport::default(),
         }
     }
 
+    /// Overrides the tool identification string sent with CMD:SET-HOST-INFO.
+    pub fn set_host_info(&mut self, info: String) {
+        self.host_info = info;
+    }
+
+    /// Provides the result of an earlier patching of the DA, so exploits skip the pattern
+    /// searches and reuse it. It must come from the same DA, see [`DA::same_layout`].
+    pub fn set_prepatched(&mut self, da: DA) {
+        self.prepatched = Some(da);
+    }
+
+    /// Sets the checksum level requested at bring-up. Must be called before uploading the DA.
+    pub fn set_checksum_level(&mut self, level: ChecksumLevel) {
+        self.checksum_level = level;
+    }
+
+    /// Overrides the IO base of the SEJ block the extensions use for seccfg,
+    /// for chipsets where it can't be told from the DA.
+    pub fn set_sej_base(&mut self, base: u32) {
+        self.sej_base = Some(base);
+    }
+
+    /// Enables or disables exploits and DA patching, enabled by default.
+    /// When disabled, the original DA2 is booted along with its signature, for devices
+    /// that only accept a genuine DA.
+    pub fn set_da_patching(&mut self, enabled: bool) {
+        self.patch = enabled;
+        self.da_patching = enabled;
+    }
+
     /// Reads data of arbitrary length taken from the header sent by the device.
     pub async fn read_data(&mut self) -> Result<Vec<u8>> {
         let mut hdr = [0u8; 12];
@@ -92,12 +167,15 @@ impl Xml {
     }
 
     pub(super) fn parse_header(&self, hdr: &[u8; 12]) -> Result<u32> {
-        let magic = u32::from_le_bytes(hdr[0..4].try_into().unwrap());
-        let len = u32::from_le_bytes(hdr[8..12].try_into().unwrap());
+        let magic = le_u32!(&hdr[..], 0)?;
+        let len = le_u32!(&hdr[..], 8)?;
 
         if magic != MAGIC {
             return Err(Error::io("Invalid magic"));
         }
+        if len > MAX_PACKET_LENGTH {
+            return Err(Error::malformed("DA packet length out of bounds", hdr));
+        }
 
         debug!("[RX] Data Length from Header: 0x{:X}", len);
 
@@ -106,29 +184,32 @@ impl Xml {
 
     /// Checks for the lifetime acknowledgment (CMD:START or CMD:END).
     async fn check_lifetime(&mut self, lifetime: XmlCmdLifetime) -> Result<bool> {
-        match timeout(Duration::from_millis(700), self.read_data()).await {
-            Ok(Ok(data)) => {
-                let pattern: &[u8] = match lifetime {
-                    XmlCmdLifetime::CmdStart => CMD_START,
-                    XmlCmdLifetime::CmdEnd => CMD_END,
-                };
-
-                if data.windows(20).any(|window| window == b"<result>ERR</result>") {
-                    // We need to ack before returning, or the device will hang.
-                    self.ack(None).await?;
-                    return Ok(false);
-                }
+        let mut hdr = [0u8; 12];
+        match self.conn.read_timeout(&mut hdr, LIFETIME_TIMEOUT).await {
+            Ok(_) => {}
+            // HACK: Since we might reinit before reading the START lifetime,
+            // if we timeout, we assume the lifetime is valid.
+            // TODO: Consider sending CANCEL to restart the handler loop instead.
+            Err(e) if e.is_timeout() && e.bytes_read() == Some(0) => return Ok(true),
+            Err(e) => return Err(e),
+        }
 
-                Ok(data.windows(pattern.len()).any(|window| window == pattern))
-            }
-            Ok(Err(e)) => Err(e),
-            Err(_) => {
-                // HACK: Since we might reinit before reading the START lifetime,
-                // if we timeout, we assume the lifetime is valid.
-                // TODO: Consider sending CANCEL to restart the handler loop instead.
-                Ok(true)
-            }
+        let len = self.parse_header(&hdr)?;
+        let mut data = vec![0u8; len as usize];
+        self.conn.read(&mut data).await?;
+
+        let pattern: &[u8] = match lifetime {
+            XmlCmdLifetime::CmdStart => CMD_START,
+            XmlCmdLifetime::CmdEnd => CMD_END,
+        };
+
+        if data.windows(20).any(|window| window == b"<result>ERR</result>") {
+            // We need to ack before returning, or the device will hang.
+            self.ack(None).await?;
+            return Ok(false);
         }
+
+        Ok(data.windows(pattern.len()).any(|window| window == pattern))
     }
 
     /// Sends an acknowledgment to the device.
@@ -161,6 +242,21 @@ impl Xml {
         Err(Error::proto("Invalid acknowledgment"))
     }
 
+    /// Reads an acknowledgment carrying a value, "OK@0x<hex>". A plain "OK" reads as 0.
+    pub async fn read_ack_value(&mut self) -> Result<u32> {
+        let resp = self.read_data().await?;
+        let s = String::from_utf8_lossy(&resp);
+        let s = s.trim_end_matches('\0');
+
+        if s == "OK" {
+            return Ok(0);
+        }
+
+        s.strip_prefix("OK@0x")
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| Error::proto(format!("Invalid acknowledgment: {s}")))
+    }
+
     /// Acknowledges the lifetime of an XML command (CMD:START or CMD:END).
     pub async fn lifetime_ack(&mut self, lifetime: XmlCmdLifetime) -> Result<bool> {
         let is_valid = self.check_lifetime(lifetime).await?;
@@ -205,7 +301,7 @@ impl Xml {
         &mut self,
         size: usize,
         mut reader: R,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()>
     where
         R: AsyncRead + Unpin,
@@ -243,24 +339,43 @@ impl Xml {
         // Read the response
         self.read_ack().await?;
 
-        let packet_length: usize = get_tag_usize(&resp_string, "arg/packet_length")?;
+        // Packets longer than advertised corrupt the transfer on some DAs
+        let packet_length = match get_tag_usize(&resp_string, "arg/packet_length") {
+            Ok(length) => cache_packet_length(&mut self.write_packet_length, length, "write")?,
+            Err(_) => self
+                .write_packet_length
+                .ok_or_else(|| Error::proto("CMD:DOWNLOAD-FILE without packet length"))?,
+        };
 
         let mut chunk = vec![0u8; packet_length];
         let mut bytes_sent = 0;
+        let mut progress = ProgressReporter::new(OperationKind::Write, progress);
 
         while bytes_sent < size {
-            let to_read = packet_length.min(size - bytes_sent);
-            reader.read_exact(&mut chunk[..to_read]).await?;
-
-            // Status
-            self.ack("0".to_string().into()).await?;
+            // Only the last packet is shorter
+            chunk.truncate(size - bytes_sent);
+            fill_chunk(&mut reader, &mut chunk, bytes_sent, size).await?;
+            self.conn.check_host_awake()?;
+
+            // Status, which carries the packet checksum when enabled
+            let checksum = match self.checksum_level {
+                ChecksumLevel::None => 0,
+                _ => packet_checksum(&chunk),
+            };
+            self.ack(format!("{:x}", checksum).into()).await?;
             self.read_ack().await?;
 
-            self.send(&chunk[..to_read]).await?;
-            self.read_ack().await?;
+            self.send(&chunk).await?;
+            match self.read_ack().await {
+                // The DA refuses the packet when its checksum doesn't match
+                Err(Error::Protocol(_)) if self.checksum_level != ChecksumLevel::None => {
+                    return Err(Error::ChecksumMismatch { offset: bytes_sent as u64 });
+                }
+                result => result?,
+            };
 
-            bytes_sent += to_read;
-            progress(bytes_sent, size);
+            bytes_sent += chunk.len();
+            progress.report(bytes_sent, size);
         }
 
         debug!("File download completed, 0x{:X} bytes sent.", size);
@@ -270,8 +385,8 @@ impl Xml {
     /// Receives a file from the device.
     pub async fn upload_file<W>(
         &mut self,
-        mut writer: W,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        writer: W,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<bool>
     where
         W: AsyncWrite + Unpin,
@@ -295,7 +410,20 @@ impl Xml {
             return Err(Error::proto("Expected CMD:UPLOAD-FILE"));
         }
 
-        let info: String = get_tag(&resp_string, "arg/info").unwrap_or_default();
+        self.upload_announced(&resp_string, writer, progress).await
+    }
+
+    /// Receives the file announced by `resp_string`, an already read CMD:UPLOAD-FILE.
+    async fn upload_announced<W>(
+        &mut self,
+        resp_string: &str,
+        mut writer: W,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<bool>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let info: String = get_tag(resp_string, "arg/info").unwrap_or_default();
         debug!("Received CMD:UPLOAD-FILE command.");
         debug!("  Info: {info}");
 
@@ -316,19 +444,46 @@ impl Xml {
 
         self.ack(None).await?;
 
-        let packet_length: usize = get_tag_usize(&resp_string, "arg/packet_length")?;
+        // Only informative: the DA picks the size of each packet, and may send them shorter
+        if let Ok(length) = get_tag_usize(resp_string, "arg/packet_length") {
+            cache_packet_length(&mut self.read_packet_length, length, "read")?;
+        }
+
         let mut bytes_received = 0;
+        let mut progress = ProgressReporter::new(OperationKind::Read, progress);
 
         while bytes_received < size {
-            let to_read = packet_length.min(size - bytes_received);
-            self.read_ack().await?;
+            self.conn.check_host_awake()?;
+            // With checksums enabled, the DA announces the packet checksum here
+            let expected = self.read_ack_value().await?;
             self.ack(None).await?;
             let data = self.read_data().await?;
-            writer.write_all(&data).await?;
+            if data.is_empty() {
+                return Err(Error::proto(format!(
+                    "Device stopped sending after 0x{:X} of 0x{:X} bytes",
+                    bytes_received, size
+                )));
+            }
+            if self.checksum_level != ChecksumLevel::None
+                && expected != 0
+                && expected != packet_checksum(&data)
+            {
+                return Err(Error::ChecksumMismatch { offset: bytes_received as u64 });
+            }
+
+            // Never write past the advertised size
+            let chunk = data.get(..size - bytes_received).unwrap_or(&data);
+            if chunk.len() < data.len() {
+                warn!(
+                    "Dropping 0x{:X} bytes sent past the end of the file",
+                    data.len() - chunk.len()
+                );
+            }
+            writer.write_all(chunk).await?;
             self.ack(None).await?;
 
-            bytes_received += to_read;
-            progress(bytes_received, size);
+            bytes_received += chunk.len();
+            progress.report(bytes_received, size);
         }
 
         debug!("File upload completed, 0x{:X} bytes received.", size);
@@ -336,10 +491,59 @@ impl Xml {
         Ok(true)
     }
 
+    /// Sends a command built at runtime and follows it until CMD:END.
+    ///
+    /// Returns the file uploaded by the DA in between if any, its CMD:END message otherwise.
+    /// Only uploads are handled: a command that expects a file or reports progress fails,
+    /// leaving the DA in the middle of it.
+    pub async fn send_raw_cmd(&mut self, cmd: &RawXmlCommand) -> Result<String> {
+        if !self.send_cmd(cmd).await? {
+            return Err(Error::unsupported(format!("DA doesn't support CMD:{}", cmd.cmd_name())));
+        }
+
+        let mut uploaded = Vec::new();
+        loop {
+            let resp = self.read_data().await?;
+            let resp_string = String::from_utf8_lossy(&resp).into_owned();
+            let command: String = get_tag(&resp_string, "command").unwrap_or_default();
+
+            match command.as_str() {
+                "CMD:UPLOAD-FILE" => {
+                    self.upload_announced(&resp_string, &mut uploaded, &mut |_| {}).await?;
+                }
+                "CMD:END" => {
+                    self.ack(None).await?;
+
+                    let result: String = get_tag(&resp_string, "result").unwrap_or_default();
+                    if result != "OK" {
+                        let message: String = get_tag(&resp_string, "message").unwrap_or_default();
+                        return Err(Error::proto(format!(
+                            "CMD:{} failed: {}",
+                            cmd.cmd_name(),
+                            message
+                        )));
+                    }
+
+                    if uploaded.is_empty() {
+                        return Ok(resp_string.trim_end_matches('\0').to_string());
+                    }
+                    return Ok(String::from_utf8_lossy(&uploaded).into_owned());
+                }
+                _ => {
+                    return Err(Error::proto(format!(
+                        "Unhandled answer to CMD:{}: {}",
+                        cmd.cmd_name(),
+                        resp_string
+                    )));
+                }
+            }
+        }
+    }
+
     /// Waits for the device to finish a certain operation, reporting progress.
     pub async fn progress_report(
         &mut self,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<bool> {
         let resp = self.read_data().await?;
         let resp_string = String::from_utf8_lossy(&resp);
@@ -354,6 +558,8 @@ impl Xml {
 
         self.ack(None).await?;
 
+        // The DA reports a percentage rather than bytes
+        let mut progress = ProgressReporter::new(OperationKind::Erase, progress);
         let mut resp: Vec<u8> = Vec::new();
         while resp != b"OK!EOT\0" {
             resp = self.read_data().await?;
@@ -374,10 +580,10 @@ impl Xml {
             let progress_value: usize =
                 prog.parse().map_err(|_| Error::proto("Invalid progress value"))?;
 
-            progress(progress_value, 100);
+            progress.report(progress_value, 100);
         }
 
-        progress(100, 100);
+        progress.report(100, 100);
 
         Ok(true)
     }
@@ -417,13 +623,15 @@ impl Xml {
         self.conn.send_da(&data, length, addr, sig_len).await?;
         info!("[Penumbra] Sent XML DA1, jumping to address 0x{:08X}...", addr);
         self.conn.jump_da(addr).await?;
+        self.conn.expect_reenumeration();
 
         let log_level = if self.verbose { "DEBUG" } else { "INFO" };
 
+        let checksum_level = self.checksum_level.to_text();
         xmlcmd_e!(
             self,
             SetRuntimeParameter,
-            "NONE",
+            checksum_level,
             "AUTO-DETECT",
             log_level,
             "UART",
@@ -433,15 +641,53 @@ impl Xml {
         xmlcmd_e!(self, HostSupportedCommands, HOST_CMDS)?;
         // Wait for the device to initialize DRAM
         xmlcmd!(self, NotifyInitHw)?;
-        let mut mock_progress = |_, _| {};
+        let mut mock_progress = |_| {};
         self.progress_report(&mut mock_progress).await?;
         self.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
 
-        xmlcmd_e!(self, SetHostInfo, format!("Penumbra v{}", VERSION))?;
+        self.send_host_info().await?;
 
         Ok(true)
     }
 
+    /// Identifies the host tool to the DA.
+    /// Some DAs refuse flash commands until this is sent, while older ones don't know it at all.
+    pub(super) async fn send_host_info(&mut self) -> Result<()> {
+        let info = self.host_info.clone();
+        if !xmlcmd!(self, SetHostInfo, info)? {
+            debug!("[Penumbra] CMD:SET-HOST-INFO not supported by this DA, skipping");
+            return Ok(());
+        }
+
+        self.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
+        Ok(())
+    }
+
+    /// Asks the DA for its packet length once DRAM is up, as it's often much larger than
+    /// before, and uses it for the following transfers.
+    /// DAs that don't tell it keep the lengths advertised by each transfer.
+    pub(super) async fn set_runtime_packet_length(&mut self) -> Result<()> {
+        if !xmlcmd!(self, GetSysProperty, "DA.PACKET_LENGTH", "0")? {
+            let length = self.write_packet_length.unwrap_or(DEFAULT_PACKET_LENGTH);
+            debug!("[Penumbra] DA doesn't report its packet length, keeping 0x{:X}", length);
+            return Ok(());
+        }
+
+        let resp = self.get_upload_file_resp().await?;
+        self.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
+
+        let value = resp.trim_end_matches('\0').trim();
+        let length = match value.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16),
+            None => value.parse(),
+        }
+        .map_err(|_| Error::proto(format!("Invalid packet length: {value}")))?;
+
+        cache_packet_length(&mut self.write_packet_length, length, "write")?;
+        cache_packet_length(&mut self.read_packet_length, length, "read")?;
+        Ok(())
+    }
+
     pub(super) async fn get_or_detect_storage(&mut self) -> Option<Arc<dyn Storage>> {
         if let Some(storage) = self.dev_info.storage().await {
             return Some(storage);
@@ -458,7 +704,7 @@ impl Xml {
     pub async fn get_upload_file_resp(&mut self) -> Result<String> {
         let mut buffer = Vec::new();
         let mut writer = BufWriter::new(&mut buffer);
-        let mut progress = |_, _| {};
+        let mut progress = |_| {};
 
         self.upload_file(&mut writer, &mut progress).await?;
         writer.flush().await?;
@@ -485,10 +731,10 @@ impl Xml {
         };
 
         let auth = AuthManager::get();
-        let mut progress = |_, _| {};
+        let mut progress = |_| {};
 
         if !auth.can_sign(&da2_data) {
-            #[cfg(not(feature = "no_exploits"))]
+            #[cfg(feature = "exploits-da-patch")]
             {
                 info!("No available signers for DA SLA, trying dummy signature...");
                 let dummy_sig = vec![0u8; 256];
@@ -517,6 +763,7 @@ impl Xml {
         let rnd = hex::decode(rnd_str).map_err(|_| Error::proto("Invalid rnd response"))?;
         let hrid = hex::decode(hrid_str).map_err(|_| Error::proto("Invalid hrid response"))?;
         let soc_id = hex::decode(socid_str).map_err(|_| Error::proto("Invalid socid response"))?;
+        let soc_id = SignData::pick_soc_id(&soc_id, self.dev_info.soc_id().await)?;
 
         let sign_data = SignData { rnd, hrid, soc_id, raw: fw_info.into() };
         let sign_req =
@@ -533,7 +780,70 @@ impl Xml {
         Ok(true)
     }
 
-    #[cfg(not(feature = "no_exploits"))]
+    /// Moves the DA to the fastest USB speed it supports. The device re-enumerates
+    /// in the process, so the port gets reopened. UART links, devices already past
+    /// full-speed and DAs that can't switch are left as they are.
+    ///
+    /// The DA random ID is compared before and after, so that another device showing up
+    /// on the port meanwhile fails the bring-up instead of being driven in its place.
+    pub(super) async fn switch_usb_speed(&mut self) -> Result<()> {
+        if self.conn.port.get_da_baudrate().is_some() {
+            return Ok(());
+        }
+
+        match self.get_usb_speed().await {
+            Ok(USB_FULL_SPEED) => {}
+            Ok(_) => {
+                debug!("DA already runs past USB full-speed");
+                return Ok(());
+            }
+            Err(e) => debug!("Could not query the USB speed: {}", e),
+        }
+
+        if !xmlcmd!(self, CanHigherUsbSpeed, "0")? {
+            debug!("DA does not support switching USB speed");
+            return Ok(());
+        }
+        let resp = self.get_upload_file_resp().await?;
+        self.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
+
+        let resp = resp.trim_end_matches('\0').trim().to_ascii_uppercase();
+        if resp.is_empty() || resp.contains("NOT") {
+            debug!("DA can't go past USB full-speed ({})", resp);
+            return Ok(());
+        }
+
+        let random_id = match self.get_random_id().await {
+            Ok(random_id) if !random_id.is_empty() => Some(random_id),
+            Ok(_) | Err(_) => {
+                warn!(
+                    "Could not read the DA random ID, the device can't be checked after the switch"
+                );
+                None
+            }
+        };
+
+        info!("Switching to a higher USB speed...");
+        if !xmlcmd!(self, SwitchHigherUsbSpeed)? {
+            return Ok(());
+        }
+        // The DA drops off the bus right after, the end of the command might never come
+        self.lifetime_ack(XmlCmdLifetime::CmdEnd).await.ok();
+
+        self.conn.reconnect(USB_RECONNECT_TIMEOUT).await?;
+
+        if let Some(expected) = random_id {
+            let current = self.get_random_id().await?;
+            if current != expected {
+                return Err(Error::conn(
+                    "DA random ID changed across the USB speed switch, another device is on the port",
+                ));
+            }
+        }
+        info!("Switched to a higher USB speed");
+        Ok(())
+    }
+
     pub(super) async fn boot_extensions(&mut self) -> Result<bool> {
         if self.using_exts {
             warn!("DA extensions already in use, skipping re-upload");
@@ -544,3 +854,17 @@ impl Xml {
         Ok(true)
     }
 }
+
+/// Caches a packet length advertised by the DA, logging it when it changes.
+fn cache_packet_length(slot: &mut Option<usize>, length: usize, kind: &str) -> Result<usize> {
+    if length == 0 {
+        return Err(Error::proto(format!("DA advertised an empty {kind} packet length")));
+    }
+
+    if *slot != Some(length) {
+        debug!("[Penumbra] DA {kind} packet length: 0x{:X}", length);
+        *slot = Some(length);
+    }
+
+    Ok(length)
+}
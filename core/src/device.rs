@@ -2,21 +2,107 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use log::{error, info};
-use tokio::io::{AsyncRead, AsyncWrite};
+use log::{debug, error, info, warn};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, repeat, sink};
 use tokio::time::timeout;
 
 use crate::connection::Connection;
 use crate::connection::port::{ConnectionType, MTKPort};
+use crate::connection::retry::{ConnectionStats, RetryPolicy};
+use crate::core::auth::AuthFile;
+use crate::core::avb::{VbMeta, strip_slot_suffix};
+use crate::core::bcb::{BCB_SIZE, MISC_PARTITION, recovery_message};
+use crate::core::chipset::chipset;
 use crate::core::crypto::config::CryptoIO;
 use crate::core::devinfo::{DevInfoData, DeviceInfo};
+use crate::core::efuse::EfuseMap;
+use crate::core::emi::parse_emi;
+use crate::core::preloader::{extract_preloader, is_boot_image, preloader_offset};
+use crate::core::progress::{ProgressEvent, ProgressReporter};
+#[cfg(feature = "seccfg")]
 use crate::core::seccfg::LockFlag;
-use crate::core::storage::{Partition, PartitionKind};
-use crate::da::protocol::BootMode;
-use crate::da::{DAFile, DAProtocol, DAType, XFlash, Xml};
+use crate::core::seccfg::{SECCFG_MAX_SIZE, SecCfg};
+use crate::core::storage::gpt::check_geometry;
+use crate::core::storage::lp::{
+    LP_METADATA_GEOMETRY_SIZE,
+    LP_SECTOR_SIZE,
+    LpExtentTarget,
+    LpGeometry,
+    SUPER_PARTITION,
+    metadata_slot,
+};
+use crate::core::storage::mbr::{MBR_SIZE, check_protective_mbr, protective_mbr};
+use crate::core::storage::{
+    Gpt,
+    GptType,
+    LpMetadata,
+    MbrStatus,
+    Partition,
+    PartitionKind,
+    PartitionRole,
+    StorageInfo,
+    StorageType,
+};
+use crate::core::summary::{
+    BadRange,
+    HashingReader,
+    HashingWriter,
+    OperationKind,
+    OperationSummary,
+    SummaryTracker,
+};
+use crate::da::protocol::{BootMode, ChecksumLevel, RPMB_FRAME_SIZE, RPMB_KEY_SIZE, fill_chunk};
+use crate::da::xml::RawXmlCommand;
+use crate::da::{BringupReport, BringupStep, DA, DAFile, DAProtocol, DAType, Legacy, XFlash, Xml};
 use crate::error::{Error, Result};
+use crate::exploit::ExploitReport;
+use crate::utilities::patching::write_bytes;
+use crate::utilities::sparse::{self, SparseInput, SparseReader};
+
+/// Default deadline for entering DA mode, exploits and storage detection included.
+pub const DEFAULT_BRINGUP_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long a DA has to answer the probe sent when resuming a session.
+const DA_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Chunk size of transfers when skipping bad blocks.
+const SKIP_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+/// Failing chunks are retried in pieces of this size, narrowing down the bad ranges.
+const SKIP_PIECE_SIZE: usize = 64 * 1024;
+/// Attempts for a piece before it's considered bad.
+const SKIP_ATTEMPTS: u32 = 3;
+
+/// Retries and skipped ranges of a transfer that skips bad blocks.
+#[derive(Default)]
+struct SkipLog {
+    retries: u32,
+    bad_ranges: Vec<BadRange>,
+}
+
+impl SkipLog {
+    /// Records a skipped range, merging it with the previous one when contiguous.
+    fn skip(&mut self, offset: u64, length: usize, error: &Error) {
+        warn!("Skipping bad range 0x{:X}-0x{:X}: {}", offset, offset + length as u64, error);
+        match self.bad_ranges.last_mut() {
+            Some(last) if last.offset + last.length == offset => {
+                last.length += length as u64;
+                last.error = error.to_string();
+            }
+            _ => self.bad_ranges.push(BadRange {
+                offset,
+                length: length as u64,
+                error: error.to_string(),
+            }),
+        }
+    }
+
+    fn apply(self, mut summary: OperationSummary) -> OperationSummary {
+        summary.retries = summary.retries.saturating_add(self.retries);
+        summary.bad_ranges = self.bad_ranges;
+        summary
+    }
+}
 
 /// A builder for creating a new [`Device`].
 ///
@@ -49,6 +135,26 @@ pub struct DeviceBuilder {
     preloader_data: Option<Vec<u8>>,
     /// Whether to enable verbose logging.
     verbose: bool,
+    /// Tool identification sent to XML DAs, defaults to Penumbra's own.
+    host_info: Option<String>,
+    /// Overall deadline for entering DA mode.
+    bringup_timeout: Option<Duration>,
+    /// Integrity checks requested from the DA on transfers.
+    checksum_level: ChecksumLevel,
+    /// DA file exported by [`Device::export_patched_da`] in an earlier session.
+    prepatched_da: Option<Vec<u8>>,
+    /// Whether exploits may patch the DA, defaults to true.
+    da_patching: Option<bool>,
+    /// Whether DA regions loaded outside of the chipset memory are an error.
+    strict_da: bool,
+    /// Baudrate overriding the default of the port.
+    baudrate: Option<u32>,
+    /// Vendor auth file, for devices with SLA or DAA enabled.
+    auth_data: Option<Vec<u8>>,
+    /// How transient USB errors are retried, defaults to `RetryPolicy::default()`.
+    retry_policy: Option<RetryPolicy>,
+    /// IO base of the SEJ block, overriding the one of the chipset.
+    sej_base: Option<u32>,
 }
 
 impl DeviceBuilder {
@@ -64,24 +170,135 @@ impl DeviceBuilder {
         self
     }
 
-    /// Assigns the preloader data to be used for the device.
-    pub fn with_preloader(mut self, data: Vec<u8>) -> Self {
+    /// Assigns the preloader image of the device. In BROM mode, its EMI settings are sent
+    /// to DA1 to initialize DRAM, which lets devices with an erased preloader reach DA mode.
+    /// [`DeviceBuilder::build`] fails if the image holds no usable EMI settings.
+    pub fn with_preloader_data(mut self, data: Vec<u8>) -> Self {
         self.preloader_data = Some(data);
         self
     }
 
+    #[deprecated(note = "use `with_preloader_data`")]
+    pub fn with_preloader(self, data: Vec<u8>) -> Self {
+        self.with_preloader_data(data)
+    }
+
     /// Enables verbose logging mode.
     pub fn with_verbose(mut self, verbose: bool) -> Self {
         self.verbose = verbose;
         self
     }
 
+    /// Overrides the tool identification string sent to XML (V6) DAs with CMD:SET-HOST-INFO.
+    ///
+    /// By default, Penumbra identifies itself as what it is. Some OEM DAs only accept a
+    /// whitelisted set of tool identifiers, in which case this can be used to match them.
+    /// Note that claiming to be another vendor's tool is impersonation: only do it on devices
+    /// you own, and be aware that it may go against the terms under which that tool was
+    /// distributed.
+    pub fn with_host_info(mut self, info: impl Into<String>) -> Self {
+        self.host_info = Some(info.into());
+        self
+    }
+
+    /// Sets the overall deadline for `enter_da_mode`, defaults to `DEFAULT_BRINGUP_TIMEOUT`.
+    pub fn with_bringup_timeout(mut self, timeout: Duration) -> Self {
+        self.bringup_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how much the DA checks transferred data, defaults to `ChecksumLevel::None`.
+    /// Higher levels catch corruption from bad cables, at the cost of speed.
+    pub fn with_checksum_level(mut self, level: ChecksumLevel) -> Self {
+        self.checksum_level = level;
+        self
+    }
+
+    /// Provides a DA file exported by [`Device::export_patched_da`], so exploits reuse
+    /// its patched regions instead of patching the DA again.
+    ///
+    /// It's only used if it was made from the DA given with `with_da_data`, and only
+    /// replaces what exploits would patch: if an exploit doesn't run, the original DA is used.
+    pub fn with_prepatched_da(mut self, data: Vec<u8>) -> Self {
+        self.prepatched_da = Some(data);
+        self
+    }
+
+    /// Enables or disables exploits and DA patching at runtime, enabled by default.
+    ///
+    /// When disabled, Kamakiri, Carbonara and the other DA exploits are skipped, no DA
+    /// extensions are booted, and the original DA2 is sent along with its signature.
+    /// Useful for devices where a patched DA fails to boot, without a build lacking exploits.
+    pub fn with_da_patching(mut self, enabled: bool) -> Self {
+        self.da_patching = Some(enabled);
+        self
+    }
+
+    /// Makes `init` fail when the DA loads DA1 or DA2 outside of the known memory
+    /// of the chipset, instead of only warning. Chipsets without a known memory map
+    /// are never checked.
+    pub fn with_strict_da(mut self, strict: bool) -> Self {
+        self.strict_da = strict;
+        self
+    }
+
+    /// Overrides the baudrate of the port, for cheap USB to UART adapters that are
+    /// unreliable at the default rate.
+    ///
+    /// Serial ports are switched to it before the handshake. On a BROM UART console,
+    /// which only handshakes at 115200, it's negotiated with the device right after the
+    /// handshake instead, staying at 115200 if the device refuses it.
+    /// USB ports have no baudrate and ignore it.
+    pub fn with_baudrate(mut self, baudrate: u32) -> Self {
+        self.baudrate = Some(baudrate);
+        self
+    }
+
+    /// Provides the vendor auth file (e.g. `auth_sv5.auth`) of a device with Serial Link
+    /// Authentication or DA Authentication enabled, sent to the BROM before the DA.
+    ///
+    /// It's only sent if the device requires it, and checked when building, so that
+    /// files obviously not auth files are refused before talking to the device.
+    pub fn with_auth_data(mut self, data: Vec<u8>) -> Self {
+        self.auth_data = Some(data);
+        self
+    }
+
+    /// Sets how transient USB errors (timeouts, stalls, overflows) are retried.
+    ///
+    /// Retries happen at the USB transfer level, for the bytes the failed transfer didn't
+    /// move, so protocol packets are never sent twice. Use `RetryPolicy::none()` to fail
+    /// on the first error. How often retries happened is reported by [`Connection::stats`].
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Overrides the IO base of the SEJ (hardware crypto) block, used to encrypt seccfg.
+    ///
+    /// By default it's detected in the DA for V6, and is 0x1000A000 otherwise. A wrong base is
+    /// caught before seccfg is written, as the SEJ block doesn't answer there.
+    pub fn with_sej_base(mut self, base: u32) -> Self {
+        self.sej_base = Some(base);
+        self
+    }
+
     /// Builds and returns a new `Device` instance.
     pub fn build(self) -> Result<Device> {
-        let connection = self.mtk_port.map(Connection::new);
+        let mut connection = self.mtk_port.map(Connection::new);
 
-        if connection.is_none() {
+        let Some(conn) = connection.as_mut() else {
             return Err(Error::penumbra("MTK port must be provided to build a Device."));
+        };
+        if let Some(data) = self.auth_data {
+            conn.set_auth(AuthFile::parse(data)?);
+        }
+        if let Some(policy) = self.retry_policy {
+            conn.set_retry_policy(policy);
+        }
+        // Only needed once in BROM, but a bad preloader is better reported before connecting
+        if let Some(preloader) = &self.preloader_data {
+            parse_emi(preloader)?;
         }
 
         Ok(Device {
@@ -90,8 +307,22 @@ impl DeviceBuilder {
             protocol: None,
             connected: false,
             da_data: self.da_data,
+            prepatched_da: self.prepatched_da,
+            da_patching: self.da_patching.unwrap_or(true),
+            strict_da: self.strict_da,
+            baudrate: self.baudrate,
+            sej_base: self.sej_base,
             preloader_data: self.preloader_data,
             verbose: self.verbose,
+            host_info: self.host_info,
+            bringup_timeout: self.bringup_timeout.unwrap_or(DEFAULT_BRINGUP_TIMEOUT),
+            checksum_level: self.checksum_level,
+            last_bringup: None,
+            last_exploits: None,
+            session_changed: false,
+            read_only: false,
+            skip_bad_blocks: false,
+            last_summary: None,
         })
     }
 }
@@ -118,10 +349,38 @@ pub struct Device {
     connected: bool,
     /// Raw DA file data, if provided.
     da_data: Option<Vec<u8>>,
+    /// Patched DA file from an earlier session, if provided.
+    prepatched_da: Option<Vec<u8>>,
+    /// Whether exploits may patch the DA.
+    da_patching: bool,
+    /// Whether DA regions loaded outside of the chipset memory are an error.
+    strict_da: bool,
+    /// Baudrate overriding the default of the port.
+    baudrate: Option<u32>,
+    /// IO base of the SEJ block, overriding the one of the chipset.
+    sej_base: Option<u32>,
     /// Preloader data, if provided.
     preloader_data: Option<Vec<u8>>,
     /// Whether verbose logging is enabled.
     verbose: bool,
+    /// Tool identification override for XML DAs.
+    host_info: Option<String>,
+    /// Overall deadline for entering DA mode.
+    bringup_timeout: Duration,
+    /// Integrity checks requested from the DA on transfers.
+    checksum_level: ChecksumLevel,
+    /// Step timeline of the last DA bring-up.
+    last_bringup: Option<BringupReport>,
+    /// Outcome of the exploits tried during the last DA upload.
+    last_exploits: Option<ExploitReport>,
+    /// Whether the DA session changed on reinit, and the change hasn't been confirmed yet.
+    session_changed: bool,
+    /// Whether destructive operations are refused.
+    read_only: bool,
+    /// Whether ranges that keep failing are skipped instead of aborting the operation.
+    skip_bad_blocks: bool,
+    /// Statistics of the last flash operation.
+    last_summary: Option<OperationSummary>,
 }
 
 impl Device {
@@ -144,8 +403,31 @@ impl Device {
             .take()
             .ok_or_else(|| Error::penumbra("Connection is not initialized."))?;
 
+        // The device enumerated with the DA VID/PID, so BROM is long gone
+        if conn.connection_type == ConnectionType::Da {
+            info!("Device is already in DA mode, resuming the running DA.");
+            self.init_running_da(conn).await?;
+            self.connected = true;
+            return Ok(());
+        }
+
+        // A BROM UART only handshakes at 115200, its rate is negotiated afterwards
+        let uart = conn.port.get_da_baudrate().is_some();
+        if let Some(baudrate) = self.baudrate
+            && !uart
+        {
+            conn.port.set_baudrate(baudrate).await?;
+            conn.baudrate = baudrate;
+        }
+
         conn.handshake().await?;
 
+        if let Some(baudrate) = self.baudrate
+            && uart
+        {
+            conn.negotiate_baudrate(baudrate).await?;
+        }
+
         let soc_id = conn.get_soc_id().await?;
         let meid = conn.get_meid().await?;
         let hw_code = conn.get_hw_code().await?;
@@ -159,10 +441,16 @@ impl Device {
             storage: None,
             partitions: vec![],
             target_config,
+            raw_pgpt: None,
+            raw_sgpt: None,
+            random_id: None,
         };
 
         self.dev_info.set_data(device_info).await;
 
+        self.warn_da_issues(hw_code);
+        self.check_da_load_addresses(hw_code)?;
+
         if self.da_data.is_some() {
             self.protocol = Some(self.init_da_protocol(conn).await?);
         } else {
@@ -176,6 +464,10 @@ impl Device {
 
     /// Reinits the device connection based on the current connection type and optional DA info.
     /// This is useful for CLIs or scenarios where the Device instance needs to be reset.
+    ///
+    /// When reconnecting to a running DA, the DA random ID is compared with the one in `dev_info`.
+    /// If it changed, destructive operations are refused until [`Device::confirm_session`]
+    /// is called.
     pub async fn reinit(&mut self, dev_info: DevInfoData) -> Result<()> {
         let mut conn = self
             .connection
@@ -199,6 +491,15 @@ impl Device {
             }
             ConnectionType::Da => {
                 self.protocol = Some(self.init_da_protocol(conn).await?);
+
+                // Anything else would garble the port if the DA is gone, so probe first
+                if !self.validate_da_session().await {
+                    return Err(Error::conn(
+                        "No DA answered on the port, the device likely rebooted.",
+                    ));
+                }
+
+                self.verify_session().await;
             }
         };
 
@@ -210,6 +511,8 @@ impl Device {
     /// Enters DA mode by uploading the DA to the device.
     /// This is required for performing DA protocol operations.
     /// After entering DA mode, the device's partition information is read and stored in `dev_info`.
+    /// A failing step returns its own error, the timeline leading to it is logged
+    /// and kept in [`Device::last_bringup_report`].
     ///
     /// # Examples
     /// ```rust
@@ -229,6 +532,68 @@ impl Device {
             return Err(Error::conn("Device is not connected. Call init() first."));
         }
 
+        if self.get_connection()?.connection_type == ConnectionType::Da {
+            debug!("Device is already in DA mode, nothing to upload.");
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        let result = timeout(self.bringup_timeout, self.bringup_da()).await;
+
+        let (steps, running) = match self.protocol.as_mut() {
+            Some(protocol) => protocol.bringup_steps().take(),
+            None => (Vec::new(), None),
+        };
+
+        let failed = !matches!(result, Ok(Ok(())));
+        let report = BringupReport {
+            steps,
+            failed_at: if failed {
+                Some(running.unwrap_or(BringupStep { name: "unknown", duration: Duration::ZERO }))
+            } else {
+                None
+            },
+            total: start.elapsed(),
+        };
+
+        if failed {
+            warn!("DA bring-up {}", report);
+        } else if !report.steps.is_empty() {
+            debug!("DA bring-up {}", report);
+        }
+        self.last_bringup = Some(report.clone());
+
+        if let Some(protocol) = self.protocol.as_mut() {
+            let exploits = protocol.take_exploit_report();
+            if !exploits.attempts.is_empty() {
+                self.last_exploits = Some(exploits);
+            }
+        }
+
+        match result {
+            // Kept as is, so callers can still tell e.g. a security refusal apart.
+            // The timeline is in the log and in `last_bringup_report`.
+            Ok(result) => result,
+            Err(_) => Err(Error::conn(format!(
+                "Timed out entering DA mode after {:.0} s ({})",
+                self.bringup_timeout.as_secs_f64(),
+                report
+            ))),
+        }
+    }
+
+    /// Returns the step timeline of the last `enter_da_mode` call.
+    pub fn last_bringup_report(&self) -> Option<&BringupReport> {
+        self.last_bringup.as_ref()
+    }
+
+    /// Returns the outcome of the exploits tried while entering DA mode,
+    /// or `None` if none were tried.
+    pub fn last_exploit_report(&self) -> Option<&ExploitReport> {
+        self.last_exploits.as_ref()
+    }
+
+    async fn bringup_da(&mut self) -> Result<()> {
         let conn_type = self.get_connection()?.connection_type;
 
         if self.protocol.is_none() {
@@ -238,17 +603,36 @@ impl Device {
             self.protocol = Some(protocol);
         }
 
-        let protocol = self.protocol.as_mut().unwrap();
+        let protocol = self.da_protocol()?;
         if conn_type != ConnectionType::Da {
             protocol.upload_da().await?;
             self.set_connection_type(ConnectionType::Da)?;
+
+            self.bringup_step("random-id");
+            if let Some(random_id) = self.fetch_random_id().await {
+                self.dev_info.set_random_id(random_id).await;
+            }
         }
 
         // Fallback to ensure we always have the partitions available.
-        self.get_partitions().await;
+        self.bringup_step("storage-detect");
+        if let Err(e) = self.load_partitions().await {
+            warn!("Could not read the partition table: {}", e);
+        }
+
+        if let Some(protocol) = self.protocol.as_mut() {
+            protocol.bringup_steps().end();
+        }
         Ok(())
     }
 
+    /// Marks the start of a bring-up step performed outside of the protocol.
+    fn bringup_step(&mut self, name: &'static str) {
+        if let Some(protocol) = self.protocol.as_mut() {
+            protocol.bringup_steps().begin(name);
+        }
+    }
+
     /// Internal helper to ensure the device enters DA mode before performing DA operations.
     async fn ensure_da_mode(&mut self) -> Result<&mut (dyn DAProtocol + Send)> {
         if !self.connected {
@@ -264,7 +648,12 @@ impl Device {
             self.enter_da_mode().await?;
         }
 
-        Ok(self.get_protocol().unwrap())
+        self.da_protocol()
+    }
+
+    /// The DA protocol, for operations that already went through [`Device::ensure_da_mode`].
+    fn da_protocol(&mut self) -> Result<&mut (dyn DAProtocol + Send)> {
+        self.protocol.as_deref_mut().ok_or_else(|| Error::conn("DA protocol is not initialized."))
     }
 
     async fn init_da_protocol(&mut self, conn: Connection) -> Result<Box<dyn DAProtocol + Send>> {
@@ -278,22 +667,346 @@ impl Device {
             Error::penumbra(format!("No compatible DA for hardware code 0x{:04X}", hw_code))
         })?;
 
+        let prepatched = self.prepatched_da(&da);
+        let protocol = self.build_protocol(conn, da, prepatched)?;
+
+        if let Err(e) = self.load_partitions().await {
+            warn!("Could not read the partition table: {}", e);
+        }
+        Ok(protocol)
+    }
+
+    /// The DA of the prepatched file matching `da`, if any.
+    fn prepatched_da(&self, da: &DA) -> Option<DA> {
+        let data = self.prepatched_da.as_deref()?;
+
+        let patched = match DAFile::parse_da(data) {
+            Ok(file) => file.das.into_iter().find(|entry| da.same_layout(entry)),
+            Err(e) => {
+                warn!("Ignoring the prepatched DA, it can't be parsed: {}", e);
+                return None;
+            }
+        };
+
+        if patched.is_none() {
+            warn!(
+                "Ignoring the prepatched DA, it wasn't made from this DA. The DA will be patched again."
+            );
+        }
+        patched
+    }
+
+    fn build_protocol(
+        &self,
+        conn: Connection,
+        da: DA,
+        prepatched: Option<DA>,
+    ) -> Result<Box<dyn DAProtocol + Send>> {
         let protocol: Box<dyn DAProtocol + Send> = match da.da_type {
-            DAType::V5 => Box::new(XFlash::new(
-                conn,
-                da,
-                self.dev_info.clone(),
-                self.preloader_data.clone(),
-                self.verbose,
-            )),
-            DAType::V6 => Box::new(Xml::new(conn, da, self.dev_info.clone(), self.verbose)),
-            _ => return Err(Error::penumbra("Unsupported DA type")),
+            DAType::V5 => {
+                let mut xflash = XFlash::new(
+                    conn,
+                    da,
+                    self.dev_info.clone(),
+                    self.preloader_data.clone(),
+                    self.verbose,
+                );
+                if let Some(patched) = prepatched {
+                    xflash.set_prepatched(patched);
+                }
+                xflash.set_da_patching(self.da_patching);
+                xflash.set_checksum_level(self.checksum_level);
+                if let Some(base) = self.sej_base {
+                    xflash.set_sej_base(base);
+                }
+                Box::new(xflash)
+            }
+            DAType::V6 => {
+                let mut xml = Xml::new(conn, da, self.dev_info.clone(), self.verbose);
+                if let Some(info) = &self.host_info {
+                    xml.set_host_info(info.clone());
+                }
+                if let Some(patched) = prepatched {
+                    xml.set_prepatched(patched);
+                }
+                xml.set_da_patching(self.da_patching);
+                xml.set_checksum_level(self.checksum_level);
+                if let Some(base) = self.sej_base {
+                    xml.set_sej_base(base);
+                }
+                Box::new(xml)
+            }
+            // Legacy DAs are booted as they are, there are no exploits for them
+            DAType::Legacy => {
+                Box::new(Legacy::new(conn, da, self.dev_info.clone(), self.preloader_data.clone()))
+            }
         };
 
-        self.get_partitions().await;
         Ok(protocol)
     }
 
+    /// Takes over a DA that is already running, like one left behind by another tool.
+    /// There's no BROM to ask for the device identity, so the DA is queried instead.
+    async fn init_running_da(&mut self, conn: Connection) -> Result<()> {
+        let da_bytes = self.da_data.clone().ok_or_else(|| {
+            Error::conn("The device is already in DA mode, a DA file is needed to talk to it.")
+        })?;
+
+        // The hardware code isn't known yet, but all entries of a DA file speak the same protocol,
+        // so the first one is enough to ask the DA for it
+        let da_file = DAFile::parse_da(&da_bytes)?;
+        let da = da_file
+            .das
+            .first()
+            .cloned()
+            .ok_or_else(|| Error::penumbra("The DA file has no entries"))?;
+        self.protocol = Some(self.build_protocol(conn, da, None)?);
+
+        if !self.validate_da_session().await {
+            return Err(Error::conn(
+                "The port is in DA mode, but no DA answered. Reset the device and try again.",
+            ));
+        }
+
+        let Some(protocol) = self.protocol.as_mut() else {
+            return Err(Error::penumbra("DA protocol is not initialized."));
+        };
+        let hw_code = match protocol.get_hw_code().await {
+            Ok(hw_code) => hw_code,
+            Err(e) => {
+                warn!("Could not read the hardware code from the DA: {}", e);
+                0
+            }
+        };
+
+        // The first entry was only needed to ask, the rest of the session needs the chip's own
+        match da_file.get_da_from_hw_code(hw_code) {
+            Some(da) if da.hw_code != protocol.get_da().hw_code => {
+                debug!("Switching to the DA entry of hardware code 0x{:04X}", hw_code);
+                if let Some(protocol) = self.protocol.take() {
+                    let conn = protocol.into_connection();
+                    self.protocol = Some(self.build_protocol(conn, da, None)?);
+                }
+            }
+            Some(_) => {}
+            None => warn!(
+                "The DA file has no entry for hardware code 0x{:04X}, using its first one",
+                hw_code
+            ),
+        }
+        self.warn_da_issues(hw_code);
+
+        let device_info = DevInfoData {
+            soc_id: None,
+            meid: None,
+            hw_code,
+            chipset: String::from("Unknown"),
+            storage: None,
+            partitions: vec![],
+            target_config: 0,
+            raw_pgpt: None,
+            raw_sgpt: None,
+            random_id: None,
+        };
+        self.dev_info.set_data(device_info).await;
+
+        if let Some(random_id) = self.fetch_random_id().await {
+            self.dev_info.set_random_id(random_id).await;
+        }
+
+        if let Err(e) = self.load_partitions().await {
+            warn!("Could not read the partition table: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Queries the DA random ID, warning if it can't be read.
+    async fn fetch_random_id(&mut self) -> Option<Vec<u8>> {
+        let protocol = self.protocol.as_mut()?;
+        match protocol.get_random_id().await {
+            Ok(random_id) if !random_id.is_empty() => Some(random_id),
+            Ok(_) | Err(_) => {
+                warn!("Could not read the DA random ID, the session can't be verified.");
+                None
+            }
+        }
+    }
+
+    /// Compares the current DA random ID with the recorded one, flagging the session
+    /// as changed on mismatch. If either is missing, the session can't be verified.
+    async fn verify_session(&mut self) {
+        let Some(expected) = self.dev_info.random_id().await else {
+            warn!("No DA random ID recorded for this session, it can't be verified.");
+            return;
+        };
+
+        let Some(current) = self.fetch_random_id().await else {
+            return;
+        };
+
+        if current != expected {
+            warn!("DA random ID changed since the last session, this might be a different device!");
+            self.dev_info.set_random_id(current).await;
+            self.session_changed = true;
+        }
+    }
+
+    /// Returns whether the DA session changed on reinit, and the change hasn't been confirmed.
+    pub fn session_changed(&self) -> bool {
+        self.session_changed
+    }
+
+    /// Confirms that a changed DA session is expected, allowing destructive operations again.
+    pub fn confirm_session(&mut self) {
+        self.session_changed = false;
+    }
+
+    /// Returns the statistics of the last read, write or erase operation, if any.
+    pub fn last_operation_summary(&self) -> Option<&OperationSummary> {
+        self.last_summary.as_ref()
+    }
+
+    /// Checks that a DA is actually running, by sending a cheap command under a short timeout.
+    /// Useful after forcing the connection type to DA from persisted state: if the device
+    /// rebooted in the meantime, this fails fast instead of timing out on the first real command.
+    pub async fn validate_da_session(&mut self) -> bool {
+        let Some(protocol) = self.protocol.as_mut() else {
+            return false;
+        };
+
+        match timeout(DA_PROBE_TIMEOUT, protocol.keepalive()).await {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                debug!("DA probe failed: {}", e);
+                false
+            }
+            Err(_) => {
+                debug!("DA probe timed out");
+                false
+            }
+        }
+    }
+
+    /// Returns whether the DA extensions are running in this session.
+    pub fn extensions_active(&self) -> bool {
+        self.protocol.as_ref().is_some_and(|protocol| protocol.exts_active())
+    }
+
+    /// Picks up the DA extensions booted by a previous session, after [`Device::reinit`],
+    /// so they don't get uploaded a second time. They're only trusted once they answered
+    /// an ack, as the DA might have been restarted without them in the meantime.
+    ///
+    /// Returns whether the extensions answered.
+    pub async fn resume_extensions(&mut self) -> Result<bool> {
+        let protocol = self.da_protocol()?;
+
+        let active = match timeout(DA_PROBE_TIMEOUT, protocol.probe_extensions()).await {
+            Ok(Ok(active)) => active,
+            Ok(Err(e)) => {
+                debug!("DA extensions probe failed: {}", e);
+                false
+            }
+            Err(_) => {
+                debug!("DA extensions probe timed out");
+                false
+            }
+        };
+
+        protocol.set_exts_state(active);
+        Ok(active)
+    }
+
+    /// Internal helper returning the DA entry for `hw_code`, if there's a DA file with one.
+    fn da_entry(&self, hw_code: u16) -> Option<DA> {
+        let da_file = DAFile::parse_da(self.da_data.as_deref()?).ok()?;
+        da_file.get_da_from_hw_code(hw_code)
+    }
+
+    /// Internal helper to warn about an inconsistent DA entry for `hw_code` before touching
+    /// the device, since repacked DAs tend to fail in confusing ways halfway through bring-up.
+    /// The entries of other chips are left alone, they won't be used.
+    fn warn_da_issues(&self, hw_code: u16) {
+        let Some(da) = self.da_entry(hw_code) else {
+            return;
+        };
+
+        for issue in &da.issues {
+            warn!("DA entry for 0x{:04X} looks inconsistent: {}", da.hw_code, issue);
+        }
+    }
+
+    /// Internal helper to check that the DA for `hw_code` loads DA1 and DA2 into memory
+    /// the chipset has. A bogus address otherwise hard-faults the device on jump_da,
+    /// without any diagnostics. Chipsets without a known memory map are skipped.
+    fn check_da_load_addresses(&self, hw_code: u16) -> Result<()> {
+        let Some(chipset) = chipset(hw_code) else {
+            debug!("No memory map known for 0x{:04X}, skipping DA load address checks", hw_code);
+            return Ok(());
+        };
+        let Some(da) = self.da_entry(hw_code) else {
+            return Ok(());
+        };
+
+        let issues = da.check_load_addresses(chipset.memory);
+        if issues.is_empty() {
+            return Ok(());
+        }
+
+        let expected = chipset.memory.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(", ");
+        for issue in &issues {
+            warn!("DA entry for 0x{:04X}: {} (expected {})", da.hw_code, issue, expected);
+        }
+
+        if self.strict_da {
+            return Err(Error::penumbra(format!(
+                "DA entry for 0x{:04X} loads outside of the chipset memory, refusing to upload it",
+                da.hw_code
+            )));
+        }
+        Ok(())
+    }
+
+    /// Makes reads and writes skip ranges that keep failing, instead of aborting.
+    ///
+    /// Meant for dying storage, where the same blocks fail consistently. Transfers are split
+    /// in chunks and failing ones are retried in smaller pieces. Pieces that still fail are
+    /// skipped (zero-filled for reads) and listed in the operation summary, with their
+    /// absolute offsets. Connection errors still abort, as nothing can be read past them.
+    pub fn set_skip_bad_blocks(&mut self, skip: bool) {
+        self.skip_bad_blocks = skip;
+    }
+
+    /// Sets the DA file after [`Device::init`], like one picked for the hardware code it read.
+    /// It's used when entering DA mode, and ignored if the DA protocol is already set up.
+    pub async fn set_da_data(&mut self, data: Vec<u8>) -> Result<()> {
+        self.da_data = Some(data);
+        let hw_code = self.dev_info.hw_code().await;
+        self.warn_da_issues(hw_code);
+        self.check_da_load_addresses(hw_code)
+    }
+
+    /// Makes the session read-only: all writes, erases and seccfg changes are refused.
+    /// Useful when the device is exposed to other tools, which should never modify it.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Internal helper to refuse destructive operations on a read-only session
+    /// or on an unconfirmed session change.
+    fn ensure_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(Error::penumbra("Session is read-only, refusing to modify the device."));
+        }
+
+        if self.session_changed {
+            return Err(Error::penumbra(
+                "DA session changed since the device was last seen, refusing to continue. \
+                 Confirm the new session first.",
+            ));
+        }
+        Ok(())
+    }
+
     /// Gets a mutable reference to the active connection.
     /// If the device is in DA mode, it retrieves the connection from the DA protocol.
     pub fn get_connection(&mut self) -> Result<&mut Connection> {
@@ -319,11 +1032,35 @@ impl Device {
         self.protocol.as_deref_mut()
     }
 
-    /// Retrieves the list of partitions from the device.
+    /// Returns the DA patched by the exploits of this session, as a DA file holding only
+    /// the entry of this device. Pass it to [`DeviceBuilder::with_prepatched_da`] in later
+    /// sessions to skip patching.
+    ///
+    /// Returns `None` if no DA was uploaded, or if no exploit changed it.
+    pub fn export_patched_da(&self) -> Option<Vec<u8>> {
+        let da = self.protocol.as_ref()?.get_da();
+        let da_file = DAFile::parse_da(self.da_data.as_deref()?).ok()?;
+
+        let original = da_file.das.iter().find(|entry| entry.same_layout(da))?;
+        let unchanged = original.regions.iter().zip(&da.regions).all(|(a, b)| a.data == b.data);
+        if unchanged {
+            return None;
+        }
+
+        match da_file.export(std::slice::from_ref(da)) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                warn!("Could not export the patched DA: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Retrieves the list of partitions from the device, entering DA mode if needed.
     /// If partitions have already been fetched, returns the cached list.
-    /// Otherwise, queries the DA protocol for partition information and caches the result.
     ///
-    /// Returns an empty list if no DA protocol is available.
+    /// The list always starts with the `preloader` and `preloader_backup` pseudo-partitions,
+    /// sized from the detected storage, followed by the GPT partitions.
     ///
     /// # Examples
     /// ```rust
@@ -335,71 +1072,417 @@ impl Device {
     ///     DeviceBuilder::default().with_mtk_port(mtk_port).with_da_data(da_data).build()?;
     ///
     /// device.init().await?;
-    /// device.enter_da_mode().await?;
-    /// let partitions = device.get_partitions().await;
+    /// let partitions = device.get_partitions().await?;
     /// for part in &partitions {
     ///     println!("{}: size={}", part.name, part.size);
     /// }
     /// ```
-    pub async fn get_partitions(&mut self) -> Vec<Partition> {
+    pub async fn get_partitions(&mut self) -> Result<Vec<Partition>> {
         let cached = self.dev_info.partitions().await;
         if !cached.is_empty() {
-            return cached;
+            return Ok(cached);
         }
 
-        let protocol = match self.get_protocol() {
-            Some(p) => p,
-            None => return Vec::new(),
-        };
-
-        info!("Retrieving partition information...");
-        let partitions = protocol.get_partitions().await;
-
-        self.dev_info.set_partitions(partitions.clone()).await;
-
-        partitions
+        self.refresh_partitions().await
     }
 
-    /// Reads data from a specified partition on the device.
-    /// This function assumes the partition to be part of the user section.
-    /// To read from other sections, use `read_offset` with appropriate address.
-    pub async fn read_partition(
+    /// Returns the partition holding `role`, whichever of its names the table uses.
+    /// See [`PartitionRole::candidates`] for the names looked up.
+    pub async fn find_partition_by_role(
         &mut self,
-        name: &str,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
-        writer: &mut (dyn AsyncWrite + Unpin + Send),
-    ) -> Result<()> {
+        role: PartitionRole,
+    ) -> Result<Option<Partition>> {
+        let partitions = self.get_partitions().await?;
+        Ok(role.find(&partitions).cloned())
+    }
+
+    /// Returns the geometry and identification of the device storage, such as its
+    /// capacity and CID. This requires DA mode, where the storage is detected.
+    pub async fn get_storage_info(&mut self) -> Result<StorageInfo> {
         self.ensure_da_mode().await?;
 
-        let part = self
-            .dev_info
-            .get_partition(name)
+        self.dev_info
+            .storage()
             .await
-            .ok_or_else(|| Error::penumbra(format!("Partition '{}' not found", name)))?;
+            .map(|storage| storage.info())
+            .ok_or_else(|| Error::penumbra("Storage not detected"))
+    }
+
+    /// Reads the eFuses through the DA. This requires DA mode.
+    ///
+    /// DAs that don't implement the command return [`Error::Unsupported`],
+    /// and the session stays usable.
+    pub async fn read_efuse(&mut self) -> Result<EfuseMap> {
+        self.ensure_da_mode().await?;
 
-        let protocol = self.protocol.as_mut().unwrap();
-        protocol.read_flash(part.address, part.size, part.kind, progress, writer).await
+        let protocol = self.da_protocol()?;
+        let data = protocol.read_efuse().await?;
+        Ok(EfuseMap::parse(&data))
     }
 
-    /// Writes data to a specified partition on the device.
-    /// This function assumes the partition to be part of the user section.
-    /// To write to other sections, use `write_offset` with appropriate address.
-    pub async fn write_partition(
-        &mut self,
-        name: &str,
-        reader: &mut (dyn AsyncRead + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
-    ) -> Result<()> {
+    /// Sends an XML DA command Penumbra doesn't wrap, returning the DA answer as text:
+    /// the file it uploaded while running the command, or else its CMD:END message.
+    /// This requires DA mode with an XML DA.
+    ///
+    /// **Warning:** the command is sent as is. Nothing checks what it does, and vendor
+    /// commands can erase, lock or brick the device. Commands that expect a file from the
+    /// host or report progress aren't handled, and leave the DA in the middle of them.
+    pub async fn send_raw_xml(&mut self, cmd: &RawXmlCommand) -> Result<String> {
         self.ensure_da_mode().await?;
 
-        let part = self
-            .dev_info
+        let protocol = self.da_protocol()?;
+        protocol.send_raw_xml(cmd).await
+    }
+
+    /// Reads the partition table from the device again, replacing the cached one.
+    /// Use it after anything that changes the partition table, like flashing a new GPT.
+    pub async fn refresh_partitions(&mut self) -> Result<Vec<Partition>> {
+        self.ensure_da_mode().await?;
+        self.load_partitions().await
+    }
+
+    /// Queries the DA for the partition table and caches it.
+    /// Returns an empty list if no DA protocol is available, and an error if neither
+    /// the primary nor the backup GPT is valid.
+    async fn load_partitions(&mut self) -> Result<Vec<Partition>> {
+        let protocol = match self.get_protocol() {
+            Some(p) => p,
+            None => return Ok(Vec::new()),
+        };
+
+        info!("Retrieving partition information...");
+        let mut partitions = protocol.get_partitions().await?;
+
+        // Only the user region is described by the GPT, the rest comes from the storage info
+        if let Some(storage) = self.dev_info.storage().await {
+            let user_part = storage.get_user_part().as_u32();
+            for part in partitions.iter_mut() {
+                let region_size =
+                    if part.kind.as_u32() == user_part { storage.get_user_size() } else { 0 };
+                part.issue = check_geometry(part, region_size);
+            }
+        }
+
+        let flagged: Vec<String> = partitions
+            .iter()
+            .filter_map(|p| p.issue.map(|issue| format!("{} ({})", p.name, issue)))
+            .collect();
+        if !flagged.is_empty() {
+            warn!("Partitions with invalid geometry, not read by default: {}", flagged.join(", "));
+        }
+
+        self.dev_info.set_partitions(partitions.clone()).await;
+
+        // The primary GPT was just read along with LBA0, check the MBR while at it
+        let storage_type = self.dev_info.storage().await.map_or(StorageType::Unknown, |s| s.kind());
+        if let Some(pgpt) = self.dev_info.raw_pgpt().await
+            && let Ok(gpt) = Gpt::parse(&pgpt, storage_type)
+        {
+            let status = check_protective_mbr(&pgpt, gpt.total_sectors());
+            if !status.is_healthy() {
+                warn!("Primary GPT is valid, but found a {}", status);
+            }
+        }
+
+        Ok(partitions)
+    }
+
+    /// Checks the protective MBR in front of the primary GPT.
+    ///
+    /// A valid GPT with a broken protective MBR boots on most devices, but some lk
+    /// versions refuse it. Use [`Device::fix_mbr`] to regenerate it.
+    pub async fn check_mbr(&mut self) -> Result<MbrStatus> {
+        self.ensure_da_mode().await?;
+        let (pgpt, gpt) = self.cached_pgpt().await?;
+        Ok(check_protective_mbr(&pgpt, gpt.total_sectors()))
+    }
+
+    /// Writes a correct protective MBR, sized from the primary GPT.
+    /// The bootstrap code and disk signature are kept, and the GPT is left untouched.
+    pub async fn fix_mbr(&mut self) -> Result<()> {
+        self.ensure_da_mode().await?;
+        self.ensure_writable()?;
+
+        let (mut pgpt, gpt) = self.cached_pgpt().await?;
+        let mbr = protective_mbr(&pgpt, gpt.total_sectors());
+
+        let part = self
+            .dev_info
+            .get_partition("PGPT")
+            .await
+            .ok_or_else(|| Error::penumbra("Partition 'PGPT' not found"))?;
+
+        let mut progress = |_| {};
+        self.write_offset(part.address, MBR_SIZE, &mut &mbr[..], part.kind, true, &mut progress)
+            .await?;
+
+        write_bytes(&mut pgpt, 0, &mbr)?;
+        self.dev_info.set_raw_pgpt(pgpt).await;
+
+        Ok(())
+    }
+
+    /// Returns the raw primary GPT region, from LBA0, along with its parsed GPT.
+    async fn cached_pgpt(&mut self) -> Result<(Vec<u8>, Gpt)> {
+        let mut pgpt = Vec::new();
+        self.dump_gpt(&mut pgpt, GptType::Pgpt).await?;
+
+        let storage_type = self.dev_info.storage().await.map_or(StorageType::Unknown, |s| s.kind());
+        let gpt = Gpt::parse(&pgpt, storage_type)?;
+
+        Ok((pgpt, gpt))
+    }
+
+    /// Writes the raw primary or backup GPT to the provided writer.
+    /// The blob cached while reading the partition table is used when available,
+    /// otherwise the GPT is read from the device and cached for later use.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use penumbra::core::storage::GptType;
+    /// use penumbra::{DeviceBuilder, find_mtk_port};
+    ///
+    /// let mtk_port = find_mtk_port().await.ok_or("No MTK port found")?;
+    /// let da_data = std::fs::read("path/to/da/file").expect("Failed to read DA file");
+    /// let mut device =
+    ///     DeviceBuilder::default().with_mtk_port(mtk_port).with_da_data(da_data).build()?;
+    ///
+    /// device.init().await?;
+    /// let mut pgpt = Vec::new();
+    /// device.dump_gpt(&mut pgpt, GptType::Pgpt).await?;
+    /// ```
+    pub async fn dump_gpt(
+        &mut self,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+        which: GptType,
+    ) -> Result<()> {
+        self.ensure_da_mode().await?;
+
+        let cached = match which {
+            GptType::Pgpt => self.dev_info.raw_pgpt().await,
+            GptType::Sgpt => self.dev_info.raw_sgpt().await,
+        };
+
+        let data = match cached {
+            Some(data) => data,
+            None => {
+                let name = match which {
+                    GptType::Pgpt => "PGPT",
+                    GptType::Sgpt => "SGPT",
+                };
+
+                let part =
+                    self.dev_info.get_partition(name).await.ok_or_else(|| {
+                        Error::penumbra(format!("Partition '{}' not found", name))
+                    })?;
+
+                let mut data = Vec::with_capacity(part.size);
+                let mut progress = |_| {};
+                let protocol = self.da_protocol()?;
+                protocol
+                    .read_flash(part.address, part.size, part.kind, &mut progress, &mut data)
+                    .await?;
+
+                match which {
+                    GptType::Pgpt => self.dev_info.set_raw_pgpt(data.clone()).await,
+                    GptType::Sgpt => self.dev_info.set_raw_sgpt(data.clone()).await,
+                }
+
+                data
+            }
+        };
+
+        writer.write_all(&data).await?;
+        writer.flush().await?;
+
+        Ok(())
+    }
+
+    /// Reads data from a specified partition on the device.
+    /// This function assumes the partition to be part of the user section.
+    /// To read from other sections, use `read_offset` with appropriate address.
+    ///
+    /// The data is streamed to `writer` as it's read, so memory use stays flat whatever the
+    /// partition size. See [`Device::read_partition_to_vec`] for small partitions.
+    pub async fn read_partition(
+        &mut self,
+        name: &str,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<()> {
+        self.ensure_da_mode().await?;
+
+        let part = self
+            .dev_info
+            .get_partition(name)
+            .await
+            .ok_or_else(|| Error::penumbra(format!("Partition '{}' not found", name)))?;
+        ensure_readable(&part)?;
+
+        self.read_range(part.address, part.size, part.kind, progress, writer).await
+    }
+
+    /// Reads a whole partition into memory, for small partitions that get parsed right away.
+    /// Partitions larger than `max_size` are refused, stream those with
+    /// [`Device::read_partition`] instead.
+    pub async fn read_partition_to_vec(
+        &mut self,
+        name: &str,
+        max_size: usize,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<Vec<u8>> {
+        self.ensure_da_mode().await?;
+
+        let part = self
+            .dev_info
+            .get_partition(name)
+            .await
+            .ok_or_else(|| Error::penumbra(format!("Partition '{}' not found", name)))?;
+        ensure_readable(&part)?;
+
+        if part.size > max_size {
+            return Err(Error::penumbra(format!(
+                "Partition '{}' is 0x{:X} bytes, over the 0x{:X} bytes limit. Stream it instead",
+                name, part.size, max_size
+            )));
+        }
+
+        let mut data = Vec::with_capacity(part.size);
+        self.read_range(part.address, part.size, part.kind, progress, &mut data).await?;
+        Ok(data)
+    }
+
+    /// Reads a partition starting at `start`, to resume a read that was interrupted.
+    /// Only the data from `start` to the end of the partition is written to `writer`,
+    /// and `progress` counts from `start` as well.
+    ///
+    /// `start` must be a multiple of the storage block size, as DAs read whole blocks.
+    pub async fn read_partition_from(
+        &mut self,
+        name: &str,
+        start: u64,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<()> {
+        self.ensure_da_mode().await?;
+
+        let part = self
+            .dev_info
+            .get_partition(name)
+            .await
+            .ok_or_else(|| Error::penumbra(format!("Partition '{}' not found", name)))?;
+        ensure_readable(&part)?;
+
+        if start > part.size as u64 {
+            return Err(Error::penumbra(format!(
+                "Cannot resume '{}' at 0x{:X}, past its end (0x{:X})",
+                name, start, part.size
+            )));
+        }
+
+        let block_size = self.dev_info.storage().await.map_or(512, |s| s.block_size() as u64);
+        if !start.is_multiple_of(block_size) {
+            return Err(Error::penumbra(format!(
+                "Cannot resume '{}' at 0x{:X}, not a multiple of the block size (0x{:X})",
+                name, start, block_size
+            )));
+        }
+
+        let remaining = part.size - start as usize;
+        if remaining == 0 {
+            return Ok(());
+        }
+
+        self.read_range(part.address + start, remaining, part.kind, progress, writer).await
+    }
+
+    /// Writes data to a specified partition on the device.
+    /// This function assumes the partition to be part of the user section.
+    /// To write to other sections, use `write_offset` with appropriate address.
+    ///
+    /// Android sparse images are detected and expanded on the fly,
+    /// with their DONT_CARE chunks left untouched on the device.
+    ///
+    /// A raw input shorter than the partition fails with [`Error::InputTooShort`],
+    /// unless `pad_with_zeros` is set, in which case the rest of the partition is zero-filled.
+    pub async fn write_partition(
+        &mut self,
+        name: &str,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        pad_with_zeros: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        self.ensure_da_mode().await?;
+        self.ensure_writable()?;
+
+        let part = self
+            .dev_info
             .get_partition(name)
             .await
             .ok_or_else(|| Error::penumbra(format!("Partition '{}' not found", name)))?;
 
-        let protocol = self.protocol.as_mut().unwrap();
-        protocol.write_flash(part.address, part.size, reader, part.kind, progress).await
+        match sparse::detect(reader).await? {
+            SparseInput::Raw(mut reader) => {
+                let (address, size, kind) = (part.address, part.size, part.kind);
+                self.write_range(address, size, &mut reader, kind, pad_with_zeros, progress).await
+            }
+            SparseInput::Sparse(mut sparse) => {
+                self.write_sparse(&part, &mut sparse, progress).await
+            }
+        }
+    }
+
+    /// Like [`Device::write_partition`], but reads the written range back afterwards
+    /// and compares its SHA-256 with the one of the data written.
+    ///
+    /// Both passes are streamed, and only the length actually read from `reader` is
+    /// verified, so inputs smaller than the partition are fine with `pad_with_zeros`,
+    /// the zero-filled rest isn't read back. `progress` reports
+    /// the [`OperationKind::Write`] phase then the [`OperationKind::Verify`] one, each from 0.
+    /// A mismatch returns [`Error::VerificationFailed`].
+    pub async fn write_partition_verified(
+        &mut self,
+        name: &str,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        pad_with_zeros: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        self.ensure_da_mode().await?;
+        self.ensure_writable()?;
+
+        let part = self
+            .dev_info
+            .get_partition(name)
+            .await
+            .ok_or_else(|| Error::penumbra(format!("Partition '{}' not found", name)))?;
+
+        // DONT_CARE chunks are written as zeros here, or the read back would differ
+        match sparse::detect(reader).await? {
+            SparseInput::Raw(mut reader) => {
+                let (address, size, kind) = (part.address, part.size, part.kind);
+                self.write_verified_range(
+                    address,
+                    size,
+                    &mut reader,
+                    kind,
+                    pad_with_zeros,
+                    progress,
+                )
+                .await
+            }
+            SparseInput::Sparse(mut sparse) => {
+                let size = sparse_size(&part, &sparse)?;
+                self.write_verified_range(
+                    part.address,
+                    size,
+                    &mut sparse,
+                    part.kind,
+                    false,
+                    progress,
+                )
+                .await
+            }
+        }
     }
 
     /// Erases a specified partition on the device.
@@ -408,7 +1491,7 @@ impl Device {
     ///
     /// # Examples
     /// ```rust
-    /// use penumbra::{DeviceBuilder, find_mtk_port};
+    /// use penumbra::{DeviceBuilder, ProgressEvent, find_mtk_port};
     ///
     /// let mtk_port = find_mtk_port().await.ok_or("No MTK port found")?;
     /// let da_data = std::fs::read("path/to/da/file").expect("Failed to read DA file");
@@ -416,15 +1499,16 @@ impl Device {
     ///     DeviceBuilder::default().with_mtk_port(mtk_port).with_da_data(da_data).build()?;
     ///
     /// device.init().await?;
-    /// let mut progress = |_erased: usize, _total: usize| {};
+    /// let mut progress = |_event: ProgressEvent| {};
     /// device.erase_partition("userdata", &mut progress).await?;
     /// ```
     pub async fn erase_partition(
         &mut self,
         partition: &str,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         self.ensure_da_mode().await?;
+        self.ensure_writable()?;
 
         let part = self
             .dev_info
@@ -432,93 +1516,586 @@ impl Device {
             .await
             .ok_or_else(|| Error::penumbra(format!("Partition '{}' not found", partition)))?;
 
-        let protocol = self.protocol.as_mut().unwrap();
-        protocol.erase_flash(part.address, part.size, part.kind, progress).await
+        let mut tracker = SummaryTracker::new(OperationKind::Erase, self.connection_retries());
+        let protocol = self.da_protocol()?;
+        let result = protocol
+            .erase_flash(part.address, part.size, part.kind, &mut tracker.wrap(progress))
+            .await;
+        self.last_summary = Some(tracker.finish(result.is_ok(), None, self.connection_retries()));
+        result
+    }
+
+    /// Reads data from a specified offset and size on the device.
+    /// This allows reading from arbitrary locations, not limited to named partitions.
+    /// To specify the section (e.g., user, pl_part1, pl_part2), provide the appropriate
+    /// `PartitionKind`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// // Let's assume we want to read preloader
+    /// use penumbra::{DeviceBuilder, PartitionKind, ProgressEvent, find_mtk_port};
+    /// use tokio::fs::File;
+    /// use tokio::io::BufWriter;
+    ///
+    /// let mtk_port = find_mtk_port().await.ok_or("No MTK port found")?;
+    /// let mut device = DeviceBuilder::default().with_mtk_port(mtk_port).build()?;
+    ///
+    /// device.init().await?;
+    ///
+    /// let mut writer = BufWriter::new(File::create("preloader.bin").await?);
+    /// let mut progress = |_event: ProgressEvent| {};
+    /// device
+    ///     .read_offset(
+    ///         0x0,
+    ///         0x40000,
+    ///         PartitionKind::Emmc(EmmcPartition::Boot1),
+    ///         &mut progress,
+    ///         &mut writer,
+    ///     )
+    ///     .await?;
+    /// ```
+    pub async fn read_offset(
+        &mut self,
+        address: u64,
+        size: usize,
+        section: PartitionKind,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<()> {
+        self.ensure_da_mode().await?;
+        self.read_range(address, size, section, progress, writer).await
+    }
+
+    /// Writes data to a specified offset and size on the device.
+    /// This allows writing to arbitrary locations, not limited to named partitions.
+    /// To specify the section (e.g., user, pl_part1, pl_part2), provide the appropriate
+    /// `PartitionKind`.
+    ///
+    /// Ranges running past the end of the section are refused, as are writes over the
+    /// protective MBR and primary GPT at the start of the user area unless `allow_gpt` is set.
+    ///
+    /// Preloaders are better written with [`Device::write_preloader`], which keeps
+    /// the boot header and checks the image.
+    ///
+    /// # Examples
+    /// ```rust
+    /// // Let's assume we want to write to preloader
+    /// use penumbra::{DeviceBuilder, PartitionKind, ProgressEvent, find_mtk_port};
+    ///
+    /// let mtk_port = find_mtk_port().await.ok_or("No MTK port found")?;
+    /// let mut device = DeviceBuilder::default().with_mtk_port(mtk_port).build()?;
+    ///
+    /// device.init().await?;
+    ///
+    /// let preloader_data = std::fs::read("path/to/preloader_penangf.bin").expect("Failed to read preloader");
+    /// let mut progress = |_event: ProgressEvent| {};
+    /// device
+    ///     .write_offset(
+    ///         0x1000, // Actual preloader offset is 0x0, but we skip the header to ensure correct writing
+    ///         preloader_data.len(),
+    ///         &preloader_data,
+    ///         PartitionKind::Emmc(EmmcPartition::Boot1),
+    ///         false,
+    ///         &mut progress,
+    ///     )
+    ///     .await?;
+    /// ```
+    pub async fn write_offset(
+        &mut self,
+        address: u64,
+        size: usize,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        section: PartitionKind,
+        allow_gpt: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        self.ensure_da_mode().await?;
+        self.ensure_writable()?;
+        self.check_range(address, size, section, allow_gpt).await?;
+        self.write_range(address, size, reader, section, false, progress).await
+    }
+
+    /// Like [`Device::write_offset`], but reads the data back and compares it afterwards.
+    /// See [`Device::write_partition_verified`].
+    pub async fn write_offset_verified(
+        &mut self,
+        address: u64,
+        size: usize,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        section: PartitionKind,
+        allow_gpt: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        self.ensure_da_mode().await?;
+        self.ensure_writable()?;
+        self.check_range(address, size, section, allow_gpt).await?;
+        self.write_verified_range(address, size, reader, section, false, progress).await
+    }
+
+    /// Refuses ranges running past the end of their section, and writes or erases of the
+    /// protective MBR and primary GPT unless `allow_gpt` is set. Nothing can be checked until the
+    /// storage is detected.
+    async fn check_range(
+        &mut self,
+        address: u64,
+        size: usize,
+        section: PartitionKind,
+        allow_gpt: bool,
+    ) -> Result<()> {
+        let Some(storage) = self.dev_info.storage().await else {
+            return Ok(());
+        };
+        let info = storage.info();
+
+        info.check_range(address, size as u64, section)?;
+        if !allow_gpt && info.overlaps_gpt(address, size as u64, section) {
+            return Err(Error::penumbra(format!(
+                "0x{:X} bytes at 0x{:X} cover the primary GPT (first 0x{:X} bytes of {}), \
+                 refusing to change them without allow_gpt",
+                size,
+                address,
+                info.gpt_size(),
+                section.as_str()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reads a range, recording the summary of the operation.
+    async fn read_range(
+        &mut self,
+        address: u64,
+        size: usize,
+        section: PartitionKind,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<()> {
+        let mut tracker = SummaryTracker::new(OperationKind::Read, self.connection_retries());
+        let mut writer = HashingWriter::new(writer);
+        let mut log = SkipLog::default();
+        let result = if self.skip_bad_blocks {
+            self.read_skipping(
+                address,
+                size,
+                section,
+                &mut tracker.wrap(progress),
+                &mut writer,
+                &mut log,
+            )
+            .await
+        } else {
+            let protocol = self.da_protocol()?;
+            protocol
+                .read_flash(address, size, section, &mut tracker.wrap(progress), &mut writer)
+                .await
+        };
+        let hash = writer.finalize();
+        self.last_summary =
+            Some(log.apply(tracker.finish(result.is_ok(), Some(hash), self.connection_retries())));
+        result
+    }
+
+    /// Writes a range, recording the summary of the operation.
+    async fn write_range(
+        &mut self,
+        address: u64,
+        size: usize,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        section: PartitionKind,
+        pad_with_zeros: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        let mut tracker = SummaryTracker::new(OperationKind::Write, self.connection_retries());
+        let mut log = SkipLog::default();
+        let result = self
+            .write_untracked(
+                address,
+                size,
+                reader,
+                section,
+                pad_with_zeros,
+                &mut tracker.wrap(progress),
+                &mut log,
+            )
+            .await;
+        self.last_summary =
+            Some(log.apply(tracker.finish(result.is_ok(), None, self.connection_retries())));
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn write_untracked(
+        &mut self,
+        address: u64,
+        size: usize,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        section: PartitionKind,
+        pad_with_zeros: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+        log: &mut SkipLog,
+    ) -> Result<()> {
+        if self.skip_bad_blocks {
+            self.write_skipping(address, size, reader, section, pad_with_zeros, progress, log).await
+        } else {
+            let protocol = self.da_protocol()?;
+            protocol.write_flash(address, size, reader, section, pad_with_zeros, progress).await
+        }
+    }
+
+    /// Writes a sparse image to a partition, one data chunk at a time.
+    /// DONT_CARE chunks are skipped rather than written as zeros.
+    async fn write_sparse<R: AsyncRead + Unpin + Send>(
+        &mut self,
+        part: &Partition,
+        sparse: &mut SparseReader<R>,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        let total = sparse_size(part, sparse)?;
+        info!("Writing sparse image to '{}', 0x{:X} bytes once expanded", part.name, total);
+
+        let mut tracker = SummaryTracker::new(OperationKind::Write, self.connection_retries());
+        let mut log = SkipLog::default();
+        let mut result = Ok(());
+        let mut progress = ProgressReporter::new(OperationKind::Write, progress);
+
+        loop {
+            let (offset, len) = match sparse.next_run().await {
+                Ok(Some(run)) => run,
+                Ok(None) => break,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            };
+
+            let mut run = (&mut *sparse).take(len);
+            let mut run_progress = |event: ProgressEvent| {
+                progress.report(offset as usize + event.bytes_done as usize, total)
+            };
+            result = self
+                .write_untracked(
+                    part.address + offset,
+                    len as usize,
+                    &mut run,
+                    part.kind,
+                    false,
+                    &mut tracker.wrap(&mut run_progress),
+                    &mut log,
+                )
+                .await;
+            if result.is_err() {
+                break;
+            }
+        }
+
+        if result.is_ok() {
+            progress.report(total, total);
+        }
+        self.last_summary =
+            Some(log.apply(tracker.finish(result.is_ok(), None, self.connection_retries())));
+        result
+    }
+
+    /// Writes a range, then reads back what was written and compares the hashes.
+    async fn write_verified_range(
+        &mut self,
+        address: u64,
+        size: usize,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        section: PartitionKind,
+        pad_with_zeros: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        // Padding is added below the hash, only the input itself is verified
+        let mut reader = HashingReader::new(reader);
+        self.write_range(address, size, &mut reader, section, pad_with_zeros, progress).await?;
+
+        let (expected, written) = reader.finalize();
+        self.verify_range(address, written as usize, section, &expected, progress).await
+    }
+
+    /// Reads `size` bytes back from `address`, hashing them on the fly,
+    /// and compares the result with `expected`.
+    async fn verify_range(
+        &mut self,
+        address: u64,
+        size: usize,
+        section: PartitionKind,
+        expected: &str,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        info!("Verifying 0x{:X} bytes at 0x{:X}...", size, address);
+
+        let mut sink = sink();
+        let mut writer = HashingWriter::new(&mut sink);
+        let mut verify_progress =
+            |event| progress(ProgressEvent { phase: OperationKind::Verify, ..event });
+        let protocol = self.da_protocol()?;
+        protocol.read_flash(address, size, section, &mut verify_progress, &mut writer).await?;
+
+        let actual = writer.finalize();
+        if actual != expected {
+            return Err(Error::VerificationFailed { expected: expected.to_string(), actual });
+        }
+
+        Ok(())
+    }
+
+    /// Reads a range in chunks, skipping the parts that keep failing.
+    /// Failing chunks are retried in smaller pieces, so that only the bad
+    /// pieces end up zero-filled and recorded in `log`.
+    async fn read_skipping(
+        &mut self,
+        address: u64,
+        size: usize,
+        section: PartitionKind,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+        log: &mut SkipLog,
+    ) -> Result<()> {
+        let mut done = 0;
+        let mut progress = ProgressReporter::new(OperationKind::Read, progress);
+        progress.report(0, size);
+
+        while done < size {
+            let len = SKIP_CHUNK_SIZE.min(size - done);
+            let chunk_address = address + done as u64;
+
+            let data = match self.read_piece(chunk_address, len, section, 1, log).await {
+                Ok(data) => data,
+                Err(e) if e.is_device_reported() => {
+                    debug!("Read of 0x{:X} failed ({}), retrying in pieces", chunk_address, e);
+                    let mut data = Vec::with_capacity(len);
+                    for offset in (0..len).step_by(SKIP_PIECE_SIZE) {
+                        let piece_len = SKIP_PIECE_SIZE.min(len - offset);
+                        let piece_address = chunk_address + offset as u64;
+                        match self
+                            .read_piece(piece_address, piece_len, section, SKIP_ATTEMPTS, log)
+                            .await
+                        {
+                            Ok(piece) => data.extend_from_slice(&piece),
+                            Err(e) if e.is_device_reported() => {
+                                log.skip(piece_address, piece_len, &e);
+                                data.resize(data.len() + piece_len, 0);
+                            }
+                            Err(e) => return Err(e),
+                        }
+                    }
+                    data
+                }
+                Err(e) => return Err(e),
+            };
+
+            writer.write_all(&data).await?;
+            done += len;
+            progress.report(done, size);
+        }
+
+        writer.flush().await?;
+        Ok(())
+    }
+
+    /// Reads a single piece, making up to `attempts` attempts.
+    async fn read_piece(
+        &mut self,
+        address: u64,
+        len: usize,
+        section: PartitionKind,
+        attempts: u32,
+        log: &mut SkipLog,
+    ) -> Result<Vec<u8>> {
+        let mut attempt = 1;
+        loop {
+            let mut data = Vec::with_capacity(len);
+            let mut progress = |_| {};
+            let protocol = self.da_protocol()?;
+            match protocol.read_flash(address, len, section, &mut progress, &mut data).await {
+                Ok(()) => return Ok(data),
+                Err(e) if attempt >= attempts || !e.is_device_reported() => return Err(e),
+                Err(e) => debug!("Read of 0x{:X} failed ({}), retrying", address, e),
+            }
+            attempt += 1;
+            log.retries += 1;
+        }
+    }
+
+    /// Writes a range in chunks, skipping the parts that keep failing.
+    /// Like [`Device::read_skipping`], failing chunks are retried in smaller pieces.
+    #[allow(clippy::too_many_arguments)]
+    async fn write_skipping(
+        &mut self,
+        address: u64,
+        size: usize,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        section: PartitionKind,
+        pad_with_zeros: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+        log: &mut SkipLog,
+    ) -> Result<()> {
+        let mut buffer = vec![0u8; SKIP_CHUNK_SIZE];
+        let mut done = 0;
+
+        // Same as the protocols do, zero-fill past the end of the input only when asked
+        let fill = if pad_with_zeros { size as u64 } else { 0 };
+        let mut reader = reader.chain(repeat(0).take(fill));
+        let mut progress = ProgressReporter::new(OperationKind::Write, progress);
+        progress.report(0, size);
+
+        while done < size {
+            let len = SKIP_CHUNK_SIZE.min(size - done);
+            let chunk_address = address + done as u64;
+            buffer.truncate(len);
+            let chunk = buffer.as_mut_slice();
+
+            fill_chunk(&mut reader, chunk, done, size).await?;
+
+            match self.write_piece(chunk_address, chunk, section, 1, log).await {
+                Ok(()) => {}
+                Err(e) if e.is_device_reported() => {
+                    debug!("Write of 0x{:X} failed ({}), retrying in pieces", chunk_address, e);
+                    for (i, piece) in chunk.chunks(SKIP_PIECE_SIZE).enumerate() {
+                        let piece_address = chunk_address + (i * SKIP_PIECE_SIZE) as u64;
+                        match self
+                            .write_piece(piece_address, piece, section, SKIP_ATTEMPTS, log)
+                            .await
+                        {
+                            Ok(()) => {}
+                            Err(e) if e.is_device_reported() => {
+                                log.skip(piece_address, piece.len(), &e)
+                            }
+                            Err(e) => return Err(e),
+                        }
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+
+            done += len;
+            progress.report(done, size);
+        }
+
+        Ok(())
+    }
+
+    /// Writes a single piece, making up to `attempts` attempts.
+    async fn write_piece(
+        &mut self,
+        address: u64,
+        data: &[u8],
+        section: PartitionKind,
+        attempts: u32,
+        log: &mut SkipLog,
+    ) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            let mut reader = data;
+            let mut progress = |_| {};
+            let protocol = self.da_protocol()?;
+            match protocol
+                .write_flash(address, data.len(), &mut reader, section, false, &mut progress)
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= attempts || !e.is_device_reported() => return Err(e),
+                Err(e) => debug!("Write of 0x{:X} failed ({}), retrying", address, e),
+            }
+            attempt += 1;
+            log.retries += 1;
+        }
     }
 
-    /// Reads data from a specified offset and size on the device.
-    /// This allows reading from arbitrary locations, not limited to named partitions.
+    /// Erases data at a specified offset and size on the device.
+    /// This allows erasing arbitrary locations, not limited to named partitions.
     /// To specify the section (e.g., user, pl_part1, pl_part2), provide the appropriate
     /// `PartitionKind`.
     ///
+    /// Like [`Device::write_offset`], ranges running past the end of the section are
+    /// refused, as are erases of the protective MBR and primary GPT unless `allow_gpt` is set.
+    ///
     /// # Examples
     /// ```rust
-    /// // Let's assume we want to read preloader
-    /// use penumbra::{DeviceBuilder, PartitionKind, find_mtk_port};
+    /// use penumbra::{DeviceBuilder, PartitionKind, ProgressEvent, find_mtk_port};
     ///
     /// let mtk_port = find_mtk_port().await.ok_or("No MTK port found")?;
-    /// let mut device = DeviceBuilder::default().with_mtk_port(mtk_port).build()?;
+    /// let da_data = std::fs::read("path/to/da/file").expect("Failed to read DA file");
+    /// let mut device =
+    ///     DeviceBuilder::default().with_mtk_port(mtk_port).with_da_data(da_data).build()?;
     ///
     /// device.init().await?;
-    ///
-    /// let mut progress = |_read: usize, _total: usize| {};
-    /// let preloader_data = device
-    ///     .read_offset(0x0, 0x40000, PartitionKind::Emmc(EmmcPartition::Boot1), &mut progress)
+    /// let mut progress = |_event: ProgressEvent| {};
+    /// device
+    ///     .erase_offset(0x0, 0x40000, PartitionKind::Emmc(EmmcPartition::Boot1), false, &mut progress)
     ///     .await?;
     /// ```
-    pub async fn read_offset(
+    pub async fn erase_offset(
         &mut self,
         address: u64,
         size: usize,
         section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
-        writer: &mut (dyn AsyncWrite + Unpin + Send),
+        allow_gpt: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         self.ensure_da_mode().await?;
-
-        let protocol = self.protocol.as_mut().unwrap();
-        protocol.read_flash(address, size, section, progress, writer).await
+        self.ensure_writable()?;
+        self.check_range(address, size, section, allow_gpt).await?;
+
+        let mut tracker = SummaryTracker::new(OperationKind::Erase, self.connection_retries());
+        let protocol = self.da_protocol()?;
+        let result =
+            protocol.erase_flash(address, size, section, &mut tracker.wrap(progress)).await;
+        self.last_summary = Some(tracker.finish(result.is_ok(), None, self.connection_retries()));
+        result
     }
 
-    /// Writes data to a specified offset and size on the device.
-    /// This allows writing to arbitrary locations, not limited to named partitions.
-    /// To specify the section (e.g., user, pl_part1, pl_part2), provide the appropriate
-    /// `PartitionKind`.
+    /// Reads the preloader from BOOT1, returning the bare preloader without the boot header,
+    /// as found in firmware packages. It can be written back with [`Device::write_preloader`].
     ///
     /// # Examples
     /// ```rust
-    /// // Let's assume we want to write to preloader
-    /// use penumbra::{DeviceBuilder, PartitionKind, find_mtk_port};
+    /// use penumbra::{DeviceBuilder, find_mtk_port};
     ///
     /// let mtk_port = find_mtk_port().await.ok_or("No MTK port found")?;
-    /// let mut device = DeviceBuilder::default().with_mtk_port(mtk_port).build()?;
+    /// let da_data = std::fs::read("path/to/da/file").expect("Failed to read DA file");
+    /// let mut device =
+    ///     DeviceBuilder::default().with_mtk_port(mtk_port).with_da_data(da_data).build()?;
     ///
     /// device.init().await?;
-    ///
-    /// let preloader_data = std::fs::read("path/to/preloader_penangf.bin").expect("Failed to read preloader");
-    /// let mut progress = |_written: usize, _total: usize| {};
-    /// device
-    ///     .write_offset(
-    ///         0x1000, // Actual preloader offset is 0x0, but we skip the header to ensure correct writing
-    ///         preloader_data.len(),
-    ///         &preloader_data,
-    ///         PartitionKind::Emmc(EmmcPartition::Boot1),
-    ///         &mut progress,
-    ///     )
-    ///     .await?;
+    /// let preloader = device.read_preloader().await?;
+    /// std::fs::write("preloader.bin", preloader)?;
     /// ```
-    pub async fn write_offset(
-        &mut self,
-        address: u64,
-        size: usize,
-        reader: &mut (dyn AsyncRead + Unpin + Send),
-        section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
-    ) -> Result<()> {
+    pub async fn read_preloader(&mut self) -> Result<Vec<u8>> {
         self.ensure_da_mode().await?;
 
-        let protocol = self.protocol.as_mut().unwrap();
-        protocol.write_flash(address, size, reader, section, progress).await
+        let part = self
+            .dev_info
+            .get_partition("preloader")
+            .await
+            .ok_or_else(|| Error::penumbra("Partition 'preloader' not found"))?;
+        // NAND keeps the preloader in the partition table, there are no boot partitions
+        if part.size == 0 {
+            return Err(Error::unsupported(
+                "This storage has no boot partitions, read the preloader partition instead",
+            ));
+        }
+
+        let image = self.read_region(&part, 0, part.size).await?;
+        Ok(extract_preloader(&image)?.to_vec())
     }
 
-    /// Erases data at a specified offset and size on the device.
-    /// This allows erasing arbitrary locations, not limited to named partitions.
-    /// To specify the section (e.g., user, pl_part1, pl_part2), provide the appropriate
-    /// `PartitionKind`.
+    /// Writes a preloader to BOOT1, and to BOOT2 too when `mirror` is set.
+    ///
+    /// Full boot images (with an `EMMC_BOOT`/`UFS_BOOT` header) are written from the start
+    /// of the partition, while bare preloaders take the place of the preloader in BOOT1,
+    /// after its boot header if it has one.
+    /// Each copy is read back and verified. The original contents are kept in memory, so if
+    /// any write or verification fails, every partition written so far is restored before
+    /// returning the error.
+    /// Without mirroring, BOOT2 keeps the old preloader, which the device falls back to
+    /// whenever BOOT1 is corrupt.
     ///
     /// # Examples
     /// ```rust
-    /// use penumbra::{DeviceBuilder, PartitionKind, find_mtk_port};
+    /// use penumbra::{DeviceBuilder, ProgressEvent, find_mtk_port};
     ///
     /// let mtk_port = find_mtk_port().await.ok_or("No MTK port found")?;
     /// let da_data = std::fs::read("path/to/da/file").expect("Failed to read DA file");
@@ -526,22 +2103,121 @@ impl Device {
     ///     DeviceBuilder::default().with_mtk_port(mtk_port).with_da_data(da_data).build()?;
     ///
     /// device.init().await?;
-    /// let mut progress = |_erased: usize, _total: usize| {};
-    /// device
-    ///     .erase_offset(0x0, 0x40000, PartitionKind::Emmc(EmmcPartition::Boot1), &mut progress)
-    ///     .await?;
+    /// let preloader = std::fs::read("preloader_penangf.bin").expect("Failed to read preloader");
+    /// let mut progress = |_event: ProgressEvent| {};
+    /// device.write_preloader(&preloader, true, &mut progress).await?;
     /// ```
-    pub async fn erase_offset(
+    pub async fn write_preloader(
         &mut self,
-        address: u64,
-        size: usize,
-        section: PartitionKind,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        data: &[u8],
+        mirror: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         self.ensure_da_mode().await?;
+        self.ensure_writable()?;
+
+        // Garbage is refused before touching the device
+        is_boot_image(data)?;
+        let mut targets = vec!["preloader"];
+        if mirror {
+            targets.push("preloader_backup");
+        }
+
+        let mut parts = Vec::with_capacity(targets.len());
+        for name in targets {
+            let part = self
+                .dev_info
+                .get_partition(name)
+                .await
+                .ok_or_else(|| Error::penumbra(format!("Partition '{}' not found", name)))?;
+            parts.push(part);
+        }
+
+        // Where the preloader starts differs between eMMC and UFS, BOOT1 tells
+        let mut current = Vec::new();
+        if let Some(boot1) = parts.first() {
+            current = self.read_region(boot1, 0, boot1.size).await?;
+        }
+        let offset = preloader_offset(data, &current)?;
+
+        // Keep what's there, so a failed write never leaves the device without a preloader
+        let mut originals = Vec::with_capacity(parts.len());
+        for part in &parts {
+            if offset + data.len() as u64 > part.size as u64 {
+                return Err(Error::penumbra(format!(
+                    "Preloader is too big for '{}' (0x{:X} bytes at 0x{:X}, partition is 0x{:X})",
+                    part.name,
+                    data.len(),
+                    offset,
+                    part.size
+                )));
+            }
+            originals.push(self.read_region(part, offset, data.len()).await?);
+        }
+
+        let mut tracker = SummaryTracker::new(OperationKind::Write, self.connection_retries());
+        let mut result = Ok(());
+        let mut attempted = 0;
+        for part in &parts {
+            info!("Writing preloader to {}...", part.name);
+            attempted += 1;
+            result = self.write_verified(part, offset, data, &mut tracker.wrap(progress)).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        self.last_summary = Some(tracker.finish(result.is_ok(), None, self.connection_retries()));
+
+        let Err(e) = result else {
+            return Ok(());
+        };
+
+        error!("Preloader write failed ({}), restoring the original contents...", e);
+        let mut noop = |_| {};
+        for (part, original) in parts.iter().zip(&originals).take(attempted) {
+            if let Err(restore_err) = self.write_verified(part, offset, original, &mut noop).await {
+                error!("Could not restore {}: {}", part.name, restore_err);
+                return Err(Error::penumbra(format!(
+                    "Preloader write failed ({}) and {} could not be restored ({}). \
+                     Do not reboot the device before writing a working preloader.",
+                    e, part.name, restore_err
+                )));
+            }
+        }
+
+        Err(e)
+    }
+
+    /// Reads `len` bytes at `offset` within a partition.
+    async fn read_region(&mut self, part: &Partition, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(len);
+        let mut progress = |_| {};
+        let protocol = self.da_protocol()?;
+        protocol
+            .read_flash(part.address + offset, len, part.kind, &mut progress, &mut data)
+            .await?;
+        Ok(data)
+    }
+
+    /// Writes `data` at `offset` within a partition, then reads it back to verify it.
+    async fn write_verified(
+        &mut self,
+        part: &Partition,
+        offset: u64,
+        data: &[u8],
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        let mut reader = data;
+        let protocol = self.da_protocol()?;
+        protocol
+            .write_flash(part.address + offset, data.len(), &mut reader, part.kind, false, progress)
+            .await?;
+
+        if self.read_region(part, offset, data.len()).await? != data {
+            return Err(Error::penumbra(format!("Verification of {} failed", part.name)));
+        }
 
-        let protocol = self.protocol.as_mut().unwrap();
-        protocol.erase_flash(address, size, section, progress).await
+        Ok(())
     }
 
     /// Like `write_partition`, but instead of writing using offsets and sizes from GPT,
@@ -552,6 +2228,9 @@ impl Device {
     /// without hitting security checks, since the data is first uploaded and then verified as a
     /// whole.
     ///
+    /// Android sparse images are detected and expanded on the fly, in which case `size`
+    /// is replaced by the expanded size. DONT_CARE chunks are written as zeros.
+    ///
     /// # Examples
     /// ```rust
     /// use penumbra::{DeviceBuilder, find_mtk_port};
@@ -568,12 +2247,74 @@ impl Device {
         partition: &str,
         size: usize,
         reader: &mut (dyn AsyncRead + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        self.ensure_da_mode().await?;
+        self.ensure_writable()?;
+
+        // The DA writes the partition as a whole, so DONT_CARE chunks can't be skipped here
+        match sparse::detect(reader).await? {
+            SparseInput::Raw(mut reader) => {
+                self.download_range(partition, size, &mut reader, progress).await
+            }
+            SparseInput::Sparse(mut sparse) => {
+                // Partitions missing from the GPT can't be checked, the DA will do it
+                let size = match self.dev_info.get_partition(partition).await {
+                    Some(part) => sparse_size(&part, &sparse)?,
+                    None => sparse.expanded_size() as usize,
+                };
+                self.download_range(partition, size, &mut sparse, progress).await
+            }
+        }
+    }
+
+    /// Downloads to a partition by name, recording the summary of the operation.
+    async fn download_range(
+        &mut self,
+        partition: &str,
+        size: usize,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        let mut tracker = SummaryTracker::new(OperationKind::Write, self.connection_retries());
+        let protocol = self.da_protocol()?;
+        let result = protocol
+            .download(partition.to_string(), size, reader, &mut tracker.wrap(progress))
+            .await;
+        self.last_summary = Some(tracker.finish(result.is_ok(), None, self.connection_retries()));
+        result
+    }
+
+    /// Like [`Device::download`], but reads the written data back afterwards and compares it.
+    /// The data is read back from the partition's range in the GPT, so the partition
+    /// must be part of it. See [`Device::write_partition_verified`].
+    pub async fn download_verified(
+        &mut self,
+        partition: &str,
+        size: usize,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         self.ensure_da_mode().await?;
+        self.ensure_writable()?;
+
+        let part = self
+            .dev_info
+            .get_partition(partition)
+            .await
+            .ok_or_else(|| Error::penumbra(format!("Partition '{}' not found", partition)))?;
 
-        let protocol = self.protocol.as_mut().unwrap();
-        protocol.download(partition.to_string(), size, reader, progress).await
+        let mut input = sparse::detect(reader).await?;
+        let (size, input): (usize, &mut (dyn AsyncRead + Unpin + Send)) = match &mut input {
+            SparseInput::Raw(reader) => (size, reader),
+            SparseInput::Sparse(sparse) => (sparse_size(&part, sparse)?, sparse),
+        };
+
+        let mut reader = HashingReader::new(input);
+        self.download_range(partition, size, &mut reader, progress).await?;
+
+        let (expected, written) = reader.finalize();
+        self.verify_range(part.address, written as usize, part.kind, &expected, progress).await
     }
 
     /// Like `read_partition`, but instead of reading using offsets and sizes from GPT,
@@ -583,7 +2324,7 @@ impl Device {
     ///
     /// # Examples
     /// ```rust
-    /// use penumbra::{DeviceBuilder, find_mtk_port};
+    /// use penumbra::{DeviceBuilder, ProgressEvent, find_mtk_port};
     /// use tokio::fs::File;
     /// use tokio::io::BufWriter;
     ///
@@ -596,26 +2337,33 @@ impl Device {
     /// // Readsback "logo" partition to "logo.bin"
     /// let file = File::create("logo.bin").await?;
     /// let mut writer = BufWriter::new(file);
-    /// let mut progress = |_written: usize, _total: usize| {};
+    /// let mut progress = |_event: ProgressEvent| {};
     /// device.upload("logo", &mut writer, &mut progress).await?;
     /// ```
     pub async fn upload(
         &mut self,
         partition: &str,
         writer: &mut (dyn AsyncWrite + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         self.ensure_da_mode().await?;
 
-        let protocol = self.protocol.as_mut().unwrap();
-        protocol.upload(partition.to_string(), writer, progress).await
+        let mut tracker = SummaryTracker::new(OperationKind::Read, self.connection_retries());
+        let protocol = self.da_protocol()?;
+        let mut writer = HashingWriter::new(writer);
+        let result =
+            protocol.upload(partition.to_string(), &mut writer, &mut tracker.wrap(progress)).await;
+        let hash = writer.finalize();
+        self.last_summary =
+            Some(tracker.finish(result.is_ok(), Some(hash), self.connection_retries()));
+        result
     }
 
     /// Formats a specified partition on the device
     ///
     /// # Examples
     /// ```rust
-    /// use penumbra::{DeviceBuilder, find_mtk_port};
+    /// use penumbra::{DeviceBuilder, ProgressEvent, find_mtk_port};
     ///
     /// let mtk_port = find_mtk_port().await.ok_or("No MTK port found")?;
     /// let da_data = std::fs::read("path/to/da/file").expect("Failed to read DA file");
@@ -623,18 +2371,22 @@ impl Device {
     ///     DeviceBuilder::default().with_mtk_port(mtk_port).with_da_data(da_data).build()?;
     ///
     /// device.init().await?;
-    /// let mut progress = |_erased: usize, _total: usize| {};
+    /// let mut progress = |_event: ProgressEvent| {};
     /// device.format("userdata", &mut progress).await?;
     /// ```
     pub async fn format(
         &mut self,
         partition: &str,
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         self.ensure_da_mode().await?;
+        self.ensure_writable()?;
 
-        let protocol = self.protocol.as_mut().unwrap();
-        protocol.format(partition.to_string(), progress).await
+        let mut tracker = SummaryTracker::new(OperationKind::Erase, self.connection_retries());
+        let protocol = self.da_protocol()?;
+        let result = protocol.format(partition.to_string(), &mut tracker.wrap(progress)).await;
+        self.last_summary = Some(tracker.finish(result.is_ok(), None, self.connection_retries()));
+        result
     }
 
     /// Shuts down the device
@@ -654,12 +2406,16 @@ impl Device {
     pub async fn shutdown(&mut self) -> Result<()> {
         self.ensure_da_mode().await?;
 
-        let protocol = self.protocol.as_mut().unwrap();
+        let protocol = self.da_protocol()?;
         protocol.shutdown().await
     }
 
     /// Reboots the device into the specified boot mode.
-    /// Supported boot modes include `Normal`, `HomeScreen`, `Fastboot`, `Test`, and `Meta`.
+    /// Supported boot modes include `Normal`, `HomeScreen`, `Fastboot`, `Test`, `Meta`,
+    /// `Recovery` and `Brom`, depending on the DA protocol.
+    ///
+    /// `Recovery` is requested by writing the bootloader message in `misc`, then rebooting
+    /// normally, so it works with any DA as long as writing is allowed.
     ///
     /// # Examples
     /// ```rust
@@ -676,14 +2432,263 @@ impl Device {
     pub async fn reboot(&mut self, bootmode: BootMode) -> Result<()> {
         self.ensure_da_mode().await?;
 
-        let protocol = self.protocol.as_mut().unwrap();
+        if bootmode == BootMode::Recovery {
+            self.ensure_writable()?;
+
+            let misc = self.dev_info.get_partition(MISC_PARTITION).await.ok_or_else(|| {
+                Error::penumbra("Partition 'misc' not found, can't request recovery")
+            })?;
+
+            let bcb = recovery_message();
+            let mut progress = |_| {};
+            self.write_range(
+                misc.address,
+                BCB_SIZE,
+                &mut &bcb[..],
+                misc.kind,
+                false,
+                &mut progress,
+            )
+            .await?;
+        }
+
+        let protocol = self.da_protocol()?;
+        protocol.reboot(bootmode).await
+    }
+
+    /// Sets the mode of the next boot, then reboots into it right away.
+    /// Only `Meta`, `AdbMeta`, `Fastboot` and `Normal` are accepted: XFlash DAs set META
+    /// through `SetMetaBootMode`, XML DAs through `SetBootMode`.
+    ///
+    /// DAs refusing the mode fail with [`Error::Unsupported`], leaving the device in DA mode.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use penumbra::{BootMode, DeviceBuilder, find_mtk_port};
+    ///
+    /// let mtk_port = find_mtk_port().await.ok_or("No MTK port found")?;
+    /// let da_data = std::fs::read("path/to/da/file").expect("Failed to read DA file");
+    /// let mut device =
+    ///     DeviceBuilder::default().with_mtk_port(mtk_port).with_da_data(da_data).build()?;
+    ///
+    /// device.init().await?;
+    /// device.set_boot_mode(BootMode::AdbMeta).await?;
+    /// ```
+    pub async fn set_boot_mode(&mut self, bootmode: BootMode) -> Result<()> {
+        match bootmode {
+            BootMode::Meta | BootMode::AdbMeta | BootMode::Fastboot | BootMode::Normal => {}
+            mode => {
+                return Err(Error::unsupported(format!("{:?} isn't a settable boot mode", mode)));
+            }
+        }
+
+        self.ensure_da_mode().await?;
+
+        let protocol = self.da_protocol()?;
         protocol.reboot(bootmode).await
     }
 
+    /// Returns how long the connection has been idle, or `None` if no connection is available.
+    pub fn idle_time(&mut self) -> Option<Duration> {
+        self.get_connection().ok().map(|conn| conn.idle_time())
+    }
+
+    /// Internal helper returning the transfers retried so far, for operation summaries.
+    fn connection_retries(&mut self) -> u64 {
+        self.connection_stats().map_or(0, |stats| stats.retries)
+    }
+
+    /// Returns the transient USB errors seen so far and how they were retried,
+    /// or `None` if no connection is available. See [`Connection::stats`].
+    pub fn connection_stats(&mut self) -> Option<ConnectionStats> {
+        self.get_connection().ok().map(|conn| conn.stats())
+    }
+
+    /// Sends a lightweight no-op command to the DA, resetting its inactivity watchdog.
+    /// Unlike other DA operations, this never enters DA mode on its own: if the device
+    /// is not already in DA mode, nothing is sent.
+    ///
+    /// For periodic keepalives during long host-side pauses, see [`KeepAlive`].
+    ///
+    /// [`KeepAlive`]: crate::keepalive::KeepAlive
+    pub async fn keepalive(&mut self) -> Result<()> {
+        if !self.connected || self.get_connection()?.connection_type != ConnectionType::Da {
+            return Ok(());
+        }
+
+        match self.protocol.as_mut() {
+            Some(protocol) => protocol.keepalive().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns whether the bootloader is locked, according to `seccfg`.
+    /// Returns `None` if the device has no `seccfg` partition, its format isn't known,
+    /// or it is an encrypted V3 one.
+    ///
+    /// Unlike `set_seccfg_lock_state`, this only reads the plain data and
+    /// doesn't need DA Extensions.
+    pub async fn is_bootloader_locked(&mut self) -> Result<Option<bool>> {
+        self.ensure_da_mode().await?;
+
+        let Some(seccfg) = self.dev_info.get_partition("seccfg").await else {
+            return Ok(None);
+        };
+
+        // V4 only needs its header, but V3 keeps the lock state further in
+        let size = SECCFG_MAX_SIZE.min(seccfg.size);
+        let mut data = Vec::with_capacity(size);
+        let mut progress = |_| {};
+        self.read_range(seccfg.address, size, seccfg.kind, &mut progress, &mut data).await?;
+
+        Ok(SecCfg::parse(&data).ok().and_then(|seccfg| seccfg.is_unlocked()).map(|u| !u))
+    }
+
+    /// Returns the vbmeta image verifying `partition`, if it is covered by an AVB chain
+    /// with verification enabled. The vbmeta of the same slot as `partition` is used,
+    /// falling back to the unsuffixed and then the `_a` one.
+    ///
+    /// Returns `None` if no vbmeta partition exists, it can't be parsed,
+    /// or it doesn't refer to `partition`.
+    pub async fn avb_vbmeta_for(&mut self, partition: &str) -> Result<Option<VbMeta>> {
+        self.ensure_da_mode().await?;
+
+        let suffix = &partition[strip_slot_suffix(partition).len()..];
+        let candidates =
+            [format!("vbmeta{}", suffix), "vbmeta".to_string(), "vbmeta_a".to_string()];
+
+        for name in candidates {
+            let Some(part) = self.dev_info.get_partition(&name).await else {
+                continue;
+            };
+
+            let mut data = Vec::with_capacity(part.size);
+            let mut progress = |_| {};
+            self.read_range(part.address, part.size, part.kind, &mut progress, &mut data).await?;
+
+            return Ok(match VbMeta::parse(&data) {
+                Ok(vbmeta) if vbmeta.verification_enabled() && vbmeta.covers(partition) => {
+                    Some(vbmeta)
+                }
+                Ok(_) => None,
+                Err(e) => {
+                    debug!("Could not parse {}: {}", name, e);
+                    None
+                }
+            });
+        }
+
+        Ok(None)
+    }
+
+    /// Reads the LP metadata of `super`, listing the dynamic partitions it holds.
+    /// The metadata of the slot matching `slot_suffix` (`_a`, `_b`) is used, the first one
+    /// for an empty suffix. Only the geometry and that metadata are read, not the whole super.
+    pub async fn read_lp_metadata(&mut self, slot_suffix: &str) -> Result<LpMetadata> {
+        self.ensure_da_mode().await?;
+
+        let sup = self
+            .dev_info
+            .get_partition(SUPER_PARTITION)
+            .await
+            .ok_or_else(|| Error::penumbra("Device has no super partition"))?;
+        ensure_readable(&sup)?;
+
+        let mut progress = |_| {};
+        let mut data = Vec::with_capacity(LP_METADATA_GEOMETRY_SIZE);
+        self.read_range(
+            sup.address + LpGeometry::OFFSET,
+            LP_METADATA_GEOMETRY_SIZE,
+            sup.kind,
+            &mut progress,
+            &mut data,
+        )
+        .await?;
+        let geometry = LpGeometry::parse(&data)?;
+
+        let offset = geometry.metadata_offset(metadata_slot(slot_suffix))?;
+        let size = geometry.metadata_max_size as usize;
+        if offset + size as u64 > sup.size as u64 {
+            return Err(Error::penumbra("LP metadata lies past the end of super"));
+        }
+
+        let mut data = Vec::with_capacity(size);
+        self.read_range(sup.address + offset, size, sup.kind, &mut progress, &mut data).await?;
+        LpMetadata::parse(&data)
+    }
+
+    /// Reads a dynamic partition out of `super`, following its extents.
+    /// The slot suffix of `name` selects the metadata to use, as in [`Device::read_lp_metadata`].
+    ///
+    /// Only extents stored in super itself are supported, not the ones of retrofitted devices
+    /// spanning other partitions.
+    pub async fn read_dynamic_partition(
+        &mut self,
+        name: &str,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<()> {
+        let suffix = &name[strip_slot_suffix(name).len()..];
+        let metadata = self.read_lp_metadata(suffix).await?;
+        let partition = metadata.get_partition(name).ok_or_else(|| {
+            Error::penumbra(format!("Dynamic partition '{}' not found in super", name))
+        })?;
+
+        let sup = self
+            .dev_info
+            .get_partition(SUPER_PARTITION)
+            .await
+            .ok_or_else(|| Error::penumbra("Device has no super partition"))?;
+
+        let total = partition.size() as usize;
+        let mut done = 0;
+        let mut progress = ProgressReporter::new(OperationKind::Read, progress);
+        for extent in &partition.extents {
+            let size = extent.size() as usize;
+            match extent.target {
+                LpExtentTarget::Linear { block_device: 0, physical_sector } => {
+                    let offset = physical_sector * LP_SECTOR_SIZE;
+                    if offset + size as u64 > sup.size as u64 {
+                        return Err(Error::penumbra(format!(
+                            "Extent of '{}' at 0x{:X} lies past the end of super",
+                            name, offset
+                        )));
+                    }
+
+                    let mut extent_progress = |event: ProgressEvent| {
+                        progress.report(done + event.bytes_done as usize, total)
+                    };
+                    self.read_range(
+                        sup.address + offset,
+                        size,
+                        sup.kind,
+                        &mut extent_progress,
+                        writer,
+                    )
+                    .await?;
+                }
+                LpExtentTarget::Linear { block_device, .. } => {
+                    return Err(Error::unsupported(format!(
+                        "'{}' has extents on block device {}, only super is supported",
+                        name, block_device
+                    )));
+                }
+                LpExtentTarget::Zero => {
+                    tokio::io::copy(&mut repeat(0).take(size as u64), writer).await?;
+                }
+            }
+
+            done += size;
+            progress.report(done, total);
+        }
+
+        Ok(())
+    }
+
     /// Sets the lock state in `seccfg` to either lock or unlock the bootloader.
     /// Returns the raw `seccfg` data on success, or `None` if the operation fails.
     ///
-    /// Only available when the `no_exploits` feature is **not** enabled.
+    /// Only available with the `seccfg` feature, enabled by default.
     /// Requires DA Extensions.
     ///
     /// # Examples
@@ -698,22 +2703,23 @@ impl Device {
     /// device.init().await?;
     /// let seccfg = device.set_seccfg_lock_state(LockFlag::Unlock).await;
     /// ```
-    #[cfg(not(feature = "no_exploits"))]
+    #[cfg(feature = "seccfg")]
     pub async fn set_seccfg_lock_state(&mut self, lock_state: LockFlag) -> Option<Vec<u8>> {
         // Ensure DA mode first; this will populate partitions and storage
         self.ensure_da_mode().await.ok()?;
-        let protocol = self.protocol.as_mut().unwrap();
+        self.ensure_writable().ok()?;
+        let protocol = self.da_protocol().ok()?;
         protocol.set_seccfg_lock_state(lock_state).await
     }
 
     /// Reads memory from the device at the given address and size.
     /// The data is written to the provided `writer` as it is read..
     ///
-    /// Only available when the `no_exploits` feature is **not** enabled.
+    /// Needs the DA extensions, which only boot on a patched DA.
     ///
     /// # Examples
     /// ```rust
-    /// use penumbra::{DeviceBuilder, find_mtk_port};
+    /// use penumbra::{DeviceBuilder, ProgressEvent, find_mtk_port};
     /// use tokio::fs::File;
     /// use tokio::io::BufWriter;
     ///
@@ -725,22 +2731,159 @@ impl Device {
     /// device.init().await?;
     /// let file = File::create("dump.bin").await?;
     /// let mut writer = BufWriter::new(file);
-    /// let mut progress = |_read: usize, _total: usize| {};
+    /// let mut progress = |_event: ProgressEvent| {};
     /// device.peek(0x0010_0000, 0x1000, &mut writer, &mut progress).await?;
     /// ```
-    #[cfg(not(feature = "no_exploits"))]
     pub async fn peek(
         &mut self,
         addr: u32,
         size: usize,
         writer: &mut (dyn AsyncWrite + Unpin + Send),
-        progress: &mut (dyn FnMut(usize, usize) + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
     ) -> Result<()> {
         self.ensure_da_mode().await?;
 
-        let protocol = self.protocol.as_mut().unwrap();
+        let protocol = self.da_protocol()?;
         protocol.peek(addr, size, writer, progress).await
     }
+
+    /// Sets the RPMB key, if given, and prepares the DA extensions for RPMB access.
+    /// Without a key, the one the extensions already hold is used.
+    ///
+    /// The key is only needed for writes, reads aren't authenticated.
+    pub async fn init_rpmb(&mut self, key: Option<&[u8; RPMB_KEY_SIZE]>) -> Result<()> {
+        self.ensure_da_mode().await?;
+
+        let protocol = self.da_protocol()?;
+        protocol.init_rpmb(key).await
+    }
+
+    /// Reads `blocks` RPMB frames of [`RPMB_FRAME_SIZE`] bytes, starting at frame `address`.
+    /// Needs the DA extensions, see [`Device::init_rpmb`].
+    pub async fn read_rpmb(
+        &mut self,
+        address: u16,
+        blocks: u16,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        check_rpmb_range(address, blocks)?;
+        self.ensure_da_mode().await?;
+
+        let protocol = self.da_protocol()?;
+        protocol.read_rpmb(address, blocks, writer, progress).await
+    }
+
+    /// Writes `size` bytes from `reader` to RPMB, starting at frame `address`.
+    /// `size` must be a whole number of [`RPMB_FRAME_SIZE`] frames.
+    ///
+    /// RPMB writes can't be undone: the write counter only ever goes up, and some
+    /// devices store their anti-rollback and unlock state there. They're refused
+    /// unless `danger_accepted` is set.
+    pub async fn write_rpmb(
+        &mut self,
+        address: u16,
+        size: usize,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        danger_accepted: bool,
+        progress: &mut (dyn FnMut(ProgressEvent) + Send),
+    ) -> Result<()> {
+        if !danger_accepted {
+            return Err(Error::penumbra(
+                "RPMB writes are irreversible, refusing to write without accepting the danger",
+            ));
+        }
+        if !size.is_multiple_of(RPMB_FRAME_SIZE) {
+            return Err(Error::penumbra(format!(
+                "RPMB writes need whole {} bytes frames, got {} bytes",
+                RPMB_FRAME_SIZE, size
+            )));
+        }
+        let blocks = u16::try_from(size / RPMB_FRAME_SIZE)
+            .map_err(|_| Error::penumbra("Too many RPMB frames to write"))?;
+        check_rpmb_range(address, blocks)?;
+
+        self.ensure_da_mode().await?;
+        self.ensure_writable()?;
+
+        let protocol = self.da_protocol()?;
+        protocol.write_rpmb(address, blocks, reader, progress).await
+    }
+
+    /// Reads memory with the Read32 command of BROM or preloader, without any DA.
+    /// Meant for bringup without a matching DA: it works as soon as [`Device::init`]
+    /// returned, but not once the device is in DA mode.
+    ///
+    /// What can be read depends on the BROM or preloader, most of them only allow some ranges.
+    pub async fn brom_read32(&mut self, addr: u32, size: usize) -> Result<Vec<u8>> {
+        let conn = self.brom_connection()?;
+        conn.read_mem(addr, size).await
+    }
+
+    /// Writes memory with the Write32 command of BROM or preloader, without any DA.
+    /// `data` is laid out like what [`Device::brom_read32`] returns, and its length
+    /// must be a multiple of 4.
+    pub async fn brom_write32(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        self.ensure_writable()?;
+
+        let conn = self.brom_connection()?;
+        conn.write_mem(addr, data).await
+    }
+
+    /// Internal helper returning the connection, as long as BROM or preloader
+    /// commands can be sent on it.
+    fn brom_connection(&mut self) -> Result<&mut Connection> {
+        if !self.connected {
+            return Err(Error::conn("Device is not connected. Call init() first."));
+        }
+
+        let conn = self.get_connection()?;
+        if conn.connection_type == ConnectionType::Da {
+            return Err(Error::conn(
+                "Device is in DA mode, BROM commands are only available before loading a DA.",
+            ));
+        }
+        Ok(conn)
+    }
+}
+
+/// Expanded size of a sparse image, checked against the partition it's written to.
+fn sparse_size<R>(part: &Partition, sparse: &SparseReader<R>) -> Result<usize> {
+    let size = sparse.expanded_size();
+    if size > part.size as u64 {
+        return Err(Error::penumbra(format!(
+            "Sparse image expands to 0x{:X} bytes, larger than '{}' (0x{:X})",
+            size, part.name, part.size
+        )));
+    }
+    Ok(size as usize)
+}
+
+/// Refuses partitions flagged while loading the partition table.
+/// They can still be read with [`Device::read_offset`].
+fn ensure_readable(part: &Partition) -> Result<()> {
+    match part.issue {
+        Some(issue) => Err(Error::penumbra(format!(
+            "Partition '{}' can't be read safely: {}. Use read_offset to read it anyway",
+            part.name, issue
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Checks that an RPMB access has at least one frame and stays within the 16-bit
+/// frame addresses RPMB uses.
+fn check_rpmb_range(address: u16, blocks: u16) -> Result<()> {
+    if blocks == 0 {
+        return Err(Error::penumbra("RPMB access needs at least one frame"));
+    }
+    if address.checked_add(blocks - 1).is_none() {
+        return Err(Error::penumbra(format!(
+            "RPMB access of {} frames at frame {} goes past the last RPMB frame",
+            blocks, address
+        )));
+    }
+    Ok(())
 }
 
 #[async_trait::async_trait]
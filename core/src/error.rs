@@ -3,6 +3,7 @@
     SPDX-FileCopyrightText: 2025 Shomy
 */
 use std::sync::PoisonError;
+use std::time::Duration;
 
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use thiserror::Error;
@@ -36,6 +37,46 @@ pub enum Error {
     /// is there (e.g. XFlash)
     #[error("{ctx}: Status is 0x{status:X}")]
     Status { ctx: String, status: u32 },
+    /// The host was suspended in the middle of an operation,
+    /// which usually leaves the device session dead.
+    #[error(
+        "Host was suspended for {}s during the operation, the device session is likely lost. \
+         Reconnect the device and resume the operation.",
+        .0.as_secs()
+    )]
+    HostSuspended(Duration),
+    /// A read from the device failed after `read` of the `expected` bytes arrived.
+    /// Those bytes are consumed: unless none arrived, the stream is out of sync.
+    #[error("Read failed after {read}/{expected} bytes: {reason}")]
+    ReadFailed { read: usize, expected: usize, timed_out: bool, reason: String },
+    /// Data from the device (or a file) too short or otherwise unparsable.
+    /// Carries the offending bytes, to make bug reports useful.
+    #[error("{ctx}: malformed data ({} bytes: {})", .data.len(), hex_preview(.data))]
+    Malformed { ctx: String, data: Vec<u8> },
+    /// The operation isn't available with the DA in use or with this build,
+    /// as opposed to having failed on the device.
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+    /// The checksum of a transferred packet didn't match, at `offset` within the transfer.
+    #[error("Checksum mismatch in the transfer at offset 0x{offset:X}")]
+    ChecksumMismatch { offset: u64 },
+    /// The data read back after a write doesn't match what was written.
+    /// Both are SHA-256 hashes, over the written length only.
+    #[error("Verification failed: wrote sha256 {expected}, read back {actual}")]
+    VerificationFailed { expected: String, actual: String },
+    /// The input of a write ended after `got` of the `expected` bytes,
+    /// and zero-filling the rest wasn't asked for.
+    #[error("Input ended after {got} of {expected} bytes")]
+    InputTooShort { expected: usize, got: usize },
+    /// A file doesn't match the SHA-256 recorded for it, like a corrupted backup dump.
+    #[error("{file} is corrupted: expected sha256 {expected}, got {actual}")]
+    HashMismatch { file: String, expected: String, actual: String },
+    /// The device has SLA or DAA enabled, and no auth file was given to send before the DA.
+    #[error(
+        "{0} is enabled on this device: the DA is only accepted after the vendor auth file \
+         of the device (e.g. auth_sv5.auth)"
+    )]
+    AuthRequired(String),
 }
 
 impl Error {
@@ -54,6 +95,102 @@ impl Error {
     pub fn penumbra<S: Into<String>>(msg: S) -> Self {
         Error::Penumbra(msg.into())
     }
+
+    pub fn unsupported<S: Into<String>>(msg: S) -> Self {
+        Error::Unsupported(msg.into())
+    }
+
+    pub fn read_failed<S: ToString>(read: usize, expected: usize, reason: S) -> Self {
+        Error::ReadFailed { read, expected, timed_out: false, reason: reason.to_string() }
+    }
+
+    pub fn read_timeout(read: usize, expected: usize) -> Self {
+        Error::ReadFailed { read, expected, timed_out: true, reason: "timed out".to_string() }
+    }
+
+    /// Whether a bounded read ran out of time, as opposed to the connection failing.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Error::ReadFailed { timed_out: true, .. })
+    }
+
+    /// Bytes a failed read consumed before failing, `None` for other errors.
+    pub fn bytes_read(&self) -> Option<usize> {
+        match self {
+            Error::ReadFailed { read, .. } => Some(*read),
+            _ => None,
+        }
+    }
+
+    pub fn malformed<S: Into<String>>(ctx: S, data: &[u8]) -> Self {
+        Error::Malformed { ctx: ctx.into(), data: data.to_vec() }
+    }
+
+    /// Whether the error was reported by the device for the operation itself,
+    /// as opposed to the connection failing. Such errors leave the session usable.
+    pub fn is_device_reported(&self) -> bool {
+        matches!(self, Error::XFlash(_) | Error::Xml(_) | Error::Status { .. })
+    }
+
+    /// Coarse classification of the error, to decide what to do about it.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::XFlash(e) => e.error_kind(),
+            Error::Xml(e) => e.error_kind(),
+            Error::Protocol(_) | Error::Malformed { .. } => ErrorKind::Protocol,
+            Error::Connection(_)
+            | Error::Io(_)
+            | Error::ReadFailed { .. }
+            | Error::HostSuspended(_) => ErrorKind::Connection,
+            Error::Status { .. } => ErrorKind::Device,
+            Error::Unsupported(_) => ErrorKind::Unsupported,
+            Error::ChecksumMismatch { .. }
+            | Error::VerificationFailed { .. }
+            | Error::HashMismatch { .. } => ErrorKind::Integrity,
+            Error::AuthRequired(_) => ErrorKind::Security,
+            Error::Penumbra(_) | Error::InputTooShort { .. } => ErrorKind::Other,
+        }
+    }
+
+    /// Whether the same operation may succeed if tried again, like after a corrupted
+    /// transfer. Errors refused by the device for a reason (security, bad parameters)
+    /// never are.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::XFlash(e) => e.is_retryable(),
+            Error::Xml(e) => e.is_retryable(),
+            Error::ChecksumMismatch { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Coarse classification of an [`Error`], see [`Error::kind`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// The link with the device failed
+    Connection,
+    /// The device answered something unexpected
+    Protocol,
+    /// The device refused the operation for security reasons, like a locked bootloader
+    Security,
+    /// The device failed the operation itself
+    Device,
+    /// Transferred or written data doesn't match
+    Integrity,
+    /// The operation isn't available with this DA or build
+    Unsupported,
+    /// Anything else
+    Other,
+}
+
+/// Hex dump of the first bytes of `data`, enough to recognize a response.
+fn hex_preview(data: &[u8]) -> String {
+    const MAX_PREVIEW: usize = 32;
+
+    match data.get(..MAX_PREVIEW) {
+        Some(head) if data.len() > MAX_PREVIEW => format!("{}...", hex::encode(head)),
+        _ => hex::encode(data),
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -62,6 +199,7 @@ impl From<std::io::Error> for Error {
     }
 }
 
+#[cfg(feature = "nusb")]
 impl From<nusb::Error> for Error {
     fn from(err: nusb::Error) -> Self {
         Error::io(err.to_string())
@@ -508,8 +646,44 @@ pub enum XFlashErrorKind {
     Unknown = 0xFFFFFFFF,
 }
 
+impl XFlashErrorKind {
+    /// Name of the status, in the style of the `STATUS_*` names of MTK tools
+    /// (e.g. `STATUS_SEC_DL_FORBIDDEN`), derived from the variant name.
+    pub fn name(&self) -> String {
+        let variant = format!("{:?}", self);
+        let chars: Vec<char> = variant.chars().collect();
+        let mut name = String::from("STATUS_");
+        if status_domain(u32::from(*self)) == DOMAIN_SECURITY {
+            name.push_str("SEC_");
+        }
+
+        for (i, &c) in chars.iter().enumerate() {
+            // A new word starts at each capital, except inside acronyms
+            let starts_word = c.is_ascii_uppercase()
+                && i.checked_sub(1).and_then(|p| chars.get(p)).is_some_and(|p| {
+                    !p.is_ascii_uppercase()
+                        || chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase())
+                });
+            if starts_word {
+                name.push('_');
+            }
+            name.push(c.to_ascii_uppercase());
+        }
+
+        name
+    }
+}
+
+/// Security domain of the status codes, see above.
+const DOMAIN_SECURITY: u32 = 2;
+
+/// Domain of a status code, see above.
+fn status_domain(code: u32) -> u32 {
+    (code >> 16) & 0xFF
+}
+
 #[derive(Debug, Error)]
-#[error("{kind} (code: {code:#010x})")]
+#[error("{} ({code:#010X}): {kind}", .kind.name())]
 pub struct XFlashError {
     pub kind: XFlashErrorKind,
     pub code: u32,
@@ -520,6 +694,49 @@ impl XFlashError {
         let kind = XFlashErrorKind::try_from(code).unwrap_or(XFlashErrorKind::Unknown);
         Self { kind, code }
     }
+
+    /// Whether the operation may succeed if tried again: the status reports a
+    /// transfer or timing hiccup rather than a refusal.
+    pub fn is_retryable(&self) -> bool {
+        use XFlashErrorKind::*;
+
+        matches!(
+            self.kind,
+            ProtocolError
+                | ProtocolBufferOverflow
+                | UsbScanError
+                | ThreadError
+                | ChecksumError
+                | NandInProgress
+                | NandTimeout
+                | DownloadException
+                | UploadException
+                | ReadDataException
+                | WriteDataException
+        )
+    }
+
+    /// Whether the device refused the operation for security reasons, like a locked
+    /// bootloader, a region that isn't whitelisted or a failed signature check.
+    /// Unknown codes of the security domain count too.
+    pub fn is_security_violation(&self) -> bool {
+        status_domain(self.code) == DOMAIN_SECURITY
+    }
+
+    fn error_kind(&self) -> ErrorKind {
+        match self.kind {
+            _ if self.is_security_violation() => ErrorKind::Security,
+            XFlashErrorKind::UnsupportedCommand
+            | XFlashErrorKind::UnsupportedCtrlCode
+            | XFlashErrorKind::NotImplemented
+            | XFlashErrorKind::UnsupportedOperation => ErrorKind::Unsupported,
+            XFlashErrorKind::ChecksumError => ErrorKind::Integrity,
+            XFlashErrorKind::ProtocolError | XFlashErrorKind::ProtocolBufferOverflow => {
+                ErrorKind::Protocol
+            }
+            _ => ErrorKind::Device,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -527,6 +744,8 @@ pub enum XmlErrorKind {
     Unknown,
     UnsupportedCmd,
     Cancel,
+    /// The DA answered with a status code, shared with XFlash, see [`XmlError::status`].
+    Status,
 }
 
 #[derive(Debug, Error)]
@@ -534,11 +753,19 @@ pub enum XmlErrorKind {
 pub struct XmlError {
     pub message: String,
     pub kind: XmlErrorKind,
+    /// Status code of the error, when the DA gave one
+    pub code: Option<u32>,
 }
 
 impl XmlError {
     pub fn new<S: Into<String>>(msg: S, kind: XmlErrorKind) -> Self {
-        XmlError { message: msg.into(), kind }
+        XmlError { message: msg.into(), kind, code: None }
+    }
+
+    /// An error carrying a status code, named like the XFlash ones.
+    pub fn from_code(code: u32) -> Self {
+        let status = XFlashError::from_code(code);
+        XmlError { message: status.to_string(), kind: XmlErrorKind::Status, code: Some(code) }
     }
 
     pub fn from_message(resp: &[u8]) -> Self {
@@ -549,7 +776,42 @@ impl XmlError {
         match msg {
             "ERR!UNSUPPORTED" => XmlError::new("Unsupported command", XmlErrorKind::UnsupportedCmd),
             "ERR!CANCEL" => XmlError::new("Cancelled", XmlErrorKind::Cancel),
-            _ => XmlError::new(msg, XmlErrorKind::UnsupportedCmd),
+            _ => {
+                // ERR!<code>, with or without 0x
+                let code = msg.strip_prefix("ERR!").and_then(|c| {
+                    let c = c.trim();
+                    let hex = c.strip_prefix("0x").or_else(|| c.strip_prefix("0X")).unwrap_or(c);
+                    u32::from_str_radix(hex, 16).ok()
+                });
+                match code {
+                    Some(code) => XmlError::from_code(code),
+                    None => XmlError::new(msg, XmlErrorKind::Unknown),
+                }
+            }
+        }
+    }
+
+    /// The status the DA reported, when it gave a code.
+    pub fn status(&self) -> Option<XFlashError> {
+        self.code.map(XFlashError::from_code)
+    }
+
+    /// Whether the operation may succeed if tried again, see [`XFlashError::is_retryable`].
+    pub fn is_retryable(&self) -> bool {
+        self.status().is_some_and(|s| s.is_retryable())
+    }
+
+    /// Whether the DA refused the operation for security reasons,
+    /// see [`XFlashError::is_security_violation`].
+    pub fn is_security_violation(&self) -> bool {
+        self.status().is_some_and(|s| s.is_security_violation())
+    }
+
+    fn error_kind(&self) -> ErrorKind {
+        match (self.kind, self.status()) {
+            (XmlErrorKind::UnsupportedCmd, _) => ErrorKind::Unsupported,
+            (_, Some(status)) => status.error_kind(),
+            _ => ErrorKind::Device,
         }
     }
 }
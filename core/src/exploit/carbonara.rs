@@ -11,7 +11,7 @@ use tokio::time::timeout;
 use crate::connection::port::ConnectionType;
 use crate::da::{DA, DAEntryRegion, DAProtocol};
 use crate::error::{Error, Result};
-use crate::exploit::{BootStage, Exploit, ExploitMeta, rebuild_patched_da};
+use crate::exploit::{BootStage, Exploit, ExploitMeta, ExploitOutcome, rebuild_patched_da};
 
 pub struct Carbonara {
     meta: ExploitMeta,
@@ -59,12 +59,12 @@ impl Carbonara {
 
 #[async_trait::async_trait]
 impl Exploit for Carbonara {
-    async fn run(&mut self, protocol: &mut dyn DAProtocol) -> Result<bool> {
+    async fn run(&mut self, protocol: &mut dyn DAProtocol) -> Result<ExploitOutcome> {
         let da = protocol.get_da();
         let da1 = da.get_da1().ok_or_else(|| Error::penumbra("DA1 region not found"))?;
 
         if !self.is_vulnerable(da1).await {
-            return Err(Error::penumbra("Device is not vulnerable to Carbonara exploit"));
+            return Ok(ExploitOutcome::Skipped("DA1 not vulnerable".to_string()));
         }
 
         info!("[Exploit] Device is vulnerable to Carbonara! Cooking...");
@@ -90,7 +90,8 @@ impl Exploit for Carbonara {
 
         let mut hasher = Sha256::new();
         if let Some(ref da2) = patched_da2 {
-            hasher.update(&da2.data[..da2.data.len().saturating_sub(da2.sig_len as usize)]);
+            let signed_len = da2.data.len().saturating_sub(da2.sig_len as usize);
+            hasher.update(da2.data.get(..signed_len).unwrap_or_default());
         }
 
         let hash_result = hasher.finalize();
@@ -110,7 +111,7 @@ impl Exploit for Carbonara {
 
         info!("[Exploit] Carbonara got served! Enjoy your meal ;)");
 
-        Ok(true)
+        Ok(ExploitOutcome::Succeeded)
     }
 
     fn get_meta(&self) -> &ExploitMeta {
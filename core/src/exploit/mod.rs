@@ -2,17 +2,26 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+#[cfg(feature = "exploits-da-patch")]
 pub mod carbonara;
+#[cfg(feature = "exploits-da-patch")]
 pub mod heapbait;
+#[cfg(feature = "exploits-brom")]
 pub mod kamakiri;
+use std::fmt;
+
+#[cfg(feature = "exploits-da-patch")]
 pub use carbonara::Carbonara;
+#[cfg(feature = "exploits-da-patch")]
 pub use heapbait::HeapBait;
+#[cfg(feature = "exploits-brom")]
 pub use kamakiri::Kamakiri2 as Kamakiri;
 
 use crate::connection::port::ConnectionType;
 use crate::da::protocol::DAProtocol;
 use crate::da::{DA, DAEntryRegion};
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::le_u32;
 
 const V6_PAYLOAD_MAGIC: &[u8] = b"PENUMBRAV6P";
 
@@ -30,34 +39,91 @@ pub struct ExploitMeta {
                                          * in BROM, Carbonara in DA1) */
 }
 
+/// Outcome of an exploit, as recorded in an [`ExploitReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExploitOutcome {
+    /// The exploit ran, and the DA can be patched.
+    Succeeded,
+    /// The exploit doesn't apply here, and nothing was sent for it.
+    Skipped(String),
+    /// The exploit was attempted and failed.
+    Failed(String),
+}
+
+impl fmt::Display for ExploitOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExploitOutcome::Succeeded => write!(f, "OK"),
+            ExploitOutcome::Skipped(reason) => write!(f, "skipped ({})", reason),
+            ExploitOutcome::Failed(reason) => write!(f, "failed ({})", reason),
+        }
+    }
+}
+
+/// An exploit that was considered while uploading the DA.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExploitAttempt {
+    pub name: String,
+    pub outcome: ExploitOutcome,
+}
+
+/// Exploits considered during the last DA upload, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExploitReport {
+    pub attempts: Vec<ExploitAttempt>,
+}
+
+impl ExploitReport {
+    pub fn record(&mut self, name: &str, outcome: ExploitOutcome) {
+        self.attempts.push(ExploitAttempt { name: name.to_string(), outcome });
+    }
+
+    /// Whether an exploit succeeded, so the DA runs patched.
+    pub fn patched(&self) -> bool {
+        self.attempts.iter().any(|a| a.outcome == ExploitOutcome::Succeeded)
+    }
+}
+
+impl fmt::Display for ExploitReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, attempt) in self.attempts.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            write!(f, "{}{}: {}", sep, attempt.name, attempt.outcome)?;
+        }
+        Ok(())
+    }
+}
+
 #[async_trait::async_trait]
 pub trait Exploit {
-    async fn run(&mut self, protocol: &mut dyn DAProtocol) -> Result<bool>;
+    /// Runs the exploit. Errors are reported as [`ExploitOutcome::Failed`], with their message
+    /// as the reason.
+    async fn run(&mut self, protocol: &mut dyn DAProtocol) -> Result<ExploitOutcome>;
 
     fn get_meta(&self) -> &ExploitMeta;
     fn get_patched_da(&self) -> Option<DA>;
 }
 
-pub fn get_v6_payload(data: &[u8], is_arm64: bool) -> &[u8] {
+pub fn get_v6_payload(data: &[u8], is_arm64: bool) -> Result<&[u8]> {
     if data.len() < 16 + 4 * 4 {
-        panic!("Data too short to contain a valid v6 header");
+        return Err(Error::malformed("Data too short to contain a valid v6 header", data));
     }
 
-    if &data[0..11] != V6_PAYLOAD_MAGIC {
-        panic!("Invalid v6 payload magic");
+    if !data.starts_with(V6_PAYLOAD_MAGIC) {
+        return Err(Error::malformed("Invalid v6 payload magic", data));
     }
 
-    // Remove the MAGIC
-    let arm7_offset = u32::from_le_bytes(data[16..20].try_into().unwrap()) as usize + 8;
-    let arm7_length = u32::from_le_bytes(data[20..24].try_into().unwrap()) as usize - 8;
-    let arm64_offset = u32::from_le_bytes(data[24..28].try_into().unwrap()) as usize + 8;
-    let arm64_length = u32::from_le_bytes(data[28..32].try_into().unwrap()) as usize - 8;
-
-    if is_arm64 {
-        &data[arm64_offset..arm64_offset + arm64_length]
+    let (offset, length) = if is_arm64 {
+        (le_u32!(data, 24)? as usize, le_u32!(data, 28)? as usize)
     } else {
-        &data[arm7_offset..arm7_offset + arm7_length]
-    }
+        (le_u32!(data, 16)? as usize, le_u32!(data, 20)? as usize)
+    };
+
+    // Remove the MAGIC
+    let start = offset + 8;
+    let end = length.checked_sub(8).map(|len| start + len);
+    end.and_then(|end| data.get(start..end))
+        .ok_or_else(|| Error::malformed("v6 payload out of bounds", data))
 }
 
 pub fn rebuild_patched_da(
@@ -92,5 +158,6 @@ pub fn rebuild_patched_da(
         magic: original_da.magic,
         hw_code: original_da.hw_code,
         hw_sub_code: original_da.hw_sub_code,
+        issues: Vec::new(),
     }
 }
@@ -0,0 +1,90 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use log::{debug, warn};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+use crate::Device;
+
+/// Keeps a DA session alive while the host is idle.
+///
+/// Some DAs arm a watchdog that reboots the device after a period of inactivity,
+/// which kills the session if the host pauses for too long (for example, while
+/// waiting for user confirmation). While a `KeepAlive` is alive, a lightweight
+/// no-op command is sent whenever the connection has been idle for `interval`.
+///
+/// The device mutex is used as the exclusivity guard: the keepalive only ever uses
+/// `try_lock`, so if an operation holds the device, the tick is simply skipped and
+/// the keepalive never interleaves with real traffic.
+///
+/// Dropping the handle stops the task. The task is never aborted mid-command, it
+/// exits on its next tick instead.
+///
+/// If a no-op fails, the session is most likely gone: the task stops, and
+/// [`KeepAlive::has_failed`] tells so.
+pub struct KeepAlive {
+    stop: Arc<AtomicBool>,
+    failed: Arc<AtomicBool>,
+}
+
+impl KeepAlive {
+    /// Default inactivity interval. Known DA watchdogs fire after tens of seconds.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// Spawns the keepalive task for the given device.
+    pub fn spawn(device: Arc<Mutex<Device>>, interval: Duration) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let failed = Arc::new(AtomicBool::new(false));
+        let task_stop = stop.clone();
+        let task_failed = failed.clone();
+
+        // Tick faster than the interval, so that we catch the idle threshold in time
+        let tick = (interval / 4).max(Duration::from_millis(250));
+
+        tokio::spawn(async move {
+            debug!("Keepalive started, interval: {:?}", interval);
+            while !task_stop.load(Ordering::Acquire) {
+                sleep(tick).await;
+
+                if task_stop.load(Ordering::Acquire) {
+                    break;
+                }
+
+                // An operation is in flight, which keeps the DA busy anyway
+                let Ok(mut dev) = device.try_lock() else {
+                    continue;
+                };
+
+                if dev.idle_time().is_none_or(|idle| idle < interval) {
+                    continue;
+                }
+
+                if let Err(e) = dev.keepalive().await {
+                    warn!("Keepalive failed, stopping: {}", e);
+                    task_failed.store(true, Ordering::Release);
+                    break;
+                }
+            }
+            debug!("Keepalive stopped");
+        });
+
+        KeepAlive { stop, failed }
+    }
+
+    /// Whether a no-op failed, which stopped the task. The device likely rebooted.
+    pub fn has_failed(&self) -> bool {
+        self.failed.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
@@ -2,17 +2,70 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+//! Penumbra talks to MediaTek devices through BROM, preloader and the DA protocols.
+//!
+//! # API stability
+//!
+//! The crate follows semver. What's covered:
+//! - Everything re-exported at the crate root, starting with [`Device`] and [`DeviceBuilder`].
+//! - The data types and parsers of [`crate::core`], [`da::DAFile`] and [`error`].
+//! - The [`MTKPort`] and [`da::DAProtocol`] traits, which stay object safe.
+//!
+//! Breaking changes to the covered surface only come with a major release. Within a major
+//! release, a covered signature that has to change keeps its old form as a `#[deprecated]`
+//! shim until the next major one. Anything else, like the binary analysis and patching
+//! helpers, is private to the crate.
+//!
+//! `tests/api.rs` holds a compile-time snapshot of the covered surface: changing it by
+//! accident breaks the tests.
+// Nothing the device sends may abort the process: parse into errors, never unwrap
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::indexing_slicing
+    )
+)]
+pub mod blocking;
+pub mod capabilities;
 pub mod connection;
 pub mod core;
 pub mod da;
 pub mod device;
 pub mod error;
-#[cfg(not(feature = "no_exploits"))]
 pub mod exploit;
-pub mod macros;
+pub mod keepalive;
+mod macros;
 pub mod utilities;
 
-pub use connection::port::{MTKPort, find_mtk_port};
+pub use capabilities::Capabilities;
+#[cfg(feature = "serial")]
+pub use connection::port::open_serial_port;
+pub use connection::port::{MTKPort, find_mtk_port, find_mtk_port_by_name, find_mtk_ports};
+pub use connection::probe::probe_port;
+pub use connection::retry::{ConnectionStats, RetryPolicy};
+pub use da::protocol::{BootMode, ChecksumLevel, RPMB_FRAME_SIZE, RPMB_KEY_SIZE};
 pub use device::{Device, DeviceBuilder};
+pub use error::{Error, Result};
+pub use keepalive::KeepAlive;
+
+pub use crate::core::progress::{ProgressEvent, progress_fn};
+#[cfg(feature = "seccfg")]
+pub use crate::core::seccfg::LockFlag;
+pub use crate::core::storage::{
+    EmmcPartition,
+    Partition,
+    PartitionKind,
+    PartitionRole,
+    UfsPartition,
+};
+#[cfg(any(feature = "exploits-brom", feature = "exploits-da-patch"))]
+pub(crate) use crate::macros::exploit;
+pub(crate) use crate::macros::{le_u16, le_u32, le_u64};
+pub(crate) use crate::utilities::arm::extract_ptr;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,46 +1,80 @@
-#[macro_export]
+#[cfg(any(feature = "exploits-brom", feature = "exploits-da-patch"))]
 macro_rules! exploit {
     ($exploit:ty, $proto:expr) => {{
-        #[cfg(not(feature = "no_exploits"))]
-        {
-            if $proto.patch {
-                let mut exploit = <$exploit>::new();
-
-                if let Ok(result) = exploit.run($proto).await {
-                    $proto.patch = !result;
-
-                    if let Some(patched_da) = exploit.get_patched_da() {
-                        $proto.da = patched_da;
-                    }
-                }
+        use $crate::exploit::{Exploit, ExploitOutcome};
+
+        let mut exploit = <$exploit>::new();
+        let name = exploit.get_meta().name.clone();
+
+        let outcome = if !$proto.da_patching {
+            ExploitOutcome::Skipped("DA patching disabled".to_string())
+        } else if !$proto.patch {
+            ExploitOutcome::Skipped("DA already patched".to_string())
+        } else {
+            match exploit.run($proto).await {
+                Ok(outcome) => outcome,
+                Err(e) => ExploitOutcome::Failed(e.to_string()),
+            }
+        };
+
+        if outcome == ExploitOutcome::Succeeded {
+            $proto.patch = false;
+
+            if let Some(patched_da) = exploit.get_patched_da() {
+                $proto.da = patched_da;
             }
         }
+
+        log::debug!("[Exploit] {}: {}", name, outcome);
+        $proto.exploits.record(&name, outcome);
     }};
 }
 
-#[macro_export]
+/// Reads a little endian integer at `offset`, returning [`Error::Malformed`] if `data`
+/// is too short. Use it on anything coming from the device, it must never panic.
+///
+/// [`Error::Malformed`]: crate::error::Error::Malformed
 macro_rules! le_u16 {
     ($data:expr, $offset:expr) => {{
-        assert!($data.len() >= $offset + 2, "Data length must be at least 2 bytes to read a u16");
-        let bytes = &$data[$offset..$offset + 2];
-        u16::from_le_bytes(bytes.try_into().expect("Failed to read u16: insufficient data"))
+        let data: &[u8] = &$data[..];
+        match data.get($offset..$offset + 2).and_then(|bytes| bytes.try_into().ok()) {
+            Some(bytes) => Ok(u16::from_le_bytes(bytes)),
+            None => Err($crate::error::Error::malformed(
+                concat!("Expected a u16 at offset ", stringify!($offset)),
+                data,
+            )),
+        }
     }};
 }
 
-#[macro_export]
 macro_rules! le_u32 {
     ($data:expr, $offset:expr) => {{
-        assert!($data.len() >= $offset + 4, "Data length must be at least 4 bytes to read a u32");
-        let bytes = &$data[$offset..$offset + 4];
-        u32::from_le_bytes(bytes.try_into().expect("Failed to read u32: insufficient data"))
+        let data: &[u8] = &$data[..];
+        match data.get($offset..$offset + 4).and_then(|bytes| bytes.try_into().ok()) {
+            Some(bytes) => Ok(u32::from_le_bytes(bytes)),
+            None => Err($crate::error::Error::malformed(
+                concat!("Expected a u32 at offset ", stringify!($offset)),
+                data,
+            )),
+        }
     }};
 }
 
-#[macro_export]
 macro_rules! le_u64 {
     ($data:expr, $offset:expr) => {{
-        assert!($data.len() >= $offset + 8, "Data length must be at least 8 bytes to read a u64");
-        let bytes = &$data[$offset..$offset + 8];
-        u64::from_le_bytes(bytes.try_into().expect("Failed to read u64: insufficient data"))
+        let data: &[u8] = &$data[..];
+        match data.get($offset..$offset + 8).and_then(|bytes| bytes.try_into().ok()) {
+            Some(bytes) => Ok(u64::from_le_bytes(bytes)),
+            None => Err($crate::error::Error::malformed(
+                concat!("Expected a u64 at offset ", stringify!($offset)),
+                data,
+            )),
+        }
     }};
 }
+
+#[cfg(any(feature = "exploits-brom", feature = "exploits-da-patch"))]
+pub(crate) use exploit;
+pub(crate) use le_u16;
+pub(crate) use le_u32;
+pub(crate) use le_u64;
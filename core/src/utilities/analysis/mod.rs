@@ -40,7 +40,7 @@ pub trait ArchAnalyzer: Downcast {
     /// Reads a little-endian u32 at the given offset.
     fn read_u32(&self, offset: usize) -> Option<u32> {
         let bytes = self.data().get(offset..offset + 4)?;
-        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
     }
 
     /// Converts a virtual address to a file offset.
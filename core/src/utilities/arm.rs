@@ -4,40 +4,28 @@
 */
 
 use crate::error::{Error, Result};
+use crate::utilities::patching::write_bytes;
 
-#[macro_export]
 macro_rules! extract_ptr {
     (u32, $data:expr, $offset:expr) => {{
-        if $offset + 4 <= $data.len() {
-            u32::from_le_bytes([
-                $data[$offset],
-                $data[$offset + 1],
-                $data[$offset + 2],
-                $data[$offset + 3],
-            ])
-        } else {
-            0
-        }
+        $data
+            .get($offset..$offset + 4)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u32::from_le_bytes)
+            .unwrap_or(0)
     }};
 
     (u64, $data:expr, $offset:expr) => {{
-        if $offset + 8 <= $data.len() {
-            u64::from_le_bytes([
-                $data[$offset],
-                $data[$offset + 1],
-                $data[$offset + 2],
-                $data[$offset + 3],
-                $data[$offset + 4],
-                $data[$offset + 5],
-                $data[$offset + 6],
-                $data[$offset + 7],
-            ])
-        } else {
-            0
-        }
+        $data
+            .get($offset..$offset + 8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_le_bytes)
+            .unwrap_or(0)
     }};
 }
 
+pub(crate) use extract_ptr;
+
 pub fn to_thumb_addr(pos: usize, base_addr: u32) -> u32 {
     ((pos as u32) + base_addr) | 1
 }
@@ -98,16 +86,13 @@ pub fn force_return(data: &mut [u8], off: usize, value: u32, thumb_mode: bool) -
         let mov_r0 = 0x2000u16 | ((value & 0xFF) as u16);
         let bx_lr = 0x4770u16;
 
-        data[off..off + 2].copy_from_slice(&mov_r0.to_le_bytes());
-        data[off + 2..off + 4].copy_from_slice(&bx_lr.to_le_bytes());
-        return Ok(());
+        write_bytes(data, off, &mov_r0.to_le_bytes())?;
+        return write_bytes(data, off + 2, &bx_lr.to_le_bytes());
     }
 
     let mov_r0 = 0xE3A00000u32 | (value & 0xFF) | ((value << 4) & 0xF00);
     let bx_lr = 0xE12FFF1Eu32;
 
-    data[off..off + 4].copy_from_slice(&mov_r0.to_le_bytes());
-    data[off + 4..off + 8].copy_from_slice(&bx_lr.to_le_bytes());
-
-    Ok(())
+    write_bytes(data, off, &mov_r0.to_le_bytes())?;
+    write_bytes(data, off + 4, &bx_lr.to_le_bytes())
 }
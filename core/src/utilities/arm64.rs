@@ -4,6 +4,7 @@
 */
 
 use crate::error::{Error, Result};
+use crate::utilities::patching::write_bytes;
 
 pub fn force_return(data: &mut [u8], off: usize, value: u32) -> Result<()> {
     // ARM64: mov x0, #imm
@@ -11,10 +12,8 @@ pub fn force_return(data: &mut [u8], off: usize, value: u32) -> Result<()> {
     let mov_x0 = 0xD2800000u32 | ((value & 0xFFFF) << 5);
     let ret = 0xD65F03C0u32;
 
-    data[off..off + 4].copy_from_slice(&mov_x0.to_le_bytes());
-    data[off + 4..off + 8].copy_from_slice(&ret.to_le_bytes());
-
-    Ok(())
+    write_bytes(data, off, &mov_x0.to_le_bytes())?;
+    write_bytes(data, off + 4, &ret.to_le_bytes())
 }
 
 pub fn encode_bl(src: u32, dst: u32) -> Result<u32> {
@@ -2,9 +2,15 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
-pub mod analysis;
-pub mod arm;
-pub mod arm64;
-pub mod patching;
-pub mod rsa;
-pub mod xml;
+// Analysis toolkit for the exploits, which don't need every helper of it
+#[allow(dead_code)]
+pub(crate) mod analysis;
+#[cfg_attr(not(feature = "exploits-da-patch"), allow(dead_code))]
+pub(crate) mod arm;
+#[cfg_attr(not(feature = "exploits-da-patch"), allow(dead_code))]
+pub(crate) mod arm64;
+#[cfg_attr(not(feature = "exploits-da-patch"), allow(dead_code))]
+pub(crate) mod patching;
+pub(crate) mod rsa;
+pub mod sparse;
+pub(crate) mod xml;
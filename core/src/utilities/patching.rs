@@ -21,7 +21,9 @@ fn parse_pattern(input: &str) -> Result<HexPattern> {
     (0..filtered.len())
         .step_by(2)
         .map(|i| {
-            let pair = &filtered[i..i + 2];
+            let pair = filtered
+                .get(i..i + 2)
+                .ok_or_else(|| Error::penumbra("Pattern has non hex characters"))?;
             if pair.eq_ignore_ascii_case("XX") {
                 Ok(None)
             } else {
@@ -62,19 +64,30 @@ pub fn find_pattern(data: &[u8], pattern_str: &str, offset: usize) -> usize {
 pub fn patch(data: &mut [u8], offset: usize, patch_str: &str) -> Result<()> {
     let patch = parse_pattern(patch_str)?;
 
-    if offset + patch.len() > data.len() {
-        return Err(Error::penumbra("Patch exceeds data bounds"));
-    }
+    let target = offset
+        .checked_add(patch.len())
+        .and_then(|end| data.get_mut(offset..end))
+        .ok_or_else(|| Error::penumbra("Patch exceeds data bounds"))?;
 
-    for (i, byte) in patch.into_iter().enumerate() {
+    for (slot, byte) in target.iter_mut().zip(patch) {
         if let Some(b) = byte {
-            data[offset + i] = b;
+            *slot = b;
         }
     }
 
     Ok(())
 }
 
+/// Overwrites the bytes at `offset` with `bytes`, failing if they don't fit in `data`.
+pub fn write_bytes(data: &mut [u8], offset: usize, bytes: &[u8]) -> Result<()> {
+    offset
+        .checked_add(bytes.len())
+        .and_then(|end| data.get_mut(offset..end))
+        .ok_or_else(|| Error::penumbra(format!("Patch at 0x{:X} exceeds data bounds", offset)))?
+        .copy_from_slice(bytes);
+    Ok(())
+}
+
 /// Finds a pattern in the data and applies a patch at the found location.
 /// Returns the position where the patch was applied, or -1 on failure.
 pub fn patch_pattern_str(data: &mut [u8], pattern: &str, patch_str: &str) -> Option<usize> {
@@ -103,14 +116,15 @@ pub fn patch_pattern(data: &mut [u8], pattern: &str, patch: u32) -> Option<usize
     patch_pattern_str(data, pattern, &patch_str)
 }
 
-pub fn patch_ptr(data: &mut [u8], ptr_off: usize, value: u32, base_addr: u32, thumb: bool) {
-    if thumb {
-        let addr = to_thumb_addr(value as usize, base_addr);
-        data[ptr_off..ptr_off + 4].copy_from_slice(&addr.to_le_bytes());
-    } else {
-        let addr = value + base_addr;
-        data[ptr_off..ptr_off + 4].copy_from_slice(&addr.to_le_bytes());
-    }
+pub fn patch_ptr(
+    data: &mut [u8],
+    ptr_off: usize,
+    value: u32,
+    base_addr: u32,
+    thumb: bool,
+) -> Result<()> {
+    let addr = if thumb { to_thumb_addr(value as usize, base_addr) } else { value + base_addr };
+    write_bytes(data, ptr_off, &addr.to_le_bytes())
 }
 
 pub fn bytes_to_hex(bytes: &[u8]) -> String {
@@ -138,21 +152,21 @@ pub fn patch_string(data: &mut [u8], original: &str, new: &str) {
 
     let original_bytes = original.as_bytes();
     let new_bytes = new.as_bytes();
-    let padding = original_bytes.len() - new_bytes.len();
 
     let mut offset = 0;
     while offset <= data.len().saturating_sub(original_bytes.len()) {
-        let pos = contains_bytes(&data[offset..], original_bytes);
+        let pos = contains_bytes(data.get(offset..).unwrap_or_default(), original_bytes);
         if pos == HEX_NOT_FOUND {
             break;
         }
 
         let pos = offset + pos;
-        data[pos..pos + new_bytes.len()].copy_from_slice(new_bytes);
-
-        for i in 0..padding {
-            data[pos + new_bytes.len() + i] = 0x00;
-        }
+        let Some(found) = data.get_mut(pos..pos + original_bytes.len()) else {
+            break;
+        };
+        let (replaced, padding) = found.split_at_mut(new_bytes.len());
+        replaced.copy_from_slice(new_bytes);
+        padding.fill(0x00);
 
         offset = pos + original_bytes.len();
     }
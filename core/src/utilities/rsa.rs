@@ -20,10 +20,6 @@ impl RsaPrivateKey {
     pub fn n(&self) -> &BigUint {
         &self.n
     }
-
-    pub fn d(&self) -> &BigUint {
-        &self.d
-    }
 }
 
 /// Mask Generation Function 1 (MGF1) using SHA-256
@@ -36,11 +32,11 @@ fn mgf1(seed: &[u8], mask_len: usize) -> Vec<u8> {
         hasher.update(seed);
         hasher.update(counter.to_be_bytes());
         let hash = hasher.finalize();
-        let remaining = mask_len - mask.len();
-        mask.extend_from_slice(&hash[..remaining.min(hash.len())]);
+        mask.extend_from_slice(&hash);
         counter += 1;
     }
 
+    mask.truncate(mask_len);
     mask
 }
 
@@ -0,0 +1,633 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::future::poll_fn;
+use std::io::{ErrorKind, SeekFrom};
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use tokio::io::{
+    AsyncRead,
+    AsyncReadExt,
+    AsyncSeek,
+    AsyncSeekExt,
+    AsyncWrite,
+    AsyncWriteExt,
+    ReadBuf,
+};
+
+use crate::error::{Error, Result};
+
+/*
+ * Android sparse image format
+ *
+ * File header (28 bytes):
+ *   magic (0xED26FF3A) | major (1) | minor (0) | file_hdr_sz (28) | chunk_hdr_sz (12)
+ *   blk_sz | total_blks | total_chunks | image_checksum
+ *
+ * Each chunk starts with a 12 bytes header:
+ *   chunk_type | reserved | chunk_sz (in blocks) | total_sz (in bytes, header included)
+ */
+pub const SPARSE_HEADER_MAGIC: u32 = 0xED26FF3A;
+pub const SPARSE_HEADER_SIZE: usize = 28;
+pub const CHUNK_HEADER_SIZE: usize = 12;
+
+pub const CHUNK_TYPE_RAW: u16 = 0xCAC1;
+pub const CHUNK_TYPE_FILL: u16 = 0xCAC2;
+pub const CHUNK_TYPE_DONT_CARE: u16 = 0xCAC3;
+pub const CHUNK_TYPE_CRC32: u16 = 0xCAC4;
+
+/// Raw blocks are buffered until a zero block is found, or this size is reached.
+const MAX_RAW_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Writes an Android sparse image from a stream of raw bytes.
+///
+/// Runs of zeroed blocks are emitted as `FILL` chunks, everything else as `RAW` chunks.
+/// Since the chunk count is only known at the end, the file header is written last,
+/// which is why the inner writer must be seekable.
+///
+/// # Example
+/// ```rust
+/// use penumbra::utilities::sparse::SparseWriter;
+///
+/// let file = tokio::fs::File::create("userdata.img").await?;
+/// let mut sparse = SparseWriter::new(file, data.len() as u64).await?;
+/// sparse.write(&data).await?;
+/// sparse.finish().await?;
+/// ```
+pub struct SparseWriter<W> {
+    inner: W,
+    blk_sz: u32,
+    total_blks: u32,
+    total_chunks: u32,
+    /// Blocks received so far
+    blocks: u32,
+    /// Partial block, waiting for more data
+    block: Vec<u8>,
+    /// Pending raw blocks, not yet emitted
+    raw: Vec<u8>,
+    /// Pending zero blocks, not yet emitted
+    zero_blocks: u32,
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> SparseWriter<W> {
+    /// Creates a new sparse writer for an image of `size` bytes.
+    /// The size must be a multiple of 512.
+    pub async fn new(mut inner: W, size: u64) -> Result<Self> {
+        let blk_sz: u32 = if size.is_multiple_of(4096) { 4096 } else { 512 };
+        if !size.is_multiple_of(blk_sz as u64) {
+            return Err(Error::penumbra("Sparse image size must be a multiple of 512"));
+        }
+
+        let total_blks = u32::try_from(size / blk_sz as u64)
+            .map_err(|_| Error::penumbra("Image too large for a sparse image"))?;
+
+        // Placeholder, the real header is written on finish
+        inner.write_all(&[0u8; SPARSE_HEADER_SIZE]).await?;
+
+        Ok(Self {
+            inner,
+            blk_sz,
+            total_blks,
+            total_chunks: 0,
+            blocks: 0,
+            block: Vec::with_capacity(blk_sz as usize),
+            raw: Vec::new(),
+            zero_blocks: 0,
+        })
+    }
+
+    /// Appends raw image data.
+    pub async fn write(&mut self, mut data: &[u8]) -> Result<()> {
+        let blk_sz = self.blk_sz as usize;
+
+        while !data.is_empty() {
+            let take = (blk_sz - self.block.len()).min(data.len());
+            let (head, rest) = data.split_at(take);
+            self.block.extend_from_slice(head);
+            data = rest;
+
+            if self.block.len() == blk_sz {
+                self.push_block().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Emits all pending chunks and writes the file header, returning the inner writer.
+    /// Fails if the amount of data written doesn't match the size given on creation.
+    pub async fn finish(mut self) -> Result<W> {
+        if !self.block.is_empty() || self.blocks != self.total_blks {
+            return Err(Error::penumbra(format!(
+                "Sparse image size mismatch: expected {} blocks, got {} (+{} bytes)",
+                self.total_blks,
+                self.blocks,
+                self.block.len()
+            )));
+        }
+
+        self.flush_raw().await?;
+        self.flush_zero().await?;
+
+        let mut hdr = [0u8; SPARSE_HEADER_SIZE];
+        hdr[0..4].copy_from_slice(&SPARSE_HEADER_MAGIC.to_le_bytes());
+        hdr[4..6].copy_from_slice(&1u16.to_le_bytes()); // major_version
+        hdr[6..8].copy_from_slice(&0u16.to_le_bytes()); // minor_version
+        hdr[8..10].copy_from_slice(&(SPARSE_HEADER_SIZE as u16).to_le_bytes());
+        hdr[10..12].copy_from_slice(&(CHUNK_HEADER_SIZE as u16).to_le_bytes());
+        hdr[12..16].copy_from_slice(&self.blk_sz.to_le_bytes());
+        hdr[16..20].copy_from_slice(&self.total_blks.to_le_bytes());
+        hdr[20..24].copy_from_slice(&self.total_chunks.to_le_bytes());
+        // image_checksum is left to 0, as it's optional
+
+        self.inner.seek(SeekFrom::Start(0)).await?;
+        self.inner.write_all(&hdr).await?;
+        self.inner.seek(SeekFrom::End(0)).await?;
+        self.inner.flush().await?;
+
+        Ok(self.inner)
+    }
+
+    async fn push_block(&mut self) -> Result<()> {
+        self.blocks += 1;
+
+        if self.block.iter().all(|&b| b == 0) {
+            self.flush_raw().await?;
+            self.zero_blocks += 1;
+        } else {
+            self.flush_zero().await?;
+            self.raw.extend_from_slice(&self.block);
+            if self.raw.len() >= MAX_RAW_CHUNK_SIZE {
+                self.flush_raw().await?;
+            }
+        }
+
+        self.block.clear();
+        Ok(())
+    }
+
+    async fn flush_raw(&mut self) -> Result<()> {
+        if self.raw.is_empty() {
+            return Ok(());
+        }
+
+        let chunk_sz = (self.raw.len() / self.blk_sz as usize) as u32;
+        let total_sz = (CHUNK_HEADER_SIZE + self.raw.len()) as u32;
+        self.write_chunk_header(CHUNK_TYPE_RAW, chunk_sz, total_sz).await?;
+        self.inner.write_all(&self.raw).await?;
+
+        self.raw.clear();
+        Ok(())
+    }
+
+    async fn flush_zero(&mut self) -> Result<()> {
+        if self.zero_blocks == 0 {
+            return Ok(());
+        }
+
+        // FILL with 0 rather than DONT_CARE, so that flashing the image
+        // actually zeroes the region instead of leaving stale data behind.
+        let total_sz = (CHUNK_HEADER_SIZE + 4) as u32;
+        self.write_chunk_header(CHUNK_TYPE_FILL, self.zero_blocks, total_sz).await?;
+        self.inner.write_all(&0u32.to_le_bytes()).await?;
+
+        self.zero_blocks = 0;
+        Ok(())
+    }
+
+    async fn write_chunk_header(&mut self, kind: u16, chunk_sz: u32, total_sz: u32) -> Result<()> {
+        let mut hdr = [0u8; CHUNK_HEADER_SIZE];
+        hdr[0..2].copy_from_slice(&kind.to_le_bytes());
+        hdr[4..8].copy_from_slice(&chunk_sz.to_le_bytes());
+        hdr[8..12].copy_from_slice(&total_sz.to_le_bytes());
+
+        self.inner.write_all(&hdr).await?;
+        self.total_chunks += 1;
+        Ok(())
+    }
+}
+
+/// Size of the image described by a sparse header once expanded,
+/// or `None` if `header` isn't the start of a sparse image.
+pub fn expanded_size(header: &[u8]) -> Option<u64> {
+    let header: &[u8; SPARSE_HEADER_SIZE] = header.get(..SPARSE_HEADER_SIZE)?.try_into().ok()?;
+    if header[0..4] != SPARSE_HEADER_MAGIC.to_le_bytes() {
+        return None;
+    }
+
+    let blk_sz = u32::from_le_bytes([header[12], header[13], header[14], header[15]]);
+    let total_blks = u32::from_le_bytes([header[16], header[17], header[18], header[19]]);
+    Some(blk_sz as u64 * total_blks as u64)
+}
+
+/// A stream with the bytes already read from it put back in front, see [`detect`].
+pub struct Peeked<R> {
+    head: Vec<u8>,
+    pos: usize,
+    inner: R,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Peeked<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if let Some(rest) = self.head.get(self.pos..).filter(|rest| !rest.is_empty()) {
+            let n = rest.len().min(buf.remaining());
+            buf.put_slice(rest.split_at(n).0);
+            self.pos += n;
+            return Poll::Ready(Ok(()));
+        }
+
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+/// Either a plain stream, or a sparse image to expand.
+pub enum SparseInput<R> {
+    Raw(Peeked<R>),
+    Sparse(SparseReader<Peeked<R>>),
+}
+
+/// Checks whether `inner` starts with a sparse image header.
+/// The bytes read to check are chained back in front, so nothing is lost either way.
+pub async fn detect<R: AsyncRead + Unpin>(mut inner: R) -> Result<SparseInput<R>> {
+    let mut magic = vec![0u8; 4];
+    let mut filled = 0;
+    while filled < magic.len() {
+        match inner.read(magic.split_at_mut(filled).1).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    magic.truncate(filled);
+
+    let is_sparse = magic[..] == SPARSE_HEADER_MAGIC.to_le_bytes();
+    let peeked = Peeked { head: magic, pos: 0, inner };
+
+    if is_sparse {
+        Ok(SparseInput::Sparse(SparseReader::new(peeked).await?))
+    } else {
+        Ok(SparseInput::Raw(peeked))
+    }
+}
+
+enum ReadState {
+    /// Reading a chunk header, `filled` bytes so far
+    Header {
+        buf: Vec<u8>,
+        filled: usize,
+    },
+    /// Reading the pattern of a FILL chunk of `len` bytes
+    Pattern {
+        buf: [u8; 4],
+        filled: usize,
+        len: u64,
+    },
+    Raw(u64),
+    Fill([u8; 4], u64),
+    DontCare(u64),
+    /// Bytes of the container to discard, like the ones of CRC32 chunks
+    Skip(u64),
+    Done,
+}
+
+/// Expands an Android sparse image while it's being read.
+///
+/// Reading it produces the whole image, with DONT_CARE chunks as zeros. When the
+/// destination can be written at arbitrary offsets, [`SparseReader::next_run`] allows
+/// skipping DONT_CARE chunks instead.
+///
+/// # Example
+/// ```rust
+/// use penumbra::utilities::sparse::SparseReader;
+///
+/// let file = tokio::fs::File::open("super.img").await?;
+/// let mut sparse = SparseReader::new(file).await?;
+/// println!("Expanded size: {}", sparse.expanded_size());
+/// tokio::io::copy(&mut sparse, &mut tokio::fs::File::create("super.raw").await?).await?;
+/// ```
+pub struct SparseReader<R> {
+    inner: R,
+    blk_sz: u32,
+    total_blks: u32,
+    chunk_hdr_sz: usize,
+    chunks_left: u32,
+    state: ReadState,
+    /// Offset in the expanded image
+    pos: u64,
+}
+
+impl<R> SparseReader<R> {
+    /// Size of the image once expanded.
+    pub fn expanded_size(&self) -> u64 {
+        self.blk_sz as u64 * self.total_blks as u64
+    }
+}
+
+impl<R: AsyncRead + Unpin> SparseReader<R> {
+    /// Parses the sparse header at the start of `inner`.
+    pub async fn new(mut inner: R) -> Result<Self> {
+        let mut hdr = [0u8; SPARSE_HEADER_SIZE];
+        inner.read_exact(&mut hdr).await?;
+
+        if expanded_size(&hdr).is_none() {
+            return Err(Error::penumbra("Not a sparse image"));
+        }
+
+        let file_hdr_sz = u16::from_le_bytes([hdr[8], hdr[9]]) as usize;
+        let chunk_hdr_sz = u16::from_le_bytes([hdr[10], hdr[11]]) as usize;
+        let blk_sz = u32::from_le_bytes([hdr[12], hdr[13], hdr[14], hdr[15]]);
+        let total_blks = u32::from_le_bytes([hdr[16], hdr[17], hdr[18], hdr[19]]);
+        let total_chunks = u32::from_le_bytes([hdr[20], hdr[21], hdr[22], hdr[23]]);
+
+        if file_hdr_sz < SPARSE_HEADER_SIZE || chunk_hdr_sz < CHUNK_HEADER_SIZE {
+            return Err(Error::malformed("Sparse header", &hdr));
+        }
+        if blk_sz == 0 || !blk_sz.is_multiple_of(4) {
+            return Err(Error::malformed("Sparse block size", &hdr));
+        }
+
+        // Newer versions may have a larger header, the extra fields are of no use here
+        let mut reader = Self {
+            inner,
+            blk_sz,
+            total_blks,
+            chunk_hdr_sz,
+            chunks_left: total_chunks,
+            state: ReadState::Skip((file_hdr_sz - SPARSE_HEADER_SIZE) as u64),
+            pos: 0,
+        };
+        poll_fn(|cx| reader.poll_chunk(cx)).await?;
+
+        Ok(reader)
+    }
+
+    /// Skips DONT_CARE chunks, up to the next chunk holding data.
+    /// Returns its offset in the expanded image and its length, or `None` at the end.
+    /// Exactly that many bytes must then be read before calling this again.
+    pub async fn next_run(&mut self) -> Result<Option<(u64, u64)>> {
+        loop {
+            poll_fn(|cx| self.poll_chunk(cx)).await?;
+
+            match self.state {
+                ReadState::DontCare(len) => {
+                    self.pos += len;
+                    self.state = self.next_header();
+                }
+                ReadState::Raw(len) | ReadState::Fill(_, len) => return Ok(Some((self.pos, len))),
+                _ => return Ok(None),
+            }
+        }
+    }
+
+    fn next_header(&self) -> ReadState {
+        if self.chunks_left == 0 {
+            ReadState::Done
+        } else {
+            ReadState::Header { buf: vec![0u8; self.chunk_hdr_sz], filled: 0 }
+        }
+    }
+
+    /// Advances through headers and skipped bytes, until the current state produces data.
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        loop {
+            let next = match &mut self.state {
+                ReadState::Header { buf, filled } => {
+                    ready!(poll_exact(&mut self.inner, cx, buf, filled))?;
+                    self.chunks_left -= 1;
+
+                    // new() made sure the header is at least this long, extra fields are ignored
+                    let hdr: [u8; CHUNK_HEADER_SIZE] = buf
+                        .get(..CHUNK_HEADER_SIZE)
+                        .and_then(|hdr| hdr.try_into().ok())
+                        .unwrap_or_default();
+                    let kind = u16::from_le_bytes([hdr[0], hdr[1]]);
+                    let blocks = u32::from_le_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]);
+                    let total_sz = u32::from_le_bytes([hdr[8], hdr[9], hdr[10], hdr[11]]);
+                    let len = blocks as u64 * self.blk_sz as u64;
+
+                    match kind {
+                        CHUNK_TYPE_RAW => ReadState::Raw(len),
+                        CHUNK_TYPE_FILL => ReadState::Pattern { buf: [0; 4], filled: 0, len },
+                        CHUNK_TYPE_DONT_CARE => ReadState::DontCare(len),
+                        CHUNK_TYPE_CRC32 => ReadState::Skip(
+                            (total_sz as u64).saturating_sub(self.chunk_hdr_sz as u64),
+                        ),
+                        _ => {
+                            return Poll::Ready(Err(std::io::Error::new(
+                                ErrorKind::InvalidData,
+                                format!("Unknown sparse chunk type 0x{:04X}", kind),
+                            )));
+                        }
+                    }
+                }
+                ReadState::Pattern { buf, filled, len } => {
+                    ready!(poll_exact(&mut self.inner, cx, buf, filled))?;
+                    ReadState::Fill(*buf, *len)
+                }
+                ReadState::Skip(0)
+                | ReadState::Raw(0)
+                | ReadState::Fill(_, 0)
+                | ReadState::DontCare(0) => self.next_header(),
+                ReadState::Skip(left) => {
+                    let mut discard = [0u8; 64];
+                    let len = (*left).min(discard.len() as u64) as usize;
+                    let mut filled = 0;
+                    let discard = discard.split_at_mut(len).0;
+                    ready!(poll_exact(&mut self.inner, cx, discard, &mut filled))?;
+                    *left -= len as u64;
+                    continue;
+                }
+                _ => return Poll::Ready(Ok(())),
+            };
+            self.state = next;
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for SparseReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = &mut *self;
+        ready!(this.poll_chunk(cx))?;
+
+        let pos = this.pos;
+        let read = match &mut this.state {
+            ReadState::Raw(left) => {
+                let max = (*left).min(buf.remaining() as u64) as usize;
+                let mut out = ReadBuf::new(buf.initialize_unfilled_to(max));
+                ready!(Pin::new(&mut this.inner).poll_read(cx, &mut out))?;
+                let n = out.filled().len();
+                if n == 0 && max > 0 {
+                    return Poll::Ready(Err(ErrorKind::UnexpectedEof.into()));
+                }
+                *left -= n as u64;
+                n
+            }
+            ReadState::Fill(pattern, left) => {
+                let max = (*left).min(buf.remaining() as u64) as usize;
+                // Chunks start on a block boundary, so the pattern lines up with the offset
+                let pattern = pattern.iter().cycle().skip(pos as usize % 4);
+                for (b, p) in buf.initialize_unfilled_to(max).iter_mut().zip(pattern) {
+                    *b = *p;
+                }
+                *left -= max as u64;
+                max
+            }
+            ReadState::DontCare(left) => {
+                let max = (*left).min(buf.remaining() as u64) as usize;
+                buf.initialize_unfilled_to(max).fill(0);
+                *left -= max as u64;
+                max
+            }
+            _ => 0,
+        };
+
+        buf.advance(read);
+        this.pos += read as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads into `buf` until it's full, keeping track of the progress in `filled`.
+fn poll_exact<R: AsyncRead + Unpin>(
+    inner: &mut R,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+    filled: &mut usize,
+) -> Poll<std::io::Result<()>> {
+    while *filled < buf.len() {
+        let mut out = ReadBuf::new(buf.split_at_mut(*filled).1);
+        ready!(Pin::new(&mut *inner).poll_read(cx, &mut out))?;
+        match out.filled().len() {
+            0 => return Poll::Ready(Err(ErrorKind::UnexpectedEof.into())),
+            n => *filled += n,
+        }
+    }
+
+    Poll::Ready(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    /// Sparse image of `image`, written `step` bytes at a time.
+    async fn sparse(image: &[u8], step: usize) -> Vec<u8> {
+        let mut writer =
+            SparseWriter::new(Cursor::new(Vec::new()), image.len() as u64).await.unwrap();
+        for part in image.chunks(step) {
+            writer.write(part).await.unwrap();
+        }
+        writer.finish().await.unwrap().into_inner()
+    }
+
+    async fn unsparse(sparse: &[u8]) -> Vec<u8> {
+        let mut reader = SparseReader::new(sparse).await.unwrap();
+        let mut image = Vec::new();
+        reader.read_to_end(&mut image).await.unwrap();
+        assert_eq!(image.len() as u64, reader.expanded_size());
+        image
+    }
+
+    /// Chunk types of a sparse image, with their size in blocks.
+    fn chunks(sparse: &[u8]) -> Vec<(u16, u32)> {
+        let mut chunks = Vec::new();
+        let mut rest = &sparse[SPARSE_HEADER_SIZE..];
+        while !rest.is_empty() {
+            let kind = u16::from_le_bytes(rest[0..2].try_into().unwrap());
+            let blocks = u32::from_le_bytes(rest[4..8].try_into().unwrap());
+            let total_sz = u32::from_le_bytes(rest[8..12].try_into().unwrap());
+            chunks.push((kind, blocks));
+            rest = &rest[total_sz as usize..];
+        }
+        chunks
+    }
+
+    /// Random blocks, with runs of zero blocks in between.
+    fn image(blocks: usize, blk_sz: usize) -> Vec<u8> {
+        let mut rng = StdRng::seed_from_u64(0x5A5A);
+        let mut image = vec![0u8; blocks * blk_sz];
+        for block in image.chunks_mut(blk_sz) {
+            if rng.random_bool(0.5) {
+                rng.fill(block);
+            }
+        }
+        image
+    }
+
+    #[tokio::test]
+    async fn sparse_image_unsparses_to_the_original() {
+        let image = image(256, 4096);
+        for step in [1000, 4096, 65536, image.len()] {
+            assert_eq!(unsparse(&sparse(&image, step).await).await, image, "step {}", step);
+        }
+    }
+
+    #[tokio::test]
+    async fn small_blocks_are_used_when_needed() {
+        let image = image(33, 512);
+        let sparse = sparse(&image, 700).await;
+
+        assert_eq!(u32::from_le_bytes(sparse[12..16].try_into().unwrap()), 512);
+        assert_eq!(unsparse(&sparse).await, image);
+    }
+
+    #[tokio::test]
+    async fn zero_runs_become_fills() {
+        let mut image = vec![0u8; 16 * 4096];
+        image[4 * 4096..6 * 4096].fill(0xAA);
+        // A single set byte is enough to keep a block raw
+        image[10 * 4096 + 1] = 1;
+
+        let sparse = sparse(&image, 4096).await;
+        assert_eq!(chunks(&sparse), vec![
+            (CHUNK_TYPE_FILL, 4),
+            (CHUNK_TYPE_RAW, 2),
+            (CHUNK_TYPE_FILL, 4),
+            (CHUNK_TYPE_RAW, 1),
+            (CHUNK_TYPE_FILL, 5),
+        ]);
+        assert_eq!(u32::from_le_bytes(sparse[20..24].try_into().unwrap()), 5);
+        assert_eq!(unsparse(&sparse).await, image);
+    }
+
+    #[tokio::test]
+    async fn long_raw_runs_are_split() {
+        let blocks = MAX_RAW_CHUNK_SIZE / 4096 + 3;
+        let mut image = vec![0u8; blocks * 4096];
+        StdRng::seed_from_u64(1).fill(&mut image[..]);
+
+        let sparse = sparse(&image, 1 << 20).await;
+        assert_eq!(chunks(&sparse), vec![
+            (CHUNK_TYPE_RAW, (MAX_RAW_CHUNK_SIZE / 4096) as u32),
+            (CHUNK_TYPE_RAW, 3)
+        ]);
+        assert_eq!(unsparse(&sparse).await, image);
+    }
+
+    #[tokio::test]
+    async fn size_mismatches_are_refused() {
+        assert!(SparseWriter::new(Cursor::new(Vec::new()), 1000).await.is_err());
+
+        let mut short = SparseWriter::new(Cursor::new(Vec::new()), 8192).await.unwrap();
+        short.write(&[1u8; 5000]).await.unwrap();
+        assert!(short.finish().await.is_err());
+
+        let mut long = SparseWriter::new(Cursor::new(Vec::new()), 8192).await.unwrap();
+        long.write(&[1u8; 12288]).await.unwrap();
+        assert!(long.finish().await.is_err());
+    }
+}
@@ -21,11 +21,9 @@ where
         let sub_nodes =
             sub_nodes.ok_or_else(|| Error::penumbra(format!("XML tag `{}` not found", subnode)))?;
 
-        if sub_nodes.is_empty() {
-            return Err(Error::penumbra(format!("XML tag `{}` empty", subnode)));
-        }
-
-        node = &sub_nodes[0];
+        node = sub_nodes
+            .first()
+            .ok_or_else(|| Error::penumbra(format!("XML tag `{}` empty", subnode)))?;
     }
 
     node.content
@@ -0,0 +1,314 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Compile-time snapshot of the API covered by semver, see the crate docs.
+//!
+//! Nothing here runs, it only has to typecheck against the crate as downstream code sees
+//! it. Changing a signature listed here, or making an item private, breaks `cargo test`:
+//! when the change is intended, keep the old signature as a `#[deprecated]` shim and
+//! update the snapshot along with it. New public API gets added here as well.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use penumbra::connection::Connection;
+use penumbra::core::avb::VbMeta;
+use penumbra::core::backup::{
+    BACKUP_MANIFEST_NAME,
+    BackupManifest,
+    BackupPartition,
+    DEFAULT_RESTORE_SKIP,
+};
+use penumbra::core::efuse::EfuseMap;
+use penumbra::core::inspect::{ContentKind, detect_content};
+use penumbra::core::scatter::{ScatterEntry, parse_scatter};
+use penumbra::core::storage::{
+    GptType,
+    LpMetadata,
+    MbrStatus,
+    NandExtension,
+    NandPartition,
+    StorageInfo,
+    StorageType,
+};
+use penumbra::core::summary::{HashingWriter, OperationSummary};
+use penumbra::da::repository::DaIndexEntry;
+use penumbra::da::xml::{RawXmlCommand, XmlCommand};
+use penumbra::da::{BringupReport, DA, DAFile, DAProtocol, DAType, DaRepository};
+use penumbra::error::{ErrorKind, XFlashError, XmlError};
+use penumbra::{
+    BootMode,
+    ChecksumLevel,
+    ConnectionStats,
+    Device,
+    DeviceBuilder,
+    Error,
+    KeepAlive,
+    MTKPort,
+    Partition,
+    PartitionKind,
+    PartitionRole,
+    ProgressEvent,
+    Result,
+    RetryPolicy,
+    find_mtk_port,
+    find_mtk_port_by_name,
+    find_mtk_ports,
+    progress_fn,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+type Progress<'a> = &'a mut (dyn FnMut(ProgressEvent) + Send);
+type Reader<'a> = &'a mut (dyn AsyncRead + Unpin + Send);
+type Writer<'a> = &'a mut (dyn AsyncWrite + Unpin + Send);
+
+/// Traits downstream code implements, or holds as trait objects.
+fn object_safety(_: &dyn MTKPort, _: &dyn DAProtocol) {}
+
+fn is_send<T: Send>() {}
+
+/// Devices are moved across tasks, as the TUI and `KeepAlive` do.
+fn thread_safety() {
+    is_send::<Device>();
+    is_send::<DeviceBuilder>();
+    is_send::<KeepAlive>();
+    is_send::<Error>();
+
+    let _: fn(&KeepAlive) -> bool = KeepAlive::has_failed;
+}
+
+/// Scatter files, parsed without a device.
+fn scatter(text: &str) -> Result<()> {
+    let entries: Vec<ScatterEntry> = parse_scatter(text)?;
+    let _: bool = entries[0].is_flashable();
+    Ok(())
+}
+
+/// Backup manifests, read and checked without a device.
+async fn backup(dir: &Path, info: &StorageInfo, partitions: &[Partition]) -> Result<()> {
+    let mut manifest = BackupManifest::new(0x0766, None, info, partitions);
+    let _: bool = manifest.record("boot", "boot.bin", "");
+    manifest.save(&dir.join(BACKUP_MANIFEST_NAME)).await?;
+
+    let manifest = BackupManifest::load(&dir.join(BACKUP_MANIFEST_NAME)).await?;
+    manifest.check_device(0x0766)?;
+    let _: Option<&BackupPartition> = manifest.partition("boot");
+    for part in manifest.restore_plan(DEFAULT_RESTORE_SKIP) {
+        part.verify(dir).await?;
+        part.check_sha256("")?;
+    }
+    Ok(())
+}
+
+/// DA repositories, opened without a device.
+async fn da_repository(dir: &Path) -> Result<()> {
+    let mut repo = DaRepository::open(dir).await?;
+    let _: Option<PathBuf> = repo.find_for_hw_code(0x0766);
+    let _: Vec<&DaIndexEntry> = repo.candidates(0x0766);
+    let _: &Path = repo.dir();
+    let entry: DaIndexEntry = repo.import(&dir.join("DA.bin")).await?;
+    let _: (&String, &DAType, &String, &Vec<u16>) =
+        (&entry.file, &entry.da_type, &entry.da_id, &entry.hw_codes);
+    repo.set_preferred(0x0766, &entry.file).await?;
+    repo.set_preferred_for_all(&entry.file).await?;
+    let _ = repo.entries().count();
+    Ok(())
+}
+
+/// Error classification, matched on by downstream code.
+fn errors(e: &Error) {
+    let _: ErrorKind = e.kind();
+    let _: bool = e.is_retryable();
+    let _: bool = e.is_device_reported();
+    let _: bool = e.is_timeout();
+    let _: Option<usize> = e.bytes_read();
+    let _: Error = Error::read_failed(2, 4, "USB endpoint halted");
+    let _: Error = Error::read_timeout(0, 4);
+    if let Error::ReadFailed { read, expected, timed_out, reason } = e {
+        let _: (usize, usize, bool, &String) = (*read, *expected, *timed_out, reason);
+    }
+    if let Error::InputTooShort { expected, got } = e {
+        let _: (usize, usize) = (*expected, *got);
+    }
+    let status = XFlashError::from_code(0xC0020004);
+    let _: String = status.kind.name();
+    let _: bool = status.is_retryable() || status.is_security_violation();
+    let xml = XmlError::from_code(0xC0020004);
+    let _: Option<XFlashError> = xml.status();
+    let _: bool = xml.is_retryable() || xml.is_security_violation();
+}
+
+async fn ports(conn: &mut Connection, port: &mut dyn MTKPort) -> Result<()> {
+    let mut buf = [0u8; 4];
+    let _: usize = port.read_exact_timeout(&mut buf, Duration::from_millis(500)).await?;
+    let _: usize = conn.read_timeout(&mut buf, Duration::from_millis(500)).await?;
+    let _: Vec<Box<dyn MTKPort>> = find_mtk_ports().await;
+    let _: Option<Box<dyn MTKPort>> = find_mtk_port().await;
+    let _: Option<Box<dyn MTKPort>> = find_mtk_port_by_name("usb:1-1").await;
+    Ok(())
+}
+
+fn protocol(protocol: Box<dyn DAProtocol + Send>) -> Connection {
+    protocol.into_connection()
+}
+
+fn builder(builder: DeviceBuilder) -> Result<Device> {
+    let _: fn(DeviceBuilder, Box<dyn MTKPort>) -> DeviceBuilder = DeviceBuilder::with_mtk_port;
+    let _: fn(DeviceBuilder, Vec<u8>) -> DeviceBuilder = DeviceBuilder::with_da_data;
+    let _: fn(DeviceBuilder, Vec<u8>) -> DeviceBuilder = DeviceBuilder::with_preloader_data;
+    #[allow(deprecated)]
+    let _: fn(DeviceBuilder, Vec<u8>) -> DeviceBuilder = DeviceBuilder::with_preloader;
+    let _: fn(DeviceBuilder, Vec<u8>) -> DeviceBuilder = DeviceBuilder::with_prepatched_da;
+    let _: fn(DeviceBuilder, bool) -> DeviceBuilder = DeviceBuilder::with_verbose;
+    let _: fn(DeviceBuilder, bool) -> DeviceBuilder = DeviceBuilder::with_da_patching;
+    let _: fn(DeviceBuilder, bool) -> DeviceBuilder = DeviceBuilder::with_strict_da;
+    let _: fn(DeviceBuilder, Duration) -> DeviceBuilder = DeviceBuilder::with_bringup_timeout;
+    let _: fn(DeviceBuilder, ChecksumLevel) -> DeviceBuilder = DeviceBuilder::with_checksum_level;
+    let _: fn(DeviceBuilder, u32) -> DeviceBuilder = DeviceBuilder::with_baudrate;
+    let _: fn(DeviceBuilder, Vec<u8>) -> DeviceBuilder = DeviceBuilder::with_auth_data;
+    let _: fn(DeviceBuilder, RetryPolicy) -> DeviceBuilder = DeviceBuilder::with_retry_policy;
+    let _: fn(DeviceBuilder, u32) -> DeviceBuilder = DeviceBuilder::with_sej_base;
+
+    builder.with_host_info("host").build()
+}
+
+async fn session(dev: &mut Device) -> Result<()> {
+    dev.init().await?;
+    dev.set_da_data(Vec::new()).await?;
+    dev.enter_da_mode().await?;
+    dev.keepalive().await?;
+    dev.shutdown().await?;
+    dev.reboot(BootMode::Normal).await?;
+    dev.set_boot_mode(BootMode::AdbMeta).await?;
+
+    let _: bool = dev.validate_da_session().await;
+    let _: bool = dev.session_changed();
+    dev.confirm_session();
+    dev.set_read_only(true);
+    dev.set_skip_bad_blocks(true);
+
+    let _: Option<Duration> = dev.idle_time();
+    let _: Option<ConnectionStats> = dev.connection_stats();
+    let _: fn(&Connection) -> ConnectionStats = Connection::stats;
+    let _: fn(&mut Connection, RetryPolicy) = Connection::set_retry_policy;
+    let _: RetryPolicy = RetryPolicy { max_retries: 3, backoff: Duration::ZERO };
+    let _: u64 = RetryPolicy::none().max_retries as u64 + ConnectionStats::default().errors();
+    let _: Option<&BringupReport> = dev.last_bringup_report();
+    let _: Option<&OperationSummary> = dev.last_operation_summary();
+    let _: Option<Vec<u8>> = dev.export_patched_da();
+    Ok(())
+}
+
+async fn storage(dev: &mut Device, writer: Writer<'_>) -> Result<()> {
+    let _: Vec<Partition> = dev.get_partitions().await?;
+    let _: Vec<Partition> = dev.refresh_partitions().await?;
+    let frp: Option<Partition> = dev.find_partition_by_role(PartitionRole::Frp).await?;
+    let _: &[&str] = PartitionRole::Misc.candidates();
+    let _: Option<&Partition> = PartitionRole::Nvram.find(frp.as_slice());
+    let _ = (PartitionRole::Nvdata, PartitionRole::Seccfg);
+    let info: StorageInfo = dev.get_storage_info().await?;
+    let sections: &[(&str, PartitionKind)] = PartitionKind::sections_for(info.kind);
+    let _: PartitionKind = PartitionKind::from_str_for("boot1", info.kind)?;
+    let _: Option<u64> = info.section_size(sections[0].1);
+    info.check_range(0, 0x1000, sections[0].1)?;
+    let _: bool = info.overlaps_gpt(0, info.gpt_size(), sections[0].1);
+    let _: String = info.kind.to_string();
+    let _: EfuseMap = dev.read_efuse().await?;
+    let mut raw = RawXmlCommand::new("CMD:DA-VERSION").arg("key", "value");
+    raw.args.push((Some("adv".to_string()), "tag".to_string(), String::new()));
+    let _: (&str, &str) = (raw.cmd_name(), raw.version());
+    let _: String = dev.send_raw_xml(&raw).await?;
+    let _: MbrStatus = dev.check_mbr().await?;
+    dev.fix_mbr().await?;
+    dev.dump_gpt(writer, GptType::Pgpt).await?;
+
+    let _: LpMetadata = dev.read_lp_metadata("_a").await?;
+    let _: Option<bool> = dev.is_bootloader_locked().await?;
+    let _: Option<VbMeta> = dev.avb_vbmeta_for("boot").await?;
+
+    let _ = (StorageType::Nand, PartitionKind::Nand(NandPartition::Whole));
+    let _: [u8; 32] = NandExtension::default().to_bytes();
+    Ok(())
+}
+
+async fn transfers(
+    dev: &mut Device,
+    progress: Progress<'_>,
+    reader: Reader<'_>,
+    writer: Writer<'_>,
+) -> Result<()> {
+    let section = PartitionKind::Unknown;
+
+    dev.read_partition("boot", progress, writer).await?;
+    let _: Vec<u8> = dev.read_partition_to_vec("boot", 0, progress).await?;
+    dev.read_partition_from("boot", 0, progress, writer).await?;
+    dev.read_dynamic_partition("system_a", progress, writer).await?;
+    dev.write_partition("boot", reader, false, progress).await?;
+    dev.write_partition_verified("boot", reader, true, progress).await?;
+    dev.erase_partition("boot", progress).await?;
+
+    dev.read_offset(0, 0, section, progress, writer).await?;
+    let mut hashing = HashingWriter::new(writer);
+    dev.read_offset(0, 0, section, progress, &mut hashing).await?;
+    let _: String = hashing.finalize();
+    dev.write_offset(0, 0, reader, section, false, progress).await?;
+    dev.write_offset_verified(0, 0, reader, section, true, progress).await?;
+    dev.erase_offset(0, 0, section, false, progress).await?;
+
+    dev.download("boot", 0, reader, progress).await?;
+    dev.download_verified("boot", 0, reader, progress).await?;
+    dev.upload("boot", writer, progress).await?;
+    dev.format("boot", progress).await?;
+    dev.write_preloader(&[], false, progress).await?;
+    let _: Vec<u8> = dev.read_preloader().await?;
+    Ok(())
+}
+
+async fn memory(dev: &mut Device) -> Result<()> {
+    let _: Vec<u8> = dev.brom_read32(0, 4).await?;
+    dev.brom_write32(0, &[0; 4]).await?;
+
+    #[cfg(feature = "seccfg")]
+    let _: Option<Vec<u8>> = dev.set_seccfg_lock_state(penumbra::LockFlag::Unlock).await;
+    Ok(())
+}
+
+async fn extensions(
+    dev: &mut Device,
+    progress: Progress<'_>,
+    reader: Reader<'_>,
+    writer: Writer<'_>,
+) -> Result<()> {
+    let _: bool = dev.extensions_active();
+    let _: Option<&penumbra::exploit::ExploitReport> = dev.last_exploit_report();
+    let _: bool = dev.resume_extensions().await?;
+    dev.init_rpmb(Some(&[0; penumbra::RPMB_KEY_SIZE])).await?;
+    dev.read_rpmb(0, 1, writer, progress).await?;
+    dev.write_rpmb(0, penumbra::RPMB_FRAME_SIZE, reader, true, progress).await?;
+    dev.peek(0, 0, writer, progress).await
+}
+
+fn progress(event: ProgressEvent) {
+    let _: f64 = event.throughput();
+    let _: Option<Duration> = event.eta();
+    let _: bool = event.is_done();
+
+    let mut adapted = progress_fn(|_done: usize, _total: usize| {});
+    let _: Progress<'_> = &mut adapted;
+}
+
+fn parsers() {
+    let _: fn(&[u8]) -> Result<DAFile> = DAFile::parse_da;
+    let _: fn(&DAFile) -> Vec<(u16, String)> = DAFile::supported_hw_codes;
+    let _: fn(&DA) -> String = DA::entry_summary;
+    let _: fn(&[u8]) -> Result<VbMeta> = VbMeta::parse;
+    let _: fn(&[u8]) -> Result<LpMetadata> = LpMetadata::parse;
+    let _: fn(&[u8]) -> ContentKind = detect_content;
+    let _: fn(&[u8]) -> EfuseMap = EfuseMap::parse;
+    let _: fn(Vec<u8>) -> Result<penumbra::core::auth::AuthFile> =
+        penumbra::core::auth::AuthFile::parse;
+    let _: fn(&[u8]) -> Result<Vec<u8>> = penumbra::core::emi::parse_emi;
+    let _: fn(&[u8]) -> Option<u32> = penumbra::core::emi::emi_version;
+}
@@ -0,0 +1,232 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! BROM / preloader commands against scripted exchanges, played back by the mock port.
+//! The scripts follow the BROM command set, they aren't recorded from a device.
+use std::time::Duration;
+
+use penumbra::connection::Connection;
+use penumbra::connection::mock::{MockHandle, MockMTKPort, Script};
+use tokio::time::timeout;
+
+const DA1_ADDR: u32 = 0x0020_0000;
+
+fn connect(script: Script) -> (Connection, MockHandle) {
+    let port = MockMTKPort::new(script);
+    let handle = port.handle();
+    (Connection::new(Box::new(port)), handle)
+}
+
+#[tokio::test]
+async fn handshake() {
+    let script = Script::new()
+        .expect([0xA0])
+        .respond([0x5F])
+        .expect([0x0A])
+        .respond([0xF5])
+        .expect([0x50])
+        .respond([0xAF])
+        .expect([0x05])
+        .respond([0xFA]);
+    let (mut conn, handle) = connect(script);
+
+    conn.handshake().await.unwrap();
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn handshake_bad_answer() {
+    let script = Script::new().expect([0xA0]).respond([0x5F]).expect([0x0A]).respond([0x0A]);
+    let (mut conn, handle) = connect(script);
+
+    assert!(conn.handshake().await.is_err());
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn get_hw_code() {
+    // MT6768, status OK
+    let script = Script::new().echo([0xFD]).respond([0x07, 0x07]).respond([0x00, 0x00]);
+    let (mut conn, handle) = connect(script);
+
+    assert_eq!(conn.get_hw_code().await.unwrap(), 0x0707);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn get_hw_code_bad_status() {
+    let script = Script::new().echo([0xFD]).respond([0x07, 0x07]).respond([0x1D, 0x1D]);
+    let (mut conn, _) = connect(script);
+
+    assert!(conn.get_hw_code().await.is_err());
+}
+
+#[tokio::test]
+async fn send_da_and_jump() {
+    let da1 = vec![0xA5; 0x120];
+
+    let script = Script::new()
+        .echo([0xD7])
+        .echo(DA1_ADDR.to_be_bytes())
+        .echo((da1.len() as u32).to_be_bytes())
+        .echo(0x100u32.to_be_bytes())
+        .respond([0x00, 0x00])
+        .expect(&da1)
+        .respond([0x5A, 0x5A]) // Checksum
+        .respond([0x00, 0x00])
+        .echo([0xD5])
+        .echo(DA1_ADDR.to_be_bytes())
+        .respond([0x00, 0x00]);
+    let (mut conn, handle) = connect(script);
+
+    conn.send_da(&da1, da1.len() as u32, DA1_ADDR, 0x100).await.unwrap();
+    conn.jump_da(DA1_ADDR).await.unwrap();
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn send_da_rejected() {
+    // Bad signature, the BROM refuses the DA after receiving it
+    let script = Script::new()
+        .echo([0xD7])
+        .echo(DA1_ADDR.to_be_bytes())
+        .echo(0x10u32.to_be_bytes())
+        .echo(0u32.to_be_bytes())
+        .respond([0x00, 0x00])
+        .expect_any(0x10)
+        .respond([0x00, 0x00])
+        .respond([0x70, 0x17]);
+    let (mut conn, handle) = connect(script);
+
+    assert!(conn.send_da(&[0u8; 0x10], 0x10, DA1_ADDR, 0).await.is_err());
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn injected_failure() {
+    let script = Script::new().echo([0xFD]).fail("device unplugged");
+    let (mut conn, handle) = connect(script);
+
+    let err = conn.get_hw_code().await.unwrap_err();
+    assert!(err.to_string().contains("device unplugged"));
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn silent_device_times_out() {
+    let script = Script::new().expect([0xFD]).hang();
+    let (mut conn, _) = connect(script);
+
+    assert!(timeout(Duration::from_millis(50), conn.get_hw_code()).await.is_err());
+}
+
+#[tokio::test]
+async fn get_soc_id_unsupported() {
+    let script = Script::new().echo([0xE7]).hang().hang().hang();
+    let (mut conn, handle) = connect(script);
+
+    assert_eq!(conn.get_soc_id().await.unwrap(), None);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn get_soc_id_late_answer_is_drained() {
+    let late = [&[0x00, 0x00, 0x00, 0x04][..], &[0x11, 0x22, 0x33, 0x44], &[0x00, 0x00]].concat();
+    let script = Script::new()
+        .echo([0xE7])
+        .hang()
+        .hang()
+        .respond(&late)
+        .hang()
+        .echo([0xFD])
+        .respond([0x0B, 0x07, 0x00, 0x00]);
+    let (mut conn, handle) = connect(script);
+
+    assert_eq!(conn.get_soc_id().await.unwrap(), None);
+    assert_eq!(conn.get_hw_code().await.unwrap(), 0x0B07);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn get_soc_id_slow_answer() {
+    let soc_id = [0x5A; 32];
+    let script = Script::new()
+        .echo([0xE7])
+        .hang()
+        .respond([0x00, 0x00, 0x00, 0x20])
+        .respond(soc_id)
+        .respond([0x00, 0x00]);
+    let (mut conn, handle) = connect(script);
+
+    assert_eq!(conn.get_soc_id().await.unwrap(), Some(soc_id.to_vec()));
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn get_soc_id_oversized_length() {
+    let script = Script::new().echo([0xE7]).respond([0x00, 0x00, 0x00, 0x41]);
+    let (mut conn, handle) = connect(script);
+
+    let err = conn.get_soc_id().await.unwrap_err();
+    assert!(err.to_string().contains("invalid length of 65 bytes"), "{}", err);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn get_soc_id_bad_status() {
+    let script = Script::new()
+        .echo([0xE7])
+        .respond([0x00, 0x00, 0x00, 0x04])
+        .respond([0x11, 0x22, 0x33, 0x44])
+        .respond([0x10, 0x1C]);
+    let (mut conn, handle) = connect(script);
+
+    assert!(conn.get_soc_id().await.is_err());
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn get_meid_oversized_length() {
+    let script = Script::new().echo([0xE1]).respond([0xFF, 0xFF, 0xFF, 0xFF]);
+    let (mut conn, handle) = connect(script);
+
+    let err = conn.get_meid().await.unwrap_err();
+    assert!(err.to_string().contains("invalid length"), "{}", err);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn get_meid_slow_answer() {
+    let meid = [0xC3; 16];
+    let script = Script::new()
+        .echo([0xE1])
+        .hang()
+        .respond([0x00, 0x00, 0x00, 0x10])
+        .respond(meid)
+        .respond([0x00, 0x00]);
+    let (mut conn, handle) = connect(script);
+
+    assert_eq!(conn.get_meid().await.unwrap(), Some(meid.to_vec()));
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn get_soc_id_partial_length() {
+    let script = Script::new().echo([0xE7]).respond([0x00, 0x00]).hang();
+    let (mut conn, handle) = connect(script);
+
+    let err = conn.get_soc_id().await.unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(err.bytes_read(), Some(2));
+    handle.assert_done();
+}
+
+#[tokio::test]
+#[should_panic(expected = "write mismatch")]
+async fn mismatched_write_panics() {
+    let script = Script::new().echo([0xFC]);
+    let (mut conn, _) = connect(script);
+
+    conn.get_hw_code().await.ok();
+}
@@ -0,0 +1,459 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Device level flows against an XFlash DA that is already running, or against BROM up to
+//! the DA upload, played back by the mock port.
+use std::sync::{Arc, Mutex, Once};
+use std::thread::{self, ThreadId};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use penumbra::connection::mock::{MockHandle, MockMTKPort, Script, packet, status};
+use penumbra::connection::port::ConnectionType;
+use penumbra::core::preloader::preloader_partitions;
+use penumbra::core::storage::emmc::{EmmcInfo, EmmcPartition, EmmcStorage};
+use penumbra::core::storage::{Partition, PartitionKind, StorageType};
+use penumbra::da::protocol::packet_checksum;
+use penumbra::da::xflash::Cmd;
+use penumbra::{Device, DeviceBuilder};
+
+/// Size of an entry in the DA file entry table.
+const DA_ENTRY_SIZE: usize = 0xDC;
+
+/// A V5 DA file with one entry per hardware code. The entries have no regions, so they're
+/// all flagged as inconsistent.
+fn da_file(hw_codes: &[u16]) -> Vec<u8> {
+    let mut data = vec![0u8; 0x6C + hw_codes.len() * DA_ENTRY_SIZE];
+    data[..0x12].copy_from_slice(b"MTK_DOWNLOAD_AGENT");
+    data[0x20..0x32].copy_from_slice(b"MTK_AllInOne_DA_v5");
+    data[0x68] = hw_codes.len() as u8;
+    for (i, hw_code) in hw_codes.iter().enumerate() {
+        let entry = 0x6C + i * DA_ENTRY_SIZE;
+        data[entry..entry + 2].copy_from_slice(&0xDADAu16.to_le_bytes());
+        data[entry + 2..entry + 4].copy_from_slice(&hw_code.to_le_bytes());
+    }
+    data
+}
+
+/// Warnings logged so far, along with the thread that logged them. The logger is shared by
+/// all tests of the binary, so each test only looks at its own thread.
+struct Warnings(Mutex<Vec<(ThreadId, String)>>);
+
+static WARNINGS: Warnings = Warnings(Mutex::new(Vec::new()));
+
+impl Log for Warnings {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = record.args().to_string();
+            self.0.lock().unwrap().push((thread::current().id(), line));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Warnings logged by the current test.
+fn warnings() -> Vec<String> {
+    let id = thread::current().id();
+    let lines = WARNINGS.0.lock().unwrap();
+    lines.iter().filter(|(thread, _)| *thread == id).map(|(_, line)| line.clone()).collect()
+}
+
+fn record_warnings() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        log::set_logger(&WARNINGS).unwrap();
+        log::set_max_level(LevelFilter::Warn);
+    });
+}
+
+/// ReadData and WriteData parameters for `len` bytes at `addr` of an eMMC partition.
+fn flash_param(part: &Partition, addr: u64, len: usize) -> Vec<u8> {
+    let mut param = vec![0u8; 56];
+    param[0..4].copy_from_slice(&(StorageType::Emmc as u32).to_le_bytes());
+    param[4..8].copy_from_slice(&part.kind.as_u32().to_le_bytes());
+    param[8..16].copy_from_slice(&(part.address + addr).to_le_bytes());
+    param[16..24].copy_from_slice(&(len as u64).to_le_bytes());
+    param
+}
+
+trait XFlashScript {
+    /// The host sends a packet, which the DA acknowledges with a status.
+    fn send(self, data: &[u8]) -> Self;
+    fn cmd(self, cmd: Cmd) -> Self;
+    /// A DeviceCtrl command the DA answers with `data`.
+    fn devctrl(self, cmd: Cmd, data: &[u8]) -> Self;
+    /// The host reads `data` at `addr` of `part`, in a single packet.
+    fn read_flash(self, part: &Partition, addr: u64, data: &[u8]) -> Self;
+    /// The host writes `data` at `addr` of `part`, in a single packet.
+    fn write_flash(self, part: &Partition, addr: u64, data: &[u8]) -> Self;
+}
+
+impl XFlashScript for Script {
+    fn send(self, data: &[u8]) -> Self {
+        self.expect(packet(data)).respond(status(0))
+    }
+
+    fn cmd(self, cmd: Cmd) -> Self {
+        self.send(&(cmd as u32).to_le_bytes())
+    }
+
+    fn devctrl(self, cmd: Cmd, data: &[u8]) -> Self {
+        self.cmd(Cmd::DeviceCtrl).cmd(cmd).respond(packet(data)).respond(status(0))
+    }
+
+    fn read_flash(self, part: &Partition, addr: u64, data: &[u8]) -> Self {
+        self.cmd(Cmd::ReadData)
+            .send(&flash_param(part, addr, data.len()))
+            .respond(status(0))
+            .respond(packet(data))
+            .send(&[0u8; 4])
+    }
+
+    fn write_flash(self, part: &Partition, addr: u64, data: &[u8]) -> Self {
+        self.cmd(Cmd::WriteData)
+            .send(&flash_param(part, addr, data.len()))
+            .expect(packet(&0u32.to_le_bytes()))
+            .expect(packet(&packet_checksum(data).to_le_bytes()))
+            .expect(packet(data))
+            .respond(status(0))
+            .respond(status(0))
+    }
+}
+
+/// Resuming a running DA: the keepalive, then the DA is asked for the hardware code and
+/// the random ID.
+fn resume_steps(hw_code: u16) -> Script {
+    let lengths = [0x0010_0000u32.to_le_bytes(), 0x0010_0000u32.to_le_bytes()].concat();
+    Script::new()
+        .devctrl(Cmd::GetPacketLength, &lengths)
+        .devctrl(Cmd::GetChipId, &hw_code.to_le_bytes())
+        .devctrl(Cmd::GetRandomId, &[0xA5; 16])
+}
+
+/// Resuming a running DA. The partition table can't be read, which only gets a warning.
+fn resume(hw_code: u16) -> Script {
+    (0..16).fold(resume_steps(hw_code), |script, _| script.fail("no storage"))
+}
+
+fn device(hw_codes: &[u16], script: Script) -> (Device, MockHandle) {
+    let port = MockMTKPort::new(script).with_connection_type(ConnectionType::Da);
+    let handle = port.handle();
+    let device = DeviceBuilder::default()
+        .with_mtk_port(Box::new(port))
+        .with_da_data(da_file(hw_codes))
+        .build()
+        .unwrap();
+    (device, handle)
+}
+
+#[tokio::test]
+async fn resume_picks_the_entry_of_the_chip() {
+    let (mut dev, _) = device(&[0x1234, 0x4321], resume(0x4321));
+
+    dev.init().await.unwrap();
+    let protocol = dev.get_protocol().unwrap();
+    assert_eq!(protocol.get_da().hw_code, 0x4321);
+    assert_eq!(dev.dev_info.hw_code().await, 0x4321);
+}
+
+#[tokio::test]
+async fn resume_keeps_the_first_entry_for_an_unknown_chip() {
+    let (mut dev, _) = device(&[0x1234, 0x4321], resume(0x5555));
+
+    dev.init().await.unwrap();
+    assert_eq!(dev.get_protocol().unwrap().get_da().hw_code, 0x1234);
+}
+
+#[tokio::test]
+async fn only_the_entry_of_the_chip_is_checked() {
+    record_warnings();
+    let (mut dev, _) = device(&[0x1234, 0x4321], resume(0x4321));
+
+    dev.init().await.unwrap();
+    let inconsistent: Vec<String> =
+        warnings().into_iter().filter(|line| line.contains("looks inconsistent")).collect();
+    assert!(!inconsistent.is_empty());
+    assert!(inconsistent.iter().all(|line| line.contains("0x4321")), "{:?}", inconsistent);
+}
+
+#[tokio::test]
+async fn entries_of_other_chips_are_not_checked() {
+    record_warnings();
+    let (mut dev, _) = device(&[0x1234], resume(0x4321));
+
+    dev.init().await.unwrap();
+    assert!(!warnings().iter().any(|line| line.contains("looks inconsistent")));
+}
+
+/// MT6765, whose DA entry is found under its commercial code.
+const MT6765: u16 = 0x0766;
+
+/// A V5 DA file with a single MT6765 entry, loading DA1 and DA2 at the given addresses.
+fn da_file_loading_at(da1_addr: u32, da2_addr: u32) -> Vec<u8> {
+    let mut data = da_file(&[0x6765]);
+    let entry = 0x6C;
+    data[entry + 0x12..entry + 0x14].copy_from_slice(&3u16.to_le_bytes());
+
+    let regions = [(0x40u32, 0, 0), (0x200, da1_addr, 0x100), (0x300, da2_addr, 0x100)];
+    for (i, (len, addr, sig_len)) in regions.into_iter().enumerate() {
+        let fields = [data.len() as u32, len, addr, len - sig_len, sig_len];
+        let table = entry + 0x14 + i * 20;
+        for (j, field) in fields.iter().enumerate() {
+            data[table + j * 4..table + j * 4 + 4].copy_from_slice(&field.to_le_bytes());
+        }
+        data.resize(data.len() + len as usize, 0x5A);
+    }
+    data
+}
+
+/// BROM answers up to the identification of the chip, right before a DA is sent.
+fn identify(hw_code: u16) -> Script {
+    Script::new()
+        .expect([0xA0])
+        .respond([0x5F])
+        .expect([0x0A])
+        .respond([0xF5])
+        .expect([0x50])
+        .respond([0xAF])
+        .expect([0x05])
+        .respond([0xFA])
+        .echo([0xE7])
+        .respond(0x20u32.to_be_bytes())
+        .respond([0x5A; 0x20])
+        .respond([0x00, 0x00])
+        .echo([0xE1])
+        .respond(0x10u32.to_be_bytes())
+        .respond([0xC3; 0x10])
+        .respond([0x00, 0x00])
+        .echo([0xFD])
+        .respond(hw_code.to_be_bytes())
+        .respond([0x00, 0x00])
+        .echo([0xD8])
+        .respond(0u32.to_be_bytes())
+        .respond([0x00, 0x00])
+}
+
+/// A device in BROM mode. The DA is checked on init, but only sent on entering DA mode.
+fn brom_device(da: Vec<u8>, strict: bool) -> (Device, MockHandle) {
+    let port = MockMTKPort::new(identify(MT6765));
+    let handle = port.handle();
+    let device = DeviceBuilder::default()
+        .with_mtk_port(Box::new(port))
+        .with_da_data(da)
+        .with_strict_da(strict)
+        .build()
+        .unwrap();
+    (device, handle)
+}
+
+fn load_address_warnings() -> Vec<String> {
+    warnings().into_iter().filter(|line| line.contains("outside of the chipset memory")).collect()
+}
+
+#[tokio::test]
+async fn da_loaded_in_memory_passes() {
+    record_warnings();
+    let (mut dev, handle) = brom_device(da_file_loading_at(0x0020_0000, 0x4000_0000), true);
+
+    dev.init().await.unwrap();
+    assert_eq!(load_address_warnings(), Vec::<String>::new());
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn da_loaded_out_of_memory_is_refused_when_strict() {
+    record_warnings();
+    // DA2 in the peripheral registers
+    let (mut dev, handle) = brom_device(da_file_loading_at(0x0020_0000, 0x1000_0000), true);
+
+    let err = dev.init().await.unwrap_err();
+    assert!(err.to_string().contains("refusing to upload it"), "{}", err);
+    let warnings = load_address_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("region 2 loads at 0x10000000-0x10000300"), "{:?}", warnings);
+
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn da_loaded_out_of_memory_only_warns_by_default() {
+    record_warnings();
+    // DA1 in the BROM
+    let (mut dev, handle) = brom_device(da_file_loading_at(0x0000_0000, 0x4000_0000), false);
+
+    dev.init().await.unwrap();
+    handle.assert_done();
+    let warnings = load_address_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("region 1 loads at 0x00000000-0x00000200"), "{:?}", warnings);
+}
+
+/// Size of the boot partitions of the eMMC the preloader tests run on.
+const BOOT_SIZE: usize = 0x100;
+/// Start of the FILE_INFO header of a preloader: `MMM`, version 1, header size 0x38, type 0.
+const FILE_INFO_HEADER: &[u8] = b"MMM\x01\x38\x00\x00\x00";
+
+/// A full boot image of 0x80 bytes: the boot header, then a 0x40 bytes preloader filled
+/// with `fill`.
+fn boot_image(fill: u8) -> Vec<u8> {
+    let mut image = vec![fill; 0x80];
+    image[..9].copy_from_slice(b"EMMC_BOOT");
+    image[0x20..0x28].copy_from_slice(FILE_INFO_HEADER);
+    image[0x40..0x44].copy_from_slice(&0x40u32.to_le_bytes());
+    image
+}
+
+/// Contents of a boot partition holding `image`.
+fn boot_partition(image: &[u8]) -> Vec<u8> {
+    let mut data = image.to_vec();
+    data.resize(BOOT_SIZE, 0);
+    data
+}
+
+/// A device resumed on an eMMC with the given boot partitions, followed by `script`.
+/// The DA reports no storage while resuming, it's then set up by hand.
+async fn emmc_device(
+    script: impl FnOnce(&Partition, &Partition) -> Script,
+) -> (Device, MockHandle) {
+    let storage = Arc::new(EmmcStorage {
+        info: EmmcInfo {
+            kind: 1,
+            block_size: 0x200,
+            boot1_size: BOOT_SIZE as u64,
+            boot2_size: BOOT_SIZE as u64,
+            rpmb_size: 0,
+            gp1_size: 0,
+            gp2_size: 0,
+            gp3_size: 0,
+            gp4_size: 0,
+            user_size: 0x100_0000,
+            cid: Vec::new(),
+            fwver: 0,
+        },
+    });
+    let [boot1, boot2] = preloader_partitions(storage.as_ref());
+
+    let script = resume_steps(0x1234)
+        .devctrl(Cmd::GetEmmcInfo, &[0; 4])
+        .devctrl(Cmd::GetUfsInfo, &[0; 4])
+        .devctrl(Cmd::GetNandInfo, &[0; 4])
+        .then(script(&boot1, &boot2));
+    let (mut dev, handle) = device(&[0x1234], script);
+
+    dev.init().await.unwrap();
+    dev.dev_info.set_storage(storage).await;
+    dev.dev_info.set_partitions(vec![boot1, boot2]).await;
+    (dev, handle)
+}
+
+/// What `write_preloader` reads before writing anything: BOOT1 as a whole to find the
+/// preloader, then the original contents of every partition it's about to write.
+fn preloader_originals(boot1: &Partition, boot2: &Partition, len: usize) -> Script {
+    let old1 = boot_partition(&boot_image(0x11));
+    let old2 = boot_partition(&boot_image(0x22));
+    Script::new().read_flash(boot1, 0, &old1).read_flash(boot1, 0, &old1[..len]).read_flash(
+        boot2,
+        0,
+        &old2[..len],
+    )
+}
+
+/// A WriteData command the DA refuses.
+fn refused_write() -> Script {
+    Script::new()
+        .expect(packet(&(Cmd::WriteData as u32).to_le_bytes()))
+        .respond(status(0xC001_0004))
+}
+
+#[tokio::test]
+async fn preloader_is_mirrored() {
+    let new = boot_image(0x33);
+    let (mut dev, handle) = emmc_device(|boot1, boot2| {
+        preloader_originals(boot1, boot2, new.len())
+            .write_flash(boot1, 0, &new)
+            .read_flash(boot1, 0, &new)
+            .write_flash(boot2, 0, &new)
+            .read_flash(boot2, 0, &new)
+    })
+    .await;
+
+    dev.write_preloader(&new, true, &mut |_| {}).await.unwrap();
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn failed_boot2_write_restores_both_copies() {
+    let new = boot_image(0x33);
+    let old1 = boot_image(0x11);
+    let old2 = boot_image(0x22);
+    let (mut dev, handle) = emmc_device(|boot1, boot2| {
+        preloader_originals(boot1, boot2, new.len())
+            .write_flash(boot1, 0, &new)
+            .read_flash(boot1, 0, &new)
+            .then(refused_write())
+            .write_flash(boot1, 0, &old1)
+            .read_flash(boot1, 0, &old1)
+            .write_flash(boot2, 0, &old2)
+            .read_flash(boot2, 0, &old2)
+    })
+    .await;
+
+    let err = dev.write_preloader(&new, true, &mut |_| {}).await.unwrap_err();
+    assert!(!err.to_string().contains("could not be restored"), "{}", err);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn failed_verification_restores_boot1() {
+    let new = boot_image(0x33);
+    let old1 = boot_image(0x11);
+    let mut corrupted = new.clone();
+    corrupted[0x50] ^= 0xFF;
+    let (mut dev, handle) = emmc_device(|boot1, boot2| {
+        preloader_originals(boot1, boot2, new.len())
+            .write_flash(boot1, 0, &new)
+            .read_flash(boot1, 0, &corrupted)
+            .write_flash(boot1, 0, &old1)
+            .read_flash(boot1, 0, &old1)
+    })
+    .await;
+
+    let err = dev.write_preloader(&new, true, &mut |_| {}).await.unwrap_err();
+    assert!(err.to_string().contains("Verification of preloader failed"), "{}", err);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn failed_restore_is_reported() {
+    let new = boot_image(0x33);
+    let (mut dev, handle) = emmc_device(|boot1, boot2| {
+        preloader_originals(boot1, boot2, new.len())
+            .write_flash(boot1, 0, &new)
+            .read_flash(boot1, 0, &new)
+            .then(refused_write())
+            .then(refused_write())
+    })
+    .await;
+
+    let err = dev.write_preloader(&new, true, &mut |_| {}).await.unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("preloader could not be restored"), "{}", message);
+    assert!(message.contains("Do not reboot"), "{}", message);
+    handle.assert_done();
+}
+
+#[tokio::test]
+async fn erasing_the_gpt_is_refused_unless_allowed() {
+    let (mut dev, handle) = emmc_device(|_, _| Script::new()).await;
+    let user = PartitionKind::Emmc(EmmcPartition::User);
+
+    let err = dev.erase_offset(0, 0x200, user, false, &mut |_| {}).await.unwrap_err();
+    assert!(err.to_string().contains("primary GPT"), "{}", err);
+    // Refused before anything is sent to the DA
+    handle.assert_done();
+}
@@ -0,0 +1,134 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Random device answers, played back by the mock port. Whatever the device sends,
+//! the host has to give up with an error instead of panicking.
+use std::future::Future;
+use std::time::Duration;
+
+use penumbra::Result;
+use penumbra::connection::Connection;
+use penumbra::connection::mock::{MockMTKPort, Script};
+use penumbra::core::devinfo::DeviceInfo;
+use penumbra::da::{DA, DAProtocol, DAType, Legacy, XFlash, Xml};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::time::timeout;
+
+/// Seeds tried for each command, a failing one can be replayed on its own.
+const ROUNDS: u64 = 256;
+/// Generous, the mock never makes the host wait.
+const OP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Header magic of XFlash and XML packets.
+const MAGIC: [u8; 4] = [0xEF, 0xEE, 0xEE, 0xFE];
+const ADDR: u32 = 0x1000_0000;
+
+/// A device answer: mostly packets with a random payload, sometimes announcing a wrong
+/// length, and sometimes plain garbage.
+fn frame(rng: &mut StdRng) -> Vec<u8> {
+    let mut payload = vec![0u8; rng.random_range(0..64)];
+    rng.fill(&mut payload[..]);
+    if rng.random_bool(0.25) {
+        return payload;
+    }
+
+    let len = if rng.random_bool(0.1) { rng.random::<u32>() } else { payload.len() as u32 };
+    let mut frame = MAGIC.to_vec();
+    frame.extend_from_slice(&rng.random::<u32>().to_le_bytes());
+    frame.extend_from_slice(&len.to_le_bytes());
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+/// Random answers for one round. Once they run out, the port keeps failing,
+/// like a device that went away.
+fn script(seed: u64) -> Script {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut noise = Vec::new();
+    for _ in 0..rng.random_range(1..16) {
+        noise.extend(frame(&mut rng));
+    }
+
+    (0..16).fold(Script::new().noise(noise), |script, _| script.fail("end of the random answers"))
+}
+
+/// Runs `op` against random answers for each seed.
+async fn fuzz<F, Fut, T>(name: &str, op: F)
+where
+    F: Fn(Connection) -> Fut,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    for seed in 0..ROUNDS {
+        let conn = Connection::new(Box::new(MockMTKPort::new(script(seed))));
+        let task = tokio::spawn(timeout(OP_TIMEOUT, op(conn)));
+        match task.await {
+            Ok(Ok(_)) => {}
+            Ok(Err(_)) => panic!("{} hung on seed {}", name, seed),
+            Err(e) => panic!("{} panicked on seed {}: {}", name, seed, e),
+        }
+    }
+}
+
+fn da(da_type: DAType) -> DA {
+    DA {
+        da_type,
+        regions: Vec::new(),
+        magic: 0xDADA,
+        hw_code: 0x6768,
+        hw_sub_code: 0xCA00,
+        issues: Vec::new(),
+    }
+}
+
+fn xflash(conn: Connection) -> XFlash {
+    XFlash::new(conn, da(DAType::V5), DeviceInfo::new(), None, false)
+}
+
+fn xml(conn: Connection) -> Xml {
+    Xml::new(conn, da(DAType::V6), DeviceInfo::new(), false)
+}
+
+fn legacy(conn: Connection) -> Legacy {
+    Legacy::new(conn, da(DAType::Legacy), DeviceInfo::new(), None)
+}
+
+#[tokio::test]
+async fn brom_commands() {
+    fuzz("handshake", |mut conn| async move { conn.handshake().await }).await;
+    fuzz("get_hw_code", |mut conn| async move { conn.get_hw_code().await }).await;
+    fuzz("get_hw_sw_ver", |mut conn| async move { conn.get_hw_sw_ver().await }).await;
+    fuzz("get_target_config", |mut conn| async move { conn.get_target_config().await }).await;
+    fuzz("get_soc_id", |mut conn| async move { conn.get_soc_id().await }).await;
+    fuzz("get_meid", |mut conn| async move { conn.get_meid().await }).await;
+    fuzz("read32", |mut conn| async move { conn.read32(ADDR, 4).await }).await;
+    fuzz("send_da", |mut conn| async move { conn.send_da(&[0x5A; 0x40], 0x40, ADDR, 0).await })
+        .await;
+}
+
+#[tokio::test]
+async fn xflash_commands() {
+    fuzz("XFlash status", |conn| async move { xflash(conn).get_status().await }).await;
+    fuzz("XFlash data", |conn| async move { xflash(conn).read_data().await }).await;
+    fuzz("XFlash read32", |conn| async move { xflash(conn).read32(ADDR).await }).await;
+    fuzz("XFlash usb speed", |conn| async move { xflash(conn).get_usb_speed().await }).await;
+    fuzz("XFlash random id", |conn| async move { xflash(conn).get_random_id().await }).await;
+    fuzz("XFlash partitions", |conn| async move { xflash(conn).get_partitions().await }).await;
+}
+
+#[tokio::test]
+async fn xml_commands() {
+    fuzz("XML data", |conn| async move { xml(conn).read_data().await }).await;
+    fuzz("XML read32", |conn| async move { xml(conn).read32(ADDR).await }).await;
+    fuzz("XML usb speed", |conn| async move { xml(conn).get_usb_speed().await }).await;
+    fuzz("XML random id", |conn| async move { xml(conn).get_random_id().await }).await;
+    fuzz("XML partitions", |conn| async move { xml(conn).get_partitions().await }).await;
+}
+
+#[tokio::test]
+async fn legacy_commands() {
+    fuzz("Legacy read32", |conn| async move { legacy(conn).read32(ADDR).await }).await;
+    fuzz("Legacy usb speed", |conn| async move { legacy(conn).get_usb_speed().await }).await;
+    fuzz("Legacy random id", |conn| async move { legacy(conn).get_random_id().await }).await;
+}
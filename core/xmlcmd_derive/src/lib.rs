@@ -65,15 +65,15 @@ pub fn xmlcmd_derive(input: TokenStream) -> TokenStream {
         }
 
         impl XmlCommand for #name {
-            fn cmd_name(&self) -> &'static str {
+            fn cmd_name(&self) -> &str {
                 #cmd_name
             }
 
-            fn version(&self) -> &'static str {
+            fn version(&self) -> &str {
                 #cmd_version
             }
 
-            fn args(&self) -> Vec<(Option<&'static str>, &'static str, String)> {
+            fn args(&self) -> Vec<(Option<&str>, &str, String)> {
                 vec![
                     #(#arg_entries),*
                 ]
@@ -2,21 +2,24 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+use std::collections::VecDeque;
 use std::fs::read;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
 
 use anyhow::Result;
 use penumbra::da::DAFile;
-use ratatui::crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 use ratatui::style::Style;
 use ratatui::widgets::Block;
 use ratatui::{DefaultTerminal, Frame};
 
 use crate::cli::CliArgs;
-use crate::components::ThemedWidgetRef;
 use crate::components::dialog::{Dialog, DialogBuilder};
+use crate::components::{LogPane, ThemedWidgetRef};
 use crate::config::AntumbraConfig;
+use crate::logger::{LogBuffer, log_buffer};
 use crate::pages::{DevicePage, OptionsPage, Page, WelcomePage};
 use crate::themes::{Theme, load_themes};
 
@@ -37,6 +40,10 @@ pub struct AppCtx {
     config: AntumbraConfig,
     pub theme: Theme,
     pub dialog: Option<Dialog>,
+    /// Log lines of the whole process, background tasks included.
+    pub logs: LogBuffer,
+    /// Log pane shared by the pages, so that it stays open across them.
+    pub log_pane: LogPane,
 }
 
 pub struct App {
@@ -44,6 +51,94 @@ pub struct App {
     pub context: AppCtx,
 }
 
+/// Maximum number of events a page handles per frame.
+/// Anything left over is handled on the next tick, so that a flood of events
+/// from a background task can never starve redraws.
+pub const MAX_EVENTS_PER_FRAME: usize = 64;
+
+/// Number of events an event queue holds before dropping progress events.
+const EVENT_QUEUE_CAPACITY: usize = 256;
+
+/// An event sent from a background task to a page.
+pub trait PageEvent: Send {
+    /// Returns the task key if the event only reports progress, `None` otherwise.
+    /// Progress events are coalesced (only the latest per task is kept) and may be dropped
+    /// when the queue is full. Any other event is always delivered, in order.
+    fn progress_key(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Creates a bounded event queue, used by pages to receive events from background tasks.
+///
+/// Policy:
+/// * Consecutive progress events for the same task are coalesced, keeping only the latest.
+/// * When the queue is full, the oldest progress event is dropped to make room.
+/// * Non progress events (completion, errors...) are never dropped, even if the queue is full.
+pub fn event_queue<E: PageEvent>() -> (EventSender<E>, EventReceiver<E>) {
+    let queue = Arc::new(Mutex::new(VecDeque::with_capacity(EVENT_QUEUE_CAPACITY)));
+    (EventSender { queue: queue.clone() }, EventReceiver { queue })
+}
+
+fn lock_queue<E>(queue: &Mutex<VecDeque<E>>) -> MutexGuard<'_, VecDeque<E>> {
+    // A panicking sender can't leave the queue in an invalid state, so recover from poisoning
+    queue.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The sending half of an [`event_queue`]. Sending never blocks.
+pub struct EventSender<E> {
+    queue: Arc<Mutex<VecDeque<E>>>,
+}
+
+impl<E> Clone for EventSender<E> {
+    fn clone(&self) -> Self {
+        Self { queue: self.queue.clone() }
+    }
+}
+
+impl<E: PageEvent> EventSender<E> {
+    pub fn send(&self, event: E) {
+        let mut queue = lock_queue(&self.queue);
+
+        if let Some(key) = event.progress_key() {
+            // Only look at the trailing run of progress events, to never reorder
+            // progress around other events.
+            let pending = queue
+                .iter_mut()
+                .rev()
+                .take_while(|e| e.progress_key().is_some())
+                .find(|e| e.progress_key() == Some(key));
+
+            if let Some(pending) = pending {
+                *pending = event;
+                return;
+            }
+        }
+
+        if queue.len() >= EVENT_QUEUE_CAPACITY
+            && let Some(oldest) = queue.iter().position(|e| e.progress_key().is_some())
+        {
+            queue.remove(oldest);
+        }
+
+        queue.push_back(event);
+    }
+}
+
+/// The receiving half of an [`event_queue`].
+pub struct EventReceiver<E> {
+    queue: Arc<Mutex<VecDeque<E>>>,
+}
+
+impl<E: PageEvent> EventReceiver<E> {
+    /// Takes up to `max` pending events, in order.
+    pub fn drain(&mut self, max: usize) -> Vec<E> {
+        let mut queue = lock_queue(&self.queue);
+        let count = queue.len().min(max);
+        queue.drain(..count).collect()
+    }
+}
+
 pub struct Loader {
     path: PathBuf,
     file: DAFile,
@@ -146,6 +241,15 @@ impl AppCtx {
         }
     }
 
+    pub fn da_patching(&self) -> bool {
+        self.config.da_patching
+    }
+
+    pub fn set_da_patching(&mut self, enabled: bool) {
+        self.config.da_patching = enabled;
+        self.config.save().ok();
+    }
+
     pub fn config(&mut self) -> &mut AntumbraConfig {
         &mut self.config
     }
@@ -170,6 +274,8 @@ impl Default for AppCtx {
             config,
             theme,
             dialog: None,
+            logs: log_buffer(),
+            log_pane: LogPane::new(),
         }
     }
 }
@@ -205,8 +311,18 @@ impl App {
     }
 
     async fn handle_events(&mut self) -> Result<()> {
-        if event::poll(Duration::from_millis(100))?
-            && let Event::Key(key) = event::read()?
+        if event::poll(Duration::from_millis(100))? {
+            self.handle_event(event::read()?).await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, event: Event) {
+        if let Event::Key(key) = event
+            // Windows reports both press and release, only act once per key.
+            // Pages needing Release/Repeat should get a dedicated path instead.
+            && key.kind == KeyEventKind::Press
         {
             // Force exit: [Ctrl + Delete]
             if key.code == KeyCode::Delete && key.modifiers.contains(KeyModifiers::CONTROL) {
@@ -226,13 +342,11 @@ impl App {
                     }
                     _ => {}
                 }
-                return Ok(());
+                return;
             }
 
             self.current_page.handle_input(&mut self.context, key).await;
         }
-
-        Ok(())
     }
 
     fn draw(&mut self, frame: &mut Frame<'_>) {
@@ -264,3 +378,180 @@ impl App {
         self.current_page.on_enter(&mut self.context).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use ratatui::crossterm::event::KeyEvent;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum TestEvent {
+        Progress { task: u32, done: u64 },
+        Done(u32),
+    }
+
+    impl PageEvent for TestEvent {
+        fn progress_key(&self) -> Option<u32> {
+            match self {
+                TestEvent::Progress { task, .. } => Some(*task),
+                TestEvent::Done(_) => None,
+            }
+        }
+    }
+
+    use TestEvent::{Done, Progress};
+
+    #[test]
+    fn progress_is_coalesced_per_task() {
+        let (tx, mut rx) = event_queue();
+        for done in 0..1000 {
+            tx.send(Progress { task: 1, done });
+            tx.send(Progress { task: 2, done: done * 2 });
+        }
+
+        let events = rx.drain(MAX_EVENTS_PER_FRAME);
+        assert_eq!(events, vec![Progress { task: 1, done: 999 }, Progress { task: 2, done: 1998 }]);
+    }
+
+    #[test]
+    fn progress_is_never_moved_across_other_events() {
+        let (tx, mut rx) = event_queue();
+        tx.send(Progress { task: 1, done: 10 });
+        tx.send(Done(1));
+        tx.send(Progress { task: 1, done: 20 });
+        tx.send(Progress { task: 1, done: 30 });
+
+        let events = rx.drain(MAX_EVENTS_PER_FRAME);
+        assert_eq!(events, vec![Progress { task: 1, done: 10 }, Done(1), Progress {
+            task: 1,
+            done: 30
+        }]);
+    }
+
+    #[test]
+    fn oldest_progress_is_dropped_when_full() {
+        let (tx, mut rx) = event_queue();
+        tx.send(Done(0));
+        for task in 1..=EVENT_QUEUE_CAPACITY as u32 {
+            tx.send(Progress { task, done: 0 });
+        }
+
+        let events = rx.drain(usize::MAX);
+        assert_eq!(events.len(), EVENT_QUEUE_CAPACITY);
+        assert_eq!(events[0], Done(0));
+        // Task 1 made room for the last one
+        assert_eq!(events[1], Progress { task: 2, done: 0 });
+        assert_eq!(events.last(), Some(&Progress { task: EVENT_QUEUE_CAPACITY as u32, done: 0 }));
+    }
+
+    #[test]
+    fn other_events_are_never_dropped() {
+        let (tx, mut rx) = event_queue();
+        for task in 0..EVENT_QUEUE_CAPACITY as u32 * 2 {
+            tx.send(Done(task));
+        }
+
+        let events = rx.drain(usize::MAX);
+        assert_eq!(events.len(), EVENT_QUEUE_CAPACITY * 2);
+        assert!(events.iter().enumerate().all(|(i, e)| *e == Done(i as u32)));
+    }
+
+    #[test]
+    fn drain_is_bounded_per_frame() {
+        let (tx, mut rx) = event_queue();
+        for task in 0..100 {
+            tx.send(Done(task));
+        }
+
+        assert_eq!(rx.drain(MAX_EVENTS_PER_FRAME).len(), MAX_EVENTS_PER_FRAME);
+        let rest = rx.drain(MAX_EVENTS_PER_FRAME);
+        assert_eq!(rest.len(), 100 - MAX_EVENTS_PER_FRAME);
+        assert_eq!(rest.first(), Some(&Done(MAX_EVENTS_PER_FRAME as u32)));
+        assert!(rx.drain(MAX_EVENTS_PER_FRAME).is_empty());
+    }
+
+    /// Page recording the keys it's given.
+    struct KeyLog(Arc<Mutex<Vec<KeyCode>>>);
+
+    #[async_trait::async_trait]
+    impl Page for KeyLog {
+        fn render(&mut self, _frame: &mut Frame<'_>, _ctx: &mut AppCtx) {}
+
+        async fn handle_input(&mut self, _ctx: &mut AppCtx, key: KeyEvent) {
+            self.0.lock().unwrap().push(key.code);
+        }
+    }
+
+    /// App on a [`KeyLog`] page, without touching the user's configuration.
+    fn app() -> (App, Arc<Mutex<Vec<KeyCode>>>) {
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let context = AppCtx {
+            loader: None,
+            preloader: None,
+            exit: false,
+            current_page_id: AppPage::default(),
+            next_page_id: None,
+            config: AntumbraConfig::default(),
+            theme: Theme::default(),
+            dialog: None,
+            logs: log_buffer(),
+            log_pane: LogPane::new(),
+        };
+        (App { current_page: Box::new(KeyLog(keys.clone())), context }, keys)
+    }
+
+    /// A key press followed by its release, as reported on Windows.
+    async fn tap(app: &mut App, code: KeyCode) {
+        for kind in [KeyEventKind::Press, KeyEventKind::Release] {
+            let key = KeyEvent::new_with_kind(code, KeyModifiers::NONE, kind);
+            app.handle_event(Event::Key(key)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_get_each_key_once() {
+        let (mut app, keys) = app();
+        for code in [KeyCode::Down, KeyCode::Down, KeyCode::Enter, KeyCode::Char('q')] {
+            tap(&mut app, code).await;
+        }
+
+        let keys = keys.lock().unwrap().clone();
+        assert_eq!(keys, [KeyCode::Down, KeyCode::Down, KeyCode::Enter, KeyCode::Char('q')]);
+    }
+
+    #[tokio::test]
+    async fn dialogs_get_each_key_once() {
+        let (mut app, keys) = app();
+        let confirmed = Arc::new(AtomicUsize::new(0));
+        let counter = confirmed.clone();
+        let ctx = &mut app.context;
+        confirm_dialog!(ctx, "Erase userdata?", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // Once Enter closes the dialog, its release would otherwise reach the page
+        tap(&mut app, KeyCode::Right).await;
+        assert_eq!(app.context.dialog.as_ref().map(|d| d.selected), Some(1));
+        tap(&mut app, KeyCode::Left).await;
+        tap(&mut app, KeyCode::Enter).await;
+
+        assert_eq!(confirmed.load(Ordering::SeqCst), 1);
+        assert!(app.context.dialog.is_none());
+        assert!(keys.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn force_exit_is_not_doubled() {
+        let (mut app, keys) = app();
+        for kind in [KeyEventKind::Press, KeyEventKind::Release] {
+            let key = KeyEvent::new_with_kind(KeyCode::Delete, KeyModifiers::CONTROL, kind);
+            app.handle_event(Event::Key(key)).await;
+        }
+
+        assert!(app.context.exit);
+        assert_eq!(keys.lock().unwrap().clone(), [KeyCode::Delete]);
+    }
+}
@@ -0,0 +1,100 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::{Args, ValueEnum};
+use log::info;
+use penumbra::Device;
+use penumbra::da::protocol::BootMode;
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum BootModeTarget {
+    Normal,
+    Fastboot,
+    Meta,
+}
+
+#[derive(Args, Debug)]
+pub struct BootModeArgs {
+    /// Mode of the next boot
+    #[arg(value_enum)]
+    pub mode: BootModeTarget,
+    /// Also enable ADB in META mode
+    #[arg(long)]
+    pub adb: bool,
+    #[command(flatten)]
+    pub da: DaArgs,
+}
+
+impl CommandMetadata for BootModeArgs {
+    fn visible_aliases() -> &'static [&'static str] {
+        &["bootmode"]
+    }
+
+    fn about() -> &'static str {
+        "Set the boot mode of the device, and reboot into it."
+    }
+
+    fn long_about() -> &'static str {
+        "Set the mode of the next boot (Normal, Fastboot or META, optionally with ADB), without
+        going through secret codes or key combos. The device reboots immediately on success.
+        If the DA rejects the mode, the device stays in DA mode and nothing is changed."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra bootmode meta --da DA.bin",
+            "antumbra bootmode meta --adb --da DA.bin",
+            "antumbra bootmode fastboot --da DA.bin",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
+    }
+}
+
+impl BootModeArgs {
+    fn boot_mode(&self) -> Result<BootMode> {
+        match self.mode {
+            BootModeTarget::Meta if self.adb => Ok(BootMode::AdbMeta),
+            BootModeTarget::Meta => Ok(BootMode::Meta),
+            _ if self.adb => Err(anyhow::anyhow!("--adb is only available in META mode")),
+            BootModeTarget::Fastboot => Ok(BootMode::Fastboot),
+            BootModeTarget::Normal => Ok(BootMode::Normal),
+        }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for BootModeArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let bootmode = self.boot_mode()?;
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        dev.set_boot_mode(bootmode).await?;
+        info!("Boot mode set to {:?}, the device is rebooting.", bootmode);
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
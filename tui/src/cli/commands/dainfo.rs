@@ -0,0 +1,103 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Args;
+use log::{info, warn};
+use penumbra::Device;
+use penumbra::core::chipset::da_code;
+use penumbra::da::{DAFile, DAIssue};
+use tokio::fs::read;
+
+use crate::cli::MtkCommand;
+use crate::cli::common::CommandMetadata;
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Args, Debug)]
+pub struct DaInfoArgs {
+    /// The DA file to inspect
+    pub da_file: PathBuf,
+    /// Also list the regions of each DA entry
+    #[arg(long)]
+    pub regions: bool,
+}
+
+impl CommandMetadata for DaInfoArgs {
+    fn visible_aliases() -> &'static [&'static str] {
+        &["dainfo"]
+    }
+
+    fn about() -> &'static str {
+        "Show the type, version and supported chipsets of a DA file, without a device."
+    }
+
+    fn long_about() -> &'static str {
+        "Show the type (Legacy, V5 or V6), version and supported chipsets of a DA file,
+        without a device. For each chipset, tells whether the DA2 hash was found in DA1,
+        which the DA exploits need to patch DA2. Handy to pick the right DA before
+        plugging the device in."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &["antumbra dainfo DA.bin", "antumbra dainfo DA.bin --regions"]
+    }
+}
+
+impl DaInfoArgs {
+    /// Prints the DA file summary, without connecting to a device.
+    pub async fn execute(&self) -> Result<()> {
+        let da_file = DAFile::parse_da(&read(&self.da_file).await?)?;
+
+        info!("DA type:    {:?}", da_file.da_type);
+        info!("DA ID:      {}", da_file.da_id);
+        info!("Version:    {}", da_file.version);
+        info!("Entries:    {}", da_file.das.len());
+
+        let supported = da_file.supported_hw_codes();
+        info!("Supported chipsets ({}):", supported.len());
+        info!("  {:<9} {:<8} {:<7} DA2 hash", "HW code", "Chip", "Entry");
+
+        for (hw_code, name) in &supported {
+            let Some(da) = da_file.get_da_from_hw_code(*hw_code) else { continue };
+
+            let hash = match da.find_da_hash_offset() {
+                Some(_)
+                    if da.issues.iter().any(|i| matches!(i, DAIssue::Da2HashMismatch { .. })) =>
+                {
+                    "found, mismatching".to_string()
+                }
+                Some(offset) => format!("found at 0x{:X}", offset),
+                None => "not found".to_string(),
+            };
+
+            info!("  0x{:<7X} {:<8} 0x{:<5X} {}", hw_code, name, da_code(*hw_code), hash);
+        }
+
+        if self.regions {
+            for da in &da_file.das {
+                info!("Entry 0x{:04X} ({:?}):", da.hw_code, da.da_type);
+                for line in da.entry_summary().lines() {
+                    info!("  {}", line);
+                }
+            }
+        }
+
+        for (hw_code, issue) in da_file.issues() {
+            warn!("Entry 0x{:04X}: {}", hw_code, issue);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MtkCommand for DaInfoArgs {
+    // Handled before connecting, see `run_cli`
+    async fn run(&self, _dev: &mut Device, _state: &mut PersistedDeviceState) -> Result<()> {
+        self.execute().await
+    }
+}
@@ -0,0 +1,122 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::{Args, Subcommand};
+use log::info;
+use penumbra::Device;
+use penumbra::da::DaRepository;
+
+use crate::cli::MtkCommand;
+use crate::cli::common::CommandMetadata;
+use crate::cli::state::PersistedDeviceState;
+use crate::config::da_repository_dir;
+
+#[derive(Debug, Subcommand)]
+pub enum DaRepoSubcommand {
+    /// Copy a DA file into the repository.
+    Import(DaImportArgs),
+    /// List the DAs of the repository and the chipsets they support.
+    List,
+}
+
+#[derive(Args, Debug)]
+pub struct DaRepoArgs {
+    #[command(subcommand)]
+    pub command: DaRepoSubcommand,
+}
+
+#[derive(Args, Debug)]
+pub struct DaImportArgs {
+    /// The DA file to import
+    pub da_file: PathBuf,
+    /// Pick this DA for all its chipsets, even over V6 DAs supporting them
+    #[arg(long)]
+    pub prefer: bool,
+}
+
+impl CommandMetadata for DaRepoArgs {
+    fn about() -> &'static str {
+        "Manage the DA repository, used when no DA is given with --da."
+    }
+
+    fn long_about() -> &'static str {
+        "Manage the DA repository, used when no DA is given with --da.
+        Once the device answers, the DA supporting its chipset is picked from the
+        repository: V6 DAs first, then V5 and Legacy ones, unless another one was
+        imported with --prefer. `da import <file>` copies a DA into the repository,
+        `da list` shows the chipsets each DA supports. No device is involved."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra da import MTK_DA_V6.bin",
+            "antumbra da import DA_SWSEC.bin --prefer",
+            "antumbra da list",
+        ]
+    }
+}
+
+impl DaRepoArgs {
+    /// Runs the subcommand, no device is involved.
+    pub async fn execute(&self) -> Result<()> {
+        let dir = da_repository_dir()
+            .ok_or_else(|| anyhow!("No config directory to keep the DA repository in"))?;
+        let mut repo = DaRepository::open(dir).await?;
+
+        match &self.command {
+            DaRepoSubcommand::Import(cmd) => cmd.import(&mut repo).await,
+            DaRepoSubcommand::List => {
+                list(&repo);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl DaImportArgs {
+    async fn import(&self, repo: &mut DaRepository) -> Result<()> {
+        let entry = repo.import(&self.da_file).await?;
+        info!(
+            "Imported '{}' ({:?}, {} chipsets) into {}",
+            entry.file,
+            entry.da_type,
+            entry.hw_codes.len(),
+            repo.dir().display()
+        );
+
+        if self.prefer {
+            repo.set_preferred_for_all(&entry.file).await?;
+            info!("'{}' is now picked for all its chipsets", entry.file);
+        }
+        Ok(())
+    }
+}
+
+fn list(repo: &DaRepository) {
+    let mut entries: Vec<_> = repo.entries().collect();
+    if entries.is_empty() {
+        info!("No DA in {}, add one with `antumbra da import <file>`", repo.dir().display());
+        return;
+    }
+
+    entries.sort_by(|a, b| a.file.cmp(&b.file));
+    info!("DAs in {}:", repo.dir().display());
+    for entry in entries {
+        let hw_codes: Vec<String> = entry.hw_codes.iter().map(|c| format!("0x{:X}", c)).collect();
+        info!("  {} ({:?}, {})", entry.file, entry.da_type, entry.da_id);
+        info!("    {}", hw_codes.join(" "));
+    }
+}
+
+#[async_trait]
+impl MtkCommand for DaRepoArgs {
+    // Handled before connecting, see `run_cli`
+    async fn run(&self, _dev: &mut Device, _state: &mut PersistedDeviceState) -> Result<()> {
+        self.execute().await
+    }
+}
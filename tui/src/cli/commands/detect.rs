@@ -0,0 +1,138 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Args;
+use log::{LevelFilter, info, warn};
+use penumbra::connection::probe::PortInfo;
+use penumbra::{Device, find_mtk_ports, probe_port};
+use serde_json::{Value, json};
+use tokio::time::sleep;
+
+use crate::cli::MtkCommand;
+use crate::cli::common::CommandMetadata;
+use crate::cli::state::PersistedDeviceState;
+
+/// Exit code when exactly one device was found
+pub const EXIT_ONE: u8 = 0;
+/// Exit code when no device was found
+pub const EXIT_NONE: u8 = 2;
+/// Exit code when more than one device was found
+pub const EXIT_MULTIPLE: u8 = 3;
+
+#[derive(Args, Debug)]
+pub struct DetectArgs {
+    /// Confirm that each device responds (handshake + HW code, BROM and preloader only)
+    #[arg(long)]
+    pub probe: bool,
+    /// Print the result as JSON
+    #[arg(long)]
+    pub json: bool,
+    /// Wait up to this many seconds for a device to show up
+    #[arg(long, short = 'w', value_name = "SECONDS", default_value_t = 0)]
+    pub wait: u64,
+}
+
+impl CommandMetadata for DetectArgs {
+    fn about() -> &'static str {
+        "Detect attached devices and their current mode, without starting a session."
+    }
+
+    fn long_about() -> &'static str {
+        "List attached MediaTek devices with their VID/PID, port and mode (BROM, preloader or DA).
+        Nothing is sent to the device unless --probe is given.
+        With several devices connected, pass the port of one to --port to pick it.
+        Exits with 0 for one device, 2 for none and 3 for multiple devices."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &["antumbra detect", "antumbra detect --probe --json", "antumbra detect --wait 30"]
+    }
+}
+
+impl DetectArgs {
+    /// Runs the detection, returning the exit code matching the device count.
+    pub async fn detect(&self) -> Result<ExitCode> {
+        if self.json {
+            // Logs go to stdout, keep it clean for the JSON output
+            log::set_max_level(LevelFilter::Off);
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(self.wait);
+
+        let mut ports = find_mtk_ports().await;
+        while ports.is_empty() && Instant::now() < deadline {
+            sleep(Duration::from_millis(250)).await;
+            ports = find_mtk_ports().await;
+        }
+
+        let mut devices = Vec::with_capacity(ports.len());
+        for port in ports {
+            let info = PortInfo::from_port(port.as_ref());
+
+            let (alive, hw_code) = if self.probe {
+                match probe_port(port).await {
+                    Ok(result) => (Some(true), result.hw_code),
+                    Err(e) => {
+                        warn!("Probe failed on {}: {}", info.port_name, e);
+                        (Some(false), None)
+                    }
+                }
+            } else {
+                (None, None)
+            };
+
+            devices.push(Self::describe(&info, alive, hw_code));
+        }
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&json!({ "devices": devices }))?);
+        } else if devices.is_empty() {
+            info!("No MTK device found.");
+        }
+
+        Ok(ExitCode::from(match devices.len() {
+            0 => EXIT_NONE,
+            1 => EXIT_ONE,
+            _ => EXIT_MULTIPLE,
+        }))
+    }
+
+    fn describe(info: &PortInfo, alive: Option<bool>, hw_code: Option<u16>) -> Value {
+        info!(
+            "{:04X}:{:04X} {} ({}){}",
+            info.vid,
+            info.pid,
+            info.port_name,
+            info.connection_type,
+            match (alive, hw_code) {
+                (Some(true), Some(code)) => format!(", HW code 0x{:04X}", code),
+                (Some(true), None) => ", alive".to_string(),
+                (Some(false), _) => ", not responding".to_string(),
+                (None, _) => String::new(),
+            }
+        );
+
+        json!({
+            "vid": format!("{:04x}", info.vid),
+            "pid": format!("{:04x}", info.pid),
+            "port": info.port_name,
+            "mode": info.connection_type.to_string(),
+            "alive": alive,
+            "hw_code": hw_code.map(|c| format!("0x{:04X}", c)),
+        })
+    }
+}
+
+#[async_trait]
+impl MtkCommand for DetectArgs {
+    // Handled before connecting, see `run_cli`
+    async fn run(&self, _dev: &mut Device, _state: &mut PersistedDeviceState) -> Result<()> {
+        self.detect().await.map(|_| ())
+    }
+}
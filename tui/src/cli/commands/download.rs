@@ -2,29 +2,51 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use clap::Args;
+use clap_num::maybe_hex;
 use log::info;
-use penumbra::Device;
-use tokio::fs::{File, metadata};
-use tokio::io::BufReader;
+use penumbra::core::summary::OperationKind;
+use penumbra::{Device, ProgressEvent};
+use tokio::io::{AsyncRead, stdin};
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
-use crate::cli::helpers::AntumbraProgress;
+use crate::cli::common::{
+    CONN_DA,
+    CommandFlags,
+    CommandMetadata,
+    DaArgs,
+    SectionArgs,
+    StableInput,
+};
+use crate::cli::helpers::{AntumbraProgress, is_stdio, padded};
+use crate::cli::journal::{Journal, WriteKind};
 use crate::cli::state::PersistedDeviceState;
 
 #[derive(Args, Debug)]
 pub struct DownloadArgs {
     #[command(flatten)]
     pub da: DaArgs,
-    /// The partition to flash
+    /// The partition to flash, left out with --section
     pub partition: String,
-    /// The file to download
-    pub file: PathBuf,
+    /// The file to download, or `-` for stdin
+    pub file: Option<PathBuf>,
+    #[command(flatten)]
+    pub section: SectionArgs,
+    /// Only download this many bytes.
+    /// Without it, stdin input is zero-padded to the whole partition or section.
+    #[arg(long, value_parser = maybe_hex::<u64>)]
+    pub size: Option<u64>,
+    /// Wait until the file size stays unchanged for this many seconds before flashing
+    #[arg(long, value_name = "SECONDS")]
+    pub wait_stable: Option<u64>,
+    /// Read the written data back and compare it with the input
+    #[arg(long)]
+    pub verify: bool,
 }
 
 impl CommandMetadata for DownloadArgs {
@@ -39,27 +61,127 @@ impl CommandMetadata for DownloadArgs {
     fn long_about() -> &'static str {
         "Download (flash) a file to a specificed partition on the device.
         Use this command for flashing stock firmware on locked bootloader, or the device
-        will return write data not allowed error."
+        will return write data not allowed error.
+        Android sparse images are expanded while flashing, and .gz or .zst files
+        are decompressed.
+        With --verify, the written data is read back and its SHA-256 compared with the input.
+        With --section, the file is written as is to a raw range of a storage section, such
+        as the boot regions of eMMC (boot1, boot2) or the boot LUs of UFS (lu0, lu1), starting
+        at --offset. The partition is then left out."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra download --da DA.bin boot boot.img",
+            "xz -dc boot.img.xz | antumbra download --da DA.bin boot - --size 0x4000000",
+            "antumbra download --da DA.bin boot boot.img --verify",
+            "antumbra write --da DA.bin --section boot2 --offset 0 boot2.bin",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
+    }
+}
+
+impl DownloadArgs {
+    async fn write_section(&self, dev: &mut Device, file: &Path) -> Result<()> {
+        // Stdin is padded to --size, or to the end of the section
+        let (reader, length): (Box<dyn AsyncRead + Unpin + Send>, Option<u64>) = if is_stdio(file) {
+            (Box::new(stdin()), self.size)
+        } else {
+            let wait_stable = self.wait_stable.map(Duration::from_secs);
+            let (reader, file_size) = StableInput::open_image(file, wait_stable).await?;
+            (reader, Some(self.size.unwrap_or(file_size)))
+        };
+
+        let Some(range) = self.section.resolve(dev, length).await? else {
+            return Ok(());
+        };
+        let mut reader = padded(reader, range.length);
+
+        let name = range.name.as_str();
+        let pb = AntumbraProgress::new(range.length).for_partition(name);
+
+        let mut verify_pb: Option<AntumbraProgress> = None;
+        let mut progress_callback = {
+            let pb = &pb;
+            move |event: ProgressEvent| {
+                if event.phase == OperationKind::Verify {
+                    let verify_pb = verify_pb.get_or_insert_with(|| {
+                        AntumbraProgress::new(event.bytes_total).for_partition(name)
+                    });
+                    verify_pb.event(&event, "Verifying...");
+                    if event.is_done() {
+                        verify_pb.finish("Verification complete!");
+                    }
+                    return;
+                }
+
+                pb.event(&event, "Writing...");
+                if event.is_done() {
+                    pb.finish("Write complete!");
+                }
+            }
+        };
+
+        info!("Writing 0x{:X} bytes to '{}' at 0x{:X}...", range.length, name, range.offset);
+
+        let (offset, length, kind) = (range.offset, range.length as usize, range.kind);
+        let result = if self.verify {
+            dev.write_offset_verified(
+                offset,
+                length,
+                &mut reader,
+                kind,
+                false,
+                &mut progress_callback,
+            )
+            .await
+        } else {
+            dev.write_offset(offset, length, &mut reader, kind, false, &mut progress_callback).await
+        };
+
+        if let Err(e) = result {
+            pb.abandon("Write failed!");
+            return Err(e)?;
+        }
+
+        Ok(())
     }
 }
 
 #[async_trait]
 impl MtkCommand for DownloadArgs {
     async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let (partition, file) = self.section.split(&self.partition, self.file.as_deref())?;
+
         dev.enter_da_mode().await?;
 
         state.connection_type = CONN_DA;
         state.flash_mode = 1;
 
-        let file = File::open(&self.file).await?;
-        let mut reader = BufReader::new(file);
-
-        let file_size = metadata(&self.file).await?.len();
+        if self.section.section.is_some() {
+            return self.write_section(dev, file).await;
+        }
+        let partition = partition.unwrap_or_default();
 
-        let part_size = match dev.dev_info.get_partition(&self.partition).await {
+        let part_size = match dev.dev_info.get_partition(partition).await {
             Some(p) => p.size as u64,
             None => {
-                return Err(anyhow::anyhow!("Partition '{}' not found on device.", self.partition));
+                return Err(anyhow::anyhow!("Partition '{}' not found on device.", partition));
+            }
+        };
+
+        let (mut reader, file_size): (Box<dyn AsyncRead + Unpin + Send>, u64) = if is_stdio(file) {
+            let size = self.size.unwrap_or(part_size);
+            (Box::new(padded(stdin(), size)), size)
+        } else {
+            let wait_stable = self.wait_stable.map(Duration::from_secs);
+            let (reader, file_size) = StableInput::open_image(file, wait_stable).await?;
+            match self.size {
+                Some(size) => (Box::new(padded(reader, size)), size),
+                None => (Box::new(reader), file_size),
             }
         };
 
@@ -71,25 +193,61 @@ impl MtkCommand for DownloadArgs {
             ));
         }
 
-        let pb = AntumbraProgress::new(file_size);
+        // Stdin can't be replayed and partial downloads can't be resumed from the whole file,
+        // so only plain file downloads are journaled
+        let mut journal = if is_stdio(file) || self.size.is_some() {
+            None
+        } else {
+            Some(
+                Journal::begin(WriteKind::Download, partition, file, file_size, &dev.dev_info)
+                    .await?,
+            )
+        };
 
+        let pb = AntumbraProgress::new(file_size).for_partition(partition);
+
+        let mut verify_pb: Option<AntumbraProgress> = None;
         let mut progress_callback = {
             let pb = &pb;
-            move |written: usize, total: usize| {
-                pb.update(written as u64, "Downloading...");
+            let journal = &mut journal;
+            move |event: ProgressEvent| {
+                if event.phase == OperationKind::Verify {
+                    let verify_pb = verify_pb.get_or_insert_with(|| {
+                        AntumbraProgress::new(event.bytes_total).for_partition(partition)
+                    });
+                    verify_pb.event(&event, "Verifying...");
+                    if event.is_done() {
+                        verify_pb.finish("Verification complete!");
+                    }
+                    return;
+                }
 
-                if written >= total {
+                pb.event(&event, "Downloading...");
+                if let Some(journal) = journal.as_mut() {
+                    journal.progress(event.bytes_done);
+                }
+
+                if event.is_done() {
                     pb.finish("Download complete!");
                 }
             }
         };
 
-        info!("Downloading to partition '{}'...", self.partition);
+        info!("Downloading to partition '{}'...", partition);
 
-        match dev
-            .download(&self.partition, file_size as usize, &mut reader, &mut progress_callback)
+        let result = if self.verify {
+            dev.download_verified(
+                partition,
+                file_size as usize,
+                &mut reader,
+                &mut progress_callback,
+            )
             .await
-        {
+        } else {
+            dev.download(partition, file_size as usize, &mut reader, &mut progress_callback).await
+        };
+
+        match result {
             Ok(_) => {}
             Err(e) => {
                 pb.abandon("Download failed!");
@@ -97,13 +255,16 @@ impl MtkCommand for DownloadArgs {
             }
         }
 
-        info!("Download to partition '{}' completed.", self.partition);
+        if let Some(journal) = journal {
+            journal.complete().await?;
+        }
+        info!("Download to partition '{}' completed.", partition);
 
         Ok(())
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.da_file.as_ref()
     }
 
     fn pl(&self) -> Option<&PathBuf> {
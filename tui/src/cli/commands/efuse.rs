@@ -0,0 +1,92 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Args;
+use log::info;
+use penumbra::Device;
+use tokio::fs::write;
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
+use crate::cli::state::PersistedDeviceState;
+
+/// eFuse words printed per line of the hex dump.
+const WORDS_PER_LINE: usize = 4;
+
+#[derive(Args, Debug)]
+pub struct EfuseArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// Save the raw eFuses to this file instead of printing them
+    pub output_file: Option<PathBuf>,
+}
+
+impl CommandMetadata for EfuseArgs {
+    fn about() -> &'static str {
+        "Read the eFuses of the device."
+    }
+
+    fn long_about() -> &'static str {
+        "Read the eFuses of the device through the DA (XFlash / V5 devices only), and print them
+        as a hex dump along with the secure boot state and the SBC public key hash.
+        With an output file, the raw little-endian eFuse words are saved there instead.
+        eFuses are only ever read: blowing them can't be undone."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &["antumbra efuse --da DA.bin", "antumbra efuse --da DA.bin efuses.bin"]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for EfuseArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        let efuses = dev.read_efuse().await?;
+
+        if let Some(path) = &self.output_file {
+            write(path, efuses.to_bytes()).await?;
+            info!("Saved {} eFuse words to {:?}", efuses.words().len(), path);
+            return Ok(());
+        }
+
+        for (line, words) in efuses.words().chunks(WORDS_PER_LINE).enumerate() {
+            let words: Vec<_> = words.iter().map(|w| format!("{:08X}", w)).collect();
+            info!("{:03X}: {}", line * WORDS_PER_LINE, words.join(" "));
+        }
+
+        match efuses.secure_boot_enabled() {
+            Some(enabled) => {
+                info!("Secure boot:   {}", if enabled { "enabled" } else { "disabled" })
+            }
+            None => info!("Secure boot:   unknown"),
+        }
+        match efuses.sbc_pubk_hash() {
+            Some(hash) => info!("SBC key hash:  {}", hex::encode_upper(hash)),
+            None => info!("SBC key hash:  not fused"),
+        }
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
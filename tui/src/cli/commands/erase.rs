@@ -8,10 +8,10 @@ use anyhow::Result;
 use async_trait::async_trait;
 use clap::Args;
 use log::info;
-use penumbra::Device;
+use penumbra::{Device, ProgressEvent};
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs, check_guarded};
 use crate::cli::helpers::AntumbraProgress;
 use crate::cli::state::PersistedDeviceState;
 
@@ -21,6 +21,9 @@ pub struct EraseArgs {
     pub da: DaArgs,
     /// The partition to erase
     pub partition: String,
+    /// Allow wiping partitions the device can't do without (preloader, seccfg, nvram)
+    #[arg(long)]
+    pub force: bool,
 }
 
 impl CommandMetadata for EraseArgs {
@@ -33,13 +36,24 @@ impl CommandMetadata for EraseArgs {
     }
 
     fn long_about() -> &'static str {
-        "Erase the specified partition on the device."
+        "Erase the specified partition on the device.
+        preloader, seccfg and nvram are refused unless --force is given."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &["antumbra erase --da DA.bin misc", "antumbra erase --da DA.bin userdata --yes"]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
     }
 }
 
 #[async_trait]
 impl MtkCommand for EraseArgs {
     async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        check_guarded(&self.partition, self.force)?;
+
         dev.enter_da_mode().await?;
 
         state.connection_type = CONN_DA;
@@ -53,14 +67,14 @@ impl MtkCommand for EraseArgs {
             }
         };
 
-        let pb = AntumbraProgress::new(partition.size as u64);
+        let pb = AntumbraProgress::new(partition.size as u64).for_partition(&self.partition);
 
         let mut progress_callback = {
             let pb = &pb;
-            move |written: usize, total: usize| {
-                pb.update(written as u64, "Erasing...");
+            move |event: ProgressEvent| {
+                pb.event(&event, "Erasing...");
 
-                if written >= total {
+                if event.is_done() {
                     pb.finish("Erase complete!");
                 }
             }
@@ -82,7 +96,7 @@ impl MtkCommand for EraseArgs {
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.da_file.as_ref()
     }
 
     fn pl(&self) -> Option<&PathBuf> {
@@ -0,0 +1,241 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::Args;
+use human_bytes::human_bytes;
+use log::{debug, info, warn};
+use penumbra::core::scatter::{ScatterEntry, parse_scatter};
+use penumbra::{Device, ProgressEvent};
+use tokio::fs::read_to_string;
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs, StableInput};
+use crate::cli::helpers::AntumbraProgress;
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Args, Debug)]
+pub struct FlashAllArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// The scatter file of the firmware (MT*_Android_scatter.txt)
+    #[arg(long)]
+    pub scatter: PathBuf,
+    /// The directory holding the images, the one of the scatter file by default
+    pub firmware_dir: Option<PathBuf>,
+    /// Only flash these partitions
+    #[arg(long, value_delimiter = ',', conflicts_with = "skip")]
+    pub only: Vec<String>,
+    /// Don't flash these partitions
+    #[arg(long, value_delimiter = ',')]
+    pub skip: Vec<String>,
+    /// Only print what would be flashed, without connecting to the device
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// A partition of the scatter file to flash, with its image.
+struct PlannedImage {
+    partition: String,
+    path: PathBuf,
+    /// Size of the data once flashed
+    size: u64,
+}
+
+impl CommandMetadata for FlashAllArgs {
+    fn visible_aliases() -> &'static [&'static str] {
+        &["flashall", "fa"]
+    }
+
+    fn about() -> &'static str {
+        "Flash a firmware package from its scatter file, like SP Flash Tool's Download Only."
+    }
+
+    fn long_about() -> &'static str {
+        "Flash every partition of a scatter file (MT*_Android_scatter.txt) that has an image and
+        is marked for download. Images are looked up next to the scatter file, or in the given
+        firmware directory. All images are checked before anything is written: each must exist
+        and fit its partition. Use --only or --skip to pick partitions, and --dry-run to only
+        print the plan without a device. Old scatter files, which list no images, can't be
+        flashed this way."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra flashall --da DA.bin --scatter fw/MT6768_Android_scatter.txt",
+            "antumbra flashall --da DA.bin --scatter fw/MT6768_Android_scatter.txt --skip userdata",
+            "antumbra flashall --da DA.bin --scatter fw/MT6768_Android_scatter.txt --only boot,vbmeta",
+            "antumbra flashall --da DA.bin --scatter fw/MT6768_Android_scatter.txt --dry-run",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
+    }
+}
+
+impl FlashAllArgs {
+    fn selected(&self, entry: &ScatterEntry) -> bool {
+        let listed =
+            |names: &[String]| names.iter().any(|n| n.eq_ignore_ascii_case(&entry.partition_name));
+
+        if !self.only.is_empty() {
+            return listed(&self.only);
+        }
+        !listed(&self.skip)
+    }
+
+    /// Lists the images to flash, checking that each exists and fits the size of its
+    /// partition in the scatter file. Fails on the first unusable image, before any write.
+    async fn plan(&self) -> Result<Vec<PlannedImage>> {
+        let entries = parse_scatter(&read_to_string(&self.scatter).await?)?;
+        let firmware_dir = match &self.firmware_dir {
+            Some(dir) => dir.clone(),
+            None => self.scatter.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+
+        for name in &self.only {
+            if !entries.iter().any(|e| e.partition_name.eq_ignore_ascii_case(name)) {
+                warn!("Partition '{}' is not in the scatter file", name);
+            }
+        }
+
+        let mut plan = Vec::new();
+        for entry in entries.iter().filter(|e| self.selected(e)) {
+            let Some(file_name) = entry.file_name.as_ref().filter(|_| entry.is_flashable()) else {
+                debug!("Skipping '{}', no image to download", entry.partition_name);
+                continue;
+            };
+
+            let path = firmware_dir.join(file_name);
+            let (_, size) = StableInput::open_image(&path, None).await.map_err(|e| {
+                anyhow!("Image of '{}' ({}): {}", entry.partition_name, path.display(), e)
+            })?;
+
+            if let Some(part_size) = entry.partition_size
+                && size > part_size
+            {
+                return Err(anyhow!(
+                    "Image of '{}' ({}) doesn't fit the partition ({}).",
+                    entry.partition_name,
+                    human_bytes(size as f64),
+                    human_bytes(part_size as f64)
+                ));
+            }
+
+            plan.push(PlannedImage { partition: entry.partition_name.clone(), path, size });
+        }
+
+        if plan.is_empty() {
+            return Err(anyhow!("Nothing to flash from '{}'.", self.scatter.display()));
+        }
+
+        Ok(plan)
+    }
+
+    fn print_plan(plan: &[PlannedImage]) {
+        for image in plan {
+            info!(
+                "  {:<20} {:>10}  {}",
+                image.partition,
+                human_bytes(image.size as f64),
+                image.path.display()
+            );
+        }
+
+        let total: u64 = plan.iter().map(|i| i.size).sum();
+        info!("{} partitions, {} in total", plan.len(), human_bytes(total as f64));
+    }
+
+    /// Prints the plan, without connecting to a device.
+    pub async fn execute(&self) -> Result<()> {
+        let plan = self.plan().await?;
+        info!("Would flash:");
+        Self::print_plan(&plan);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MtkCommand for FlashAllArgs {
+    // --dry-run is handled before connecting, see `run_cli`
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let plan = self.plan().await?;
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        // The scatter file might not match the device, check against its own partitions too
+        for image in &plan {
+            let Some(part) = dev.dev_info.get_partition(&image.partition).await else {
+                return Err(anyhow!("Partition '{}' not found on device.", image.partition));
+            };
+            if image.size > part.size as u64 {
+                return Err(anyhow!(
+                    "Image of '{}' ({}) exceeds the partition on the device ({}).",
+                    image.partition,
+                    human_bytes(image.size as f64),
+                    human_bytes(part.size as f64)
+                ));
+            }
+        }
+
+        info!("Flashing:");
+        Self::print_plan(&plan);
+
+        let total: u64 = plan.iter().map(|i| i.size).sum();
+        let overall = AntumbraProgress::new(total);
+        let mut flashed = 0;
+
+        for (i, image) in plan.iter().enumerate() {
+            let (mut reader, size) = StableInput::open_image(&image.path, None).await?;
+            let pb = AntumbraProgress::new(size).for_partition(&image.partition);
+            let overall_msg = format!("Partition {}/{}", i + 1, plan.len());
+
+            let mut progress_callback = {
+                let pb = &pb;
+                let overall = &overall;
+                let overall_msg = &overall_msg;
+                move |event: ProgressEvent| {
+                    pb.event(&event, &format!("Flashing {}...", image.partition));
+                    overall.update(flashed + event.bytes_done, overall_msg);
+
+                    if event.is_done() {
+                        pb.finish(&format!("Flashed {}", image.partition));
+                    }
+                }
+            };
+
+            if let Err(e) = dev
+                .download(&image.partition, size as usize, &mut reader, &mut progress_callback)
+                .await
+            {
+                pb.abandon(&format!("Flashing {} failed!", image.partition));
+                overall.abandon("Flashing failed!");
+                return Err(anyhow::Error::from(e)
+                    .context(format!("Failed to flash '{}'", image.partition)));
+            }
+
+            flashed += size;
+        }
+
+        overall.finish("All partitions flashed!");
+        info!("Flashed {} partitions from '{}'.", plan.len(), self.scatter.display());
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
@@ -0,0 +1,244 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::Args;
+use log::{error, info};
+use penumbra::core::backup::BackupManifest;
+use penumbra::core::summary::HashingWriter;
+use penumbra::{Device, ProgressEvent};
+use tokio::fs::metadata;
+use tokio::io::sink;
+use tokio::task::{JoinHandle, spawn_blocking};
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
+use crate::cli::helpers::{AntumbraProgress, DumpFormat, dump_sha256};
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Args, Debug)]
+pub struct HashCheckArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// Pairs of `partition=file`, the files being raw images
+    #[arg(value_parser = parse_pair)]
+    pub pairs: Vec<(String, PathBuf)>,
+    /// Check the dumps of a backup.json written by read-all, against their recorded hashes
+    #[arg(long, conflicts_with = "pairs")]
+    pub manifest: Option<PathBuf>,
+}
+
+fn parse_pair(s: &str) -> Result<(String, PathBuf), String> {
+    match s.split_once('=') {
+        Some((partition, file)) if !partition.is_empty() && !file.is_empty() => {
+            Ok((partition.to_string(), PathBuf::from(file)))
+        }
+        _ => Err(format!("'{}' isn't a partition=file pair", s)),
+    }
+}
+
+/// What the partition is compared with.
+enum Local {
+    /// A raw image, hashed while the partition is read
+    File(PathBuf),
+    /// A dump with its SHA-256 and raw size recorded in a backup manifest
+    Recorded { sha256: String, size: u64 },
+}
+
+/// Hash of the local side, still being computed for files.
+enum LocalHash {
+    Known(String),
+    Pending(JoinHandle<Result<String>>),
+}
+
+struct Check {
+    partition: String,
+    source: String,
+    local: Local,
+}
+
+impl CommandMetadata for HashCheckArgs {
+    fn visible_aliases() -> &'static [&'static str] {
+        &["hashcheck", "hc"]
+    }
+
+    fn about() -> &'static str {
+        "Compare the SHA-256 of partitions on the device with local images."
+    }
+
+    fn long_about() -> &'static str {
+        "Compare the SHA-256 of partitions on the device with local images.
+        Each partition is streamed into a hash without being written to disk, while the local
+        file is hashed alongside. When the partition is larger than the file, only the length
+        of the file is hashed on the device. With --manifest, the dumps of a read-all backup
+        are checked against the hashes recorded in its backup.json.
+        Exits with an error if any partition doesn't match."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra hash-check --da DA.bin boot_a=boot.img vbmeta_a=vbmeta.img",
+            "antumbra hash-check --da DA.bin --manifest dump/backup.json",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
+    }
+}
+
+impl HashCheckArgs {
+    async fn checks(&self) -> Result<Vec<Check>> {
+        let Some(path) = &self.manifest else {
+            if self.pairs.is_empty() {
+                return Err(anyhow!("Nothing to check, give partition=file pairs or --manifest."));
+            }
+            return Ok(self
+                .pairs
+                .iter()
+                .map(|(partition, file)| Check {
+                    partition: partition.clone(),
+                    source: file.display().to_string(),
+                    local: Local::File(file.clone()),
+                })
+                .collect());
+        };
+
+        let manifest = BackupManifest::load(path)
+            .await
+            .map_err(|e| anyhow!("Failed to load '{}': {}", path.display(), e))?;
+
+        Ok(manifest
+            .partitions
+            .into_iter()
+            .filter_map(|p| {
+                let (file, sha256) = (p.file?, p.sha256?);
+                Some(Check {
+                    partition: p.name,
+                    source: file,
+                    local: Local::Recorded { sha256, size: p.size },
+                })
+            })
+            .collect())
+    }
+
+    /// Hashes the partition against its local counterpart, returning both digests.
+    async fn check(&self, dev: &mut Device, check: &Check) -> Result<(String, String)> {
+        let Some(part) = dev.dev_info.get_partition(&check.partition).await else {
+            return Err(anyhow!("Partition '{}' not found on device", check.partition));
+        };
+        let part_size = part.size as u64;
+
+        let (len, local) = match &check.local {
+            Local::Recorded { sha256, size } => (*size, LocalHash::Known(sha256.to_lowercase())),
+            Local::File(path) => {
+                let len = metadata(path)
+                    .await
+                    .map_err(|e| anyhow!("Failed to open '{}': {}", path.display(), e))?
+                    .len();
+                // Hashed on a blocking thread while the partition is being read
+                let path = path.clone();
+                let task = spawn_blocking(move || dump_sha256(&path, DumpFormat::Raw, &mut |_| {}));
+                (len, LocalHash::Pending(task))
+            }
+        };
+
+        if len > part_size {
+            return Err(anyhow!(
+                "'{}' (0x{:X} bytes) is larger than the partition (0x{:X} bytes)",
+                check.source,
+                len,
+                part_size
+            ));
+        }
+        if len < part_size {
+            info!(
+                "'{}' is smaller than '{}', only its first 0x{:X} of 0x{:X} bytes are hashed.",
+                check.source, check.partition, len, part_size
+            );
+        }
+
+        let pb = AntumbraProgress::new(len).for_partition(&check.partition);
+        let mut progress_callback = {
+            let pb = &pb;
+            move |event: ProgressEvent| {
+                pb.event(&event, "Hashing...");
+            }
+        };
+
+        let mut sink = sink();
+        let mut writer = HashingWriter::new(&mut sink);
+        let result = dev
+            .read_offset(part.address, len as usize, part.kind, &mut progress_callback, &mut writer)
+            .await;
+        if let Err(e) = result {
+            pb.abandon("Read failed!");
+            return Err(e)?;
+        }
+        pb.finish("Hashed!");
+        let device = writer.finalize();
+
+        let local = match local {
+            LocalHash::Known(sha256) => sha256,
+            LocalHash::Pending(task) => task.await??,
+        };
+
+        Ok((device, local))
+    }
+}
+
+#[async_trait]
+impl MtkCommand for HashCheckArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let checks = self.checks().await?;
+        if checks.is_empty() {
+            return Err(anyhow!("No backed up partitions in the manifest."));
+        }
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        let (mut matched, mut failures) = (0, 0);
+        for check in &checks {
+            match self.check(dev, check).await {
+                Ok((device, local)) if device == local => {
+                    matched += 1;
+                    info!("MATCH     {} = {} (sha256 {})", check.partition, check.source, device);
+                }
+                Ok((device, local)) => {
+                    failures += 1;
+                    error!(
+                        "MISMATCH  {} != {} (device {}, file {})",
+                        check.partition, check.source, device, local
+                    );
+                }
+                Err(e) => {
+                    failures += 1;
+                    error!("ERROR     {} ({})", check.partition, e);
+                }
+            }
+        }
+
+        info!("{} matched, {} failed", matched, failures);
+
+        if failures > 0 {
+            return Err(anyhow!("{} of {} partitions don't match", failures, checks.len()));
+        }
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
@@ -0,0 +1,220 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::available_parallelism;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::{Args, Subcommand};
+use futures::{StreamExt, stream};
+use log::{error, info, warn};
+use penumbra::Device;
+use tokio::fs::metadata;
+use tokio::task::spawn_blocking;
+
+use crate::cli::MtkCommand;
+use crate::cli::common::CommandMetadata;
+use crate::cli::helpers::{AntumbraProgress, DumpFormat, Manifest, ManifestWriter, dump_sha256};
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Debug, Subcommand)]
+pub enum ManifestSubcommand {
+    /// Hash the dumps of a directory and compare them with its manifest.
+    Verify(ManifestVerifyArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ManifestArgs {
+    #[command(subcommand)]
+    pub command: ManifestSubcommand,
+}
+
+#[derive(Args, Debug)]
+pub struct ManifestVerifyArgs {
+    /// The dump directory, holding the manifest
+    pub dir: PathBuf,
+    /// Record the current hash of mismatching files, for files modified on purpose
+    #[arg(long)]
+    pub update: bool,
+    /// Number of files hashed at once, defaults to the number of CPUs
+    #[arg(long, short = 'j')]
+    pub jobs: Option<usize>,
+}
+
+/// Outcome of the check of a single file.
+enum FileStatus {
+    Ok,
+    Mismatch(String),
+    Missing,
+    /// The dump didn't complete, so there's no hash to compare with
+    NoHash,
+    Failed(String),
+}
+
+impl CommandMetadata for ManifestArgs {
+    fn about() -> &'static str {
+        "Check dumps against the manifest written by read-all, without a device."
+    }
+
+    fn long_about() -> &'static str {
+        "Check dumps against the manifest written by read-all, without a device.
+        `manifest verify <dir>` hashes every file listed in the manifest, decoding sparse
+        and zstd dumps, and reports each one as OK, MISMATCH or MISSING.
+        Exits with an error if any file fails. Use --update to record the new hashes
+        of files that were modified on purpose."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra manifest verify dump/",
+            "antumbra manifest verify dump/ --jobs 2",
+            "antumbra manifest verify dump/ --update",
+        ]
+    }
+}
+
+impl ManifestArgs {
+    /// Runs the subcommand, no device is involved.
+    pub async fn execute(&self) -> Result<()> {
+        match &self.command {
+            ManifestSubcommand::Verify(cmd) => cmd.verify().await,
+        }
+    }
+}
+
+impl ManifestVerifyArgs {
+    pub async fn verify(&self) -> Result<()> {
+        let manifest_path = self.dir.join(ManifestWriter::FILE_NAME);
+        let mut manifest = Manifest::load(&manifest_path)
+            .await
+            .map_err(|e| anyhow!("Failed to load '{}': {}", manifest_path.display(), e))?;
+
+        let jobs = self.jobs.unwrap_or_else(|| available_parallelism().map_or(1, |n| n.get()));
+
+        let mut total_size = 0;
+        for entry in manifest.entries.iter().filter(|e| e.sha256.is_some()) {
+            total_size += metadata(self.dir.join(&entry.file)).await.map_or(0, |m| m.len());
+        }
+
+        let pb = Arc::new(AntumbraProgress::new(total_size));
+        let hashed = Arc::new(AtomicU64::new(0));
+
+        let files: Vec<_> = manifest
+            .entries
+            .iter()
+            .map(|entry| (self.dir.join(&entry.file), entry.sha256.clone()))
+            .collect();
+        let checks = files.into_iter().enumerate().map(|(i, (path, expected))| {
+            let pb = pb.clone();
+            let hashed = hashed.clone();
+            async move { (i, check_file(path, expected, pb, hashed).await) }
+        });
+        let mut results: Vec<_> =
+            stream::iter(checks).buffer_unordered(jobs.max(1)).collect().await;
+        results.sort_by_key(|(i, _)| *i);
+
+        pb.finish("Hashing complete!");
+
+        let (mut ok, mut mismatched, mut missing, mut skipped, mut failed) = (0, 0, 0, 0, 0);
+        let mut updated = 0;
+        for (i, status) in results {
+            let entry = &mut manifest.entries[i];
+            match status {
+                FileStatus::Ok => {
+                    ok += 1;
+                    info!("OK        {}", entry.file);
+                }
+                FileStatus::Mismatch(actual) => {
+                    mismatched += 1;
+                    error!(
+                        "MISMATCH  {} (expected {}, got {})",
+                        entry.file,
+                        entry.sha256.as_deref().unwrap_or_default(),
+                        actual
+                    );
+                    if self.update {
+                        entry.sha256 = Some(actual);
+                        updated += 1;
+                    }
+                }
+                FileStatus::Missing => {
+                    missing += 1;
+                    error!("MISSING   {}", entry.file);
+                }
+                FileStatus::NoHash => {
+                    skipped += 1;
+                    warn!("SKIPPED   {} (incomplete dump, no recorded hash)", entry.file);
+                }
+                FileStatus::Failed(e) => {
+                    failed += 1;
+                    error!("ERROR     {} ({})", entry.file, e);
+                }
+            }
+        }
+
+        info!(
+            "{} OK, {} mismatched, {} missing, {} skipped, {} unreadable",
+            ok, mismatched, missing, skipped, failed
+        );
+
+        if updated > 0 {
+            manifest.save(&manifest_path).await?;
+            info!("Updated the hash of {} files in the manifest.", updated);
+        }
+
+        let failures = missing + failed + if self.update { 0 } else { mismatched };
+        if failures > 0 {
+            return Err(anyhow!(
+                "{} of {} files failed verification",
+                failures,
+                manifest.entries.len()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+async fn check_file(
+    path: PathBuf,
+    expected: Option<String>,
+    pb: Arc<AntumbraProgress>,
+    hashed: Arc<AtomicU64>,
+) -> FileStatus {
+    if metadata(&path).await.is_err() {
+        return FileStatus::Missing;
+    }
+    let Some(expected) = expected else {
+        return FileStatus::NoHash;
+    };
+
+    let result = spawn_blocking(move || {
+        let mut last = 0;
+        let mut progress = |read: u64| {
+            let done = hashed.fetch_add(read - last, Ordering::Relaxed) + read - last;
+            last = read;
+            pb.update(done, "Hashing...");
+        };
+        dump_sha256(&path, DumpFormat::from_path(&path), &mut progress)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(actual)) if actual.eq_ignore_ascii_case(&expected) => FileStatus::Ok,
+        Ok(Ok(actual)) => FileStatus::Mismatch(actual),
+        Ok(Err(e)) => FileStatus::Failed(e.to_string()),
+        Err(e) => FileStatus::Failed(e.to_string()),
+    }
+}
+
+#[async_trait]
+impl MtkCommand for ManifestArgs {
+    // Handled before connecting, see `run_cli`
+    async fn run(&self, _dev: &mut Device, _state: &mut PersistedDeviceState) -> Result<()> {
+        self.execute().await
+    }
+}
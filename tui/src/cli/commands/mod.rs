@@ -2,30 +2,70 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+pub mod bootmode;
+pub mod dainfo;
+pub mod darepo;
+pub mod detect;
 pub mod download;
+pub mod efuse;
 pub mod erase;
+pub mod flashall;
 pub mod format;
+pub mod hashcheck;
+pub mod manifest;
 pub mod peek;
 pub mod pgpt;
+pub mod preloader;
+pub mod rawxml;
 pub mod readall;
+pub mod readdynamic;
 pub mod readflash;
 pub mod reboot;
+pub mod restoreall;
+pub mod rpmb;
+pub mod safeformat;
 pub mod seccfg;
+#[cfg(feature = "net")]
+pub mod serve;
 pub mod shutdown;
+pub mod storageinfo;
 pub mod upload;
+pub mod watch;
+pub mod wipefrp;
 pub mod writeflash;
+pub mod writepreloader;
 pub mod xflash;
 
+pub use bootmode::BootModeArgs;
+pub use dainfo::DaInfoArgs;
+pub use darepo::DaRepoArgs;
+pub use detect::DetectArgs;
 pub use download::DownloadArgs;
+pub use efuse::EfuseArgs;
 pub use erase::EraseArgs;
+pub use flashall::FlashAllArgs;
 pub use format::FormatArgs;
+pub use hashcheck::HashCheckArgs;
+pub use manifest::ManifestArgs;
 pub use peek::PeekArgs;
 pub use pgpt::PgptArgs;
+pub use preloader::PreloaderArgs;
+pub use rawxml::RawXmlArgs;
 pub use readall::ReadAllArgs;
+pub use readdynamic::ReadDynamicArgs;
 pub use readflash::ReadArgs;
 pub use reboot::RebootArgs;
+pub use restoreall::RestoreAllArgs;
+pub use rpmb::RpmbArgs;
+pub use safeformat::SafeFormatArgs;
 pub use seccfg::SeccfgArgs;
+#[cfg(feature = "net")]
+pub use serve::ServeArgs;
 pub use shutdown::ShutdownArgs;
+pub use storageinfo::StorageInfoArgs;
 pub use upload::UploadArgs;
+pub use watch::WatchArgs;
+pub use wipefrp::WipeFrpArgs;
 pub use writeflash::WriteArgs;
+pub use writepreloader::WritePreloaderArgs;
 pub use xflash::XFlashArgs;
@@ -4,29 +4,35 @@
 */
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use clap::Args;
 use clap_num::maybe_hex;
 use log::info;
-use penumbra::Device;
+use penumbra::{Device, ProgressEvent};
 use tokio::fs::File;
-use tokio::io::BufWriter;
+use tokio::io::{AsyncWriteExt, BufWriter};
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
 use crate::cli::helpers::AntumbraProgress;
 use crate::cli::state::PersistedDeviceState;
 
+/// Bytes read per BROM request, so progress moves along on slow UART links.
+const BROM_PEEK_CHUNK: usize = 0x1000;
+
 #[derive(Args, Debug)]
 pub struct PeekArgs {
     #[command(flatten)]
-    pub da: DaArgs,
+    pub da: Option<DaArgs>,
+    /// Read through the BROM/preloader Read32 command, without any DA
+    #[arg(long)]
+    pub brom: bool,
     /// The address to read from.
-    #[clap(value_parser=maybe_hex::<u32>)]
+    #[arg(long = "addr", visible_alias = "address", value_parser = maybe_hex::<u32>)]
     pub address: u32,
     /// The number of bytes to read.
-    #[clap(value_parser=maybe_hex::<usize>)]
+    #[arg(long, value_parser = maybe_hex::<usize>)]
     pub length: usize,
     /// The output file to save the read data to.
     pub output_file: PathBuf,
@@ -38,13 +44,35 @@ impl CommandMetadata for PeekArgs {
     }
 
     fn long_about() -> &'static str {
-        "Read memory from the specified address and length. DA Extensions must be loaded for this command to work."
+        "Read memory from the specified address and length. DA Extensions must be loaded for this command to work,
+        which needs a DA that can be patched. Unaligned addresses and lengths are fine.
+        With --brom, the memory is read with the Read32 command of BROM or preloader instead,
+        so no DA is needed. The device must not be in DA mode, and most BROMs and preloaders
+        only allow reading some ranges."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra peek --da DA.bin --addr 0x200000 --length 0x40000 sram.bin",
+            "antumbra peek --brom --addr 0x10001000 --length 0x100 regs.bin",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, needs_exploits: true, ..Default::default() }
     }
 }
 
 #[async_trait]
 impl MtkCommand for PeekArgs {
     async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        if self.brom {
+            return self.brom_peek(dev).await;
+        }
+        if self.da.is_none() {
+            return Err(anyhow!("A DA file is needed, give one with --da or use --brom"));
+        }
+
         dev.enter_da_mode().await?;
 
         state.connection_type = CONN_DA;
@@ -57,10 +85,10 @@ impl MtkCommand for PeekArgs {
 
         let mut progress_callback = {
             let pb = &pb;
-            move |read: usize, total: usize| {
-                pb.update(read as u64, "Reading memory...");
+            move |event: ProgressEvent| {
+                pb.event(&event, "Reading memory...");
 
-                if read >= total {
+                if event.is_done() {
                     pb.finish("Memory readback completed!");
                 }
             }
@@ -74,7 +102,7 @@ impl MtkCommand for PeekArgs {
         match dev.peek(self.address, self.length, &mut writer, &mut progress_callback).await {
             Ok(_) => {}
             Err(e) => {
-                pb.abandon("Format failed!");
+                pb.abandon("Memory readback failed!");
                 return Err(e)?;
             }
         }
@@ -85,10 +113,54 @@ impl MtkCommand for PeekArgs {
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.as_ref().and_then(|da| da.da_file.as_ref())
     }
 
     fn pl(&self) -> Option<&PathBuf> {
-        self.da.preloader_file.as_ref()
+        self.da.as_ref().and_then(|da| da.preloader_file.as_ref())
+    }
+
+    fn invocation_flags(&self) -> Option<CommandFlags> {
+        // BROM reads need neither a DA nor its extensions
+        self.brom.then(CommandFlags::default)
+    }
+}
+
+impl PeekArgs {
+    /// Reads the memory through BROM/preloader commands, a chunk at a time.
+    async fn brom_peek(&self, dev: &mut Device) -> Result<()> {
+        if self.address as u64 + self.length as u64 > 1 << 32 {
+            return Err(anyhow!("Range 0x{:08X}+0x{:X} is past 4 GB", self.address, self.length));
+        }
+
+        let mut writer = BufWriter::new(File::create(&self.output_file).await?);
+        let pb = AntumbraProgress::new(self.length as u64);
+
+        info!(
+            "Reading memory from address 0x{:08X}, length 0x{:X} bytes through BROM...",
+            self.address, self.length
+        );
+
+        let mut read = 0;
+        while read < self.length {
+            let chunk = BROM_PEEK_CHUNK.min(self.length - read);
+            let data = match dev.brom_read32(self.address + read as u32, chunk).await {
+                Ok(data) => data,
+                Err(e) => {
+                    pb.abandon("Memory readback failed!");
+                    return Err(e)?;
+                }
+            };
+
+            writer.write_all(&data).await?;
+            read += chunk;
+            pb.update(read as u64, "Reading memory...");
+        }
+
+        writer.flush().await?;
+        pb.finish("Memory readback completed!");
+        info!("Memory readback completed, saved to {:?}", self.output_file);
+
+        Ok(())
     }
 }
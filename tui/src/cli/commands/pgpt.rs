@@ -4,26 +4,260 @@
 */
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use clap::Args;
+use clap::{Args, Subcommand};
 use human_bytes::human_bytes;
-use log::info;
+use log::{debug, info, warn};
 use penumbra::Device;
+use penumbra::core::inspect::{PROBE_SIZE, detect_content};
+use penumbra::core::storage::{Gpt, GptType, Partition};
+use serde_json::{Value, json};
+use tokio::fs::{File, read, write};
+use tokio::io::{AsyncWriteExt, BufWriter};
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs, OutputArgs};
 use crate::cli::state::PersistedDeviceState;
 
+/// Partitions whose content is detected at most, one small read each.
+const MAX_CONTENT_PROBES: usize = 128;
+
+/// Reads the start of `partition` and classifies it, or tells why it couldn't.
+async fn probe_content(dev: &mut Device, partition: &Partition) -> String {
+    if partition.issue.is_some() {
+        return "invalid geometry".to_string();
+    }
+
+    let size = PROBE_SIZE.min(partition.size);
+    let mut data = Vec::with_capacity(size);
+    let mut progress = |_| {};
+    match dev.read_offset(partition.address, size, partition.kind, &mut progress, &mut data).await {
+        Ok(()) => detect_content(&data).to_string(),
+        Err(e) => {
+            debug!("Could not read the start of '{}': {}", partition.name, e);
+            "unreadable".to_string()
+        }
+    }
+}
+
+/// Lists the dynamic partitions of super, or tells why they couldn't be read.
+async fn print_dynamic_partitions(dev: &mut Device) {
+    let metadata = match dev.read_lp_metadata("").await {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            warn!("Could not read the dynamic partitions of super: {}", e);
+            return;
+        }
+    };
+
+    info!(
+        "Dynamic Partitions (LP metadata {}.{}):",
+        metadata.major_version, metadata.minor_version
+    );
+    for p in &metadata.partitions {
+        info!(
+            "Name: {:<15} \t Group: {:<15} \t Size: 0x{:08X} ({}) \t Extents: {}{}",
+            p.name,
+            p.group,
+            p.size(),
+            human_bytes(p.size() as f64),
+            p.extents.len(),
+            if p.is_readonly() { " \t Read-only" } else { "" }
+        );
+    }
+}
+
+/// Machine-readable description of a partition, as saved by backups and printed by --json.
+fn partition_json(p: &Partition) -> Value {
+    json!({
+        "name": p.name,
+        "address": p.address,
+        "size": p.size,
+        "section": p.kind.as_str(),
+    })
+}
+
 #[derive(Args, Debug)]
-pub struct PgptArgs {
+pub struct GptBackupArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// Output path prefix. Writes `<file>.pgpt.bin`, `<file>.sgpt.bin` and `<file>.json`
+    pub file: PathBuf,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[async_trait]
+impl MtkCommand for GptBackupArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        // Check every output before touching the device, to not leave a partial backup
+        let resolver = self.output.resolver();
+        let pgpt_path = resolver.resolve(&self.file.with_added_extension("pgpt.bin")).await?;
+        let sgpt_path = resolver.resolve(&self.file.with_added_extension("sgpt.bin")).await?;
+        let json_path = resolver.resolve(&self.file.with_added_extension("json")).await?;
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        for (which, path) in [(GptType::Pgpt, pgpt_path), (GptType::Sgpt, sgpt_path)] {
+            let mut writer = BufWriter::new(File::create(&path).await?);
+            dev.dump_gpt(&mut writer, which).await?;
+            writer.flush().await?;
+            info!("Saved {:?} to '{}'", which, path.display());
+        }
+
+        let partitions: Vec<_> = dev.get_partitions().await?.iter().map(partition_json).collect();
+
+        write(&json_path, serde_json::to_vec_pretty(&json!({ "partitions": partitions }))?).await?;
+        info!("Saved parsed partition table to '{}'", json_path.display());
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct GptFixMbrArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+}
+
+#[async_trait]
+impl MtkCommand for GptFixMbrArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        let status = dev.check_mbr().await?;
+        if status.is_healthy() {
+            info!("Found a {}, nothing to fix.", status);
+            return Ok(());
+        }
+
+        info!("Found a {}, writing a new protective MBR...", status);
+        dev.fix_mbr().await?;
+        info!("Protective MBR written, GPT partition entries were left untouched.");
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct GptRestoreArgs {
     #[command(flatten)]
     pub da: DaArgs,
+    /// Raw primary GPT from LBA0, like the `<file>.pgpt.bin` written by `gpt backup`
+    pub file: PathBuf,
+}
+
+#[async_trait]
+impl MtkCommand for GptRestoreArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let data = read(&self.file).await?;
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        // Only a valid primary GPT for this storage is written over the current one
+        let storage = dev.get_storage_info().await?;
+        Gpt::parse_as(&data, GptType::Pgpt, storage.kind)
+            .map_err(|e| anyhow!("'{}' isn't a primary GPT: {}", self.file.display(), e))?;
+
+        let Some(pgpt) = dev.dev_info.get_partition("PGPT").await else {
+            return Err(anyhow!("No PGPT partition found on the device."));
+        };
+        if data.len() > pgpt.size {
+            return Err(anyhow!(
+                "'{}' (0x{:X} bytes) is larger than the PGPT region (0x{:X} bytes).",
+                self.file.display(),
+                data.len(),
+                pgpt.size
+            ));
+        }
+
+        info!("Writing the primary GPT from '{}'...", self.file.display());
+        let mut progress = |_| {};
+        dev.write_offset_verified(
+            pgpt.address,
+            data.len(),
+            &mut &data[..],
+            pgpt.kind,
+            true,
+            &mut progress,
+        )
+        .await?;
+
+        let partitions = dev.refresh_partitions().await?;
+        info!("Primary GPT restored, {} partitions.", partitions.len());
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum GptSubcommand {
+    /// Backup both raw GPT copies, along with a JSON of the parsed partition table.
+    Backup(GptBackupArgs),
+    /// Regenerate the protective MBR in LBA0, keeping the GPT as it is.
+    FixMbr(GptFixMbrArgs),
+    /// Write back a raw primary GPT saved by `gpt backup`.
+    Restore(GptRestoreArgs),
+}
+
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct PgptArgs {
+    #[command(subcommand)]
+    pub command: Option<GptSubcommand>,
+    #[command(flatten)]
+    pub da: Option<DaArgs>,
+    /// Read the start of each partition to tell its content (filesystem, boot image, empty...)
+    #[arg(long)]
+    pub detect_content: bool,
+    /// Also list the dynamic partitions inside super, from its LP metadata
+    #[arg(long)]
+    pub dynamic: bool,
+    /// Print the partition table as JSON on stdout
+    #[arg(long)]
+    pub json: bool,
+    /// Also save the raw primary GPT to this file
+    #[arg(long, value_name = "FILE")]
+    pub raw_gpt: Option<PathBuf>,
 }
 
 impl CommandMetadata for PgptArgs {
     fn visible_aliases() -> &'static [&'static str] {
-        &["gpt"]
+        &["pgpt", "partitions", "ls"]
     }
 
     fn about() -> &'static str {
@@ -31,39 +265,178 @@ impl CommandMetadata for PgptArgs {
     }
 
     fn long_about() -> &'static str {
-        Self::about()
+        "Display the partition table of the connected device.
+        Use `gpt backup <file>` to save the raw primary and backup GPT along with
+        a JSON of the parsed partition table, `gpt restore <file>` to write such a primary
+        GPT back, and `gpt fix-mbr` to regenerate a broken protective MBR.
+        With --detect-content, the first 4 KB of each partition are read to tell
+        filesystems, boot and vbmeta images and empty partitions apart.
+        With --dynamic, the LP metadata of super is read as well, to list the dynamic
+        partitions (system, vendor, product...) it holds.
+        With --json, the table is printed on stdout as JSON, and logs go to stderr.
+        With --raw-gpt, the raw primary GPT is saved as well, for offline inspection."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra gpt --da DA.bin",
+            "antumbra gpt backup --da DA.bin gpt --timestamp",
+            "antumbra gpt fix-mbr --da DA.bin",
+            "antumbra gpt restore --da DA.bin gpt.pgpt.bin",
+            "antumbra gpt --da DA.bin --detect-content",
+            "antumbra gpt --da DA.bin --dynamic",
+            "antumbra ls --da DA.bin --json",
+            "antumbra partitions --da DA.bin --raw-gpt pgpt.bin",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
     }
 }
 
 #[async_trait]
 impl MtkCommand for PgptArgs {
     async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        match &self.command {
+            Some(GptSubcommand::Backup(cmd)) => return cmd.run(dev, state).await,
+            Some(GptSubcommand::FixMbr(cmd)) => return cmd.run(dev, state).await,
+            Some(GptSubcommand::Restore(cmd)) => return cmd.run(dev, state).await,
+            None => {}
+        }
+
         dev.enter_da_mode().await?;
 
         state.connection_type = CONN_DA;
         state.flash_mode = 1;
 
-        let partitions = dev.dev_info.partitions().await;
+        let partitions = dev.get_partitions().await?;
+
+        if self.detect_content && partitions.len() > MAX_CONTENT_PROBES {
+            warn!(
+                "Only detecting the content of the first {} partitions out of {}.",
+                MAX_CONTENT_PROBES,
+                partitions.len()
+            );
+        }
+
+        if !self.json {
+            info!("Partition Table:");
+        }
+        let mut entries = Vec::with_capacity(partitions.len());
+        for (i, p) in partitions.iter().enumerate() {
+            let content = if self.detect_content && i < MAX_CONTENT_PROBES {
+                Some(probe_content(dev, p).await)
+            } else {
+                None
+            };
+
+            if self.json {
+                let mut entry = partition_json(p);
+                if let Some(content) = &content {
+                    entry["content"] = json!(content);
+                }
+                entries.push(entry);
+                continue;
+            }
 
-        info!("Partition Table:");
-        for p in partitions {
             info!(
-                "Name: {:<15} \t Addr: 0x{:08X} \t Size: 0x{:08X} ({})",
+                "Name: {:<15} \t Addr: 0x{:08X} \t Size: 0x{:08X} ({}) \t Section: {}{}",
                 p.name,
                 p.address,
                 p.size,
-                human_bytes(p.size as f64)
+                human_bytes(p.size as f64),
+                p.kind.as_str(),
+                content.map(|c| format!(" \t Content: {}", c)).unwrap_or_default()
             );
         }
 
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&json!({ "partitions": entries }))?);
+        }
+
+        if let Some(path) = &self.raw_gpt {
+            let mut writer = BufWriter::new(File::create(path).await?);
+            dev.dump_gpt(&mut writer, GptType::Pgpt).await?;
+            writer.flush().await?;
+            info!("Saved the raw primary GPT to '{}'", path.display());
+        }
+
+        if self.dynamic {
+            print_dynamic_partitions(dev).await;
+        }
+
+        match dev.check_mbr().await {
+            Ok(status) if status.is_healthy() => info!("LBA0: {}", status),
+            Ok(status) => warn!("LBA0: {}. Use `gpt fix-mbr` to regenerate it.", status),
+            Err(e) => warn!("LBA0: could not check the protective MBR: {}", e),
+        }
+
         Ok(())
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        match &self.command {
+            Some(GptSubcommand::Backup(cmd)) => cmd.da(),
+            Some(GptSubcommand::FixMbr(cmd)) => cmd.da(),
+            Some(GptSubcommand::Restore(cmd)) => cmd.da(),
+            None => self.da.as_ref().and_then(|da| da.da_file.as_ref()),
+        }
     }
 
     fn pl(&self) -> Option<&PathBuf> {
-        self.da.preloader_file.as_ref()
+        match &self.command {
+            Some(GptSubcommand::Backup(cmd)) => cmd.pl(),
+            Some(GptSubcommand::FixMbr(cmd)) => cmd.pl(),
+            Some(GptSubcommand::Restore(cmd)) => cmd.pl(),
+            None => self.da.as_ref().and_then(|da| da.preloader_file.as_ref()),
+        }
+    }
+
+    fn writes_stdout(&self) -> bool {
+        self.command.is_none() && self.json
+    }
+
+    fn invocation_flags(&self) -> Option<CommandFlags> {
+        match &self.command {
+            Some(GptSubcommand::FixMbr(_) | GptSubcommand::Restore(_)) => {
+                Some(CommandFlags { destructive: true, needs_da: true, ..Default::default() })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::{CliArgs, Commands};
+
+    fn parse(args: &[&str]) -> Commands {
+        CliArgs::try_parse_from([&["antumbra"], args].concat()).unwrap().command.unwrap()
+    }
+
+    #[test]
+    fn gpt_backup_is_parsed() {
+        for name in ["gpt", "pgpt"] {
+            let Commands::Gpt(PgptArgs { command: Some(GptSubcommand::Backup(backup)), .. }) =
+                parse(&[name, "backup", "--da", "DA.bin", "gpt"])
+            else {
+                panic!("{} backup isn't a GPT backup", name);
+            };
+            assert_eq!(backup.file, PathBuf::from("gpt"));
+        }
+    }
+
+    #[test]
+    fn gpt_lists_partitions() {
+        for name in ["gpt", "pgpt", "partitions", "ls"] {
+            let Commands::Gpt(args) = parse(&[name, "--da", "DA.bin", "--json"]) else {
+                panic!("{} doesn't list partitions", name);
+            };
+            assert!(args.command.is_none() && args.json);
+        }
     }
 }
@@ -0,0 +1,198 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::{Args, Subcommand};
+use log::info;
+use penumbra::{Device, ProgressEvent};
+use tokio::fs::{read, write};
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs, OutputArgs};
+use crate::cli::helpers::AntumbraProgress;
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Args, Debug)]
+pub struct PreloaderDumpArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// The file to save the bare preloader to
+    pub output_file: PathBuf,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+impl CommandMetadata for PreloaderDumpArgs {
+    fn examples() -> &'static [&'static str] {
+        &["antumbra preloader dump --da DA.bin preloader.bin"]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for PreloaderDumpArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let path = self.output.resolver().resolve(&self.output_file).await?;
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        info!("Reading preloader...");
+        let preloader = dev.read_preloader().await?;
+        write(&path, &preloader).await?;
+
+        info!("Saved the preloader (0x{:X} bytes) to '{}'", preloader.len(), path.display());
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct PreloaderFlashArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// The preloader to write, either a bare preloader or a full boot image
+    pub file: PathBuf,
+}
+
+impl CommandMetadata for PreloaderFlashArgs {
+    fn examples() -> &'static [&'static str] {
+        &["antumbra preloader flash --da DA.bin preloader_penangf.bin"]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for PreloaderFlashArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let data = read(&self.file).await?;
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        let pb = AntumbraProgress::new(data.len() as u64).for_partition("preloader");
+        let mut progress_callback = {
+            let pb = &pb;
+            move |event: ProgressEvent| {
+                pb.event(&event, "Writing preloader...");
+            }
+        };
+
+        info!("Writing preloader from '{}' to both boot partitions...", self.file.display());
+
+        match dev.write_preloader(&data, true, &mut progress_callback).await {
+            Ok(_) => pb.finish("Preloader written!"),
+            Err(e) => {
+                pb.abandon("Preloader write failed!");
+                return Err(e)?;
+            }
+        }
+
+        info!("Preloader written and verified.");
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PreloaderSubcommand {
+    /// Save the bare preloader from BOOT1, without its boot header.
+    #[command(after_help = PreloaderDumpArgs::after_help())]
+    Dump(PreloaderDumpArgs),
+    /// Write a preloader to BOOT1 and BOOT2, keeping the boot header on the device.
+    #[command(after_help = PreloaderFlashArgs::after_help())]
+    Flash(PreloaderFlashArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct PreloaderArgs {
+    #[command(subcommand)]
+    pub command: PreloaderSubcommand,
+}
+
+impl CommandMetadata for PreloaderArgs {
+    fn about() -> &'static str {
+        "Dump or flash the preloader of the boot partitions."
+    }
+
+    fn long_about() -> &'static str {
+        "Dump or flash the preloader, handling the EMMC_BOOT / UFS_BOOT header of the boot
+        partitions instead of relying on manual offsets.
+        `dump` saves the bare preloader, as found in firmware packages, without the header.
+        `flash` takes a bare preloader or a full boot image, refuses anything else, and writes
+        it to both BOOT1 and BOOT2. Bare preloaders take the place of the one on the device,
+        after its boot header if it has one. Each copy is verified, and the original preloader
+        is restored on failure."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra preloader dump --da DA.bin preloader.bin",
+            "antumbra preloader flash --da DA.bin preloader_penangf.bin",
+        ]
+    }
+
+    // Each verb declares its own, only flashing modifies the device
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for PreloaderArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        match &self.command {
+            PreloaderSubcommand::Dump(cmd) => cmd.run(dev, state).await,
+            PreloaderSubcommand::Flash(cmd) => cmd.run(dev, state).await,
+        }
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        match &self.command {
+            PreloaderSubcommand::Dump(cmd) => cmd.da(),
+            PreloaderSubcommand::Flash(cmd) => cmd.da(),
+        }
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        match &self.command {
+            PreloaderSubcommand::Dump(cmd) => cmd.pl(),
+            PreloaderSubcommand::Flash(cmd) => cmd.pl(),
+        }
+    }
+
+    fn invocation_flags(&self) -> Option<CommandFlags> {
+        Some(match &self.command {
+            PreloaderSubcommand::Dump(_) => PreloaderDumpArgs::flags(),
+            PreloaderSubcommand::Flash(_) => PreloaderFlashArgs::flags(),
+        })
+    }
+}
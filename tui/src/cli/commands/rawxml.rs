@@ -0,0 +1,128 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::Args;
+use log::info;
+use penumbra::Device;
+use penumbra::da::xml::RawXmlCommand;
+use serde::Deserialize;
+use tokio::fs::{read, write};
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Args, Debug)]
+pub struct RawXmlArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// JSON file describing the command to send
+    pub command_file: PathBuf,
+    /// Save the answer of the DA to this file instead of printing it
+    #[arg(long, short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+/// Command description, as read from the JSON file.
+#[derive(Deserialize, Debug)]
+struct RawXmlFile {
+    command: String,
+    #[serde(default = "default_version")]
+    version: String,
+    #[serde(default)]
+    args: Vec<RawXmlArg>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RawXmlArg {
+    /// Section holding the argument, `arg` when missing
+    section: Option<String>,
+    tag: String,
+    value: String,
+}
+
+fn default_version() -> String {
+    "1.0".to_string()
+}
+
+impl From<RawXmlFile> for RawXmlCommand {
+    fn from(file: RawXmlFile) -> Self {
+        RawXmlCommand {
+            name: file.command,
+            version: file.version,
+            args: file.args.into_iter().map(|a| (a.section, a.tag, a.value)).collect(),
+        }
+    }
+}
+
+impl CommandMetadata for RawXmlArgs {
+    fn visible_aliases() -> &'static [&'static str] {
+        &["rawxml"]
+    }
+
+    fn about() -> &'static str {
+        "Send a raw XML command to the DA (XML / V6 devices only)."
+    }
+
+    fn long_about() -> &'static str {
+        "Send an XML DA command described by a JSON file, and print the answer of the DA.
+        The file gives the command, its version (1.0 by default) and its arguments, e.g.
+        {\"command\": \"CMD:GET-SYS-PROPERTY\", \"args\": [{\"tag\": \"key\", \"value\": \"DA.SLA\"}]}
+        Arguments go in the arg section, unless another one is given with \"section\".
+        The command is sent as is: vendor commands can erase, lock or brick the device.
+        Only commands uploading their answer are followed, commands expecting a file stop
+        with an error."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra raw-xml --da DA.bin cmd.json",
+            "antumbra raw-xml --da DA.bin cmd.json -o out.bin",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for RawXmlArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let json = read(&self.command_file).await?;
+        let file: RawXmlFile = serde_json::from_slice(&json).map_err(|e| {
+            anyhow!("Invalid command file '{}': {}", self.command_file.display(), e)
+        })?;
+        let cmd = RawXmlCommand::from(file);
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        info!("Sending {}...", cmd.name);
+        let resp = dev.send_raw_xml(&cmd).await?;
+
+        if let Some(path) = &self.output {
+            write(path, &resp).await?;
+            info!("Saved the answer ({} bytes) to {:?}", resp.len(), path);
+            return Ok(());
+        }
+
+        info!("{}", resp);
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
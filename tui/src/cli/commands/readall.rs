@@ -6,17 +6,45 @@ use std::path::{Path, PathBuf};
 
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use clap::Args;
-use log::info;
-use penumbra::Device;
-use tokio::fs::{File, create_dir_all, read_dir};
-use tokio::io::{AsyncWriteExt, BufWriter};
+use clap::{Args, ValueEnum};
+use log::{info, warn};
+use penumbra::core::backup::{BACKUP_MANIFEST_NAME, BackupManifest};
+use penumbra::core::storage::Partition;
+use penumbra::{Device, ProgressEvent};
+use tokio::fs::create_dir_all;
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
-use crate::cli::helpers::AntumbraProgress;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs, OutputArgs};
+use crate::cli::helpers::{
+    AntumbraProgress,
+    DumpFormat,
+    DumpWriter,
+    ManifestBadRange,
+    ManifestEntry,
+    ManifestWriter,
+    file_sha256,
+};
 use crate::cli::state::PersistedDeviceState;
 
+/// Partitions that are small, device specific and hard or impossible to recover
+/// if lost, dumped first by default.
+const CRITICAL_PARTITIONS: &[&str] = &[
+    "proinfo", "nvram", "nvdata", "nvcfg", "persist", "protect1", "protect2", "seccfg", "frp",
+    "para", "expdb", "otp",
+];
+
+/// Order in which partitions are dumped.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadOrder {
+    /// Critical partitions first, then everything else from the smallest
+    #[default]
+    Critical,
+    /// From the smallest to the largest partition
+    Size,
+    /// Partition table order
+    Gpt,
+}
+
 #[derive(Args, Debug)]
 pub struct ReadAllArgs {
     #[command(flatten)]
@@ -26,6 +54,50 @@ pub struct ReadAllArgs {
     /// The destination file
     #[arg(long, short = 's', value_delimiter = ',')]
     pub skip: Vec<String>,
+    /// The on-disk format of the dump
+    #[arg(long, value_enum, default_value_t = DumpFormat::Raw)]
+    pub format: DumpFormat,
+    /// The order in which partitions are dumped
+    #[arg(long, value_enum, default_value_t = ReadOrder::Critical)]
+    pub order: ReadOrder,
+    /// Partitions to dump first with `--order critical`, replacing the built-in list
+    #[arg(long, value_delimiter = ',')]
+    pub critical: Vec<String>,
+    /// Skip ranges that keep failing instead of the whole partition, zero-filling them
+    #[arg(long)]
+    pub skip_bad_blocks: bool,
+    /// Continue an interrupted raw dump in the same directory: complete files are kept,
+    /// partial ones are appended to
+    #[arg(long, conflicts_with_all = ["overwrite", "timestamp"])]
+    pub resume: bool,
+    /// Also dump partitions whose reported geometry looks invalid
+    #[arg(long)]
+    pub force: bool,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+impl ReadAllArgs {
+    /// Sorts partitions according to the selected order.
+    /// The sort is stable, so partitions of equal rank keep their GPT order.
+    fn sort_partitions(&self, partitions: &mut [Partition]) {
+        match self.order {
+            ReadOrder::Gpt => {}
+            ReadOrder::Size => partitions.sort_by_key(|p| p.size),
+            ReadOrder::Critical => {
+                let rank = |p: &Partition| {
+                    let critical = if self.critical.is_empty() {
+                        CRITICAL_PARTITIONS.iter().position(|c| *c == p.name)
+                    } else {
+                        self.critical.iter().position(|c| *c == p.name)
+                    };
+                    // Critical partitions keep the configured order, the rest go by size
+                    (critical.unwrap_or(usize::MAX), p.size)
+                };
+                partitions.sort_by_key(rank);
+            }
+        }
+    }
 }
 
 impl CommandMetadata for ReadAllArgs {
@@ -39,7 +111,29 @@ impl CommandMetadata for ReadAllArgs {
 
     fn long_about() -> &'static str {
         "Read all partitions from the device and save them to the specified output directory,
-        skipping any partitions listed in the skip option."
+        skipping any partitions listed in the skip option.
+        Existing files are never overwritten unless --overwrite is given. Use --timestamp
+        to dump again in the same directory, or --resume to finish an interrupted raw dump.
+        Partitions reported with a zero size or past the end of the storage are skipped,
+        unless --force is given. --format zstd or gzip compresses each dump while reading,
+        the manifest still records the hash of the uncompressed data.
+        A backup.json describing the device, its partition table and the dumps is written
+        along with the dumps, for restore-all."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra read-all --da DA.bin dump/",
+            "antumbra read-all --da DA.bin dump/ --skip userdata,super --format sparse",
+            "antumbra read-all --da DA.bin dump/ --format gzip",
+            "antumbra read-all --da DA.bin dump/ --skip-bad-blocks",
+            "antumbra read-all --da DA.bin dump/ --timestamp",
+            "antumbra read-all --da DA.bin dump/ --resume",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
     }
 }
 
@@ -48,6 +142,10 @@ impl MtkCommand for ReadAllArgs {
     async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
         let output_dir: &Path = &self.output_dir;
 
+        if self.resume && self.format != DumpFormat::Raw {
+            return Err(anyhow!("Only raw dumps can be resumed."));
+        }
+
         if let Err(e) = create_dir_all(output_dir).await {
             return Err(anyhow!(
                 "Failed to create output directory '{}': {}",
@@ -56,23 +154,56 @@ impl MtkCommand for ReadAllArgs {
             ));
         }
 
-        let mut dir_entries = read_dir(output_dir).await?;
-        if dir_entries.next_entry().await?.is_some() {
-            return Err(anyhow!("Output directory '{}' is not empty", output_dir.display()));
-        }
+        // Resuming rewrites the manifest and appends to the existing files
+        let resolver = self.output.resolver();
+        let resolve = |path: PathBuf| async {
+            if self.resume { Ok(path) } else { resolver.resolve(&path).await }
+        };
+        let manifest_path = resolve(output_dir.join(ManifestWriter::FILE_NAME)).await?;
+        let backup_path = resolve(output_dir.join(BACKUP_MANIFEST_NAME)).await?;
 
         dev.enter_da_mode().await?;
 
         state.connection_type = CONN_DA;
         state.flash_mode = 1;
 
-        let partitions = dev.get_partitions().await;
+        let mut partitions = dev.get_partitions().await?;
         if partitions.is_empty() {
             info!("No partitions found on device.");
             return Ok(());
         }
 
-        let proto = dev.get_protocol().ok_or(anyhow!("Failed to get device protocol"))?;
+        // Describes the whole table, the dumped partitions get their file and hash below
+        let mut backup = match dev.get_storage_info().await {
+            Ok(info) => {
+                let soc_id = dev.dev_info.soc_id().await;
+                let hw_code = dev.dev_info.hw_code().await;
+                let backup = BackupManifest::new(hw_code, soc_id.as_deref(), &info, &partitions);
+                backup.save(&backup_path).await?;
+                Some(backup)
+            }
+            Err(e) => {
+                warn!("Failed to read the storage info, no backup manifest will be written: {}", e);
+                None
+            }
+        };
+
+        if !self.force {
+            let (valid, flagged): (Vec<_>, Vec<_>) =
+                partitions.into_iter().partition(|p| p.issue.is_none());
+            if !flagged.is_empty() {
+                let names: Vec<_> = flagged.iter().map(|p| p.name.as_str()).collect();
+                warn!("Skipping partitions with invalid geometry: {}", names.join(", "));
+                warn!("Use --force to dump them anyway.");
+            }
+            partitions = valid;
+        }
+
+        self.sort_partitions(&mut partitions);
+
+        let mut manifest = ManifestWriter::create(&manifest_path).await?;
+        dev.set_skip_bad_blocks(self.skip_bad_blocks);
+        let mut bad_ranges = Vec::new();
 
         for p in partitions {
             if self.skip.contains(&p.name) {
@@ -80,44 +211,121 @@ impl MtkCommand for ReadAllArgs {
                 continue;
             }
 
-            let output_path = self.output_dir.join(format!("{}.bin", p.name));
-            let mut output_file = BufWriter::new(File::create(&output_path).await?);
-
             let part_size = p.size as u64;
-            let pb = AntumbraProgress::new(part_size);
+            let output_path =
+                resolve(output_dir.join(format!("{}.{}", p.name, self.format.extension()))).await?;
+            let file_name = output_path.file_name().unwrap_or_default().to_string_lossy().into();
+            let (mut output_file, start) = if self.resume {
+                DumpWriter::resume(&output_path, part_size).await?
+            } else {
+                (DumpWriter::create(&output_path, self.format, part_size).await?, 0)
+            };
+
+            if start > 0 && start < part_size {
+                info!("Resuming '{}' at 0x{:X}", p.name, start);
+            }
+
+            let pb = AntumbraProgress::resumed(part_size, start).for_partition(&p.name);
 
             let mut progress_callback = {
                 let pb = &pb;
-                move |read: usize, total: usize| {
-                    pb.update(read as u64, "Reading...");
+                move |event: ProgressEvent| {
+                    pb.event(&event, "Reading...");
 
-                    if read >= total {
+                    if event.is_done() {
                         pb.finish("Read complete!");
                     }
                 }
             };
 
-            match proto
-                .read_flash(p.address, p.size, p.kind, &mut progress_callback, &mut output_file)
+            let mut entry = ManifestEntry {
+                name: p.name.clone(),
+                file: file_name,
+                address: p.address,
+                size: part_size,
+                sha256: None,
+                complete: false,
+                bad_ranges: Vec::new(),
+            };
+
+            let result = if start >= part_size {
+                pb.finish("Already complete");
+                Ok(())
+            } else if p.issue.is_some() {
+                let remaining = p.size - start as usize;
+                dev.read_offset(
+                    p.address + start,
+                    remaining,
+                    p.kind,
+                    &mut progress_callback,
+                    &mut output_file,
+                )
                 .await
-            {
+            } else {
+                dev.read_partition_from(&p.name, start, &mut progress_callback, &mut output_file)
+                    .await
+            };
+
+            match result {
                 Ok(_) => {}
                 Err(_) => {
                     pb.abandon("Read failed! Skipping partition.");
+                    // A partial dump can't be encoded properly, only make sure the file is closed
+                    output_file.finish().await.ok();
+                    manifest.push(entry).await?;
+                    continue;
+                }
+            }
+
+            output_file.finish().await?;
+
+            // The device only hashed what it read this time
+            if start > 0 {
+                entry.sha256 = Some(file_sha256(&output_path).await?);
+            }
+
+            if let Some(summary) = dev.last_operation_summary().filter(|_| start < part_size) {
+                if start == 0 {
+                    entry.sha256 = summary.sha256.clone();
+                }
+                for range in &summary.bad_ranges {
+                    bad_ranges.push((p.name.clone(), range.clone()));
+                    entry.bad_ranges.push(ManifestBadRange {
+                        offset: start + range.offset,
+                        length: range.length,
+                        error: range.error.clone(),
+                    });
                 }
             }
+            entry.complete = true;
+            if let (Some(backup), Some(sha256)) = (&mut backup, &entry.sha256) {
+                backup.record(&p.name, &entry.file, sha256);
+                backup.save(&backup_path).await?;
+            }
+            manifest.push(entry).await?;
 
-            output_file.flush().await?;
             info!("Saved partition '{}' to '{}'", p.name, output_path.display());
         }
 
+        if !bad_ranges.is_empty() {
+            warn!("{} bad ranges were skipped and zero-filled:", bad_ranges.len());
+            println!("{:<16} {:>18} {:>12}  Error", "Partition", "Offset", "Length");
+            for (name, range) in &bad_ranges {
+                println!(
+                    "{:<16} {:>#18x} {:>#12x}  {}",
+                    name, range.offset, range.length, range.error
+                );
+            }
+            return Ok(());
+        }
+
         info!("All partitions read successfully.");
 
         Ok(())
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.da_file.as_ref()
     }
 
     fn pl(&self) -> Option<&PathBuf> {
@@ -0,0 +1,123 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::Args;
+use log::info;
+use penumbra::core::avb::strip_slot_suffix;
+use penumbra::{Device, ProgressEvent};
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs, OutputArgs};
+use crate::cli::helpers::{AntumbraProgress, DumpFormat, DumpWriter, is_stdio};
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Args, Debug)]
+pub struct ReadDynamicArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// The dynamic partition to read, with its slot suffix if any (e.g. system_a)
+    pub partition: String,
+    /// The destination file, or `-` for stdout
+    pub output_file: PathBuf,
+    /// The on-disk format of the dump
+    #[arg(long, value_enum, default_value_t = DumpFormat::Raw)]
+    pub format: DumpFormat,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+impl CommandMetadata for ReadDynamicArgs {
+    fn visible_aliases() -> &'static [&'static str] {
+        &["rd"]
+    }
+
+    fn about() -> &'static str {
+        "Read a dynamic partition out of super and save it to a file."
+    }
+
+    fn long_about() -> &'static str {
+        "Read a dynamic partition (system, vendor, product...) out of the super partition,
+        following the extents listed in its LP metadata, and save it to a file.
+        The slot suffix of the partition selects the metadata to use.
+        Use `pgpt --dynamic` to list the dynamic partitions of the device."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra read-dynamic --da DA.bin system_a system_a.img",
+            "antumbra read-dynamic --da DA.bin vendor_b vendor_b.img.zst --format zstd",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for ReadDynamicArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let output_file = self.output.resolver().resolve(&self.output_file).await?;
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        let suffix = &self.partition[strip_slot_suffix(&self.partition).len()..];
+        let metadata = dev.read_lp_metadata(suffix).await?;
+        let partition = metadata
+            .get_partition(&self.partition)
+            .ok_or_else(|| anyhow!("Dynamic partition '{}' not found in super.", self.partition))?;
+        let total_size = partition.size();
+
+        info!(
+            "Reading '{}' from super: {} extents, 0x{:X} bytes",
+            self.partition,
+            partition.extents.len(),
+            total_size
+        );
+
+        let mut writer = DumpWriter::create(&output_file, self.format, total_size).await?;
+        let pb = AntumbraProgress::new(total_size).for_partition(&self.partition);
+
+        let mut progress_callback = {
+            let pb = &pb;
+            move |event: ProgressEvent| {
+                pb.event(&event, "Reading dynamic partition");
+
+                if event.is_done() {
+                    pb.finish("Read complete!");
+                }
+            }
+        };
+
+        if let Err(e) =
+            dev.read_dynamic_partition(&self.partition, &mut progress_callback, &mut writer).await
+        {
+            pb.abandon("Read failed!");
+            return Err(e)?;
+        }
+
+        writer.finish().await?;
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+
+    fn writes_stdout(&self) -> bool {
+        is_stdio(&self.output_file)
+    }
+}
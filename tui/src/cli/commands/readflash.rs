@@ -7,14 +7,12 @@ use std::path::PathBuf;
 use anyhow::Result;
 use async_trait::async_trait;
 use clap::Args;
-use log::info;
-use penumbra::Device;
-use tokio::fs::File;
-use tokio::io::{AsyncWriteExt, BufWriter};
+use log::{info, warn};
+use penumbra::{Device, ProgressEvent};
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
-use crate::cli::helpers::AntumbraProgress;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs, OutputArgs};
+use crate::cli::helpers::{AntumbraProgress, DumpFormat, DumpWriter, is_stdio};
 use crate::cli::state::PersistedDeviceState;
 
 #[derive(Args, Debug)]
@@ -23,8 +21,19 @@ pub struct ReadArgs {
     pub da: DaArgs,
     /// The partition to read
     pub partition: String,
-    /// The destination file
+    /// The destination file, or `-` for stdout
     pub output_file: PathBuf,
+    /// The on-disk format of the dump
+    #[arg(long, value_enum, default_value_t = DumpFormat::Raw)]
+    pub format: DumpFormat,
+    /// Continue an interrupted raw dump, appending to the existing output file
+    #[arg(long, conflicts_with_all = ["overwrite", "timestamp"])]
+    pub resume: bool,
+    /// Read the partition even if its reported geometry looks invalid
+    #[arg(long)]
+    pub force: bool,
+    #[command(flatten)]
+    pub output: OutputArgs,
 }
 
 impl CommandMetadata for ReadArgs {
@@ -37,13 +46,38 @@ impl CommandMetadata for ReadArgs {
     }
 
     fn long_about() -> &'static str {
-        "Read a specified partition from the device and save it to a file with the given output filename."
+        "Read a specified partition from the device and save it to a file with the given output filename.
+        Partitions reported with a zero size or past the end of the storage are refused,
+        unless --force is given."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra read-flash --da DA.bin boot boot.img",
+            "antumbra read-flash --da DA.bin super super.img.zst --format zstd",
+            "antumbra read-flash --da DA.bin userdata userdata.bin --resume",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
     }
 }
 
 #[async_trait]
 impl MtkCommand for ReadArgs {
     async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        if self.resume && self.format != DumpFormat::Raw {
+            return Err(anyhow::anyhow!("Only raw dumps can be resumed."));
+        }
+
+        // Resuming appends to the existing file, so there's nothing to resolve
+        let output_file = if self.resume {
+            self.output_file.clone()
+        } else {
+            self.output.resolver().resolve(&self.output_file).await?
+        };
+
         dev.enter_da_mode().await?;
 
         state.connection_type = CONN_DA;
@@ -57,24 +91,65 @@ impl MtkCommand for ReadArgs {
             }
         };
 
+        if let Some(issue) = partition.issue {
+            if !self.force {
+                return Err(anyhow::anyhow!(
+                    "Partition '{}' has an invalid geometry ({}). Use --force to read it anyway.",
+                    self.partition,
+                    issue
+                ));
+            }
+            warn!("Reading '{}' despite its invalid geometry ({})", self.partition, issue);
+        }
+
         let total_size = partition.size as u64;
-        let pb = AntumbraProgress::new(total_size);
+
+        let (mut writer, start) = if self.resume {
+            DumpWriter::resume(&output_file, total_size).await?
+        } else {
+            (DumpWriter::create(&output_file, self.format, total_size).await?, 0)
+        };
+
+        if start >= total_size {
+            writer.finish().await?;
+            if self.resume {
+                info!("'{}' is already complete, nothing to resume.", output_file.display());
+            }
+            return Ok(());
+        }
+        if start > 0 {
+            info!("Resuming '{}' at 0x{:X}", self.partition, start);
+        }
+
+        let pb = AntumbraProgress::resumed(total_size, start).for_partition(&self.partition);
 
         let mut progress_callback = {
             let pb = &pb;
-            move |written: usize, total: usize| {
-                pb.update(written as u64, "Reading flash");
+            move |event: ProgressEvent| {
+                pb.event(&event, "Reading flash");
 
-                if written >= total {
+                if event.is_done() {
                     pb.finish("Read complete!");
                 }
             }
         };
 
-        let file = File::create(&self.output_file).await?;
-        let mut writer = BufWriter::new(file);
+        let result = if partition.issue.is_some() {
+            let remaining = partition.size - start as usize;
+            dev.read_offset(
+                partition.address + start,
+                remaining,
+                partition.kind,
+                &mut progress_callback,
+                &mut writer,
+            )
+            .await
+        } else {
+            dev.read_partition_from(&self.partition, start, &mut progress_callback, &mut writer)
+                .await
+        };
 
-        match dev.read_partition(&self.partition, &mut progress_callback, &mut writer).await {
+        match result {
             Ok(_) => {}
             Err(e) => {
                 pb.abandon("Read failed!");
@@ -82,16 +157,20 @@ impl MtkCommand for ReadArgs {
             }
         };
 
-        writer.flush().await?;
+        writer.finish().await?;
 
         Ok(())
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.da_file.as_ref()
     }
 
     fn pl(&self) -> Option<&PathBuf> {
         self.da.preloader_file.as_ref()
     }
+
+    fn writes_stdout(&self) -> bool {
+        is_stdio(&self.output_file)
+    }
 }
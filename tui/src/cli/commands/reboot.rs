@@ -11,16 +11,20 @@ use penumbra::Device;
 use penumbra::da::protocol::BootMode;
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
 use crate::cli::state::PersistedDeviceState;
 
-#[derive(Debug, ValueEnum, Clone)]
+#[derive(Debug, ValueEnum, Clone, Copy, Default)]
 pub enum RebootAction {
+    #[default]
     Normal,
     HomeScreen,
     Fastboot,
     Meta,
     Test,
+    Recovery,
+    Brom,
+    Shutdown,
 }
 
 impl CommandMetadata for RebootArgs {
@@ -29,28 +33,51 @@ impl CommandMetadata for RebootArgs {
     }
 
     fn long_about() -> &'static str {
-        "Reboot the device into a specified mode. On XFlash and Legacy, only Normal, HomeScreen
-        and Fastboot modes are supported, the rest will default to Normal.
-        On XML, also the Meta and Test modes are available."
+        "Reboot the device into a specified mode, or shut it down. On XFlash and Legacy, only
+        Normal, HomeScreen, Fastboot and Brom modes are supported, the rest will default to Normal,
+        except Meta on XFlash. On XML, also the Meta and Test modes are available, but not Brom.
+        META with ADB is set with the bootmode command.
+        Recovery works everywhere, by writing the bootloader message in misc before rebooting."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra reboot --da DA.bin",
+            "antumbra reboot fastboot --da DA.bin",
+            "antumbra reboot --mode recovery --da DA.bin",
+            "antumbra reboot shutdown --da DA.bin",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
     }
 }
 
-impl From<RebootAction> for BootMode {
-    fn from(action: RebootAction) -> Self {
-        match action {
-            RebootAction::Normal => BootMode::Normal,
-            RebootAction::HomeScreen => BootMode::HomeScreen,
-            RebootAction::Fastboot => BootMode::Fastboot,
-            RebootAction::Test => BootMode::Test,
-            RebootAction::Meta => BootMode::Meta,
+impl RebootAction {
+    /// The boot mode to reboot into, `None` for shutting down.
+    fn boot_mode(self) -> Option<BootMode> {
+        match self {
+            RebootAction::Normal => Some(BootMode::Normal),
+            RebootAction::HomeScreen => Some(BootMode::HomeScreen),
+            RebootAction::Fastboot => Some(BootMode::Fastboot),
+            RebootAction::Test => Some(BootMode::Test),
+            RebootAction::Meta => Some(BootMode::Meta),
+            RebootAction::Recovery => Some(BootMode::Recovery),
+            RebootAction::Brom => Some(BootMode::Brom),
+            RebootAction::Shutdown => None,
         }
     }
 }
 
 #[derive(Args, Debug)]
 pub struct RebootArgs {
-    #[arg(value_enum, default_value_t = RebootAction::Normal)]
-    pub action: RebootAction,
+    /// Mode to reboot into [default: normal]
+    #[arg(value_enum)]
+    pub action: Option<RebootAction>,
+    /// Same as the positional mode
+    #[arg(long, value_enum, conflicts_with = "action")]
+    pub mode: Option<RebootAction>,
     #[command(flatten)]
     pub da: DaArgs,
 }
@@ -63,14 +90,17 @@ impl MtkCommand for RebootArgs {
         state.connection_type = CONN_DA;
         state.flash_mode = 1;
 
-        let bootmode: BootMode = self.action.clone().into();
-        dev.reboot(bootmode).await?;
+        let action = self.action.or(self.mode).unwrap_or_default();
+        match action.boot_mode() {
+            Some(bootmode) => dev.reboot(bootmode).await?,
+            None => dev.shutdown().await?,
+        }
 
         Ok(())
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.da_file.as_ref()
     }
 
     fn pl(&self) -> Option<&PathBuf> {
@@ -0,0 +1,200 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::Args;
+use human_bytes::human_bytes;
+use log::{info, warn};
+use penumbra::core::backup::{
+    BACKUP_MANIFEST_NAME,
+    BackupManifest,
+    BackupPartition,
+    DEFAULT_RESTORE_SKIP,
+};
+use penumbra::{Device, ProgressEvent};
+use tokio::task::spawn_blocking;
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs, StableInput};
+use crate::cli::helpers::{AntumbraProgress, DumpFormat, dump_sha256};
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Args, Debug)]
+pub struct RestoreAllArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// The backup directory, holding the backup.json written by read-all
+    pub dir: PathBuf,
+    /// Don't restore these partitions, on top of userdata and frp
+    #[arg(long, short = 's', value_delimiter = ',')]
+    pub skip: Vec<String>,
+    /// Restore these partitions even if skipped by default (userdata, frp)
+    #[arg(long, value_delimiter = ',')]
+    pub include: Vec<String>,
+    /// Restore even if the backup comes from a device with another hardware code
+    #[arg(long)]
+    pub force: bool,
+}
+
+impl CommandMetadata for RestoreAllArgs {
+    fn visible_aliases() -> &'static [&'static str] {
+        &["restoreall", "ra"]
+    }
+
+    fn about() -> &'static str {
+        "Restore a full backup made by read-all, from its backup.json."
+    }
+
+    fn long_about() -> &'static str {
+        "Restore the partitions of a backup made by read-all, as described by its backup.json.
+        The backup must come from a device with the same hardware code, unless --force is given.
+        userdata and frp are skipped, unless listed with --include. Every dump is hashed and
+        checked against the manifest before anything is written, and each partition must exist
+        on the device and fit it. The preloader is written last."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra restore-all --da DA.bin dump/",
+            "antumbra restore-all --da DA.bin dump/ --skip nvram,nvdata",
+            "antumbra restore-all --da DA.bin dump/ --include userdata",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
+    }
+}
+
+impl RestoreAllArgs {
+    fn skipped(&self) -> Vec<&str> {
+        DEFAULT_RESTORE_SKIP
+            .iter()
+            .copied()
+            .filter(|s| !self.include.iter().any(|i| i.eq_ignore_ascii_case(s)))
+            .chain(self.skip.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Hashes every dump of the plan, failing on the first missing or corrupted one.
+    async fn verify(&self, plan: &[&BackupPartition]) -> Result<()> {
+        for part in plan {
+            let file = part.file.as_deref().unwrap_or_default();
+            let path = self.dir.join(file);
+            let format = DumpFormat::from_path(&path);
+
+            info!("Checking '{}'...", file);
+            if format == DumpFormat::Raw {
+                part.verify(&self.dir).await?;
+                continue;
+            }
+
+            let actual = spawn_blocking(move || dump_sha256(&path, format, &mut |_| {}))
+                .await?
+                .map_err(|e| anyhow!("Failed to hash '{}': {}", file, e))?;
+            part.check_sha256(&actual)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MtkCommand for RestoreAllArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let manifest_path = self.dir.join(BACKUP_MANIFEST_NAME);
+        let backup = BackupManifest::load(&manifest_path)
+            .await
+            .map_err(|e| anyhow!("Failed to load '{}': {}", manifest_path.display(), e))?;
+
+        if let Err(e) = backup.check_device(dev.dev_info.hw_code().await) {
+            if !self.force {
+                return Err(anyhow!("{}. Use --force to restore it anyway.", e));
+            }
+            warn!("{}, restoring anyway.", e);
+        }
+
+        let plan = backup.restore_plan(&self.skipped());
+        if plan.is_empty() {
+            return Err(anyhow!("Nothing to restore from '{}'.", self.dir.display()));
+        }
+
+        self.verify(&plan).await?;
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        // The table may have changed since the backup, check against the current one
+        for part in &plan {
+            let Some(current) = dev.dev_info.get_partition(&part.name).await else {
+                return Err(anyhow!("Partition '{}' not found on device.", part.name));
+            };
+            if part.size > current.size as u64 {
+                return Err(anyhow!(
+                    "Backup of '{}' ({}) exceeds the partition on the device ({}).",
+                    part.name,
+                    human_bytes(part.size as f64),
+                    human_bytes(current.size as f64)
+                ));
+            }
+        }
+
+        let total: u64 = plan.iter().map(|p| p.size).sum();
+        info!("Restoring {} partitions, {} in total", plan.len(), human_bytes(total as f64));
+
+        let overall = AntumbraProgress::new(total);
+        let mut restored = 0;
+
+        for (i, part) in plan.iter().enumerate() {
+            let path = self.dir.join(part.file.as_deref().unwrap_or_default());
+            let (mut reader, size) = StableInput::open_image(&path, None).await?;
+            let pb = AntumbraProgress::new(size).for_partition(&part.name);
+            let overall_msg = format!("Partition {}/{}", i + 1, plan.len());
+
+            let mut progress_callback = {
+                let pb = &pb;
+                let overall = &overall;
+                let overall_msg = &overall_msg;
+                move |event: ProgressEvent| {
+                    pb.event(&event, &format!("Restoring {}...", part.name));
+                    overall.update(restored + event.bytes_done, overall_msg);
+
+                    if event.is_done() {
+                        pb.finish(&format!("Restored {}", part.name));
+                    }
+                }
+            };
+
+            if let Err(e) =
+                dev.download(&part.name, size as usize, &mut reader, &mut progress_callback).await
+            {
+                pb.abandon(&format!("Restoring {} failed!", part.name));
+                overall.abandon("Restore failed!");
+                return Err(
+                    anyhow::Error::from(e).context(format!("Failed to restore '{}'", part.name))
+                );
+            }
+
+            restored += size;
+        }
+
+        overall.finish("All partitions restored!");
+        info!("Restored {} partitions from '{}'.", plan.len(), self.dir.display());
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
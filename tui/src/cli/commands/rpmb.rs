@@ -0,0 +1,259 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::{Args, Subcommand};
+use clap_num::maybe_hex;
+use log::info;
+use penumbra::{Device, ProgressEvent, RPMB_FRAME_SIZE, RPMB_KEY_SIZE};
+use tokio::fs::{File, metadata, read};
+use tokio::io::{AsyncWriteExt, BufReader, BufWriter};
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
+use crate::cli::helpers::AntumbraProgress;
+use crate::cli::state::PersistedDeviceState;
+
+/// Loads a raw RPMB key file.
+async fn read_key(path: &Path) -> Result<[u8; RPMB_KEY_SIZE]> {
+    let key = read(path).await?;
+    key.as_slice().try_into().map_err(|_| {
+        anyhow!("RPMB key must be {} bytes, {:?} has {}", RPMB_KEY_SIZE, path, key.len())
+    })
+}
+
+/// Enters DA mode and prepares RPMB access, with the key in `key_file` if given.
+async fn init_rpmb(
+    dev: &mut Device,
+    state: &mut PersistedDeviceState,
+    key_file: Option<&Path>,
+) -> Result<()> {
+    let key = match key_file {
+        Some(path) => Some(read_key(path).await?),
+        None => None,
+    };
+
+    dev.enter_da_mode().await?;
+
+    state.connection_type = CONN_DA;
+    state.flash_mode = 1;
+
+    dev.init_rpmb(key.as_ref()).await?;
+    Ok(())
+}
+
+#[derive(Args, Debug)]
+pub struct RpmbReadArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// First RPMB frame to read
+    #[clap(value_parser=maybe_hex::<u16>)]
+    pub address: u16,
+    /// Number of 256 bytes frames to read
+    #[clap(value_parser=maybe_hex::<u16>)]
+    pub blocks: u16,
+    /// The output file to save the frames to
+    pub output_file: PathBuf,
+    /// Raw 32 bytes RPMB key to set before reading
+    #[arg(long, value_name = "FILE")]
+    pub key: Option<PathBuf>,
+}
+
+#[async_trait]
+impl MtkCommand for RpmbReadArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        init_rpmb(dev, state, self.key.as_deref()).await?;
+
+        let file = File::create(&self.output_file).await?;
+        let mut writer = BufWriter::new(file);
+
+        let pb = AntumbraProgress::new((self.blocks as usize * RPMB_FRAME_SIZE) as u64);
+
+        let mut progress_callback = {
+            let pb = &pb;
+            move |event: ProgressEvent| {
+                pb.event(&event, "Reading RPMB...");
+
+                if event.is_done() {
+                    pb.finish("RPMB read completed!");
+                }
+            }
+        };
+
+        info!("Reading {} RPMB frames from frame {}...", self.blocks, self.address);
+
+        if let Err(e) =
+            dev.read_rpmb(self.address, self.blocks, &mut writer, &mut progress_callback).await
+        {
+            pb.abandon("RPMB read failed!");
+            return Err(e)?;
+        }
+        writer.flush().await?;
+
+        info!("RPMB read completed, saved to {:?}", self.output_file);
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RpmbWriteArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// First RPMB frame to write
+    #[clap(value_parser=maybe_hex::<u16>)]
+    pub address: u16,
+    /// File to write, a whole number of 256 bytes frames
+    pub input_file: PathBuf,
+    /// Raw 32 bytes RPMB key to set before writing
+    #[arg(long, value_name = "FILE")]
+    pub key: Option<PathBuf>,
+    /// Acknowledge that RPMB writes can't be undone
+    #[arg(long)]
+    pub i_understand_this_is_irreversible: bool,
+}
+
+#[async_trait]
+impl MtkCommand for RpmbWriteArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        if !self.i_understand_this_is_irreversible {
+            return Err(anyhow!(
+                "RPMB writes can't be undone, pass --i-understand-this-is-irreversible to write"
+            ));
+        }
+
+        let size = metadata(&self.input_file).await?.len() as usize;
+        if size == 0 || !size.is_multiple_of(RPMB_FRAME_SIZE) {
+            return Err(anyhow!(
+                "{:?} must be a whole number of {} bytes frames, it has {} bytes",
+                self.input_file,
+                RPMB_FRAME_SIZE,
+                size
+            ));
+        }
+
+        init_rpmb(dev, state, self.key.as_deref()).await?;
+
+        let file = File::open(&self.input_file).await?;
+        let mut reader = BufReader::new(file);
+
+        let pb = AntumbraProgress::new(size as u64);
+
+        let mut progress_callback = {
+            let pb = &pb;
+            move |event: ProgressEvent| {
+                pb.event(&event, "Writing RPMB...");
+
+                if event.is_done() {
+                    pb.finish("RPMB write completed!");
+                }
+            }
+        };
+
+        info!("Writing {} RPMB frames at frame {}...", size / RPMB_FRAME_SIZE, self.address);
+
+        if let Err(e) =
+            dev.write_rpmb(self.address, size, &mut reader, true, &mut progress_callback).await
+        {
+            pb.abandon("RPMB write failed!");
+            return Err(e)?;
+        }
+
+        info!("RPMB write completed.");
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RpmbSubcommand {
+    /// Read RPMB frames to a file.
+    Read(RpmbReadArgs),
+    /// Write a file to RPMB frames. This can't be undone.
+    Write(RpmbWriteArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct RpmbArgs {
+    #[command(subcommand)]
+    pub command: RpmbSubcommand,
+}
+
+impl CommandMetadata for RpmbArgs {
+    fn about() -> &'static str {
+        "Read or write the RPMB partition."
+    }
+
+    fn long_about() -> &'static str {
+        "Read or write the RPMB partition through the DA extensions (XFlash / V5 devices only).
+        Data is addressed in 256 bytes frames. Writes are authenticated with the RPMB key
+        of the device, given with --key unless the extensions already hold it.
+        RPMB writes can't be undone, as the write counter only goes up: they need
+        --i-understand-this-is-irreversible on top of the usual confirmation."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra rpmb read --da DA.bin 0 0x80 rpmb.bin",
+            "antumbra rpmb write --da DA.bin --key rpmb.key --i-understand-this-is-irreversible 0 frames.bin",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, needs_exploits: true }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for RpmbArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        match &self.command {
+            RpmbSubcommand::Read(cmd) => cmd.run(dev, state).await,
+            RpmbSubcommand::Write(cmd) => cmd.run(dev, state).await,
+        }
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        match &self.command {
+            RpmbSubcommand::Read(cmd) => cmd.da(),
+            RpmbSubcommand::Write(cmd) => cmd.da(),
+        }
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        match &self.command {
+            RpmbSubcommand::Read(cmd) => cmd.pl(),
+            RpmbSubcommand::Write(cmd) => cmd.pl(),
+        }
+    }
+
+    fn invocation_flags(&self) -> Option<CommandFlags> {
+        // Reads leave the device untouched
+        match &self.command {
+            RpmbSubcommand::Read(_) => {
+                Some(CommandFlags { needs_da: true, needs_exploits: true, ..Default::default() })
+            }
+            RpmbSubcommand::Write(_) => None,
+        }
+    }
+}
@@ -0,0 +1,108 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::Args;
+use log::{info, warn};
+use penumbra::{Device, ProgressEvent};
+
+use crate::cli::MtkCommand;
+use crate::cli::commands::wipefrp::wipe_frp;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
+use crate::cli::helpers::AntumbraProgress;
+use crate::cli::state::PersistedDeviceState;
+
+/// Partitions wiped by a factory reset, formatted when present.
+const SAFE_FORMAT_PARTITIONS: &[&str] = &["userdata", "metadata", "cache"];
+
+#[derive(Args, Debug)]
+pub struct SafeFormatArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// Clear the factory reset protection as well, like wipe-frp
+    #[arg(long)]
+    pub frp: bool,
+}
+
+impl CommandMetadata for SafeFormatArgs {
+    fn about() -> &'static str {
+        "Format user data like a factory reset, keeping calibration and lock state."
+    }
+
+    fn long_about() -> &'static str {
+        "Format userdata, metadata and cache, the partitions a factory reset wipes.
+        Unlike a full format, nvram, nvdata and seccfg are never touched, so the device keeps
+        its IMEI, calibration and bootloader lock state. With --frp, the factory reset
+        protection is cleared too, as wipe-frp does."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &["antumbra safe-format --da DA.bin", "antumbra safe-format --da DA.bin --frp"]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for SafeFormatArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        let mut formatted = 0;
+        for &name in SAFE_FORMAT_PARTITIONS {
+            let Some(part) = dev.dev_info.get_partition(name).await else {
+                warn!("No '{}' partition on the device, skipping it.", name);
+                continue;
+            };
+
+            let pb = AntumbraProgress::new(part.size as u64).for_partition(name);
+            let mut progress_callback = {
+                let pb = &pb;
+                move |event: ProgressEvent| {
+                    pb.event(&event, "Formatting...");
+
+                    if event.is_done() {
+                        pb.finish("Format complete!");
+                    }
+                }
+            };
+
+            if let Err(e) = dev.format(name, &mut progress_callback).await {
+                pb.abandon("Format failed!");
+                return Err(anyhow::Error::from(e).context(format!("Failed to format '{}'", name)));
+            }
+            formatted += 1;
+        }
+
+        if formatted == 0 {
+            return Err(anyhow!(
+                "None of {} found on the device.",
+                SAFE_FORMAT_PARTITIONS.join(", ")
+            ));
+        }
+
+        if self.frp {
+            wipe_frp(dev).await?;
+        }
+
+        info!("Formatted {} partitions.", formatted);
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
@@ -12,7 +12,7 @@ use penumbra::Device;
 use penumbra::core::seccfg::LockFlag;
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
 use crate::cli::state::PersistedDeviceState;
 
 #[derive(Debug, ValueEnum, Clone)]
@@ -38,6 +38,14 @@ impl CommandMetadata for SeccfgArgs {
         This command only work when the device is in DA mode and vulnerable to an exploit or unfused,
         because it requires DA extensions to be loaded."
     }
+
+    fn examples() -> &'static [&'static str] {
+        &["antumbra seccfg unlock --da DA.bin", "antumbra seccfg lock --da DA.bin"]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, needs_exploits: true }
+    }
 }
 
 #[async_trait]
@@ -77,7 +85,7 @@ impl MtkCommand for SeccfgArgs {
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.da_file.as_ref()
     }
 
     fn pl(&self) -> Option<&PathBuf> {
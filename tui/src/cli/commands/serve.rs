@@ -0,0 +1,445 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::Args;
+use log::{debug, info, warn};
+use penumbra::Device;
+use penumbra::core::storage::Partition;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
+use crate::cli::state::PersistedDeviceState;
+
+/// Size of the chunks read from the device and cached
+const CHUNK_SIZE: u64 = 1024 * 1024;
+/// Number of chunks kept in the cache
+const CACHE_CHUNKS: usize = 16;
+/// Maximum size of a request head, anything bigger is rejected
+const MAX_HEAD_SIZE: usize = 8 * 1024;
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// The partition to expose
+    pub partition: String,
+    /// The address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub bind: SocketAddr,
+    /// Allow binding to a non-loopback address, exposing the partition to the network
+    #[arg(long)]
+    pub listen: bool,
+}
+
+impl CommandMetadata for ServeArgs {
+    fn about() -> &'static str {
+        "Expose a partition over HTTP, with range request support."
+    }
+
+    fn long_about() -> &'static str {
+        "Expose a partition read-only over HTTP, so that other tools can read it without dumping it first. \
+        Range requests are supported, and reads are served from the device on demand.\n\n\
+        The server has no authentication and no encryption: anyone who can reach it can read the whole partition. \
+        It only binds to localhost by default, use --listen together with --bind to expose it to the network, \
+        and only do so on networks you trust."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra serve --da DA.bin super",
+            "antumbra serve --da DA.bin super --bind 0.0.0.0:8080 --listen",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
+    }
+}
+
+/// A read of `len` bytes at `offset` within the partition, answered by the device loop.
+struct ChunkRequest {
+    offset: u64,
+    len: usize,
+    reply: oneshot::Sender<Result<Vec<u8>, String>>,
+}
+
+/// Small LRU cache of device chunks, indexed by chunk number.
+#[derive(Default)]
+struct ChunkCache {
+    chunks: VecDeque<(u64, Vec<u8>)>,
+}
+
+impl ChunkCache {
+    fn get(&mut self, index: u64) -> Option<Vec<u8>> {
+        let pos = self.chunks.iter().position(|(i, _)| *i == index)?;
+        let entry = self.chunks.remove(pos)?;
+        let data = entry.1.clone();
+        self.chunks.push_front(entry);
+        Some(data)
+    }
+
+    fn insert(&mut self, index: u64, data: Vec<u8>) {
+        if self.chunks.len() >= CACHE_CHUNKS {
+            self.chunks.pop_back();
+        }
+        self.chunks.push_front((index, data));
+    }
+}
+
+#[async_trait]
+impl MtkCommand for ServeArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        if !self.bind.ip().is_loopback() && !self.listen {
+            return Err(anyhow!(
+                "Refusing to bind to {} without --listen, the partition would be exposed to the network.",
+                self.bind
+            ));
+        }
+
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        let partition = match dev.dev_info.get_partition(&self.partition).await {
+            Some(p) => p,
+            None => {
+                return Err(anyhow!("Partition '{}' not found on device.", self.partition));
+            }
+        };
+
+        let listener = TcpListener::bind(self.bind).await?;
+        if !self.bind.ip().is_loopback() {
+            warn!(
+                "Serving on a non-loopback address, anyone on the network can read '{}'!",
+                partition.name
+            );
+        }
+        info!(
+            "Serving '{}' (0x{:X} bytes) on http://{}/{}",
+            partition.name, partition.size, self.bind, partition.name
+        );
+        info!("Press Ctrl+C to stop.");
+
+        let (req_tx, mut req_rx) = mpsc::channel::<ChunkRequest>(CACHE_CHUNKS);
+        let mut cache = ChunkCache::default();
+
+        // The device can only do one thing at a time, so every read goes through this loop
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, peer) = accepted?;
+                    debug!("[Serve] Connection from {}", peer);
+
+                    let req_tx = req_tx.clone();
+                    let name = partition.name.clone();
+                    let size = partition.size as u64;
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, &name, size, req_tx).await {
+                            debug!("[Serve] Connection from {} failed: {}", peer, e);
+                        }
+                    });
+                }
+                Some(req) = req_rx.recv() => {
+                    let data = read_chunk(dev, &partition, &mut cache, req.offset, req.len).await;
+                    req.reply.send(data.map_err(|e| e.to_string())).ok();
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping server.");
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
+
+/// Reads `len` bytes at `offset`, going through the chunk cache.
+/// Requests never cross a chunk boundary, see `handle_connection`.
+async fn read_chunk(
+    dev: &mut Device,
+    partition: &Partition,
+    cache: &mut ChunkCache,
+    offset: u64,
+    len: usize,
+) -> Result<Vec<u8>> {
+    let index = offset / CHUNK_SIZE;
+    let start = index * CHUNK_SIZE;
+
+    let chunk = match cache.get(index) {
+        Some(chunk) => chunk,
+        None => {
+            let size = CHUNK_SIZE.min(partition.size as u64 - start) as usize;
+            let mut chunk = Vec::with_capacity(size);
+            dev.read_offset(
+                partition.address + start,
+                size,
+                partition.kind,
+                &mut |_| {},
+                &mut chunk,
+            )
+            .await?;
+            cache.insert(index, chunk.clone());
+            chunk
+        }
+    };
+
+    let from = (offset - start) as usize;
+    let to = (from + len).min(chunk.len());
+    chunk
+        .get(from..to)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| anyhow!("Device returned a short chunk at offset 0x{:X}", offset))
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    name: &str,
+    size: u64,
+    req_tx: mpsc::Sender<ChunkRequest>,
+) -> Result<()> {
+    let head = read_head(&mut stream).await?;
+    let RequestHead { method, path, range } = parse_head(&head);
+
+    if method != "GET" && method != "HEAD" {
+        return respond_empty(&mut stream, "405 Method Not Allowed", &["Allow: GET, HEAD"]).await;
+    }
+
+    if path != "/" && path.trim_start_matches('/') != name {
+        return respond_empty(&mut stream, "404 Not Found", &[]).await;
+    }
+
+    let (status, start, end) = match range {
+        None => ("200 OK", 0, size),
+        Some(range) => match parse_range(range, size) {
+            Some((start, end)) => ("206 Partial Content", start, end),
+            None => {
+                let content_range = format!("Content-Range: bytes */{}", size);
+                return respond_empty(&mut stream, "416 Range Not Satisfiable", &[&content_range])
+                    .await;
+            }
+        },
+    };
+
+    let mut response = format!(
+        "HTTP/1.1 {}\r\n\
+        Accept-Ranges: bytes\r\n\
+        Content-Type: application/octet-stream\r\n\
+        Content-Length: {}\r\n\
+        Connection: close\r\n",
+        status,
+        end - start
+    );
+    if status.starts_with("206") {
+        response.push_str(&format!("Content-Range: bytes {}-{}/{}\r\n", start, end - 1, size));
+    }
+    response.push_str("\r\n");
+    stream.write_all(response.as_bytes()).await?;
+
+    if method == "HEAD" {
+        return Ok(());
+    }
+
+    let mut offset = start;
+    while offset < end {
+        let chunk_end = ((offset / CHUNK_SIZE) + 1) * CHUNK_SIZE;
+        let len = (chunk_end.min(end) - offset) as usize;
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        req_tx.send(ChunkRequest { offset, len, reply: reply_tx }).await?;
+        let data = reply_rx.await?.map_err(|e| anyhow!(e))?;
+        if data.is_empty() {
+            return Err(anyhow!("Device returned no data at offset 0x{:X}", offset));
+        }
+
+        stream.write_all(&data).await?;
+        offset += data.len() as u64;
+    }
+
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Reads the request line and headers, the body (if any) is ignored.
+async fn read_head(stream: &mut TcpStream) -> Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() > MAX_HEAD_SIZE {
+            return Err(anyhow!("Request head too large"));
+        }
+
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Err(anyhow!("Connection closed before the request was complete"));
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// What `handle_connection` needs from a request head.
+#[derive(Debug, PartialEq)]
+struct RequestHead<'a> {
+    method: &'a str,
+    path: &'a str,
+    range: Option<&'a str>,
+}
+
+/// Splits the request line, and picks the `Range` header out of the others.
+fn parse_head(head: &str) -> RequestHead<'_> {
+    let mut lines = head.lines();
+
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let range = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("range"))
+        .map(|(_, value)| value.trim());
+
+    RequestHead { method, path, range }
+}
+
+async fn respond_empty(stream: &mut TcpStream, status: &str, headers: &[&str]) -> Result<()> {
+    let mut response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n", status);
+    for header in headers {
+        response.push_str(header);
+        response.push_str("\r\n");
+    }
+    response.push_str("\r\n");
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Parses a single `bytes=` range into a `[start, end)` pair.
+/// Multiple ranges aren't supported and are treated as unsatisfiable.
+fn parse_range(range: &str, size: u64) -> Option<(u64, u64)> {
+    let spec = range.strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (first, last) = spec.split_once('-')?;
+    let (start, end) = match (first.trim(), last.trim()) {
+        ("", suffix) => {
+            let len: u64 = suffix.parse().ok()?;
+            (size.saturating_sub(len), size)
+        }
+        (start, "") => (start.parse().ok()?, size),
+        (start, end) => {
+            let end: u64 = end.parse().ok()?;
+            (start.parse().ok()?, end.saturating_add(1).min(size))
+        }
+    };
+
+    if start >= end || start >= size {
+        return None;
+    }
+
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u64 = 1000;
+
+    #[test]
+    fn parses_closed_ranges() {
+        assert_eq!(parse_range("bytes=0-99", SIZE), Some((0, 100)));
+        assert_eq!(parse_range("bytes= 10 - 19 ", SIZE), Some((10, 20)));
+        // The end is clamped to the partition
+        assert_eq!(parse_range("bytes=900-5000", SIZE), Some((900, SIZE)));
+    }
+
+    #[test]
+    fn parses_open_ended_ranges() {
+        assert_eq!(parse_range("bytes=100-", SIZE), Some((100, SIZE)));
+        assert_eq!(parse_range("bytes=999-", SIZE), Some((999, SIZE)));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-100", SIZE), Some((900, SIZE)));
+        // A suffix longer than the partition is the whole partition
+        assert_eq!(parse_range("bytes=-5000", SIZE), Some((0, SIZE)));
+        assert_eq!(parse_range("bytes=-0", SIZE), None);
+    }
+
+    #[test]
+    fn refuses_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", SIZE), None);
+        assert_eq!(parse_range("bytes=1000-1100", SIZE), None);
+        assert_eq!(parse_range("bytes=500-100", SIZE), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+    }
+
+    #[test]
+    fn refuses_multiple_ranges() {
+        assert_eq!(parse_range("bytes=0-9,20-29", SIZE), None);
+    }
+
+    #[test]
+    fn refuses_malformed_ranges() {
+        assert_eq!(parse_range("0-99", SIZE), None);
+        assert_eq!(parse_range("items=0-99", SIZE), None);
+        assert_eq!(parse_range("bytes=abc-", SIZE), None);
+        assert_eq!(parse_range("bytes=-", SIZE), None);
+        assert_eq!(parse_range("bytes=10", SIZE), None);
+    }
+
+    #[test]
+    fn parses_request_heads() {
+        let head = "GET /boot_a HTTP/1.1\r\nHost: localhost\r\nRange: bytes=0-511\r\n\r\n";
+        assert_eq!(parse_head(head), RequestHead {
+            method: "GET",
+            path: "/boot_a",
+            range: Some("bytes=0-511")
+        });
+    }
+
+    #[test]
+    fn matches_the_range_header_case_insensitively() {
+        let head = "HEAD / HTTP/1.1\r\nrAnGe:bytes=-10 \r\n\r\n";
+        assert_eq!(parse_head(head).range, Some("bytes=-10"));
+    }
+
+    #[test]
+    fn parses_heads_without_a_range() {
+        let head = "GET / HTTP/1.1\r\nHost: localhost:8080\r\n\r\n";
+        assert_eq!(parse_head(head), RequestHead { method: "GET", path: "/", range: None });
+    }
+
+    #[test]
+    fn parses_garbage_heads_to_empty_requests() {
+        assert_eq!(parse_head(""), RequestHead { method: "", path: "", range: None });
+        assert_eq!(parse_head("\r\n\r\n").method, "");
+    }
+}
@@ -11,7 +11,7 @@ use log::info;
 use penumbra::Device;
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
 use crate::cli::state::PersistedDeviceState;
 
 #[derive(Args, Debug)]
@@ -28,6 +28,14 @@ impl CommandMetadata for ShutdownArgs {
     fn long_about() -> &'static str {
         "Shutdown the device through DA mode."
     }
+
+    fn examples() -> &'static [&'static str] {
+        &["antumbra shutdown --da DA.bin"]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
+    }
 }
 
 #[async_trait]
@@ -45,7 +53,7 @@ impl MtkCommand for ShutdownArgs {
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.da_file.as_ref()
     }
 
     fn pl(&self) -> Option<&PathBuf> {
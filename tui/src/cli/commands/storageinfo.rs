@@ -0,0 +1,97 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Args;
+use human_bytes::human_bytes;
+use log::info;
+use penumbra::Device;
+use penumbra::core::storage::StorageType;
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Args, Debug)]
+pub struct StorageInfoArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+}
+
+impl CommandMetadata for StorageInfoArgs {
+    fn visible_aliases() -> &'static [&'static str] {
+        &["storageinfo"]
+    }
+
+    fn about() -> &'static str {
+        "Show the type, capacity and identification of the device storage."
+    }
+
+    fn long_about() -> &'static str {
+        "Show the type, capacity and identification of the device storage, as reported
+        by the DA: the size of each region, the CID and, when available, the model,
+        firmware version and serial number."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &["antumbra storage-info --da DA.bin"]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for StorageInfoArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        let info = dev.get_storage_info().await?;
+        let size = |bytes: u64| format!("{} ({} bytes)", human_bytes(bytes as f64), bytes);
+        let (boot1, boot2) = match info.kind {
+            StorageType::Ufs => ("LU0", "LU1"),
+            _ => ("BOOT1", "BOOT2"),
+        };
+
+        let kind = match info.kind {
+            StorageType::Emmc => "eMMC",
+            StorageType::Ufs => "UFS",
+            StorageType::Nand => "NAND",
+            StorageType::Unknown => "Unknown",
+        };
+
+        info!("Storage type:  {}", kind);
+        info!("Block size:    {}", info.block_size);
+        info!("Capacity:      {}", size(info.total_size));
+        info!("User:          {}", size(info.user_size));
+        info!("{}:         {}", boot1, size(info.boot1_size));
+        info!("{}:         {}", boot2, size(info.boot2_size));
+        if info.rpmb_size != 0 {
+            info!("RPMB:          {}", size(info.rpmb_size));
+        }
+        info!("CID:           {}", hex::encode_upper(&info.cid));
+
+        let unknown = || "Unknown".to_string();
+        info!("Model:         {}", info.model.unwrap_or_else(unknown));
+        info!("Firmware:      {}", info.fw_version.unwrap_or_else(unknown));
+        info!("Serial:        {}", info.serial.unwrap_or_else(unknown));
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
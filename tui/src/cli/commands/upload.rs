@@ -2,29 +2,90 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use async_trait::async_trait;
 use clap::Args;
+use clap_num::maybe_hex;
 use log::info;
-use penumbra::Device;
-use tokio::fs::File;
-use tokio::io::BufWriter;
+use penumbra::{Device, ProgressEvent};
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
-use crate::cli::helpers::AntumbraProgress;
+use crate::cli::common::{
+    CONN_DA,
+    CommandFlags,
+    CommandMetadata,
+    DaArgs,
+    OutputArgs,
+    SectionArgs,
+    SectionRange,
+};
+use crate::cli::helpers::{AntumbraProgress, DumpFormat, DumpWriter, is_stdio};
 use crate::cli::state::PersistedDeviceState;
 
 #[derive(Args, Debug)]
 pub struct UploadArgs {
     #[command(flatten)]
     pub da: DaArgs,
-    /// The partition to read
+    /// The partition to read, left out with --section
     pub partition: String,
-    /// The destination file
-    pub output_file: PathBuf,
+    /// The destination file, or `-` for stdout
+    pub output_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub section: SectionArgs,
+    /// With --section, only read this many bytes instead of up to the end of the section
+    #[arg(long, requires = "section", value_parser = maybe_hex::<u64>)]
+    pub length: Option<u64>,
+    /// The on-disk format of the dump
+    #[arg(long, value_enum, default_value_t = DumpFormat::Raw)]
+    pub format: DumpFormat,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+impl UploadArgs {
+    async fn read_section(
+        &self,
+        dev: &mut Device,
+        range: &SectionRange,
+        output_file: &Path,
+    ) -> Result<()> {
+        let pb = AntumbraProgress::new(range.length).for_partition(&range.name);
+
+        let mut progress_callback = {
+            let pb = &pb;
+            move |event: ProgressEvent| {
+                pb.event(&event, "Reading...");
+
+                if event.is_done() {
+                    pb.finish("Read complete!");
+                }
+            }
+        };
+
+        info!("Reading 0x{:X} bytes of '{}' at 0x{:X}...", range.length, range.name, range.offset);
+
+        let mut writer = DumpWriter::create(output_file, self.format, range.length).await?;
+        let result = dev
+            .read_offset(
+                range.offset,
+                range.length as usize,
+                range.kind,
+                &mut progress_callback,
+                &mut writer,
+            )
+            .await;
+
+        if let Err(e) = result {
+            pb.abandon("Read failed!");
+            return Err(e)?;
+        }
+
+        writer.finish().await?;
+
+        Ok(())
+    }
 }
 
 impl CommandMetadata for UploadArgs {
@@ -38,44 +99,67 @@ impl CommandMetadata for UploadArgs {
 
     fn long_about() -> &'static str {
         "Upload (readback) a specificed partition on the device to a file on the host.
-        Use this command for reading back if the `read` command fails."
+        Use this command for reading back if the `read` command fails.
+        With --section, a raw range of a storage section is read instead, such as the boot
+        regions of eMMC (boot1, boot2) or the boot LUs of UFS (lu0, lu1). The partition is
+        then left out, and the range given with --offset and --length."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra upload --da DA.bin boot boot.img",
+            "antumbra upload --da DA.bin userdata - | gzip > userdata.img.gz",
+            "antumbra read --da DA.bin --section boot2 --offset 0 --length 0x80000 boot2.bin",
+            "antumbra read --da DA.bin --section lu0 lu0.bin",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { needs_da: true, ..Default::default() }
     }
 }
 
 #[async_trait]
 impl MtkCommand for UploadArgs {
     async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        let (partition, output_file) =
+            self.section.split(&self.partition, self.output_file.as_deref())?;
+        let output_file = self.output.resolver().resolve(output_file).await?;
+
         dev.enter_da_mode().await?;
 
         state.connection_type = CONN_DA;
         state.flash_mode = 1;
 
-        let partition = match dev.dev_info.get_partition(&self.partition).await {
-            Some(p) => p,
+        if let Some(range) = self.section.resolve(dev, self.length).await? {
+            return self.read_section(dev, &range, &output_file).await;
+        }
+        let partition = partition.unwrap_or_default();
+
+        let total_size = match dev.dev_info.get_partition(partition).await {
+            Some(p) => p.size as u64,
             None => {
-                info!("Partition '{}' not found on device.", self.partition);
-                return Err(anyhow::anyhow!("Partition '{}' not found on device.", self.partition));
+                info!("Partition '{}' not found on device.", partition);
+                return Err(anyhow::anyhow!("Partition '{}' not found on device.", partition));
             }
         };
 
-        let total_size = partition.size as u64;
-        let pb = AntumbraProgress::new(total_size);
+        let pb = AntumbraProgress::new(total_size).for_partition(partition);
 
         let mut progress_callback = {
             let pb = &pb;
-            move |written: usize, total: usize| {
-                pb.update(written as u64, "Uploading...");
+            move |event: ProgressEvent| {
+                pb.event(&event, "Uploading...");
 
-                if written >= total {
+                if event.is_done() {
                     pb.finish("Upload complete!");
                 }
             }
         };
 
-        let file = File::create(&self.output_file).await?;
-        let mut writer = BufWriter::new(file);
+        let mut writer = DumpWriter::create(&output_file, self.format, total_size).await?;
 
-        match dev.upload(&self.partition, &mut writer, &mut progress_callback).await {
+        match dev.upload(partition, &mut writer, &mut progress_callback).await {
             Ok(_) => {}
             Err(e) => {
                 pb.abandon("Upload failed!");
@@ -83,14 +167,22 @@ impl MtkCommand for UploadArgs {
             }
         };
 
+        writer.finish().await?;
+
         Ok(())
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.da_file.as_ref()
     }
 
     fn pl(&self) -> Option<&PathBuf> {
         self.da.preloader_file.as_ref()
     }
+
+    fn writes_stdout(&self) -> bool {
+        self.section
+            .split(&self.partition, self.output_file.as_deref())
+            .is_ok_and(|(_, file)| is_stdio(file))
+    }
 }
@@ -0,0 +1,133 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::{Args, Parser};
+use log::{error, info};
+use penumbra::{Device, find_mtk_ports};
+use tokio::time::sleep;
+
+use crate::cli::common::{CommandMetadata, confirm_command};
+use crate::cli::state::PersistedDeviceState;
+use crate::cli::{CliArgs, Commands, MtkCommand, run_command};
+
+/// How often ports are listed while waiting for the device to go away.
+const DISCONNECT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long no MTK port must be seen before the device counts as unplugged.
+/// Devices re-enumerate when rebooting (e.g. into preloader after a flash),
+/// which must not be mistaken for the next device.
+const DISCONNECT_SETTLE: Duration = Duration::from_secs(3);
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    /// Stop after this many devices
+    #[arg(long, value_name = "N")]
+    pub max: Option<u32>,
+    /// Stop at the first device the command fails on
+    #[arg(long)]
+    pub stop_on_error: bool,
+    /// The command to run on each device, with its arguments, after `--`
+    #[arg(last = true, required = true, value_name = "COMMAND")]
+    pub command: Vec<String>,
+}
+
+/// The command given to `watch`, parsed like a top level one.
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true)]
+struct WatchedCommand {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+impl CommandMetadata for WatchArgs {
+    fn about() -> &'static str {
+        "Run a command on every device that gets plugged in."
+    }
+
+    fn long_about() -> &'static str {
+        "Wait for a device, run the given command on it, then wait for it to be unplugged
+        and start over, counting successes and failures.
+        Each device gets a fresh session: nothing is resumed from the previous one.
+        A failure doesn't stop the loop unless --stop-on-error is given.
+        Global options (e.g. --yes) go before `watch`, and apply to every run."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra --yes watch -- write-flash --da DA.bin boot boot.img",
+            "antumbra watch --max 10 --stop-on-error -- read-all --da DA.bin dumps --timestamp",
+        ]
+    }
+}
+
+impl WatchArgs {
+    /// Runs the watched command on each device until `--max` is reached or it fails
+    /// with `--stop-on-error`. Fails if any device failed.
+    pub async fn execute(&self, args: &CliArgs) -> Result<()> {
+        let watched = WatchedCommand::try_parse_from(&self.command)?.command;
+        if matches!(watched, Commands::Watch(_) | Commands::Detect(_) | Commands::Manifest(_)) {
+            return Err(anyhow!("{} can't be run by watch", watched.name()));
+        }
+
+        // Asked once for the whole run, there's nobody to answer between devices
+        confirm_command(watched.name(), watched.flags(), args.yes)?;
+
+        let (mut succeeded, mut failed) = (0u32, 0u32);
+        while self.max.is_none_or(|max| succeeded + failed < max) {
+            let number = succeeded + failed + 1;
+
+            // Every device starts its own session, never the one of the previous device
+            PersistedDeviceState::load().await.reset().await?;
+
+            info!("[watch] Waiting for device #{}...", number);
+            match run_command(args, &watched).await {
+                Ok(()) => {
+                    succeeded += 1;
+                    info!("[watch] Device #{}: {} succeeded", number, watched.name());
+                }
+                Err(e) => {
+                    failed += 1;
+                    error!("[watch] Device #{}: {} failed: {:#}", number, watched.name(), e);
+                    if self.stop_on_error {
+                        info!("[watch] Stopping: {} succeeded, {} failed", succeeded, failed);
+                        return Err(e);
+                    }
+                }
+            }
+
+            info!("[watch] {} succeeded, {} failed so far. Unplug the device.", succeeded, failed);
+            wait_for_disconnect(args.port.as_deref()).await;
+        }
+
+        info!("[watch] Done: {} succeeded, {} failed", succeeded, failed);
+        if failed > 0 {
+            return Err(anyhow!("{} of {} devices failed", failed, succeeded + failed));
+        }
+
+        Ok(())
+    }
+}
+
+/// Waits until no MTK port, or only `port` when given, has been seen for `DISCONNECT_SETTLE`.
+async fn wait_for_disconnect(port: Option<&str>) {
+    let mut last_seen = Instant::now();
+    while last_seen.elapsed() < DISCONNECT_SETTLE {
+        let ports = find_mtk_ports().await;
+        if ports.iter().any(|p| port.is_none_or(|name| p.get_port_name() == name)) {
+            last_seen = Instant::now();
+        }
+        sleep(DISCONNECT_POLL_INTERVAL).await;
+    }
+}
+
+#[async_trait]
+impl MtkCommand for WatchArgs {
+    // Handled before connecting, see `run_cli`
+    async fn run(&self, _dev: &mut Device, _state: &mut PersistedDeviceState) -> Result<()> {
+        Err(anyhow!("watch must be run on its own"))
+    }
+}
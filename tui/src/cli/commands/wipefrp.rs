@@ -0,0 +1,105 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::Args;
+use log::info;
+use penumbra::{Device, PartitionRole, ProgressEvent};
+use tokio::io::{AsyncReadExt, repeat};
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
+use crate::cli::helpers::AntumbraProgress;
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Args, Debug)]
+pub struct WipeFrpArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+}
+
+impl CommandMetadata for WipeFrpArgs {
+    fn about() -> &'static str {
+        "Clear the factory reset protection (FRP)."
+    }
+
+    fn long_about() -> &'static str {
+        "Clear the factory reset protection, by zero-filling its partition.
+        The partition is looked up under the names devices give it (frp, config, persistent),
+        and its first sector is read back to check that it's cleared."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &["antumbra wipe-frp --da DA.bin", "antumbra wipe-frp --da DA.bin --yes"]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
+    }
+}
+
+/// Zero-fills the FRP partition, whatever its name, then checks its first sector.
+/// The device must be in DA mode.
+pub async fn wipe_frp(dev: &mut Device) -> Result<()> {
+    let role = PartitionRole::Frp;
+    let Some(part) = dev.find_partition_by_role(role).await? else {
+        return Err(anyhow!(
+            "No {} partition found on the device (looked for {}).",
+            role,
+            role.candidates().join(", ")
+        ));
+    };
+
+    let pb = AntumbraProgress::new(part.size as u64).for_partition(&part.name);
+    let mut progress_callback = {
+        let pb = &pb;
+        move |event: ProgressEvent| {
+            pb.event(&event, "Wiping FRP...");
+        }
+    };
+
+    info!("Wiping {} partition '{}'...", role, part.name);
+
+    let mut zeros = repeat(0).take(part.size as u64);
+    let result = dev.write_partition(&part.name, &mut zeros, false, &mut progress_callback).await;
+    if let Err(e) = result {
+        pb.abandon("FRP wipe failed!");
+        return Err(e)?;
+    }
+    pb.finish("FRP wiped!");
+
+    let sector = dev.get_storage_info().await.map_or(512, |info| info.block_size as usize);
+    let mut first = Vec::with_capacity(sector);
+    dev.read_offset(part.address, sector, part.kind, &mut |_| {}, &mut first).await?;
+
+    if first.iter().any(|&b| b != 0) {
+        return Err(anyhow!("'{}' doesn't read back as zeros after the wipe.", part.name));
+    }
+
+    info!("'{}' cleared and checked.", part.name);
+    Ok(())
+}
+
+#[async_trait]
+impl MtkCommand for WipeFrpArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        wipe_frp(dev).await
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
@@ -3,17 +3,21 @@
     SPDX-FileCopyrightText: 2025 Shomy
 */
 use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use clap::Args;
-use penumbra::Device;
-use tokio::fs::{File, metadata};
-use tokio::io::BufReader;
+use clap_num::maybe_hex;
+use log::warn;
+use penumbra::core::summary::OperationKind;
+use penumbra::{Device, ProgressEvent};
+use tokio::io::{AsyncRead, stdin};
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
-use crate::cli::helpers::AntumbraProgress;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs, StableInput};
+use crate::cli::helpers::{AntumbraProgress, is_stdio, padded};
+use crate::cli::journal::{Journal, WriteKind};
 use crate::cli::state::PersistedDeviceState;
 
 #[derive(Args, Debug)]
@@ -22,8 +26,27 @@ pub struct WriteArgs {
     pub da: DaArgs,
     /// The partition to flash
     pub partition: String,
-    /// The file to download
+    /// The file to write, or `-` for stdin
     pub file: PathBuf,
+    /// Only write this many bytes from the start of the partition, or from --offset.
+    /// Without it, stdin input is zero-padded to the end of the partition.
+    #[arg(long, value_parser = maybe_hex::<u64>)]
+    pub size: Option<u64>,
+    /// Write at this offset inside the partition, leaving the rest of it untouched
+    #[arg(long, value_parser = maybe_hex::<u64>)]
+    pub offset: Option<u64>,
+    /// With --offset, zero-fill the partition from the end of the file up to its end
+    #[arg(long, requires = "offset")]
+    pub pad: bool,
+    /// Wait until the file size stays unchanged for this many seconds before flashing
+    #[arg(long, value_name = "SECONDS")]
+    pub wait_stable: Option<u64>,
+    /// Read the written data back and compare it with the input
+    #[arg(long)]
+    pub verify: bool,
+    /// Write even if the bootloader is locked and AVB verifies the partition
+    #[arg(long)]
+    pub acknowledge_avb: bool,
 }
 
 impl CommandMetadata for WriteArgs {
@@ -37,7 +60,33 @@ impl CommandMetadata for WriteArgs {
 
     fn long_about() -> &'static str {
         "Write (flash) a file to a specificed partition on the device.
-        If this command fails, use `download` instead."
+        Android sparse images are expanded while flashing, skipping their unused blocks.
+        Files ending in .gz or .zst are decompressed while flashing.
+        If this command fails, use `download` instead.
+        With --offset, the file is written at that offset inside the partition, e.g. to patch
+        a header or write half of a split image. Sparse images aren't expanded in this mode,
+        and writes that don't fit in the partition are refused. Bytes of the partition past
+        the end of the file are left untouched, unless --pad is given to zero-fill them.
+        Without --offset, the whole partition is written, zero-filled after the file.
+        With --verify, the written data is read back and its SHA-256 compared with the input.
+        On a locked bootloader, writing a partition verified by AVB (listed in vbmeta) is
+        refused unless --acknowledge-avb is given, as modified images won't boot."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra write-flash --da DA.bin boot boot.img",
+            "antumbra write-flash --da DA.bin super super.img --wait-stable 5",
+            "antumbra write-flash --da DA.bin boot boot.img --verify",
+            "antumbra write-flash --da DA.bin boot dump/boot.bin.gz",
+            "antumbra write-flash --da DA.bin boot boot.img --acknowledge-avb",
+            "antumbra write-flash --da DA.bin lk lk_header.bin --offset 0x0",
+            "antumbra write-flash --da DA.bin super super.img.2 --offset 0x80000000 --pad",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
     }
 }
 
@@ -49,33 +98,170 @@ impl MtkCommand for WriteArgs {
         state.connection_type = CONN_DA;
         state.flash_mode = 1;
 
-        let file = File::open(&self.file).await?;
-        let mut reader = BufReader::new(file);
-
-        let file_size = metadata(&self.file).await?.len();
-
-        let part_size = match dev.dev_info.get_partition(&self.partition).await {
-            Some(p) => p.size as u64,
+        let partition = match dev.dev_info.get_partition(&self.partition).await {
+            Some(p) => p,
             None => {
                 return Err(anyhow::anyhow!("Partition '{}' not found on device.", self.partition));
             }
         };
+        let part_size = partition.size as u64;
 
-        let total_size = file_size.min(part_size);
-        let pb = AntumbraProgress::new(total_size);
+        let offset = self.offset.unwrap_or(0);
+        if offset > part_size {
+            return Err(anyhow::anyhow!(
+                "Offset 0x{:X} is past the end of '{}' (0x{:X} bytes).",
+                offset,
+                self.partition,
+                part_size
+            ));
+        }
+        let space = part_size - offset;
+        let overflow = |len: u64| {
+            anyhow::anyhow!(
+                "Writing 0x{:X} bytes at offset 0x{:X} overflows '{}' by 0x{:X} bytes.",
+                len,
+                offset,
+                self.partition,
+                len - space
+            )
+        };
+
+        if let Some(size) = self.size
+            && size > space
+        {
+            return Err(overflow(size));
+        }
+
+        // Best effort: failing to read seccfg or vbmeta shouldn't prevent the write itself
+        let locked = dev.is_bootloader_locked().await.ok().flatten() == Some(true);
+        if locked && dev.avb_vbmeta_for(&self.partition).await.ok().flatten().is_some() {
+            let explanation = format!(
+                "The bootloader is locked and '{}' is verified by AVB: \
+                 a modified image will be rejected at boot, leaving the device in a bootloop. \
+                 Flash stock images with `download` instead, or unlock the bootloader first.",
+                self.partition
+            );
+            if !self.acknowledge_avb {
+                return Err(anyhow::anyhow!(
+                    "{} Pass --acknowledge-avb to write anyway.",
+                    explanation
+                ));
+            }
+            warn!("{}", explanation);
+        }
+
+        let (mut reader, total_size): (Box<dyn AsyncRead + Unpin + Send>, u64) =
+            if is_stdio(&self.file) {
+                let size = self.size.unwrap_or(space);
+                (Box::new(padded(stdin(), size)), size)
+            } else {
+                let wait_stable = self.wait_stable.map(Duration::from_secs);
+                let (reader, file_size) = StableInput::open_image(&self.file, wait_stable).await?;
+                match self.size {
+                    Some(size) => (Box::new(padded(reader, size)), size),
+                    None if self.offset.is_some() => {
+                        if file_size > space {
+                            return Err(overflow(file_size));
+                        }
+                        if self.pad {
+                            (Box::new(padded(reader, space)), space)
+                        } else {
+                            (Box::new(reader), file_size)
+                        }
+                    }
+                    None => (Box::new(reader), file_size.min(part_size)),
+                }
+            };
 
+        // Ranges are written as is, without expanding sparse images
+        let ranged = self.size.is_some() || self.offset.is_some();
+        let address = partition.address + offset;
+
+        // Stdin can't be replayed and partial writes can't be resumed as a whole partition,
+        // so only plain file writes are journaled
+        let mut journal = if is_stdio(&self.file) || ranged {
+            None
+        } else {
+            Some(
+                Journal::begin(
+                    WriteKind::Write,
+                    &self.partition,
+                    &self.file,
+                    total_size,
+                    &dev.dev_info,
+                )
+                .await?,
+            )
+        };
+
+        let pb = AntumbraProgress::new(total_size).for_partition(&self.partition);
+
+        let mut verify_pb: Option<AntumbraProgress> = None;
         let mut progress_callback = {
             let pb = &pb;
-            move |written: usize, total: usize| {
-                pb.update(written as u64, "Writing flash");
+            let journal = &mut journal;
+            move |event: ProgressEvent| {
+                if event.phase == OperationKind::Verify {
+                    let verify_pb = verify_pb.get_or_insert_with(|| {
+                        AntumbraProgress::new(event.bytes_total).for_partition(&self.partition)
+                    });
+                    verify_pb.event(&event, "Verifying...");
+                    if event.is_done() {
+                        verify_pb.finish("Verification complete!");
+                    }
+                    return;
+                }
+
+                pb.event(&event, "Writing flash");
+                if let Some(journal) = journal.as_mut() {
+                    journal.progress(event.bytes_done);
+                }
 
-                if written >= total {
+                if event.is_done() {
                     pb.finish("Write complete!");
                 }
             }
         };
+        let result = match (ranged, self.verify) {
+            (true, true) => {
+                dev.write_offset_verified(
+                    address,
+                    total_size as usize,
+                    &mut reader,
+                    partition.kind,
+                    false,
+                    &mut progress_callback,
+                )
+                .await
+            }
+            (true, false) => {
+                dev.write_offset(
+                    address,
+                    total_size as usize,
+                    &mut reader,
+                    partition.kind,
+                    false,
+                    &mut progress_callback,
+                )
+                .await
+            }
+            // The whole partition is replaced, zero-filled after images smaller than it
+            (false, true) => {
+                dev.write_partition_verified(
+                    &self.partition,
+                    &mut reader,
+                    true,
+                    &mut progress_callback,
+                )
+                .await
+            }
+            (false, false) => {
+                dev.write_partition(&self.partition, &mut reader, true, &mut progress_callback)
+                    .await
+            }
+        };
 
-        match dev.write_partition(&self.partition, &mut reader, &mut progress_callback).await {
+        match result {
             Ok(_) => {}
             Err(e) => {
                 pb.abandon("Write failed!");
@@ -83,11 +269,15 @@ impl MtkCommand for WriteArgs {
             }
         }
 
+        if let Some(journal) = journal {
+            journal.complete().await?;
+        }
+
         Ok(())
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.da_file.as_ref()
     }
 
     fn pl(&self) -> Option<&PathBuf> {
@@ -0,0 +1,102 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Args;
+use log::{info, warn};
+use penumbra::{Device, ProgressEvent};
+use tokio::fs::read;
+
+use crate::cli::MtkCommand;
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
+use crate::cli::helpers::AntumbraProgress;
+use crate::cli::state::PersistedDeviceState;
+
+#[derive(Args, Debug)]
+pub struct WritePreloaderArgs {
+    #[command(flatten)]
+    pub da: DaArgs,
+    /// The preloader to write, either a bare preloader or a full boot image
+    pub file: PathBuf,
+    /// Also write the preloader to BOOT2, the copy used when BOOT1 is corrupt
+    #[arg(long)]
+    pub mirror: bool,
+}
+
+impl CommandMetadata for WritePreloaderArgs {
+    fn visible_aliases() -> &'static [&'static str] {
+        &["wpl"]
+    }
+
+    fn about() -> &'static str {
+        "Write a preloader to the boot partitions."
+    }
+
+    fn long_about() -> &'static str {
+        "Write a preloader to BOOT1, and with --mirror to BOOT2 as well.
+        Each copy is verified after writing, and the original preloader is restored if anything fails.
+        Without --mirror, BOOT2 keeps the old preloader, which the device boots when BOOT1 is corrupt."
+    }
+
+    fn examples() -> &'static [&'static str] {
+        &[
+            "antumbra write-preloader --da DA.bin preloader_penangf.bin --mirror",
+            "antumbra write-preloader --da DA.bin preloader_penangf.bin",
+        ]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
+    }
+}
+
+#[async_trait]
+impl MtkCommand for WritePreloaderArgs {
+    async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()> {
+        dev.enter_da_mode().await?;
+
+        state.connection_type = CONN_DA;
+        state.flash_mode = 1;
+
+        let data = read(&self.file).await?;
+        if !self.mirror {
+            warn!(
+                "Only writing BOOT1, BOOT2 keeps the old preloader. Use --mirror to update both."
+            );
+        }
+
+        let pb = AntumbraProgress::new(data.len() as u64);
+        let mut progress_callback = {
+            let pb = &pb;
+            move |event: ProgressEvent| {
+                pb.event(&event, "Writing preloader...");
+            }
+        };
+
+        info!("Writing preloader from '{}'...", self.file.display());
+
+        match dev.write_preloader(&data, self.mirror, &mut progress_callback).await {
+            Ok(_) => pb.finish("Preloader written!"),
+            Err(e) => {
+                pb.abandon("Preloader write failed!");
+                return Err(e)?;
+            }
+        }
+
+        info!("Preloader written and verified.");
+
+        Ok(())
+    }
+
+    fn da(&self) -> Option<&PathBuf> {
+        self.da.da_file.as_ref()
+    }
+
+    fn pl(&self) -> Option<&PathBuf> {
+        self.da.preloader_file.as_ref()
+    }
+}
@@ -9,14 +9,14 @@ use anyhow::Result;
 use async_trait::async_trait;
 use clap::{Args, Subcommand};
 use log::info;
-use penumbra::Device;
 use penumbra::da::XFlash;
-use penumbra::da::xflash::flash::set_rsc_info;
+use penumbra::da::xflash::set_rsc_info;
+use penumbra::{Device, ProgressEvent};
 use tokio::fs::{File, metadata};
 use tokio::io::BufReader;
 
 use crate::cli::MtkCommand;
-use crate::cli::common::{CONN_DA, CommandMetadata, DaArgs};
+use crate::cli::common::{CONN_DA, CommandFlags, CommandMetadata, DaArgs};
 use crate::cli::helpers::AntumbraProgress;
 use crate::cli::state::PersistedDeviceState;
 
@@ -69,10 +69,10 @@ impl MtkCommand for RscFlashArgs {
 
         let mut progress_callback = {
             let pb = &pb;
-            move |written: usize, total: usize| {
-                pb.update(written as u64, "Flashing...");
+            move |event: ProgressEvent| {
+                pb.event(&event, "Flashing...");
 
-                if written >= total {
+                if event.is_done() {
                     pb.finish("Flash complete!");
                 }
             }
@@ -93,7 +93,7 @@ impl MtkCommand for RscFlashArgs {
     }
 
     fn da(&self) -> Option<&PathBuf> {
-        Some(&self.da.da_file)
+        self.da.da_file.as_ref()
     }
 
     fn pl(&self) -> Option<&PathBuf> {
@@ -124,6 +124,14 @@ impl CommandMetadata for XFlashArgs {
     fn long_about() -> &'static str {
         "Commands specific to XFlash / V5 devices."
     }
+
+    fn examples() -> &'static [&'static str] {
+        &["antumbra x-flash rsc-flash --da DA.bin rsc rsc.bin"]
+    }
+
+    fn flags() -> CommandFlags {
+        CommandFlags { destructive: true, needs_da: true, ..Default::default() }
+    }
 }
 
 #[async_trait]
@@ -2,7 +2,27 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
-use std::path::PathBuf;
+use std::fs::TryLockError;
+use std::io::{IsTerminal, Read, Write, stderr, stdin};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow};
+use log::info;
+use penumbra::core::avb::strip_slot_suffix;
+use penumbra::utilities::sparse::{SPARSE_HEADER_SIZE, expanded_size};
+use penumbra::{Capabilities, Device, PartitionKind};
+use tokio::fs::{File, metadata, try_exists};
+use tokio::io::{AsyncRead, BufReader, ReadBuf};
+use tokio::task::{JoinHandle, spawn_blocking};
+use tokio::time::sleep;
+
+use crate::cli::helpers::{DumpFormat, DumpReader, is_stdio};
+
+/// How often the input size is polled while waiting for it to settle
+const STABLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 #[allow(dead_code)]
 pub const CONN_BR: u8 = 0;
@@ -11,19 +31,210 @@ pub const CONN_PL: u8 = 1;
 pub const CONN_DA: u8 = 2;
 
 use clap::Args;
+use clap_num::maybe_hex;
 
 #[derive(Args, Debug)]
 pub struct DaArgs {
-    // The DA file to use
+    /// The DA file to use. Defaults to the DA repository's best one for the chipset,
+    /// see `antumbra da import`
     #[arg(short, long = "da", value_name = "DA_FILE")]
-    pub da_file: PathBuf,
+    pub da_file: Option<PathBuf>,
     // #[arg(long, value_name = "AUTH_FILE")]
     // pub auth_file: Option<PathBuf>,
-    // The preloader file to use
+    /// Preloader image of the device. Its EMI settings let the DA initialize DRAM
+    /// when only BROM answers, like with an erased preloader
     #[arg(short, long = "pl", value_name = "PRELOADER_FILE")]
     pub preloader_file: Option<PathBuf>,
 }
 
+/// Raw range of a storage section, for commands that otherwise take a partition.
+#[derive(Args, Debug)]
+pub struct SectionArgs {
+    /// Work on a raw range of this storage section instead of a partition, which is then
+    /// left out: user, boot1, boot2 or rpmb on eMMC, user, lu0, lu1, lu2 or rpmb on UFS
+    #[arg(long, value_name = "SECTION")]
+    pub section: Option<String>,
+    /// Start of the range within the section
+    #[arg(long, requires = "section", value_parser = maybe_hex::<u64>)]
+    pub offset: Option<u64>,
+}
+
+/// A range of a storage section, resolved from [`SectionArgs`].
+pub struct SectionRange {
+    pub name: String,
+    pub kind: PartitionKind,
+    pub offset: u64,
+    pub length: u64,
+}
+
+impl SectionArgs {
+    /// Splits `<PARTITION> <FILE>` positionals: with --section, only the file is given.
+    pub fn split<'a>(
+        &self,
+        first: &'a str,
+        second: Option<&'a Path>,
+    ) -> Result<(Option<&'a str>, &'a Path)> {
+        match (&self.section, second) {
+            (None, Some(file)) => Ok((Some(first), file)),
+            (Some(_), None) => Ok((None, Path::new(first))),
+            (Some(section), Some(_)) => Err(anyhow!(
+                "--section {} works on a raw range, only give the file, not a partition.",
+                section
+            )),
+            (None, None) => Err(anyhow!("Missing the file, after the partition.")),
+        }
+    }
+
+    /// Resolves the section against the storage of the device, which must be in DA mode.
+    /// The range spans `length` bytes, or up to the end of the section, and must fit in it.
+    pub async fn resolve(
+        &self,
+        dev: &mut Device,
+        length: Option<u64>,
+    ) -> Result<Option<SectionRange>> {
+        let Some(name) = &self.section else {
+            return Ok(None);
+        };
+
+        let info = dev.get_storage_info().await?;
+        let kind = PartitionKind::from_str_for(name, info.kind)?;
+        let offset = self.offset.unwrap_or(0);
+        let size = info.section_size(kind);
+
+        let length = match (length, size) {
+            (Some(length), _) => length,
+            (None, Some(size)) => size.saturating_sub(offset),
+            (None, None) => {
+                return Err(anyhow!("The size of '{}' isn't known, give a length.", name));
+            }
+        };
+
+        if let Some(size) = size
+            && offset.saturating_add(length) > size
+        {
+            return Err(anyhow!(
+                "0x{:X} bytes at 0x{:X} don't fit in '{}' (0x{:X} bytes).",
+                length,
+                offset,
+                name,
+                size
+            ));
+        }
+
+        Ok(Some(SectionRange { name: name.to_lowercase(), kind, offset, length }))
+    }
+}
+
+/// What dump-producing commands do when an output file already exists.
+#[derive(Args, Debug, Clone, Copy)]
+pub struct OutputArgs {
+    /// Overwrite existing output files
+    #[arg(long, conflicts_with = "timestamp")]
+    pub overwrite: bool,
+    /// Append a UTC timestamp to output file names, keeping existing files
+    #[arg(long)]
+    pub timestamp: bool,
+}
+
+impl OutputArgs {
+    pub fn resolver(&self) -> OutputResolver {
+        let policy = if self.overwrite {
+            CollisionPolicy::Overwrite
+        } else if self.timestamp {
+            CollisionPolicy::Timestamp
+        } else {
+            CollisionPolicy::Refuse
+        };
+        OutputResolver::new(policy, SystemTime::now())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    /// Fail if the file exists
+    Refuse,
+    Overwrite,
+    /// Stamp every file name, failing only if the stamped file exists too
+    Timestamp,
+}
+
+/// Turns the output paths given by the user into the paths a command actually writes.
+/// Every path resolved by the same resolver gets the same timestamp, so files dumped
+/// together can be told apart from other dumps.
+pub struct OutputResolver {
+    policy: CollisionPolicy,
+    stamp: String,
+}
+
+impl OutputResolver {
+    pub fn new(policy: CollisionPolicy, time: SystemTime) -> Self {
+        Self { policy, stamp: file_timestamp(time) }
+    }
+
+    /// Returns the path to write for `path`, or an error naming the file that would be
+    /// overwritten. `-` (stdout) is returned as is.
+    pub async fn resolve(&self, path: &Path) -> Result<PathBuf> {
+        if is_stdio(path) {
+            return Ok(path.to_path_buf());
+        }
+
+        let path = match self.policy {
+            CollisionPolicy::Overwrite => return Ok(path.to_path_buf()),
+            CollisionPolicy::Refuse => path.to_path_buf(),
+            CollisionPolicy::Timestamp => stamped(path, &self.stamp),
+        };
+
+        if try_exists(&path).await? {
+            return Err(anyhow!(
+                "'{}' already exists. Use --overwrite to replace it, or --timestamp to keep both.",
+                path.display()
+            ));
+        }
+
+        Ok(path)
+    }
+}
+
+/// Inserts `stamp` before the extensions of the file name: `boot.img.zst` becomes
+/// `boot_<stamp>.img.zst`.
+fn stamped(path: &Path, stamp: &str) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    // A leading dot is part of the name, not an extension
+    let name = match name.char_indices().skip(1).find(|(_, c)| *c == '.') {
+        Some((i, _)) => format!("{}_{}{}", &name[..i], stamp, &name[i..]),
+        None => format!("{}_{}", name, stamp),
+    };
+    path.with_file_name(name)
+}
+
+/// Formats `time` as a basic ISO 8601 UTC timestamp (`20250101T120000Z`),
+/// which unlike the extended format is a valid file name everywhere.
+fn file_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Days since the epoch to a civil date, from Howard Hinnant's `civil_from_days`
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
 /// A trait for providing metadata for CLI commands.
 /// This trait can be implemented by command structs to give additional info
 pub trait CommandMetadata {
@@ -42,4 +253,484 @@ pub trait CommandMetadata {
     fn hide() -> bool {
         false
     }
+    /// Copy-pasteable invocations, shown after the options in `--help`
+    fn examples() -> &'static [&'static str] {
+        &[]
+    }
+    fn flags() -> CommandFlags {
+        CommandFlags::default()
+    }
+    fn after_help() -> String {
+        render_after_help(Self::examples(), Self::flags())
+    }
+}
+
+/// Prerequisites and side effects of a command.
+/// They're checked before the command runs, so commands don't need to repeat the checks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommandFlags {
+    /// Writes to the device. Asks for confirmation, otherwise the session is made read-only.
+    pub destructive: bool,
+    /// Needs a DA to be loaded on the device
+    pub needs_da: bool,
+    /// Needs DA extensions, which only boot on a DA patched by an exploit
+    pub needs_exploits: bool,
+}
+
+fn render_after_help(examples: &[&str], flags: CommandFlags) -> String {
+    let mut prerequisites = Vec::new();
+    if flags.needs_da {
+        prerequisites.push("A DA file, given with --da or imported with `antumbra da import`");
+    }
+    if flags.needs_exploits {
+        prerequisites.push("DA extensions: the device must be vulnerable to an exploit or unfused");
+    }
+    if flags.destructive {
+        prerequisites
+            .push("Modifies the device: confirmation is asked, or --yes without a terminal");
+    }
+
+    let mut help = String::new();
+    if !prerequisites.is_empty() {
+        help.push_str("Prerequisites:\n");
+        for prerequisite in prerequisites {
+            help.push_str(&format!("  - {}\n", prerequisite));
+        }
+    }
+    if !examples.is_empty() {
+        if !help.is_empty() {
+            help.push('\n');
+        }
+        help.push_str("Examples:\n");
+        for example in examples {
+            help.push_str(&format!("  {}\n", example));
+        }
+    }
+    help
+}
+
+/// Checks that the build supports the command, and confirms destructive ones.
+///
+/// Without a terminal to ask on, e.g. when stdin is piped and might carry the image
+/// itself, destructive commands are refused unless `assume_yes` is set.
+pub fn confirm_command(name: &str, flags: CommandFlags, assume_yes: bool) -> Result<()> {
+    let caps = Capabilities::current();
+    if flags.needs_exploits && !caps.exploits_brom && !caps.exploits_da_patch {
+        return Err(anyhow!(
+            "{} needs DA extensions, and this build has no exploit to patch the DA with",
+            name
+        ));
+    }
+
+    if !flags.destructive || assume_yes {
+        return Ok(());
+    }
+    if !stdin().is_terminal() {
+        return Err(anyhow!(
+            "{} will modify the device, and there's no terminal to confirm it. \
+             Pass --yes to run it anyway",
+            name
+        ));
+    }
+
+    eprint!("{} will modify the device. Continue? [y/N] ", name);
+    stderr().flush()?;
+
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(anyhow!("Aborted by user")),
+    }
+}
+
+/// Partitions whose loss bricks the device or wipes its calibration and identity,
+/// which erase commands refuse unless forced.
+pub const GUARDED_PARTITIONS: &[&str] = &["preloader", "seccfg", "nvram"];
+
+/// Refuses to wipe a guarded partition (slot suffixes included) unless `force` is set.
+pub fn check_guarded(partition: &str, force: bool) -> Result<()> {
+    if force || !GUARDED_PARTITIONS.contains(&strip_slot_suffix(partition)) {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "Refusing to wipe '{}': the device may not boot or lose its calibration data without it. \
+         Use --force to wipe it anyway.",
+        partition
+    ))
+}
+
+/// An image opened for flashing, guarding against files that are still being written.
+///
+/// The size is checked when opening and again once the end of the file is reached,
+/// before the last chunk makes it to the device. A shared advisory lock is held where
+/// the platform supports it, so writers that lock the file are detected too.
+pub struct StableInput {
+    file: File,
+    /// Handle used to re-check the size, and that holds the lock
+    guard: std::fs::File,
+    size: u64,
+    /// Expanded size, if the input is a sparse image
+    sparse_size: Option<u64>,
+    read: u64,
+    /// Size check running before the last chunk is read
+    size_check: Option<JoinHandle<std::io::Result<u64>>>,
+    checked: bool,
+}
+
+impl StableInput {
+    /// Opens `path`, optionally waiting for its size to stay unchanged for `wait_stable`.
+    pub async fn open(path: &Path, wait_stable: Option<Duration>) -> Result<Self> {
+        if let Some(wait) = wait_stable {
+            wait_for_stable_size(path, wait).await?;
+        }
+
+        let size = metadata(path).await?.len();
+        let guard = std::fs::File::open(path)?;
+
+        match guard.try_lock_shared() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(anyhow!(
+                    "'{}' is locked by another process, it might still be written.",
+                    path.display()
+                ));
+            }
+            // Not every platform or filesystem supports locks, the size checks still apply
+            Err(TryLockError::Error(_)) => {}
+        }
+
+        let file = File::from_std(guard.try_clone()?);
+
+        if guard.metadata()?.len() != size {
+            return Err(anyhow!(
+                "'{}' changed size while being opened, it's probably still being written. \
+                Use --wait-stable to wait for it.",
+                path.display()
+            ));
+        }
+
+        // Sparse images are expanded while flashing, see `Device::write_partition`
+        let mut header = [0u8; SPARSE_HEADER_SIZE];
+        let sparse_size = match std::fs::File::open(path)?.read_exact(&mut header) {
+            Ok(()) => expanded_size(&header),
+            Err(_) => None,
+        };
+
+        Ok(StableInput {
+            file,
+            guard,
+            size,
+            sparse_size,
+            read: 0,
+            size_check: None,
+            checked: false,
+        })
+    }
+
+    /// Size of the data once flashed: the expanded size for sparse images,
+    /// the file size otherwise.
+    pub fn flashed_size(&self) -> u64 {
+        self.sparse_size.unwrap_or(self.size)
+    }
+
+    /// Opens an image to flash, like `open`, decoding gzip and zstd dumps on the fly.
+    /// Returns the reader along with the size of the data once flashed.
+    pub async fn open_image(
+        path: &Path,
+        wait_stable: Option<Duration>,
+    ) -> Result<(Box<dyn AsyncRead + Unpin + Send>, u64)> {
+        let input = Self::open(path, wait_stable).await?;
+
+        let format = DumpFormat::from_path(path);
+        if !format.is_compressed() {
+            let size = input.flashed_size();
+            return Ok((Box::new(BufReader::new(input)), size));
+        }
+
+        info!("Decompressing '{}' to measure it...", path.display());
+        let size = DumpReader::flashed_size(path, format).await?;
+        // The decoder reads through the locked handle, and fails by itself on truncated data
+        let reader = DumpReader::new(input.guard, format)?;
+
+        Ok((Box::new(reader), size))
+    }
+
+    fn changed_size() -> std::io::Error {
+        std::io::Error::other(
+            "Input file changed size while flashing, it's probably still being written.",
+        )
+    }
+
+    /// Re-checks the size of the file, off the runtime as stat can block on slow or
+    /// network filesystems.
+    fn poll_check_size(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let check = match &mut self.size_check {
+            Some(check) => check,
+            None => {
+                let guard = self.guard.try_clone()?;
+                self.size_check.insert(spawn_blocking(move || Ok(guard.metadata()?.len())))
+            }
+        };
+
+        let size = ready!(Pin::new(check).poll(cx)).map_err(std::io::Error::other)??;
+        self.size_check = None;
+        self.checked = true;
+
+        if size != self.size {
+            return Poll::Ready(Err(Self::changed_size()));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for StableInput {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        // Last chunk, make sure the file is still what we started with before reading it
+        if !self.checked && self.read + buf.remaining() as u64 >= self.size {
+            ready!(self.poll_check_size(cx))?;
+        }
+
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.file).poll_read(cx, buf))?;
+
+        let n = buf.filled().len() - before;
+        self.read += n as u64;
+
+        // Shrunk before the end was reached, or grown past the checked size
+        if (n == 0 && self.read < self.size) || self.read > self.size {
+            return Poll::Ready(Err(Self::changed_size()));
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Polls the size of `path` until it stays the same for `duration`.
+async fn wait_for_stable_size(path: &Path, duration: Duration) -> Result<()> {
+    let mut last_size = metadata(path).await?.len();
+    let mut stable_since = Instant::now();
+    let mut waiting = false;
+
+    while stable_since.elapsed() < duration {
+        sleep(STABLE_POLL_INTERVAL).await;
+
+        let size = metadata(path).await?.len();
+        if size != last_size {
+            if !waiting {
+                info!("'{}' is still changing, waiting for it to settle...", path.display());
+                waiting = true;
+            }
+            last_size = size;
+            stable_since = Instant::now();
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs::OpenOptions;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    const SIZE: usize = 256 * 1024;
+    const CHUNK: usize = 16 * 1024;
+
+    /// Scratch image of `SIZE` bytes, removed once dropped.
+    struct Image(PathBuf);
+
+    impl Image {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "antumbra-stable-{}-{}.img",
+                std::process::id(),
+                name
+            ));
+            std::fs::write(&path, vec![0xA5; SIZE]).unwrap();
+            Image(path)
+        }
+    }
+
+    impl Drop for Image {
+        fn drop(&mut self) {
+            std::fs::remove_file(&self.0).ok();
+        }
+    }
+
+    /// Reads half of the image, lets `change` run in a background task, then reads
+    /// the rest.
+    async fn read_while<F>(image: &Image, change: F) -> std::io::Result<Vec<u8>>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut input = StableInput::open(&image.0, None).await.unwrap();
+        let mut data = vec![0u8; SIZE / 2];
+        for chunk in data.chunks_mut(CHUNK) {
+            input.read_exact(chunk).await?;
+        }
+
+        tokio::spawn(change).await.unwrap();
+
+        let mut chunk = vec![0u8; CHUNK];
+        loop {
+            let n = input.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    #[tokio::test]
+    async fn unchanged_file_is_read_whole() {
+        let image = Image::new("unchanged");
+
+        let data = read_while(&image, async {}).await.unwrap();
+        assert_eq!(data, vec![0xA5; SIZE]);
+    }
+
+    #[tokio::test]
+    async fn appended_file_is_refused() {
+        let image = Image::new("appended");
+        let path = image.0.clone();
+
+        let err = read_while(&image, async move {
+            let mut file = OpenOptions::new().append(true).open(path).await.unwrap();
+            file.write_all(&[0x5A; CHUNK]).await.unwrap();
+        })
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("changed size"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn truncated_file_is_refused() {
+        let image = Image::new("truncated");
+        let path = image.0.clone();
+
+        let err = read_while(&image, async move {
+            let file = OpenOptions::new().write(true).open(path).await.unwrap();
+            file.set_len((SIZE - CHUNK) as u64).await.unwrap();
+        })
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("changed size"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn growing_file_is_waited_for() {
+        let image = Image::new("growing");
+        let path = image.0.clone();
+
+        let writer = tokio::spawn(async move {
+            let mut file = OpenOptions::new().append(true).open(path).await.unwrap();
+            for _ in 0..3 {
+                sleep(STABLE_POLL_INTERVAL / 2).await;
+                file.write_all(&[0x5A; CHUNK]).await.unwrap();
+            }
+        });
+
+        let input = StableInput::open(&image.0, Some(STABLE_POLL_INTERVAL * 2)).await.unwrap();
+        writer.await.unwrap();
+        assert_eq!(input.flashed_size(), (SIZE + 3 * CHUNK) as u64);
+    }
+
+    /// A path under the temp directory, unique to the test and the process.
+    fn scratch(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("antumbra-output-{}-{}", std::process::id(), name))
+    }
+
+    fn resolver(policy: CollisionPolicy) -> OutputResolver {
+        // 2023-11-14 22:13:20 UTC
+        OutputResolver::new(policy, UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+    }
+
+    #[tokio::test]
+    async fn existing_files_are_refused_by_default() {
+        let path = scratch("refused.bin");
+        std::fs::write(&path, b"only good backup").unwrap();
+
+        let err = resolver(CollisionPolicy::Refuse).resolve(&path).await.unwrap_err();
+        assert!(err.to_string().contains(&*path.to_string_lossy()), "{}", err);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(resolver(CollisionPolicy::Refuse).resolve(&path).await.unwrap(), path);
+    }
+
+    #[tokio::test]
+    async fn overwrite_keeps_the_path() {
+        let path = scratch("overwritten.bin");
+        std::fs::write(&path, b"old dump").unwrap();
+
+        let resolved = resolver(CollisionPolicy::Overwrite).resolve(&path).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resolved.unwrap(), path);
+    }
+
+    #[tokio::test]
+    async fn timestamp_keeps_existing_files() {
+        let path = scratch("stamped.img.zst");
+        let stamped = scratch("stamped_20231114T221320Z.img.zst");
+        std::fs::write(&path, b"old dump").unwrap();
+
+        let resolver = resolver(CollisionPolicy::Timestamp);
+        assert_eq!(resolver.resolve(&path).await.unwrap(), stamped);
+
+        // A second dump within the same second doesn't replace the first either
+        std::fs::write(&stamped, b"new dump").unwrap();
+        let err = resolver.resolve(&path).await.unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&stamped).unwrap();
+        assert!(err.to_string().contains("already exists"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn stdout_is_never_resolved() {
+        for policy in
+            [CollisionPolicy::Refuse, CollisionPolicy::Overwrite, CollisionPolicy::Timestamp]
+        {
+            assert_eq!(resolver(policy).resolve(Path::new("-")).await.unwrap(), Path::new("-"));
+        }
+    }
+
+    #[test]
+    fn stamp_goes_before_the_extensions() {
+        let stamp = "20231114T221320Z";
+        assert_eq!(
+            stamped(Path::new("dump/boot.img.zst"), stamp),
+            Path::new("dump/boot_20231114T221320Z.img.zst")
+        );
+        assert_eq!(stamped(Path::new("preloader"), stamp), Path::new("preloader_20231114T221320Z"));
+        assert_eq!(stamped(Path::new(".hidden"), stamp), Path::new(".hidden_20231114T221320Z"));
+    }
+
+    #[test]
+    fn timestamps_are_iso_8601_utc() {
+        assert_eq!(file_timestamp(UNIX_EPOCH), "19700101T000000Z");
+        // Leap day
+        assert_eq!(
+            file_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "20000229T000000Z"
+        );
+        assert_eq!(
+            file_timestamp(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            "20231114T221320Z"
+        );
+    }
+
+    #[test]
+    fn flags_pick_the_policy() {
+        let policy = |overwrite, timestamp| OutputArgs { overwrite, timestamp }.resolver().policy;
+        assert_eq!(policy(false, false), CollisionPolicy::Refuse);
+        assert_eq!(policy(true, false), CollisionPolicy::Overwrite);
+        assert_eq!(policy(false, true), CollisionPolicy::Timestamp);
+    }
 }
@@ -0,0 +1,86 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+//! Line-delimited JSON events on stdout, for tools driving antumbra with `--json`.
+//! Human logs go to stderr in this mode, so that stdout only ever holds events.
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use penumbra::error::ErrorKind;
+use serde::Serialize;
+
+static JSON_EVENTS: AtomicBool = AtomicBool::new(false);
+
+/// Switches progress and status reporting to JSON events.
+pub fn enable_json() {
+    JSON_EVENTS.store(true, Ordering::Release);
+}
+
+pub fn json_enabled() -> bool {
+    JSON_EVENTS.load(Ordering::Acquire)
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    DeviceInfo {
+        hw_code: u16,
+        soc_id: Option<String>,
+        meid: Option<String>,
+        sbc: bool,
+        sla: bool,
+        daa: bool,
+    },
+    OperationStart {
+        op: Option<&'a str>,
+        partition: Option<&'a str>,
+        total: u64,
+    },
+    Progress {
+        op: Option<&'a str>,
+        partition: Option<&'a str>,
+        done: u64,
+        total: u64,
+    },
+    OperationEnd {
+        op: Option<&'a str>,
+        partition: Option<&'a str>,
+        ok: bool,
+        done: u64,
+    },
+    Error {
+        kind: &'static str,
+        message: String,
+    },
+}
+
+impl Event<'_> {
+    /// An error event for `err`, typed after the core error it comes from.
+    pub fn error(err: &anyhow::Error) -> Event<'static> {
+        let kind = match err.downcast_ref::<penumbra::error::Error>().map(|e| e.kind()) {
+            Some(ErrorKind::Connection) => "connection",
+            Some(ErrorKind::Protocol) => "protocol",
+            Some(ErrorKind::Security) => "security",
+            Some(ErrorKind::Device) => "device",
+            Some(ErrorKind::Integrity) => "integrity",
+            Some(ErrorKind::Unsupported) => "unsupported",
+            Some(ErrorKind::Other) | None => "other",
+        };
+
+        Event::Error { kind, message: format!("{:#}", err) }
+    }
+}
+
+/// Writes `event` as a single line on stdout, when JSON events are enabled.
+pub fn emit(event: &Event) {
+    if !json_enabled() {
+        return;
+    }
+
+    if let Ok(line) = serde_json::to_string(event) {
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", line).ok();
+        stdout.flush().ok();
+    }
+}
@@ -0,0 +1,156 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::fs;
+use std::io::{BufReader, Read, copy, sink};
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use flate2::read::MultiGzDecoder;
+use penumbra::utilities::sparse::{
+    CHUNK_HEADER_SIZE,
+    CHUNK_TYPE_CRC32,
+    CHUNK_TYPE_DONT_CARE,
+    CHUNK_TYPE_FILL,
+    CHUNK_TYPE_RAW,
+    SPARSE_HEADER_MAGIC,
+    SPARSE_HEADER_SIZE,
+};
+use sha2::{Digest, Sha256};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+use crate::cli::helpers::DumpFormat;
+
+const HASH_BUF_SIZE: usize = 1024 * 1024;
+
+/// SHA-256 of a whole file, for dumps whose data didn't all go through the device hash.
+pub async fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; HASH_BUF_SIZE];
+
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// SHA-256 of the raw data held by a dump, decoding it according to `format`.
+/// This matches the hash recorded in the manifest, whatever the dump format.
+///
+/// This blocks, run it on a blocking thread. `progress` gets the number of bytes
+/// of the file consumed so far, which for compressed dumps is less than the data hashed.
+pub fn dump_sha256(
+    path: &Path,
+    format: DumpFormat,
+    progress: &mut dyn FnMut(u64),
+) -> Result<String> {
+    let file = CountingReader { inner: fs::File::open(path)?, count: 0, progress };
+    let mut hasher = Sha256::new();
+
+    match format {
+        DumpFormat::Raw => hash_reader(file, &mut hasher)?,
+        DumpFormat::Zstd => hash_reader(zstd::stream::read::Decoder::new(file)?, &mut hasher)?,
+        DumpFormat::Gzip => hash_reader(MultiGzDecoder::new(BufReader::new(file)), &mut hasher)?,
+        DumpFormat::Sparse => hash_sparse(BufReader::new(file), &mut hasher)?,
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn hash_reader(mut reader: impl Read, hasher: &mut Sha256) -> Result<()> {
+    let mut buf = vec![0u8; HASH_BUF_SIZE];
+
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+/// Hashes the expanded content of a sparse image, without expanding it on disk.
+fn hash_sparse(mut reader: impl Read, hasher: &mut Sha256) -> Result<()> {
+    let mut header = [0u8; SPARSE_HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+
+    let le_u16 = |b: &[u8], off: usize| u16::from_le_bytes([b[off], b[off + 1]]);
+    let le_u32 =
+        |b: &[u8], off: usize| u32::from_le_bytes([b[off], b[off + 1], b[off + 2], b[off + 3]]);
+
+    if le_u32(&header, 0) != SPARSE_HEADER_MAGIC {
+        return Err(anyhow!("Not a sparse image"));
+    }
+
+    let file_hdr_sz = le_u16(&header, 8) as u64;
+    let chunk_hdr_sz = le_u16(&header, 10) as u64;
+    let blk_sz = le_u32(&header, 12) as usize;
+    let total_chunks = le_u32(&header, 20);
+
+    // Headers may be larger than the ones we know of, skip the extra bytes
+    skip(&mut reader, file_hdr_sz.saturating_sub(SPARSE_HEADER_SIZE as u64))?;
+
+    let mut block = vec![0u8; blk_sz];
+    for _ in 0..total_chunks {
+        let mut chunk = [0u8; CHUNK_HEADER_SIZE];
+        reader.read_exact(&mut chunk)?;
+        skip(&mut reader, chunk_hdr_sz.saturating_sub(CHUNK_HEADER_SIZE as u64))?;
+
+        let blocks = le_u32(&chunk, 4);
+        match le_u16(&chunk, 0) {
+            CHUNK_TYPE_RAW => {
+                let len = blocks as u64 * blk_sz as u64;
+                let mut data = (&mut reader).take(len);
+                hash_reader(&mut data, hasher)?;
+                if data.limit() != 0 {
+                    return Err(anyhow!("Sparse image truncated"));
+                }
+            }
+            CHUNK_TYPE_FILL | CHUNK_TYPE_DONT_CARE => {
+                let mut pattern = [0u8; 4];
+                if le_u16(&chunk, 0) == CHUNK_TYPE_FILL {
+                    reader.read_exact(&mut pattern)?;
+                }
+                for (i, b) in block.iter_mut().enumerate() {
+                    *b = pattern[i % 4];
+                }
+                for _ in 0..blocks {
+                    hasher.update(&block);
+                }
+            }
+            CHUNK_TYPE_CRC32 => skip(&mut reader, 4)?,
+            other => return Err(anyhow!("Unknown sparse chunk type 0x{:04X}", other)),
+        }
+    }
+
+    Ok(())
+}
+
+fn skip(reader: &mut impl Read, len: u64) -> Result<()> {
+    copy(&mut reader.take(len), &mut sink())?;
+    Ok(())
+}
+
+/// Reports how many bytes went through the inner reader.
+struct CountingReader<'a, R> {
+    inner: R,
+    count: u64,
+    progress: &'a mut dyn FnMut(u64),
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        (self.progress)(self.count);
+        Ok(n)
+    }
+}
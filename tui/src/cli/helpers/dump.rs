@@ -0,0 +1,448 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::future::Future;
+use std::io::{Read, SeekFrom, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use penumbra::utilities::sparse::{SPARSE_HEADER_SIZE, SparseWriter, expanded_size};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{
+    AsyncRead,
+    AsyncReadExt,
+    AsyncSeekExt,
+    AsyncWrite,
+    AsyncWriteExt,
+    BufWriter,
+    DuplexStream,
+    ReadBuf,
+    stdout,
+};
+use tokio::task::{JoinHandle, block_in_place, spawn_blocking};
+
+use crate::cli::helpers::is_stdio;
+
+/// Size of the in-memory pipe between the device reader and the encoder
+const PIPE_SIZE: usize = 1024 * 1024;
+
+/// Resumed dumps restart at a multiple of this. It's a multiple of every storage block
+/// size, so the device can read from there, and re-reading up to it costs nothing.
+pub const RESUME_ALIGNMENT: u64 = 64 * 1024;
+
+/// On-disk format used when dumping partitions.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// Plain raw image, byte for byte what's on the device
+    #[default]
+    Raw,
+    /// Android sparse image, zeroed blocks are not stored
+    Sparse,
+    /// Zstandard compressed raw image
+    Zstd,
+    /// Gzip compressed raw image, slower than zstd but readable everywhere
+    Gzip,
+}
+
+impl DumpFormat {
+    /// File extension matching the format, without the leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DumpFormat::Raw => "bin",
+            DumpFormat::Sparse => "img",
+            DumpFormat::Zstd => "bin.zst",
+            DumpFormat::Gzip => "bin.gz",
+        }
+    }
+
+    /// Whether dumps in this format must be decoded before flashing.
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, DumpFormat::Zstd | DumpFormat::Gzip)
+    }
+
+    /// Format of an existing dump, guessed from its file name.
+    pub fn from_path(path: &Path) -> Self {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        // Compressed dumps are matched on their last extension only, so `boot.img.gz` works too
+        let ext = |format: DumpFormat| match format.extension().rsplit_once('.') {
+            Some((_, last)) => format!(".{}", last),
+            None => format!(".{}", format.extension()),
+        };
+        [DumpFormat::Zstd, DumpFormat::Gzip, DumpFormat::Sparse]
+            .into_iter()
+            .find(|format| name.ends_with(&ext(*format)))
+            .unwrap_or(DumpFormat::Raw)
+    }
+}
+
+/// Writer that encodes a partition dump in the given `DumpFormat` while it's being read.
+///
+/// Data written here is piped to an encoder task, so the device reader never waits on
+/// compression more than the pipe allows. `finish` must be called to flush the encoder
+/// and get back any encoding error.
+pub struct DumpWriter {
+    pipe: DuplexStream,
+    task: JoinHandle<Result<()>>,
+}
+
+impl DumpWriter {
+    /// Creates the output file and starts the encoder.
+    /// `size` is the raw size of the data, which is needed by the sparse format.
+    /// A `-` path writes to stdout instead.
+    pub async fn create(path: &Path, format: DumpFormat, size: u64) -> Result<Self> {
+        let (pipe, rx) = tokio::io::duplex(PIPE_SIZE);
+
+        let task = if is_stdio(path) {
+            match format {
+                DumpFormat::Raw => tokio::spawn(encode_raw(rx, Box::new(stdout()))),
+                DumpFormat::Sparse => {
+                    return Err(anyhow!("Sparse dumps need a seekable output, not stdout."));
+                }
+                DumpFormat::Zstd => tokio::spawn(encode_zstd(rx, Box::new(std::io::stdout()))),
+                DumpFormat::Gzip => tokio::spawn(encode_gzip(rx, Box::new(std::io::stdout()))),
+            }
+        } else {
+            let file = File::create(path).await?;
+            match format {
+                DumpFormat::Raw => tokio::spawn(encode_raw(rx, Box::new(file))),
+                DumpFormat::Sparse => tokio::spawn(encode_sparse(rx, file, size)),
+                DumpFormat::Zstd => tokio::spawn(encode_zstd(rx, Box::new(file.into_std().await))),
+                DumpFormat::Gzip => tokio::spawn(encode_gzip(rx, Box::new(file.into_std().await))),
+            }
+        };
+
+        Ok(Self { pipe, task })
+    }
+
+    /// Reopens an interrupted raw dump of `size` bytes, to append the missing data.
+    /// The file is cut back to the last `RESUME_ALIGNMENT` boundary, which is returned as
+    /// the offset to read from. A missing file starts from 0, a complete one is left as is.
+    pub async fn resume(path: &Path, size: u64) -> Result<(Self, u64)> {
+        if is_stdio(path) {
+            return Err(anyhow!("Dumps to stdout can't be resumed."));
+        }
+
+        let mut file =
+            OpenOptions::new().create(true).write(true).truncate(false).open(path).await?;
+        let len = file.metadata().await?.len();
+        if len > size {
+            return Err(anyhow!(
+                "'{}' is larger than the partition ({} > {} bytes), it's not a dump to resume.",
+                path.display(),
+                len,
+                size
+            ));
+        }
+
+        let start = if len == size { size } else { len - len % RESUME_ALIGNMENT };
+        file.set_len(start).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+
+        let (pipe, rx) = tokio::io::duplex(PIPE_SIZE);
+        let task = tokio::spawn(encode_raw(rx, Box::new(file)));
+
+        Ok((Self { pipe, task }, start))
+    }
+
+    /// Closes the pipe and waits for the encoder to write everything out.
+    pub async fn finish(mut self) -> Result<()> {
+        self.pipe.shutdown().await?;
+        drop(self.pipe);
+
+        self.task.await.map_err(|e| anyhow!("Encoder task failed: {}", e))?
+    }
+}
+
+impl AsyncWrite for DumpWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.pipe).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.pipe).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.pipe).poll_shutdown(cx)
+    }
+}
+
+async fn encode_raw(mut rx: DuplexStream, out: Box<dyn AsyncWrite + Unpin + Send>) -> Result<()> {
+    let mut writer = BufWriter::new(out);
+    tokio::io::copy(&mut rx, &mut writer).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn encode_sparse(mut rx: DuplexStream, file: File, size: u64) -> Result<()> {
+    let mut sparse = SparseWriter::new(BufWriter::new(file), size).await?;
+    let mut buf = vec![0u8; PIPE_SIZE];
+
+    while let Some(n) = read_some(&mut rx, &mut buf).await? {
+        sparse.write(&buf[..n]).await?;
+    }
+
+    sparse.finish().await?;
+    Ok(())
+}
+
+async fn encode_zstd(mut rx: DuplexStream, out: Box<dyn Write + Send>) -> Result<()> {
+    let mut encoder = zstd::stream::write::Encoder::new(std::io::BufWriter::new(out), 0)?;
+    let mut buf = vec![0u8; PIPE_SIZE];
+
+    while let Some(n) = read_some(&mut rx, &mut buf).await? {
+        block_in_place(|| encoder.write_all(&buf[..n]))?;
+    }
+
+    block_in_place(|| encoder.finish()?.flush())?;
+    Ok(())
+}
+
+async fn encode_gzip(mut rx: DuplexStream, out: Box<dyn Write + Send>) -> Result<()> {
+    let mut encoder = GzEncoder::new(std::io::BufWriter::new(out), Compression::default());
+    let mut buf = vec![0u8; PIPE_SIZE];
+
+    while let Some(n) = read_some(&mut rx, &mut buf).await? {
+        block_in_place(|| encoder.write_all(&buf[..n]))?;
+    }
+
+    block_in_place(|| encoder.finish()?.flush())?;
+    Ok(())
+}
+
+/// Reader that decodes a compressed dump while it's being flashed, the counterpart
+/// of `DumpWriter`.
+///
+/// A truncated or corrupted dump fails the read instead of ending it early, so a bad
+/// archive never results in a partially flashed partition being reported as complete.
+pub struct DumpReader {
+    pipe: DuplexStream,
+    task: Option<JoinHandle<Result<()>>>,
+}
+
+impl DumpReader {
+    /// Starts decoding `file`, which holds a dump in the compressed `format`.
+    pub fn new(file: std::fs::File, format: DumpFormat) -> Result<Self> {
+        let decoder = decoder(file, format)?;
+        let (pipe, tx) = tokio::io::duplex(PIPE_SIZE);
+        let task = tokio::spawn(decode(decoder, tx));
+
+        Ok(Self { pipe, task: Some(task) })
+    }
+
+    /// Size of the data that will be flashed from the dump at `path`: the decoded size,
+    /// or the expanded size if the dump holds a sparse image.
+    ///
+    /// Compressed formats don't reliably store it, so the whole dump is decoded once.
+    /// That also makes sure the dump is intact before anything is written.
+    pub async fn flashed_size(path: &Path, format: DumpFormat) -> Result<u64> {
+        let file = std::fs::File::open(path)?;
+
+        spawn_blocking(move || {
+            let mut decoder = decoder(file, format)?;
+
+            let mut header = [0u8; SPARSE_HEADER_SIZE];
+            let mut len = 0;
+            while len < header.len() {
+                match decoder.read(&mut header[len..])? {
+                    0 => break,
+                    n => len += n,
+                }
+            }
+            if let Some(size) = expanded_size(&header[..len]) {
+                return Ok(size);
+            }
+
+            let rest = std::io::copy(&mut decoder, &mut std::io::sink())?;
+            Ok(len as u64 + rest)
+        })
+        .await
+        .map_err(|e| anyhow!("Decoder task failed: {}", e))?
+    }
+}
+
+impl AsyncRead for DumpReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        match Pin::new(&mut self.pipe).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) if buf.filled().len() == before => {}
+            poll => return poll,
+        }
+
+        // The pipe is closed, only report the end of the data once the decoder succeeded
+        let Some(task) = self.task.as_mut() else {
+            return Poll::Ready(Ok(()));
+        };
+        let result = match Pin::new(task).poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.task = None;
+
+        match result {
+            Ok(Ok(())) => Poll::Ready(Ok(())),
+            Ok(Err(e)) => Poll::Ready(Err(std::io::Error::other(e))),
+            Err(e) => Poll::Ready(Err(std::io::Error::other(e))),
+        }
+    }
+}
+
+fn decoder(file: std::fs::File, format: DumpFormat) -> Result<Box<dyn Read + Send>> {
+    let file = std::io::BufReader::new(file);
+    match format {
+        DumpFormat::Zstd => Ok(Box::new(zstd::stream::read::Decoder::with_buffer(file)?)),
+        DumpFormat::Gzip => Ok(Box::new(MultiGzDecoder::new(file))),
+        _ => Err(anyhow!("{:?} dumps are not compressed", format)),
+    }
+}
+
+async fn decode(mut decoder: Box<dyn Read + Send>, mut tx: DuplexStream) -> Result<()> {
+    let mut buf = vec![0u8; PIPE_SIZE];
+
+    loop {
+        let n = block_in_place(|| decoder.read(&mut buf))?;
+        if n == 0 {
+            break;
+        }
+        tx.write_all(&buf[..n]).await?;
+    }
+
+    tx.shutdown().await?;
+    Ok(())
+}
+
+async fn read_some<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> Result<Option<usize>> {
+    match reader.read(buf).await? {
+        0 => Ok(None),
+        n => Ok(Some(n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            Scratch(std::env::temp_dir().join(format!(
+                "antumbra-dump-{}-{}",
+                std::process::id(),
+                name
+            )))
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            std::fs::remove_file(&self.0).ok();
+        }
+    }
+
+    /// A few pipe lengths of data, random blocks between zeroed ones.
+    fn partition() -> Vec<u8> {
+        let mut rng = StdRng::seed_from_u64(0x1507);
+        let mut data = vec![0u8; 3 * PIPE_SIZE + 4096];
+        for block in data.chunks_mut(64 * 1024).step_by(2) {
+            rng.fill(block);
+        }
+        data
+    }
+
+    async fn dump(path: &Path, format: DumpFormat, data: &[u8]) {
+        let mut writer = DumpWriter::create(path, format, data.len() as u64).await.unwrap();
+        writer.write_all(data).await.unwrap();
+        writer.finish().await.unwrap();
+    }
+
+    async fn undump(path: &Path, format: DumpFormat) -> std::io::Result<Vec<u8>> {
+        let file = std::fs::File::open(path).unwrap();
+        let mut reader = DumpReader::new(file, format).unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn compressed_dumps_round_trip() {
+        let data = partition();
+        for format in [DumpFormat::Gzip, DumpFormat::Zstd] {
+            let file = Scratch::new(&format!("boot.{}", format.extension()));
+            dump(&file.0, format, &data).await;
+
+            assert_eq!(DumpFormat::from_path(&file.0), format);
+            assert!(std::fs::metadata(&file.0).unwrap().len() < data.len() as u64);
+            assert_eq!(DumpReader::flashed_size(&file.0, format).await.unwrap(), data.len() as u64);
+            assert!(undump(&file.0, format).await.unwrap() == data, "{:?} differs", format);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn compressed_sparse_images_report_the_expanded_size() {
+        let data = partition();
+        let sparse = Scratch::new("super.img");
+        dump(&sparse.0, DumpFormat::Sparse, &data).await;
+        let sparse = std::fs::read(&sparse.0).unwrap();
+
+        // Compressing an existing image, rather than dumping in a compressed format
+        for format in [DumpFormat::Gzip, DumpFormat::Zstd] {
+            let file = Scratch::new(&format!("super.img.{}", format.extension()));
+            dump(&file.0, format, &sparse).await;
+
+            assert_eq!(DumpFormat::from_path(&file.0), format);
+            assert_eq!(DumpReader::flashed_size(&file.0, format).await.unwrap(), data.len() as u64);
+            assert!(undump(&file.0, format).await.unwrap() == sparse, "{:?} differs", format);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn truncated_dumps_fail() {
+        let data = partition();
+        for format in [DumpFormat::Gzip, DumpFormat::Zstd] {
+            let file = Scratch::new(&format!("cut.{}", format.extension()));
+            dump(&file.0, format, &data).await;
+            let len = std::fs::metadata(&file.0).unwrap().len();
+            std::fs::File::options().write(true).open(&file.0).unwrap().set_len(len / 2).unwrap();
+
+            assert!(DumpReader::flashed_size(&file.0, format).await.is_err(), "{:?}", format);
+            assert!(undump(&file.0, format).await.is_err(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn formats_are_guessed_from_the_name() {
+        for (name, format) in [
+            ("boot.bin", DumpFormat::Raw),
+            ("boot", DumpFormat::Raw),
+            ("super.img", DumpFormat::Sparse),
+            ("boot.bin.zst", DumpFormat::Zstd),
+            ("boot.img.zst", DumpFormat::Zstd),
+            ("boot.bin.gz", DumpFormat::Gzip),
+            ("boot.img.gz", DumpFormat::Gzip),
+            ("boot.gz.bin", DumpFormat::Raw),
+        ] {
+            assert_eq!(DumpFormat::from_path(Path::new(name)), format, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{read, rename, write};
+
+/// A single dumped partition.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestEntry {
+    pub name: String,
+    /// Output file name, relative to the manifest
+    pub file: String,
+    pub address: u64,
+    pub size: u64,
+    /// SHA-256 of the raw partition data, before any dump encoding
+    pub sha256: Option<String>,
+    pub complete: bool,
+    /// Ranges that were skipped and zero-filled, with `--skip-bad-blocks`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bad_ranges: Vec<ManifestBadRange>,
+}
+
+/// A skipped range, with its absolute device offset.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestBadRange {
+    pub offset: u64,
+    pub length: u64,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub async fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_slice(&read(path).await?)?)
+    }
+
+    /// Writes the manifest, replacing the file atomically.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("json.tmp");
+        write(&tmp, serde_json::to_vec_pretty(self)?).await?;
+        rename(&tmp, path).await?;
+        Ok(())
+    }
+}
+
+/// Writes a dump manifest, rewriting it after every entry.
+///
+/// This way an interrupted dump still leaves a valid manifest behind, listing exactly
+/// the partitions that were completed. The file is replaced atomically, so a crash
+/// mid-write never leaves a truncated manifest.
+pub struct ManifestWriter {
+    path: PathBuf,
+    manifest: Manifest,
+}
+
+impl ManifestWriter {
+    pub const FILE_NAME: &'static str = "manifest.json";
+
+    /// Creates an empty manifest at `path`.
+    pub async fn create(path: &Path) -> Result<Self> {
+        let writer = ManifestWriter { path: path.to_path_buf(), manifest: Manifest::default() };
+        writer.save().await?;
+        Ok(writer)
+    }
+
+    /// Appends an entry and persists the manifest.
+    pub async fn push(&mut self, entry: ManifestEntry) -> Result<()> {
+        self.manifest.entries.push(entry);
+        self.save().await
+    }
+
+    async fn save(&self) -> Result<()> {
+        self.manifest.save(&self.path).await
+    }
+}
@@ -1,3 +1,11 @@
+mod digest;
+mod dump;
+mod manifest;
 mod progress_bar;
+mod stdio;
 
-pub use progress_bar::AntumbraProgress;
+pub use digest::{dump_sha256, file_sha256};
+pub use dump::{DumpFormat, DumpReader, DumpWriter};
+pub use manifest::{Manifest, ManifestBadRange, ManifestEntry, ManifestWriter};
+pub use progress_bar::{AntumbraProgress, suspend_progress};
+pub use stdio::{is_stdio, padded};
@@ -1,45 +1,361 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
 use colored::Colorize;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{
+    HumanBytes,
+    HumanDuration,
+    MultiProgress,
+    ProgressBar,
+    ProgressDrawTarget,
+    ProgressStyle,
+};
+use penumbra::ProgressEvent;
+use penumbra::core::summary::OperationKind;
 
+use crate::cli::events::{Event, emit, json_enabled};
 use crate::logger::{INFO_SYMBOL, LOGGER_PREIX};
 
+/// Minimum time between two JSON progress events of a bar.
+const JSON_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Every progress bar is drawn through this, so that logs can hide them while printing
+static PROGRESS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+/// Runs `f` with all progress bars hidden, so that its output doesn't mix with them.
+pub fn suspend_progress<R>(f: impl FnOnce() -> R) -> R {
+    PROGRESS.suspend(f)
+}
+
 /// A wrapper around indicatif ProgressBar
 /// With custom styling from the logger
+///
+/// Finishing is idempotent, so retries reaching the end twice print a single line.
+/// A bar dropped before finishing (e.g. an error propagated with `?`) is abandoned,
+/// leaving the terminal clean.
+///
+/// The rate and ETA come from the core progress events when fed with [`Self::event`],
+/// and from the bar's own clock otherwise.
+///
+/// With `--json`, the bar is hidden and reports JSON events instead.
 pub struct AntumbraProgress {
     pb: ProgressBar,
     #[allow(dead_code)]
     prefix: String,
+    finished: AtomicBool,
+    start: Instant,
+    /// Bytes already done before the events started, for resumed transfers
+    base: u64,
+    /// Partition named in JSON events
+    partition: Option<String>,
+    json: Mutex<JsonProgress>,
+}
+
+/// What the JSON events of a bar reported so far.
+#[derive(Default)]
+struct JsonProgress {
+    op: Option<&'static str>,
+    started: bool,
+    last: Option<Instant>,
+    done: u64,
+}
+
+fn op_name(kind: OperationKind) -> &'static str {
+    match kind {
+        OperationKind::Read => "read",
+        OperationKind::Write => "write",
+        OperationKind::Erase => "erase",
+        OperationKind::Verify => "verify",
+    }
 }
 
 impl AntumbraProgress {
     pub fn new(total_size: u64) -> Self {
         let prefix = format!("{} {}", LOGGER_PREIX.bold().purple(), INFO_SYMBOL.purple());
 
-        let pb = ProgressBar::new(total_size);
+        let pb = if json_enabled() {
+            ProgressBar::with_draw_target(Some(total_size), ProgressDrawTarget::hidden())
+        } else {
+            PROGRESS.add(ProgressBar::new(total_size))
+        };
         pb.set_style(
-            ProgressStyle::with_template(
-                &format!(
-                     "{}  [{{bar:40.white/red}}] {{bytes}}/{{total_bytes}} ({{elapsed}} / ETA: {{eta}}, {{bytes_per_sec}}) {{msg}}",
-                     prefix
-                 )
-            )
+            ProgressStyle::with_template(&format!(
+                "{}  [{{bar:40.white/red}}] {{bytes}}/{{total_bytes}} ({{prefix}}) {{msg}}",
+                prefix
+            ))
             .unwrap()
             .progress_chars("##-"),
         );
 
-        Self { pb, prefix }
+        Self {
+            pb,
+            prefix,
+            finished: AtomicBool::new(false),
+            start: Instant::now(),
+            base: 0,
+            partition: None,
+            json: Mutex::new(JsonProgress::default()),
+        }
+    }
+
+    /// Names the partition this bar is about in JSON events.
+    pub fn for_partition(mut self, name: &str) -> Self {
+        self.partition = Some(name.to_string());
+        self
+    }
+
+    /// A bar for a transfer resumed at `start`, whose events count from there.
+    pub fn resumed(total_size: u64, start: u64) -> Self {
+        let mut progress = Self::new(total_size);
+        progress.base = start;
+        progress.pb.set_position(start);
+        progress
     }
 
     pub fn update(&self, written: u64, msg: &str) {
+        let elapsed = self.start.elapsed();
+        let secs = elapsed.as_secs_f64();
+        let rate = if secs > 0.0 { written as f64 / secs } else { 0.0 };
+        let left = self.pb.length().unwrap_or(0).saturating_sub(written) as f64;
+        let eta = (rate > 0.0).then(|| Duration::from_secs_f64(left / rate));
+
+        self.show(written, elapsed, rate, eta, msg);
+    }
+
+    /// Updates the bar from a core progress event.
+    pub fn event(&self, event: &ProgressEvent, msg: &str) {
+        self.json().op.get_or_insert(op_name(event.phase));
+        let written = self.base + event.bytes_done;
+        self.show(written, event.elapsed, event.throughput(), event.eta(), msg);
+    }
+
+    fn show(&self, written: u64, elapsed: Duration, rate: f64, eta: Option<Duration>, msg: &str) {
+        if self.finished.load(Ordering::Acquire) {
+            return;
+        }
+
+        let eta = eta.map_or_else(|| String::from("-"), |eta| HumanDuration(eta).to_string());
+        self.pb.set_prefix(format!(
+            "{} / ETA: {}, {}/s",
+            HumanDuration(elapsed),
+            eta,
+            HumanBytes(rate as u64)
+        ));
         self.pb.set_position(written);
         self.pb.set_message(msg.to_string());
+        self.json_progress(written);
     }
 
     pub fn finish(&self, msg: &str) {
-        self.pb.finish_with_message(msg.to_string());
+        if !self.finished.swap(true, Ordering::AcqRel) {
+            self.pb.finish_with_message(msg.to_string());
+            self.json_end(true);
+        }
     }
 
     pub fn abandon(&self, msg: &str) {
-        self.pb.abandon_with_message(msg.to_string());
+        if !self.finished.swap(true, Ordering::AcqRel) {
+            self.pb.abandon_with_message(msg.to_string());
+            self.json_end(false);
+        }
+    }
+
+    fn json(&self) -> std::sync::MutexGuard<'_, JsonProgress> {
+        self.json.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn json_progress(&self, done: u64) {
+        if !json_enabled() {
+            return;
+        }
+
+        let total = self.pb.length().unwrap_or(0);
+        let partition = self.partition.as_deref();
+        let mut json = self.json();
+        let op = json.op;
+
+        if !json.started {
+            json.started = true;
+            emit(&Event::OperationStart { op, partition, total });
+        }
+
+        // Throttled, as core reports every packet
+        let due = json.last.is_none_or(|last| last.elapsed() >= JSON_PROGRESS_INTERVAL);
+        if due || done >= total {
+            json.last = Some(Instant::now());
+            emit(&Event::Progress { op, partition, done, total });
+        }
+        json.done = done;
+    }
+
+    fn json_end(&self, ok: bool) {
+        if !json_enabled() {
+            return;
+        }
+
+        let json = self.json();
+        let partition = self.partition.as_deref();
+        if !json.started {
+            let total = self.pb.length().unwrap_or(0);
+            emit(&Event::OperationStart { op: json.op, partition, total });
+        }
+        emit(&Event::OperationEnd { op: json.op, partition, ok, done: json.done });
+    }
+}
+
+impl Drop for AntumbraProgress {
+    fn drop(&mut self) {
+        if !self.finished.swap(true, Ordering::AcqRel) {
+            self.pb.abandon();
+            self.json_end(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use std::{io, thread};
+
+    use indicatif::TermLike;
+
+    use super::*;
+
+    const BARS: usize = 4;
+    const LOGGERS: usize = 4;
+    const LINES: usize = 200;
+    /// Spreads updates and logs over enough redraws for them to meet.
+    const PACE: Duration = Duration::from_micros(200);
+
+    /// Terminal keeping what's left on screen, so that a bar drawn over a log line or a
+    /// log line written in the middle of a bar shows up in the result.
+    #[derive(Debug, Default, Clone)]
+    struct Screen(Arc<Mutex<ScreenState>>);
+
+    #[derive(Debug, Default)]
+    struct ScreenState {
+        lines: Vec<String>,
+        row: usize,
+    }
+
+    impl ScreenState {
+        fn line(&mut self) -> &mut String {
+            if self.lines.len() <= self.row {
+                self.lines.resize(self.row + 1, String::new());
+            }
+            &mut self.lines[self.row]
+        }
+    }
+
+    impl Screen {
+        fn state(&self) -> std::sync::MutexGuard<'_, ScreenState> {
+            self.0.lock().unwrap()
+        }
+
+        fn lines(&self) -> Vec<String> {
+            self.state().lines.iter().filter(|line| !line.is_empty()).cloned().collect()
+        }
+    }
+
+    impl TermLike for Screen {
+        fn width(&self) -> u16 {
+            120
+        }
+
+        fn move_cursor_up(&self, n: usize) -> io::Result<()> {
+            let mut state = self.state();
+            state.row = state.row.saturating_sub(n);
+            Ok(())
+        }
+
+        fn move_cursor_down(&self, n: usize) -> io::Result<()> {
+            self.state().row += n;
+            Ok(())
+        }
+
+        fn move_cursor_right(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn move_cursor_left(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn write_line(&self, s: &str) -> io::Result<()> {
+            let mut state = self.state();
+            state.line().push_str(s);
+            state.row += 1;
+            Ok(())
+        }
+
+        fn write_str(&self, s: &str) -> io::Result<()> {
+            self.state().line().push_str(s);
+            Ok(())
+        }
+
+        fn clear_line(&self) -> io::Result<()> {
+            self.state().line().clear();
+            Ok(())
+        }
+
+        fn flush(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn logs_and_bars_do_not_interleave() {
+        let screen = Screen::default();
+        PROGRESS
+            .set_draw_target(ProgressDrawTarget::term_like_with_hz(Box::new(screen.clone()), 200));
+
+        let bars: Vec<AntumbraProgress> =
+            (0..BARS).map(|_| AntumbraProgress::new(LINES as u64)).collect();
+
+        thread::scope(|s| {
+            for (i, bar) in bars.iter().enumerate() {
+                s.spawn(move || {
+                    for written in 0..=LINES as u64 {
+                        bar.update(written, "writing");
+                        thread::sleep(PACE);
+                    }
+                    // A retry reaching the end again
+                    bar.finish(&format!("bar {} done", i));
+                    bar.finish(&format!("bar {} done", i));
+                });
+            }
+
+            for logger in 0..LOGGERS {
+                let screen = screen.clone();
+                s.spawn(move || {
+                    for line in 0..LINES {
+                        let log = format!("log {}-{}", logger, line);
+                        suspend_progress(|| screen.write_line(&log)).unwrap();
+                        thread::sleep(PACE);
+                    }
+                });
+            }
+        });
+
+        PROGRESS.set_draw_target(ProgressDrawTarget::hidden());
+        let lines = screen.lines();
+
+        for logger in 0..LOGGERS {
+            for line in 0..LINES {
+                let log = format!("log {}-{}", logger, line);
+                let count = lines.iter().filter(|l| **l == log).count();
+                assert_eq!(count, 1, "{:?} is missing or duplicated", log);
+            }
+        }
+        let garbled: Vec<&String> =
+            lines.iter().filter(|l| l.contains("log ") && !l.starts_with("log ")).collect();
+        assert!(garbled.is_empty(), "log lines mixed with bars: {:?}", garbled);
+
+        for i in 0..BARS {
+            let done = format!("bar {} done", i);
+            assert_eq!(lines.iter().filter(|l| l.contains(&done)).count(), 1, "{:?}", lines);
+        }
     }
 }
@@ -0,0 +1,24 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::Path;
+
+use tokio::io::{AsyncRead, AsyncReadExt, repeat};
+
+/// File argument meaning stdin for writes and stdout for reads
+pub const STDIO_PATH: &str = "-";
+
+/// Whether `path` is `-`, meaning the data should be piped through stdin/stdout.
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO_PATH
+}
+
+/// Returns a reader yielding exactly `size` bytes of `reader`, zero-padded if it's shorter.
+///
+/// Protocols need the size before the transfer starts, which isn't known for stdin,
+/// and a short input would otherwise fail halfway through the write.
+/// Only what the protocol asks for is buffered.
+pub fn padded<R: AsyncRead + Unpin + Send>(reader: R, size: u64) -> impl AsyncRead + Unpin + Send {
+    reader.take(size).chain(repeat(0)).take(size)
+}
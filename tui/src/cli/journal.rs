@@ -0,0 +1,452 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use penumbra::core::devinfo::DeviceInfo;
+use penumbra::core::summary::OperationSummary;
+use penumbra::{Device, ProgressEvent};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs::{File, OpenOptions, create_dir_all, metadata, read, remove_file, write};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::task::{JoinHandle, spawn_blocking};
+
+use crate::cli::common::{CONN_DA, StableInput};
+use crate::cli::helpers::AntumbraProgress;
+use crate::cli::state::PersistedDeviceState;
+use crate::config::journal_path;
+
+/// How often the reached offset is persisted while writing
+const PROGRESS_STEP: u64 = 16 * 1024 * 1024;
+
+/// The command that started a write, since the DA path differs between them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteKind {
+    /// Name based write, see `Device::download`
+    Download,
+    /// GPT offset based write, see `Device::write_partition`
+    Write,
+}
+
+/// A destructive write that was started but never confirmed as complete.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingWrite {
+    pub kind: WriteKind,
+    pub partition: String,
+    pub file: PathBuf,
+    /// Size of the data flashed, once decompressed and expanded
+    pub size: u64,
+    /// SHA-256 of the image, used to make sure it didn't change before resuming
+    pub sha256: String,
+    /// Last offset known to be written
+    pub offset: u64,
+    /// Device the write was started on, missing in entries that predate it
+    #[serde(default)]
+    pub device: Option<DeviceIdentity>,
+}
+
+/// What tells devices apart across sessions. The DA random ID isn't part of it,
+/// as it changes whenever the DA is booted again, which a resume always needs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    pub hw_code: u16,
+    /// Hex encoded, when the BROM reported it
+    pub soc_id: Option<String>,
+    /// Hex encoded, when the BROM reported it
+    pub meid: Option<String>,
+}
+
+impl DeviceIdentity {
+    pub async fn of(info: &DeviceInfo) -> Self {
+        DeviceIdentity {
+            hw_code: info.hw_code().await,
+            soc_id: info.soc_id().await.map(hex::encode),
+            meid: info.meid().await.map(hex::encode),
+        }
+    }
+
+    /// Whether `other` can be the same device: same chipset, and no ID differing.
+    /// IDs only one of them knows aren't held against it.
+    fn matches(&self, other: &DeviceIdentity) -> bool {
+        let same = |a: &Option<String>, b: &Option<String>| match (a, b) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        };
+        self.hw_code == other.hw_code
+            && same(&self.soc_id, &other.soc_id)
+            && same(&self.meid, &other.meid)
+    }
+}
+
+impl std::fmt::Display for DeviceIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "hw code 0x{:04X}", self.hw_code)?;
+        if let Some(soc_id) = &self.soc_id {
+            write!(f, ", SoC ID {}", soc_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Operation journal, kept in its own file so that it survives state resets.
+///
+/// A `PendingWrite` is recorded before any destructive write and removed once
+/// the write succeeds. If one is still there on the next run, the write was interrupted
+/// and the partition is likely half-written.
+pub struct Journal {
+    pending: PendingWrite,
+    saved_offset: u64,
+    /// Last progress save, still running on the blocking pool
+    saving: Option<JoinHandle<()>>,
+}
+
+impl Journal {
+    const HISTORY_FILE: &'static str = ".antumbra_history";
+    /// Where the journal goes when there's no configuration directory
+    const JOURNAL_FILE: &'static str = ".antumbra_journal";
+
+    fn path() -> PathBuf {
+        journal_path().unwrap_or_else(|| PathBuf::from(Self::JOURNAL_FILE))
+    }
+
+    /// Returns the interrupted write left by a previous run, if any.
+    pub async fn pending() -> Option<PendingWrite> {
+        let json = read(Self::path()).await.ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    /// Records the start of a write of `file` to `partition` of the device described by
+    /// `info`, `size` being the size of the data flashed from it.
+    pub async fn begin(
+        kind: WriteKind,
+        partition: &str,
+        file: &Path,
+        size: u64,
+        info: &DeviceInfo,
+    ) -> Result<Self> {
+        let device = DeviceIdentity::of(info).await;
+        let pending = PendingWrite::new(kind, partition, file, size, device).await?;
+
+        let journal = Journal { pending, saved_offset: 0, saving: None };
+        journal.save().await?;
+        Ok(journal)
+    }
+
+    /// Records the offset reached so far.
+    /// Called from progress callbacks, so it's synchronous: the file is written on the
+    /// blocking pool, every few MiB. While a save is still running, newer offsets wait
+    /// for the next call, so that saves never land out of order.
+    pub fn progress(&mut self, offset: u64) {
+        if offset < self.saved_offset + PROGRESS_STEP
+            || self.saving.as_ref().is_some_and(|saving| !saving.is_finished())
+        {
+            return;
+        }
+
+        self.pending.offset = offset;
+        self.saved_offset = offset;
+        if let Ok(json) = serde_json::to_vec_pretty(&self.pending) {
+            self.saving = Some(spawn_blocking(move || {
+                std::fs::write(Self::path(), json).ok();
+            }));
+        }
+    }
+
+    /// Marks the write as completed, removing the journal entry.
+    pub async fn complete(mut self) -> Result<()> {
+        // A save finishing after the removal would bring the entry back
+        if let Some(saving) = self.saving.take() {
+            saving.await.ok();
+        }
+        Self::clear().await
+    }
+
+    /// Removes any journal entry.
+    pub async fn clear() -> Result<()> {
+        let path = Self::path();
+        if metadata(&path).await.is_ok() {
+            remove_file(path).await?;
+        }
+        Ok(())
+    }
+
+    /// Appends the summary of a finished operation to the history file.
+    pub async fn record_summary(command: &str, summary: &OperationSummary) -> Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+        let mut file =
+            OpenOptions::new().create(true).append(true).open(Self::HISTORY_FILE).await?;
+        file.write_all(format!("{} {} {}\n", timestamp, command, summary).as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn save(&self) -> Result<()> {
+        let json = serde_json::to_vec_pretty(&self.pending)?;
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir).await?;
+        }
+        write(path, json).await.map_err(|e| anyhow!("Failed to write journal file: {}", e))?;
+        Ok(())
+    }
+}
+
+impl PendingWrite {
+    async fn new(
+        kind: WriteKind,
+        partition: &str,
+        file: &Path,
+        size: u64,
+        device: DeviceIdentity,
+    ) -> Result<Self> {
+        let file = tokio::fs::canonicalize(file).await?;
+        let sha256 = hash_file(&file).await?;
+
+        Ok(PendingWrite {
+            kind,
+            partition: partition.to_string(),
+            file,
+            size,
+            sha256,
+            offset: 0,
+            device: Some(device),
+        })
+    }
+
+    /// Fails unless the device described by `info` is the one the write was started on.
+    async fn check_device(&self, info: &DeviceInfo) -> Result<()> {
+        let Some(recorded) = &self.device else {
+            return Err(anyhow!(
+                "The interrupted write doesn't record its device, refusing to resume."
+            ));
+        };
+
+        let current = DeviceIdentity::of(info).await;
+        if !recorded.matches(&current) {
+            return Err(anyhow!(
+                "The interrupted write was started on another device ({}, this one has {}), \
+                 refusing to resume.",
+                recorded,
+                current
+            ));
+        }
+        Ok(())
+    }
+
+    /// Opens the image again, the way it was flashed: decompressed if needed.
+    /// Fails if it changed since the interrupted write.
+    async fn open_image(&self) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+        if hash_file(&self.file).await? != self.sha256 {
+            return Err(anyhow!(
+                "Image '{}' changed since the interrupted write, refusing to resume.",
+                self.file.display()
+            ));
+        }
+
+        let (reader, _) = StableInput::open_image(&self.file, None).await?;
+        Ok(reader)
+    }
+}
+
+/// Warns about an interrupted write, so that users know which partition needs reflashing.
+pub fn warn_interrupted(pending: &PendingWrite) {
+    warn!("=====================================");
+    warn!("A previous write to '{}' was interrupted!", pending.partition);
+    warn!("Reached offset: 0x{:X} of 0x{:X} bytes", pending.offset, pending.size);
+    warn!("Image: {}", pending.file.display());
+    if let Some(device) = &pending.device {
+        warn!("Device: {}", device);
+    }
+    warn!("The partition is likely corrupted, and the device might not boot.");
+    warn!("Re-run with --resume-interrupted to reflash it before the requested command.");
+    warn!("=====================================");
+}
+
+/// Reflashes the partition of an interrupted write from its original image.
+pub async fn resume_interrupted(
+    dev: &mut Device,
+    state: &mut PersistedDeviceState,
+    pending: &PendingWrite,
+) -> Result<()> {
+    let mut reader = pending.open_image().await?;
+
+    dev.enter_da_mode().await?;
+
+    state.connection_type = CONN_DA;
+    state.flash_mode = 1;
+
+    pending.check_device(&dev.dev_info).await?;
+
+    info!("Reflashing partition '{}' from '{}'...", pending.partition, pending.file.display());
+
+    let pb = AntumbraProgress::new(pending.size);
+
+    let mut progress_callback = {
+        let pb = &pb;
+        move |event: ProgressEvent| {
+            pb.event(&event, "Resuming...");
+
+            if event.is_done() {
+                pb.finish("Resume complete!");
+            }
+        }
+    };
+
+    let result = match pending.kind {
+        WriteKind::Download => {
+            dev.download(
+                &pending.partition,
+                pending.size as usize,
+                &mut reader,
+                &mut progress_callback,
+            )
+            .await
+        }
+        WriteKind::Write => {
+            dev.write_partition(&pending.partition, &mut reader, true, &mut progress_callback).await
+        }
+    };
+
+    if let Err(e) = result {
+        pb.abandon("Resume failed!");
+        return Err(e)?;
+    }
+
+    Journal::clear().await?;
+    info!("Partition '{}' restored.", pending.partition);
+
+    Ok(())
+}
+
+async fn hash_file(path: &Path) -> Result<String> {
+    let mut reader = BufReader::new(File::open(path).await?);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use penumbra::core::devinfo::DevInfoData;
+
+    use super::*;
+    use crate::cli::helpers::{DumpFormat, DumpWriter};
+
+    /// Compressed image, removed once dropped.
+    struct Image(PathBuf);
+
+    impl Image {
+        async fn new(name: &str, data: &[u8]) -> Self {
+            let image = Image(std::env::temp_dir().join(format!(
+                "antumbra-journal-{}-{}",
+                std::process::id(),
+                name
+            )));
+            image.write(data).await;
+            image
+        }
+
+        async fn write(&self, data: &[u8]) {
+            let format = DumpFormat::from_path(&self.0);
+            let mut writer = DumpWriter::create(&self.0, format, 0).await.unwrap();
+            writer.write_all(data).await.unwrap();
+            writer.finish().await.unwrap();
+        }
+    }
+
+    impl Drop for Image {
+        fn drop(&mut self) {
+            std::fs::remove_file(&self.0).ok();
+        }
+    }
+
+    /// A device known by its hardware code and, if given, its SoC ID.
+    async fn device(hw_code: u16, soc_id: Option<&[u8]>) -> DeviceInfo {
+        let info = DeviceInfo::new();
+        info.set_data(DevInfoData {
+            hw_code,
+            soc_id: soc_id.map(<[u8]>::to_vec),
+            ..Default::default()
+        })
+        .await;
+        info
+    }
+
+    /// The entry as read back from the journal file by the next run.
+    async fn recorded(image: &Image, size: u64) -> PendingWrite {
+        let identity = DeviceIdentity::of(&device(0x0766, Some(&[0x11; 32])).await).await;
+        let pending =
+            PendingWrite::new(WriteKind::Write, "boot_a", &image.0, size, identity).await.unwrap();
+        serde_json::from_slice(&serde_json::to_vec_pretty(&pending).unwrap()).unwrap()
+    }
+
+    fn boot_image() -> Vec<u8> {
+        [&b"ANDROID!"[..], &[0xA5; 0x10000], &[0x00; 0x10000]].concat()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn compressed_images_are_replayed_decompressed() {
+        let data = boot_image();
+        let image = Image::new("boot.img.zst", &data).await;
+        let pending = recorded(&image, data.len() as u64).await;
+
+        let mut replayed = Vec::new();
+        pending.open_image().await.unwrap().read_to_end(&mut replayed).await.unwrap();
+
+        assert_eq!(pending.size, data.len() as u64);
+        assert!(replayed == data, "the replayed image isn't the flashed data");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn changed_images_are_not_replayed() {
+        let data = boot_image();
+        let image = Image::new("changed.img.zst", &data).await;
+        let pending = recorded(&image, data.len() as u64).await;
+
+        let mut changed = data.clone();
+        changed[0x100] ^= 0xFF;
+        image.write(&changed).await;
+
+        let err = pending.open_image().await.err().unwrap();
+        assert!(err.to_string().contains("changed since the interrupted write"), "{}", err);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn only_the_same_device_is_resumed() {
+        let image = Image::new("device.bin", &boot_image()).await;
+        let pending = recorded(&image, 0).await;
+
+        pending.check_device(&device(0x0766, Some(&[0x11; 32])).await).await.unwrap();
+        // A device without a SoC ID can't be told apart by it
+        pending.check_device(&device(0x0766, None).await).await.unwrap();
+
+        let err = pending.check_device(&device(0x0766, Some(&[0x22; 32])).await).await.unwrap_err();
+        assert!(err.to_string().contains("another device"), "{}", err);
+        assert!(pending.check_device(&device(0x0788, None).await).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn entries_without_a_device_are_not_resumed() {
+        let image = Image::new("anonymous.bin", &boot_image()).await;
+        let mut pending = recorded(&image, 0).await;
+        pending.device = None;
+
+        assert!(pending.check_device(&device(0x0766, None).await).await.is_err());
+    }
+}
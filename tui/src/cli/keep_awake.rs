@@ -0,0 +1,214 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use log::{debug, warn};
+
+/// Keeps the host from sleeping for as long as it's alive.
+///
+/// A suspend in the middle of an operation kills the USB session, so long transfers
+/// should hold one of these. Where the platform doesn't allow inhibiting sleep, this
+/// is a no-op and the operation runs as usual.
+pub struct KeepAwake {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    inhibitor: Option<std::process::Child>,
+    /// Thread holding the execution state, which Windows ties to the thread that set it.
+    /// Dropping the sender lets it reset the state and exit.
+    #[cfg(windows)]
+    holder: Option<(std::sync::mpsc::Sender<()>, std::thread::JoinHandle<()>)>,
+}
+
+impl KeepAwake {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub fn acquire() -> Self {
+        Self::spawn(inhibitor_command())
+    }
+
+    /// Runs `cmd`, which inhibits sleep until it's killed.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn spawn(mut cmd: std::process::Command) -> Self {
+        use std::process::Stdio;
+
+        let inhibitor =
+            cmd.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn();
+        match inhibitor {
+            Ok(child) => {
+                debug!("Inhibiting system sleep");
+                Self { inhibitor: Some(child) }
+            }
+            Err(e) => {
+                warn!("Could not inhibit system sleep: {}", e);
+                Self { inhibitor: None }
+            }
+        }
+    }
+
+    /// The execution state is set and reset on a thread of its own, as the caller
+    /// is an async task that may hop between threads at every await.
+    #[cfg(windows)]
+    pub fn acquire() -> Self {
+        use std::sync::mpsc;
+        use std::thread;
+
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (set_tx, set_rx) = mpsc::channel();
+        let spawned = thread::Builder::new().name("keep-awake".into()).spawn(move || {
+            let set =
+                windows::set_execution_state(windows::ES_CONTINUOUS | windows::ES_SYSTEM_REQUIRED);
+            set_tx.send(set.is_some()).ok();
+            // Returns once the sender is dropped
+            release_rx.recv().ok();
+            windows::set_execution_state(windows::ES_CONTINUOUS);
+        });
+
+        let holder = match spawned {
+            Ok(handle) => {
+                if set_rx.recv().unwrap_or(false) {
+                    debug!("Inhibiting system sleep");
+                } else {
+                    warn!("Could not inhibit system sleep");
+                }
+                Some((release_tx, handle))
+            }
+            Err(e) => {
+                warn!("Could not inhibit system sleep: {}", e);
+                None
+            }
+        };
+        Self { holder }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    pub fn acquire() -> Self {
+        warn!("Inhibiting system sleep is not supported on this platform");
+        Self {}
+    }
+}
+
+impl Drop for KeepAwake {
+    fn drop(&mut self) {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        if let Some(mut child) = self.inhibitor.take() {
+            child.kill().ok();
+            child.wait().ok();
+        }
+
+        #[cfg(windows)]
+        if let Some((release_tx, handle)) = self.holder.take() {
+            drop(release_tx);
+            handle.join().ok();
+        }
+    }
+}
+
+/// Command inhibiting sleep for as long as it runs.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn inhibitor_command() -> std::process::Command {
+    use std::process::Command;
+
+    if cfg!(target_os = "macos") {
+        // Exits on its own once we do
+        let mut cmd = Command::new("caffeinate");
+        cmd.args(["-i", "-w", &std::process::id().to_string()]);
+        cmd
+    } else {
+        let mut cmd = Command::new("systemd-inhibit");
+        cmd.args([
+            "--what=sleep:idle",
+            "--who=Antumbra",
+            "--why=Device operation in progress",
+            "--mode=block",
+            "sleep",
+            "infinity",
+        ]);
+        cmd
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    pub const ES_CONTINUOUS: u32 = 0x8000_0000;
+    pub const ES_SYSTEM_REQUIRED: u32 = 0x0000_0001;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetThreadExecutionState(flags: u32) -> u32;
+    }
+
+    /// Sets the execution state of the calling thread, returning the previous one.
+    /// The state belongs to that thread, so it must be reset from the same one.
+    pub fn set_execution_state(flags: u32) -> Option<u32> {
+        // SAFETY: plain Win32 call, taking flags by value and touching no memory of ours
+        match unsafe { SetThreadExecutionState(flags) } {
+            0 => None,
+            previous => Some(previous),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn inhibits_with_systemd() {
+        let cmd = inhibitor_command();
+        assert_eq!(cmd.get_program(), "systemd-inhibit");
+        assert!(cmd.get_args().any(|arg| arg == "--mode=block"));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn inhibits_with_caffeinate_until_we_exit() {
+        let cmd = inhibitor_command();
+        assert_eq!(cmd.get_program(), "caffeinate");
+        let pid = std::process::id().to_string();
+        assert!(cmd.get_args().any(|arg| arg == pid.as_str()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn dropping_stops_the_inhibitor() {
+        let mut cmd = std::process::Command::new("sleep");
+        cmd.arg("infinity");
+        let awake = KeepAwake::spawn(cmd);
+        let pid = awake.inhibitor.as_ref().map(std::process::Child::id).unwrap();
+        assert!(std::path::Path::new(&format!("/proc/{}", pid)).exists());
+
+        drop(awake);
+        // Killed and reaped, so the pid is gone
+        assert!(!std::path::Path::new(&format!("/proc/{}", pid)).exists());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn missing_inhibitor_is_not_fatal() {
+        let awake = KeepAwake::spawn(std::process::Command::new("antumbra-no-such-inhibitor"));
+        assert!(awake.inhibitor.is_none());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn execution_state_round_trips() {
+        use super::windows::{ES_CONTINUOUS, ES_SYSTEM_REQUIRED, set_execution_state};
+
+        std::thread::spawn(|| {
+            set_execution_state(ES_CONTINUOUS | ES_SYSTEM_REQUIRED).unwrap();
+            let previous = set_execution_state(ES_CONTINUOUS).unwrap();
+            assert_eq!(previous, ES_CONTINUOUS | ES_SYSTEM_REQUIRED);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn can_be_released_from_another_thread() {
+        let awake = KeepAwake::acquire();
+        assert!(awake.holder.is_some());
+
+        // Like an async task resumed on another worker before the command ends
+        std::thread::spawn(move || drop(awake)).join().unwrap();
+    }
+}
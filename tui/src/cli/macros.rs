@@ -1,25 +1,51 @@
 #[macro_export]
 macro_rules! mtk_commands {
-    ( $( $variant:ident ($ty:ty) ),+ $(,)? ) => {
+    ( $( $(#[$meta:meta])* $variant:ident ($ty:ty) ),+ $(,)? ) => {
         #[derive(clap::Subcommand, Debug)]
         pub enum Commands {
             $(
+                $(#[$meta])*
                 #[command(
                     aliases = <$ty as $crate::cli::common::CommandMetadata>::aliases(),
                     visible_aliases = <$ty as $crate::cli::common::CommandMetadata>::visible_aliases(),
                     about = <$ty as $crate::cli::common::CommandMetadata>::about(),
                     long_about = <$ty as $crate::cli::common::CommandMetadata>::long_about(),
                     hide = <$ty as $crate::cli::common::CommandMetadata>::hide(),
+                    after_help = <$ty as $crate::cli::common::CommandMetadata>::after_help(),
                 )]
                 $variant($ty),
             )+
         }
 
+        impl Commands {
+            /// Name of the subcommand, as declared
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        Commands::$variant(_) => stringify!($variant),
+                    )+
+                }
+            }
+
+            /// Prerequisites and side effects of the subcommand
+            pub fn flags(&self) -> $crate::cli::common::CommandFlags {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        Commands::$variant(inner) => $crate::cli::MtkCommand::invocation_flags(inner)
+                            .unwrap_or_else(<$ty as $crate::cli::common::CommandMetadata>::flags),
+                    )+
+                }
+            }
+        }
+
         #[async_trait::async_trait]
         impl $crate::cli::MtkCommand for Commands {
             fn da(&self) -> Option<&std::path::PathBuf> {
                 match self {
                     $(
+                        $(#[$meta])*
                         Commands::$variant(inner) => inner.da(),
                     )+
                 }
@@ -28,11 +54,21 @@ macro_rules! mtk_commands {
             fn pl(&self) -> Option<&std::path::PathBuf> {
                 match self {
                     $(
+                        $(#[$meta])*
                         Commands::$variant(inner) => inner.pl(),
                     )+
                 }
             }
 
+            fn writes_stdout(&self) -> bool {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        Commands::$variant(inner) => inner.writes_stdout(),
+                    )+
+                }
+            }
+
             async fn run(
                 &self,
                 dev: &mut penumbra::Device,
@@ -40,6 +76,7 @@ macro_rules! mtk_commands {
             ) -> anyhow::Result<()> {
                 match self {
                     $(
+                        $(#[$meta])*
                         Commands::$variant(inner) => inner.run(dev, state).await,
                     )+
                 }
@@ -4,25 +4,43 @@
 */
 mod commands;
 mod common;
+pub mod events;
 mod helpers;
+mod journal;
+mod keep_awake;
 mod macros;
+#[cfg(feature = "notify")]
+mod notify;
 mod state;
+mod wait;
 
 use std::path::{Path, PathBuf};
-use std::time::{Duration, Instant};
+use std::process::ExitCode;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use clap::{CommandFactory, Parser};
-use log::info;
+use clap_num::maybe_hex;
+use log::{debug, info, warn};
 use penumbra::connection::port::ConnectionType;
 use penumbra::core::devinfo::DevInfoData;
-use penumbra::{Device, DeviceBuilder, find_mtk_port};
-use tokio::fs::read;
+use penumbra::error::ErrorKind;
+#[cfg(feature = "serial")]
+use penumbra::find_mtk_ports;
+use penumbra::{Capabilities, Device, DeviceBuilder, RetryPolicy};
+use tokio::fs::{read, try_exists, write};
 
 use crate::cli::commands::*;
+use crate::cli::common::{CONN_BR, CONN_DA, CommandFlags, confirm_command};
+use crate::cli::events::{Event, emit};
+pub use crate::cli::helpers::suspend_progress;
+use crate::cli::journal::{Journal, resume_interrupted, warn_interrupted};
+use crate::cli::keep_awake::KeepAwake;
 use crate::cli::macros::mtk_commands;
-use crate::cli::state::PersistedDeviceState;
+use crate::cli::state::{DaStage, PersistedDeviceState};
+use crate::cli::wait::{MtkPortFinder, WaitPolicy, wait_for_port};
+use crate::config::repository_da;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -39,25 +57,107 @@ pub struct CliArgs {
     /// The preloader file to use
     #[arg(short, long = "pl", value_name = "PRELOADER_FILE")]
     pub preloader_file: Option<PathBuf>,
+    /// Continue even if the DA session changed since the last command (e.g. a different device)
+    #[arg(long)]
+    pub accept_session_change: bool,
+    /// Reflash the partition of an interrupted write before running the command
+    #[arg(long)]
+    pub resume_interrupted: bool,
+    /// Report progress and status as line-delimited JSON events on stdout,
+    /// with human logs on stderr. Goes before the subcommand, e.g. `antumbra --json read`
+    #[arg(long)]
+    pub json: bool,
+    /// Keep the host from sleeping while the command runs
+    #[arg(long)]
+    pub keep_awake: bool,
+    /// Don't ask for confirmation before commands that modify the device.
+    /// Needed to run them without a terminal, e.g. from scripts
+    #[arg(short, long)]
+    pub yes: bool,
+    /// Reuse the exploit-patched DA saved in this file, and save it there after patching.
+    /// Skips the patching step of the next sessions
+    #[arg(long, value_name = "FILE")]
+    pub save_patched_da: Option<PathBuf>,
+    /// Don't run exploits nor patch the DA: boot the signed DA as is, for devices
+    /// where a patched DA fails
+    #[arg(long)]
+    pub no_patch: bool,
+    /// Refuse DAs loading DA1 or DA2 outside of the known memory of the chipset,
+    /// instead of only warning
+    #[arg(long)]
+    pub strict_da: bool,
+    /// Vendor auth file (e.g. auth_sv5.auth), for devices with SLA or DAA enabled
+    #[arg(long, value_name = "FILE")]
+    pub auth: Option<PathBuf>,
+    /// Tool identification sent to V6 DAs (defaults to Penumbra's own)
+    #[arg(long, value_name = "INFO")]
+    pub host_info: Option<String>,
+    /// Use the device on this port, as listed by `detect`, when several are connected.
+    /// With the serial feature, a serial port not listed there (e.g. a UART header) is
+    /// opened as a BROM console, skipping USB enumeration
+    #[arg(long, value_name = "PORT")]
+    pub port: Option<String>,
+    /// Give up if no device shows up within this many seconds, instead of waiting forever
+    #[arg(long, value_name = "SECONDS")]
+    pub wait_timeout: Option<u64>,
+    /// Baudrate of serial ports, e.g. 460800 for adapters unreliable at 921600.
+    /// On a UART console, it's negotiated with the BROM after the 115200 handshake,
+    /// and used from DA1 on
+    #[arg(long, value_name = "RATE")]
+    pub baud: Option<u32>,
+    /// Retries of a USB transfer failing with a timeout, stall or overflow, 0 to fail right away
+    #[arg(long, value_name = "COUNT")]
+    pub usb_retries: Option<u32>,
+    /// IO base of the SEJ block used for seccfg, for chipsets where the detected one is wrong
+    #[arg(long, value_name = "ADDR", value_parser = maybe_hex::<u32>)]
+    pub sej_base: Option<u32>,
+    /// Send a desktop notification when a long operation finishes
+    #[cfg(feature = "notify")]
+    #[arg(long)]
+    pub notify: bool,
+    /// Only notify operations taking at least this many seconds
+    #[cfg(feature = "notify")]
+    #[arg(long, value_name = "SECONDS")]
+    pub notify_after: Option<u64>,
     /// Subcommands for CLI mode. If provided, TUI mode will be disabled.
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
 mtk_commands! {
+    Detect(DetectArgs),
+    DaInfo(DaInfoArgs),
+    Da(DaRepoArgs),
     Download(DownloadArgs),
+    FlashAll(FlashAllArgs),
     Upload(UploadArgs),
     Format(FormatArgs),
     WriteFlash(WriteArgs),
+    WritePreloader(WritePreloaderArgs),
+    Preloader(PreloaderArgs),
     ReadFlash(ReadArgs),
+    ReadDynamic(ReadDynamicArgs),
     Erase(EraseArgs),
+    WipeFrp(WipeFrpArgs),
+    SafeFormat(SafeFormatArgs),
     ReadAll(ReadAllArgs),
+    RestoreAll(RestoreAllArgs),
+    Manifest(ManifestArgs),
+    HashCheck(HashCheckArgs),
     Seccfg(SeccfgArgs),
-    Pgpt(PgptArgs),
+    #[cfg(feature = "net")]
+    Serve(ServeArgs),
+    Gpt(PgptArgs),
+    StorageInfo(StorageInfoArgs),
+    Efuse(EfuseArgs),
     Peek(PeekArgs),
+    Rpmb(RpmbArgs),
     Shutdown(ShutdownArgs),
     Reboot(RebootArgs),
+    BootMode(BootModeArgs),
     XFlash(XFlashArgs),
+    RawXml(RawXmlArgs),
+    Watch(WatchArgs),
 }
 
 #[async_trait]
@@ -68,71 +168,89 @@ pub trait MtkCommand {
     fn pl(&self) -> Option<&PathBuf> {
         None
     }
+    /// Whether the command writes its data to stdout, so logs must go elsewhere
+    fn writes_stdout(&self) -> bool {
+        false
+    }
+    /// Flags of this invocation, when they differ from the ones of the command
+    /// (e.g. a destructive subcommand of a read-only command)
+    fn invocation_flags(&self) -> Option<CommandFlags> {
+        None
+    }
     async fn run(&self, dev: &mut Device, state: &mut PersistedDeviceState) -> Result<()>;
 }
 
-pub async fn run_cli(args: &CliArgs) -> Result<()> {
-    if args.command.is_none() {
-        CliArgs::command().print_help()?;
-        return Ok(());
+/// Runs the command given on the command line, returning the exit code of the process.
+pub async fn run_cli(args: &CliArgs) -> Result<ExitCode> {
+    // Detection must not start a session, nor touch the persisted state.
+    // Its exit code tells how many devices were found.
+    if let Some(Commands::Detect(detect)) = &args.command {
+        return detect.detect().await;
     }
 
-    let mut state = PersistedDeviceState::load().await;
+    dispatch(args).await.map(|()| ExitCode::SUCCESS)
+}
 
-    let da_data = if let Some(cmd) = &args.command {
-        if let Some(da_path) = cmd.da() {
-            let data = read(da_path).await?;
-            state.da_file_path = Some(da_path.to_string_lossy().to_string());
-            Some(data)
-        } else {
-            None
-        }
-    } else {
-        None
+async fn dispatch(args: &CliArgs) -> Result<()> {
+    let Some(cmd) = &args.command else {
+        CliArgs::command().print_help()?;
+        return Ok(());
     };
 
-    let pl_data = if let Some(cmd) = &args.command {
-        if let Some(pl_path) = cmd.pl() {
-            let data = read(pl_path).await?;
-            Some(data)
-        } else {
-            None
-        }
-    } else {
-        None
-    };
+    if args.json && cmd.writes_stdout() {
+        return Err(anyhow::anyhow!("--json needs stdout for events, write the data to a file."));
+    }
 
-    let mut last_seen = Instant::now();
-    let timeout = Duration::from_millis(500);
+    if let Commands::Manifest(manifest) = cmd {
+        return manifest.execute().await;
+    }
+    if let Commands::DaInfo(dainfo) = cmd {
+        return dainfo.execute().await;
+    }
+    if let Commands::Da(repo) = cmd {
+        return repo.execute().await;
+    }
+    if let Commands::FlashAll(flashall) = cmd
+        && flashall.dry_run
+    {
+        return flashall.execute().await;
+    }
 
-    info!("Waiting for MTK device...");
-    let mtk_port = loop {
-        if let Some(port) = find_mtk_port().await {
-            info!("Found MTK port: {}", port.get_port_name());
-            break port;
-        } else if last_seen.elapsed() > timeout {
-            state.reset().await?;
-            last_seen = Instant::now();
-        }
-    };
+    debug!("Penumbra capabilities: {}", Capabilities::current());
+    if let Commands::Watch(watch) = cmd {
+        return watch.execute(args).await;
+    }
 
-    let mut builder = DeviceBuilder::default()
-        .with_mtk_port(mtk_port)
-        .with_verbose(args.verbose);
+    confirm_command(cmd.name(), cmd.flags(), args.yes)?;
+    run_command(args, cmd).await
+}
 
-    builder = if let Some(da) = da_data {
-        builder.with_da_data(da)
-    } else if let Some(da_path_str) = &state.da_file_path {
-        let da_path = Path::new(da_path_str);
+/// Connects to a device and runs `cmd` on it, resuming the persisted session when possible.
+pub(crate) async fn run_command(args: &CliArgs, cmd: &Commands) -> Result<()> {
+    let mut state = PersistedDeviceState::load().await;
+
+    let interrupted = Journal::pending().await;
+    if let Some(pending) = &interrupted
+        && !args.resume_interrupted
+    {
+        warn_interrupted(pending);
+    }
+
+    let da_data = if let Some(da_path) = cmd.da() {
         let data = read(da_path).await?;
-        builder.with_da_data(data)
+        state.da_file_path = Some(da_path.to_string_lossy().to_string());
+        Some(data)
     } else {
-        builder
+        None
     };
 
-    builder = if let Some(pl) = pl_data { builder.with_preloader(pl) } else { builder };
+    let pl_data = if let Some(pl_path) = cmd.pl() { Some(read(pl_path).await?) } else { None };
+
+    // A suspend kills the USB session, so hold it off until the command is done
+    let _awake = args.keep_awake.then(KeepAwake::acquire);
 
-    let mut dev = builder.build()?;
+    let mut dev = connect(args, &mut state, da_data.clone(), pl_data.clone()).await?;
+    let mut resumed = false;
 
     if state.hw_code != 0 {
         let dev_info = DevInfoData {
@@ -143,14 +261,59 @@ pub async fn run_cli(args: &CliArgs) -> Result<()> {
             storage: None,
             partitions: vec![],
             target_config: state.target_config,
+            raw_pgpt: None,
+            raw_sgpt: None,
+            random_id: state.random_id.clone(),
         };
 
         if state.flash_mode != 0 {
             dev.set_connection_type(ConnectionType::Da)?;
         }
 
-        dev.reinit(dev_info).await?;
-    } else {
+        if state.da_stage == DaStage::Da1 {
+            warn!("The last DA bring-up stopped at DA1, reset the device if it doesn't answer.");
+        }
+
+        match dev.reinit(dev_info).await {
+            Ok(()) => resumed = true,
+            // The persisted state says DA, but the device might have rebooted since.
+            // Start over from a clean port rather than failing every command from now on.
+            Err(e) if state.flash_mode != 0 => {
+                warn!("Could not resume the DA session ({}), reinitializing...", e);
+                state.flash_mode = 0;
+                state.connection_type = CONN_BR;
+                state.save().await?;
+
+                drop(dev);
+                dev = connect(args, &mut state, da_data, pl_data).await?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        // Extensions of the last session are only trusted if they still answer
+        if resumed && state.extensions_active {
+            match dev.resume_extensions().await {
+                Ok(true) => debug!("DA extensions still running, not booting them again."),
+                _ => {
+                    warn!("DA extensions of the last session don't answer, continuing without.");
+                    state.extensions_active = false;
+                    state.save().await?;
+                }
+            }
+        }
+
+        if resumed && dev.session_changed() {
+            if args.accept_session_change {
+                warn!("DA session changed since the last command, continuing as requested.");
+                dev.confirm_session();
+            } else {
+                warn!("DA session changed, destructive commands will be refused.");
+                warn!("If this is expected, re-run with --accept-session-change.");
+            }
+        }
+    }
+
+    if !resumed {
         info!("Initializing device...");
         dev.init().await?;
 
@@ -159,20 +322,227 @@ pub async fn run_cli(args: &CliArgs) -> Result<()> {
         state.hw_code = dev.dev_info.hw_code().await;
         state.target_config = dev.dev_info.target_config().await;
 
+        // Devices found already in DA mode skip the bring-up, record them as such
+        if dev.get_connection()?.connection_type == ConnectionType::Da {
+            state.connection_type = CONN_DA;
+            state.flash_mode = 1;
+            state.random_id = dev.dev_info.random_id().await;
+        }
+
         state.save().await?;
     }
 
+    emit(&Event::DeviceInfo {
+        hw_code: state.hw_code,
+        soc_id: state.soc_id.as_ref().map(hex::encode),
+        meid: state.meid.as_ref().map(hex::encode),
+        sbc: (state.target_config & 0x1) != 0,
+        sla: (state.target_config & 0x2) != 0,
+        daa: (state.target_config & 0x4) != 0,
+    });
+
     info!("=====================================");
     info!("SBC: {}", (state.target_config & 0x1) != 0);
     info!("SLA: {}", (state.target_config & 0x2) != 0);
     info!("DAA: {}", (state.target_config & 0x4) != 0);
     info!("=====================================");
 
-    if let Some(cmd) = &args.command {
-        cmd.run(&mut dev, &mut state).await?;
-        state.target_config = dev.dev_info.target_config().await; // Update just in case after Kamakiri
+    // Without --da, the repository's DA for the chipset is used, and kept for the session
+    if cmd.da().is_none()
+        && state.da_file_path.is_none()
+        && state.flash_mode == 0
+        && cmd.flags().needs_da
+    {
+        let path = repository_da(state.hw_code).await?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No DA given and none in the repository supports 0x{:04X}. \
+                 Pass one with --da, or add it with `antumbra da import <file>`",
+                state.hw_code
+            )
+        })?;
+        info!("Using DA '{}' from the repository for 0x{:04X}", path.display(), state.hw_code);
+
+        dev.set_da_data(read(&path).await?).await?;
+        state.da_file_path = Some(path.to_string_lossy().to_string());
+        state.save().await?;
+    }
+
+    if let Some(pending) = &interrupted
+        && args.resume_interrupted
+    {
+        resume_interrupted(&mut dev, &mut state, pending).await?;
+    }
+
+    // Anything not declared destructive has no business writing to the device
+    dev.set_read_only(!cmd.flags().destructive);
+    let result = cmd.run(&mut dev, &mut state).await.map_err(|e| {
+        match e.downcast_ref::<penumbra::error::Error>() {
+            Some(penumbra::error::Error::AuthRequired(_)) => {
+                e.context("Pass the auth file of the device with --auth <file>")
+            }
+            Some(err) if err.kind() == ErrorKind::Security => {
+                e.context("The device refused the operation, is the bootloader locked?")
+            }
+            _ => e,
+        }
+    });
+
+    if let Some(report) = dev.last_exploit_report() {
+        info!("Exploits: {}", report);
+    }
+
+    state.extensions_active = dev.extensions_active();
+    state.da_stage = if state.flash_mode != 0 {
+        DaStage::Da2
+    } else if dev.last_bringup_report().is_some_and(|r| r.steps.iter().any(|s| s.name == "DA1")) {
+        DaStage::Da1
+    } else {
+        DaStage::Brom
+    };
+
+    if let Some(path) = &args.save_patched_da
+        && let Some(data) = dev.export_patched_da()
+    {
+        match write(path, data).await {
+            Ok(()) => info!("Saved the patched DA to '{}'", path.display()),
+            Err(e) => warn!("Could not save the patched DA to '{}': {}", path.display(), e),
+        }
+    }
+
+    if let Some(stats) = dev.connection_stats().filter(|s| s.errors() > 0) {
+        warn!("USB errors during this command: {}. Check the cable and hub.", stats);
+    }
+
+    if let Some(summary) = dev.last_operation_summary() {
+        info!("{}", summary);
+        Journal::record_summary(cmd.name(), summary).await.ok();
+
+        #[cfg(feature = "notify")]
+        {
+            let config = crate::config::AntumbraConfig::load();
+            if args.notify || config.notify {
+                let threshold = args.notify_after.unwrap_or(config.notify_threshold);
+                notify::notify_completion(
+                    &notify::DesktopNotifier,
+                    cmd.name(),
+                    summary,
+                    Duration::from_secs(threshold),
+                );
+            }
+        }
+    }
+
+    if result.is_err() {
+        // Keep track of a bring-up that stopped halfway
         state.save().await?;
     }
+    result?;
+    state.target_config = dev.dev_info.target_config().await; // Update just in case after Kamakiri
+    state.random_id = dev.dev_info.random_id().await;
+    state.save().await?;
 
     Ok(())
 }
+
+/// Waits for an MTK port and builds a device on it.
+async fn connect(
+    args: &CliArgs,
+    state: &mut PersistedDeviceState,
+    da_data: Option<Vec<u8>>,
+    pl_data: Option<Vec<u8>>,
+) -> Result<Device> {
+    #[cfg(feature = "serial")]
+    let serial_port = match &args.port {
+        Some(path) if is_uart_console(path).await => {
+            info!("Opening serial port {}, power the device on now...", path);
+            Some(penumbra::open_serial_port(path, args.baud.unwrap_or(115_200)).await?)
+        }
+        _ => None,
+    };
+    #[cfg(not(feature = "serial"))]
+    let serial_port = None;
+
+    let mtk_port = match serial_port {
+        Some(port) => port,
+        None => {
+            match &args.port {
+                Some(name) => info!(
+                    "Waiting for MTK device on {} (hold Vol+ / Vol- while plugging in)...",
+                    name
+                ),
+                None => info!("Waiting for MTK device (hold Vol+ / Vol- while plugging in)..."),
+            }
+
+            let policy = WaitPolicy {
+                timeout: args.wait_timeout.map(Duration::from_secs),
+                ..WaitPolicy::default()
+            };
+            let mut finder = MtkPortFinder(args.port.as_deref());
+            let port = wait_for_port(&mut finder, &policy, async || {
+                debug!("No device yet, the last session is gone");
+                state.reset().await
+            })
+            .await?;
+
+            info!("Found MTK port: {}", port.get_port_name());
+            port
+        }
+    };
+
+    // The session belongs to the device it was started on, another one starts from scratch
+    let port_name = mtk_port.get_port_name();
+    if state.port_name.as_ref().is_some_and(|last| *last != port_name) {
+        info!("Not the device of the last session, starting a new one.");
+        let da_file_path = state.da_file_path.take();
+        state.reset().await?;
+        state.da_file_path = da_file_path;
+    }
+    state.port_name = Some(port_name);
+
+    let mut builder = DeviceBuilder::default().with_mtk_port(mtk_port).with_verbose(args.verbose);
+
+    if let Some(info) = &args.host_info {
+        builder = builder.with_host_info(info.clone());
+    }
+    if args.no_patch {
+        builder = builder.with_da_patching(false);
+    }
+    builder = builder.with_strict_da(args.strict_da);
+    if let Some(baud) = args.baud {
+        builder = builder.with_baudrate(baud);
+    }
+    if let Some(max_retries) = args.usb_retries {
+        builder = builder.with_retry_policy(RetryPolicy { max_retries, ..RetryPolicy::default() });
+    }
+    if let Some(base) = args.sej_base {
+        builder = builder.with_sej_base(base);
+    }
+    if let Some(path) = &args.auth {
+        builder = builder.with_auth_data(read(path).await?);
+    }
+
+    builder = if let Some(da) = da_data {
+        builder.with_da_data(da)
+    } else if let Some(da_path_str) = &state.da_file_path {
+        let da_path = Path::new(da_path_str);
+        let data = read(da_path).await?;
+        builder.with_da_data(data)
+    } else {
+        builder
+    };
+    builder = if let Some(pl) = pl_data { builder.with_preloader_data(pl) } else { builder };
+
+    if let Some(path) = &args.save_patched_da
+        && try_exists(path).await?
+    {
+        builder = builder.with_prepatched_da(read(path).await?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Whether `port` names a UART console rather than one of the enumerated MTK ports.
+#[cfg(feature = "serial")]
+async fn is_uart_console(port: &str) -> bool {
+    !port.starts_with("usb:") && !find_mtk_ports().await.iter().any(|p| p.get_port_name() == port)
+}
@@ -0,0 +1,119 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::time::Duration;
+
+use log::debug;
+use notify_rust::Notification;
+use penumbra::core::summary::OperationSummary;
+
+/// Something able to show a notification to the user.
+pub trait Notifier {
+    fn notify(&self, title: &str, body: &str);
+}
+
+/// Desktop notifications through the platform notifier: D-Bus on Linux and the BSDs,
+/// the notification center on macOS and toasts on Windows.
+///
+/// Failures are ignored on purpose: on headless systems there's nobody to notify,
+/// and a missing notifier must never make a finished operation fail.
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, title: &str, body: &str) {
+        let result = Notification::new().appname("Antumbra").summary(title).body(body).show();
+        if let Err(e) = result {
+            debug!("Could not send desktop notification: {}", e);
+        }
+    }
+}
+
+/// Notifies about a finished operation, if it took at least `threshold`.
+/// Short operations finish while the user is still looking, so they're not worth a ping.
+pub fn notify_completion(
+    notifier: &dyn Notifier,
+    command: &str,
+    summary: &OperationSummary,
+    threshold: Duration,
+) {
+    if summary.elapsed < threshold {
+        return;
+    }
+
+    let title = if summary.success {
+        format!("Antumbra: {} complete", command)
+    } else {
+        format!("Antumbra: {} failed", command)
+    };
+    let body = format!(
+        "{} {:.2} MiB in {:.0}s",
+        summary.kind,
+        summary.bytes as f64 / (1024.0 * 1024.0),
+        summary.elapsed.as_secs_f64()
+    );
+
+    notifier.notify(&title, &body);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use penumbra::core::summary::OperationKind;
+
+    use super::*;
+
+    /// Keeps the notifications instead of showing them.
+    #[derive(Default)]
+    struct Recorder(RefCell<Vec<(String, String)>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, title: &str, body: &str) {
+            self.0.borrow_mut().push((title.to_string(), body.to_string()));
+        }
+    }
+
+    fn summary(elapsed: u64, success: bool) -> OperationSummary {
+        OperationSummary {
+            kind: OperationKind::Read,
+            bytes: 64 * 1024 * 1024,
+            elapsed: Duration::from_secs(elapsed),
+            peak_throughput: 0.0,
+            retries: 0,
+            sha256: None,
+            success,
+            bad_ranges: Vec::new(),
+        }
+    }
+
+    const THRESHOLD: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn short_operations_are_not_notified() {
+        let recorder = Recorder::default();
+        notify_completion(&recorder, "read", &summary(29, true), THRESHOLD);
+        assert!(recorder.0.borrow().is_empty());
+    }
+
+    #[test]
+    fn success_is_notified_from_the_threshold() {
+        let recorder = Recorder::default();
+        notify_completion(&recorder, "read", &summary(30, true), THRESHOLD);
+
+        let sent = recorder.0.borrow();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "Antumbra: read complete");
+        assert!(sent[0].1.contains("64.00 MiB in 30s"), "{}", sent[0].1);
+    }
+
+    #[test]
+    fn failure_is_notified() {
+        let recorder = Recorder::default();
+        notify_completion(&recorder, "write", &summary(120, false), THRESHOLD);
+
+        let sent = recorder.0.borrow();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "Antumbra: write failed");
+    }
+}
@@ -6,15 +6,34 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use tokio::fs::{metadata, read, remove_file, write};
 
+/// How far the DA bring-up of the session went.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+pub enum DaStage {
+    /// No DA running, the device is in BROM or preloader
+    #[default]
+    Brom,
+    /// A DA was booted, but bring-up failed before DA2 was ready. Only a reset gets out of it
+    Da1,
+    Da2,
+}
+
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct PersistedDeviceState {
     pub da_file_path: Option<String>,
-    pub soc_id: Vec<u8>,
-    pub meid: Vec<u8>,
+    pub soc_id: Option<Vec<u8>>,
+    pub meid: Option<Vec<u8>>,
     pub hw_code: u16,
     pub target_config: u32,
     pub connection_type: u8,
     pub flash_mode: u8,
+    pub random_id: Option<Vec<u8>>,
+    /// Port of the device the session was started on
+    pub port_name: Option<String>,
+    #[serde(default)]
+    pub da_stage: DaStage,
+    /// Whether the DA extensions were booted, so they don't get uploaded again
+    #[serde(default)]
+    pub extensions_active: bool,
 }
 
 impl PersistedDeviceState {
@@ -0,0 +1,160 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use log::info;
+use penumbra::{MTKPort, find_mtk_port, find_mtk_port_by_name};
+use tokio::time::{Instant, sleep};
+
+/// Looks for the port of a device, so the wait can be tested without one.
+#[async_trait]
+pub trait PortFinder {
+    type Port: Send;
+
+    async fn find(&mut self) -> Option<Self::Port>;
+}
+
+/// Finds the first MTK port, or the one named by `--port`.
+pub struct MtkPortFinder<'a>(pub Option<&'a str>);
+
+#[async_trait]
+impl PortFinder for MtkPortFinder<'_> {
+    type Port = Box<dyn MTKPort>;
+
+    async fn find(&mut self) -> Option<Self::Port> {
+        match self.0 {
+            Some(name) => find_mtk_port_by_name(name).await,
+            None => find_mtk_port().await,
+        }
+    }
+}
+
+/// Timings of [`wait_for_port`].
+pub struct WaitPolicy {
+    /// Pause between two enumerations, so waiting doesn't hog a core
+    pub poll_interval: Duration,
+    /// How long the device of the last session can be missing before it's deemed gone
+    pub gone_after: Duration,
+    /// How often to remind the user that we're still waiting
+    pub hint_interval: Duration,
+    /// Give up after this long, `None` to wait forever
+    pub timeout: Option<Duration>,
+}
+
+impl Default for WaitPolicy {
+    fn default() -> Self {
+        WaitPolicy {
+            poll_interval: Duration::from_millis(300),
+            gone_after: Duration::from_millis(500),
+            hint_interval: Duration::from_secs(10),
+            timeout: None,
+        }
+    }
+}
+
+/// Polls `finder` until a port shows up.
+///
+/// `on_gone` runs once, when no port showed up within `gone_after`: whatever device the
+/// last session was on has rebooted or was unplugged since.
+pub async fn wait_for_port<F: PortFinder>(
+    finder: &mut F,
+    policy: &WaitPolicy,
+    mut on_gone: impl AsyncFnMut() -> Result<()>,
+) -> Result<F::Port> {
+    let start = Instant::now();
+    let mut gone = false;
+    let mut last_hint = start;
+
+    loop {
+        if let Some(port) = finder.find().await {
+            return Ok(port);
+        }
+
+        if !gone && start.elapsed() >= policy.gone_after {
+            gone = true;
+            on_gone().await?;
+        }
+        if let Some(timeout) = policy.timeout
+            && start.elapsed() >= timeout
+        {
+            return Err(anyhow!("No MTK device found after {}s", timeout.as_secs()));
+        }
+        if last_hint.elapsed() >= policy.hint_interval {
+            last_hint = Instant::now();
+            info!("Still waiting for MTK device, hold Vol+ / Vol- while plugging it in...");
+        }
+
+        sleep(policy.poll_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Finds a port after `misses` empty enumerations.
+    struct DelayedFinder {
+        misses: usize,
+    }
+
+    #[async_trait]
+    impl PortFinder for DelayedFinder {
+        type Port = ();
+
+        async fn find(&mut self) -> Option<()> {
+            match self.misses {
+                0 => Some(()),
+                _ => {
+                    self.misses -= 1;
+                    None
+                }
+            }
+        }
+    }
+
+    fn policy(timeout: Option<Duration>) -> WaitPolicy {
+        WaitPolicy {
+            poll_interval: Duration::from_millis(1),
+            gone_after: Duration::from_millis(5),
+            hint_interval: Duration::from_secs(10),
+            timeout,
+        }
+    }
+
+    #[tokio::test]
+    async fn state_is_reset_once() {
+        let mut resets = 0;
+        let mut finder = DelayedFinder { misses: 50 };
+        wait_for_port(&mut finder, &policy(None), async || {
+            resets += 1;
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(resets, 1);
+
+        // A device already there is the one of the last session
+        let mut resets = 0;
+        let mut finder = DelayedFinder { misses: 0 };
+        wait_for_port(&mut finder, &policy(None), async || {
+            resets += 1;
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(resets, 0);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_timeout() {
+        let mut finder = DelayedFinder { misses: usize::MAX };
+        let result =
+            wait_for_port(&mut finder, &policy(Some(Duration::from_millis(20))), async || Ok(()))
+                .await;
+        assert!(result.is_err());
+    }
+}
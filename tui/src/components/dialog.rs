@@ -3,6 +3,8 @@
     SPDX-FileCopyrightText: 2025 DiabloSat
     SPDX-FileCopyrightText: 2025 Shomy
 */
+use std::sync::{Arc, Mutex};
+
 use derive_builder::Builder;
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Paragraph, Widget, Wrap};
@@ -23,9 +25,11 @@ pub enum DialogType {
     Other,
 }
 
+/// A dialog button. Clones share the action, since building the dialog clones its buttons.
+#[derive(Clone)]
 pub struct DialogButton {
     pub title: String,
-    pub action: Box<dyn FnMut() + Send>,
+    pub action: Arc<Mutex<dyn FnMut() + Send>>,
 }
 
 impl DialogButton {
@@ -33,13 +37,7 @@ impl DialogButton {
     where
         F: FnMut() + Send + 'static,
     {
-        Self { title: title.to_string(), action: Box::new(action) }
-    }
-}
-
-impl Clone for DialogButton {
-    fn clone(&self) -> Self {
-        Self { title: self.title.clone(), action: Box::new(|| {}) }
+        Self { title: title.to_string(), action: Arc::new(Mutex::new(action)) }
     }
 }
 
@@ -137,8 +135,10 @@ impl ThemedWidgetRef for Dialog {
 // Actions
 impl Dialog {
     pub fn press_selected(&mut self) {
-        if let Some(button) = self.buttons.get_mut(self.selected) {
-            (button.action)();
+        if let Some(button) = self.buttons.get(self.selected) {
+            // A poisoned lock only means an earlier press panicked
+            let mut action = button.action.lock().unwrap_or_else(|e| e.into_inner());
+            (*action)();
         }
     }
 
@@ -0,0 +1,132 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use log::Level;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::prelude::{Buffer, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, BorderType, Borders, Clear, Paragraph, Widget};
+
+use crate::components::StatefulThemedWidget;
+use crate::logger::{ERROR_SYMBOL, INFO_SYMBOL, LogBuffer, WARN_SYMBOL};
+use crate::themes::Theme;
+
+/// Height of the pane when shown, borders included.
+const PANE_HEIGHT: u16 = 12;
+
+/// Collapsible pane showing the log lines of the process, so that failures can be
+/// understood without going back to the CLI.
+///
+/// Keys: `l` toggles it, `PgUp`/`PgDn` scroll back and forth, `f` cycles the level filter.
+pub struct LogPane {
+    visible: bool,
+    /// Lines scrolled back from the latest one
+    scroll: usize,
+    /// Least severe level shown
+    level: Level,
+    /// Lines shown at once, as of the last render
+    page_size: usize,
+}
+
+impl LogPane {
+    pub fn new() -> Self {
+        Self { visible: false, scroll: 0, level: Level::Info, page_size: 1 }
+    }
+
+    /// Handles the pane keys, returns whether `key` was one of them.
+    /// The other keys are left to the page, even while the pane is shown.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('l') => {
+                self.visible = !self.visible;
+                self.scroll = 0;
+            }
+            _ if !self.visible => return false,
+            KeyCode::PageUp => self.scroll += self.page_size,
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(self.page_size),
+            KeyCode::Char('f') => {
+                self.level = match self.level {
+                    Level::Debug | Level::Trace => Level::Info,
+                    Level::Info => Level::Warn,
+                    Level::Warn => Level::Error,
+                    Level::Error => Level::Debug,
+                };
+                self.scroll = 0;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Splits `area` between the page, on top, and the pane at the bottom.
+    /// The page gets the whole area while the pane is hidden.
+    pub fn split(&self, area: Rect) -> (Rect, Option<Rect>) {
+        if !self.visible || area.height <= PANE_HEIGHT {
+            return (area, None);
+        }
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(PANE_HEIGHT)])
+            .split(area);
+
+        (chunks[0], Some(chunks[1]))
+    }
+
+    fn level_style(&self, level: Level, theme: &Theme) -> (&'static str, Style) {
+        match level {
+            Level::Error => (ERROR_SYMBOL, Style::default().fg(theme.error)),
+            Level::Warn => (WARN_SYMBOL, Style::default().fg(theme.warning)),
+            Level::Info => (INFO_SYMBOL, Style::default().fg(theme.text)),
+            Level::Debug | Level::Trace => ("·", Style::default().fg(theme.muted)),
+        }
+    }
+}
+
+/// Rendered from the [`LogBuffer`] of the app context, which any page can reach.
+impl StatefulThemedWidget for LogPane {
+    type State = LogBuffer;
+
+    fn render(&mut self, area: Rect, buf: &mut Buffer, logs: &mut LogBuffer, theme: &Theme) {
+        let level = self.level;
+        let lines = logs.filtered(|line| line.level <= level);
+
+        let title = if self.scroll > 0 {
+            format!(" LOGS ({}+, {} lines back) ", level, self.scroll)
+        } else {
+            format!(" LOGS ({}+) ", level)
+        };
+
+        let block = Block::default()
+            .title(title)
+            .title_bottom(" [l] Hide  [PgUp/PgDn] Scroll  [f] Level ")
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .style(Style::default().bg(theme.background).fg(theme.accent));
+
+        let inner = block.inner(area);
+        Clear.render(area, buf);
+        block.render(area, buf);
+
+        self.page_size = (inner.height as usize).max(1);
+        self.scroll = self.scroll.min(lines.len().saturating_sub(self.page_size));
+
+        let end = lines.len() - self.scroll;
+        let start = end.saturating_sub(self.page_size);
+        let shown: Vec<Line> = lines[start..end]
+            .iter()
+            .map(|line| {
+                let (symbol, style) = self.level_style(line.level, theme);
+                Line::from(vec![
+                    Span::styled(format!("{symbol} "), style.add_modifier(Modifier::BOLD)),
+                    Span::styled(line.message.clone(), style),
+                ])
+            })
+            .collect();
+
+        Paragraph::new(shown).render(inner, buf);
+    }
+}
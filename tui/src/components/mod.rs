@@ -4,6 +4,7 @@ pub mod description_menu;
 pub mod dialog;
 pub mod dropdown;
 pub mod file_explorer;
+pub mod log_pane;
 pub mod progress_bar;
 pub mod selectable_list;
 // Re-exports :D
@@ -14,6 +15,7 @@ pub use description_menu::{DescriptionMenu, DescriptionMenuItem};
 pub use dialog::{DialogBuilder, DialogButton};
 pub use dropdown::{Dropdown, DropdownOption};
 pub use file_explorer::{ExplorerResult, FileExplorer};
+pub use log_pane::LogPane;
 pub use progress_bar::ProgressBar;
 use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
@@ -2,9 +2,10 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use human_bytes::human_bytes;
+use penumbra::ProgressEvent;
 use ratatui::prelude::{Buffer, Rect};
 use ratatui::style::{Modifier, Style};
 use ratatui::text::{Line, Span};
@@ -25,6 +26,8 @@ pub struct ProgressBar {
     written_bytes: u64,
     message: String,
     start_time: Option<Instant>,
+    /// Latest event from the core, whose timing is used over the bar's own clock
+    last_event: Option<ProgressEvent>,
 }
 
 impl ProgressBar {
@@ -35,6 +38,7 @@ impl ProgressBar {
             written_bytes: 0,
             message: String::from("No active operation"),
             start_time: None,
+            last_event: None,
         }
     }
 
@@ -44,6 +48,7 @@ impl ProgressBar {
         self.written_bytes = 0;
         self.message = message.into();
         self.start_time = Some(Instant::now());
+        self.last_event = None;
     }
 
     /// Update written bytes
@@ -53,6 +58,14 @@ impl ProgressBar {
         }
     }
 
+    /// Update written bytes from a core progress event, taking its speed and ETA as well
+    pub fn set_event(&mut self, event: &ProgressEvent) {
+        if matches!(self.mode, ProgressMode::Active) {
+            self.written_bytes = event.bytes_done.min(self.total_bytes);
+            self.last_event = Some(*event);
+        }
+    }
+
     /// Update message
     pub fn set_message(&mut self, message: impl Into<String>) {
         if matches!(self.mode, ProgressMode::Active) {
@@ -66,6 +79,7 @@ impl ProgressBar {
         self.written_bytes = 0;
         self.message = String::from("No active operation");
         self.start_time = None;
+        self.last_event = None;
     }
 
     fn ratio(&self) -> f64 {
@@ -76,7 +90,30 @@ impl ProgressBar {
         }
     }
 
+    pub fn is_active(&self) -> bool {
+        matches!(self.mode, ProgressMode::Active)
+    }
+
+    /// Estimated time left, based on the average speed so far.
+    fn eta(&self) -> Option<Duration> {
+        if let Some(event) = &self.last_event {
+            return event.eta();
+        }
+
+        let speed = self.speed();
+        if speed <= 0.0 {
+            return None;
+        }
+
+        let left = self.total_bytes.saturating_sub(self.written_bytes) as f64;
+        Some(Duration::from_secs_f64(left / speed))
+    }
+
     fn speed(&self) -> f64 {
+        if let Some(event) = &self.last_event {
+            return event.throughput();
+        }
+
         match self.start_time {
             Some(start) => {
                 let elapsed = start.elapsed().as_secs_f64();
@@ -120,6 +157,13 @@ impl ThemedWidgetRef for ProgressBar {
                 let written = human_bytes(self.written_bytes as f64);
                 let total = human_bytes(self.total_bytes as f64);
                 let speed = human_bytes(self.speed());
+                let eta = match self.eta() {
+                    Some(eta) => {
+                        let secs = eta.as_secs();
+                        format!("ETA {:02}:{:02}", secs / 60, secs % 60)
+                    }
+                    None => String::from("ETA --:--"),
+                };
 
                 let lines = vec![
                     Line::from(Span::styled(&self.message, style)),
@@ -128,6 +172,8 @@ impl ThemedWidgetRef for ProgressBar {
                         Span::raw(format!("{written} / {total}")),
                         Span::raw("  •  "),
                         Span::raw(format!("{speed}/s")),
+                        Span::raw("  •  "),
+                        Span::raw(eta),
                     ]),
                 ];
 
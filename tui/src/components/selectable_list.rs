@@ -4,6 +4,8 @@
  *    SPDX-FileCopyrightText: 2025 Shomy
  */
 
+use std::time::{Duration, Instant};
+
 use derive_builder::Builder;
 use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
@@ -51,8 +53,14 @@ pub struct SelectableList {
     pub borders: Borders,
     #[builder(default)]
     pub block_title: String,
+    /// Direction, time and count of the last consecutive navigation presses
+    #[builder(setter(skip))]
+    repeat: Option<(bool, Instant, usize)>,
 }
 
+/// Held keys repeat every 30-50ms, anything slower is treated as a new press
+const REPEAT_WINDOW: Duration = Duration::from_millis(150);
+
 impl ThemedWidgetMut for SelectableList {
     fn render(&mut self, area: Rect, buf: &mut Buffer, theme: &Theme) {
         let list_items: Vec<ListItem> = self
@@ -110,6 +118,45 @@ impl SelectableList {
         }
     }
 
+    /// Like `next`, but moves faster the longer the key is held.
+    /// Accelerated moves stop at the end of the list instead of wrapping.
+    pub fn scroll_down(&mut self) {
+        match self.repeat_step(true) {
+            1 => self.next(),
+            step => {
+                let i = self.state.selected().unwrap_or(0);
+                let last = self.items.len().saturating_sub(1);
+                self.state.select(Some((i + step).min(last)));
+            }
+        }
+    }
+
+    /// Like `previous`, but moves faster the longer the key is held.
+    pub fn scroll_up(&mut self) {
+        match self.repeat_step(false) {
+            1 => self.previous(),
+            step => {
+                let i = self.state.selected().unwrap_or(0);
+                self.state.select(Some(i.saturating_sub(step)));
+            }
+        }
+    }
+
+    fn repeat_step(&mut self, down: bool) -> usize {
+        let now = Instant::now();
+        let count = match self.repeat {
+            Some((dir, last, count)) if dir == down && now - last < REPEAT_WINDOW => count + 1,
+            _ => 0,
+        };
+        self.repeat = Some((down, now, count));
+
+        match count {
+            0..10 => 1,
+            10..30 => 4,
+            _ => 8,
+        }
+    }
+
     pub fn selected_index(&self) -> Option<usize> {
         self.state.selected()
     }
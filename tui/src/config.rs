@@ -2,22 +2,31 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2026 Shomy
 */
+// The settings themselves are only read by the TUI and notifications
+#![cfg_attr(not(any(feature = "tui", feature = "notify")), allow(dead_code))]
 
 use std::fs;
 use std::path::PathBuf;
 
 use anyhow::Result;
 use config::{Config, Environment, File};
+use penumbra::da::DaRepository;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct AntumbraConfig {
     pub theme: String,
+    /// Send a desktop notification when a long CLI operation finishes
+    pub notify: bool,
+    /// Minimum duration of an operation, in seconds, for it to be notified
+    pub notify_threshold: u64,
+    /// Let exploits patch the DA, disable for devices that only boot a genuine DA
+    pub da_patching: bool,
 }
 
 impl Default for AntumbraConfig {
     fn default() -> Self {
-        Self { theme: "system".to_string() }
+        Self { theme: "system".to_string(), notify: false, notify_threshold: 30, da_patching: true }
     }
 }
 
@@ -27,6 +36,9 @@ impl AntumbraConfig {
         let defaults = AntumbraConfig::default();
 
         builder = builder.set_default("theme", defaults.theme).unwrap();
+        builder = builder.set_default("notify", defaults.notify).unwrap();
+        builder = builder.set_default("notify_threshold", defaults.notify_threshold).unwrap();
+        builder = builder.set_default("da_patching", defaults.da_patching).unwrap();
 
         if let Some(config_dir) = dirs::config_dir().map(|p| p.join("antumbra")) {
             builder =
@@ -58,3 +70,20 @@ impl AntumbraConfig {
         dirs::config_dir().map(|p| p.join("antumbra/config.toml"))
     }
 }
+
+/// Directory of the DA repository, where `antumbra da import` copies DAs.
+pub fn da_repository_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("antumbra/da"))
+}
+
+/// Journal of interrupted writes, kept out of the working directory so that it's found
+/// whatever directory the next run is started from.
+pub fn journal_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("antumbra/journal.json"))
+}
+
+/// Best DA of the repository for `hw_code`, for when none was given.
+pub async fn repository_da(hw_code: u16) -> Result<Option<PathBuf>> {
+    let Some(dir) = da_repository_dir() else { return Ok(None) };
+    Ok(DaRepository::open(dir).await?.find_for_hw_code(hw_code))
+}
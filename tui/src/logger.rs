@@ -2,13 +2,19 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+#[cfg(feature = "tui")]
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::Write;
+#[cfg(feature = "tui")]
+use std::sync::OnceLock;
 use std::sync::{Arc, Mutex};
 
 use colored::Colorize;
 use env_logger::fmt::Formatter;
-use log::{Level, LevelFilter, Record};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::cli::suspend_progress;
 
 pub const LOG_FILE_PATH: &str = "antumbra.log";
 pub const LOGGER_PREIX: &str = "Antumbra";
@@ -16,7 +22,71 @@ pub const INFO_SYMBOL: &str = "✦";
 pub const WARN_SYMBOL: &str = "✧";
 pub const ERROR_SYMBOL: &str = "❂";
 
-pub fn init_logger(tui_mode: bool, verbose: bool) {
+/// Number of log lines kept for the TUI log pane.
+#[cfg(feature = "tui")]
+pub const LOG_CAPACITY: usize = 2000;
+
+/// A log record, as kept for the TUI.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub level: Level,
+    /// Whether it comes from the core, rather than from Antumbra or a dependency.
+    pub core: bool,
+    pub message: String,
+}
+
+/// Shared handle to the latest log lines, oldest first.
+/// Nothing is printed in TUI mode, so this is the only place records end up.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<LogLine>>>,
+}
+
+#[cfg(feature = "tui")]
+impl LogBuffer {
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<LogLine>> {
+        // A panicking writer can't leave the buffer in an invalid state
+        self.lines.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, record: &Record) {
+        let mut lines = self.lock();
+        if lines.len() >= LOG_CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(LogLine {
+            level: record.level(),
+            core: record.target().starts_with("penumbra"),
+            message: record.args().to_string(),
+        });
+    }
+
+    /// Returns the lines matching `filter`, oldest first.
+    pub fn filtered(&self, filter: impl Fn(&LogLine) -> bool) -> Vec<LogLine> {
+        self.lock().iter().filter(|line| filter(line)).cloned().collect()
+    }
+}
+
+/// Log lines of this process, written from any task or thread.
+#[cfg(feature = "tui")]
+static LOG_BUFFER: OnceLock<LogBuffer> = OnceLock::new();
+
+/// Returns the handle to the log lines of this process.
+#[cfg(feature = "tui")]
+pub fn log_buffer() -> LogBuffer {
+    LOG_BUFFER.get_or_init(LogBuffer::default).clone()
+}
+
+/// Returns up to `count` of the latest core messages, oldest first.
+#[cfg(feature = "tui")]
+pub fn recent_messages(count: usize) -> Vec<String> {
+    let messages = log_buffer().filtered(|line| line.core && line.level <= Level::Info);
+    messages[messages.len().saturating_sub(count)..].iter().map(|l| l.message.clone()).collect()
+}
+
+pub fn init_logger(tui_mode: bool, verbose: bool, stderr: bool) {
     let mut builder = env_logger::Builder::new();
 
     let log_file: Option<Arc<Mutex<File>>> = if verbose {
@@ -31,8 +101,14 @@ pub fn init_logger(tui_mode: bool, verbose: bool) {
         None
     };
 
+    #[cfg(feature = "tui")]
+    let logs = log_buffer();
+
     builder.format(move |buf: &mut Formatter, record: &Record| {
         if tui_mode {
+            #[cfg(feature = "tui")]
+            logs.push(record);
+
             if verbose
                 && record.level() == Level::Debug
                 && let Some(ref log_file) = log_file
@@ -60,9 +136,36 @@ pub fn init_logger(tui_mode: bool, verbose: bool) {
         }
     });
 
-    builder.filter_level(if verbose { LevelFilter::Debug } else { LevelFilter::Info });
+    // The TUI log pane filters records itself, so it gets them all
+    builder.filter_level(if verbose || tui_mode { LevelFilter::Debug } else { LevelFilter::Info });
     builder.filter_module("nusb", LevelFilter::Off); // Annoying logs :D
 
-    builder.target(env_logger::Target::Stdout);
-    builder.init();
+    builder.target(if stderr { env_logger::Target::Stderr } else { env_logger::Target::Stdout });
+
+    let logger = builder.build();
+    log::set_max_level(logger.filter());
+    log::set_boxed_logger(Box::new(ProgressAwareLogger { inner: logger }))
+        .expect("Logger already initialized");
+}
+
+/// Logger hiding progress bars while a record is printed.
+/// Core logs from inside flash loops, so without this records and bars get interleaved.
+struct ProgressAwareLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for ProgressAwareLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            suspend_progress(|| self.inner.log(record));
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
 }
@@ -64,9 +64,9 @@ macro_rules! confirm_dialog {
     };
     ($ctx:expr, $message:expr, $on_confirm:expr, $on_cancel:expr) => {
         $ctx.dialog = Some({
-            let mut builder = DialogBuilder::info($message);
-            builder.button(DialogButton::new("OK", $on_confirm));
-            builder.button(DialogButton::new("Cancel", $on_cancel));
+            let mut builder = crate::components::DialogBuilder::info($message, &$ctx.theme);
+            builder.button(crate::components::DialogButton::new("OK", $on_confirm));
+            builder.button(crate::components::DialogButton::new("Cancel", $on_cancel));
             builder.build().unwrap()
         })
     };
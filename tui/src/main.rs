@@ -2,6 +2,7 @@
     SPDX-License-Identifier: AGPL-3.0-or-later
     SPDX-FileCopyrightText: 2025 Shomy
 */
+#[cfg(feature = "tui")]
 #[macro_use]
 mod macros;
 
@@ -19,22 +20,34 @@ mod config;
 mod error;
 mod logger;
 
+use std::process::ExitCode;
+
 use anyhow::Result;
 use clap::Parser;
-use cli::{CliArgs, run_cli};
+use cli::{CliArgs, MtkCommand, events, run_cli};
 use logger::init_logger;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     let args = CliArgs::parse();
 
     let cli_mode = args.cli || args.command.is_some() || !cfg!(feature = "tui");
     let tui_mode = !cli_mode;
 
-    init_logger(tui_mode, args.verbose);
+    // Keep stdout clean when data or JSON events are piped through it
+    let log_to_stderr = args.json || args.command.as_ref().is_some_and(|cmd| cmd.writes_stdout());
+    init_logger(tui_mode, args.verbose, log_to_stderr);
 
     if cli_mode {
-        return run_cli(&args).await;
+        if args.json {
+            events::enable_json();
+        }
+
+        let result = run_cli(&args).await;
+        if let Err(e) = &result {
+            events::emit(&events::Event::error(e));
+        }
+        return result;
     }
 
     #[cfg(feature = "tui")]
@@ -47,7 +60,7 @@ async fn main() -> Result<()> {
         let app_result = app.run(&mut terminal).await;
 
         ratatui::restore();
-        return app_result;
+        return app_result.map(|()| ExitCode::SUCCESS);
     }
 
     unreachable!()
@@ -10,28 +10,44 @@ use std::time::Instant;
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use human_bytes::human_bytes;
+use log::{error, info};
 use penumbra::core::devinfo::DevInfoData;
 use penumbra::core::seccfg::LockFlag;
+use penumbra::core::storage::lp::SUPER_PARTITION;
 use penumbra::core::storage::{Partition, Storage};
-use penumbra::{Device, DeviceBuilder, find_mtk_port};
-#[cfg(target_os = "windows")]
-use ratatui::crossterm::event::KeyEventKind;
+use penumbra::error::ErrorKind;
+use penumbra::{
+    Device,
+    DeviceBuilder,
+    KeepAlive,
+    ProgressEvent,
+    find_mtk_port_by_name,
+    find_mtk_ports,
+};
 use ratatui::crossterm::event::{KeyCode, KeyEvent};
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::prelude::{Alignment, Frame};
-use ratatui::style::{Modifier, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, BorderType, Borders, Paragraph, Row, Table};
 use strum::IntoEnumIterator;
 use strum_macros::{AsRefStr, EnumIter};
-use tokio::fs::File;
+use tokio::fs::{File, read};
 use tokio::io::{BufReader, BufWriter};
 use tokio::spawn;
-use tokio::sync::{Mutex, mpsc};
+use tokio::sync::{Mutex, mpsc, oneshot};
 use tokio::task::JoinHandle;
 use tokio::time::{Duration, sleep};
 
-use crate::app::{AppCtx, AppPage};
+use crate::app::{
+    AppCtx,
+    AppPage,
+    EventReceiver,
+    EventSender,
+    MAX_EVENTS_PER_FRAME,
+    PageEvent,
+    event_queue,
+};
 use crate::components::selectable_list::{
     ListItemEntry,
     ListItemEntryBuilder,
@@ -43,15 +59,22 @@ use crate::components::{
     FileExplorer,
     ProgressBar,
     Stars,
+    StatefulThemedWidget,
     ThemedWidgetMut,
     ThemedWidgetRef,
 };
-use crate::pages::Page;
+use crate::config::repository_da;
+use crate::pages::{Page, ProgressAction, ProgressPage, ProgressState};
+
+/// How often the device info is read again while connected
+const DEVICE_INFO_REFRESH: Duration = Duration::from_secs(5);
 
 /// Which panel is currently focused
 pub enum FocusedPanel {
     Menu,
     PartitionMenu,
+    /// Choosing which of the connected devices to use
+    PortMenu,
 }
 
 /// Device connection status, used for UI updates
@@ -81,10 +104,24 @@ pub enum DeviceEvent {
     ProgressFinish {
         message: String,
     },
+    /// Progress of the item currently being transferred, usually a partition
+    ItemProgress {
+        name: String,
+        event: ProgressEvent,
+    },
+    /// The running operation ended, with the error if it failed
+    OperationDone(Option<String>),
+    /// The user confirmed the cancellation of the running operation
+    CancelOperation,
     /// Notify of device status change (Disconnected, Connecting, Connected)
     StatusChanged(DeviceStatus),
     /// Notify that device is connected (To be sent once)
     Connected(Device),
+    /// More than one device is connected, ask the user which port to use
+    ChoosePort {
+        ports: Vec<String>,
+        reply: oneshot::Sender<String>,
+    },
 
     /// Change focused panel
     FocusPanel(FocusedPanel),
@@ -103,6 +140,17 @@ pub enum DeviceEvent {
     Input(bool),
 }
 
+impl PageEvent for DeviceEvent {
+    fn progress_key(&self) -> Option<u32> {
+        // A single operation runs at a time, with an overall and an item progress
+        match self {
+            DeviceEvent::ProgressUpdate { .. } => Some(0),
+            DeviceEvent::ItemProgress { .. } => Some(1),
+            _ => None,
+        }
+    }
+}
+
 /// A list of event used by the page and callbacks to communicate to
 /// each other.
 /// Works via a bi-directional channel.
@@ -141,12 +189,20 @@ pub trait DeviceActionCallback: Send + Sync {
     async fn execute(
         &self,
         device: Arc<Mutex<Device>>,
-        event_tx: mpsc::Sender<DeviceEvent>,
+        event_tx: EventSender<DeviceEvent>,
         cb_tx: mpsc::Sender<CallbackEvent>,
         cb_rx: mpsc::Receiver<CallbackEvent>,
     ) -> Result<()>;
 }
 
+/// Adds a hint for the user to an error message, for the errors with a likely cause.
+fn with_hint(msg: String, error: Option<&penumbra::Error>) -> String {
+    match error.map(|e| e.kind()) {
+        Some(ErrorKind::Security) => format!("{} (bootloader locked?)", msg),
+        _ => msg,
+    }
+}
+
 pub struct DeviceState {
     pub status: DeviceStatus,
     pub last_status_change: Instant,
@@ -174,8 +230,8 @@ pub struct DevicePage {
 
     // Event channel for async communication
     /// UI Events
-    pub event_tx: mpsc::Sender<DeviceEvent>,
-    pub event_rx: mpsc::Receiver<DeviceEvent>,
+    pub event_tx: EventSender<DeviceEvent>,
+    pub event_rx: EventReceiver<DeviceEvent>,
     /// Callbacks Events, for transmitting Callback results
     pub callback_tx: Option<mpsc::Sender<CallbackEvent>>,
     pub callback_rx: Option<mpsc::Receiver<CallbackEvent>>,
@@ -183,12 +239,19 @@ pub struct DevicePage {
     // Action callbacks and active operations
     pub action_callbacks: HashMap<DeviceAction, Arc<dyn DeviceActionCallback>>,
     pub active_operations: HashMap<DeviceAction, JoinHandle<()>>,
+    /// Keeps the DA session alive while waiting on the user
+    keepalive: Option<KeepAlive>,
 
     // UI components (foundation only, not rendered yet)
     stars: Stars,
     progress_bar: ProgressBar,
+    /// Full-screen progress of the running operation, if any
+    progress_page: Option<ProgressPage>,
     menu: SelectableList,
     partition_list: SelectableList,
+    port_list: SelectableList,
+    /// Where to send the port picked in `port_list`, while the user is choosing one
+    port_reply: Option<oneshot::Sender<String>>,
     explorer: Option<FileExplorer>,
 
     // UI State
@@ -199,11 +262,15 @@ pub struct DevicePage {
     pub partitions: Vec<Partition>,
     pub devinfo: Option<DevInfoData>,
     pub storage: Option<Arc<dyn Storage + Send + Sync>>,
+    pub patched_da: bool,
+    pub exts_active: bool,
+    /// When the device info was last refreshed
+    last_refresh: Instant,
 }
 
 impl DevicePage {
     pub fn new() -> Self {
-        let (event_tx, event_rx) = mpsc::channel(32);
+        let (event_tx, event_rx) = event_queue();
         let progress_bar = ProgressBar::new();
 
         // Build menu from actions
@@ -234,6 +301,12 @@ impl DevicePage {
             .build()
             .unwrap();
 
+        let port_list = SelectableListBuilder::default()
+            .items(Vec::new())
+            .highlight_symbol(">> ".to_string())
+            .build()
+            .unwrap();
+
         let mut page = Self {
             device: None,
             device_state: DeviceState::new(),
@@ -244,16 +317,23 @@ impl DevicePage {
             callback_rx: None,
             action_callbacks: HashMap::new(),
             active_operations: HashMap::new(),
+            keepalive: None,
             stars: Stars::default(),
             progress_bar,
+            progress_page: None,
             menu,
             explorer: None,
             focused_panel: FocusedPanel::Menu,
             input_enabled: true,
             partition_list,
+            port_list,
+            port_reply: None,
             partitions: Vec::new(),
             devinfo: None,
             storage: None,
+            patched_da: false,
+            exts_active: false,
+            last_refresh: Instant::now(),
         };
 
         page.register_action(DeviceAction::UnlockBootloader, Arc::new(UnlockBootloaderCallback));
@@ -279,12 +359,12 @@ impl DevicePage {
         }
 
         let Some(device) = self.device.clone() else {
-            self.event_tx.send(DeviceEvent::Error("Device not connected".to_string())).await.ok();
+            self.event_tx.send(DeviceEvent::Error("Device not connected".to_string()));
             return;
         };
 
         let Some(callback) = self.action_callbacks.get(&action).cloned() else {
-            self.event_tx.send(DeviceEvent::Error("No callback registered".to_string())).await.ok();
+            self.event_tx.send(DeviceEvent::Error("No callback registered".to_string()));
             return;
         };
 
@@ -302,9 +382,9 @@ impl DevicePage {
             let result = callback
                 .execute(device, event_tx.clone(), cb_tx_from_callback, cb_rx_from_callback)
                 .await;
-            if let Err(e) = result {
-                event_tx.send(DeviceEvent::Error(e.to_string())).await.ok();
-            }
+            event_tx.send(DeviceEvent::OperationDone(
+                result.err().map(|e| with_hint(e.to_string(), e.downcast_ref())),
+            ));
         });
 
         self.active_operations.insert(action, handle);
@@ -312,12 +392,16 @@ impl DevicePage {
 
     /// Process all pending events from the event channel
     pub async fn process_events(&mut self, ctx: &mut AppCtx) {
-        while let Ok(event) = self.event_rx.try_recv() {
+        for event in self.event_rx.drain(MAX_EVENTS_PER_FRAME) {
             match event {
                 DeviceEvent::ProgressStart { total_bytes, message } => {
+                    self.progress_page = Some(ProgressPage::new(message.clone(), total_bytes));
                     self.progress_bar.start(total_bytes, message);
                 }
                 DeviceEvent::ProgressUpdate { written, message } => {
+                    if let Some(page) = &mut self.progress_page {
+                        page.update_overall(written, message.clone());
+                    }
                     self.progress_bar.set_written(written);
                     if let Some(msg) = message {
                         self.progress_bar.set_message(msg);
@@ -327,33 +411,57 @@ impl DevicePage {
                     self.progress_bar.finish();
                     self.status_message = Some(message);
                 }
+                DeviceEvent::ItemProgress { name, event } => {
+                    if let Some(page) = &mut self.progress_page {
+                        page.update_item(name, &event);
+                    }
+                }
+                DeviceEvent::OperationDone(error) => match (error, &mut self.progress_page) {
+                    (None, _) => self.progress_page = None,
+                    // Stay on the progress page, so the user sees where it failed
+                    (Some(msg), Some(page)) => page.fail(msg),
+                    (Some(msg), None) => error_dialog!(ctx, msg),
+                },
+                DeviceEvent::CancelOperation => {
+                    if let Some(page) = &mut self.progress_page {
+                        page.set_cancelling();
+                    }
+                    for handle in self.active_operations.values() {
+                        handle.abort();
+                    }
+                }
 
                 DeviceEvent::StatusChanged(status) => {
                     self.device_state.set_status(status);
                 }
-                DeviceEvent::Connected(mut device) => {
-                    self.devinfo = Some(device.dev_info.get_data().await);
-
-                    let partitions = device.get_partitions().await;
-                    let partition_list_items: Vec<ListItemEntry> = partitions
-                        .iter()
-                        .map(|p| {
-                            ListItemEntryBuilder::new(format!(
-                                "{} ({})",
-                                p.name,
-                                human_bytes(p.size as f64)
-                            ))
-                            .value(p.name.clone())
-                            .build()
-                            .unwrap()
+                DeviceEvent::ChoosePort { ports, reply } => {
+                    self.port_list.items = ports
+                        .into_iter()
+                        .map(|port| {
+                            ListItemEntryBuilder::new(port.clone()).value(port).build().unwrap()
                         })
                         .collect();
+                    self.port_list.state.select(Some(0));
+                    self.port_reply = Some(reply);
+                    self.focused_panel = FocusedPanel::PortMenu;
+                }
+                DeviceEvent::Connected(mut device) => {
+                    self.patched_da = device.last_exploit_report().is_some_and(|r| r.patched());
+                    self.exts_active = device.extensions_active();
+
+                    let partitions = match device.get_partitions().await {
+                        Ok(partitions) => partitions,
+                        Err(e) => {
+                            error_dialog!(ctx, format!("Failed to read partitions: {}", e));
+                            Vec::new()
+                        }
+                    };
+                    self.set_partitions(&mut device, partitions, ctx.theme.muted).await;
 
-                    self.partition_list.items = partition_list_items;
-
-                    self.partitions = partitions;
+                    self.devinfo = Some(device.dev_info.get_data().await);
                     self.storage = device.dev_info.storage().await.clone();
                     self.device = Some(Arc::new(Mutex::new(device)));
+                    self.last_refresh = Instant::now();
                     self.device_state.set_status(DeviceStatus::Connected);
                 }
 
@@ -391,6 +499,125 @@ impl DevicePage {
         }
     }
 
+    /// Lists `partitions` in the partition panel, with the dynamic partitions under super,
+    /// drawn in `muted`.
+    async fn set_partitions(
+        &mut self,
+        device: &mut Device,
+        partitions: Vec<Partition>,
+        muted: Color,
+    ) {
+        let mut partition_list_items: Vec<ListItemEntry> = Vec::new();
+        for p in &partitions {
+            partition_list_items.push(
+                ListItemEntryBuilder::new(format!("{} ({})", p.name, human_bytes(p.size as f64)))
+                    .value(p.name.clone())
+                    .build()
+                    .unwrap(),
+            );
+
+            // Dynamic partitions are listed under super, for reference only
+            if p.name == SUPER_PARTITION {
+                match device.read_lp_metadata("").await {
+                    Ok(metadata) => {
+                        partition_list_items.extend(metadata.partitions.iter().map(|dp| {
+                            ListItemEntryBuilder::new(format!(
+                                "  └ {} ({})",
+                                dp.name,
+                                human_bytes(dp.size() as f64)
+                            ))
+                            .style(Style::default().fg(muted))
+                            .build()
+                            .unwrap()
+                        }));
+                    }
+                    Err(e) => {
+                        log::warn!("Could not read the dynamic partitions: {}", e)
+                    }
+                }
+            }
+        }
+
+        self.partition_list.items = partition_list_items;
+        self.partitions = partitions;
+    }
+
+    /// Picks up what changed on the device since the last snapshot, every few seconds.
+    /// The partitions are read again if they couldn't be while entering DA mode.
+    /// Skipped while an operation holds the device.
+    async fn refresh_device_info(&mut self, ctx: &mut AppCtx) {
+        if self.last_refresh.elapsed() < DEVICE_INFO_REFRESH {
+            return;
+        }
+        self.last_refresh = Instant::now();
+
+        let Some(device) = self.device.clone() else { return };
+        let Ok(mut device) = device.try_lock() else { return };
+
+        if self.partitions.is_empty() {
+            match device.refresh_partitions().await {
+                Ok(partitions) if !partitions.is_empty() => {
+                    self.set_partitions(&mut device, partitions, ctx.theme.muted).await;
+                }
+                Ok(_) => {}
+                Err(e) => log::debug!("Could not read the partitions yet: {}", e),
+            }
+        }
+
+        self.devinfo = Some(device.dev_info.get_data().await);
+        self.storage = device.dev_info.storage().await;
+        self.exts_active = device.extensions_active();
+    }
+
+    /// Starts the DA keepalive while the page is waiting on the user (dialogs, file explorer
+    /// or partition selection), and stops it as soon as the user is done.
+    /// If the device stops answering in the meantime, the page waits for it to come back.
+    fn update_keepalive(&mut self, ctx: &mut AppCtx) {
+        if self.keepalive.as_ref().is_some_and(|k| k.has_failed()) {
+            self.keepalive = None;
+            self.device = None;
+            self.status_message = Some("Device stopped answering, waiting for it...".to_string());
+            self.device_state.set_status(DeviceStatus::Disconnected);
+            self.connect_device(ctx);
+            return;
+        }
+
+        let awaiting_user = ctx.dialog.is_some()
+            || self.explorer.is_some()
+            || matches!(self.focused_panel, FocusedPanel::PartitionMenu);
+
+        match (&self.device, awaiting_user) {
+            (Some(device), true) if self.keepalive.is_none() => {
+                self.keepalive =
+                    Some(KeepAlive::spawn(device.clone(), KeepAlive::DEFAULT_INTERVAL));
+            }
+            (_, false) => self.keepalive = None,
+            _ => {}
+        }
+    }
+
+    /// Goes back from the progress page once a cancelled operation actually stopped.
+    fn update_progress_page(&mut self) {
+        let cancelled = self
+            .progress_page
+            .as_ref()
+            .is_some_and(|page| *page.state() == ProgressState::Cancelling);
+
+        if cancelled && self.active_operations.values().all(|h| h.is_finished()) {
+            self.progress_page = None;
+            self.progress_bar.finish();
+            self.status_message =
+                Some("Operation cancelled, reconnect the device if it stops responding.".into());
+            self.restore_input();
+        }
+    }
+
+    /// Gives control back to the menu after an operation that didn't do it itself.
+    fn restore_input(&mut self) {
+        self.focused_panel = FocusedPanel::Menu;
+        self.input_enabled = true;
+    }
+
     pub fn cancel_all_operations(&mut self) {
         for (_, handle) in self.active_operations.drain() {
             handle.abort();
@@ -406,46 +633,87 @@ impl DevicePage {
 
         let da_data = ctx.loader().map(|da| da.file().da_raw_data.clone());
         let pl_data = ctx.preloader().map(|pl| pl.data());
+        let da_patching = ctx.config().da_patching;
 
         spawn(async move {
             let port = loop {
-                match find_mtk_port().await {
-                    Some(p) => break p,
-                    None => sleep(Duration::from_millis(700)).await,
+                let mut ports: Vec<String> =
+                    find_mtk_ports().await.iter().map(|p| p.get_port_name()).collect();
+
+                let name = match ports.len() {
+                    0 => None,
+                    1 => ports.pop(),
+                    _ => {
+                        let (reply, choice) = oneshot::channel();
+                        tx.send(DeviceEvent::ChoosePort { ports, reply });
+                        match choice.await {
+                            Ok(name) => Some(name),
+                            // The page went away without a choice
+                            Err(_) => return,
+                        }
+                    }
+                };
+
+                // The chosen device might have been unplugged in the meantime
+                if let Some(name) = name
+                    && let Some(port) = find_mtk_port_by_name(&name).await
+                {
+                    break port;
                 }
+                sleep(Duration::from_millis(700)).await;
             };
-            let _ = tx.send(DeviceEvent::StatusChanged(DeviceStatus::Connecting)).await;
+            tx.send(DeviceEvent::StatusChanged(DeviceStatus::Connecting));
 
-            let mut devbuilder = DeviceBuilder::default().with_mtk_port(port);
+            let mut devbuilder =
+                DeviceBuilder::default().with_mtk_port(port).with_da_patching(da_patching);
 
+            let da_given = da_data.is_some();
             if let Some(da) = da_data {
                 devbuilder = devbuilder.with_da_data(da);
             }
             if let Some(pl) = pl_data {
-                devbuilder = devbuilder.with_preloader(pl);
+                devbuilder = devbuilder.with_preloader_data(pl);
             }
 
             match devbuilder.build() {
                 Ok(mut dev) => {
                     if let Err(e) = dev.init().await {
-                        let _ = tx.send(DeviceEvent::Error(format!("Init failed: {}", e))).await;
-                        let _ =
-                            tx.send(DeviceEvent::StatusChanged(DeviceStatus::Disconnected)).await;
+                        let msg = with_hint(format!("Init failed: {}", e), Some(&e));
+                        error!("{}", msg);
+                        tx.send(DeviceEvent::Error(msg));
+                        tx.send(DeviceEvent::StatusChanged(DeviceStatus::Disconnected));
                         return;
                     }
 
+                    // Without a loaded DA, fall back to the repository's one for the chipset
+                    if !da_given
+                        && let Ok(Some(path)) = repository_da(dev.dev_info.hw_code().await).await
+                        && let Ok(data) = read(&path).await
+                    {
+                        info!("Using DA '{}' from the repository", path.display());
+                        if let Err(e) = dev.set_da_data(data).await {
+                            let msg = with_hint(format!("DA check failed: {}", e), Some(&e));
+                            error!("{}", msg);
+                            tx.send(DeviceEvent::Error(msg));
+                            tx.send(DeviceEvent::StatusChanged(DeviceStatus::Disconnected));
+                            return;
+                        }
+                    }
+
                     if let Err(e) = dev.enter_da_mode().await {
-                        let _ = tx.send(DeviceEvent::Error(format!("DA Mode failed: {}", e))).await;
-                        let _ =
-                            tx.send(DeviceEvent::StatusChanged(DeviceStatus::Disconnected)).await;
+                        let msg = with_hint(format!("DA Mode failed: {}", e), Some(&e));
+                        error!("{}", msg);
+                        tx.send(DeviceEvent::Error(msg));
+                        tx.send(DeviceEvent::StatusChanged(DeviceStatus::Disconnected));
                         return;
                     }
 
-                    let _ = tx.send(DeviceEvent::Connected(dev)).await;
+                    tx.send(DeviceEvent::Connected(dev));
                 }
                 Err(e) => {
-                    let _ = tx.send(DeviceEvent::Error(format!("Build failed: {}", e))).await;
-                    let _ = tx.send(DeviceEvent::StatusChanged(DeviceStatus::Disconnected)).await;
+                    error!("Build failed: {}", e);
+                    tx.send(DeviceEvent::Error(format!("Build failed: {}", e)));
+                    tx.send(DeviceEvent::StatusChanged(DeviceStatus::Disconnected));
                 }
             }
         });
@@ -459,10 +727,7 @@ impl DevicePage {
 
             KeyCode::Right => {
                 if self.device_state.is_connected() {
-                    let _ = self
-                        .event_tx
-                        .send(DeviceEvent::FocusPanel(FocusedPanel::PartitionMenu))
-                        .await;
+                    self.event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::PartitionMenu));
                 }
             }
 
@@ -482,16 +747,38 @@ impl DevicePage {
         }
     }
 
+    /// Handles the port selection input
+    fn handle_port_input(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Up => self.port_list.previous(),
+            KeyCode::Down => self.port_list.next(),
+
+            KeyCode::Enter => {
+                let Some(port) = self.port_list.selected_item().and_then(|i| i.value.clone())
+                else {
+                    return;
+                };
+
+                if let Some(reply) = self.port_reply.take() {
+                    reply.send(port).ok();
+                }
+                self.focused_panel = FocusedPanel::Menu;
+            }
+
+            _ => {}
+        }
+    }
+
     /// Handles the partition menu input
     async fn handle_partition_input(&mut self, _ctx: &mut AppCtx, key: KeyEvent) {
         match key.code {
-            KeyCode::Up => self.partition_list.previous(),
-            KeyCode::Down => self.partition_list.next(),
+            KeyCode::Up => self.partition_list.scroll_up(),
+            KeyCode::Down => self.partition_list.scroll_down(),
 
             KeyCode::Esc => {
                 self.partition_list.toggled = false;
                 self.partition_list.clear_selections();
-                let _ = self.event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::Menu)).await;
+                self.event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::Menu));
             }
 
             KeyCode::Enter => {
@@ -516,6 +803,11 @@ impl DevicePage {
                 }
             }
             KeyCode::Char('x') => {
+                // Dynamic partitions have no value, they can't be selected
+                if self.partition_list.selected_item().is_none_or(|item| item.value.is_none()) {
+                    return;
+                }
+
                 self.partition_list.toggled = true;
                 self.partition_list.toggle_selected();
 
@@ -649,6 +941,11 @@ impl DevicePage {
         frame.render_widget(block.clone(), area);
         let inner = block.inner(area);
 
+        if self.port_reply.is_some() {
+            self.render_port_choice(frame, inner, ctx);
+            return;
+        }
+
         if !self.device_state.is_connected() {
             self.render_disconnected(frame, inner, ctx);
             return;
@@ -656,7 +953,7 @@ impl DevicePage {
 
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Length(8), Constraint::Length(1), Constraint::Min(0)])
+            .constraints([Constraint::Length(10), Constraint::Length(1), Constraint::Min(0)])
             .split(inner);
 
         self.render_device_table(frame, chunks[0], ctx);
@@ -681,6 +978,25 @@ impl DevicePage {
         frame.render_widget(message, area);
     }
 
+    /// List of the connected devices to choose from
+    fn render_port_choice(&mut self, frame: &mut Frame<'_>, area: Rect, ctx: &mut AppCtx) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(area);
+
+        let message = Paragraph::new(vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                " Multiple devices found, choose one:",
+                Style::default().fg(ctx.theme.warning).add_modifier(Modifier::BOLD),
+            )),
+        ]);
+
+        frame.render_widget(message, chunks[0]);
+        self.port_list.render(chunks[1], frame.buffer_mut(), &ctx.theme);
+    }
+
     /// Device configuration table
     fn render_device_table(&self, frame: &mut Frame<'_>, area: Rect, ctx: &mut AppCtx) {
         let Some(devinfo) = &self.devinfo else { return };
@@ -690,12 +1006,27 @@ impl DevicePage {
         let sbc = if devinfo.target_config & 0x1 != 0 { "Yes" } else { "No" };
         let sla = if devinfo.target_config & 0x2 != 0 { "Yes" } else { "No" };
         let daa = if devinfo.target_config & 0x4 != 0 { "Yes" } else { "No" };
+        let patched_da = if self.patched_da { "Yes" } else { "No" };
+        let exts_active = if self.exts_active { "Yes" } else { "No" };
+        let partitions = self.partitions.len().to_string();
+
+        let storage = self.storage.as_ref().map(|s| s.info());
+        let summary = storage.as_ref().map_or("Unknown".to_string(), |info| {
+            format!("{} {}", info.kind, human_bytes(info.total_size as f64))
+        });
+        let cid =
+            storage.as_ref().map_or("Unknown".to_string(), |info| hex::encode_upper(&info.cid));
 
         let rows = vec![
             Row::new(vec!["HW Code", hw_code.as_str()]),
             Row::new(vec!["Secure Boot (SBC)", sbc]),
             Row::new(vec!["Serial Link Auth (SLA)", sla]),
             Row::new(vec!["Download Agent Auth (DAA)", daa]),
+            Row::new(vec!["Patched DA", patched_da]),
+            Row::new(vec!["DA Extensions", exts_active]),
+            Row::new(vec!["Storage", summary.as_str()]),
+            Row::new(vec!["Partitions", partitions.as_str()]),
+            Row::new(vec!["Storage CID", cid.as_str()]),
         ];
 
         let table = Table::new(rows, [Constraint::Percentage(45), Constraint::Percentage(55)])
@@ -729,7 +1060,7 @@ impl DevicePage {
 
     /// Footer help text
     fn render_footer(&self, frame: &mut Frame<'_>, area: Rect, ctx: &mut AppCtx) {
-        let footer = Paragraph::new("[↑↓] Navigate   [Enter] Select   [Esc] Back")
+        let footer = Paragraph::new("[↑↓] Navigate   [Enter] Select   [Esc] Back   [l] Logs")
             .alignment(Alignment::Center)
             .style(Style::default().fg(ctx.theme.foreground));
 
@@ -741,18 +1072,45 @@ impl DevicePage {
 impl Page for DevicePage {
     fn render(&mut self, frame: &mut Frame<'_>, ctx: &mut AppCtx) {
         let area = frame.area();
+        let (page_area, log_area) = ctx.log_pane.split(area);
 
         self.render_background(frame, area, ctx);
-        self.render_layout(frame, area, ctx);
+        self.render_layout(frame, page_area, ctx);
 
         if let Some(explorer) = &mut self.explorer {
-            explorer.render_modal(area, frame.buffer_mut(), &ctx.theme);
+            explorer.render_modal(page_area, frame.buffer_mut(), &ctx.theme);
+        }
+
+        if let Some(page) = &self.progress_page {
+            page.render_ref(page_area, frame.buffer_mut(), &ctx.theme);
+        }
+
+        if let Some(log_area) = log_area {
+            ctx.log_pane.render(log_area, frame.buffer_mut(), &mut ctx.logs, &ctx.theme);
         }
     }
 
     async fn handle_input(&mut self, ctx: &mut AppCtx, key: KeyEvent) {
-        #[cfg(target_os = "windows")]
-        if key.kind != KeyEventKind::Press {
+        // The progress page is modal, and stays usable while input is disabled
+        if let Some(page) = &self.progress_page {
+            if ctx.log_pane.handle_key(key) {
+                return;
+            }
+
+            match page.handle_key(key) {
+                ProgressAction::RequestCancel => {
+                    let tx = self.event_tx.clone();
+                    confirm_dialog!(ctx, "Cancel the running operation?", move || {
+                        tx.send(DeviceEvent::CancelOperation)
+                    });
+                }
+                ProgressAction::Close => {
+                    self.progress_page = None;
+                    self.progress_bar.finish();
+                    self.restore_input();
+                }
+                ProgressAction::None => {}
+            }
             return;
         }
 
@@ -763,13 +1121,18 @@ impl Page for DevicePage {
         // The explorer takes priority if active
         if let Some(explorer) = &mut self.explorer {
             let result = explorer.handle_key(key);
-            let _ = self.event_tx.send(DeviceEvent::ExplorerResult(result)).await;
+            self.event_tx.send(DeviceEvent::ExplorerResult(result));
+            return;
+        }
+
+        if ctx.log_pane.handle_key(key) {
             return;
         }
 
         match self.focused_panel {
             FocusedPanel::Menu => self.handle_menu_input(ctx, key).await,
             FocusedPanel::PartitionMenu => self.handle_partition_input(ctx, key).await,
+            FocusedPanel::PortMenu => self.handle_port_input(key),
         }
     }
 
@@ -781,11 +1144,18 @@ impl Page for DevicePage {
 
     async fn on_exit(&mut self, _ctx: &mut AppCtx) {
         self.cancel_all_operations();
+        self.keepalive = None;
+        if self.port_reply.take().is_some() {
+            self.focused_panel = FocusedPanel::Menu;
+        }
         // TOOD: Add device shutdown if connected
     }
 
     async fn update(&mut self, ctx: &mut AppCtx) {
         self.process_events(ctx).await;
+        self.refresh_device_info(ctx).await;
+        self.update_progress_page();
+        self.update_keepalive(ctx);
     }
 }
 
@@ -795,17 +1165,16 @@ impl DeviceActionCallback for UnlockBootloaderCallback {
     async fn execute(
         &self,
         device: Arc<Mutex<Device>>,
-        event_tx: mpsc::Sender<DeviceEvent>,
+        event_tx: EventSender<DeviceEvent>,
         _cb_tx: mpsc::Sender<CallbackEvent>,
         _cb_rx: mpsc::Receiver<CallbackEvent>,
     ) -> Result<()> {
-        let _ = event_tx.send(DeviceEvent::HeaderStatus("Unlocking bootloader...".into())).await;
+        event_tx.send(DeviceEvent::HeaderStatus("Unlocking bootloader...".into()));
 
         let mut dev = device.lock().await;
         match dev.set_seccfg_lock_state(LockFlag::Unlock).await {
             Some(_) => {
-                let _ =
-                    event_tx.send(DeviceEvent::HeaderStatus("Bootloader unlocked.".into())).await;
+                event_tx.send(DeviceEvent::HeaderStatus("Bootloader unlocked.".into()));
                 Ok(())
             }
             None => Err(anyhow!("Failed to unlock bootloader")),
@@ -819,16 +1188,16 @@ impl DeviceActionCallback for LockBootloaderCallback {
     async fn execute(
         &self,
         device: Arc<Mutex<Device>>,
-        event_tx: mpsc::Sender<DeviceEvent>,
+        event_tx: EventSender<DeviceEvent>,
         _cb_tx: mpsc::Sender<CallbackEvent>,
         _cb_rx: mpsc::Receiver<CallbackEvent>,
     ) -> Result<()> {
-        event_tx.send(DeviceEvent::HeaderStatus("Locking bootloader...".into())).await.ok();
+        event_tx.send(DeviceEvent::HeaderStatus("Locking bootloader...".into()));
 
         let mut dev = device.lock().await;
         match dev.set_seccfg_lock_state(LockFlag::Unlock).await {
             Some(_) => {
-                event_tx.send(DeviceEvent::HeaderStatus("Bootloader locked.".into())).await.ok();
+                event_tx.send(DeviceEvent::HeaderStatus("Bootloader locked.".into()));
                 Ok(())
             }
             None => Err(anyhow!("Failed to lock bootloader")),
@@ -842,11 +1211,11 @@ impl DeviceActionCallback for ReadPartitionCallback {
     async fn execute(
         &self,
         device: Arc<Mutex<Device>>,
-        event_tx: mpsc::Sender<DeviceEvent>,
+        event_tx: EventSender<DeviceEvent>,
         _cb_tx: mpsc::Sender<CallbackEvent>,
         mut cb_rx: mpsc::Receiver<CallbackEvent>,
     ) -> Result<()> {
-        let _ = event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::PartitionMenu)).await;
+        event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::PartitionMenu));
 
         let explorer = FileExplorer::new("Output dump directory")?.directories_only();
 
@@ -860,7 +1229,7 @@ impl DeviceActionCallback for ReadPartitionCallback {
             }
         };
 
-        let _ = event_tx.send(DeviceEvent::ShowExplorer(explorer)).await;
+        event_tx.send(DeviceEvent::ShowExplorer(explorer));
 
         let output_dir = loop {
             match cb_rx.recv().await {
@@ -880,33 +1249,25 @@ impl DeviceActionCallback for ReadPartitionCallback {
 
         let mut dev = device.lock().await;
         // Block page input to avoid interruptions
-        event_tx.send(DeviceEvent::Input(false)).await.ok();
+        event_tx.send(DeviceEvent::Input(false));
 
-        event_tx
-            .send(DeviceEvent::ProgressStart {
-                total_bytes: total_size,
-                message: "Reading partitions...".into(),
-            })
-            .await
-            .ok();
+        event_tx.send(DeviceEvent::ProgressStart {
+            total_bytes: total_size,
+            message: "Reading partitions...".into(),
+        });
         for partition in partitions {
             let output_path = output_dir.join(format!("{}.bin", partition.name));
             let file = File::create(&output_path).await?;
             let mut writer = BufWriter::new(file);
 
-            let mut progress_cb = |written: usize, _total_partition_bytes: usize| {
-                let total_bytes = bytes_read + written as u64;
+            let mut progress_cb = |event: ProgressEvent| {
+                let total_bytes = bytes_read + event.bytes_done;
 
-                let event_tx = event_tx.clone();
-                let part_name = partition.name.clone();
-                spawn(async move {
-                    let _ = event_tx
-                        .send(DeviceEvent::ProgressUpdate {
-                            written: total_bytes,
-                            message: Some(format!("Reading partition '{}'...", part_name,)),
-                        })
-                        .await;
+                event_tx.send(DeviceEvent::ProgressUpdate {
+                    written: total_bytes,
+                    message: Some(format!("Reading partition '{}'...", partition.name)),
                 });
+                event_tx.send(DeviceEvent::ItemProgress { name: partition.name.clone(), event });
             };
 
             dev.upload(&partition.name, &mut writer, &mut progress_cb).await?;
@@ -914,13 +1275,11 @@ impl DeviceActionCallback for ReadPartitionCallback {
             bytes_read += partition.size as u64;
         }
 
-        let _ = event_tx
-            .send(DeviceEvent::ProgressFinish { message: "Partition read complete.".into() })
-            .await;
+        event_tx.send(DeviceEvent::ProgressFinish { message: "Partition read complete.".into() });
 
         // Focus back the menu panel to avoid confusion
-        let _ = event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::Menu)).await;
-        event_tx.send(DeviceEvent::Input(true)).await.ok();
+        event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::Menu));
+        event_tx.send(DeviceEvent::Input(true));
 
         Ok(())
     }
@@ -932,11 +1291,11 @@ impl DeviceActionCallback for WritePartitionCallback {
     async fn execute(
         &self,
         device: Arc<Mutex<Device>>,
-        event_tx: mpsc::Sender<DeviceEvent>,
+        event_tx: EventSender<DeviceEvent>,
         _cb_tx: mpsc::Sender<CallbackEvent>,
         mut cb_rx: mpsc::Receiver<CallbackEvent>,
     ) -> Result<()> {
-        event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::PartitionMenu)).await.ok();
+        event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::PartitionMenu));
 
         let mut partition_map: HashMap<String, PathBuf> = HashMap::new();
 
@@ -952,7 +1311,7 @@ impl DeviceActionCallback for WritePartitionCallback {
                             partition.name
                         ))?;
 
-                        event_tx.send(DeviceEvent::ShowExplorer(explorer)).await.ok();
+                        event_tx.send(DeviceEvent::ShowExplorer(explorer));
 
                         let path = loop {
                             match cb_rx.recv().await {
@@ -993,47 +1352,43 @@ impl DeviceActionCallback for WritePartitionCallback {
 
         let mut dev = device.lock().await;
         // Block page input to avoid interruptions
-        event_tx.send(DeviceEvent::Input(false)).await.ok();
+        event_tx.send(DeviceEvent::Input(false));
 
-        event_tx
-            .send(DeviceEvent::ProgressStart {
-                total_bytes: total_size,
-                message: "Writing partitions...".into(),
-            })
-            .await
-            .ok();
+        event_tx.send(DeviceEvent::ProgressStart {
+            total_bytes: total_size,
+            message: "Writing partitions...".into(),
+        });
 
         for (partition, path) in part_to_write {
-            let file = File::open(path).await?;
-            let mut reader = BufReader::new(file);
-
-            let mut progress_cb = |written: usize, _total_partition_bytes: usize| {
-                let total_bytes = bytes_written + written as u64;
-
-                let event_tx = event_tx.clone();
-                let part_name = partition.name.clone();
-                spawn(async move {
-                    let _ = event_tx
-                        .send(DeviceEvent::ProgressUpdate {
-                            written: total_bytes,
-                            message: Some(format!("Flashing partition '{}'...", part_name,)),
-                        })
-                        .await;
+            let mut progress_cb = |event: ProgressEvent| {
+                let total_bytes = bytes_written + event.bytes_done;
+
+                event_tx.send(DeviceEvent::ProgressUpdate {
+                    written: total_bytes,
+                    message: Some(format!("Flashing partition '{}'...", partition.name)),
                 });
+                event_tx.send(DeviceEvent::ItemProgress { name: partition.name.clone(), event });
             };
 
-            dev.download(&partition.name, partition.size, &mut reader, &mut progress_cb).await?;
+            if partition.name == "preloader" {
+                // Keep BOOT2 in sync, a stale backup preloader can come back to bite later
+                let data = read(path).await?;
+                dev.write_preloader(&data, true, &mut progress_cb).await?;
+            } else {
+                let file = File::open(path).await?;
+                let mut reader = BufReader::new(file);
+                dev.download(&partition.name, partition.size, &mut reader, &mut progress_cb)
+                    .await?;
+            }
 
             bytes_written += partition.size as u64;
         }
 
-        let _ = event_tx
-            .send(DeviceEvent::ProgressFinish { message: "Partition write complete.".into() })
-            .await;
+        event_tx.send(DeviceEvent::ProgressFinish { message: "Partition write complete.".into() });
 
         // Focus back the menu panel to avoid confusion
-        let _ = event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::Menu)).await;
-        event_tx.send(DeviceEvent::Input(true)).await.ok();
+        event_tx.send(DeviceEvent::FocusPanel(FocusedPanel::Menu));
+        event_tx.send(DeviceEvent::Input(true));
 
         Ok(())
     }
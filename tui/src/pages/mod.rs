@@ -4,9 +4,11 @@
 */
 pub mod device;
 pub mod options;
+pub mod progress;
 pub mod welcome;
 pub use device::DevicePage;
 pub use options::OptionsPage;
+pub use progress::{ProgressAction, ProgressPage, ProgressState};
 use ratatui::Frame;
 use ratatui::crossterm::event::KeyEvent;
 pub use welcome::WelcomePage;
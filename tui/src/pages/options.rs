@@ -85,7 +85,26 @@ impl OptionsPage {
             }],
         };
 
-        Self { sections: vec![ui_section], selected_idx: 0, stars: Stars::new(2.0) }
+        let patching_options = vec![
+            DropdownOption { label: "Enabled".to_string(), value: "enabled".to_string() },
+            DropdownOption { label: "Disabled".to_string(), value: "disabled".to_string() },
+        ];
+
+        let device_section = OptionSection {
+            title: "DEVICE",
+            items: vec![OptionItem {
+                label: "DA Patching",
+                description: "Run exploits and patch the DA. Disable if a patched DA won't boot",
+                widget: OptionWidget::Dropdown(Dropdown::new("Patching", patching_options, 0)),
+                on_change: Box::new(|ctx, val| ctx.set_da_patching(val == "enabled")),
+                sync: Box::new(|w, ctx| {
+                    let OptionWidget::Dropdown(d) = w;
+                    d.set_by_value(if ctx.da_patching() { "enabled" } else { "disabled" });
+                }),
+            }],
+        };
+
+        Self { sections: vec![ui_section, device_section], selected_idx: 0, stars: Stars::new(2.0) }
     }
 
     fn total_items(&self) -> usize {
@@ -0,0 +1,290 @@
+/*
+    SPDX-License-Identifier: AGPL-3.0-or-later
+    SPDX-FileCopyrightText: 2025 Shomy
+*/
+use penumbra::ProgressEvent;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::prelude::{Alignment, Buffer};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, BorderType, Borders, Clear, Paragraph, Widget, Wrap};
+
+use crate::components::{ProgressBar, ThemedWidgetRef};
+use crate::logger::recent_messages;
+use crate::themes::Theme;
+
+/// State of the operation shown by a [`ProgressPage`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressState {
+    Running,
+    /// Cancellation was requested, waiting for the operation to stop
+    Cancelling,
+    Failed(String),
+}
+
+/// What the invoking page should do after a key press on the progress page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressAction {
+    None,
+    /// Ask the user to confirm the cancellation
+    RequestCancel,
+    /// Go back to the invoking page
+    Close,
+}
+
+/// Full-screen progress of a long operation, pushed on top of the page that started it.
+/// Shows the overall progress, the current item (usually a partition) and the latest
+/// messages from the core, which are otherwise hidden in TUI mode.
+pub struct ProgressPage {
+    title: String,
+    state: ProgressState,
+    overall: ProgressBar,
+    current: ProgressBar,
+    current_item: Option<String>,
+}
+
+impl ProgressPage {
+    pub fn new(title: impl Into<String>, total_bytes: u64) -> Self {
+        let title = title.into();
+        let mut overall = ProgressBar::new();
+        overall.start(total_bytes, title.clone());
+
+        Self {
+            title,
+            state: ProgressState::Running,
+            overall,
+            current: ProgressBar::new(),
+            current_item: None,
+        }
+    }
+
+    pub fn state(&self) -> &ProgressState {
+        &self.state
+    }
+
+    pub fn update_overall(&mut self, written: u64, message: Option<String>) {
+        self.overall.set_written(written);
+        if let Some(msg) = message {
+            self.overall.set_message(msg);
+        }
+    }
+
+    /// Updates the current item bar, restarting it when the item changes.
+    pub fn update_item(&mut self, name: String, event: &ProgressEvent) {
+        if self.current_item.as_ref() != Some(&name) {
+            self.current.start(event.bytes_total, name.clone());
+            self.current_item = Some(name);
+        }
+        self.current.set_event(event);
+    }
+
+    pub fn set_cancelling(&mut self) {
+        if self.state == ProgressState::Running {
+            self.state = ProgressState::Cancelling;
+        }
+    }
+
+    pub fn fail(&mut self, message: impl Into<String>) {
+        self.state = ProgressState::Failed(message.into());
+    }
+
+    pub fn handle_key(&self, key: KeyEvent) -> ProgressAction {
+        match (&self.state, key.code) {
+            (ProgressState::Running, KeyCode::Char('c') | KeyCode::Esc) => {
+                ProgressAction::RequestCancel
+            }
+            (ProgressState::Failed(_), KeyCode::Enter | KeyCode::Esc) => ProgressAction::Close,
+            _ => ProgressAction::None,
+        }
+    }
+
+    fn render_status(&self, area: Rect, buf: &mut Buffer, theme: &Theme) {
+        let (status, hint) = match &self.state {
+            ProgressState::Running => {
+                (Span::styled("Running", Style::default().fg(theme.info)), "[c] Cancel")
+            }
+            ProgressState::Cancelling => (
+                Span::styled(
+                    "Cancelling… waiting for the device",
+                    Style::default().fg(theme.warning).add_modifier(Modifier::BOLD),
+                ),
+                "",
+            ),
+            ProgressState::Failed(msg) => (
+                Span::styled(
+                    format!("Failed: {msg}"),
+                    Style::default().fg(theme.error).add_modifier(Modifier::BOLD),
+                ),
+                "[Enter] Back",
+            ),
+        };
+
+        Paragraph::new(vec![
+            Line::from(status),
+            Line::from(Span::styled(hint, Style::default().fg(theme.muted))),
+        ])
+        .wrap(Wrap { trim: true })
+        .render(area, buf);
+    }
+
+    fn render_messages(&self, area: Rect, buf: &mut Buffer, theme: &Theme) {
+        let block = Block::default()
+            .title(" MESSAGES ")
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(theme.text));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let lines: Vec<Line> = recent_messages(inner.height as usize)
+            .into_iter()
+            .map(|msg| Line::from(Span::styled(msg, Style::default().fg(theme.muted))))
+            .collect();
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
+
+impl ThemedWidgetRef for ProgressPage {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer, theme: &Theme) {
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .title(format!(" {} ", self.title))
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .style(Style::default().bg(theme.background).fg(theme.accent));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1), // Overall label
+                Constraint::Length(3), // Overall bar
+                Constraint::Length(1), // Current label
+                Constraint::Length(3), // Current bar
+                Constraint::Length(2), // Status
+                Constraint::Min(3),    // Messages
+            ])
+            .margin(1)
+            .split(inner);
+
+        let label = Style::default().fg(theme.text).add_modifier(Modifier::BOLD);
+        Paragraph::new(Span::styled("Overall", label)).render(chunks[0], buf);
+        self.overall.render_ref(chunks[1], buf, theme);
+
+        Paragraph::new(Span::styled("Current", label)).render(chunks[2], buf);
+        if self.current.is_active() {
+            self.current.render_ref(chunks[3], buf, theme);
+        } else {
+            Paragraph::new(Span::styled("Waiting…", Style::default().fg(theme.muted)))
+                .render(chunks[3], buf);
+        }
+
+        self.render_status(chunks[4], buf, theme);
+        self.render_messages(chunks[5], buf, theme);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use penumbra::core::summary::OperationKind;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    /// A page halfway through the second partition of a 64 MiB flash.
+    fn page() -> ProgressPage {
+        let mut page = ProgressPage::new("Flashing", 64 * MIB);
+        let event = ProgressEvent {
+            bytes_done: 8 * MIB,
+            bytes_total: 16 * MIB,
+            elapsed: Duration::from_secs(2),
+            phase: OperationKind::Write,
+        };
+        page.update_overall(0, Some("Writing system".to_string()));
+        page.update_item("system".to_string(), &event);
+        page
+    }
+
+    /// Renders the page, returning the text of each row.
+    fn render(page: &ProgressPage) -> (Vec<String>, Buffer) {
+        let mut terminal = Terminal::new(TestBackend::new(48, 20)).unwrap();
+        terminal
+            .draw(|frame| page.render_ref(frame.area(), frame.buffer_mut(), &Theme::default()))
+            .unwrap();
+
+        let buf = terminal.backend().buffer().clone();
+        let rows = (0..buf.area.height)
+            .map(|y| (0..buf.area.width).map(|x| buf[(x, y)].symbol()).collect::<String>())
+            .collect();
+        (rows, buf)
+    }
+
+    /// The page with `status` in place of the two status rows.
+    fn screen(status: [&str; 2]) -> Vec<String> {
+        let status = status.map(|line| format!("│ {:<44} │", line));
+        [
+            "╭────────────────── Flashing ──────────────────╮",
+            "│                                              │",
+            "│ Overall                                      │",
+            "│ Writing system                               │",
+            "│ ░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░   0%  │",
+            "│ 0 B / 64 MiB  •  0 B/s  •  ETA --:--         │",
+            "│ Current                                      │",
+            "│ system                                       │",
+            "│ ███████████████████░░░░░░░░░░░░░░░░░░░  50%  │",
+            "│ 8 MiB / 16 MiB  •  4 MiB/s  •  ETA 00:02     │",
+            &status[0],
+            &status[1],
+            "│ ╭ MESSAGES ────────────────────────────────╮ │",
+            "│ │                                          │ │",
+            "│ │                                          │ │",
+            "│ │                                          │ │",
+            "│ │                                          │ │",
+            "│ ╰──────────────────────────────────────────╯ │",
+            "│                                              │",
+            "╰──────────────────────────────────────────────╯",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    #[test]
+    fn running() {
+        let (rows, buf) = render(&page());
+        assert_eq!(rows, screen(["Running", "[c] Cancel"]));
+        assert_eq!(buf[(2, 10)].fg, Theme::default().info);
+    }
+
+    #[test]
+    fn cancelling() {
+        let mut page = page();
+        page.set_cancelling();
+
+        let (rows, buf) = render(&page);
+        assert_eq!(rows, screen(["Cancelling… waiting for the device", ""]));
+        assert_eq!(buf[(2, 10)].fg, Theme::default().warning);
+    }
+
+    #[test]
+    fn failed() {
+        let mut page = page();
+        page.set_cancelling();
+        page.fail("device went away");
+
+        let (rows, buf) = render(&page);
+        assert_eq!(rows, screen(["Failed: device went away", "[Enter] Back"]));
+        assert_eq!(buf[(2, 10)].fg, Theme::default().error);
+    }
+}
@@ -9,8 +9,6 @@ use anyhow::Result;
 use penumbra::da::DAFile;
 use ratatui::Frame;
 use ratatui::buffer::Buffer;
-#[cfg(target_os = "windows")]
-use ratatui::crossterm::event::KeyEventKind;
 use ratatui::crossterm::event::{KeyCode, KeyEvent};
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use ratatui::style::Style;
@@ -222,11 +220,6 @@ impl Page for WelcomePage {
     }
 
     async fn handle_input(&mut self, ctx: &mut AppCtx, key: KeyEvent) {
-        #[cfg(target_os = "windows")]
-        if key.kind != KeyEventKind::Press {
-            return;
-        }
-
         match &mut self.state {
             WelcomeState::Browsing { explorer, callback } => match explorer.handle_key(key) {
                 ExplorerResult::Selected(path) => match fs::read(&path) {